@@ -12,6 +12,7 @@ use zerocopy::{Immutable, IntoBytes};
 use crate::{AcpiError, Result, Sdt, SdtHeader, checksum};
 
 const MADT_CPU_ENABLE_FLAG: u32 = 0;
+const MADT_CPU_ONLINE_CAPABLE_FLAG: u32 = 1;
 
 // clippy doesn't understand that we actually "use" the fields of this struct when we serialize
 // them as bytes in guest memory, so here we just ignore dead code to avoid having to name
@@ -37,6 +38,15 @@ impl LocalAPIC {
             flags: U32::new(1u32 << MADT_CPU_ENABLE_FLAG),
         }
     }
+
+    /// Creates the entry of a CPU which is disabled at boot, but which the OS can enable later,
+    /// e.g. when it is hotplugged.
+    pub fn new_online_capable(cpu_id: u8) -> Self {
+        Self {
+            flags: U32::new(1u32 << MADT_CPU_ONLINE_CAPABLE_FLAG),
+            ..Self::new(cpu_id)
+        }
+    }
 }
 
 // clippy doesn't understand that we actually "use" the fields of this struct when we serialize
@@ -26,6 +26,8 @@ enum HelperError {
     /// {0}
     Utils(#[from] utils::UtilsError),
     /// {0}
+    TemplateCommon(#[from] template::common::CommonError),
+    /// {0}
     TemplateDump(#[from] template::dump::DumpError),
     /// {0}
     TemplateStrip(#[from] template::strip::StripError),
@@ -73,6 +75,21 @@ enum TemplateOperation {
         #[arg(short, long, default_value = "_stripped")]
         suffix: String,
     },
+    /// Generate a CPU template which makes the guest CPU configuration identical on all the hosts
+    /// the given CPU configuration files were dumped on.
+    Common {
+        /// List of paths of input CPU configuration files.
+        #[arg(short, long, value_name = "PATH", num_args = 2..)]
+        paths: Vec<PathBuf>,
+        /// Path of output file.
+        #[arg(
+            short,
+            long,
+            value_name = "PATH",
+            default_value = "common_cpu_template.json"
+        )]
+        output: PathBuf,
+    },
     /// Verify that the given CPU template file is applied as intended.
     Verify {
         /// Path of firecracker config file.
@@ -152,6 +169,24 @@ fn run(cli: Cli) -> Result<(), HelperError> {
                     write(path, template_json)?;
                 }
             }
+            TemplateOperation::Common { paths, output } => {
+                let templates = paths
+                    .iter()
+                    .map(utils::load_cpu_template)
+                    .collect::<Result<Vec<_>, utils::UtilsError>>()?;
+
+                let (common_template, notes) = template::common::common(templates)?;
+
+                let template_json = serde_json::to_string_pretty(&common_template)?;
+                write(output, template_json)?;
+
+                for (host, path) in paths.iter().enumerate() {
+                    println!("host {host}: {}", path.display());
+                }
+                for note in notes {
+                    println!("{note}");
+                }
+            }
             TemplateOperation::Verify { config, template } => {
                 let config = config.map(read_to_string).transpose()?;
                 let template = template
@@ -327,6 +362,29 @@ mod tests {
         run(cli).unwrap();
     }
 
+    #[test]
+    fn test_template_common_command() {
+        let files = [generate_sample_template(), generate_sample_template()];
+        let output_file = TempFile::new().unwrap();
+
+        let mut args = vec![
+            "cpu-template-helper",
+            "template",
+            "common",
+            "--output",
+            output_file.as_path().to_str().unwrap(),
+            "-p",
+        ];
+        let paths = files
+            .iter()
+            .map(|file| file.as_path().to_str().unwrap())
+            .collect::<Vec<_>>();
+        args.extend(paths);
+        let cli = Cli::parse_from(args);
+
+        run(cli).unwrap();
+    }
+
     #[test]
     fn test_template_verify_command() {
         let template_file = generate_sample_template();
@@ -0,0 +1,140 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::cpu_config::aarch64::custom_cpu_template::RegisterModifier;
+use vmm::cpu_config::templates::CustomCpuTemplate;
+
+use crate::template::common::{CommonError, Merge, common_modifiers};
+use crate::utils::aarch64::{RegModifierMap, RegModifierMapKey};
+
+/// Mask of the coprocessor (register type) bits of a KVM register ID.
+const KVM_REG_ARM64_SYSREG_MASK: u64 = 0x0fff_0000;
+/// Register type of system registers (`KVM_REG_ARM64_SYSREG`).
+const KVM_REG_ARM64_SYSREG: u64 = 0x0013 << 16;
+
+/// Returns whether a register is one of the ID registers (op0=3, op1=0, CRn=0, CRm=1..7), which
+/// are made of 4-bit feature fields.
+fn is_id_register(id: u64) -> bool {
+    if id & KVM_REG_ARM64_SYSREG_MASK != KVM_REG_ARM64_SYSREG {
+        return false;
+    }
+    let op0 = (id >> 14) & 0b11;
+    let op1 = (id >> 11) & 0b111;
+    let crn = (id >> 7) & 0b1111;
+    let crm = (id >> 3) & 0b1111;
+    op0 == 3 && op1 == 0 && crn == 0 && (1..=7).contains(&crm)
+}
+
+/// Returns how the values of a register which differ between hosts are combined.
+fn reg_merge(key: &RegModifierMapKey) -> Merge {
+    if is_id_register(key.0) {
+        Merge::FieldMinimum
+    } else {
+        Merge::BitwiseAnd
+    }
+}
+
+pub fn common(
+    templates: Vec<CustomCpuTemplate>,
+) -> Result<(CustomCpuTemplate, Vec<String>), CommonError> {
+    // Convert `Vec<CustomCpuTemplate>` to `Vec<HashMap<_>>`.
+    let reg_modifiers_maps = templates
+        .into_iter()
+        .map(|template| RegModifierMap::from(template.reg_modifiers).0)
+        .collect::<Vec<_>>();
+
+    // Compute the modifiers shared by all the hosts.
+    let (reg_modifiers_map, notes) = common_modifiers(&reg_modifiers_maps, reg_merge)?;
+
+    // Convert back to `CustomCpuTemplate`.
+    let template = CustomCpuTemplate {
+        reg_modifiers: Vec::<RegisterModifier>::from(RegModifierMap(reg_modifiers_map)),
+        ..Default::default()
+    };
+
+    Ok((template, notes))
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::cpu_config::templates::RegisterValueFilter;
+
+    use super::*;
+    use crate::utils::aarch64::reg_modifier;
+
+    // ID_AA64PFR0_EL1 (op0=3, op1=0, CRn=0, CRm=4, op2=0).
+    const ID_AA64PFR0_EL1: u64 = 0x6030_0000_0013_c020;
+
+    // Summary of reg modifiers:
+    // * ID_AA64PFR0_EL1 differs and the smallest value of each field is used.
+    // * An addr 0x1 modifier differs and the bits not set on all the hosts are cleared.
+    // * An addr 0x2 modifier has the same value on all the hosts and needs no modifier.
+    // * An addr 0x3 modifier only exists on the first host and is left unmodified.
+    #[rustfmt::skip]
+    fn build_input_templates() -> Vec<CustomCpuTemplate> {
+        vec![
+            CustomCpuTemplate {
+                reg_modifiers: vec![
+                    reg_modifier!(ID_AA64PFR0_EL1, 0x1211),
+                    reg_modifier!(0x1, 0b0110),
+                    reg_modifier!(0x2, 0b0001),
+                    reg_modifier!(0x3, 0b0001),
+                ],
+                ..Default::default()
+            },
+            CustomCpuTemplate {
+                reg_modifiers: vec![
+                    reg_modifier!(ID_AA64PFR0_EL1, 0x2111),
+                    reg_modifier!(0x1, 0b0011),
+                    reg_modifier!(0x2, 0b0001),
+                ],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[rustfmt::skip]
+    fn build_expected_template() -> CustomCpuTemplate {
+        CustomCpuTemplate {
+            reg_modifiers: vec![
+                reg_modifier!(0x1, 0b0000, 0b0101),
+                reg_modifier!(ID_AA64PFR0_EL1, 0x1100, 0x3300),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_id_register() {
+        assert!(is_id_register(ID_AA64PFR0_EL1));
+        // MIDR_EL1 (op0=3, op1=0, CRn=0, CRm=0, op2=0).
+        assert!(!is_id_register(0x6030_0000_0013_c000));
+        // A core register.
+        assert!(!is_id_register(0x6030_0000_0010_0000));
+    }
+
+    #[test]
+    fn test_common_with_single_input() {
+        let input = vec![CustomCpuTemplate::default()];
+
+        match common(input) {
+            Err(CommonError::NumberOfInputs) => (),
+            _ => panic!("Should fail with `Error::NumberOfInputs`."),
+        }
+    }
+
+    #[test]
+    fn test_common() {
+        let (template, notes) = common(build_input_templates()).unwrap();
+
+        assert_eq!(template, build_expected_template());
+        assert_eq!(notes.len(), 3);
+        assert!(notes[0].starts_with("ID=0x1: the hosts differ"));
+        assert_eq!(
+            notes[1],
+            "ID=0x3: left unmodified, since it is missing on host(s) 1."
+        );
+        assert!(notes[2].starts_with("ID=0x603000000013c020: the hosts differ"));
+        assert!(notes[2].contains("the smallest value of each 4-bit field"));
+    }
+}
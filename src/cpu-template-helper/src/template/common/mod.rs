@@ -0,0 +1,242 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use vmm::cpu_config::templates::{Numeric, RegisterValueFilter};
+
+use crate::utils::ModifierMapKey;
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(target_arch = "aarch64")]
+pub use aarch64::common;
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::common;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum CommonError {
+    /// The number of inputs should be two or more.
+    NumberOfInputs,
+    /// The hosts have different CPU vendors: {0}
+    #[cfg(target_arch = "x86_64")]
+    Vendors(String),
+}
+
+/// How the values of a register which differ between hosts are combined.
+// Not all the merges are used on every architecture.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Merge {
+    /// Keep the bits set on all the hosts. Used for feature flags.
+    BitwiseAnd,
+    /// Keep the smallest value. Used for maximum supported values, such as the highest CPUID leaf.
+    Minimum,
+    /// Keep the smallest value of each 8-bit field. Used for registers made of several maximum
+    /// supported values, such as the address widths.
+    ByteMinimum,
+    /// Keep the smallest value of each 4-bit field, or `0b1111` if a host has it. Used for ID
+    /// registers, where a higher value of a field means more features, except for signed fields
+    /// where `0b1111` means that the feature is not implemented.
+    FieldMinimum,
+    /// Keep the value of the first host. Used for identification values, such as the CPU model,
+    /// which cannot be combined.
+    First,
+}
+
+impl Merge {
+    fn merge<V: Numeric + Ord>(self, values: &[V]) -> V {
+        match self {
+            Merge::BitwiseAnd => values.iter().fold(!V::zero(), |acc, value| acc & *value),
+            Merge::Minimum => values.iter().copied().min().unwrap_or_else(V::zero),
+            Merge::ByteMinimum => {
+                let byte = (0..8).fold(V::zero(), |byte, bit| byte | V::one() << bit);
+                let mut merged = V::zero();
+                for shift in (0..V::BITS).step_by(8) {
+                    let mask = byte << shift;
+                    merged |= values
+                        .iter()
+                        .map(|value| *value & mask)
+                        .min()
+                        .unwrap_or_else(V::zero);
+                }
+                merged
+            }
+            Merge::FieldMinimum => {
+                let nibble = V::one() | V::one() << 1 | V::one() << 2 | V::one() << 3;
+                let mut merged = V::zero();
+                for shift in (0..V::BITS).step_by(4) {
+                    let mask = nibble << shift;
+                    let fields = values.iter().map(|value| *value & mask);
+                    merged |= match fields.clone().any(|field| field == mask) {
+                        true => mask,
+                        false => fields.min().unwrap_or_else(V::zero),
+                    };
+                }
+                merged
+            }
+            Merge::First => values.first().copied().unwrap_or_else(V::zero),
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Merge::BitwiseAnd => "bits not set on all the hosts are cleared",
+            Merge::Minimum => "the smallest value among the hosts is used",
+            Merge::ByteMinimum => "the smallest value of each 8-bit field among the hosts is used",
+            Merge::FieldMinimum => "the smallest value of each 4-bit field among the hosts is used",
+            Merge::First => "the value of host 0 is used",
+        }
+    }
+}
+
+/// Generate the modifiers which make a register identical on all the hosts.
+///
+/// `maps` are the guest CPU configurations dumped on each host. Registers with the same value on
+/// all the hosts need no modifier. For the others, only the bits which differ are modified, and
+/// set to the value combined with the `Merge` returned by `merge_of` for the register. Registers
+/// missing on some hosts cannot be modified, since Firecracker refuses templates with modifiers
+/// for registers the host does not have. The returned notes explain each of these decisions.
+fn common_modifiers<K, V>(
+    maps: &[HashMap<K, RegisterValueFilter<V>>],
+    merge_of: impl Fn(&K) -> Merge,
+) -> Result<(HashMap<K, RegisterValueFilter<V>>, Vec<String>), CommonError>
+where
+    K: ModifierMapKey + Debug,
+    V: Numeric + Ord + Debug,
+{
+    if maps.len() < 2 {
+        return Err(CommonError::NumberOfInputs);
+    }
+
+    let mut common = HashMap::new();
+    let mut notes = Vec::new();
+
+    let mut keys = maps.iter().flat_map(|map| map.keys()).collect::<Vec<_>>();
+    keys.sort_by_key(|key| key.to_string());
+    keys.dedup();
+
+    for key in keys {
+        let values = maps
+            .iter()
+            .map(|map| map.get(key).map(|vf| vf.value & vf.filter))
+            .collect::<Vec<_>>();
+        let Some(values) = values.iter().copied().collect::<Option<Vec<_>>>() else {
+            let missing = values
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| value.is_none())
+                .map(|(host, _)| host.to_string())
+                .collect::<Vec<_>>();
+            notes.push(format!(
+                "{key}: left unmodified, since it is missing on host(s) {}.",
+                missing.join(", ")
+            ));
+            continue;
+        };
+
+        let diff = values
+            .iter()
+            .fold(V::zero(), |diff, value| diff | (*value ^ values[0]));
+        if diff == V::zero() {
+            continue;
+        }
+
+        let merge = merge_of(key);
+        let merged = merge.merge(&values) & diff;
+        common.insert(
+            key.clone(),
+            RegisterValueFilter {
+                filter: diff,
+                value: merged,
+            },
+        );
+
+        let width = V::BITS as usize;
+        let mut note = format!("{key}: the hosts differ, {}.", merge.reason());
+        for (host, value) in values.iter().enumerate() {
+            note.push_str(&format!("\n* host {host:<5}: 0b{value:0width$b}"));
+        }
+        note.push_str(&format!("\n* filter    : 0b{diff:0width$b}"));
+        note.push_str(&format!("\n* value     : 0b{merged:0width$b}"));
+        notes.push(note);
+    }
+
+    Ok((common, notes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{MockModifierMapKey, mock_modifier};
+
+    #[test]
+    fn test_common_modifiers_with_single_input() {
+        let input = vec![HashMap::from([mock_modifier!(0x0, 0b0000_0000)])];
+
+        match common_modifiers(&input, |_| Merge::BitwiseAnd) {
+            Err(CommonError::NumberOfInputs) => (),
+            _ => panic!("Should fail with `Error::NumberOfInputs`."),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            Merge::BitwiseAnd.merge(&[0b1100_1010u8, 0b1010_1010]),
+            0b1000_1010
+        );
+        assert_eq!(Merge::Minimum.merge(&[0x0du32, 0x0b, 0x16]), 0x0b);
+        assert_eq!(
+            Merge::FieldMinimum.merge(&[0x1210_0021u64, 0x0120_00f1]),
+            0x0111_00f1
+        );
+        assert_eq!(
+            Merge::ByteMinimum.merge(&[0x0030_2e2eu32, 0x0030_302d]),
+            0x0030_2e2d
+        );
+        assert_eq!(
+            Merge::First.merge(&[0x000a_06a4u32, 0x0009_06ea]),
+            0x000a_06a4
+        );
+    }
+
+    #[test]
+    fn test_common_modifiers() {
+        let input = vec![
+            HashMap::from([
+                mock_modifier!(0x0, 0b1111_1111), // Identical on all the hosts.
+                mock_modifier!(0x1, 0b1111_0000), // Different on the hosts.
+                mock_modifier!(0x2, 0b0000_0001), // Missing on the second host.
+                mock_modifier!(0x3, 0b0000_1000), // Smallest value is used.
+            ]),
+            HashMap::from([
+                mock_modifier!(0x0, 0b1111_1111),
+                mock_modifier!(0x1, 0b1100_1100),
+                mock_modifier!(0x3, 0b0000_0110),
+            ]),
+        ];
+        let expected = HashMap::from([
+            mock_modifier!(0x1, 0b0000_0000, 0b0011_1100),
+            mock_modifier!(0x3, 0b0000_0110, 0b0000_1110),
+        ]);
+
+        let (common, notes) = common_modifiers(&input, |key: &MockModifierMapKey| match key.0 {
+            0x3 => Merge::Minimum,
+            _ => Merge::BitwiseAnd,
+        })
+        .unwrap();
+        assert_eq!(common, expected);
+        assert_eq!(notes.len(), 3);
+        assert!(notes[0].starts_with("ID=0x1: the hosts differ"));
+        assert_eq!(
+            notes[1],
+            "ID=0x2: left unmodified, since it is missing on host(s) 1."
+        );
+        assert!(notes[2].starts_with("ID=0x3: the hosts differ"));
+    }
+}
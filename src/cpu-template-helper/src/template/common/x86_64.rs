@@ -0,0 +1,309 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+
+use vmm::cpu_config::templates::{CustomCpuTemplate, RegisterValueFilter};
+use vmm::cpu_config::x86_64::custom_cpu_template::{
+    CpuidLeafModifier, CpuidRegister, RegisterModifier,
+};
+
+use crate::template::common::{CommonError, Merge, common_modifiers};
+use crate::utils::x86_64::{CpuidModifierMap, CpuidModifierMapKey, MsrModifierMap};
+
+/// Returns how the values of a CPUID register which differ between hosts are combined.
+fn cpuid_merge(key: &CpuidModifierMapKey) -> Merge {
+    match (key.leaf, key.subleaf, &key.register) {
+        // Highest standard leaf, highest subleaf of leaf 0x7 and highest extended leaf.
+        (0x0, _, CpuidRegister::Eax)
+        | (0x7, 0x0, CpuidRegister::Eax)
+        | (0x8000_0000, _, CpuidRegister::Eax) => Merge::Minimum,
+        // Sizes of the XSAVE area, for the enabled and for all the supported components, and
+        // sizes of the components.
+        (0xd, 0x0, CpuidRegister::Ebx | CpuidRegister::Ecx)
+        | (0xd, 0x1, CpuidRegister::Ebx)
+        | (0xd, 0x2.., CpuidRegister::Eax) => Merge::Minimum,
+        // Physical, linear and guest physical address widths.
+        (0x8000_0008, _, CpuidRegister::Eax) => Merge::ByteMinimum,
+        // Family, model and stepping, offsets of the XSAVE components and processor brand string.
+        (0x1, _, CpuidRegister::Eax)
+        | (0xd, 0x2.., CpuidRegister::Ebx)
+        | (0x8000_0001, _, CpuidRegister::Eax)
+        | (0x8000_0002..=0x8000_0004, _, _) => Merge::First,
+        _ => Merge::BitwiseAnd,
+    }
+}
+
+/// Returns the vendor string of a host, held by EBX, EDX and ECX of CPUID leaf 0x0.
+fn vendor(cpuid_modifiers_map: &HashMap<CpuidModifierMapKey, RegisterValueFilter<u32>>) -> String {
+    [CpuidRegister::Ebx, CpuidRegister::Edx, CpuidRegister::Ecx]
+        .iter()
+        .flat_map(|register| {
+            cpuid_modifiers_map
+                .iter()
+                .find(|(key, _)| key.leaf == 0x0 && key.register == *register)
+                .map_or(0, |(_, vf)| vf.value & vf.filter)
+                .to_le_bytes()
+        })
+        .map(char::from)
+        .collect()
+}
+
+pub fn common(
+    templates: Vec<CustomCpuTemplate>,
+) -> Result<(CustomCpuTemplate, Vec<String>), CommonError> {
+    // Convert `Vec<CustomCpuTemplate>` to two `Vec<HashMap<_>>` of modifiers.
+    let (cpuid_modifiers_maps, msr_modifiers_maps): (Vec<_>, Vec<_>) = templates
+        .into_iter()
+        .map(|template| {
+            (
+                CpuidModifierMap::from(template.cpuid_modifiers).0,
+                MsrModifierMap::from(template.msr_modifiers).0,
+            )
+        })
+        .unzip();
+
+    // The CPUID of hosts from different vendors cannot be made identical.
+    let vendors = cpuid_modifiers_maps.iter().map(vendor).collect::<Vec<_>>();
+    if vendors.windows(2).any(|pair| pair[0] != pair[1]) {
+        return Err(CommonError::Vendors(vendors.join(", ")));
+    }
+
+    // Compute the modifiers shared by all the hosts.
+    let (cpuid_modifiers_map, cpuid_notes) = common_modifiers(&cpuid_modifiers_maps, cpuid_merge)?;
+    let (msr_modifiers_map, msr_notes) =
+        common_modifiers(&msr_modifiers_maps, |_| Merge::BitwiseAnd)?;
+
+    let notes = cpuid_notes
+        .into_iter()
+        .map(|note| format!("CPUID {note}"))
+        .chain(msr_notes.into_iter().map(|note| format!("MSR {note}")))
+        .collect();
+
+    // Convert back to `CustomCpuTemplate`.
+    let template = CustomCpuTemplate {
+        cpuid_modifiers: Vec::<CpuidLeafModifier>::from(CpuidModifierMap(cpuid_modifiers_map)),
+        msr_modifiers: Vec::<RegisterModifier>::from(MsrModifierMap(msr_modifiers_map)),
+        ..Default::default()
+    };
+
+    Ok((template, notes))
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::cpu_config::x86_64::cpuid::KvmCpuidFlags;
+    use vmm::cpu_config::x86_64::custom_cpu_template::CpuidRegister::*;
+    use vmm::cpu_config::x86_64::custom_cpu_template::CpuidRegisterModifier;
+
+    use super::*;
+    use crate::utils::x86_64::{cpuid_leaf_modifier, cpuid_reg_modifier, msr_modifier};
+
+    // Summary of modifiers:
+    // * The highest standard leaf (leaf 0x0 / EAX) differs and the smallest one is used.
+    // * A feature bit in leaf 0x1 / ECX is only set on the first host and is cleared.
+    // * Leaf 0x2 / subleaf 0x1 only exists on the second host and is left unmodified.
+    // * An MSR 0x10 bit is only set on the second host and is cleared.
+    // * MSR 0x11 has the same value on all the hosts and needs no modifier.
+    #[rustfmt::skip]
+    fn build_input_templates() -> Vec<CustomCpuTemplate> {
+        vec![
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![
+                    cpuid_leaf_modifier!(0x0, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x16),
+                    ]),
+                    cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Ecx, 0b1011),
+                    ]),
+                ],
+                msr_modifiers: vec![
+                    msr_modifier!(0x10, 0b0001),
+                    msr_modifier!(0x11, 0b0110),
+                ],
+                ..Default::default()
+            },
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![
+                    cpuid_leaf_modifier!(0x0, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x0d),
+                    ]),
+                    cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Ecx, 0b0011),
+                    ]),
+                    cpuid_leaf_modifier!(0x2, 0x1, KvmCpuidFlags::SIGNIFICANT_INDEX, vec![
+                        cpuid_reg_modifier!(Eax, 0x1),
+                    ]),
+                ],
+                msr_modifiers: vec![
+                    msr_modifier!(0x10, 0b0101),
+                    msr_modifier!(0x11, 0b0110),
+                ],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[rustfmt::skip]
+    fn build_expected_template() -> CustomCpuTemplate {
+        CustomCpuTemplate {
+            cpuid_modifiers: vec![
+                cpuid_leaf_modifier!(0x0, 0x0, KvmCpuidFlags::EMPTY, vec![
+                    cpuid_reg_modifier!(Eax, 0x0d & 0x1b, 0x1b),
+                ]),
+                cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                    cpuid_reg_modifier!(Ecx, 0b0000, 0b1000),
+                ]),
+            ],
+            msr_modifiers: vec![
+                msr_modifier!(0x10, 0b0000, 0b0100),
+            ],
+            ..Default::default()
+        }
+    }
+
+    // Leaf 0x0 holding the vendor string in EBX, EDX and ECX.
+    fn vendor_modifier(vendor: &str) -> CpuidLeafModifier {
+        let register = |index: usize| {
+            u32::from_le_bytes(
+                vendor.as_bytes()[index * 4..index * 4 + 4]
+                    .try_into()
+                    .unwrap(),
+            )
+        };
+        cpuid_leaf_modifier!(
+            0x0,
+            0x0,
+            KvmCpuidFlags::EMPTY,
+            vec![
+                cpuid_reg_modifier!(Ebx, register(0)),
+                cpuid_reg_modifier!(Edx, register(1)),
+                cpuid_reg_modifier!(Ecx, register(2)),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_common_with_single_input() {
+        let input = vec![CustomCpuTemplate::default()];
+
+        match common(input) {
+            Err(CommonError::NumberOfInputs) => (),
+            _ => panic!("Should fail with `Error::NumberOfInputs`."),
+        }
+    }
+
+    #[test]
+    fn test_common() {
+        let (template, notes) = common(build_input_templates()).unwrap();
+
+        assert_eq!(template, build_expected_template());
+        assert_eq!(notes.len(), 4);
+        assert!(notes[0].starts_with("CPUID leaf=0x0, subleaf=0x0, flags=0b0, register=eax"));
+        assert!(notes[0].contains("the smallest value among the hosts is used"));
+        assert!(notes[1].contains("bits not set on all the hosts are cleared"));
+        assert!(notes[2].ends_with("left unmodified, since it is missing on host(s) 0."));
+        assert!(notes[3].starts_with("MSR index=0x10: the hosts differ"));
+    }
+
+    #[test]
+    fn test_common_vendors() {
+        let templates = vec![
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![vendor_modifier("GenuineIntel")],
+                ..Default::default()
+            },
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![vendor_modifier("AuthenticAMD")],
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            common(templates).unwrap_err().to_string(),
+            "The hosts have different CPU vendors: GenuineIntel, AuthenticAMD"
+        );
+
+        let templates = vec![
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![vendor_modifier("GenuineIntel")],
+                ..Default::default()
+            },
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![vendor_modifier("GenuineIntel")],
+                ..Default::default()
+            },
+        ];
+        let (template, notes) = common(templates).unwrap();
+        assert_eq!(template, CustomCpuTemplate::default());
+        assert!(notes.is_empty());
+    }
+
+    // Summary of modifiers:
+    // * The family, model and stepping (leaf 0x1 / EAX) are those of the first host.
+    // * The size of the XSAVE area for all the supported components (leaf 0xd / subleaf 0x0 / ECX)
+    //   is the smallest one.
+    // * The physical address width (leaf 0x80000008 / EAX, bits 7:0) is the smallest one, 46 bits,
+    //   and so is the linear address width (bits 15:8), 48 bits.
+    #[test]
+    #[rustfmt::skip]
+    fn test_common_numeric_fields() {
+        let templates = vec![
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![
+                    vendor_modifier("GenuineIntel"),
+                    cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x000a_06a7),
+                    ]),
+                    cpuid_leaf_modifier!(0xd, 0x0, KvmCpuidFlags::SIGNIFICANT_INDEX, vec![
+                        cpuid_reg_modifier!(Ebx, 0x0240),
+                        cpuid_reg_modifier!(Ecx, 0x0a88),
+                    ]),
+                    cpuid_leaf_modifier!(0x8000_0008, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x0000_302e),
+                    ]),
+                ],
+                ..Default::default()
+            },
+            CustomCpuTemplate {
+                cpuid_modifiers: vec![
+                    vendor_modifier("GenuineIntel"),
+                    cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x0009_06ea),
+                    ]),
+                    cpuid_leaf_modifier!(0xd, 0x0, KvmCpuidFlags::SIGNIFICANT_INDEX, vec![
+                        cpuid_reg_modifier!(Ebx, 0x0240),
+                        cpuid_reg_modifier!(Ecx, 0x0988),
+                    ]),
+                    cpuid_leaf_modifier!(0x8000_0008, 0x0, KvmCpuidFlags::EMPTY, vec![
+                        cpuid_reg_modifier!(Eax, 0x0000_3930),
+                    ]),
+                ],
+                ..Default::default()
+            },
+        ];
+        let expected = CustomCpuTemplate {
+            cpuid_modifiers: vec![
+                cpuid_leaf_modifier!(0x1, 0x0, KvmCpuidFlags::EMPTY, vec![
+                    cpuid_reg_modifier!(Eax, 0x000a_06a7 & 0x0003_004d, 0x0003_004d),
+                ]),
+                cpuid_leaf_modifier!(0xd, 0x0, KvmCpuidFlags::SIGNIFICANT_INDEX, vec![
+                    cpuid_reg_modifier!(Ecx, 0x0988 & 0x0300, 0x0300),
+                ]),
+                cpuid_leaf_modifier!(0x8000_0008, 0x0, KvmCpuidFlags::EMPTY, vec![
+                    cpuid_reg_modifier!(Eax, 0x0000_302e & 0x0000_091e, 0x0000_091e),
+                ]),
+            ],
+            ..Default::default()
+        };
+
+        let (template, notes) = common(templates).unwrap();
+        assert_eq!(template, expected);
+        assert_eq!(notes.len(), 3);
+        assert!(notes[0].starts_with("CPUID leaf=0x1,"));
+        assert!(notes[0].contains("the value of host 0 is used"));
+        assert!(notes[1].starts_with("CPUID leaf=0x80000008,"));
+        assert!(notes[1].contains("the smallest value of each 8-bit field"));
+        assert!(notes[2].starts_with("CPUID leaf=0xd,"));
+        assert!(notes[2].contains("the smallest value among the hosts is used"));
+    }
+}
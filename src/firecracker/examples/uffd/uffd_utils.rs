@@ -39,6 +39,18 @@ pub struct GuestRegionUffdMapping {
     pub offset: u64,
     /// The configured page size for this memory region.
     pub page_size: usize,
+    /// Ranges of this region that hold no data and can be populated with zeros.
+    #[serde(default)]
+    pub holes: Vec<GuestRegionUffdHole>,
+}
+
+/// A range of a guest memory region that does not need any backing data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuestRegionUffdHole {
+    /// Offset of the range from the start of the region.
+    pub offset: u64,
+    /// Range size.
+    pub size: usize,
 }
 
 impl GuestRegionUffdMapping {
@@ -46,6 +58,14 @@ impl GuestRegionUffdMapping {
         fault_page_addr >= self.base_host_virt_addr
             && fault_page_addr < self.base_host_virt_addr + self.size as u64
     }
+
+    fn is_hole(&self, fault_page_addr: u64, len: usize) -> bool {
+        let start = fault_page_addr - self.base_host_virt_addr;
+        let end = start + len as u64;
+        self.holes
+            .iter()
+            .any(|hole| hole.offset <= start && end <= hole.offset + hole.size as u64)
+    }
 }
 
 #[derive(Debug)]
@@ -60,7 +80,8 @@ impl UffdHandler {
     fn try_get_mappings_and_file(
         stream: &UnixStream,
     ) -> Result<(String, Option<File>), std::io::Error> {
-        let mut message_buf = vec![0u8; 1024];
+        // The mappings carry the list of holes, so leave plenty of room for them.
+        let mut message_buf = vec![0u8; 1 << 20];
         let (bytes_read, file) = stream.recv_with_fd(&mut message_buf[..])?;
         message_buf.resize(bytes_read, 0);
 
@@ -151,6 +172,11 @@ impl UffdHandler {
 
         for region in self.mem_regions.iter() {
             if region.contains(fault_page_addr) {
+                // UFFDIO_ZEROPAGE is not supported for hugetlbfs, which reads the holes
+                // punched in the memory file as zeros anyway.
+                if self.page_size == host_page_size() && region.is_hole(fault_page_addr, len) {
+                    return self.populate_zeros(fault_page_addr, len);
+                }
                 return self.populate_from_file(region, fault_page_addr, len);
             }
         }
@@ -190,6 +216,31 @@ impl UffdHandler {
 
         true
     }
+
+    fn populate_zeros(&self, dst: u64, len: usize) -> bool {
+        unsafe {
+            match self.uffd.zeropage(dst as *mut _, len, true) {
+                Ok(value) => assert!(value > 0),
+                // Same as for copies, a `remove` event may be pending.
+                Err(Error::ZeropageFailed(errno))
+                    if std::io::Error::from(errno).raw_os_error().unwrap() == libc::EAGAIN =>
+                {
+                    return false;
+                }
+                Err(Error::ZeropageFailed(errno))
+                    if std::io::Error::from(errno).raw_os_error().unwrap() == libc::EEXIST => {}
+                Err(e) => {
+                    panic!("Uffd zeropage failed: {e:?}");
+                }
+            }
+        };
+
+        true
+    }
+}
+
+fn host_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
 }
 
 #[derive(Debug)]
@@ -375,6 +426,7 @@ mod tests {
             size: 0x1000,
             offset: 0,
             page_size: 4096,
+            holes: vec![],
         }];
         let dummy_memory_region_json = serde_json::to_string(&dummy_memory_region).unwrap();
 
@@ -408,6 +460,7 @@ mod tests {
             size: 0,
             offset: 0,
             page_size: 4096,
+            holes: vec![],
         }];
         let error_memory_region_json = serde_json::to_string(&error_memory_region).unwrap();
         stream
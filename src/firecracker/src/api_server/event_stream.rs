@@ -0,0 +1,551 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Streams the lifecycle events of the microVM as server-sent events.
+//!
+//! The HTTP server of the API answers every request right away and cannot keep a connection open
+//! until new events are emitted, so the stream is served on its own Unix socket, by a separate
+//! thread. Clients send a `GET /events` request and then receive the events as they are emitted,
+//! until they close the connection. The sequence number of every event is sent as its SSE id, so
+//! clients resume the stream after reconnecting with the `Last-Event-ID` header.
+//!
+//! The socket also answers `GET /metrics` with the metrics in the OpenMetrics text format, under
+//! the OpenMetrics content type which the HTTP server of the API cannot send.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+use vmm::logger::{EVENT_LOG, METRICS, OPENMETRICS_CONTENT_TYPE, debug, error, warn};
+use vmm::seccomp::BpfProgramRef;
+use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
+use vmm_sys_util::eventfd::EventFd;
+
+/// Maximum size of the request of a client, headers included.
+const MAX_REQUEST_SIZE: usize = 4096;
+/// Maximum number of epoll events handled in one iteration.
+const MAX_EPOLL_EVENTS: usize = 32;
+/// Maximum size of the events a client has yet to receive before it is disconnected.
+const MAX_PENDING_OUTPUT: usize = 1 << 20;
+/// Response to a valid request, followed by the events.
+const STREAM_HEADERS: &str = concat!(
+    "HTTP/1.1 200 OK\r\n",
+    "Content-Type: text/event-stream\r\n",
+    "Cache-Control: no-cache\r\n",
+    "\r\n"
+);
+
+/// Errors associated with the event stream server.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum EventStreamError {
+    /// Failed to bind the events socket: {0}
+    Bind(io::Error),
+    /// Failed to set up the epoll instance of the events socket: {0}
+    Epoll(io::Error),
+    /// Failed to create the event notifier: {0}
+    Notifier(io::Error),
+}
+
+/// Requests served on the events socket.
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    /// Streams the events from the given cursor, or from the oldest available event.
+    Events(Option<u64>),
+    /// Returns the metrics in the OpenMetrics text format.
+    Metrics,
+}
+
+#[derive(Debug)]
+enum ClientState {
+    /// The request of the client is being received.
+    Request(Vec<u8>),
+    /// Events are sent to the client, starting from the given cursor, or from the oldest
+    /// available event if there is none.
+    Streaming(Option<u64>),
+    /// The response was written, the connection is closed once it is sent.
+    Closing,
+}
+
+#[derive(Debug)]
+struct Client {
+    stream: UnixStream,
+    state: ClientState,
+    /// Output that the client was too slow to receive.
+    pending_output: Vec<u8>,
+    /// Events for which the connection is registered in the epoll set.
+    interest: EventSet,
+}
+
+impl Client {
+    /// Handles incoming data, returning whether the connection stays open.
+    fn receive(&mut self) -> bool {
+        if matches!(self.state, ClientState::Closing) {
+            // The connection is only watched until the response is sent.
+            return true;
+        }
+        let mut buf = [0u8; 512];
+        let count = match self.stream.read(&mut buf) {
+            Ok(0) => return false,
+            Ok(count) => count,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+            Err(err) => {
+                debug!("Failed to read from an events socket client: {}", err);
+                return false;
+            }
+        };
+        let ClientState::Request(request) = &mut self.state else {
+            // Nothing is expected from the client once the stream started.
+            return true;
+        };
+        request.extend_from_slice(&buf[..count]);
+        let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+            if request.len() > MAX_REQUEST_SIZE {
+                self.reject("431 Request Header Fields Too Large");
+            }
+            return true;
+        };
+
+        let parsed = parse_request(&String::from_utf8_lossy(&request[..end]));
+        match parsed {
+            Ok(Route::Events(cursor)) => {
+                self.state = ClientState::Streaming(cursor);
+                self.pending_output
+                    .extend_from_slice(STREAM_HEADERS.as_bytes());
+                self.send_events();
+            }
+            Ok(Route::Metrics) => self.send_metrics(),
+            Err(status) => self.reject(status),
+        }
+        true
+    }
+
+    /// Writes an error response to the client.
+    fn reject(&mut self, status: &str) {
+        let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+        self.pending_output.extend_from_slice(response.as_bytes());
+        self.state = ClientState::Closing;
+    }
+
+    /// Writes the metrics to the client.
+    fn send_metrics(&mut self) {
+        let response = match METRICS.openmetrics() {
+            Ok(text) => format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {OPENMETRICS_CONTENT_TYPE}\r\nContent-Length: \
+                 {}\r\n\r\n{text}",
+                text.len()
+            ),
+            Err(err) => {
+                error!("Failed to encode the metrics: {}", err);
+                "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n".to_string()
+            }
+        };
+        self.pending_output.extend_from_slice(response.as_bytes());
+        self.state = ClientState::Closing;
+    }
+
+    /// Writes the events emitted since the previous call.
+    fn send_events(&mut self) {
+        let ClientState::Streaming(cursor) = self.state else {
+            return;
+        };
+        let batch = EVENT_LOG.read(cursor);
+        let mut frames = String::new();
+        if batch.missed > 0 {
+            frames.push_str(&format!(
+                "event: missed\ndata: {{\"missed\":{}}}\n\n",
+                batch.missed
+            ));
+        }
+        for event in &batch.events {
+            let data = serde_json::to_string(event).expect("Cannot serialize an event");
+            frames.push_str(&format!("id: {}\ndata: {data}\n\n", event.seq));
+        }
+        self.state = ClientState::Streaming(Some(batch.next_cursor));
+        self.pending_output.extend_from_slice(frames.as_bytes());
+    }
+
+    /// Sends the output that was written to the client, until its socket buffer is full.
+    fn flush_output(&mut self) -> io::Result<()> {
+        while !self.pending_output.is_empty() {
+            match self.stream.write(&self.pending_output) {
+                Ok(count) => {
+                    self.pending_output.drain(..count);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Events for which the connection has to be watched.
+    fn wanted_interest(&self) -> EventSet {
+        let mut interest = match self.state {
+            ClientState::Closing => EventSet::empty(),
+            _ => EventSet::IN,
+        };
+        if !self.pending_output.is_empty() {
+            interest |= EventSet::OUT;
+        }
+        interest
+    }
+}
+
+/// Parses the request of a client, or returns the status of the error response.
+fn parse_request(request: &str) -> Result<Route, &'static str> {
+    let mut lines = request.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    if request_line.next() != Some("GET") {
+        return Err("405 Method Not Allowed");
+    }
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match path {
+        "/events" => (),
+        "/metrics" if query.is_empty() => return Ok(Route::Metrics),
+        "/metrics" => return Err("400 Bad Request"),
+        _ => return Err("404 Not Found"),
+    }
+
+    let mut cursor = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        match param.split_once('=') {
+            Some(("cursor", value)) => {
+                cursor = Some(value.parse::<u64>().map_err(|_| "400 Bad Request")?);
+            }
+            _ => return Err("400 Bad Request"),
+        }
+    }
+    // The id of the last event a client received takes precedence over the cursor of the URL,
+    // which clients keep when reconnecting.
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("Last-Event-ID")
+        {
+            let last_seq = value.trim().parse::<u64>().map_err(|_| "400 Bad Request")?;
+            cursor = Some(last_seq.saturating_add(1));
+        }
+    }
+    Ok(Route::Events(cursor))
+}
+
+/// Server of the stream of lifecycle events.
+#[derive(Debug)]
+pub struct EventStreamServer {
+    listener: UnixListener,
+    epoll: Epoll,
+    /// Written to by the event log when a new event is emitted.
+    notifier: EventFd,
+    /// Signals the server to stop.
+    kill_switch: EventFd,
+    clients: HashMap<RawFd, Client>,
+}
+
+impl EventStreamServer {
+    /// Binds the events socket at `path` and subscribes to the event log.
+    pub fn new(path: &Path, kill_switch: EventFd) -> Result<Self, EventStreamError> {
+        let listener = UnixListener::bind(path).map_err(EventStreamError::Bind)?;
+        listener
+            .set_nonblocking(true)
+            .map_err(EventStreamError::Bind)?;
+        let notifier = EventFd::new(libc::EFD_NONBLOCK).map_err(EventStreamError::Notifier)?;
+        let epoll = Epoll::new().map_err(EventStreamError::Epoll)?;
+        for fd in [
+            listener.as_raw_fd(),
+            notifier.as_raw_fd(),
+            kill_switch.as_raw_fd(),
+        ] {
+            epoll
+                .ctl(
+                    ControlOperation::Add,
+                    fd,
+                    EpollEvent::new(EventSet::IN, u64::try_from(fd).unwrap()),
+                )
+                .map_err(EventStreamError::Epoll)?;
+        }
+        EVENT_LOG.set_notifier(notifier.try_clone().map_err(EventStreamError::Notifier)?);
+
+        Ok(Self {
+            listener,
+            epoll,
+            notifier,
+            kill_switch,
+            clients: HashMap::new(),
+        })
+    }
+
+    /// Serves the clients until the kill switch is triggered.
+    pub fn run(&mut self, seccomp_filter: BpfProgramRef) {
+        // The thread only uses the syscalls the API thread needs, so it gets the same filters.
+        if let Err(err) = vmm::seccomp::apply_filter(seccomp_filter) {
+            panic!(
+                "Failed to set the requested seccomp filters on the events thread: {}",
+                err
+            );
+        }
+
+        let mut events = vec![EpollEvent::new(EventSet::empty(), 0); MAX_EPOLL_EVENTS];
+        loop {
+            let count = match self.epoll.wait(-1, events.as_mut_slice()) {
+                Ok(count) => count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error!("Failed to wait on the events socket: {}", err);
+                    return;
+                }
+            };
+            for event in &events[..count] {
+                let fd = event.fd();
+                if fd == self.kill_switch.as_raw_fd() {
+                    debug!("shutdown request received, events thread ending.");
+                    return;
+                } else if fd == self.listener.as_raw_fd() {
+                    self.accept();
+                } else if fd == self.notifier.as_raw_fd() {
+                    // The notifier only wakes the thread up, its value does not matter.
+                    let _ = self.notifier.read();
+                    let fds: Vec<RawFd> = self.clients.keys().copied().collect();
+                    for fd in fds {
+                        if let Some(client) = self.clients.get_mut(&fd) {
+                            client.send_events();
+                        }
+                        self.send_output(fd);
+                    }
+                } else if self
+                    .clients
+                    .get_mut(&fd)
+                    .is_some_and(|client| client.receive())
+                {
+                    self.send_output(fd);
+                } else {
+                    self.remove_client(fd);
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self) {
+        let stream = match self.listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+            Err(err) => {
+                error!("Failed to accept an events socket client: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = stream.set_nonblocking(true) {
+            error!("Failed to set up an events socket client: {}", err);
+            return;
+        }
+        let fd = stream.as_raw_fd();
+        if let Err(err) = self.epoll.ctl(
+            ControlOperation::Add,
+            fd,
+            EpollEvent::new(EventSet::IN, u64::try_from(fd).unwrap()),
+        ) {
+            error!("Failed to register an events socket client: {}", err);
+            return;
+        }
+        self.clients.insert(
+            fd,
+            Client {
+                stream,
+                state: ClientState::Request(Vec::new()),
+                pending_output: Vec::new(),
+                interest: EventSet::IN,
+            },
+        );
+    }
+
+    /// Sends the pending output of a client, and watches its connection until the rest can be
+    /// sent.
+    ///
+    /// Streaming clients which have more than `MAX_PENDING_OUTPUT` bytes left to receive are
+    /// disconnected and have to resume the stream.
+    fn send_output(&mut self, fd: RawFd) {
+        let Some(client) = self.clients.get_mut(&fd) else {
+            return;
+        };
+        if let Err(err) = client.flush_output() {
+            debug!("Failed to write to an events socket client: {}", err);
+            self.remove_client(fd);
+            return;
+        }
+        let done = match client.state {
+            ClientState::Closing => client.pending_output.is_empty(),
+            ClientState::Streaming(_) if client.pending_output.len() > MAX_PENDING_OUTPUT => {
+                warn!("Disconnecting an events socket client which does not read the stream.");
+                true
+            }
+            _ => false,
+        };
+        if done {
+            self.remove_client(fd);
+            return;
+        }
+        let interest = client.wanted_interest();
+        if interest != client.interest {
+            if let Err(err) = self.epoll.ctl(
+                ControlOperation::Modify,
+                fd,
+                EpollEvent::new(interest, u64::try_from(fd).unwrap()),
+            ) {
+                error!("Failed to watch an events socket client: {}", err);
+                self.remove_client(fd);
+                return;
+            }
+            client.interest = interest;
+        }
+    }
+
+    fn remove_client(&mut self, fd: RawFd) {
+        // Closing the connection removes it from the epoll set.
+        self.clients.remove(&fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::thread;
+    use std::time::Duration;
+
+    use vmm::logger::EventKind;
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request("GET /events HTTP/1.1\r\nHost: localhost"),
+            Ok(Route::Events(None))
+        );
+        assert_eq!(
+            parse_request("GET /events?cursor=3 HTTP/1.1\r\nHost: localhost"),
+            Ok(Route::Events(Some(3)))
+        );
+        assert_eq!(
+            parse_request("GET /events?cursor=3 HTTP/1.1\r\nlast-event-id: 7"),
+            Ok(Route::Events(Some(8)))
+        );
+        assert_eq!(
+            parse_request("GET /metrics HTTP/1.1\r\nHost: localhost"),
+            Ok(Route::Metrics)
+        );
+        assert_eq!(
+            parse_request("PUT /events HTTP/1.1"),
+            Err("405 Method Not Allowed")
+        );
+        assert_eq!(parse_request("GET /foo HTTP/1.1"), Err("404 Not Found"));
+        assert_eq!(
+            parse_request("GET /metrics/foo HTTP/1.1"),
+            Err("404 Not Found")
+        );
+        assert_eq!(
+            parse_request("GET /metrics?cursor=1 HTTP/1.1"),
+            Err("400 Bad Request")
+        );
+        assert_eq!(
+            parse_request("GET /events?cursor=-1 HTTP/1.1"),
+            Err("400 Bad Request")
+        );
+        assert_eq!(
+            parse_request("GET /events?foo=1 HTTP/1.1"),
+            Err("400 Bad Request")
+        );
+        assert_eq!(
+            parse_request("GET /events HTTP/1.1\r\nLast-Event-ID: foo"),
+            Err("400 Bad Request")
+        );
+    }
+
+    #[test]
+    fn test_pending_output() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut client = Client {
+            stream,
+            state: ClientState::Closing,
+            pending_output: Vec::new(),
+            interest: EventSet::IN,
+        };
+        // The response does not fit in the socket buffer, so the rest is kept until the client
+        // reads it.
+        let response: Vec<u8> = (0..MAX_PENDING_OUTPUT)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        client.pending_output.extend_from_slice(&response);
+        client.flush_output().unwrap();
+        assert!(!client.pending_output.is_empty());
+        assert_eq!(client.wanted_interest(), EventSet::OUT);
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        while received.len() < response.len() {
+            let count = peer.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..count]);
+            client.flush_output().unwrap();
+        }
+        assert_eq!(received, response);
+        assert!(client.pending_output.is_empty());
+        assert_eq!(client.wanted_interest(), EventSet::empty());
+    }
+
+    #[test]
+    fn test_stream() {
+        let tmp_socket = TempFile::new().unwrap();
+        let path = tmp_socket.as_path().to_path_buf();
+        std::fs::remove_file(&path).unwrap();
+        let kill_switch = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let mut server = EventStreamServer::new(&path, kill_switch.try_clone().unwrap()).unwrap();
+        let server_thread = thread::spawn(move || server.run(&[]));
+
+        // Other tests emit events concurrently, so start from the next one.
+        let cursor = EVENT_LOG.next_seq();
+        let mut client = UnixStream::connect(&path).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(format!("GET /events?cursor={cursor} HTTP/1.1\r\n\r\n").as_bytes())
+            .unwrap();
+        let mut reader = io::BufReader::new(client);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "HTTP/1.1 200 OK\r\n");
+
+        EVENT_LOG.emit(EventKind::GuestCrashLoaded);
+        let mut found = false;
+        while !found {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert!(!line.is_empty());
+            found = line.starts_with("data: ") && line.contains(r#""type":"guest_crash_loaded""#);
+        }
+
+        // The metrics are served with the OpenMetrics content type.
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(
+            "\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n"
+        ));
+        assert!(response.ends_with("# EOF\n"));
+
+        // Unknown paths are rejected.
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"GET /foo HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        kill_switch.write(1).unwrap();
+        server_thread.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -6,6 +6,8 @@
 //! It is constructed on top of an HTTP Server that uses Unix Domain Sockets and `EPOLL` to
 //! handle multiple connections on the same thread.
 
+pub mod event_stream;
+pub mod operations;
 pub mod parsed_request;
 pub mod request;
 
@@ -13,11 +15,12 @@ use std::fmt::Debug;
 use std::sync::mpsc;
 
 pub use micro_http::{Body, HttpServer, Request, Response, ServerError, StatusCode, Version};
-use parsed_request::{ParsedRequest, RequestAction};
+use operations::{LatencyMetric, OperationType, Operations};
+use parsed_request::{ParsedRequest, RequestAction, RequestError};
 use serde_json::json;
 use utils::time::{ClockType, get_time_us};
 use vmm::logger::{
-    METRICS, ProcessTimeReporter, debug, error, info, update_metric_with_elapsed_time, warn,
+    METRICS, ProcessTimeReporter, SPANS, debug, error, info, update_metric_with_elapsed_time, warn,
 };
 use vmm::rpc_interface::{ApiRequest, ApiResponse, VmmAction};
 use vmm::seccomp::BpfProgramRef;
@@ -34,6 +37,8 @@ pub struct ApiServer {
     /// FD on which we notify the VMM that we have sent at least one
     /// `VmmRequest`.
     to_vmm_fd: EventFd,
+    /// VMM actions submitted asynchronously.
+    operations: Operations,
 }
 
 impl ApiServer {
@@ -49,6 +54,7 @@ impl ApiServer {
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            operations: Operations::default(),
         }
     }
 
@@ -123,12 +129,23 @@ impl ApiServer {
         request: &Request,
         request_processing_start_us: u64,
     ) -> Response {
+        let mut span = SPANS.start("api_request");
+        span.set_attribute("http.request.method", request.method().to_str());
+        span.set_attribute("url.path", request.uri().get_abs_path());
+        // The spans of the VMM thread are children of the request they serve.
+        span.propagate();
+
         match ParsedRequest::try_from(request).map(|r| r.into_parts()) {
             Ok((req_action, mut parsing_info)) => {
                 let mut response = match req_action {
                     RequestAction::Sync(vmm_action) => {
                         self.serve_vmm_action_request(vmm_action, request_processing_start_us)
                     }
+                    RequestAction::Async(vmm_action) => {
+                        self.submit_operation(vmm_action, request_processing_start_us)
+                    }
+                    RequestAction::GetOperation(id) => self.get_operation(id),
+                    RequestAction::CancelOperation(id) => self.cancel_operation(id),
                 };
                 if let Some(message) = parsing_info.take_deprecation_message() {
                     warn!("{}", message);
@@ -143,13 +160,9 @@ impl ApiServer {
         }
     }
 
-    fn serve_vmm_action_request(
-        &mut self,
-        vmm_action: Box<VmmAction>,
-        request_processing_start_us: u64,
-    ) -> Response {
-        let metric_with_action = match *vmm_action {
-            VmmAction::CreateSnapshot(ref params) => match params.snapshot_type {
+    fn latency_metric(vmm_action: &VmmAction) -> Option<LatencyMetric> {
+        match vmm_action {
+            VmmAction::CreateSnapshot(params) => match params.snapshot_type {
                 SnapshotType::Full => Some((
                     &METRICS.latencies_us.full_create_snapshot,
                     "create full snapshot",
@@ -159,18 +172,46 @@ impl ApiServer {
                     "create diff snapshot",
                 )),
             },
+            VmmAction::CreateCoreDump(_) => {
+                Some((&METRICS.latencies_us.create_core_dump, "create core dump"))
+            }
             VmmAction::LoadSnapshot(_) => {
                 Some((&METRICS.latencies_us.load_snapshot, "load snapshot"))
             }
             VmmAction::Pause => Some((&METRICS.latencies_us.pause_vm, "pause vm")),
             VmmAction::Resume => Some((&METRICS.latencies_us.resume_vm, "resume vm")),
             _ => None,
-        };
+        }
+    }
 
+    fn send_vmm_action(&mut self, vmm_action: Box<VmmAction>) {
         self.api_request_sender
             .send(vmm_action)
             .expect("Failed to send VMM message");
         self.to_vmm_fd.write(1).expect("Cannot update send VMM fd");
+    }
+
+    fn serve_vmm_action_request(
+        &mut self,
+        vmm_action: Box<VmmAction>,
+        request_processing_start_us: u64,
+    ) -> Response {
+        // The VMM thread is busy until the running operation finishes, so the requests it has to
+        // serve are rejected rather than blocking the other connections until then.
+        self.poll_operation();
+        if self.operations.is_running() {
+            if let Some(outcome) = vmm_action.outcome_without_vmm() {
+                return ParsedRequest::convert_to_response(&outcome);
+            }
+            return RequestError::Generic(
+                StatusCode::ServiceUnavailable,
+                "An operation is running, retry once it finished.".to_string(),
+            )
+            .into();
+        }
+
+        let metric_with_action = Self::latency_metric(&vmm_action);
+        self.send_vmm_action(vmm_action);
         let vmm_outcome = *(self.vmm_response_receiver.recv().expect("VMM disconnected"));
         let response = ParsedRequest::convert_to_response(&vmm_outcome);
 
@@ -184,6 +225,89 @@ impl ApiServer {
         response
     }
 
+    /// Sends a VMM action without waiting for its outcome, which is recorded in the
+    /// operation returned to the client.
+    fn submit_operation(
+        &mut self,
+        vmm_action: Box<VmmAction>,
+        request_processing_start_us: u64,
+    ) -> Response {
+        self.poll_operation();
+        if self.operations.is_running() {
+            return RequestError::Generic(
+                StatusCode::ServiceUnavailable,
+                "Another operation is already running.".to_string(),
+            )
+            .into();
+        }
+        let Some(action_type) = OperationType::from_action(&vmm_action) else {
+            return RequestError::Generic(
+                StatusCode::BadRequest,
+                "This request cannot be submitted as an operation.".to_string(),
+            )
+            .into();
+        };
+
+        let id = self.operations.start(
+            action_type,
+            Self::latency_metric(&vmm_action),
+            request_processing_start_us,
+        );
+        self.send_vmm_action(vmm_action);
+        info!("Started operation {id}.");
+        ParsedRequest::success_response_with_data(&json!({ "operation_id": id }))
+    }
+
+    /// Records the outcome of the running operation, if the VMM sent it.
+    fn poll_operation(&mut self) {
+        if !self.operations.is_running() {
+            return;
+        }
+        let vmm_outcome = match self.vmm_response_receiver.try_recv() {
+            Ok(vmm_outcome) => *vmm_outcome,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => panic!("VMM disconnected"),
+        };
+
+        if let Some(operation) = self.operations.finish(&vmm_outcome) {
+            info!(
+                "Operation {} finished: {:?}.",
+                operation.id, operation.state
+            );
+            if vmm_outcome.is_ok()
+                && let Some(((metric, action), start_time_us)) = operation.latency()
+            {
+                let elapsed_time_us = update_metric_with_elapsed_time(metric, start_time_us);
+                info!("'{}' API request took {} us.", action, elapsed_time_us);
+            }
+        }
+    }
+
+    fn get_operation(&mut self, id: u64) -> Response {
+        self.poll_operation();
+        match self.operations.get(id) {
+            Ok(operation) => ParsedRequest::success_response_with_data(&operation),
+            Err(err) => {
+                error!("{:?}", err);
+                err.into()
+            }
+        }
+    }
+
+    fn cancel_operation(&mut self, id: u64) -> Response {
+        self.poll_operation();
+        match self.operations.cancel(id) {
+            Ok(()) => {
+                info!("Requested the cancellation of operation {id}.");
+                Response::new(Version::Http11, StatusCode::NoContent)
+            }
+            Err(err) => {
+                error!("{:?}", err);
+                err.into()
+            }
+        }
+    }
+
     /// An HTTP response which also includes a body.
     pub(crate) fn json_response<T: Into<String> + Debug>(status: StatusCode, body: T) -> Response {
         let mut response = Response::new(Version::Http11, status);
@@ -214,6 +338,7 @@ mod tests {
     use vmm::vmm_config::snapshot::CreateSnapshotParams;
     use vmm_sys_util::tempfile::TempFile;
 
+    use super::operations::OperationState;
     use super::request::cpu_configuration::parse_put_cpu_config;
     use super::*;
 
@@ -297,6 +422,73 @@ mod tests {
         assert_eq!(METRICS.latencies_us.full_create_snapshot.fetch(), 0);
     }
 
+    #[test]
+    fn test_operations() {
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, _from_api) = channel();
+        let (to_api, vmm_response_receiver) = channel();
+
+        let mut api_server = ApiServer::new(api_request_sender, vmm_response_receiver, to_vmm_fd);
+        let response = api_server.submit_operation(Box::new(VmmAction::StartMicroVm), 0);
+        assert_eq!(response.status(), StatusCode::OK);
+        // Only one operation runs at a time.
+        let response = api_server.submit_operation(Box::new(VmmAction::StartMicroVm), 0);
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        // Starting the microVM cannot be cancelled.
+        let response = api_server.cancel_operation(0);
+        assert_eq!(response.status(), StatusCode::BadRequest);
+        assert_eq!(api_server.get_operation(0).status(), StatusCode::OK);
+        assert_eq!(api_server.get_operation(1).status(), StatusCode::NotFound);
+
+        // A synchronous request is rejected while the operation runs, without waiting for it.
+        let response =
+            api_server.serve_vmm_action_request(Box::new(VmmAction::GetVmInstanceInfo), 0);
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        assert!(api_server.operations.is_running());
+        // Reading the metrics or the event log does not need the VMM thread.
+        let response = api_server.serve_vmm_action_request(Box::new(VmmAction::GetMetrics), 0);
+        assert_eq!(response.status(), StatusCode::OK);
+        let response =
+            api_server.serve_vmm_action_request(Box::new(VmmAction::GetEvents(Some(u64::MAX))), 0);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(api_server.operations.is_running());
+
+        // Once the outcome of the operation is received, synchronous requests are served again.
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        let response =
+            api_server.serve_vmm_action_request(Box::new(VmmAction::GetVmInstanceInfo), 0);
+        assert_eq!(response.status(), StatusCode::NoContent);
+        assert!(!api_server.operations.is_running());
+        assert_eq!(
+            api_server.operations.get(0).unwrap().state,
+            OperationState::Succeeded
+        );
+
+        let response = api_server.submit_operation(
+            Box::new(VmmAction::CreateSnapshot(CreateSnapshotParams {
+                snapshot_type: SnapshotType::Full,
+                snapshot_path: PathBuf::new(),
+                mem_file_path: PathBuf::new(),
+            })),
+            0,
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = api_server.cancel_operation(1);
+        assert_eq!(response.status(), StatusCode::NoContent);
+        to_api
+            .send(Box::new(Err(VmmActionError::OperationNotSupportedPreBoot)))
+            .unwrap();
+        assert_eq!(api_server.get_operation(1).status(), StatusCode::OK);
+        assert_eq!(
+            api_server.operations.get(1).unwrap().state,
+            OperationState::Cancelled
+        );
+        // Finished operations cannot be cancelled.
+        let response = api_server.cancel_operation(1);
+        assert_eq!(response.status(), StatusCode::BadRequest);
+    }
+
     #[test]
     fn test_handle_request() {
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
@@ -0,0 +1,336 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Keeps track of the VMM actions submitted asynchronously through the API.
+//!
+//! The VMM thread executes one action at a time, so at most one operation runs at any given
+//! moment. The progress of the operations that measure it is read from [`OPERATION_PROGRESS`]
+//! while they run and saved when they finish. Finished operations are kept until
+//! [`MAX_OPERATIONS`] newer ones were submitted.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use vmm::logger::SharedStoreMetric;
+use vmm::operation::OPERATION_PROGRESS;
+use vmm::rpc_interface::{VmmAction, VmmActionError, VmmData};
+
+use super::StatusCode;
+use super::parsed_request::RequestError;
+
+/// Maximum number of operations kept for clients to query.
+pub(crate) const MAX_OPERATIONS: usize = 64;
+
+/// Latency metric to update when an action succeeds, with the description of the action.
+pub(crate) type LatencyMetric = (&'static SharedStoreMetric, &'static str);
+
+/// Actions that can be submitted as operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum OperationType {
+    CreateSnapshot,
+    CreateCoreDump,
+    LoadSnapshot,
+    InstanceStart,
+}
+
+impl OperationType {
+    /// Returns the type of operation executing `vmm_action`, if it can run asynchronously.
+    pub(crate) fn from_action(vmm_action: &VmmAction) -> Option<Self> {
+        match vmm_action {
+            VmmAction::CreateSnapshot(_) => Some(OperationType::CreateSnapshot),
+            VmmAction::CreateCoreDump(_) => Some(OperationType::CreateCoreDump),
+            VmmAction::LoadSnapshot(_) => Some(OperationType::LoadSnapshot),
+            VmmAction::StartMicroVm => Some(OperationType::InstanceStart),
+            _ => None,
+        }
+    }
+
+    /// Stopping a snapshot or core dump creation before the guest memory is fully written
+    /// leaves the microVM paused and untouched. Loading a snapshot or starting the microVM cannot
+    /// be stopped without leaving the process in an unusable state.
+    fn is_cancellable(self) -> bool {
+        matches!(
+            self,
+            OperationType::CreateSnapshot | OperationType::CreateCoreDump
+        )
+    }
+
+    /// Snapshot and core dump creations measure their progress as they write the guest memory
+    /// chunk by chunk, and loading a snapshot as it reads the snapshot file and maps the guest
+    /// memory. Starting the microVM does not process any file.
+    fn reports_progress(self) -> bool {
+        !matches!(self, OperationType::InstanceStart)
+    }
+}
+
+/// States of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum OperationState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Number of guest memory bytes processed by an operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct OperationProgress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+impl OperationProgress {
+    fn current() -> Self {
+        let (bytes_done, bytes_total) = OPERATION_PROGRESS.get();
+        OperationProgress {
+            bytes_done,
+            bytes_total,
+        }
+    }
+}
+
+/// Action submitted asynchronously.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Operation {
+    pub id: u64,
+    pub action_type: OperationType,
+    pub state: OperationState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<OperationProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    cancel_requested: bool,
+    #[serde(skip)]
+    start_time_us: u64,
+    #[serde(skip)]
+    metric: Option<LatencyMetric>,
+}
+
+impl Operation {
+    /// Returns the latency metric to update if the operation succeeded, with its start time.
+    pub(crate) fn latency(&self) -> Option<(LatencyMetric, u64)> {
+        self.metric.map(|metric| (metric, self.start_time_us))
+    }
+}
+
+/// Registry of the operations.
+#[derive(Debug, Default)]
+pub(crate) struct Operations {
+    operations: VecDeque<Operation>,
+    next_id: u64,
+    running: bool,
+}
+
+impl Operations {
+    /// Returns true if an operation is running.
+    pub(crate) fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Registers a new running operation and returns its ID.
+    pub(crate) fn start(
+        &mut self,
+        action_type: OperationType,
+        metric: Option<LatencyMetric>,
+        start_time_us: u64,
+    ) -> u64 {
+        debug_assert!(!self.running);
+        OPERATION_PROGRESS.reset();
+        if self.operations.len() == MAX_OPERATIONS {
+            self.operations.pop_front();
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running = true;
+        self.operations.push_back(Operation {
+            id,
+            action_type,
+            state: OperationState::Running,
+            progress: action_type
+                .reports_progress()
+                .then(OperationProgress::default),
+            error: None,
+            cancel_requested: false,
+            start_time_us,
+            metric,
+        });
+        id
+    }
+
+    /// Records the outcome of the running operation and returns it.
+    pub(crate) fn finish(
+        &mut self,
+        outcome: &Result<VmmData, VmmActionError>,
+    ) -> Option<&Operation> {
+        if !self.running {
+            return None;
+        }
+        self.running = false;
+
+        let operation = self.operations.back_mut()?;
+        if operation.progress.is_some() {
+            operation.progress = Some(OperationProgress::current());
+        }
+        operation.state = match outcome {
+            Ok(_) => OperationState::Succeeded,
+            Err(_) if operation.cancel_requested => OperationState::Cancelled,
+            Err(_) => OperationState::Failed,
+        };
+        operation.error = outcome.as_ref().err().map(|err| err.to_string());
+        // Do not let a late cancellation request affect the next action.
+        OPERATION_PROGRESS.reset();
+        Some(operation)
+    }
+
+    /// Returns the operation with the given ID.
+    pub(crate) fn get(&self, id: u64) -> Result<Operation, RequestError> {
+        let mut operation = self
+            .operations
+            .iter()
+            .find(|operation| operation.id == id)
+            .cloned()
+            .ok_or_else(|| not_found(id))?;
+        if operation.state == OperationState::Running && operation.progress.is_some() {
+            operation.progress = Some(OperationProgress::current());
+        }
+        Ok(operation)
+    }
+
+    /// Requests the cancellation of the operation with the given ID.
+    pub(crate) fn cancel(&mut self, id: u64) -> Result<(), RequestError> {
+        let operation = self
+            .operations
+            .iter_mut()
+            .find(|operation| operation.id == id)
+            .ok_or_else(|| not_found(id))?;
+        if operation.state != OperationState::Running {
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                format!("Operation {id} is not running."),
+            ));
+        }
+        if !operation.action_type.is_cancellable() {
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                format!(
+                    "{:?} operations cannot be cancelled.",
+                    operation.action_type
+                ),
+            ));
+        }
+        operation.cancel_requested = true;
+        OPERATION_PROGRESS.cancel();
+        Ok(())
+    }
+}
+
+fn not_found(id: u64) -> RequestError {
+    RequestError::Generic(
+        StatusCode::NotFound,
+        format!("Operation {id} does not exist."),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::logger::METRICS;
+
+    use super::*;
+
+    #[test]
+    fn test_operation_lifecycle() {
+        let mut operations = Operations::default();
+        assert!(!operations.is_running());
+        operations.get(0).unwrap_err();
+
+        let metric = (&METRICS.latencies_us.load_snapshot, "load snapshot");
+        let id = operations.start(OperationType::LoadSnapshot, Some(metric), 10);
+        assert!(operations.is_running());
+        let operation = operations.get(id).unwrap();
+        assert_eq!(operation.state, OperationState::Running);
+        assert_eq!(operation.action_type, OperationType::LoadSnapshot);
+        // Loading a snapshot cannot be cancelled.
+        operations.cancel(id).unwrap_err();
+
+        let operation = operations.finish(&Ok(VmmData::Empty)).unwrap();
+        assert_eq!(operation.state, OperationState::Succeeded);
+        assert!(operation.error.is_none());
+        assert_eq!(operation.latency().unwrap().1, 10);
+        assert!(!operations.is_running());
+        assert!(operations.finish(&Ok(VmmData::Empty)).is_none());
+        // Finished operations cannot be cancelled.
+        operations.cancel(id).unwrap_err();
+
+        let id = operations.start(OperationType::InstanceStart, None, 0);
+        assert_eq!(id, 1);
+        let operation = operations
+            .finish(&Err(VmmActionError::OperationNotSupportedPostBoot))
+            .unwrap();
+        assert_eq!(operation.state, OperationState::Failed);
+        assert!(operation.error.is_some());
+        assert!(operation.latency().is_none());
+    }
+
+    #[test]
+    fn test_operation_cancel() {
+        let mut operations = Operations::default();
+        let id = operations.start(OperationType::CreateSnapshot, None, 0);
+        operations.cancel(id).unwrap();
+        operations.cancel(id + 1).unwrap_err();
+        let operation = operations
+            .finish(&Err(VmmActionError::OperationNotSupportedPreBoot))
+            .unwrap();
+        assert_eq!(operation.state, OperationState::Cancelled);
+    }
+
+    #[test]
+    fn test_operations_limit() {
+        let mut operations = Operations::default();
+        for _ in 0..=MAX_OPERATIONS {
+            operations.start(OperationType::InstanceStart, None, 0);
+            operations.finish(&Ok(VmmData::Empty));
+        }
+        operations.get(0).unwrap_err();
+        operations.get(1).unwrap();
+        operations.get(64).unwrap();
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut operations = Operations::default();
+        let id = operations.start(OperationType::CreateSnapshot, None, 0);
+        let operation = operations.finish(&Ok(VmmData::Empty)).unwrap().clone();
+        assert_eq!(operation.id, id);
+        let mut value = serde_json::to_value(&operation).unwrap();
+        value["progress"] = serde_json::json!(null);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "id": 0,
+                "action_type": "CreateSnapshot",
+                "state": "Succeeded",
+                "progress": null,
+            })
+        );
+
+        // Loading a snapshot reports its progress.
+        let id = operations.start(OperationType::LoadSnapshot, None, 0);
+        assert!(operations.get(id).unwrap().progress.is_some());
+        operations.finish(&Ok(VmmData::Empty));
+
+        // Operations which do not measure their progress do not report any.
+        let id = operations.start(OperationType::InstanceStart, None, 0);
+        assert!(operations.get(id).unwrap().progress.is_none());
+        let operation = operations.finish(&Ok(VmmData::Empty)).unwrap();
+        assert_eq!(
+            serde_json::to_value(operation).unwrap(),
+            serde_json::json!({
+                "id": 2,
+                "action_type": "InstanceStart",
+                "state": "Succeeded",
+            })
+        );
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::fmt::Debug;
 
-use micro_http::{Body, Method, Request, Response, StatusCode, Version};
+use micro_http::{Body, MediaType, Method, Request, Response, StatusCode, Version};
 use serde::ser::Serialize;
 use serde_json::Value;
 use vmm::logger::{Level, error, info, log_enabled};
@@ -13,29 +13,38 @@ use super::ApiServer;
 use super::request::actions::parse_put_actions;
 use super::request::balloon::{parse_get_balloon, parse_patch_balloon, parse_put_balloon};
 use super::request::boot_source::parse_put_boot_source;
+use super::request::console::parse_put_console;
 use super::request::cpu_configuration::parse_put_cpu_config;
 use super::request::drive::{parse_patch_drive, parse_put_drive};
 use super::request::entropy::parse_put_entropy;
+use super::request::events::parse_get_events;
 use super::request::instance_info::parse_get_instance_info;
 use super::request::logger::parse_put_logger;
 use super::request::machine_configuration::{
     parse_get_machine_config, parse_patch_machine_config, parse_put_machine_config,
 };
-use super::request::metrics::parse_put_metrics;
+use super::request::metrics::{parse_get_metrics, parse_put_metrics};
 use super::request::mmds::{parse_get_mmds, parse_patch_mmds, parse_put_mmds};
 use super::request::net::{parse_patch_net, parse_put_net};
+use super::request::operations::{parse_get_operation, parse_patch_operation, parse_put_operation};
 use super::request::pmem::parse_put_pmem;
 use super::request::snapshot::{parse_patch_vm_state, parse_put_snapshot};
 use super::request::version::parse_get_version;
+use super::request::vm_config::parse_put_vm_config;
 use super::request::vsock::parse_put_vsock;
+use crate::api_server::request::hotplug::device::parse_put_device_unplug;
 use crate::api_server::request::hotplug::memory::{
     parse_get_memory_hotplug, parse_patch_memory_hotplug, parse_put_memory_hotplug,
 };
+use crate::api_server::request::hotplug::vcpus::parse_patch_vcpu_hotplug;
 use crate::api_server::request::serial::parse_put_serial;
 
 #[derive(Debug)]
 pub(crate) enum RequestAction {
     Sync(Box<VmmAction>),
+    Async(Box<VmmAction>),
+    GetOperation(u64),
+    CancelOperation(u64),
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -82,12 +91,15 @@ impl TryFrom<&Request> for ParsedRequest {
         match (request.method(), path, request.body.as_ref()) {
             (Method::Get, "", None) => parse_get_instance_info(),
             (Method::Get, "balloon", None) => parse_get_balloon(path_tokens),
+            (Method::Get, "events", None) => parse_get_events(path_tokens.next()),
             (Method::Get, "version", None) => parse_get_version(),
             (Method::Get, "vm", None) if path_tokens.next() == Some("config") => {
                 Ok(ParsedRequest::new_sync(VmmAction::GetFullVmConfig))
             }
             (Method::Get, "machine-config", None) => parse_get_machine_config(),
+            (Method::Get, "metrics", None) => parse_get_metrics(),
             (Method::Get, "mmds", None) => parse_get_mmds(),
+            (Method::Get, "operations", None) => parse_get_operation(path_tokens.next()),
             (Method::Get, "hotplug", None) if path_tokens.next() == Some("memory") => {
                 parse_get_memory_hotplug()
             }
@@ -106,12 +118,22 @@ impl TryFrom<&Request> for ParsedRequest {
             (Method::Put, "network-interfaces", Some(body)) => {
                 parse_put_net(body, path_tokens.next())
             }
+            (Method::Put, "operations", Some(body)) => parse_put_operation(body, path_tokens),
             (Method::Put, "snapshot", Some(body)) => parse_put_snapshot(body, path_tokens.next()),
+            (Method::Put, "vm", Some(body)) if path_tokens.next() == Some("config") => {
+                parse_put_vm_config(body)
+            }
             (Method::Put, "vsock", Some(body)) => parse_put_vsock(body),
             (Method::Put, "entropy", Some(body)) => parse_put_entropy(body),
-            (Method::Put, "hotplug", Some(body)) if path_tokens.next() == Some("memory") => {
-                parse_put_memory_hotplug(body)
-            }
+            (Method::Put, "console", Some(body)) => parse_put_console(body),
+            (Method::Put, "hotplug", Some(body)) => match path_tokens.next() {
+                Some("memory") => parse_put_memory_hotplug(body),
+                Some("unplug") => parse_put_device_unplug(body),
+                _ => Err(RequestError::InvalidPathMethod(
+                    path.to_string(),
+                    Method::Put,
+                )),
+            },
             (Method::Put, _, None) => method_to_error(Method::Put),
             (Method::Patch, "balloon", body) => parse_patch_balloon(body, path_tokens),
             (Method::Patch, "drives", Some(body)) => parse_patch_drive(body, path_tokens.next()),
@@ -120,10 +142,18 @@ impl TryFrom<&Request> for ParsedRequest {
             (Method::Patch, "network-interfaces", Some(body)) => {
                 parse_patch_net(body, path_tokens.next())
             }
-            (Method::Patch, "vm", Some(body)) => parse_patch_vm_state(body),
-            (Method::Patch, "hotplug", Some(body)) if path_tokens.next() == Some("memory") => {
-                parse_patch_memory_hotplug(body)
+            (Method::Patch, "operations", Some(body)) => {
+                parse_patch_operation(body, path_tokens.next())
             }
+            (Method::Patch, "vm", Some(body)) => parse_patch_vm_state(body),
+            (Method::Patch, "hotplug", Some(body)) => match path_tokens.next() {
+                Some("memory") => parse_patch_memory_hotplug(body),
+                Some("vcpus") => parse_patch_vcpu_hotplug(body),
+                _ => Err(RequestError::InvalidPathMethod(
+                    path.to_string(),
+                    Method::Patch,
+                )),
+            },
             (Method::Patch, _, None) => method_to_error(Method::Patch),
             (method, unknown_uri, _) => Err(RequestError::InvalidPathMethod(
                 unknown_uri.to_string(),
@@ -182,6 +212,13 @@ impl ParsedRequest {
                 VmmData::MachineConfiguration(machine_config) => {
                     Self::success_response_with_data(machine_config)
                 }
+                VmmData::Metrics(text) => {
+                    info!("The request was executed successfully. Status code: 200 OK.");
+                    let mut response = Response::new(Version::Http11, StatusCode::OK);
+                    response.set_content_type(MediaType::PlainText);
+                    response.set_body(Body::new(text.as_str()));
+                    response
+                }
                 VmmData::MmdsValue(value) => Self::success_response_with_mmds_value(value),
                 VmmData::BalloonConfig(balloon_config) => {
                     Self::success_response_with_data(balloon_config)
@@ -196,6 +233,7 @@ impl ParsedRequest {
                     &serde_json::json!({ "firecracker_version": version.as_str() }),
                 ),
                 VmmData::FullVmConfig(config) => Self::success_response_with_data(config),
+                VmmData::Events(batch) => Self::success_response_with_data(batch),
             },
             Err(vmm_action_error) => {
                 let mut response = match vmm_action_error {
@@ -342,6 +380,7 @@ pub mod tests {
     use vmm::builder::StartMicrovmError;
     use vmm::cpu_config::templates::test_utils::build_test_template;
     use vmm::devices::virtio::balloon::device::HintingStatus;
+    use vmm::logger::{Event, EventBatch, EventKind};
     use vmm::resources::VmmConfig;
     use vmm::rpc_interface::VmmActionError;
     use vmm::vmm_config::balloon::{BalloonDeviceConfig, BalloonStats};
@@ -360,6 +399,16 @@ pub mod tests {
                 (RequestAction::Sync(sync_req), RequestAction::Sync(other_sync_req)) => {
                     sync_req == other_sync_req
                 }
+                (RequestAction::Async(async_req), RequestAction::Async(other_async_req)) => {
+                    async_req == other_async_req
+                }
+                (RequestAction::GetOperation(id), RequestAction::GetOperation(other_id)) => {
+                    id == other_id
+                }
+                (RequestAction::CancelOperation(id), RequestAction::CancelOperation(other_id)) => {
+                    id == other_id
+                }
+                _ => false,
             }
         }
     }
@@ -367,6 +416,7 @@ pub mod tests {
     pub(crate) fn vmm_action_from_request(req: ParsedRequest) -> VmmAction {
         match req.action {
             RequestAction::Sync(vmm_action) => *vmm_action,
+            _ => panic!("Unexpected request action: {:?}", req.action),
         }
     }
 
@@ -379,6 +429,7 @@ pub mod tests {
                 assert_eq!(req_msg, msg);
                 *vmm_action
             }
+            _ => panic!("Unexpected request action: {:?}", action_req),
         }
     }
 
@@ -594,12 +645,28 @@ pub mod tests {
                     http_response(&serde_json::to_string(status).unwrap(), 200)
                 }
                 VmmData::Empty => http_response("", 204),
+                VmmData::Events(batch) => {
+                    http_response(&serde_json::to_string(batch).unwrap(), 200)
+                }
                 VmmData::FullVmConfig(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
                 VmmData::MachineConfiguration(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
+                VmmData::Metrics(text) => format!(
+                    concat!(
+                        "HTTP/1.1 200 \r\n",
+                        "Server: Firecracker API\r\n",
+                        "Connection: keep-alive\r\n",
+                        "Content-Type: text/plain\r\n",
+                        "Content-Length: {}\r\n",
+                        "\r\n",
+                        "{}"
+                    ),
+                    text.len(),
+                    text
+                ),
                 VmmData::MmdsValue(value) => {
                     http_response(&serde_json::to_string(value).unwrap(), 200)
                 }
@@ -626,8 +693,18 @@ pub mod tests {
             ..Default::default()
         }));
         verify_ok_response_with(VmmData::Empty);
+        verify_ok_response_with(VmmData::Events(EventBatch {
+            events: vec![Event {
+                seq: 0,
+                timestamp_us: 1,
+                kind: EventKind::VmPaused,
+            }],
+            next_cursor: 1,
+            missed: 0,
+        }));
         verify_ok_response_with(VmmData::FullVmConfig(VmmConfig::default()));
         verify_ok_response_with(VmmData::MachineConfiguration(MachineConfig::default()));
+        verify_ok_response_with(VmmData::Metrics("# EOF\n".to_string()));
         verify_ok_response_with(VmmData::MmdsValue(serde_json::from_str("{}").unwrap()));
         verify_ok_response_with(VmmData::InstanceInformation(InstanceInfo::default()));
         verify_ok_response_with(VmmData::VmmVersion(String::default()));
@@ -655,6 +732,18 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_get_events() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/events/3", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_get_balloon() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -769,6 +858,20 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_put_console() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"ports\": [{ \"name\": \"console\", \"console\": true, \"output_path\": \
+                    \"console.log\" }] }";
+        sender
+            .write_all(http_request("PUT", "/console", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_boot() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -948,6 +1051,59 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_put_hotplug() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"device_type\": \"drive\", \"drive_id\": \"string\" }";
+        sender
+            .write_all(http_request("PUT", "/hotplug/unplug", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+
+        sender
+            .write_all(http_request("PUT", "/hotplug/invalid", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap_err();
+    }
+
+    #[test]
+    fn test_try_from_patch_hotplug() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"vcpu_count\": 2 }";
+        sender
+            .write_all(http_request("PATCH", "/hotplug/vcpus", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+
+        sender
+            .write_all(http_request("PATCH", "/hotplug/invalid", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap_err();
+    }
+
+    #[test]
+    fn test_try_from_put_vm_config() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"boot-source\": { \"kernel_image_path\": \"string\" } }";
+        sender
+            .write_all(http_request("PUT", "/vm/config", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_patch_balloon() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
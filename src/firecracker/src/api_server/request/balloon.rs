@@ -4,7 +4,7 @@
 use micro_http::{Method, StatusCode};
 use vmm::rpc_interface::VmmAction;
 use vmm::vmm_config::balloon::{
-    BalloonDeviceConfig, BalloonUpdateConfig, BalloonUpdateStatsConfig,
+    BalloonDeviceConfig, BalloonUpdateConfig, BalloonUpdatePolicyConfig, BalloonUpdateStatsConfig,
 };
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
@@ -93,6 +93,11 @@ where
                 serde_json::from_slice::<BalloonUpdateStatsConfig>(body.raw())?,
             )))
         }
+        (Some("policy"), Some(body)) => {
+            Ok(ParsedRequest::new_sync(VmmAction::UpdateBalloonPolicy(
+                serde_json::from_slice::<BalloonUpdatePolicyConfig>(body.raw())?,
+            )))
+        }
         (Some("hinting"), body) => parse_patch_hinting(body, path_tokens),
         (_, Some(body)) => Ok(ParsedRequest::new_sync(VmmAction::UpdateBalloon(
             serde_json::from_slice::<BalloonUpdateConfig>(body.raw())?,
@@ -103,6 +108,8 @@ where
 
 #[cfg(test)]
 mod tests {
+    use vmm::vmm_config::balloon::BalloonPolicy;
+
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
@@ -204,6 +211,48 @@ mod tests {
             VmmAction::UpdateBalloonStatistics(expected_config)
         );
 
+        // PATCH policy with valid data.
+        let body = r#"{
+            "policy": {
+                "target_free_mib": 256,
+                "min_mib": 0,
+                "max_mib": 1024,
+                "max_step_mib": 64,
+                "min_interval_s": 5
+            }
+        }"#;
+        let expected_config = BalloonUpdatePolicyConfig {
+            policy: Some(BalloonPolicy {
+                target_free_mib: 256,
+                min_mib: 0,
+                max_mib: 1024,
+                max_step_mib: 64,
+                min_interval_s: 5,
+            }),
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_patch_balloon(Some(&Body::new(body)), ["policy"].into_iter()).unwrap()
+            ),
+            VmmAction::UpdateBalloonPolicy(expected_config)
+        );
+
+        // PATCH policy without a policy clears it.
+        assert_eq!(
+            vmm_action_from_request(
+                parse_patch_balloon(Some(&Body::new("{}")), ["policy"].into_iter()).unwrap()
+            ),
+            VmmAction::UpdateBalloonPolicy(BalloonUpdatePolicyConfig { policy: None })
+        );
+
+        // PATCH policy with missing required fields.
+        let body = r#"{
+            "policy": {
+                "target_free_mib": 256
+            }
+        }"#;
+        parse_patch_balloon(Some(&Body::new(body)), ["policy"].into_iter()).unwrap_err();
+
         // PATCH start hinting run valid data
         let body = r#"{
             "acknowledge_on_stop": true
@@ -275,5 +324,30 @@ mod tests {
             "free_page_reporting": true
         }"#;
         parse_put_balloon(&Body::new(body)).unwrap();
+
+        // PUT with valid policy
+        let body = r#"{
+            "amount_mib": 0,
+            "deflate_on_oom": true,
+            "stats_polling_interval_s": 1,
+            "policy": {
+                "target_free_mib": 256,
+                "max_mib": 1024
+            }
+        }"#;
+        parse_put_balloon(&Body::new(body)).unwrap();
+
+        // PUT with unknown policy fields
+        let body = r#"{
+            "amount_mib": 0,
+            "deflate_on_oom": true,
+            "stats_polling_interval_s": 1,
+            "policy": {
+                "target_free_mib": 256,
+                "max_mib": 1024,
+                "foo": 1
+            }
+        }"#;
+        parse_put_balloon(&Body::new(body)).unwrap_err();
     }
 }
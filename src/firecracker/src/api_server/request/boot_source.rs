@@ -34,6 +34,38 @@ mod tests {
             kernel_image_path: String::from("/foo/bar"),
             initrd_path: Some(String::from("/bar/foo")),
             boot_args: Some(String::from("foobar")),
+            firmware_path: None,
+            measured_boot: false,
+        };
+        let parsed_req = parse_put_boot_source(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::ConfigureBootSource(same_body))
+        );
+
+        let body = r#"{
+            "firmware_path": "/foo/firmware"
+        }"#;
+        let same_body = BootSourceConfig {
+            firmware_path: Some(String::from("/foo/firmware")),
+            ..Default::default()
+        };
+        let parsed_req = parse_put_boot_source(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::ConfigureBootSource(same_body))
+        );
+
+        let body = r#"{
+            "kernel_image_path": "/foo/bar",
+            "measured_boot": true
+        }"#;
+        let same_body = BootSourceConfig {
+            kernel_image_path: String::from("/foo/bar"),
+            measured_boot: true,
+            ..Default::default()
         };
         let parsed_req = parse_put_boot_source(&Body::new(body)).unwrap();
 
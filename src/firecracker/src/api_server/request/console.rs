@@ -0,0 +1,60 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::console::ConsoleDeviceConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_console(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let cfg = serde_json::from_slice::<ConsoleDeviceConfig>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetConsoleDevice(cfg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::vmm_config::console::ConsolePortConfig;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_console_request() {
+        parse_put_console(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with invalid fields.
+        let body = r#"{
+            "ports": [{ "name": "console", "some_id": 4 }]
+        }"#;
+        parse_put_console(&Body::new(body)).unwrap_err();
+
+        // PUT with valid fields.
+        let body = r#"{
+            "ports": [
+                { "name": "console", "console": true, "output_path": "console.log" },
+                { "name": "agent", "uds_path": "agent.sock" }
+            ]
+        }"#;
+        let expected_config = ConsoleDeviceConfig {
+            ports: vec![
+                ConsolePortConfig {
+                    name: "console".to_string(),
+                    console: true,
+                    uds_path: None,
+                    output_path: Some("console.log".to_string()),
+                },
+                ConsolePortConfig {
+                    name: "agent".to_string(),
+                    console: false,
+                    uds_path: Some("agent.sock".to_string()),
+                    output_path: None,
+                },
+            ],
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_console(&Body::new(body)).unwrap()),
+            VmmAction::SetConsoleDevice(expected_config)
+        );
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::StatusCode;
+
+pub(crate) fn parse_get_events(cursor: Option<&str>) -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.events_count.inc();
+    let cursor = cursor
+        .map(|cursor| {
+            cursor.parse::<u64>().map_err(|_| {
+                RequestError::Generic(
+                    StatusCode::BadRequest,
+                    format!("Invalid events cursor: {cursor}."),
+                )
+            })
+        })
+        .transpose()?;
+    Ok(ParsedRequest::new_sync(VmmAction::GetEvents(cursor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_events_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_events(None).unwrap()),
+            VmmAction::GetEvents(None)
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_events(Some("42")).unwrap()),
+            VmmAction::GetEvents(Some(42))
+        );
+        parse_get_events(Some("-1")).unwrap_err();
+        parse_get_events(Some("foo")).unwrap_err();
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use micro_http::Body;
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::pci_hotplug::DeviceUnplugConfig;
+
+use crate::api_server::parsed_request::{ParsedRequest, RequestError};
+
+pub(crate) fn parse_put_device_unplug(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.hotplug_unplug_count.inc();
+    let config = serde_json::from_slice::<DeviceUnplugConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.hotplug_unplug_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::UnplugDevice(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_device_unplug_request() {
+        parse_put_device_unplug(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with an unknown device type.
+        let body = r#"{
+            "device_type": "balloon"
+        }"#;
+        parse_put_device_unplug(&Body::new(body)).unwrap_err();
+
+        // PUT without the id of the device.
+        let body = r#"{
+            "device_type": "network_interface"
+        }"#;
+        parse_put_device_unplug(&Body::new(body)).unwrap_err();
+
+        let body = r#"{
+            "device_type": "network_interface",
+            "iface_id": "eth1"
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_put_device_unplug(&Body::new(body)).unwrap()),
+            VmmAction::UnplugDevice(DeviceUnplugConfig::NetworkInterface {
+                iface_id: String::from("eth1")
+            })
+        );
+
+        let body = r#"{
+            "device_type": "vsock"
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_put_device_unplug(&Body::new(body)).unwrap()),
+            VmmAction::UnplugDevice(DeviceUnplugConfig::Vsock)
+        );
+    }
+}
@@ -109,6 +109,21 @@ mod tests {
         }"#;
         let expected_config = MemoryHotplugSizeUpdate {
             requested_size_mib: 2048,
+            timeout_s: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_memory_hotplug(&Body::new(body)).unwrap()),
+            VmmAction::UpdateMemoryHotplugSize(expected_config)
+        );
+
+        // PATCH with a timeout.
+        let body = r#"{
+            "requested_size_mib": 512,
+            "timeout_s": 30
+        }"#;
+        let expected_config = MemoryHotplugSizeUpdate {
+            requested_size_mib: 512,
+            timeout_s: Some(30),
         };
         assert_eq!(
             vmm_action_from_request(parse_patch_memory_hotplug(&Body::new(body)).unwrap()),
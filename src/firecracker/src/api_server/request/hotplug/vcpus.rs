@@ -0,0 +1,50 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use micro_http::Body;
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::vcpu_hotplug::VcpuCountUpdate;
+
+use crate::api_server::parsed_request::{ParsedRequest, RequestError};
+
+pub(crate) fn parse_patch_vcpu_hotplug(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.patch_api_requests.hotplug_vcpus_count.inc();
+    let update = serde_json::from_slice::<VcpuCountUpdate>(body.raw()).inspect_err(|_| {
+        METRICS.patch_api_requests.hotplug_vcpus_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::UpdateVcpuCount(update)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_patch_vcpu_hotplug_request() {
+        parse_patch_vcpu_hotplug(&Body::new("invalid_payload")).unwrap_err();
+
+        // PATCH with invalid fields.
+        let body = r#"{
+            "vcpu_count": "bar"
+        }"#;
+        parse_patch_vcpu_hotplug(&Body::new(body)).unwrap_err();
+
+        // PATCH with unknown fields.
+        let body = r#"{
+            "vcpu_count": 2,
+            "foo": 1
+        }"#;
+        parse_patch_vcpu_hotplug(&Body::new(body)).unwrap_err();
+
+        // PATCH with valid input fields.
+        let body = r#"{
+            "vcpu_count": 4
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_patch_vcpu_hotplug(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVcpuCount(VcpuCountUpdate { vcpu_count: 4 })
+        );
+    }
+}
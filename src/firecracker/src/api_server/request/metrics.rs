@@ -8,6 +8,11 @@ use vmm::vmm_config::metrics::MetricsConfig;
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
 
+pub(crate) fn parse_get_metrics() -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.metrics_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::GetMetrics))
+}
+
 pub(crate) fn parse_put_metrics(body: &Body) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.metrics_count.inc();
     Ok(ParsedRequest::new_sync(VmmAction::ConfigureMetrics(
@@ -24,6 +29,14 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_metrics_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_metrics().unwrap()),
+            VmmAction::GetMetrics
+        );
+    }
+
     #[test]
     fn test_parse_put_metrics_request() {
         let body = r#"{
@@ -4,9 +4,11 @@
 pub mod actions;
 pub mod balloon;
 pub mod boot_source;
+pub mod console;
 pub mod cpu_configuration;
 pub mod drive;
 pub mod entropy;
+pub mod events;
 pub mod hotplug;
 pub mod instance_info;
 pub mod logger;
@@ -14,9 +16,11 @@ pub mod machine_configuration;
 pub mod metrics;
 pub mod mmds;
 pub mod net;
+pub mod operations;
 pub mod pmem;
 pub mod serial;
 pub mod snapshot;
 pub mod version;
+pub mod vm_config;
 pub mod vsock;
 pub use micro_http::{Body, Method, StatusCode};
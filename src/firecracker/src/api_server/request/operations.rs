@@ -0,0 +1,172 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Deserialize;
+use vmm::logger::{IncMetric, METRICS};
+
+use super::super::operations::{OperationState, OperationType};
+use super::super::parsed_request::{ParsedRequest, RequestAction, RequestError};
+use super::actions::parse_put_actions;
+use super::snapshot::parse_put_snapshot;
+use super::{Body, Method, StatusCode};
+
+// The model of the json body used to update an operation.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OperationUpdate {
+    state: OperationState,
+}
+
+fn parse_operation_id(id: Option<&str>) -> Result<u64, RequestError> {
+    match id {
+        Some(id) => id.parse::<u64>().map_err(|_| {
+            RequestError::Generic(
+                StatusCode::BadRequest,
+                format!("Invalid operation ID: {id}."),
+            )
+        }),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Missing operation ID.".to_string(),
+        )),
+    }
+}
+
+pub(crate) fn parse_get_operation(id: Option<&str>) -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.operations_count.inc();
+    let id = parse_operation_id(id)?;
+    Ok(ParsedRequest::new(RequestAction::GetOperation(id)))
+}
+
+pub(crate) fn parse_put_operation<'a, T>(
+    body: &Body,
+    mut path_tokens: T,
+) -> Result<ParsedRequest, RequestError>
+where
+    T: Iterator<Item = &'a str>,
+{
+    METRICS.put_api_requests.operations_count.inc();
+    // The operation is described by the path and body of the equivalent synchronous request.
+    let parsed_req = match path_tokens.next() {
+        Some("actions") => parse_put_actions(body),
+        Some("snapshot") => parse_put_snapshot(body, path_tokens.next()),
+        Some(path) => Err(RequestError::InvalidPathMethod(
+            format!("/operations/{path}"),
+            Method::Put,
+        )),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Missing operation path.".to_string(),
+        )),
+    }
+    .inspect_err(|_| METRICS.put_api_requests.operations_fails.inc())?;
+
+    let (action, mut parsing_info) = parsed_req.into_parts();
+    let vmm_action = match action {
+        RequestAction::Sync(vmm_action) if OperationType::from_action(&vmm_action).is_some() => {
+            vmm_action
+        }
+        _ => {
+            METRICS.put_api_requests.operations_fails.inc();
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                "This request cannot be submitted as an operation.".to_string(),
+            ));
+        }
+    };
+
+    let mut parsed_req = ParsedRequest::new(RequestAction::Async(vmm_action));
+    if let Some(message) = parsing_info.take_deprecation_message() {
+        parsed_req
+            .parsing_info()
+            .append_deprecation_message(&message);
+    }
+    Ok(parsed_req)
+}
+
+pub(crate) fn parse_patch_operation(
+    body: &Body,
+    id: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.patch_api_requests.operations_count.inc();
+    let id = parse_operation_id(id)
+        .inspect_err(|_| METRICS.patch_api_requests.operations_fails.inc())?;
+    let update = serde_json::from_slice::<OperationUpdate>(body.raw())
+        .inspect_err(|_| METRICS.patch_api_requests.operations_fails.inc())?;
+
+    match update.state {
+        OperationState::Cancelled => Ok(ParsedRequest::new(RequestAction::CancelOperation(id))),
+        _ => {
+            METRICS.patch_api_requests.operations_fails.inc();
+            Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                "Operations can only be updated to the `Cancelled` state.".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use vmm::rpc_interface::VmmAction;
+    use vmm::vmm_config::snapshot::{CreateSnapshotParams, SnapshotType};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_get_operation_request() {
+        assert!(
+            parse_get_operation(Some("3")).unwrap()
+                == ParsedRequest::new(RequestAction::GetOperation(3))
+        );
+        parse_get_operation(None).unwrap_err();
+        parse_get_operation(Some("foo")).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_put_operation_request() {
+        let body = r#"{
+            "snapshot_type": "Full",
+            "snapshot_path": "foo",
+            "mem_file_path": "bar"
+        }"#;
+        let expected = VmmAction::CreateSnapshot(CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+        });
+        assert!(
+            parse_put_operation(&Body::new(body), ["snapshot", "create"].into_iter()).unwrap()
+                == ParsedRequest::new(RequestAction::Async(Box::new(expected)))
+        );
+
+        let body = r#"{ "action_type": "InstanceStart" }"#;
+        assert!(
+            parse_put_operation(&Body::new(body), ["actions"].into_iter()).unwrap()
+                == ParsedRequest::new(RequestAction::Async(Box::new(VmmAction::StartMicroVm)))
+        );
+
+        // Only long-running actions can be submitted as operations.
+        let body = r#"{ "action_type": "FlushMetrics" }"#;
+        parse_put_operation(&Body::new(body), ["actions"].into_iter()).unwrap_err();
+        parse_put_operation(&Body::new(body), ["drives"].into_iter()).unwrap_err();
+        parse_put_operation(&Body::new(body), [].into_iter()).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_patch_operation_request() {
+        let body = r#"{ "state": "Cancelled" }"#;
+        assert!(
+            parse_patch_operation(&Body::new(body), Some("1")).unwrap()
+                == ParsedRequest::new(RequestAction::CancelOperation(1))
+        );
+        parse_patch_operation(&Body::new(body), None).unwrap_err();
+
+        let body = r#"{ "state": "Running" }"#;
+        parse_patch_operation(&Body::new(body), Some("1")).unwrap_err();
+        let body = r#"{ "state": "Cancelled", "foo": "bar" }"#;
+        parse_patch_operation(&Body::new(body), Some("1")).unwrap_err();
+    }
+}
@@ -60,7 +60,8 @@ mod tests {
             "id": "1000",
             "path_on_host": "dummy",
             "root_device": true,
-            "read_only": true
+            "read_only": true,
+            "shared_lock": true
         }"#;
         let r = vmm_action_from_request(parse_put_pmem(&Body::new(body), Some("1000")).unwrap());
 
@@ -69,6 +70,7 @@ mod tests {
             path_on_host: "dummy".to_string(),
             root_device: true,
             read_only: true,
+            shared_lock: true,
         };
         assert_eq!(r, VmmAction::InsertPmemDevice(expected_config));
     }
@@ -32,6 +32,22 @@ mod tests {
 
         let expected_config = SerialConfig {
             serial_out_path: Some(PathBuf::from("serial")),
+            serial_socket_path: None,
+            rate_limiter: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_serial(&Body::new(body)).unwrap()),
+            VmmAction::ConfigureSerial(expected_config)
+        );
+    }
+
+    #[test]
+    fn test_parse_put_serial_socket_request() {
+        let body = r#"{"serial_socket_path": "serial.sock"}"#;
+
+        let expected_config = SerialConfig {
+            serial_out_path: None,
+            serial_socket_path: Some(PathBuf::from("serial.sock")),
             rate_limiter: None,
         };
         assert_eq!(
@@ -49,6 +65,7 @@ mod tests {
 
         let expected_config = SerialConfig {
             serial_out_path: Some(PathBuf::from("serial")),
+            serial_socket_path: None,
             rate_limiter: Some(TokenBucketConfig {
                 size: 1024,
                 one_time_burst: Some(65536),
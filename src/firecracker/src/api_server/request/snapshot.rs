@@ -5,8 +5,8 @@ use serde::de::Error as DeserializeError;
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
 use vmm::vmm_config::snapshot::{
-    CreateSnapshotParams, LoadSnapshotConfig, LoadSnapshotParams, MemBackendConfig, MemBackendType,
-    Vm, VmState,
+    CreateCoreDumpParams, CreateSnapshotParams, LoadSnapshotConfig, LoadSnapshotParams,
+    MemBackendConfig, MemBackendType, Vm, VmState,
 };
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
@@ -30,6 +30,7 @@ pub(crate) fn parse_put_snapshot(
     match request_type_from_path {
         Some(request_type) => match request_type {
             "create" => parse_put_snapshot_create(body),
+            "coredump" => parse_put_snapshot_coredump(body),
             "load" => parse_put_snapshot_load(body),
             _ => Err(RequestError::InvalidPathMethod(
                 format!("/snapshot/{}", request_type),
@@ -59,6 +60,13 @@ fn parse_put_snapshot_create(body: &Body) -> Result<ParsedRequest, RequestError>
     )))
 }
 
+fn parse_put_snapshot_coredump(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let core_dump_config = serde_json::from_slice::<CreateCoreDumpParams>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::CreateCoreDump(
+        core_dump_config,
+    )))
+}
+
 fn parse_put_snapshot_load(body: &Body) -> Result<ParsedRequest, RequestError> {
     let snapshot_config = serde_json::from_slice::<LoadSnapshotConfig>(body.raw())?;
 
@@ -409,6 +417,29 @@ mod tests {
         parse_put_snapshot(&Body::new(body), None).unwrap_err();
     }
 
+    #[test]
+    fn test_parse_put_snapshot_coredump() {
+        use std::path::PathBuf;
+
+        let body = r#"{
+            "core_dump_path": "/tmp/vmcore"
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("coredump")).unwrap()
+            ),
+            VmmAction::CreateCoreDump(CreateCoreDumpParams {
+                core_dump_path: PathBuf::from("/tmp/vmcore"),
+            })
+        );
+
+        let invalid_body = r#"{
+            "core_dump_path": "/tmp/vmcore",
+            "mem_file_path": "bar"
+        }"#;
+        parse_put_snapshot(&Body::new(invalid_body), Some("coredump")).unwrap_err();
+    }
+
     #[test]
     fn test_parse_patch_vm_state() {
         let body = r#"{
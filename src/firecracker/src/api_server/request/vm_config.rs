@@ -0,0 +1,54 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::resources::VmmConfig;
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_vm_config(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.vm_config_count.inc();
+    let config = serde_json::from_slice::<VmmConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.vm_config_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetVmConfig(Box::new(
+        config,
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::vmm_config::boot_source::BootSourceConfig;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_vm_config_request() {
+        parse_put_vm_config(&Body::new("invalid_payload")).unwrap_err();
+
+        // The boot source is mandatory.
+        parse_put_vm_config(&Body::new("{}")).unwrap_err();
+
+        let body = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux" },
+            "machine-config": { "vcpu_count": 2, "mem_size_mib": 128 },
+            "memory-hotplug-size": { "requested_size_mib": 1024 }
+        }"#;
+        let config = match vmm_action_from_request(parse_put_vm_config(&Body::new(body)).unwrap()) {
+            VmmAction::SetVmConfig(config) => config,
+            vmm_action => panic!("Unexpected action: {vmm_action:?}"),
+        };
+        assert_eq!(
+            config.boot_source,
+            BootSourceConfig {
+                kernel_image_path: "vmlinux".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.machine_config.unwrap().vcpu_count, 2);
+        assert_eq!(config.memory_hotplug_size.unwrap().requested_size_mib, 1024);
+    }
+}
@@ -8,6 +8,7 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 
 use event_manager::{EventOps, Events, MutEventSubscriber, SubscriberOps};
+use vmm::landlock::LandlockConfig;
 use vmm::logger::{ProcessTimeReporter, error, info, warn};
 use vmm::rpc_interface::{
     ApiRequest, ApiResponse, BuildMicrovmFromRequestsError, PrebootApiController,
@@ -19,6 +20,7 @@ use vmm::{EventManager, FcExitCode, Vmm};
 use vmm_sys_util::epoll::EventSet;
 use vmm_sys_util::eventfd::EventFd;
 
+use super::api_server::event_stream::{EventStreamError, EventStreamServer};
 use super::api_server::{ApiServer, HttpServer, ServerError};
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -31,6 +33,8 @@ pub enum ApiServerError {
     FailedToBindSocket(String),
     /// Failed to bind and run the HTTP server: {0}
     FailedToBindAndRunHttpServer(ServerError),
+    /// Failed to start the event stream server: {0}
+    EventStream(EventStreamError),
     /// Failed to build MicroVM from Json: {0}
     BuildFromJson(crate::BuildFromJsonError),
 }
@@ -65,9 +69,15 @@ impl ApiServerAdapter {
                 .run()
                 .expect("EventManager events driver fatal error");
 
-            match vmm.lock().unwrap().shutdown_exit_code() {
+            let mut vmm = vmm.lock().unwrap();
+            // Devices hotplugged or removed while handling the events change the subscribers.
+            vmm.update_device_subscribers(event_manager);
+            match vmm.shutdown_exit_code() {
                 Some(FcExitCode::Ok) => break,
-                Some(exit_code) => return Err(ApiServerError::MicroVMStoppedWithError(exit_code)),
+                Some(exit_code) => {
+                    vmm.write_core_dump_on_fault();
+                    return Err(ApiServerError::MicroVMStoppedWithError(exit_code));
+                }
                 None => continue,
             }
         }
@@ -138,10 +148,14 @@ pub(crate) fn run_with_api(
     seccomp_filters: &mut BpfThreadMap,
     config_json: Option<String>,
     bind_path: PathBuf,
+    events_sock: Option<PathBuf>,
     instance_info: InstanceInfo,
     process_time_reporter: ProcessTimeReporter,
     boot_timer_enabled: bool,
     pci_enabled: bool,
+    landlock: Option<LandlockConfig>,
+    coredump_on_fault: Option<PathBuf>,
+    pause_on_panic: bool,
     api_payload_limit: usize,
     mmds_size_limit: usize,
     metadata_json: Option<&str>,
@@ -163,6 +177,7 @@ pub(crate) fn run_with_api(
     let api_seccomp_filter = seccomp_filters
         .remove("api")
         .expect("Missing seccomp filter for API thread.");
+    let events_seccomp_filter = api_seccomp_filter.clone();
 
     let mut server = match HttpServer::new(&bind_path) {
         Ok(s) => s,
@@ -176,6 +191,21 @@ pub(crate) fn run_with_api(
     };
     info!("Listening on API socket ({bind_path:?}).");
 
+    let events_kill_switch =
+        EventFd::new(libc::EFD_NONBLOCK).expect("Cannot create events kill switch.");
+    let events_server = match events_sock {
+        Some(events_path) => {
+            let events_kill_switch_clone = events_kill_switch
+                .try_clone()
+                .expect("Failed to clone events kill switch");
+            let events_server = EventStreamServer::new(&events_path, events_kill_switch_clone)
+                .map_err(ApiServerError::EventStream)?;
+            info!("Streaming events on socket ({events_path:?}).");
+            Some(events_server)
+        }
+        None => None,
+    };
+
     let api_kill_switch_clone = api_kill_switch
         .try_clone()
         .expect("Failed to clone API kill switch");
@@ -185,9 +215,18 @@ pub(crate) fn run_with_api(
         .expect("Cannot add HTTP server kill switch");
 
     // Start the separate API thread.
+    let api_landlock = landlock.is_some();
     let api_thread = thread::Builder::new()
         .name("fc_api".to_owned())
         .spawn(move || {
+            // The API thread does not need to open any path, so it is denied all filesystem
+            // access.
+            if api_landlock && let Err(err) = vmm::landlock::restrict_thread(&[]) {
+                panic!(
+                    "Failed to install the Landlock ruleset on the API thread: {}",
+                    err
+                );
+            }
             ApiServer::new(to_vmm, from_vmm, to_vmm_event_fd).run(
                 server,
                 process_time_reporter,
@@ -197,6 +236,22 @@ pub(crate) fn run_with_api(
         })
         .expect("API thread spawn failed.");
 
+    // Start the thread streaming the lifecycle events, if requested.
+    let events_thread = events_server.map(|mut events_server| {
+        thread::Builder::new()
+            .name("fc_events".to_owned())
+            .spawn(move || {
+                if api_landlock && let Err(err) = vmm::landlock::restrict_thread(&[]) {
+                    panic!(
+                        "Failed to install the Landlock ruleset on the events thread: {}",
+                        err
+                    );
+                }
+                events_server.run(&events_seccomp_filter);
+            })
+            .expect("Events thread spawn failed.")
+    });
+
     let mut event_manager = EventManager::new().expect("Unable to create EventManager");
 
     // Create the firecracker metrics object responsible for periodically printing metrics.
@@ -212,6 +267,9 @@ pub(crate) fn run_with_api(
             instance_info,
             boot_timer_enabled,
             pci_enabled,
+            landlock,
+            coredump_on_fault,
+            pause_on_panic,
             mmds_size_limit,
             metadata_json,
         )
@@ -225,6 +283,9 @@ pub(crate) fn run_with_api(
             &api_event_fd,
             boot_timer_enabled,
             pci_enabled,
+            landlock,
+            coredump_on_fault,
+            pause_on_panic,
             mmds_size_limit,
             metadata_json,
         )
@@ -244,6 +305,10 @@ pub(crate) fn run_with_api(
     // This call to thread::join() should block until the API thread has processed the
     // shutdown-internal and returns from its function.
     api_thread.join().expect("Api thread should join");
+    if let Some(events_thread) = events_thread {
+        events_kill_switch.write(1).unwrap();
+        events_thread.join().expect("Events thread should join");
+    }
 
     result
 }
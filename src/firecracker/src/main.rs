@@ -27,10 +27,12 @@ use utils::arg_parser::{ArgParser, Argument};
 use utils::validators::validate_instance_id;
 use vmm::arch::host_page_size;
 use vmm::builder::StartMicrovmError;
+use vmm::landlock::LandlockConfig;
 #[cfg(feature = "fuzzing")]
 use vmm::logger::warn;
 use vmm::logger::{
-    LOGGER, LoggerConfig, METRICS, ProcessTimeReporter, StoreMetric, debug, error, info,
+    LOGGER, LoggerConfig, METRICS, ProcessTimeReporter, SPANS, StoreMetric, TraceFormat, debug,
+    error, info,
 };
 use vmm::persist::SNAPSHOT_VERSION;
 use vmm::resources::VmResources;
@@ -71,6 +73,8 @@ enum MainError {
     LoggerInitialization(vmm::logger::LoggerUpdateError),
     /// Could not initialize metrics: {0}
     MetricsInitialization(MetricsConfigError),
+    /// Could not initialize span tracing: {0}
+    TracingInitialization(vmm::logger::SpansError),
     /// Seccomp error: {0}
     SeccompFilter(FilterError),
     /// Failed to resize fd table: {0}
@@ -161,6 +165,15 @@ fn main_exec() -> Result<(), MainError> {
                     .default_value(DEFAULT_API_SOCK_PATH)
                     .help("Path to unix domain socket used by the API."),
             )
+            .arg(
+                Argument::new("events-sock")
+                    .takes_value(true)
+                    .forbids(vec!["no-api"])
+                    .help(
+                        "Path to the unix domain socket on which the lifecycle events of the \
+                         microVM are streamed and the metrics are exposed.",
+                    ),
+            )
             .arg(
                 Argument::new("id")
                     .takes_value(true)
@@ -243,6 +256,17 @@ fn main_exec() -> Result<(), MainError> {
                     .takes_value(true)
                     .help("Path to a fifo or a file used for configuring the metrics on startup."),
             )
+            .arg(
+                Argument::new("trace-path")
+                    .takes_value(true)
+                    .help("Path to a fifo or a file where the spans are written."),
+            )
+            .arg(
+                Argument::new("trace-format")
+                    .takes_value(true)
+                    .requires("trace-path")
+                    .help("Format of the trace file: chrome (default) or otlp."),
+            )
             .arg(Argument::new("boot-timer").takes_value(false).help(
                 "Whether or not to load boot timer device for logging elapsed time since \
                  InstanceStart command.",
@@ -277,7 +301,30 @@ fn main_exec() -> Result<(), MainError> {
                 Argument::new("enable-pci")
                     .takes_value(false)
                     .help("Enables PCIe support."),
-            );
+            )
+            .arg(Argument::new("landlock").takes_value(false).help(
+                "Restrict the filesystem access of Firecracker with a Landlock ruleset to the \
+                 paths referenced by the microVM configuration, once the microVM is built.",
+            ))
+            .arg(
+                Argument::new("landlock-allow")
+                    .allow_multiple(true)
+                    .requires("landlock")
+                    .help(
+                        "Path which Firecracker can read and write in addition to the ones \
+                         referenced by the microVM configuration when Landlock is enabled, such \
+                         as the directory where snapshots are created. This argument can be used \
+                         multiple times to allow multiple paths.",
+                    ),
+            )
+            .arg(Argument::new("coredump-on-fault").takes_value(true).help(
+                "Path of the ELF core dump of the guest written when a vCPU stops because of an \
+                 error.",
+            ))
+            .arg(Argument::new("pause-on-panic").takes_value(false).help(
+                "Pause the microVM instead of exiting when the guest kernel panics, so that it \
+                 can be inspected or snapshotted.",
+            ));
 
     arg_parser.parse_from_cmdline()?;
     let arguments = arg_parser.arguments();
@@ -365,6 +412,7 @@ fn main_exec() -> Result<(), MainError> {
         state: VmState::NotStarted,
         vmm_version: FIRECRACKER_VERSION.to_string(),
         app_name: "Firecracker".to_string(),
+        boot_measurements: Vec::new(),
     };
 
     if let Some(metrics_path) = arguments.single_value("metrics-path") {
@@ -374,6 +422,18 @@ fn main_exec() -> Result<(), MainError> {
         init_metrics(metrics_config).map_err(MainError::MetricsInitialization)?;
     }
 
+    if let Some(trace_path) = arguments.single_value("trace-path") {
+        let trace_format = arguments
+            .single_value("trace-format")
+            .map(TraceFormat::from_str)
+            .transpose()
+            .map_err(MainError::TracingInitialization)?
+            .unwrap_or(TraceFormat::Chrome);
+        SPANS
+            .init(&PathBuf::from(trace_path), trace_format)
+            .map_err(MainError::TracingInitialization)?;
+    }
+
     let mut seccomp_filters: BpfThreadMap = SeccompConfig::from_args(
         arguments.flag_present("no-seccomp"),
         arguments.single_value("seccomp-filter"),
@@ -393,6 +453,18 @@ fn main_exec() -> Result<(), MainError> {
 
     let boot_timer_enabled = arguments.flag_present("boot-timer");
     let pci_enabled = arguments.flag_present("enable-pci");
+    let landlock = arguments.flag_present("landlock").then(|| LandlockConfig {
+        allowed_paths: arguments
+            .multiple_values("landlock-allow")
+            .unwrap_or_default()
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+    });
+    let coredump_on_fault = arguments
+        .single_value("coredump-on-fault")
+        .map(PathBuf::from);
+    let pause_on_panic = arguments.flag_present("pause-on-panic");
     let api_enabled = !arguments.flag_present("no-api");
     let api_payload_limit = arg_parser
         .arguments()
@@ -420,6 +492,7 @@ fn main_exec() -> Result<(), MainError> {
             .single_value("api-sock")
             .map(PathBuf::from)
             .expect("Missing argument: api-sock");
+        let events_sock = arguments.single_value("events-sock").map(PathBuf::from);
 
         let start_time_us = arguments.single_value("start-time-us").map(|s| {
             s.parse::<u64>()
@@ -443,10 +516,14 @@ fn main_exec() -> Result<(), MainError> {
             &mut seccomp_filters,
             vmm_config_json,
             bind_path,
+            events_sock,
             instance_info,
             process_time_reporter,
             boot_timer_enabled,
             pci_enabled,
+            landlock,
+            coredump_on_fault,
+            pause_on_panic,
             api_payload_limit,
             mmds_size_limit,
             metadata_json.as_deref(),
@@ -463,6 +540,9 @@ fn main_exec() -> Result<(), MainError> {
             instance_info,
             boot_timer_enabled,
             pci_enabled,
+            landlock,
+            coredump_on_fault,
+            pause_on_panic,
             mmds_size_limit,
             metadata_json.as_deref(),
         )
@@ -588,6 +668,9 @@ fn build_microvm_from_json(
     instance_info: InstanceInfo,
     boot_timer_enabled: bool,
     pci_enabled: bool,
+    landlock: Option<LandlockConfig>,
+    coredump_on_fault: Option<PathBuf>,
+    pause_on_panic: bool,
     mmds_size_limit: usize,
     metadata_json: Option<&str>,
 ) -> Result<Arc<Mutex<vmm::Vmm>>, BuildFromJsonError> {
@@ -596,6 +679,9 @@ fn build_microvm_from_json(
             .map_err(BuildFromJsonError::ParseFromJson)?;
     vm_resources.boot_timer = boot_timer_enabled;
     vm_resources.pci_enabled = pci_enabled;
+    vm_resources.landlock = landlock;
+    vm_resources.coredump_on_fault = coredump_on_fault;
+    vm_resources.pause_on_panic = pause_on_panic;
     let vmm = vmm::builder::build_and_boot_microvm(
         &instance_info,
         &vm_resources,
@@ -617,12 +703,16 @@ enum RunWithoutApiError {
     BuildMicroVMFromJson(BuildFromJsonError),
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_without_api(
     seccomp_filters: &BpfThreadMap,
     config_json: Option<String>,
     instance_info: InstanceInfo,
     bool_timer_enabled: bool,
     pci_enabled: bool,
+    landlock: Option<LandlockConfig>,
+    coredump_on_fault: Option<PathBuf>,
+    pause_on_panic: bool,
     mmds_size_limit: usize,
     metadata_json: Option<&str>,
 ) -> Result<(), RunWithoutApiError> {
@@ -641,6 +731,9 @@ fn run_without_api(
         instance_info,
         bool_timer_enabled,
         pci_enabled,
+        landlock,
+        coredump_on_fault,
+        pause_on_panic,
         mmds_size_limit,
         metadata_json,
     )
@@ -658,9 +751,15 @@ fn run_without_api(
             .run()
             .expect("Failed to start the event manager");
 
-        match vmm.lock().unwrap().shutdown_exit_code() {
+        let mut vmm = vmm.lock().unwrap();
+        // Devices removed while handling the events change the subscribers.
+        vmm.update_device_subscribers(&mut event_manager);
+        match vmm.shutdown_exit_code() {
             Some(FcExitCode::Ok) => break,
-            Some(exit_code) => return Err(RunWithoutApiError::Shutdown(exit_code)),
+            Some(exit_code) => {
+                vmm.write_core_dump_on_fault();
+                return Err(RunWithoutApiError::Shutdown(exit_code));
+            }
             None => continue,
         }
     }
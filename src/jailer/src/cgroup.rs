@@ -381,6 +381,9 @@ impl CgroupV2 {
     // To be able to use a leaf controller within a nested cgroup hierarchy,
     // the controller needs to be enabled by writing to the cgroup.subtree_control
     // of it's parent. This rule applies recursively.
+    // A controller enabled in a cgroup is enabled in all its ancestors, so the walk stops at the
+    // first cgroup which already has it. This lets unprivileged users enable controllers in a
+    // subtree delegated to them without write access to the cgroups above it.
     fn write_all_subtree_control<P>(path: P, controller: &str) -> Result<(), JailerError>
     where
         P: AsRef<Path> + Debug,
@@ -389,6 +392,12 @@ impl CgroupV2 {
         if !cg_subtree_ctrl.exists() {
             return Ok(());
         }
+        if readln_special(&cg_subtree_ctrl)?
+            .split(' ')
+            .any(|enabled| enabled == controller)
+        {
+            return Ok(());
+        }
         let parent = match path.as_ref().parent() {
             Some(p) => p,
             None => {
@@ -834,6 +843,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cgroup_conf_v2_delegated_subtree() {
+        let mut mock_cgroups = MockCgroupFs::new().unwrap();
+        mock_cgroups.add_v2_mounts().unwrap();
+        let mut builder =
+            CgroupConfigurationBuilder::new(2, mock_cgroups.proc_mounts_path.to_str().unwrap())
+                .unwrap();
+        builder
+            .add_cgroup_property(
+                "cpuset.mems".to_string(),
+                "1".to_string(),
+                "101",
+                Path::new("fc_test_cgv2"),
+            )
+            .unwrap();
+        let cg_conf = builder.build();
+
+        let cg_root = mock_cgroups.sys_cgroups_path.join("unified");
+        fs::create_dir_all(cg_root.join("fc_test_cgv2/101")).unwrap();
+        MockCgroupFs::create_file_with_contents(
+            cg_root.join("cgroup.subtree_control"),
+            "cpuset memory\n",
+        )
+        .unwrap();
+        MockCgroupFs::create_file_with_contents(
+            cg_root.join("fc_test_cgv2/cgroup.subtree_control"),
+            "",
+        )
+        .unwrap();
+
+        cg_conf.setup().unwrap();
+
+        // The controller is only enabled below the first cgroup which already has it.
+        assert_eq!(
+            read_first_line(cg_root.join("cgroup.subtree_control")).unwrap(),
+            "cpuset memory\n"
+        );
+        assert_eq!(
+            read_first_line(cg_root.join("fc_test_cgv2/cgroup.subtree_control")).unwrap(),
+            "+cpuset\n"
+        );
+    }
+
     #[test]
     fn test_inherit_from_parent() {
         // 1. If parent file does not exist, return an error.
@@ -1,22 +1,260 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::env;
 use std::ffi::CStr;
-use std::path::Path;
-use std::ptr::null;
+use std::fs::{self, File, OpenOptions};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::ptr::{null, null_mut};
+use std::{env, io};
 
 use vmm_sys_util::syscall::SyscallReturnCode;
 
-use super::{JailerError, to_cstring};
+use super::{JailerError, to_cstring, writeln_special};
+use crate::env::clone;
 
 const OLD_ROOT_DIR: &CStr = c"old_root";
 const ROOT_DIR: &CStr = c"/";
 const CURRENT_DIR: &CStr = c".";
+const RUN_DIR: &CStr = c"run";
+
+// Mount flags which have to be kept when remounting a bind mount, as they may be locked by a
+// more privileged mount namespace. The ST_* flags returned by statvfs() have the same values as
+// the MS_* mount flags.
+const LOCKED_MOUNT_FLAGS: libc::c_ulong = libc::MS_NOSUID
+    | libc::MS_NODEV
+    | libc::MS_NOEXEC
+    | libc::MS_NOATIME
+    | libc::MS_NODIRATIME
+    | libc::MS_RELATIME;
+
+// Flags of the mount API missing from libc, from include/uapi/linux/fcntl.h and
+// include/uapi/linux/mount.h.
+const AT_RECURSIVE: libc::c_uint = 0x8000;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x0000_0004;
+
+// A child process kept in a new user namespace, which only holds the id mappings of an idmapped
+// mount. The namespace outlives the process as long as a file descriptor refers to it.
+struct IdmapProcess(libc::pid_t);
+
+impl IdmapProcess {
+    fn new() -> Result<Self, JailerError> {
+        let pid = clone(null_mut(), libc::CLONE_NEWUSER | libc::SIGCHLD)?;
+        if pid == 0 {
+            // The child only waits to be killed by its parent.
+            loop {
+                // SAFETY: Safe because pause() takes no parameters.
+                unsafe { libc::pause() };
+            }
+        }
+        Ok(Self(pid))
+    }
+
+    // Maps `host_uid:host_gid` in the user namespace of the process to `uid:gid` outside of it,
+    // and returns the namespace. Used by an idmapped mount, the namespace makes the files owned by
+    // `host_uid:host_gid` on the filesystem appear as owned by `uid:gid`.
+    fn user_ns(
+        &self,
+        (host_uid, host_gid): (u32, u32),
+        (uid, gid): (u32, u32),
+    ) -> Result<File, JailerError> {
+        let proc_dir = PathBuf::from(format!("/proc/{}", self.0));
+        writeln_special(&proc_dir.join("uid_map"), format!("{host_uid} {uid} 1"))?;
+        writeln_special(&proc_dir.join("gid_map"), format!("{host_gid} {gid} 1"))?;
+        let user_ns_path = proc_dir.join("ns/user");
+        File::open(&user_ns_path).map_err(|err| JailerError::Open(user_ns_path, err))
+    }
+}
+
+impl Drop for IdmapProcess {
+    fn drop(&mut self) {
+        // SAFETY: Safe because the pid is the one of a child of this process.
+        unsafe {
+            libc::kill(self.0, libc::SIGKILL);
+            libc::waitpid(self.0, null_mut(), 0);
+        }
+    }
+}
+
+// A file or directory from the host made visible inside the jail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindMount {
+    // Absolute path of the file on the host.
+    pub source: PathBuf,
+    // Path of the file relative to the jail root. It is created if it doesn't exist.
+    pub target: PathBuf,
+    pub read_only: bool,
+    // Owner of the file as seen inside the jail, if it differs from the owner on the host. The
+    // owner on the host is left unchanged.
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl BindMount {
+    // Creates the mount point, relative to the current directory.
+    fn create_target(&self) -> Result<(), JailerError> {
+        if self.source.is_dir() {
+            return fs::create_dir_all(&self.target)
+                .map_err(|err| JailerError::CreateDir(self.target.clone(), err));
+        }
+        if let Some(parent) = self.target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| JailerError::CreateDir(parent.to_owned(), err))?;
+        }
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .mode(0o600)
+            .open(&self.target)
+            .map(drop)
+            .map_err(|err| JailerError::FileOpen(self.target.clone(), err))
+    }
+
+    fn mount(&self) -> Result<(), JailerError> {
+        let source = to_cstring(&self.source)?;
+        let target = to_cstring(&self.target)?;
+        let mount_err = |err| JailerError::MountBindFile(self.source.clone(), err);
+
+        self.create_target()?;
+        if self.uid.is_some() || self.gid.is_some() {
+            self.mount_idmapped(&source, &target)?;
+        } else {
+            // SAFETY: Safe because we provide valid parameters.
+            SyscallReturnCode(unsafe {
+                libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    null(),
+                    libc::MS_BIND | libc::MS_REC,
+                    null(),
+                )
+            })
+            .into_empty_result()
+            .map_err(mount_err)?;
+        }
+
+        if self.read_only {
+            // The read-only flag of a bind mount can only be set by remounting it.
+            // SAFETY: An all-zero statvfs is a valid value for the structure.
+            let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+            // SAFETY: Safe because target is null-terminated and stat is a valid structure.
+            SyscallReturnCode(unsafe { libc::statvfs(target.as_ptr(), &mut stat) })
+                .into_empty_result()
+                .map_err(mount_err)?;
+            let flags = libc::MS_BIND
+                | libc::MS_REMOUNT
+                | libc::MS_RDONLY
+                | (stat.f_flag & LOCKED_MOUNT_FLAGS);
+            // SAFETY: Safe because we provide valid parameters.
+            SyscallReturnCode(unsafe {
+                libc::mount(null(), target.as_ptr(), null(), flags, null())
+            })
+            .into_empty_result()
+            .map_err(mount_err)?;
+        }
+        Ok(())
+    }
+
+    // Bind mounts the source through an idmapped mount, in which the owner of the source on the
+    // host is seen as `uid:gid`. The other owners are not mapped, so the files they own inside a
+    // mounted directory are seen as owned by the overflow ids.
+    fn mount_idmapped(&self, source: &CStr, target: &CStr) -> Result<(), JailerError> {
+        let mount_err = |err| JailerError::MountBindFile(self.source.clone(), err);
+
+        let metadata = fs::metadata(&self.source)
+            .map_err(|err| JailerError::Metadata(self.source.clone(), err))?;
+        let host_owner = (metadata.uid(), metadata.gid());
+        let owner = (
+            self.uid.unwrap_or(host_owner.0),
+            self.gid.unwrap_or(host_owner.1),
+        );
+        let user_ns = IdmapProcess::new()?.user_ns(host_owner, owner)?;
+
+        // SAFETY: Safe because source is a null-terminated string.
+        let tree = SyscallReturnCode(unsafe {
+            libc::syscall(
+                libc::SYS_open_tree,
+                libc::AT_FDCWD,
+                source.as_ptr(),
+                libc::OPEN_TREE_CLONE | libc::OPEN_TREE_CLOEXEC | AT_RECURSIVE,
+            )
+        })
+        .into_result()
+        .map_err(mount_err)?;
+        // SAFETY: Safe because open_tree() returned a new file descriptor, owned from now on.
+        let tree = unsafe { OwnedFd::from_raw_fd(i32::try_from(tree).unwrap()) };
+
+        let attr = libc::mount_attr {
+            attr_set: libc::MOUNT_ATTR_IDMAP,
+            attr_clr: 0,
+            propagation: 0,
+            userns_fd: u64::try_from(user_ns.as_raw_fd()).unwrap(),
+        };
+        // SAFETY: Safe because the path is null-terminated and attr is a valid structure.
+        SyscallReturnCode(unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                tree.as_raw_fd(),
+                c"".as_ptr(),
+                libc::AT_EMPTY_PATH.cast_unsigned() | AT_RECURSIVE,
+                &attr as *const libc::mount_attr,
+                size_of::<libc::mount_attr>(),
+            )
+        })
+        .into_empty_result()
+        .map_err(mount_err)?;
+
+        // SAFETY: Safe because both paths are null-terminated strings.
+        SyscallReturnCode(unsafe {
+            libc::syscall(
+                libc::SYS_move_mount,
+                tree.as_raw_fd(),
+                c"".as_ptr(),
+                libc::AT_FDCWD,
+                target.as_ptr(),
+                MOVE_MOUNT_F_EMPTY_PATH,
+            )
+        })
+        .into_empty_result()
+        .map_err(mount_err)
+    }
+}
+
+// Mounts a private tmpfs on /run, relative to the current directory, owned by uid:gid.
+fn mount_run_tmpfs(uid: u32, gid: u32) -> Result<(), JailerError> {
+    match fs::create_dir(RUN_DIR.to_str().unwrap()) {
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => {
+            return Err(JailerError::CreateDir(PathBuf::from("run"), err));
+        }
+        _ => (),
+    }
+
+    let options = to_cstring(format!("mode=700,uid={uid},gid={gid}"))?;
+    // SAFETY: Safe because we provide valid parameters.
+    SyscallReturnCode(unsafe {
+        libc::mount(
+            c"tmpfs".as_ptr(),
+            RUN_DIR.as_ptr(),
+            c"tmpfs".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            options.as_ptr().cast(),
+        )
+    })
+    .into_empty_result()
+    .map_err(JailerError::MountTmpfs)
+}
 
 // This uses switching to a new mount namespace + pivot_root(), together with the regular chroot,
 // to provide a hardened jail (at least compared to only relying on chroot).
-pub fn chroot(path: &Path) -> Result<(), JailerError> {
+// The tmpfs on /run, if `run_tmpfs_owner` is set, and the `bind_mounts` are set up before
+// pivoting root, while the host files are still reachable.
+pub fn chroot(
+    path: &Path,
+    bind_mounts: &[BindMount],
+    run_tmpfs_owner: Option<(u32, u32)>,
+) -> Result<(), JailerError> {
     // We unshare into a new mount namespace.
     // SAFETY: The call is safe because we're invoking a C library
     // function with valid parameters.
@@ -61,6 +299,12 @@ pub fn chroot(path: &Path) -> Result<(), JailerError> {
     // Change current dir to the chroot dir, so we only need to handle relative paths from now on.
     env::set_current_dir(path).map_err(JailerError::SetCurrentDir)?;
 
+    // The tmpfs is mounted first, so that files can be bind mounted under /run.
+    if let Some((uid, gid)) = run_tmpfs_owner {
+        mount_run_tmpfs(uid, gid)?;
+    }
+    bind_mounts.iter().try_for_each(BindMount::mount)?;
+
     // Create the old_root folder we're going to use for pivot_root, using a relative path.
     // SAFETY: The call is safe because we provide valid arguments.
     SyscallReturnCode(unsafe { libc::mkdir(OLD_ROOT_DIR.as_ptr(), libc::S_IRUSR | libc::S_IWUSR) })
@@ -16,10 +16,10 @@ use utils::time::{ClockType, get_time_us};
 use utils::{arg_parser, validators};
 use vmm_sys_util::syscall::SyscallReturnCode;
 
-use crate::JailerError;
 use crate::cgroup::{CgroupConfiguration, CgroupConfigurationBuilder};
-use crate::chroot::chroot;
+use crate::chroot::{BindMount, chroot};
 use crate::resource_limits::{FSIZE_ARG, NO_FILE_ARG, ResourceLimits};
+use crate::{JailerError, writeln_special};
 
 pub const PROC_MOUNTS: &str = "/proc/mounts";
 
@@ -82,7 +82,10 @@ fn dup2(old_fd: libc::c_int, new_fd: libc::c_int) -> Result<(), JailerError> {
 // not use the CLONE_VM flag, this will result with the original stack replicated, in a similar
 // manner to the fork syscall. The libc wrapper prevents use of a NULL stack pointer, so we will
 // call the syscall directly.
-fn clone(child_stack: *mut libc::c_void, flags: libc::c_int) -> Result<libc::c_int, JailerError> {
+pub(crate) fn clone(
+    child_stack: *mut libc::c_void,
+    flags: libc::c_int,
+) -> Result<libc::c_int, JailerError> {
     SyscallReturnCode(
         // SAFETY: This is safe because we are using a library function with valid parameters.
         libc::c_int::try_from(unsafe {
@@ -124,6 +127,9 @@ pub struct Env {
     netns: Option<String>,
     daemonize: bool,
     new_pid_ns: bool,
+    user_ns: bool,
+    bind_mounts: Vec<BindMount>,
+    tmpfs_run: bool,
     start_time_us: u64,
     start_time_cpu_us: u64,
     jailer_cpu_time_us: u64,
@@ -187,6 +193,13 @@ impl Env {
 
         let new_pid_ns = arguments.flag_present("new-pid-ns");
 
+        let user_ns = arguments.flag_present("user-ns");
+        // A process running as root in the user namespace keeps all the capabilities of the
+        // namespace after exec.
+        if user_ns && uid == 0 {
+            return Err(JailerError::UserNsRootUid);
+        }
+
         // Optional arguments.
         let mut cgroup_conf = None;
         let parent_cgroup = match arguments.single_value("parent-cgroup") {
@@ -208,6 +221,10 @@ impl Env {
             .map_err(|_| JailerError::CgroupInvalidVersion(cgroup_ver.to_string()))?;
 
         let cgroups_args: &[String] = arguments.multiple_values("cgroup").unwrap_or_default();
+        // Only cgroup v2 supports delegating a subtree to an unprivileged user.
+        if user_ns && cgroup_ver != 2 && !cgroups_args.is_empty() {
+            return Err(JailerError::UserNsCgroupV1);
+        }
 
         // If the --parent-cgroup exists, and we have no other cgroups,
         // then the intent is to move the process to that cgroup.
@@ -252,6 +269,24 @@ impl Env {
             Env::parse_resource_limits(&mut resource_limits, args)?;
         }
 
+        let bind_mounts = arguments
+            .multiple_values("bind-mount")
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| Env::parse_bind_mount(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Idmapped mounts can only be created with the privileges of the user namespace owning
+        // the filesystem.
+        if user_ns
+            && let Some(bind_mount) = bind_mounts
+                .iter()
+                .find(|bind_mount| bind_mount.uid.is_some() || bind_mount.gid.is_some())
+        {
+            return Err(JailerError::MountBindOwnerUserNs(bind_mount.source.clone()));
+        }
+
+        let tmpfs_run = arguments.flag_present("tmpfs-run");
+
         let uffd_dev_minor = Self::get_userfaultfd_minor_dev_number().ok();
 
         Ok(Env {
@@ -263,6 +298,9 @@ impl Env {
             netns,
             daemonize,
             new_pid_ns,
+            user_ns,
+            bind_mounts,
+            tmpfs_run,
             start_time_us,
             start_time_cpu_us,
             jailer_cpu_time_us: 0,
@@ -325,6 +363,58 @@ impl Env {
         Ok(())
     }
 
+    fn parse_bind_mount(arg: &str) -> Result<BindMount, JailerError> {
+        let format_err = || JailerError::MountBindFormat(arg.to_string());
+        // bind mount format: <host_path>:<jail_path>[:<option>,...]
+        let mut parts = arg.splitn(3, ':');
+        let (Some(host_path), Some(jail_path)) = (parts.next(), parts.next()) else {
+            return Err(format_err());
+        };
+
+        let source = canonicalize(host_path)
+            .map_err(|err| JailerError::Canonicalize(PathBuf::from(host_path), err))?;
+        // The mount point must be strictly inside the jail.
+        let target = Path::new(jail_path)
+            .strip_prefix("/")
+            .map_err(|_| format_err())?
+            .to_path_buf();
+        if target.as_os_str().is_empty()
+            || target
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(format_err());
+        }
+
+        let mut bind_mount = BindMount {
+            source,
+            target,
+            read_only: true,
+            uid: None,
+            gid: None,
+        };
+        for option in parts.next().into_iter().flat_map(|opts| opts.split(',')) {
+            match option.split_once('=') {
+                None if option == "ro" => bind_mount.read_only = true,
+                None if option == "rw" => bind_mount.read_only = false,
+                Some(("uid", uid)) => {
+                    bind_mount.uid = Some(
+                        uid.parse::<u32>()
+                            .map_err(|_| JailerError::Uid(uid.to_owned()))?,
+                    );
+                }
+                Some(("gid", gid)) => {
+                    bind_mount.gid = Some(
+                        gid.parse::<u32>()
+                            .map_err(|_| JailerError::Gid(gid.to_owned()))?,
+                    );
+                }
+                _ => return Err(format_err()),
+            }
+        }
+        Ok(bind_mount)
+    }
+
     fn exec_into_new_pid_ns(&mut self, chroot_exec_file: PathBuf) -> Result<(), JailerError> {
         // https://man7.org/linux/man-pages/man7/pid_namespaces.7.html
         // > a process in an ancestor namespace can send signals to the "init" process of a child
@@ -451,6 +541,92 @@ impl Env {
             })
     }
 
+    fn mknod_and_own_devs(&self) -> Result<(), JailerError> {
+        // Here we are creating the /dev/kvm and /dev/net/tun devices inside the jailer.
+        // Following commands can be translated into bash like this:
+        // $: mkdir -p $chroot_dir/dev/net
+        // $: dev_net_tun_path={$chroot_dir}/"tun"
+        // $: mknod $dev_net_tun_path c 10 200
+        // www.kernel.org/doc/Documentation/networking/tuntap.txt specifies 10 and 200 as the major
+        // and minor for the /dev/net/tun device.
+        self.mknod_and_own_dev(DEV_NET_TUN, DEV_NET_TUN_MAJOR, DEV_NET_TUN_MINOR)?;
+        // Do the same for /dev/kvm with (major, minor) = (10, 232).
+        self.mknod_and_own_dev(DEV_KVM, DEV_KVM_MAJOR, DEV_KVM_MINOR)?;
+        // And for /dev/urandom with (major, minor) = (1, 9).
+        // If the device is not accessible on the host, output a warning to inform user that MMDS
+        // version 2 will not be available to use.
+        let _ = self
+            .mknod_and_own_dev(DEV_URANDOM, DEV_URANDOM_MAJOR, DEV_URANDOM_MINOR)
+            .map_err(|err| {
+                println!(
+                    "Warning! Could not create /dev/urandom device inside jailer: {}.",
+                    err
+                );
+                println!("MMDS version 2 will not be available to use.");
+            });
+
+        // If we have a minor version for /dev/userfaultfd the device is present on the host.
+        // Expose the device in the jailed environment.
+        if let Some(minor) = self.uffd_dev_minor {
+            self.mknod_and_own_dev(DEV_UFFD_PATH, DEV_UFFD_MAJOR, minor)?;
+        }
+        Ok(())
+    }
+
+    // Device nodes cannot be created from a user namespace, so the host devices are bind mounted
+    // over empty files of the jail instead. Their permissions on the host still apply.
+    fn dev_bind_mounts(&self) -> Vec<BindMount> {
+        let mut devices = vec![DEV_NET_TUN, DEV_KVM];
+        if Path::new(DEV_URANDOM.to_str().unwrap()).exists() {
+            devices.push(DEV_URANDOM);
+        } else {
+            println!("Warning! Could not find /dev/urandom on the host.");
+            println!("MMDS version 2 will not be available to use.");
+        }
+        if self.uffd_dev_minor.is_some() {
+            devices.push(DEV_UFFD_PATH);
+        }
+
+        devices
+            .into_iter()
+            .map(|dev_path| {
+                // Safe to unwrap as the device paths are valid UTF-8 absolute paths.
+                let source = PathBuf::from(dev_path.to_str().unwrap());
+                let target = source.strip_prefix("/").unwrap().to_path_buf();
+                BindMount {
+                    source,
+                    target,
+                    read_only: false,
+                    uid: None,
+                    gid: None,
+                }
+            })
+            .collect()
+    }
+
+    // Moves the jailer into a new user namespace, where it has the privileges required to build
+    // the jail. The uid and gid of the caller are mapped to the ones Firecracker runs with.
+    fn enter_user_ns(&self) -> Result<(), JailerError> {
+        // SAFETY: Safe because these functions cannot fail.
+        let (host_uid, host_gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+
+        // SAFETY: Safe because we are passing valid parameters.
+        SyscallReturnCode(unsafe { libc::unshare(libc::CLONE_NEWUSER) })
+            .into_empty_result()
+            .map_err(JailerError::UnshareUserNs)?;
+
+        // Unprivileged processes have to give up setgroups() before writing their gid map.
+        writeln_special(&"/proc/self/setgroups", "deny")?;
+        writeln_special(
+            &"/proc/self/uid_map",
+            format!("{} {} 1", self.uid, host_uid),
+        )?;
+        writeln_special(
+            &"/proc/self/gid_map",
+            format!("{} {} 1", self.gid, host_gid),
+        )
+    }
+
     fn setup_jailed_folder(&self, folder: impl AsRef<Path>) -> Result<(), JailerError> {
         let folder_path = folder.as_ref();
         fs::create_dir_all(folder_path)
@@ -551,7 +727,7 @@ impl Env {
 
     #[cfg(target_arch = "aarch64")]
     fn copy_cache_info(&self) -> Result<(), JailerError> {
-        use crate::{readln_special, to_cstring, writeln_special};
+        use crate::{readln_special, to_cstring};
 
         const HOST_CACHE_INFO: &str = "/sys/devices/system/cpu/cpu0/cache";
         // Based on https://elixir.free-electrons.com/linux/v4.9.62/source/arch/arm64/kernel/cacheinfo.c#L29.
@@ -615,7 +791,7 @@ impl Env {
 
     #[cfg(target_arch = "aarch64")]
     fn copy_midr_el1_info(&self) -> Result<(), JailerError> {
-        use crate::{readln_special, to_cstring, writeln_special};
+        use crate::{readln_special, to_cstring};
 
         const HOST_MIDR_EL1_INFO: &str = "/sys/devices/system/cpu/cpu0/regs/identification";
 
@@ -642,14 +818,21 @@ impl Env {
     }
 
     pub fn run(mut self) -> Result<(), JailerError> {
-        let exec_file_name = self.copy_exec_to_chroot()?;
-        let chroot_exec_file = PathBuf::from("/").join(exec_file_name);
-
-        // Join the specified network namespace, if applicable.
+        // Join the specified network namespace, if applicable. This is done before entering the
+        // user namespace, which does not own it.
         if let Some(ref path) = self.netns {
             Env::join_netns(path)?;
         }
 
+        // Every following step runs in the user namespace, if applicable, so it sees the mapped
+        // uid and gid.
+        if self.user_ns {
+            self.enter_user_ns()?;
+        }
+
+        let exec_file_name = self.copy_exec_to_chroot()?;
+        let chroot_exec_file = PathBuf::from("/").join(exec_file_name);
+
         // Set limits on resources.
         self.resource_limits.install()?;
 
@@ -672,8 +855,16 @@ impl Env {
         #[cfg(target_arch = "aarch64")]
         self.copy_midr_el1_info()?;
 
+        let mut bind_mounts = if self.user_ns {
+            self.dev_bind_mounts()
+        } else {
+            Vec::new()
+        };
+        bind_mounts.append(&mut self.bind_mounts);
+        let run_tmpfs_owner = self.tmpfs_run.then_some((self.uid, self.gid));
+
         // Jail self.
-        chroot(self.chroot_dir())?;
+        chroot(self.chroot_dir(), &bind_mounts, run_tmpfs_owner)?;
 
         // This will not only create necessary directories, but will also change ownership
         // for all of them.
@@ -681,33 +872,9 @@ impl Env {
             .iter()
             .try_for_each(|f| self.setup_jailed_folder(f))?;
 
-        // Here we are creating the /dev/kvm and /dev/net/tun devices inside the jailer.
-        // Following commands can be translated into bash like this:
-        // $: mkdir -p $chroot_dir/dev/net
-        // $: dev_net_tun_path={$chroot_dir}/"tun"
-        // $: mknod $dev_net_tun_path c 10 200
-        // www.kernel.org/doc/Documentation/networking/tuntap.txt specifies 10 and 200 as the major
-        // and minor for the /dev/net/tun device.
-        self.mknod_and_own_dev(DEV_NET_TUN, DEV_NET_TUN_MAJOR, DEV_NET_TUN_MINOR)?;
-        // Do the same for /dev/kvm with (major, minor) = (10, 232).
-        self.mknod_and_own_dev(DEV_KVM, DEV_KVM_MAJOR, DEV_KVM_MINOR)?;
-        // And for /dev/urandom with (major, minor) = (1, 9).
-        // If the device is not accessible on the host, output a warning to inform user that MMDS
-        // version 2 will not be available to use.
-        let _ = self
-            .mknod_and_own_dev(DEV_URANDOM, DEV_URANDOM_MAJOR, DEV_URANDOM_MINOR)
-            .map_err(|err| {
-                println!(
-                    "Warning! Could not create /dev/urandom device inside jailer: {}.",
-                    err
-                );
-                println!("MMDS version 2 will not be available to use.");
-            });
-
-        // If we have a minor version for /dev/userfaultfd the device is present on the host.
-        // Expose the device in the jailed environment.
-        if let Some(minor) = self.uffd_dev_minor {
-            self.mknod_and_own_dev(DEV_UFFD_PATH, DEV_UFFD_MAJOR, minor)?;
+        // The devices were bind mounted from the host when running in a user namespace.
+        if !self.user_ns {
+            self.mknod_and_own_devs()?;
         }
 
         self.jailer_cpu_time_us = get_time_us(ClockType::ProcessCpu) - self.start_time_cpu_us;
@@ -807,6 +974,7 @@ mod tests {
         pub netns: Option<&'a str>,
         pub daemonize: bool,
         pub new_pid_ns: bool,
+        pub user_ns: bool,
         pub cgroups: Vec<&'a str>,
         pub resource_limits: Vec<&'a str>,
         pub parent_cgroup: Option<&'a str>,
@@ -826,6 +994,7 @@ mod tests {
                 netns: Some("zzzns"),
                 daemonize: true,
                 new_pid_ns: true,
+                user_ns: false,
                 cgroups: vec!["cpu.shares=2", "cpuset.mems=0"],
                 resource_limits: vec!["no-file=1024", "fsize=1048575"],
                 parent_cgroup: None,
@@ -876,6 +1045,10 @@ mod tests {
             arg_vec.push("--new-pid-ns".to_string());
         }
 
+        if arg_vals.user_ns {
+            arg_vec.push("--user-ns".to_string());
+        }
+
         if let Some(parent_cg) = arg_vals.parent_cgroup {
             arg_vec.push("--parent-cgroup".to_string());
             arg_vec.push(parent_cg.to_string());
@@ -1032,6 +1205,60 @@ mod tests {
         args.parse(&make_args(&invalid_format)).unwrap();
         Env::new(&args, 0, 0, mock_cgroups.proc_mounts_path.to_str().unwrap()).unwrap_err();
 
+        let user_ns_arg_vals = ArgVals {
+            user_ns: true,
+            cgroups: vec![],
+            ..another_good_arg_vals.clone()
+        };
+        let arg_parser = build_arg_parser();
+        args = arg_parser.arguments().clone();
+        args.parse(&make_args(&user_ns_arg_vals)).unwrap();
+        let user_ns_env = Env::new(&args, 0, 0, mock_cgroups.proc_mounts_path.to_str().unwrap())
+            .expect("This user namespace environment should be created successfully.");
+        assert!(user_ns_env.user_ns);
+
+        // Firecracker must not run as root in the user namespace.
+        let user_ns_root_arg_vals = ArgVals {
+            uid: "0",
+            ..user_ns_arg_vals.clone()
+        };
+        let arg_parser = build_arg_parser();
+        args = arg_parser.arguments().clone();
+        args.parse(&make_args(&user_ns_root_arg_vals)).unwrap();
+        assert!(matches!(
+            Env::new(&args, 0, 0, mock_cgroups.proc_mounts_path.to_str().unwrap()),
+            Err(JailerError::UserNsRootUid)
+        ));
+
+        // Cgroups can only be set with cgroup v2 in a user namespace.
+        let user_ns_cgroup_v1_arg_vals = ArgVals {
+            cgroups: vec!["cpu.shares=2"],
+            ..user_ns_arg_vals.clone()
+        };
+        let arg_parser = build_arg_parser();
+        args = arg_parser.arguments().clone();
+        args.parse(&make_args(&user_ns_cgroup_v1_arg_vals)).unwrap();
+        assert!(matches!(
+            Env::new(&args, 0, 0, mock_cgroups.proc_mounts_path.to_str().unwrap()),
+            Err(JailerError::UserNsCgroupV1)
+        ));
+
+        // Bind mounts cannot change the owner of their files in a user namespace.
+        let host_file = TempFile::new().unwrap();
+        let mut user_ns_args = make_args(&user_ns_arg_vals);
+        user_ns_args.push("--bind-mount".to_string());
+        user_ns_args.push(format!(
+            "{}:/rootfs:uid=123",
+            host_file.as_path().to_str().unwrap()
+        ));
+        let arg_parser = build_arg_parser();
+        args = arg_parser.arguments().clone();
+        args.parse(&user_ns_args).unwrap();
+        assert!(matches!(
+            Env::new(&args, 0, 0, mock_cgroups.proc_mounts_path.to_str().unwrap()),
+            Err(JailerError::MountBindOwnerUserNs(_))
+        ));
+
         // The chroot-base-dir param is not validated by Env::new, but rather in run, when we
         // actually attempt to create the folder structure (the same goes for netns).
     }
@@ -1190,6 +1417,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dev_bind_mounts() {
+        let mut mock_cgroups = MockCgroupFs::new().unwrap();
+        mock_cgroups.add_v1_mounts().unwrap();
+        let env = create_env(&mock_cgroups.proc_mounts_path);
+
+        let bind_mounts = env.dev_bind_mounts();
+        for bind_mount in &bind_mounts {
+            assert!(bind_mount.target.is_relative());
+            assert_eq!(Path::new("/").join(&bind_mount.target), bind_mount.source);
+            assert!(!bind_mount.read_only);
+        }
+        let sources: Vec<_> = bind_mounts.iter().map(|m| m.source.as_path()).collect();
+        assert!(sources.contains(&Path::new("/dev/kvm")));
+        assert!(sources.contains(&Path::new("/dev/net/tun")));
+        assert_eq!(
+            sources.contains(&Path::new("/dev/userfaultfd")),
+            env.uffd_dev_minor.is_some()
+        );
+    }
+
+    #[test]
+    fn test_parse_bind_mount() {
+        let host_file = TempFile::new().unwrap();
+        let host_path = host_file.as_path().to_str().unwrap();
+
+        let bind_mount = Env::parse_bind_mount(&format!("{host_path}:/images/rootfs")).unwrap();
+        assert_eq!(
+            bind_mount,
+            BindMount {
+                source: host_file.as_path().to_owned(),
+                target: PathBuf::from("images/rootfs"),
+                read_only: true,
+                uid: None,
+                gid: None,
+            }
+        );
+
+        let bind_mount = Env::parse_bind_mount(&format!("{host_path}:/rootfs:ro,rw")).unwrap();
+        assert!(!bind_mount.read_only);
+
+        let bind_mount =
+            Env::parse_bind_mount(&format!("{host_path}:/rootfs:rw,uid=123,gid=456")).unwrap();
+        assert!(!bind_mount.read_only);
+        assert_eq!(bind_mount.uid, Some(123));
+        assert_eq!(bind_mount.gid, Some(456));
+
+        // Either id of the owner can be left unchanged.
+        let bind_mount = Env::parse_bind_mount(&format!("{host_path}:/rootfs:gid=456")).unwrap();
+        assert!(bind_mount.read_only);
+        assert_eq!(bind_mount.uid, None);
+        assert_eq!(bind_mount.gid, Some(456));
+
+        assert!(matches!(
+            Env::parse_bind_mount(&format!("{host_path}:/rootfs:uid=foo")),
+            Err(JailerError::Uid(_))
+        ));
+        assert!(matches!(
+            Env::parse_bind_mount(&format!("{host_path}:/rootfs:gid=-1")),
+            Err(JailerError::Gid(_))
+        ));
+
+        // Error cases.
+        for arg in [
+            host_path.to_string(),
+            format!("{host_path}:rootfs"),
+            format!("{host_path}:/"),
+            format!("{host_path}:/../rootfs"),
+            format!("{host_path}:/rootfs:rx"),
+            format!("{host_path}:/rootfs:owner=foo"),
+            "/this!/file!/should!/not!/exist!:/rootfs".to_string(),
+        ] {
+            Env::parse_bind_mount(&arg).unwrap_err();
+        }
+    }
+
     #[test]
     fn test_userfaultfd_dev() {
         let mut mock_cgroups = MockCgroupFs::new().unwrap();
@@ -1229,6 +1532,7 @@ mod tests {
             netns: Some("zzzns"),
             daemonize: false,
             new_pid_ns: false,
+            user_ns: false,
             cgroups: Vec::new(),
             resource_limits: Vec::new(),
             parent_cgroup: None,
@@ -107,6 +107,14 @@ pub enum JailerError {
     MknodDev(io::Error, String),
     #[error("Failed to bind mount the jail root directory: {0}")]
     MountBind(io::Error),
+    #[error("Failed to bind mount {} inside the jail: {}", .0.display(), .1)]
+    MountBindFile(PathBuf, io::Error),
+    #[error("Invalid bind mount: {0}")]
+    MountBindFormat(String),
+    #[error("Bind mounts cannot change the owner of {} in a user namespace", .0.display())]
+    MountBindOwnerUserNs(PathBuf),
+    #[error("Failed to mount a tmpfs on /run inside the jail: {0}")]
+    MountTmpfs(io::Error),
     #[error("Failed to change the propagation type to slave: {0}")]
     MountPropagationSlave(io::Error),
     #[error("{}", format!("{:?} is not a file", .0).replace('\"', ""))]
@@ -149,8 +157,14 @@ pub enum JailerError {
     UnexpectedListenerFd(i32),
     #[error("Failed to unshare into new mount namespace: {0}")]
     UnshareNewNs(io::Error),
+    #[error("Failed to unshare into new user namespace: {0}")]
+    UnshareUserNs(io::Error),
     #[error("Failed to unset the O_CLOEXEC flag on the socket fd: {0}")]
     UnsetCloexec(io::Error),
+    #[error("The uid must not be 0 when running in a user namespace")]
+    UserNsRootUid,
+    #[error("Cgroups can only be set from a user namespace with cgroup v2")]
+    UserNsCgroupV1,
     #[error("Slice contains invalid UTF-8 data : {0}")]
     UTF8Parsing(std::str::Utf8Error),
     #[error("{}", format!("Failed to write to {:?}: {}", .0, .1).replace('\"', ""))]
@@ -205,6 +219,21 @@ pub fn build_arg_parser() -> ArgParser<'static> {
                 .takes_value(false)
                 .help("Exec into a new PID namespace."),
         )
+        .arg(Argument::new("bind-mount").allow_multiple(true).help(
+            "Host path to bind mount inside the jail. It must follow this format: \
+             <host_path>:<jail_path>[:<option>,...] (e.g /srv/rootfs.ext4:/rootfs.ext4:rw). The \
+             options are ro (the default) or rw, uid=<uid> and gid=<gid>, which set the owner of \
+             the path inside the jail through an idmapped mount, leaving the host path unchanged. \
+             This argument can be used multiple times to add multiple mounts.",
+        ))
+        .arg(Argument::new("tmpfs-run").takes_value(false).help(
+            "Mount a private tmpfs on /run inside the jail. Its content, including the default \
+             API socket, is not visible from the host under the jail root directory.",
+        ))
+        .arg(Argument::new("user-ns").takes_value(false).help(
+            "Run without root privileges, in a new user namespace where the uid and gid are \
+             mapped to those of the caller.",
+        ))
         .arg(Argument::new("cgroup").allow_multiple(true).help(
             "Cgroup and value to be set by the jailer. It must follow this format: \
              <cgroup_file>=<value> (e.g cpu.shares=10). This argument can be used multiple times \
@@ -0,0 +1,447 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Learning of seccomp filters from the audit log.
+//!
+//! Filters compiled in audit mode log every syscall with `SECCOMP_RET_LOG`. The kernel then emits
+//! a `SECCOMP` audit record per syscall, holding the pid, the thread name, the architecture and
+//! the syscall number, but not the syscall arguments. This module collects the syscalls used by
+//! each thread category from these records and generates a minimal filter from them, or compares
+//! them against an existing filter.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io::BufRead;
+
+use crate::bindings::seccomp_syscall_resolve_num_arch;
+use crate::types::{BpfJson, Filter, SeccompAction, SyscallRule};
+
+/// Syscall names observed for each thread category.
+pub type ObservedSyscalls = BTreeMap<String, BTreeSet<String>>;
+
+/// Audit log processing errors.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum AuditError {
+    /// Cannot read the audit log: {0}
+    LogRead(std::io::Error),
+    /// Malformed seccomp audit record: {0}
+    MalformedRecord(String),
+    /// Cannot resolve syscall number {1} for architecture {0:#x}
+    UnknownSyscall(u32, i32),
+}
+
+/// Returns the thread category of a Firecracker thread from its name.
+fn thread_category(comm: &str) -> &'static str {
+    if comm == "fc_api" {
+        "api"
+    } else if comm.starts_with("fc_vcpu") {
+        "vcpu"
+    } else {
+        "vmm"
+    }
+}
+
+/// Returns the value of `key` in an audit record.
+///
+/// The kernel quotes the thread name, unless it contains spaces or special characters, in which
+/// case it is hex encoded instead.
+fn record_value(record: &str, key: &str) -> Option<String> {
+    let pattern = format!(" {key}=");
+    let value = &record[record.find(&pattern)? + pattern.len()..];
+
+    if let Some(quoted) = value.strip_prefix('"') {
+        return quoted.split('"').next().map(str::to_string);
+    }
+    let value = value.split(' ').next().unwrap_or_default();
+    if key != "comm" {
+        return Some(value.to_string());
+    }
+    let bytes = (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Returns the name of syscall `nr` on architecture `arch`.
+fn syscall_name(arch: u32, nr: i32) -> Result<String, AuditError> {
+    // SAFETY: Safe because the parameters are valid.
+    let name = unsafe { seccomp_syscall_resolve_num_arch(arch, nr) };
+    if name.is_null() {
+        return Err(AuditError::UnknownSyscall(arch, nr));
+    }
+    // SAFETY: libseccomp returns a valid nul-terminated string, which is owned by the caller.
+    let resolved = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    // SAFETY: The string was allocated with `malloc()` and is not used afterwards.
+    unsafe { libc::free(name.cast()) };
+    Ok(resolved)
+}
+
+/// Collects the syscalls logged by seccomp from an audit log, or from the kernel log.
+///
+/// If `pid` is given, only the records of this process are taken into account.
+pub fn parse_audit_log<R: BufRead>(
+    reader: R,
+    pid: Option<u32>,
+) -> Result<ObservedSyscalls, AuditError> {
+    let mut observed = ObservedSyscalls::new();
+    for line in reader.lines() {
+        let line = line.map_err(AuditError::LogRead)?;
+        if !line.contains("type=SECCOMP") && !line.contains("type=1326") {
+            continue;
+        }
+        let malformed = || AuditError::MalformedRecord(line.clone());
+
+        let record_pid = record_value(&line, "pid")
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .ok_or_else(malformed)?;
+        if pid.is_some_and(|pid| pid != record_pid) {
+            continue;
+        }
+        let comm = record_value(&line, "comm").ok_or_else(malformed)?;
+        let arch = record_value(&line, "arch")
+            .and_then(|arch| u32::from_str_radix(&arch, 16).ok())
+            .ok_or_else(malformed)?;
+        let nr = record_value(&line, "syscall")
+            .and_then(|nr| nr.parse::<i32>().ok())
+            .ok_or_else(malformed)?;
+
+        observed
+            .entry(thread_category(&comm).to_string())
+            .or_default()
+            .insert(syscall_name(arch, nr)?);
+    }
+    Ok(observed)
+}
+
+/// Generates a filter allowing exactly the observed syscalls.
+///
+/// Syscalls are allowed irrespective of their arguments, unless `base` has rules for them, in
+/// which case these rules, with their argument conditions, are kept. The default and filter
+/// actions are also taken from `base`, if it has a filter for the thread category.
+pub fn generate_filter(observed: &ObservedSyscalls, base: Option<&BpfJson>) -> BpfJson {
+    let filters = observed
+        .iter()
+        .map(|(category, syscalls)| {
+            let base_filter = base.and_then(|base| base.0.get(category));
+            let filter = syscalls
+                .iter()
+                .flat_map(|syscall| {
+                    let base_rules = base_filter
+                        .map(|filter| {
+                            filter
+                                .filter
+                                .iter()
+                                .filter(|rule| rule.syscall.to_bytes() == syscall.as_bytes())
+                                .cloned()
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    if base_rules.is_empty() {
+                        vec![SyscallRule {
+                            syscall: CString::new(syscall.as_str()).unwrap(),
+                            comment: None,
+                            args: None,
+                        }]
+                    } else {
+                        base_rules
+                    }
+                })
+                .collect();
+            let filter = Filter {
+                default_action: base_filter
+                    .map_or(SeccompAction::Trap, |filter| filter.default_action.clone()),
+                filter_action: base_filter
+                    .map_or(SeccompAction::Allow, |filter| filter.filter_action.clone()),
+                filter,
+            };
+            (category.clone(), filter)
+        })
+        .collect();
+    BpfJson(filters)
+}
+
+/// Differences between the observed syscalls and the syscalls allowed by a filter.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FilterDiff {
+    /// Syscalls observed but missing from the filter, per thread category.
+    pub missing: ObservedSyscalls,
+    /// Syscalls in the filter which were not observed, per thread category.
+    pub unused: ObservedSyscalls,
+}
+
+impl FilterDiff {
+    /// Compares the observed syscalls with the syscalls which have rules in `filters`.
+    pub fn new(observed: &ObservedSyscalls, filters: &BpfJson) -> Self {
+        let mut diff = FilterDiff::default();
+        let categories = observed
+            .keys()
+            .chain(filters.0.keys())
+            .collect::<BTreeSet<_>>();
+        for category in categories {
+            let seen = observed.get(category).cloned().unwrap_or_default();
+            let allowed = filters
+                .0
+                .get(category)
+                .map(|filter| {
+                    filter
+                        .filter
+                        .iter()
+                        .map(|rule| rule.syscall.to_string_lossy().into_owned())
+                        .collect::<BTreeSet<_>>()
+                })
+                .unwrap_or_default();
+
+            let missing = seen.difference(&allowed).cloned().collect::<BTreeSet<_>>();
+            if !missing.is_empty() {
+                diff.missing.insert(category.clone(), missing);
+            }
+            let unused = allowed.difference(&seen).cloned().collect::<BTreeSet<_>>();
+            if !unused.is_empty() {
+                diff.unused.insert(category.clone(), unused);
+            }
+        }
+        diff
+    }
+
+    /// Returns whether the observed syscalls match the filter exactly.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unused.is_empty()
+    }
+}
+
+impl fmt::Display for FilterDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let categories = self
+            .missing
+            .keys()
+            .chain(self.unused.keys())
+            .collect::<BTreeSet<_>>();
+        for category in categories {
+            writeln!(f, "{category}:")?;
+            for syscall in self.missing.get(category).into_iter().flatten() {
+                writeln!(f, "+ {syscall}")?;
+            }
+            for syscall in self.unused.get(category).into_iter().flatten() {
+                writeln!(f, "- {syscall}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x86_64 records, as written to the audit log and to the kernel log. `fc_vcpu 0` has a space,
+    // so the kernel hex encodes it.
+    const AUDIT_LOG: &str = concat!(
+        "type=SYSCALL msg=audit(1700000000.100:10): arch=c000003e syscall=59 success=yes exit=0 ",
+        "pid=1234 comm=\"firecracker\"\n",
+        "type=SECCOMP msg=audit(1700000000.200:11): auid=4294967295 uid=0 gid=0 ses=4294967295 ",
+        "pid=1234 comm=\"fc_api\" exe=\"/usr/bin/firecracker\" sig=0 arch=c000003e syscall=0 ",
+        "compat=0 ip=0x7f0000000000 code=0x7ffc0000\n",
+        "type=SECCOMP msg=audit(1700000000.300:12): auid=4294967295 uid=0 gid=0 ses=4294967295 ",
+        "pid=1234 comm=66635F766370752030 exe=\"/usr/bin/firecracker\" sig=0 arch=c000003e ",
+        "syscall=16 compat=0 ip=0x7f0000000000 code=0x7ffc0000\n",
+        "[   12.345678] audit: type=1326 audit(1700000000.400:13): auid=4294967295 uid=0 gid=0 ",
+        "ses=4294967295 pid=1234 comm=\"firecracker\" exe=\"/usr/bin/firecracker\" sig=0 ",
+        "arch=c000003e syscall=1 compat=0 ip=0x7f0000000000 code=0x7ffc0000\n",
+        "type=SECCOMP msg=audit(1700000000.500:14): auid=4294967295 uid=0 gid=0 ses=4294967295 ",
+        "pid=5678 comm=\"fc_api\" exe=\"/usr/bin/firecracker\" sig=0 arch=c000003e syscall=1 ",
+        "compat=0 ip=0x7f0000000000 code=0x7ffc0000\n",
+    );
+
+    fn observed(categories: &[(&str, &[&str])]) -> ObservedSyscalls {
+        categories
+            .iter()
+            .map(|(category, syscalls)| {
+                let syscalls = syscalls.iter().map(|syscall| syscall.to_string()).collect();
+                (category.to_string(), syscalls)
+            })
+            .collect()
+    }
+
+    fn filters(json: &str) -> BpfJson {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_thread_category() {
+        assert_eq!(thread_category("fc_api"), "api");
+        assert_eq!(thread_category("fc_vcpu 0"), "vcpu");
+        assert_eq!(thread_category("fc_vcpu 12"), "vcpu");
+        assert_eq!(thread_category("firecracker"), "vmm");
+        assert_eq!(thread_category("fc_vmm"), "vmm");
+    }
+
+    #[test]
+    fn test_record_value() {
+        let record = "type=SECCOMP pid=1234 comm=\"fc_api\" arch=c000003e syscall=0";
+        assert_eq!(record_value(record, "pid").unwrap(), "1234");
+        assert_eq!(record_value(record, "comm").unwrap(), "fc_api");
+        assert_eq!(record_value(record, "arch").unwrap(), "c000003e");
+        assert_eq!(record_value(record, "syscall").unwrap(), "0");
+        assert!(record_value(record, "sig").is_none());
+
+        let record = "type=SECCOMP pid=1234 comm=66635F766370752030 arch=c000003e";
+        assert_eq!(record_value(record, "comm").unwrap(), "fc_vcpu 0");
+        // A thread name which is neither quoted nor valid hex is rejected.
+        let record = "type=SECCOMP pid=1234 comm=fc_api arch=c000003e";
+        assert!(record_value(record, "comm").is_none());
+    }
+
+    #[test]
+    fn test_parse_audit_log() {
+        let observed_syscalls = parse_audit_log(AUDIT_LOG.as_bytes(), None).unwrap();
+        assert_eq!(
+            observed_syscalls,
+            observed(&[
+                ("api", &["read", "write"]),
+                ("vcpu", &["ioctl"]),
+                ("vmm", &["write"]),
+            ])
+        );
+
+        let observed_syscalls = parse_audit_log(AUDIT_LOG.as_bytes(), Some(1234)).unwrap();
+        assert_eq!(
+            observed_syscalls,
+            observed(&[
+                ("api", &["read"]),
+                ("vcpu", &["ioctl"]),
+                ("vmm", &["write"])
+            ])
+        );
+
+        let observed_syscalls = parse_audit_log(AUDIT_LOG.as_bytes(), Some(1)).unwrap();
+        assert!(observed_syscalls.is_empty());
+    }
+
+    #[test]
+    fn test_parse_audit_log_errors() {
+        let record =
+            "type=SECCOMP msg=audit(1700000000.200:11): pid=1234 comm=\"fc_api\" arch=c000003e";
+        assert!(matches!(
+            parse_audit_log(record.as_bytes(), None),
+            Err(AuditError::MalformedRecord(_))
+        ));
+
+        let record = "type=SECCOMP msg=audit(1700000000.200:11): pid=1234 comm=\"fc_api\" \
+                      arch=c000003e syscall=100000";
+        assert!(matches!(
+            parse_audit_log(record.as_bytes(), None),
+            Err(AuditError::UnknownSyscall(0xc000_003e, 100000))
+        ));
+    }
+
+    #[test]
+    fn test_generate_filter() {
+        let observed_syscalls = observed(&[("api", &["read", "write"]), ("vmm", &["ioctl"])]);
+
+        let generated = generate_filter(&observed_syscalls, None);
+        assert_eq!(
+            serde_json::to_value(&generated).unwrap(),
+            serde_json::json!({
+                "api": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "read" }, { "syscall": "write" }]
+                },
+                "vmm": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "ioctl" }]
+                }
+            })
+        );
+
+        // The rules and actions of the base filter are kept, but only for observed syscalls.
+        let base = filters(
+            r#"{
+                "vmm": {
+                    "default_action": "kill_process",
+                    "filter_action": "allow",
+                    "filter": [
+                        {
+                            "syscall": "ioctl",
+                            "args": [{ "index": 1, "type": "dword", "op": "eq", "val": 44672 }]
+                        },
+                        {
+                            "syscall": "ioctl",
+                            "args": [{ "index": 1, "type": "dword", "op": "eq", "val": 44673 }]
+                        },
+                        { "syscall": "close", "comment": "Not observed" }
+                    ]
+                }
+            }"#,
+        );
+        let generated = generate_filter(&observed_syscalls, Some(&base));
+        assert_eq!(
+            serde_json::to_value(&generated).unwrap(),
+            serde_json::json!({
+                "api": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "read" }, { "syscall": "write" }]
+                },
+                "vmm": {
+                    "default_action": "kill_process",
+                    "filter_action": "allow",
+                    "filter": [
+                        {
+                            "syscall": "ioctl",
+                            "args": [{ "index": 1, "type": "dword", "op": "eq", "val": 44672 }]
+                        },
+                        {
+                            "syscall": "ioctl",
+                            "args": [{ "index": 1, "type": "dword", "op": "eq", "val": 44673 }]
+                        }
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_diff() {
+        let observed_syscalls = observed(&[("api", &["read", "write"]), ("vmm", &["ioctl"])]);
+        let base = filters(
+            r#"{
+                "api": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "read" }, { "syscall": "write" }]
+                },
+                "vcpu": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "ioctl" }]
+                },
+                "vmm": {
+                    "default_action": "trap",
+                    "filter_action": "allow",
+                    "filter": [{ "syscall": "close" }, { "syscall": "close" }]
+                }
+            }"#,
+        );
+
+        let diff = FilterDiff::new(&observed_syscalls, &base);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.missing, observed(&[("vmm", &["ioctl"])]));
+        assert_eq!(
+            diff.unused,
+            observed(&[("vcpu", &["ioctl"]), ("vmm", &["close"])])
+        );
+        assert_eq!(diff.to_string(), "vcpu:\n- ioctl\nvmm:\n+ ioctl\n- close\n");
+
+        // A filter generated from the observed syscalls matches them exactly.
+        let generated = generate_filter(&observed_syscalls, Some(&base));
+        let diff = FilterDiff::new(&observed_syscalls, &generated);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "");
+    }
+}
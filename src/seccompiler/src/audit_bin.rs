@@ -0,0 +1,128 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs::File;
+use std::io::{BufReader, Write};
+
+use clap::Parser;
+use seccompiler::BpfJson;
+use seccompiler::audit::{AuditError, FilterDiff, generate_filter, parse_audit_log};
+use serde::Serialize;
+
+const DEFAULT_OUTPUT_FILENAME: &str = "seccomp_audit_filter.json";
+
+#[derive(Debug, Parser)]
+#[command(version = format!("v{}", env!("CARGO_PKG_VERSION")))]
+struct Cli {
+    #[arg(
+        short,
+        long,
+        help = "File path of the audit log, or of the kernel log, holding the records of filters \
+                compiled with seccompiler-bin --audit."
+    )]
+    log_file: String,
+    #[arg(
+        short,
+        long,
+        help = "Only use the records of the process with this pid."
+    )]
+    pid: Option<u32>,
+    #[arg(
+        short,
+        long,
+        help = "Optional path of a JSON filter file to take the actions and the argument \
+                conditions of the generated filters from."
+    )]
+    base_file: Option<String>,
+    #[arg(short, long, help = "Optional path of the output file.", default_value = DEFAULT_OUTPUT_FILENAME)]
+    output_file: String,
+    #[arg(
+        long,
+        conflicts_with_all = ["base_file", "output_file"],
+        help = "Instead of generating a filter, print the syscalls missing from (+) and unused \
+                by (-) the filters of this JSON filter file."
+    )]
+    diff: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+enum AuditBinError {
+    /// Cannot open the audit log: {0}
+    LogOpen(std::io::Error),
+    /// Cannot parse the audit log: {0}
+    Audit(#[from] AuditError),
+    /// Cannot open filter file: {0}
+    FilterOpen(std::io::Error),
+    /// Cannot deserialize json: {0}
+    JsonDeserialize(serde_json::Error),
+    /// Cannot serialize json: {0}
+    JsonSerialize(serde_json::Error),
+    /// Cannot write output file: {0}
+    OutputWrite(std::io::Error),
+}
+
+fn read_filters(path: &str) -> Result<BpfJson, AuditBinError> {
+    let file = File::open(path).map_err(AuditBinError::FilterOpen)?;
+    serde_json::from_reader(BufReader::new(file)).map_err(AuditBinError::JsonDeserialize)
+}
+
+// Serializes the filters indented with 4 spaces, like the filters in `resources/seccomp`.
+fn serialize_filters(filters: &BpfJson) -> Result<Vec<u8>, AuditBinError> {
+    let mut output = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    filters
+        .serialize(&mut serializer)
+        .map_err(AuditBinError::JsonSerialize)?;
+    output.push(b'\n');
+    Ok(output)
+}
+
+fn main() -> Result<(), AuditBinError> {
+    let cli = Cli::parse();
+    let log = File::open(&cli.log_file).map_err(AuditBinError::LogOpen)?;
+    let observed = parse_audit_log(BufReader::new(log), cli.pid)?;
+
+    if let Some(path) = &cli.diff {
+        print!("{}", FilterDiff::new(&observed, &read_filters(path)?));
+        return Ok(());
+    }
+
+    let base = cli.base_file.as_deref().map(read_filters).transpose()?;
+    let output = serialize_filters(&generate_filter(&observed, base.as_ref()))?;
+    File::create(&cli.output_file)
+        .and_then(|mut file| file.write_all(&output))
+        .map_err(AuditBinError::OutputWrite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_filter_file() {
+        let log = "type=SECCOMP msg=audit(1700000000.200:11): pid=1234 comm=\"fc_api\" sig=0 \
+                   arch=c000003e syscall=0 compat=0 ip=0x7f0000000000 code=0x7ffc0000\n";
+        let observed = parse_audit_log(log.as_bytes(), None).unwrap();
+        let output = serialize_filters(&generate_filter(&observed, None)).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            r#"{
+    "api": {
+        "default_action": "trap",
+        "filter_action": "allow",
+        "filter": [
+            {
+                "syscall": "read"
+            }
+        ]
+    }
+}
+"#
+        );
+
+        // The generated file can be read back as a filter file.
+        let filters: BpfJson = serde_json::from_slice(&output).unwrap();
+        assert!(FilterDiff::new(&observed, &filters).is_empty());
+    }
+}
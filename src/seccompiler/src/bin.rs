@@ -33,6 +33,13 @@ struct Cli {
                 Used for testing purposes."
     )]
     split_output: bool,
+    #[arg(
+        long,
+        help = "Compiles filters which allow and log every syscall instead of enforcing the \
+                rules. Used with seccompiler-audit to learn the syscalls used by each thread \
+                category."
+    )]
+    audit: bool,
 }
 
 fn main() -> Result<(), CompilationError> {
@@ -43,5 +50,6 @@ fn main() -> Result<(), CompilationError> {
         &cli.output_file,
         cli.basic,
         cli.split_output,
+        cli.audit,
     )
 }
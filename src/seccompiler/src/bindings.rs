@@ -113,6 +113,16 @@ unsafe extern "C" {
     /// returns [`__NR_SCMP_ERROR`] on failure.
     pub fn seccomp_syscall_resolve_name(name: *const c_char) -> c_int;
 
+    /// Resolve a syscall number to a name
+    ///
+    /// - `arch_token`: the architecture token, e.g. `SCMP_ARCH_*`
+    /// - `num`: the syscall number
+    ///
+    /// Resolve the given syscall number to the syscall name for the given architecture.
+    /// Returns a pointer to a string allocated with `malloc()` that must be released by the
+    /// caller with `free()`, or `ptr::null()` on failure.
+    pub fn seccomp_syscall_resolve_num_arch(arch_token: u32, num: c_int) -> *mut c_char;
+
     /// Add a new rule to the filter
     ///
     /// - `ctx`: the filter context
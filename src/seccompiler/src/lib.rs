@@ -12,6 +12,7 @@ use std::str::FromStr;
 mod bindings;
 use bindings::*;
 
+pub mod audit;
 pub mod types;
 pub use types::*;
 use zerocopy::IntoBytes;
@@ -57,12 +58,18 @@ pub enum CompilationError {
     SizeLimitExceeded(usize),
 }
 
+/// Compiles the JSON filters at `input_path` into BPF programs written to `out_path`.
+///
+/// In `audit` mode the rules are ignored and every filter allows and logs all syscalls with
+/// `SECCOMP_RET_LOG`, so that the syscalls used by each thread category can be collected from
+/// the audit log with the [`audit`] module.
 pub fn compile_bpf(
     input_path: &str,
     arch: &str,
     out_path: &str,
     basic: bool,
     split_output: bool,
+    audit: bool,
 ) -> Result<(), CompilationError> {
     let mut file_content = String::new();
     File::open(input_path)
@@ -87,7 +94,11 @@ pub fn compile_bpf(
 
     let mut bpf_map: BTreeMap<String, Vec<u64>> = BTreeMap::new();
     for (name, filter) in bpf_map_json.0.iter() {
-        let default_action = filter.default_action.to_scmp_type();
+        let default_action = if audit {
+            SCMP_ACT_LOG
+        } else {
+            filter.default_action.to_scmp_type()
+        };
         let filter_action = filter.filter_action.to_scmp_type();
 
         // SAFETY: Safe as all args are correct.
@@ -107,7 +118,9 @@ pub fn compile_bpf(
             }
         }
 
-        for rule in filter.filter.iter() {
+        // An audit filter logs all syscalls, so it has no rules.
+        let rules = if audit { &[][..] } else { &filter.filter[..] };
+        for rule in rules {
             // SAFETY: Safe as all args are correct.
             let syscall = unsafe {
                 let r = seccomp_syscall_resolve_name(rule.syscall.as_ptr());
@@ -11,7 +11,7 @@ use serde::*;
 use crate::bindings::*;
 
 /// Comparison to perform when matching a condition.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SeccompCmpOp {
     Eq,
@@ -24,7 +24,7 @@ pub enum SeccompCmpOp {
 }
 
 /// Seccomp argument value length.
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum SeccompCmpArgLen {
     /// Argument value length is 4 bytes.
@@ -34,13 +34,15 @@ pub enum SeccompCmpArgLen {
 }
 
 /// Condition that syscall must match in order to satisfy a rule.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SeccompCondition {
     pub index: u8,
-    pub op: SeccompCmpOp,
-    pub val: u64,
     #[serde(rename = "type")]
     pub val_len: SeccompCmpArgLen,
+    pub op: SeccompCmpOp,
+    pub val: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
 }
 
 impl SeccompCondition {
@@ -115,7 +117,7 @@ impl SeccompCondition {
 }
 
 /// Actions that `seccomp` can apply to process calling a syscall.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SeccompAction {
     Allow,
@@ -146,22 +148,30 @@ impl SeccompAction {
 /// If all conditions match then rule gets matched.
 /// The action of the first rule that matches will be applied to the calling process.
 /// If no rule matches the default action is applied.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SyscallRule {
+    #[serde(serialize_with = "serialize_cstring")]
     pub syscall: CString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub args: Option<Vec<SeccompCondition>>,
 }
 
+fn serialize_cstring<S: Serializer>(value: &CString, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string_lossy())
+}
+
 /// Filter containing rules assigned to syscall numbers.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Filter {
     pub default_action: SeccompAction,
     pub filter_action: SeccompAction,
     pub filter: Vec<SyscallRule>,
 }
 
-/// Deserializable object that represents the Json filter file.
-#[derive(Debug, Deserialize)]
+/// Serializable object that represents the Json filter file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct BpfJson(pub BTreeMap<String, Filter>);
 
 /// Supported target architectures.
@@ -99,6 +99,13 @@ impl AcpiTableWriter<'_> {
             pci_segment.append_aml_bytes(&mut dsdt_data)?;
         }
 
+        if let Some(controller) = &device_manager.pci_devices.hotplug_controller {
+            controller
+                .lock()
+                .expect("Poisoned lock")
+                .append_aml_bytes(&mut dsdt_data)?;
+        }
+
         // Architecture specific DSDT data
         setup_arch_dsdt(&mut dsdt_data)?;
 
@@ -131,13 +138,14 @@ impl AcpiTableWriter<'_> {
         &mut self,
         resource_allocator: &mut ResourceAllocator,
         nr_vcpus: u8,
+        max_vcpus: u8,
     ) -> Result<u64, AcpiError> {
         let mut madt = Madt::new(
             OEM_ID,
             *b"FCVMMADT",
             OEM_REVISION,
             apic_addr(),
-            setup_interrupt_controllers(nr_vcpus),
+            setup_interrupt_controllers(nr_vcpus, max_vcpus),
         );
         self.write_acpi_table(resource_allocator, &mut madt)
     }
@@ -193,18 +201,24 @@ impl AcpiTableWriter<'_> {
 /// Create ACPI tables for the guest
 ///
 /// This will create the ACPI tables needed to describe to the guest OS the available hardware,
-/// such as interrupt controllers, vCPUs and VirtIO devices.
+/// such as interrupt controllers, vCPUs and VirtIO devices. Only the first `vcpu_count` vCPUs are
+/// enabled at boot, the others can be hotplugged later.
 pub(crate) fn create_acpi_tables(
     mem: &GuestMemoryMmap,
     device_manager: &mut DeviceManager,
     resource_allocator: &mut ResourceAllocator,
     vcpus: &[Vcpu],
+    vcpu_count: u8,
 ) -> Result<(), AcpiError> {
     let mut writer = AcpiTableWriter { mem };
     let dsdt_addr = writer.build_dsdt(device_manager, resource_allocator)?;
 
     let fadt_addr = writer.build_fadt(resource_allocator, dsdt_addr)?;
-    let madt_addr = writer.build_madt(resource_allocator, vcpus.len().try_into().unwrap())?;
+    let madt_addr = writer.build_madt(
+        resource_allocator,
+        vcpu_count,
+        vcpus.len().try_into().unwrap(),
+    )?;
     let mcfg_addr = writer.build_mcfg(resource_allocator, layout::PCI_MMCONFIG_START)?;
     let xsdt_addr = writer.build_xsdt(resource_allocator, fadt_addr, madt_addr, mcfg_addr)?;
     writer.build_rsdp(xsdt_addr)
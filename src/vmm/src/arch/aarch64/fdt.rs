@@ -448,6 +448,17 @@ fn create_rtc_node(fdt: &mut FdtWriter, dev_info: &MMIODeviceInfo) -> Result<(),
     Ok(())
 }
 
+fn create_pvpanic_node(fdt: &mut FdtWriter, dev_info: &MMIODeviceInfo) -> Result<(), FdtError> {
+    // Driver requirements:
+    // https://elixir.bootlin.com/linux/latest/source/Documentation/devicetree/bindings/misc/qemu,pvpanic-mmio.yaml
+    let pvpanic = fdt.begin_node(&format!("pvpanic@{:x}", dev_info.addr))?;
+    fdt.property_string("compatible", "qemu,pvpanic-mmio")?;
+    fdt.property_array_u64("reg", &[dev_info.addr, dev_info.len])?;
+    fdt.end_node(pvpanic)?;
+
+    Ok(())
+}
+
 fn create_devices_node(
     fdt: &mut FdtWriter,
     device_manager: &DeviceManager,
@@ -460,6 +471,10 @@ fn create_devices_node(
         create_serial_node(fdt, serial_info)?;
     }
 
+    if let Some(pvpanic_info) = device_manager.mmio_devices.pvpanic_device_info() {
+        create_pvpanic_node(fdt, pvpanic_info)?;
+    }
+
     let mut virtio_mmio = device_manager.mmio_devices.virtio_device_info();
 
     // Sort out virtio devices by address from low to high and insert them into fdt table.
@@ -583,7 +598,7 @@ mod tests {
         cmdline.insert("console", "/dev/tty0").unwrap();
 
         device_manager
-            .attach_legacy_devices_aarch64(&vm, &mut event_manager, &mut cmdline, None, None)
+            .attach_legacy_devices_aarch64(&vm, &mut event_manager, &mut cmdline, None, None, None)
             .unwrap();
         let dummy = Arc::new(Mutex::new(DummyDevice::new()));
         device_manager
@@ -38,7 +38,7 @@ pub use x86_64::vm::{ArchVm, ArchVmError, VmState};
 #[cfg(target_arch = "x86_64")]
 pub use crate::arch::x86_64::{
     ConfigurationError, arch_memory_regions, configure_system_for_boot, get_kernel_start,
-    initrd_load_addr, layout::*, load_kernel,
+    initrd_load_addr, layout::*, load_firmware, load_kernel,
 };
 
 /// Types of devices that can get attached to this platform.
@@ -54,6 +54,9 @@ pub enum DeviceType {
     Rtc,
     /// Device Type: BootTimer.
     BootTimer,
+    /// Device Type: pvpanic.
+    #[cfg(target_arch = "aarch64")]
+    PvPanic,
 }
 
 /// Default page size for the guest OS.
@@ -33,6 +33,7 @@ pub mod generated;
 
 use std::cmp::max;
 use std::fs::File;
+use std::os::unix::fs::FileExt;
 
 use kvm::Kvm;
 use layout::{
@@ -43,6 +44,7 @@ use linux_loader::configurator::linux::LinuxBootConfigurator;
 use linux_loader::configurator::pvh::PvhBootConfigurator;
 use linux_loader::configurator::{BootConfigurator, BootParams};
 use linux_loader::loader::bootparam::boot_params;
+use linux_loader::loader::bzimage::BzImage;
 use linux_loader::loader::elf::Elf as Loader;
 use linux_loader::loader::elf::start_info::{
     hvm_memmap_table_entry, hvm_modlist_entry, hvm_start_info,
@@ -60,7 +62,7 @@ use crate::initrd::InitrdConfig;
 use crate::utils::{align_down, u64_to_usize, usize_to_u64};
 use crate::vmm_config::machine_config::MachineConfig;
 use crate::vstate::memory::{
-    Address, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion, GuestRegionType,
+    Address, Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion, GuestRegionType,
 };
 use crate::vstate::vcpu::KvmVcpuConfigureError;
 use crate::{Vcpu, VcpuConfig, Vm, logger};
@@ -73,6 +75,16 @@ const E820_RAM: u32 = 1;
 const E820_RESERVED: u32 = 2;
 const MEMMAP_TYPE_RAM: u32 = 1;
 
+// Magic number at the start of ELF kernel images (vmlinux).
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+// Offset of the 64-bit entry point from the start of the protected-mode code of a bzImage.
+// See https://www.kernel.org/doc/html/latest/arch/x86/boot.html#bit-boot-protocol
+const BZIMAGE_64BIT_ENTRY_OFFSET: u64 = 0x200;
+// First boot protocol version with the `xloadflags` field of the setup header.
+const BOOT_PROTOCOL_XLOADFLAGS: u16 = 0x020c;
+// The kernel has the legacy 64-bit entry point at 0x200.
+const XLF_KERNEL_64: u16 = 1 << 0;
+
 /// Errors thrown while configuring x86_64 system.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum ConfigurationError {
@@ -92,6 +104,10 @@ pub enum ConfigurationError {
     KernelFile,
     /// Cannot load kernel due to invalid memory configuration or invalid kernel image: {0}
     KernelLoader(linux_loader::loader::Error),
+    /// The bzImage kernel does not support the 64-bit boot protocol.
+    BzImageNot64Bit,
+    /// The firmware is not an ELF binary with a PVH entry point.
+    FirmwareNotPvh,
     /// Cannot load command line string: {0}
     LoadCommandline(linux_loader::loader::Error),
     /// Failed to create guest config: {0}
@@ -189,8 +205,10 @@ pub fn configure_system_for_boot(
     // Apply CPU template to the base CpuConfiguration.
     let cpu_config = CpuConfiguration::apply_template(cpu_config, cpu_template)?;
 
+    // The CPU topology covers the vCPUs which can be hotplugged later, which are configured at
+    // boot as well.
     let vcpu_config = VcpuConfig {
-        vcpu_count: machine_config.vcpu_count,
+        vcpu_count: machine_config.max_vcpus(),
         smt: machine_config.smt,
         cpu_config,
     };
@@ -219,7 +237,7 @@ pub fn configure_system_for_boot(
     mptable::setup_mptable(
         vm.guest_memory(),
         &mut vm.resource_allocator(),
-        vcpu_config.vcpu_count,
+        machine_config.vcpu_count,
     )
     .map_err(ConfigurationError::MpTableSetup)?;
 
@@ -244,6 +262,7 @@ pub fn configure_system_for_boot(
         device_manager,
         &mut vm.resource_allocator(),
         vcpus,
+        machine_config.vcpu_count,
     )?;
     Ok(())
 }
@@ -353,18 +372,23 @@ fn configure_64bit_boot(
 
     let himem_start = GuestAddress(layout::HIMEM_START);
 
-    // Set the location of RSDP in Boot Parameters to help the guest kernel find it faster.
-    let mut params = boot_params {
-        acpi_rsdp_addr: layout::RSDP_ADDR,
-        ..Default::default()
-    };
+    // The setup header of a bzImage kernel is already in the zero page, see `load_bzimage()`.
+    // The zero page is still empty for ELF kernels.
+    let mut params: boot_params = guest_mem
+        .read_obj(GuestAddress(layout::ZERO_PAGE_START))
+        .map_err(|_| ConfigurationError::ZeroPageSetup)?;
+    let has_setup_header = params.hdr.header == KERNEL_HDR_MAGIC;
 
+    // Set the location of RSDP in Boot Parameters to help the guest kernel find it faster.
+    params.acpi_rsdp_addr = layout::RSDP_ADDR;
     params.hdr.type_of_loader = KERNEL_LOADER_OTHER;
     params.hdr.boot_flag = KERNEL_BOOT_FLAG_MAGIC;
     params.hdr.header = KERNEL_HDR_MAGIC;
     params.hdr.cmd_line_ptr = u32::try_from(cmdline_addr.raw_value()).unwrap();
     params.hdr.cmdline_size = u32::try_from(cmdline_size).unwrap();
-    params.hdr.kernel_alignment = KERNEL_MIN_ALIGNMENT_BYTES;
+    if !has_setup_header {
+        params.hdr.kernel_alignment = KERNEL_MIN_ALIGNMENT_BYTES;
+    }
     if let Some(initrd_config) = initrd {
         params.hdr.ramdisk_image = u32::try_from(initrd_config.address.raw_value()).unwrap();
         params.hdr.ramdisk_size = u32::try_from(initrd_config.size).unwrap();
@@ -439,6 +463,16 @@ pub fn load_kernel(
         .try_clone()
         .map_err(|_| ConfigurationError::KernelFile)?;
 
+    // Uncompressed kernels (vmlinux) are ELF files, while the compressed kernels shipped by
+    // distributions are bzImages.
+    let mut magic = [0u8; ELF_MAGIC.len()];
+    kernel_file
+        .read_exact_at(&mut magic, 0)
+        .map_err(|_| ConfigurationError::KernelFile)?;
+    if magic != ELF_MAGIC {
+        return load_bzimage(&mut kernel_file, guest_memory);
+    }
+
     let entry_addr = Loader::load(
         guest_memory,
         None,
@@ -463,6 +497,63 @@ pub fn load_kernel(
     })
 }
 
+/// Load a firmware into guest memory.
+///
+/// The firmware is booted like a PVH kernel, so it gets the same ACPI tables and memory map as
+/// the kernels, and finds the kernel to boot on the root block device.
+pub fn load_firmware(
+    firmware: &File,
+    guest_memory: &GuestMemoryMmap,
+) -> Result<EntryPoint, ConfigurationError> {
+    let entry_point = load_kernel(firmware, guest_memory)?;
+    if entry_point.protocol != BootProtocol::PvhBoot {
+        return Err(ConfigurationError::FirmwareNotPvh);
+    }
+    Ok(entry_point)
+}
+
+/// Load a bzImage kernel into guest memory, to boot it with the Linux 64-bit boot protocol.
+fn load_bzimage(
+    kernel_file: &mut File,
+    guest_memory: &GuestMemoryMmap,
+) -> Result<EntryPoint, ConfigurationError> {
+    let kernel_start = GuestAddress(get_kernel_start());
+    let loader_result = BzImage::load(
+        guest_memory,
+        Some(kernel_start),
+        kernel_file,
+        Some(kernel_start),
+    )
+    .map_err(ConfigurationError::KernelLoader)?;
+
+    // It is safe to unwrap because the bzImage loader always returns the setup header.
+    let setup_header = loader_result.setup_header.unwrap();
+    if setup_header.version < BOOT_PROTOCOL_XLOADFLAGS
+        || setup_header.xloadflags & XLF_KERNEL_64 == 0
+    {
+        return Err(ConfigurationError::BzImageNot64Bit);
+    }
+
+    // Like boot loaders do, the setup header of the kernel is copied to the zero page, where
+    // `configure_64bit_boot()` fills in the rest of the boot parameters.
+    let params = boot_params {
+        hdr: setup_header,
+        ..Default::default()
+    };
+    guest_memory
+        .write_obj(params, GuestAddress(layout::ZERO_PAGE_START))
+        .map_err(|_| ConfigurationError::ZeroPageSetup)?;
+
+    debug!("bzImage kernel loaded using {}", BootProtocol::LinuxBoot);
+
+    Ok(EntryPoint {
+        entry_addr: loader_result
+            .kernel_load
+            .unchecked_add(BZIMAGE_64BIT_ENTRY_OFFSET),
+        protocol: BootProtocol::LinuxBoot,
+    })
+}
+
 #[cfg(kani)]
 mod verification {
 
@@ -533,14 +624,98 @@ mod verification {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
     use linux_loader::loader::bootparam::boot_e820_entry;
+    use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
     use crate::arch::x86_64::layout::FIRST_ADDR_PAST_32BITS;
+    use crate::test_utils::mock_resources::kernel_image_path;
     use crate::test_utils::{arch_mem, single_region_mem};
     use crate::utils::mib_to_bytes;
     use crate::vstate::resources::ResourceAllocator;
 
+    // Builds a minimal bzImage, with a single setup sector followed by `payload`.
+    fn make_bzimage(version: u16, xloadflags: u16, payload: &[u8]) -> TempFile {
+        let mut image = vec![0u8; 1024];
+        // setup_sects
+        image[0x1f1] = 1;
+        // boot_flag
+        image[0x1fe..0x200].copy_from_slice(&0xaa55u16.to_le_bytes());
+        // header
+        image[0x202..0x206].copy_from_slice(b"HdrS");
+        image[0x206..0x208].copy_from_slice(&version.to_le_bytes());
+        // loadflags: LOADED_HIGH
+        image[0x211] = 1;
+        // code32_start
+        image[0x214..0x218].copy_from_slice(&0x0010_0000u32.to_le_bytes());
+        // kernel_alignment
+        image[0x230..0x234].copy_from_slice(&0x0020_0000u32.to_le_bytes());
+        image[0x236..0x238].copy_from_slice(&xloadflags.to_le_bytes());
+        image.extend_from_slice(payload);
+
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(&image).unwrap();
+        file
+    }
+
+    // Builds a minimal ELF file with a PVH entry point, whose code is loaded at `entry`.
+    fn make_pvh_elf(entry: u32, code: &[u8; 16]) -> TempFile {
+        const EHDR_SIZE: u16 = 64;
+        const PHDR_SIZE: u16 = 56;
+        const CODE_OFFSET: u64 = 0x100;
+        const NOTE_OFFSET: u64 = 0x110;
+
+        let mut image = vec![0u8; 0x110];
+        // e_ident: magic, ELFCLASS64, ELFDATA2LSB, EV_CURRENT
+        image[0..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        // e_type: ET_EXEC
+        image[16..18].copy_from_slice(&2u16.to_le_bytes());
+        // e_machine: EM_X86_64
+        image[18..20].copy_from_slice(&62u16.to_le_bytes());
+        // e_version
+        image[20..24].copy_from_slice(&1u32.to_le_bytes());
+        // e_entry
+        image[24..32].copy_from_slice(&u64::from(entry).to_le_bytes());
+        // e_phoff
+        image[32..40].copy_from_slice(&u64::from(EHDR_SIZE).to_le_bytes());
+        // e_ehsize, e_phentsize, e_phnum
+        image[52..54].copy_from_slice(&EHDR_SIZE.to_le_bytes());
+        image[54..56].copy_from_slice(&PHDR_SIZE.to_le_bytes());
+        image[56..58].copy_from_slice(&2u16.to_le_bytes());
+
+        // Program headers: (p_type, p_offset, p_paddr, p_filesz)
+        let phdrs = [
+            // PT_LOAD with the code
+            (1u32, CODE_OFFSET, u64::from(entry), 16u64),
+            // PT_NOTE with the PVH note
+            (4u32, NOTE_OFFSET, 0, 20u64),
+        ];
+        for (i, (p_type, p_offset, p_paddr, p_filesz)) in phdrs.into_iter().enumerate() {
+            let start = usize::from(EHDR_SIZE) + i * usize::from(PHDR_SIZE);
+            let phdr = &mut image[start..start + usize::from(PHDR_SIZE)];
+            phdr[0..4].copy_from_slice(&p_type.to_le_bytes());
+            phdr[8..16].copy_from_slice(&p_offset.to_le_bytes());
+            phdr[16..24].copy_from_slice(&p_paddr.to_le_bytes());
+            phdr[24..32].copy_from_slice(&p_paddr.to_le_bytes());
+            phdr[32..40].copy_from_slice(&p_filesz.to_le_bytes());
+            phdr[40..48].copy_from_slice(&p_filesz.to_le_bytes());
+        }
+
+        image[0x100..0x110].copy_from_slice(code);
+        // XEN_ELFNOTE_PHYS32_ENTRY note: n_namesz, n_descsz, n_type, "Xen", entry point
+        image.extend_from_slice(&4u32.to_le_bytes());
+        image.extend_from_slice(&4u32.to_le_bytes());
+        image.extend_from_slice(&18u32.to_le_bytes());
+        image.extend_from_slice(b"Xen\0");
+        image.extend_from_slice(&entry.to_le_bytes());
+
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(&image).unwrap();
+        file
+    }
+
     #[test]
     fn regions_lt_4gb() {
         let regions = arch_memory_regions(1usize << 29);
@@ -644,4 +819,102 @@ mod tests {
             .is_err()
         );
     }
+
+    #[test]
+    fn test_load_bzimage() {
+        let gm = arch_mem(mib_to_bytes(128));
+        let payload = [0x90u8; 512];
+        let kernel = make_bzimage(0x020f, XLF_KERNEL_64, &payload);
+
+        let entry_point = load_kernel(kernel.as_file(), &gm).unwrap();
+        assert_eq!(entry_point.protocol, BootProtocol::LinuxBoot);
+        assert_eq!(
+            entry_point.entry_addr,
+            GuestAddress(get_kernel_start() + BZIMAGE_64BIT_ENTRY_OFFSET)
+        );
+        let mut loaded = [0u8; 512];
+        gm.read_slice(&mut loaded, GuestAddress(get_kernel_start()))
+            .unwrap();
+        assert_eq!(loaded, payload);
+
+        // The setup header of the kernel is kept in the boot parameters.
+        configure_64bit_boot(&gm, GuestAddress(layout::CMDLINE_START), 10, &None).unwrap();
+        let params: boot_params = gm.read_obj(GuestAddress(layout::ZERO_PAGE_START)).unwrap();
+        assert_eq!({ params.hdr.version }, 0x020f);
+        assert_eq!({ params.hdr.xloadflags }, XLF_KERNEL_64);
+        assert_eq!({ params.hdr.kernel_alignment }, 0x0020_0000);
+        assert_eq!({ params.hdr.code32_start }, 0x0010_0000);
+        assert_eq!({ params.hdr.type_of_loader }, 0xff);
+        assert_eq!(
+            { params.hdr.cmd_line_ptr },
+            u32::try_from(layout::CMDLINE_START).unwrap()
+        );
+        assert_eq!({ params.hdr.cmdline_size }, 10);
+        assert_ne!(params.e820_entries, 0);
+    }
+
+    #[test]
+    fn test_load_bzimage_not_64bit() {
+        let gm = arch_mem(mib_to_bytes(128));
+
+        // The boot protocol is too old to tell whether the 64-bit entry point is there.
+        let kernel = make_bzimage(0x020b, 0, &[0u8; 512]);
+        assert!(matches!(
+            load_kernel(kernel.as_file(), &gm),
+            Err(ConfigurationError::BzImageNot64Bit)
+        ));
+
+        let kernel = make_bzimage(0x020f, 0, &[0u8; 512]);
+        assert!(matches!(
+            load_kernel(kernel.as_file(), &gm),
+            Err(ConfigurationError::BzImageNot64Bit)
+        ));
+    }
+
+    #[test]
+    fn test_load_firmware() {
+        let gm = arch_mem(mib_to_bytes(128));
+
+        let code = [0xf4; 16];
+        let firmware = make_pvh_elf(0x0010_0000, &code);
+        let entry_point = load_firmware(firmware.as_file(), &gm).unwrap();
+        assert_eq!(entry_point.entry_addr, GuestAddress(0x0010_0000));
+        assert_eq!(entry_point.protocol, BootProtocol::PvhBoot);
+        let mut loaded = [0u8; 16];
+        gm.read_slice(&mut loaded, GuestAddress(0x0010_0000))
+            .unwrap();
+        assert_eq!(loaded, code);
+
+        // The firmware needs a PVH entry point.
+        let firmware = File::open(kernel_image_path(None)).unwrap();
+        assert!(matches!(
+            load_firmware(&firmware, &gm),
+            Err(ConfigurationError::FirmwareNotPvh)
+        ));
+        let firmware = make_bzimage(0x020f, XLF_KERNEL_64, &[0u8; 512]);
+        assert!(matches!(
+            load_firmware(firmware.as_file(), &gm),
+            Err(ConfigurationError::FirmwareNotPvh)
+        ));
+    }
+
+    #[test]
+    fn test_load_invalid_kernel() {
+        let gm = arch_mem(mib_to_bytes(128));
+
+        // Neither an ELF file nor a bzImage.
+        let kernel = TempFile::new().unwrap();
+        kernel.as_file().write_all(&[0u8; 1024]).unwrap();
+        assert!(matches!(
+            load_kernel(kernel.as_file(), &gm),
+            Err(ConfigurationError::KernelLoader(_))
+        ));
+
+        // Too short to hold the magic number.
+        let kernel = TempFile::new().unwrap();
+        assert!(matches!(
+            load_kernel(kernel.as_file(), &gm),
+            Err(ConfigurationError::KernelFile)
+        ));
+    }
 }
@@ -31,6 +31,7 @@ use crate::device_manager::{
 };
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::block::device::Block;
+use crate::devices::virtio::console::Console;
 use crate::devices::virtio::device::VirtioDevice;
 use crate::devices::virtio::mem::{VIRTIO_MEM_DEFAULT_SLOT_SIZE_MIB, VirtioMem};
 use crate::devices::virtio::net::Net;
@@ -40,7 +41,8 @@ use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
 #[cfg(feature = "gdb")]
 use crate::gdb;
 use crate::initrd::{InitrdConfig, InitrdError};
-use crate::logger::debug;
+use crate::logger::{EVENT_LOG, SPANS, debug};
+use crate::measured_boot::{MeasuredBootError, measure_boot_config, measure_boot_files};
 use crate::persist::{MicrovmState, MicrovmStateError};
 use crate::resources::VmResources;
 use crate::seccomp::BpfThreadMap;
@@ -96,8 +98,12 @@ pub enum StartMicrovmError {
     KernelCmdline(String),
     /// Kvm error: {0}
     Kvm(#[from] KvmError),
+    /// Failed to install the Landlock ruleset: {0}
+    Landlock(crate::landlock::LandlockError),
     /// Cannot load command line string: {0}
     LoadCommandline(linux_loader::loader::Error),
+    /// Failed to measure the boot components: {0}
+    MeasuredBoot(#[from] MeasuredBootError),
     /// Cannot start microvm without kernel configuration.
     MissingKernelConfig,
     /// Cannot start microvm without guest mem_size config.
@@ -147,6 +153,7 @@ pub fn build_microvm_for_boot(
 ) -> Result<Arc<Mutex<Vmm>>, StartMicrovmError> {
     // Timestamp for measuring microVM boot duration.
     let request_ts = TimestampUs::default();
+    let _span = SPANS.start("build_microvm_for_boot");
 
     let boot_config = vm_resources
         .boot_source
@@ -154,9 +161,11 @@ pub fn build_microvm_for_boot(
         .as_ref()
         .ok_or(StartMicrovmError::MissingKernelConfig)?;
 
+    let span = SPANS.start("allocate_guest_memory");
     let guest_memory = vm_resources
         .allocate_guest_memory()
         .map_err(StartMicrovmError::GuestMemory)?;
+    drop(span);
 
     // Clone the command-line so that a failed boot doesn't pollute the original.
     #[allow(unused_mut)]
@@ -167,11 +176,13 @@ pub fn build_microvm_for_boot(
         .cpu_template
         .get_cpu_template()?;
 
+    let span = SPANS.start("create_vm");
     let kvm = Kvm::new(cpu_template.kvm_capabilities.clone())?;
     // Set up Kvm Vm and register memory regions.
     // Build custom CPU config if a custom template is provided.
     let mut vm = Vm::new(&kvm)?;
-    let (mut vcpus, vcpus_exit_evt) = vm.create_vcpus(vm_resources.machine_config.vcpu_count)?;
+    // The vCPUs which can be hotplugged later are created and configured at boot as well.
+    let (mut vcpus, vcpus_exit_evt) = vm.create_vcpus(vm_resources.machine_config.max_vcpus())?;
     vm.register_dram_memory_regions(guest_memory)?;
 
     // Allocate memory as soon as possible to make hotpluggable memory available to all consumers,
@@ -189,19 +200,41 @@ pub fn build_microvm_for_boot(
     } else {
         None
     };
+    drop(span);
 
+    let span = SPANS.start("device_manager");
     let mut device_manager = DeviceManager::new(
         event_manager,
         &vcpus_exit_evt,
         &vm,
         vm_resources.serial_out_path.as_ref(),
+        vm_resources.serial_socket_path.as_ref(),
         vm_resources.serial_rate_limiter(),
     )?;
 
     let vm = Arc::new(vm);
+    drop(span);
 
+    // With measured boot, the guest is loaded from the measured copies of the files, so that the
+    // log matches what is booted even if the files change on the host.
+    let span = SPANS.start("measure_boot");
+    let (measured_config, mut boot_measurements) = measure_boot_files(boot_config)?.unzip();
+    let boot_config = measured_config.as_ref().unwrap_or(boot_config);
+    drop(span);
+
+    let span = SPANS.start("load_kernel");
+    #[cfg(target_arch = "x86_64")]
+    let entry_point = if boot_config.firmware {
+        crate::arch::load_firmware(&boot_config.kernel_file, vm.guest_memory())?
+    } else {
+        load_kernel(&boot_config.kernel_file, vm.guest_memory())?
+    };
+    #[cfg(target_arch = "aarch64")]
     let entry_point = load_kernel(&boot_config.kernel_file, vm.guest_memory())?;
     let initrd = InitrdConfig::from_config(boot_config, vm.guest_memory())?;
+    drop(span);
+
+    let span = SPANS.start("attach_devices");
 
     if vm_resources.pci_enabled {
         device_manager.enable_pci(&vm)?;
@@ -268,6 +301,16 @@ pub fn build_microvm_for_boot(
         )?;
     }
 
+    if let Some(console) = vm_resources.console.get() {
+        attach_console_device(
+            &mut device_manager,
+            &vm,
+            &mut boot_cmdline,
+            console,
+            event_manager,
+        )?;
+    }
+
     // Attach virtio-mem device if configured
     if let Some(memory_hotplug) = &vm_resources.memory_hotplug {
         attach_virtio_mem_device(
@@ -286,19 +329,41 @@ pub fn build_microvm_for_boot(
         event_manager,
         &mut boot_cmdline,
         vm_resources.serial_out_path.as_ref(),
+        vm_resources.serial_socket_path.as_ref(),
         vm_resources.serial_rate_limiter(),
     )?;
 
     device_manager.attach_vmgenid_device(&vm)?;
     device_manager.attach_vmclock_device(&vm)?;
 
+    #[cfg(target_arch = "x86_64")]
+    if vm_resources.pci_enabled {
+        device_manager.attach_pci_hotplug_controller(&vm)?;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    if let Some(max_vcpu_count) = vm_resources.machine_config.max_vcpu_count {
+        device_manager.attach_cpu_hotplug_controller(
+            &vm,
+            vm_resources.machine_config.vcpu_count,
+            max_vcpu_count,
+        )?;
+    }
+
     #[cfg(target_arch = "aarch64")]
     if vcpus[0].kvm_vcpu.supports_pvtime() {
         setup_pvtime(&mut vm.resource_allocator(), &mut vcpus)?;
     } else {
         log::warn!("Vcpus do not support pvtime, steal time will not be reported to guest");
     }
+    drop(span);
+
+    // The command line is complete once the devices are attached.
+    if let Some(boot_measurements) = boot_measurements.as_mut() {
+        measure_boot_config(boot_measurements, &boot_cmdline, &cpu_template)?;
+    }
 
+    let span = SPANS.start("configure_system");
     configure_system_for_boot(
         &kvm,
         &vm,
@@ -310,9 +375,13 @@ pub fn build_microvm_for_boot(
         &initrd,
         boot_cmdline,
     )?;
+    drop(span);
 
     let vmm = Vmm {
-        instance_info: instance_info.clone(),
+        instance_info: InstanceInfo {
+            boot_measurements: boot_measurements.unwrap_or_default(),
+            ..instance_info.clone()
+        },
         machine_config: vm_resources.machine_config.clone(),
         boot_source_config: vm_resources.boot_source.config.clone(),
         shutdown_exit_code: None,
@@ -320,8 +389,11 @@ pub fn build_microvm_for_boot(
         vm,
         uffd: None,
         vcpus_handles: Vec::new(),
+        parked_vcpus: 0,
         vcpus_exit_evt,
         device_manager,
+        coredump_on_fault: vm_resources.coredump_on_fault.clone(),
+        pause_on_panic: vm_resources.pause_on_panic,
     };
     let vmm = Arc::new(Mutex::new(vmm));
 
@@ -333,17 +405,30 @@ pub fn build_microvm_for_boot(
         .iter_mut()
         .for_each(|vcpu| vcpu.attach_debug_info(gdb_tx.clone()));
 
-    // Move vcpus to their own threads and start their state machine in the 'Paused' state.
+    // Install the Landlock ruleset before spawning the vcpu and gdb threads, so that they
+    // inherit it.
+    if let Some(landlock) = &vm_resources.landlock {
+        crate::landlock::restrict_thread(&landlock.rules(vm_resources))
+            .map_err(StartMicrovmError::Landlock)?;
+    }
+
+    // Move vcpus to their own threads and start their state machine in the 'Paused' state. The
+    // vcpus which are not online at boot stay paused until they are hotplugged.
+    let span = SPANS.start("start_vcpus");
     vmm.lock()
         .unwrap()
         .start_vcpus(
             vcpus,
+            u32::MAX
+                .checked_shl(u32::from(vm_resources.machine_config.vcpu_count))
+                .unwrap_or(0),
             seccomp_filters
                 .get("vcpu")
                 .ok_or_else(|| StartMicrovmError::MissingSeccompFilters("vcpu".to_string()))?
                 .clone(),
         )
         .map_err(VmmError::VcpuStart)?;
+    drop(span);
 
     #[cfg(feature = "gdb")]
     if let Some(gdb_socket_path) = &vm_resources.machine_config.gdb_socket_path {
@@ -446,6 +531,7 @@ pub fn build_microvm_from_snapshot(
 ) -> Result<Arc<Mutex<Vmm>>, BuildMicrovmFromSnapshotError> {
     // Build Vmm.
     debug!("event_start: build microvm from snapshot");
+    let _span = SPANS.start("build_microvm_from_snapshot");
 
     let kvm = Kvm::new(microvm_state.kvm_state.kvm_cap_modifiers.clone())
         .map_err(StartMicrovmError::Kvm)?;
@@ -454,12 +540,14 @@ pub fn build_microvm_from_snapshot(
     let mut vm = Vm::new(&kvm).map_err(StartMicrovmError::Vm)?;
 
     let (mut vcpus, vcpus_exit_evt) = vm
-        .create_vcpus(vm_resources.machine_config.vcpu_count)
+        .create_vcpus(vm_resources.machine_config.max_vcpus())
         .map_err(StartMicrovmError::Vm)?;
 
     vm.restore_memory_regions(guest_memory, &microvm_state.vm_state.memory)
         .map_err(StartMicrovmError::Vm)?;
 
+    let span = SPANS.start("restore_vcpus");
+
     #[cfg(target_arch = "x86_64")]
     {
         // Scale TSC to match, extract the TSC freq from the state if specified
@@ -495,9 +583,12 @@ pub fn build_microvm_from_snapshot(
     // Restore kvm vm state.
     #[cfg(target_arch = "x86_64")]
     vm.restore_state(&microvm_state.vm_state, clock_realtime)?;
+    drop(span);
 
     // Restore the boot source config paths.
-    vm_resources.boot_source.config = microvm_state.vm_info.boot_source;
+    vm_resources.boot_source.config = microvm_state.vm_info.boot_source.into();
+    // Keep the cursors of the event log clients valid.
+    EVENT_LOG.resume_from(microvm_state.vm_info.next_event_seq);
 
     let vm = Arc::new(vm);
 
@@ -505,6 +596,7 @@ pub fn build_microvm_from_snapshot(
     // Restoring VMGenID injects an interrupt in the guest to notify it about the new generation
     // ID. As a result, we need to restore DeviceManager after restoring the KVM state, otherwise
     // the injected interrupt will be overwritten.
+    let span = SPANS.start("restore_devices");
     let device_ctor_args = DeviceRestoreArgs {
         mem: vm.guest_memory(),
         vm: &vm,
@@ -516,9 +608,23 @@ pub fn build_microvm_from_snapshot(
     #[allow(unused_mut)]
     let mut device_manager =
         DeviceManager::restore(device_ctor_args, &microvm_state.device_states)?;
+    drop(span);
+
+    // The vCPUs known to the guest are online, the others stay paused until they are hotplugged.
+    let parked_vcpus = device_manager
+        .acpi_devices
+        .cpu_hotplug
+        .as_ref()
+        .map_or(0, |controller| {
+            !controller.lock().expect("Poisoned lock").enabled_vcpus()
+        });
 
     let mut vmm = Vmm {
-        instance_info: instance_info.clone(),
+        // The measured boot log is the one of the microVM booted originally.
+        instance_info: InstanceInfo {
+            boot_measurements: microvm_state.vm_info.boot_measurements.clone(),
+            ..instance_info.clone()
+        },
         machine_config: vm_resources.machine_config.clone(),
         boot_source_config: vm_resources.boot_source.config.clone(),
         shutdown_exit_code: None,
@@ -526,18 +632,30 @@ pub fn build_microvm_from_snapshot(
         vm,
         uffd,
         vcpus_handles: Vec::new(),
+        parked_vcpus: 0,
         vcpus_exit_evt,
         device_manager,
+        coredump_on_fault: vm_resources.coredump_on_fault.clone(),
+        pause_on_panic: vm_resources.pause_on_panic,
     };
 
+    // Install the Landlock ruleset before spawning the vcpu threads, so that they inherit it.
+    if let Some(landlock) = &vm_resources.landlock {
+        crate::landlock::restrict_thread(&landlock.rules(vm_resources))
+            .map_err(StartMicrovmError::Landlock)?;
+    }
+
     // Move vcpus to their own threads and start their state machine in the 'Paused' state.
+    let span = SPANS.start("start_vcpus");
     vmm.start_vcpus(
         vcpus,
+        parked_vcpus,
         seccomp_filters
             .get("vcpu")
             .ok_or(BuildMicrovmFromSnapshotError::MissingVcpuSeccompFilters)?
             .clone(),
     )?;
+    drop(span);
 
     let vmm = Arc::new(Mutex::new(vmm));
     event_manager.add_subscriber(vmm.clone());
@@ -620,6 +738,29 @@ fn attach_entropy_device(
     )
 }
 
+fn attach_console_device(
+    device_manager: &mut DeviceManager,
+    vm: &Arc<Vm>,
+    cmdline: &mut LoaderKernelCmdline,
+    console_device: &Arc<Mutex<Console>>,
+    event_manager: &mut EventManager,
+) -> Result<(), AttachDeviceError> {
+    let id = console_device
+        .lock()
+        .expect("Poisoned lock")
+        .id()
+        .to_string();
+
+    device_manager.attach_virtio_device(
+        vm,
+        id,
+        console_device.clone(),
+        cmdline,
+        event_manager,
+        false,
+    )
+}
+
 fn allocate_virtio_mem_address(
     vm: &Vm,
     total_size_mib: usize,
@@ -791,6 +932,7 @@ pub(crate) mod tests {
     use super::*;
     use crate::device_manager::tests::default_device_manager;
     use crate::devices::virtio::block::CacheType;
+    use crate::devices::virtio::console::device::CONSOLE_DEV_ID;
     use crate::devices::virtio::device::VirtioDeviceType;
     use crate::devices::virtio::rng::device::ENTROPY_DEV_ID;
     use crate::devices::virtio::vsock::VSOCK_DEV_ID;
@@ -799,6 +941,9 @@ pub(crate) mod tests {
     use crate::utils::mib_to_bytes;
     use crate::vmm_config::balloon::{BALLOON_DEV_ID, BalloonBuilder, BalloonDeviceConfig};
     use crate::vmm_config::boot_source::{BootSourceConfig, DEFAULT_KERNEL_CMDLINE};
+    use crate::vmm_config::console::{
+        ConsoleDeviceBuilder, ConsoleDeviceConfig, ConsolePortConfig,
+    };
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
     use crate::vmm_config::entropy::{EntropyDeviceBuilder, EntropyDeviceConfig};
     use crate::vmm_config::machine_config::MachineConfig;
@@ -875,8 +1020,11 @@ pub(crate) mod tests {
             vm: Arc::new(vm),
             uffd: None,
             vcpus_handles: Vec::new(),
+            parked_vcpus: 0,
             vcpus_exit_evt,
             device_manager: default_device_manager(),
+            coredump_on_fault: None,
+            pause_on_panic: false,
         }
     }
 
@@ -1026,6 +1174,31 @@ pub(crate) mod tests {
         );
     }
 
+    pub(crate) fn insert_console_device(
+        vmm: &mut Vmm,
+        cmdline: &mut Cmdline,
+        event_manager: &mut EventManager,
+        console_config: ConsoleDeviceConfig,
+    ) {
+        let mut builder = ConsoleDeviceBuilder::new();
+        let console = builder.build(console_config).unwrap();
+
+        attach_console_device(
+            &mut vmm.device_manager,
+            &vmm.vm,
+            cmdline,
+            &console,
+            event_manager,
+        )
+        .unwrap();
+
+        assert!(
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::Console, CONSOLE_DEV_ID)
+                .is_some()
+        );
+    }
+
     pub(crate) fn insert_pmem_devices(
         vmm: &mut Vmm,
         cmdline: &mut Cmdline,
@@ -1313,6 +1486,7 @@ pub(crate) mod tests {
             path_on_host: "".into(),
             root_device: true,
             read_only: true,
+            shared_lock: false,
         }];
         let mut vmm = default_vmm();
         let mut cmdline = default_kernel_cmdline();
@@ -1348,6 +1522,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
 
         let mut cmdline = default_kernel_cmdline();
@@ -1377,6 +1552,31 @@ pub(crate) mod tests {
         ));
     }
 
+    #[test]
+    fn test_attach_console_device() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+
+        let output = TempFile::new().unwrap();
+        let console_config = ConsoleDeviceConfig {
+            ports: vec![ConsolePortConfig {
+                name: "console".to_string(),
+                console: true,
+                uds_path: None,
+                output_path: Some(output.as_path().to_str().unwrap().to_string()),
+            }],
+        };
+
+        let mut cmdline = default_kernel_cmdline();
+        insert_console_device(&mut vmm, &mut cmdline, &mut event_manager, console_config);
+        // Check if the console device is described in kernel_cmdline.
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        assert!(cmdline_contains(
+            &cmdline,
+            "virtio_mmio.device=4K@0xc0001000:5"
+        ));
+    }
+
     #[test]
     fn test_attach_vsock_device() {
         let mut event_manager = EventManager::new().expect("Unable to create EventManager");
@@ -0,0 +1,87 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::mem::offset_of;
+
+use kvm_bindings::{KVM_REG_ARM_CORE, KVM_REG_ARM64, KVM_REG_SIZE_U64, kvm_regs, user_pt_regs};
+
+use crate::arch::aarch64::regs::arm64_core_reg_id;
+use crate::vstate::vcpu::VcpuState;
+
+/// `e_machine` of aarch64 ELF files.
+pub const ELF_MACHINE: u16 = 183;
+
+/// Number of general purpose registers, x0 to x30.
+const GENERAL_PURPOSE_REG_COUNT: usize = 31;
+/// Mask of the exception level and stack pointer selection in `pstate`.
+const PSTATE_MODE_MASK: u64 = 0xf;
+/// Mode of `pstate` when running at EL1 with the SP_EL1 stack pointer.
+const PSTATE_MODE_EL1H: u64 = 0x5;
+
+/// Returns the value of the core register at `offset` in `struct kvm_regs`, or 0 if it was not
+/// saved.
+fn core_reg(state: &VcpuState, offset: usize) -> u64 {
+    let id = arm64_core_reg_id!(KVM_REG_SIZE_U64, offset);
+    state
+        .regs
+        .iter()
+        .find(|reg| reg.id == id)
+        .map_or(0, |reg| reg.value())
+}
+
+/// Returns the registers of a vCPU in the order of `struct user_pt_regs`.
+pub fn prstatus_regs(state: &VcpuState) -> Vec<u64> {
+    let mut regs: Vec<u64> = (0..GENERAL_PURPOSE_REG_COUNT)
+        .map(|i| core_reg(state, offset_of!(user_pt_regs, regs) + i * 8))
+        .collect();
+
+    // `sp` in `user_pt_regs` is SP_EL0, while the kernel runs on SP_EL1.
+    let pstate = core_reg(state, offset_of!(user_pt_regs, pstate));
+    let sp = if pstate & PSTATE_MODE_MASK == PSTATE_MODE_EL1H {
+        core_reg(state, offset_of!(kvm_regs, sp_el1))
+    } else {
+        core_reg(state, offset_of!(user_pt_regs, sp))
+    };
+    regs.push(sp);
+    regs.push(core_reg(state, offset_of!(user_pt_regs, pc)));
+    regs.push(pstate);
+    regs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::aarch64::regs::Aarch64RegisterRef;
+
+    fn push_core_reg(state: &mut VcpuState, offset: usize, value: u64) {
+        let id = arm64_core_reg_id!(KVM_REG_SIZE_U64, offset);
+        state
+            .regs
+            .push(Aarch64RegisterRef::new(id, &value.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_prstatus_regs() {
+        let mut state = VcpuState::default();
+        push_core_reg(&mut state, offset_of!(user_pt_regs, regs), 1);
+        push_core_reg(&mut state, offset_of!(user_pt_regs, sp), 2);
+        push_core_reg(&mut state, offset_of!(kvm_regs, sp_el1), 3);
+        push_core_reg(&mut state, offset_of!(user_pt_regs, pc), 4);
+
+        let regs = prstatus_regs(&state);
+        assert_eq!(regs.len(), 34);
+        assert_eq!(regs[0], 1);
+        assert_eq!(regs[1], 0);
+        assert_eq!(regs[31], 2);
+        assert_eq!(regs[32], 4);
+
+        push_core_reg(
+            &mut state,
+            offset_of!(user_pt_regs, pstate),
+            PSTATE_MODE_EL1H,
+        );
+        let regs = prstatus_regs(&state);
+        assert_eq!(regs[31], 3);
+        assert_eq!(regs[33], PSTATE_MODE_EL1H);
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Creates core dumps of the guest in the ELF format used by kdump, which `crash` can analyze.
+//!
+//! A core dump holds one `NT_PRSTATUS` note with the registers of every vCPU, the `VMCOREINFO`
+//! note of the guest kernel when it can be found in guest memory, and one `PT_LOAD` segment per
+//! guest memory region, whose physical address is the guest physical address of the region.
+
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::Vmm;
+use crate::arch::GUEST_PAGE_SIZE;
+use crate::operation::OPERATION_PROGRESS;
+use crate::persist::MicrovmStateError;
+use crate::utils::usize_to_u64;
+use crate::vstate::memory::{
+    Bytes, GuestAddress, GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion,
+    GuestRegionType, MemoryError,
+};
+use crate::vstate::vcpu::VcpuState;
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(target_arch = "aarch64")]
+use aarch64::{ELF_MACHINE, prstatus_regs};
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+#[cfg(target_arch = "x86_64")]
+use x86_64::{ELF_MACHINE, prstatus_regs};
+
+/// Size of the ELF header.
+const ELF_HEADER_SIZE: usize = 64;
+/// Size of an ELF program header.
+const PROGRAM_HEADER_SIZE: usize = 56;
+/// Size of the header of an ELF note, before its name.
+const NOTE_HEADER_SIZE: usize = 12;
+
+/// `e_type` of core files.
+const ET_CORE: u16 = 4;
+/// Program header type of loadable segments.
+const PT_LOAD: u32 = 1;
+/// Program header type of note segments.
+const PT_NOTE: u32 = 4;
+/// Read, write and execute permissions of a segment.
+const PF_RWX: u32 = 7;
+/// Note type of the process status, which holds the registers of a vCPU.
+const NT_PRSTATUS: u32 = 1;
+
+/// Offset of `pr_pid` in `struct elf_prstatus`.
+const PRSTATUS_PID_OFFSET: usize = 32;
+/// Offset of `pr_reg` in `struct elf_prstatus`.
+const PRSTATUS_REGS_OFFSET: usize = 112;
+/// Size of the fields following `pr_reg` in `struct elf_prstatus`.
+const PRSTATUS_TRAILER_SIZE: usize = 8;
+
+/// Name of the `VMCOREINFO` note, padded to 4 bytes.
+const VMCOREINFO_NAME: &[u8; 12] = b"VMCOREINFO\0\0";
+/// The `VMCOREINFO` data always starts with the release of the kernel.
+const VMCOREINFO_PREFIX: &[u8] = b"OSRELEASE=";
+
+/// Errors associated with creating a core dump of the guest.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum CoreDumpError {
+    /// Cannot perform {0} on the core dump file: {1}
+    CoreDumpFile(&'static str, io::Error),
+    /// Cannot write guest memory to the core dump file: {0}
+    Memory(#[from] MemoryError),
+    /// Cannot save the state of the vCPUs: {0}
+    VcpuState(#[from] MicrovmStateError),
+}
+
+/// Rounds `len` up to a multiple of 4, the alignment of ELF notes.
+fn note_align(len: usize) -> usize {
+    len.next_multiple_of(4)
+}
+
+/// Builds an ELF note with the given name and descriptor.
+fn note(name: &[u8], n_type: u32, desc: &[u8]) -> Vec<u8> {
+    // The name is stored with its NUL terminator.
+    let namesz = name.len() + 1;
+    let mut note = Vec::with_capacity(NOTE_HEADER_SIZE + note_align(namesz) + desc.len());
+    note.extend_from_slice(&u32::try_from(namesz).unwrap().to_le_bytes());
+    note.extend_from_slice(&u32::try_from(desc.len()).unwrap().to_le_bytes());
+    note.extend_from_slice(&n_type.to_le_bytes());
+    note.extend_from_slice(name);
+    note.resize(NOTE_HEADER_SIZE + note_align(namesz), 0);
+    note.extend_from_slice(desc);
+    note.resize(note_align(note.len()), 0);
+    note
+}
+
+/// Builds the `NT_PRSTATUS` note of a vCPU from its registers, in the order of `pr_reg`.
+fn prstatus_note(cpu_index: usize, regs: &[u64]) -> Vec<u8> {
+    let mut desc = vec![0u8; PRSTATUS_REGS_OFFSET];
+    // Process ids start at 1, there is one per vCPU.
+    let pid = u32::try_from(cpu_index + 1).unwrap();
+    desc[PRSTATUS_PID_OFFSET..PRSTATUS_PID_OFFSET + 4].copy_from_slice(&pid.to_le_bytes());
+    for reg in regs {
+        desc.extend_from_slice(&reg.to_le_bytes());
+    }
+    desc.resize(desc.len() + PRSTATUS_TRAILER_SIZE, 0);
+    note(b"CORE", NT_PRSTATUS, &desc)
+}
+
+/// Builds the header of an ELF core file with `phnum` program headers.
+fn elf_header(phnum: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(ELF_HEADER_SIZE);
+    // Magic, 64 bit class, little endian, current version, System V ABI.
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.resize(16, 0);
+    header.extend_from_slice(&ET_CORE.to_le_bytes());
+    header.extend_from_slice(&ELF_MACHINE.to_le_bytes());
+    // e_version
+    header.extend_from_slice(&1u32.to_le_bytes());
+    // e_entry
+    header.extend_from_slice(&0u64.to_le_bytes());
+    // e_phoff, the program headers follow the ELF header.
+    header.extend_from_slice(&usize_to_u64(ELF_HEADER_SIZE).to_le_bytes());
+    // e_shoff, there are no section headers.
+    header.extend_from_slice(&0u64.to_le_bytes());
+    // e_flags
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&u16::try_from(ELF_HEADER_SIZE).unwrap().to_le_bytes());
+    header.extend_from_slice(&u16::try_from(PROGRAM_HEADER_SIZE).unwrap().to_le_bytes());
+    header.extend_from_slice(&phnum.to_le_bytes());
+    // e_shentsize, e_shnum and e_shstrndx
+    header.resize(ELF_HEADER_SIZE, 0);
+    header
+}
+
+/// Builds an ELF program header.
+fn program_header(
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_align: u64,
+) -> Vec<u8> {
+    let mut header = Vec::with_capacity(PROGRAM_HEADER_SIZE);
+    header.extend_from_slice(&p_type.to_le_bytes());
+    header.extend_from_slice(&p_flags.to_le_bytes());
+    header.extend_from_slice(&p_offset.to_le_bytes());
+    // p_vaddr, the guest virtual addresses of the memory are not known.
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&p_paddr.to_le_bytes());
+    header.extend_from_slice(&p_filesz.to_le_bytes());
+    // p_memsz
+    header.extend_from_slice(&p_filesz.to_le_bytes());
+    header.extend_from_slice(&p_align.to_le_bytes());
+    header
+}
+
+/// Looks for the `VMCOREINFO` note of the guest kernel in guest memory and returns it.
+///
+/// Linux writes this note at the start of pages it allocates at boot, when it is built with
+/// `CONFIG_VMCORE_INFO`. Only the DRAM regions are looked at, since hotpluggable memory can be
+/// unplugged and inaccessible.
+fn find_vmcoreinfo(guest_memory: &GuestMemoryMmap) -> Option<Vec<u8>> {
+    let header_size = NOTE_HEADER_SIZE + VMCOREINFO_NAME.len();
+    guest_memory
+        .iter()
+        .filter(|region| region.region_type == GuestRegionType::Dram)
+        .flat_map(|region| {
+            let start = region.start_addr().raw_value();
+            (start..start + region.len()).step_by(GUEST_PAGE_SIZE)
+        })
+        .find_map(|page| {
+            let mut header = [0u8; NOTE_HEADER_SIZE + VMCOREINFO_NAME.len()];
+            guest_memory
+                .read_slice(&mut header, GuestAddress(page))
+                .ok()?;
+            let word = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+            let descsz = usize::try_from(word(1)).ok()?;
+            // The size of the name includes its NUL terminator, but not its padding.
+            if word(0) != 11
+                || word(2) != 0
+                || header[NOTE_HEADER_SIZE..] != VMCOREINFO_NAME[..]
+                || descsz < VMCOREINFO_PREFIX.len()
+                || descsz > GUEST_PAGE_SIZE - header_size
+            {
+                return None;
+            }
+
+            let mut note = vec![0u8; header_size + descsz];
+            guest_memory
+                .read_slice(&mut note, GuestAddress(page))
+                .ok()?;
+            note[header_size..].starts_with(VMCOREINFO_PREFIX).then(|| {
+                note.resize(note_align(note.len()), 0);
+                note
+            })
+        })
+}
+
+/// Builds the notes of the core dump from the vCPU states and guest memory.
+fn build_notes(vcpu_states: &[VcpuState], guest_memory: &GuestMemoryMmap) -> Vec<u8> {
+    let mut notes: Vec<u8> = vcpu_states
+        .iter()
+        .enumerate()
+        .flat_map(|(index, state)| prstatus_note(index, &prstatus_regs(state)))
+        .collect();
+    if let Some(vmcoreinfo) = find_vmcoreinfo(guest_memory) {
+        notes.extend_from_slice(&vmcoreinfo);
+    }
+    notes
+}
+
+/// Builds the headers and notes of a core dump of `guest_memory`, which is written right after
+/// them, at the returned offset.
+fn build_core_headers(vcpu_states: &[VcpuState], guest_memory: &GuestMemoryMmap) -> (Vec<u8>, u64) {
+    let notes = build_notes(vcpu_states, guest_memory);
+    let phnum = 1 + guest_memory.num_regions();
+    let notes_offset = ELF_HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+    let memory_offset =
+        usize_to_u64((notes_offset + notes.len()).next_multiple_of(GUEST_PAGE_SIZE));
+
+    let mut headers = elf_header(u16::try_from(phnum).unwrap());
+    headers.extend(program_header(
+        PT_NOTE,
+        0,
+        usize_to_u64(notes_offset),
+        0,
+        usize_to_u64(notes.len()),
+        4,
+    ));
+    let mut offset = memory_offset;
+    for region in guest_memory.iter() {
+        headers.extend(program_header(
+            PT_LOAD,
+            PF_RWX,
+            offset,
+            region.start_addr().raw_value(),
+            region.len(),
+            usize_to_u64(GUEST_PAGE_SIZE),
+        ));
+        offset += region.len();
+    }
+    headers.extend(notes);
+    (headers, memory_offset)
+}
+
+/// Writes a core dump of the guest to `path`.
+///
+/// The vCPUs must not be running, they are either paused or stopped after an error.
+pub fn create_core_dump(vmm: &mut Vmm, path: &Path) -> Result<(), CoreDumpError> {
+    use self::CoreDumpError::*;
+
+    // The states of the parked vCPUs, which are not known to the guest, are not dumped.
+    let vcpu_states: Vec<_> = vmm
+        .save_vcpu_states()?
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| !vmm.is_parked(*idx))
+        .map(|(_, state)| state)
+        .collect();
+
+    let guest_memory = vmm.vm.guest_memory();
+    let (headers, memory_offset) = build_core_headers(&vcpu_states, guest_memory);
+    let memory_size: u64 = guest_memory.iter().map(|region| region.len()).sum();
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|err| CoreDumpFile("open", err))?;
+    file.write_all(&headers)
+        .map_err(|err| CoreDumpFile("write", err))?;
+    // Memory which is not written, because it is unplugged or discarded, is left as holes.
+    file.set_len(memory_offset + memory_size)
+        .map_err(|err| CoreDumpFile("set_length", err))?;
+    file.seek(SeekFrom::Start(memory_offset))
+        .map_err(|err| CoreDumpFile("seek", err))?;
+
+    OPERATION_PROGRESS.set_total(memory_size);
+    let discarded = vmm.device_manager.save().discarded_memory_ranges();
+    guest_memory.dump(&mut file, &discarded)?;
+    file.flush().map_err(|err| CoreDumpFile("flush", err))?;
+    file.sync_all().map_err(|err| CoreDumpFile("sync_all", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{multi_region_mem, single_region_mem};
+
+    /// Reads the little endian u64 at `offset`.
+    fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_note() {
+        let note = note(b"CORE", NT_PRSTATUS, &[1, 2, 3]);
+        assert_eq!(
+            note,
+            [
+                5, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, b'C', b'O', b'R', b'E', 0, 0, 0, 0, 1, 2, 3, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prstatus_note() {
+        let regs: Vec<u64> = (1..=27).collect();
+        let note = prstatus_note(2, &regs);
+        let desc = &note[NOTE_HEADER_SIZE + 8..];
+        assert_eq!(
+            desc.len(),
+            PRSTATUS_REGS_OFFSET + 27 * 8 + PRSTATUS_TRAILER_SIZE
+        );
+        assert_eq!(
+            &desc[PRSTATUS_PID_OFFSET..PRSTATUS_PID_OFFSET + 4],
+            &[3, 0, 0, 0]
+        );
+        assert_eq!(read_u64(desc, PRSTATUS_REGS_OFFSET), 1);
+        assert_eq!(read_u64(desc, PRSTATUS_REGS_OFFSET + 26 * 8), 27);
+    }
+
+    #[test]
+    fn test_find_vmcoreinfo() {
+        let guest_memory = single_region_mem(0x10000);
+        assert_eq!(find_vmcoreinfo(&guest_memory), None);
+
+        let vmcoreinfo = note(b"VMCOREINFO", 0, b"OSRELEASE=6.1.0\nPAGESIZE=4096\n");
+        // A note which is not at the start of a page is not found.
+        guest_memory
+            .write_slice(&vmcoreinfo, GuestAddress(0x2010))
+            .unwrap();
+        assert_eq!(find_vmcoreinfo(&guest_memory), None);
+
+        guest_memory
+            .write_slice(&vmcoreinfo, GuestAddress(0x3000))
+            .unwrap();
+        assert_eq!(find_vmcoreinfo(&guest_memory), Some(vmcoreinfo));
+
+        // Notes which do not hold the kernel release are ignored.
+        let guest_memory = single_region_mem(0x10000);
+        let other = note(b"VMCOREINFO", 0, b"CRASHTIME=1\n");
+        guest_memory
+            .write_slice(&other, GuestAddress(0x3000))
+            .unwrap();
+        assert_eq!(find_vmcoreinfo(&guest_memory), None);
+    }
+
+    #[test]
+    fn test_build_core_headers() {
+        let guest_memory = multi_region_mem(&[
+            (GuestAddress(0), 0x10000),
+            (GuestAddress(0x100000), 0x20000),
+        ]);
+        let (headers, memory_offset) = build_core_headers(&[], &guest_memory);
+        assert_eq!(memory_offset, usize_to_u64(GUEST_PAGE_SIZE));
+
+        assert_eq!(&headers[..4], b"\x7fELF");
+        assert_eq!(&headers[16..18], &ET_CORE.to_le_bytes());
+        assert_eq!(&headers[18..20], &ELF_MACHINE.to_le_bytes());
+        // e_phnum
+        assert_eq!(&headers[56..58], &[3, 0]);
+
+        let phdr = |i: usize| &headers[ELF_HEADER_SIZE + i * PROGRAM_HEADER_SIZE..];
+        // The note segment is empty, without vCPUs nor VMCOREINFO.
+        assert_eq!(&phdr(0)[..4], &PT_NOTE.to_le_bytes());
+        assert_eq!(read_u64(phdr(0), 32), 0);
+
+        assert_eq!(&phdr(1)[..4], &PT_LOAD.to_le_bytes());
+        assert_eq!(read_u64(phdr(1), 8), memory_offset);
+        assert_eq!(read_u64(phdr(1), 24), 0);
+        assert_eq!(read_u64(phdr(1), 32), 0x10000);
+        assert_eq!(&phdr(2)[..4], &PT_LOAD.to_le_bytes());
+        assert_eq!(read_u64(phdr(2), 8), memory_offset + 0x10000);
+        assert_eq!(read_u64(phdr(2), 24), 0x100000);
+        assert_eq!(read_u64(phdr(2), 32), 0x20000);
+    }
+}
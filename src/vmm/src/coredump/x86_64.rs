@@ -0,0 +1,64 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::vstate::vcpu::VcpuState;
+
+/// `e_machine` of x86_64 ELF files.
+pub const ELF_MACHINE: u16 = 62;
+
+/// Returns the registers of a vCPU in the order of `struct user_regs_struct`.
+pub fn prstatus_regs(state: &VcpuState) -> Vec<u64> {
+    let regs = &state.regs;
+    let sregs = &state.sregs;
+    vec![
+        regs.r15,
+        regs.r14,
+        regs.r13,
+        regs.r12,
+        regs.rbp,
+        regs.rbx,
+        regs.r11,
+        regs.r10,
+        regs.r9,
+        regs.r8,
+        regs.rax,
+        regs.rcx,
+        regs.rdx,
+        regs.rsi,
+        regs.rdi,
+        // orig_rax, the vCPU is not in a system call.
+        u64::MAX,
+        regs.rip,
+        u64::from(sregs.cs.selector),
+        regs.rflags,
+        regs.rsp,
+        u64::from(sregs.ss.selector),
+        sregs.fs.base,
+        sregs.gs.base,
+        u64::from(sregs.ds.selector),
+        u64::from(sregs.es.selector),
+        u64::from(sregs.fs.selector),
+        u64::from(sregs.gs.selector),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prstatus_regs() {
+        let mut state = VcpuState::default();
+        state.regs.r15 = 1;
+        state.regs.rip = 2;
+        state.sregs.cs.selector = 0x10;
+        state.sregs.gs.selector = 0x18;
+
+        let regs = prstatus_regs(&state);
+        assert_eq!(regs.len(), 27);
+        assert_eq!(regs[0], 1);
+        assert_eq!(regs[16], 2);
+        assert_eq!(regs[17], 0x10);
+        assert_eq!(regs[26], 0x18);
+    }
+}
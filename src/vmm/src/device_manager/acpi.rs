@@ -1,12 +1,18 @@
 // Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::sync::{Arc, Mutex};
+
 #[cfg(target_arch = "x86_64")]
 use acpi_tables::{Aml, aml};
 
 use crate::Vm;
+use crate::devices::acpi::cpu_hotplug::{
+    CPU_HOTPLUG_MMIO_SIZE, CpuHotplugController, CpuHotplugError,
+};
 use crate::devices::acpi::vmclock::{VmClock, VmClockError};
 use crate::devices::acpi::vmgenid::{VmGenId, VmGenIdError};
+use crate::vstate::bus::BusError;
 use crate::vstate::memory::GuestMemoryMmap;
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -17,6 +23,10 @@ pub enum ACPIDeviceError {
     VmClock(#[from] VmClockError),
     /// Could not register IRQ with KVM: {0}
     RegisterIrq(#[from] kvm_ioctls::Error),
+    /// CPU hotplug controller: {0}
+    CpuHotplug(#[from] CpuHotplugError),
+    /// Could not insert device in bus: {0}
+    Bus(#[from] BusError),
 }
 
 // Although both VMGenID and VMClock devices are always present, they should be instantiated when
@@ -27,6 +37,12 @@ pub struct ACPIDeviceManager {
     vmgenid: Option<VmGenId>,
     /// VMclock device
     vmclock: Option<VmClock>,
+    /// GSI of the PCI hotplug controller, if PCI is enabled. It is only needed to build the GED
+    /// at boot, so it is not saved in snapshots.
+    #[cfg(target_arch = "x86_64")]
+    pci_hotplug_gsi: Option<u32>,
+    /// ACPI controller used to hotplug and unplug vCPUs, if vCPU hotplug is enabled.
+    pub cpu_hotplug: Option<Arc<Mutex<CpuHotplugController>>>,
 }
 
 impl ACPIDeviceManager {
@@ -35,6 +51,9 @@ impl ACPIDeviceManager {
         ACPIDeviceManager {
             vmgenid: Some(vmgenid),
             vmclock: Some(vmclock),
+            #[cfg(target_arch = "x86_64")]
+            pci_hotplug_gsi: None,
+            cpu_hotplug: None,
         }
     }
 
@@ -48,6 +67,41 @@ impl ACPIDeviceManager {
         Ok(())
     }
 
+    /// Let the GED notify the PCI hotplug controller of the interrupts on `gsi`.
+    #[cfg(target_arch = "x86_64")]
+    pub fn set_pci_hotplug_gsi(&mut self, gsi: u32) {
+        self.pci_hotplug_gsi = Some(gsi);
+    }
+
+    /// Attaches the controller used to hotplug vCPUs, for `max_vcpus` vCPUs of which the first
+    /// `vcpu_count` are online.
+    #[cfg(target_arch = "x86_64")]
+    pub fn attach_cpu_hotplug(
+        &mut self,
+        vm: &Vm,
+        vcpu_count: u8,
+        max_vcpus: u8,
+    ) -> Result<(), ACPIDeviceError> {
+        let controller =
+            CpuHotplugController::new(&mut vm.resource_allocator(), vcpu_count, max_vcpus)?;
+        self.register_cpu_hotplug(vm, controller)
+    }
+
+    pub(crate) fn register_cpu_hotplug(
+        &mut self,
+        vm: &Vm,
+        controller: CpuHotplugController,
+    ) -> Result<(), ACPIDeviceError> {
+        vm.register_irq(&controller.interrupt_evt, controller.gsi)?;
+        let mmio_address = controller.mmio_address;
+        let controller = Arc::new(Mutex::new(controller));
+        vm.common
+            .mmio_bus
+            .insert(controller.clone(), mmio_address, CPU_HOTPLUG_MMIO_SIZE)?;
+        self.cpu_hotplug = Some(controller);
+        Ok(())
+    }
+
     pub fn vmgenid(&self) -> &VmGenId {
         self.vmgenid.as_ref().expect("Missing VMGenID device")
     }
@@ -92,47 +146,79 @@ impl Aml for ACPIDeviceManager {
         self.vmgenid().append_aml_bytes(v)?;
         // AML for [`VmClock`] device.
         self.vmclock().append_aml_bytes(v)?;
+        // AML for [`CpuHotplugController`] device.
+        let cpu_hotplug = self
+            .cpu_hotplug
+            .as_ref()
+            .map(|controller| controller.lock().expect("Poisoned lock"));
+        if let Some(controller) = &cpu_hotplug {
+            controller.append_aml_bytes(v)?;
+        }
 
         // Create the AML for the GED interrupt handler
+        let mut interrupts = vec![
+            aml::Interrupt::new(true, true, false, false, self.vmgenid().gsi),
+            aml::Interrupt::new(true, true, false, false, self.vmclock().gsi),
+        ];
+        if let Some(gsi) = self.pci_hotplug_gsi {
+            interrupts.push(aml::Interrupt::new(true, true, false, false, gsi));
+        }
+        if let Some(controller) = &cpu_hotplug {
+            interrupts.push(aml::Interrupt::new(
+                true,
+                true,
+                false,
+                false,
+                controller.gsi,
+            ));
+        }
+        let interrupts: Vec<&dyn Aml> = interrupts.iter().map(|irq| irq as &dyn Aml).collect();
+
+        // We know that the maximum IRQ number fits in a u8. We have up to
+        // 32 IRQs in x86 and up to 128 in ARM (look into
+        // `vmm::crate::arch::layout::GSI_LEGACY_END`). `vmgenid.gsi`, `vmclock.gsi`
+        // and the GSIs of the PCI and CPU hotplug controllers can safely be cast to
+        // `u8` without truncation, so we let clippy know.
+        #[allow(clippy::cast_possible_truncation)]
+        let vmgenid_gsi = self.vmgenid().gsi as u8;
+        #[allow(clippy::cast_possible_truncation)]
+        let vmclock_gsi = self.vmclock().gsi as u8;
+        #[allow(clippy::cast_possible_truncation)]
+        let pci_hotplug_gsi = self.pci_hotplug_gsi.map(|gsi| gsi as u8);
+        #[allow(clippy::cast_possible_truncation)]
+        let cpu_hotplug_gsi = cpu_hotplug.as_ref().map(|controller| controller.gsi as u8);
+
+        let vmgenid_notify = aml::Notify::new(&aml::Path::new("\\_SB_.VGEN")?, &0x80usize);
+        let vmclock_notify = aml::Notify::new(&aml::Path::new("\\_SB_.VCLK")?, &0x80usize);
+        let pci_hotplug_scan = aml::MethodCall::new("\\_SB_.PHPR.PSCN".try_into()?, vec![]);
+        let cpu_hotplug_scan = aml::MethodCall::new("\\_SB_.CPUS.CSCN".try_into()?, vec![]);
+        let vmgenid_equal = aml::Equal::new(&aml::Arg(0), &vmgenid_gsi);
+        let vmclock_equal = aml::Equal::new(&aml::Arg(0), &vmclock_gsi);
+        let pci_hotplug_equal = pci_hotplug_gsi
+            .as_ref()
+            .map(|gsi| aml::Equal::new(&aml::Arg(0), gsi));
+        let cpu_hotplug_equal = cpu_hotplug_gsi
+            .as_ref()
+            .map(|gsi| aml::Equal::new(&aml::Arg(0), gsi));
+
+        let mut events = vec![
+            aml::If::new(&vmgenid_equal, vec![&vmgenid_notify]),
+            aml::If::new(&vmclock_equal, vec![&vmclock_notify]),
+        ];
+        if let Some(pci_hotplug_equal) = &pci_hotplug_equal {
+            events.push(aml::If::new(pci_hotplug_equal, vec![&pci_hotplug_scan]));
+        }
+        if let Some(cpu_hotplug_equal) = &cpu_hotplug_equal {
+            events.push(aml::If::new(cpu_hotplug_equal, vec![&cpu_hotplug_scan]));
+        }
+        let events: Vec<&dyn Aml> = events.iter().map(|event| event as &dyn Aml).collect();
+
         aml::Device::new(
             "_SB_.GED_".try_into()?,
             vec![
                 &aml::Name::new("_HID".try_into()?, &"ACPI0013")?,
-                &aml::Name::new(
-                    "_CRS".try_into()?,
-                    &aml::ResourceTemplate::new(vec![
-                        &aml::Interrupt::new(true, true, false, false, self.vmgenid().gsi),
-                        &aml::Interrupt::new(true, true, false, false, self.vmclock().gsi),
-                    ]),
-                )?,
-                // We know that the maximum IRQ number fits in a u8. We have up to
-                // 32 IRQs in x86 and up to 128 in ARM (look into
-                // `vmm::crate::arch::layout::GSI_LEGACY_END`). Both `vmgenid.gsi`
-                // and `vmclock.gsi` can safely be cast to `u8` without truncation,
-                // so we let clippy know.
-                &aml::Method::new(
-                    "_EVT".try_into()?,
-                    1,
-                    true,
-                    vec![
-                        &aml::If::new(
-                            #[allow(clippy::cast_possible_truncation)]
-                            &aml::Equal::new(&aml::Arg(0), &(self.vmgenid().gsi as u8)),
-                            vec![&aml::Notify::new(
-                                &aml::Path::new("\\_SB_.VGEN")?,
-                                &0x80usize,
-                            )],
-                        ),
-                        &aml::If::new(
-                            #[allow(clippy::cast_possible_truncation)]
-                            &aml::Equal::new(&aml::Arg(0), &(self.vmclock().gsi as u8)),
-                            vec![&aml::Notify::new(
-                                &aml::Path::new("\\_SB_.VCLK")?,
-                                &0x80usize,
-                            )],
-                        ),
-                    ],
-                ),
+                &aml::Name::new("_CRS".try_into()?, &aml::ResourceTemplate::new(interrupts))?,
+                &aml::Method::new("_EVT".try_into()?, 1, true, events),
             ],
         )
         .append_aml_bytes(v)
@@ -12,7 +12,7 @@ use acpi_tables::aml::AmlError;
 use acpi_tables::{Aml, aml};
 
 use crate::Vm;
-use crate::devices::legacy::{I8042Device, SerialDevice};
+use crate::devices::legacy::{I8042Device, PvPanicDevice, SerialDevice};
 use crate::vstate::bus::BusError;
 
 /// Errors corresponding to the `PortIODeviceManager`.
@@ -25,13 +25,15 @@ pub enum LegacyDeviceError {
 }
 
 /// The `PortIODeviceManager` is a wrapper that is used for registering legacy devices
-/// on an I/O Bus. It currently manages the uart and i8042 devices.
+/// on an I/O Bus. It currently manages the uart, i8042 and pvpanic devices.
 #[derive(Debug)]
 pub struct PortIODeviceManager {
     // BusDevice::Serial
     pub stdio_serial: Arc<Mutex<SerialDevice>>,
     // BusDevice::I8042Device
     pub i8042: Arc<Mutex<I8042Device>>,
+    // BusDevice::PvPanicDevice
+    pub pvpanic: Arc<Mutex<PvPanicDevice>>,
 }
 
 impl PortIODeviceManager {
@@ -50,6 +52,10 @@ impl PortIODeviceManager {
     const I8042_KDB_DATA_REGISTER_ADDRESS: u64 = 0x060;
     /// i8042 keyboard data register size.
     const I8042_KDB_DATA_REGISTER_SIZE: u64 = 0x5;
+    /// pvpanic port address. See <https://www.qemu.org/docs/master/specs/pvpanic.html>.
+    const PVPANIC_PORT_ADDRESS: u64 = 0x505;
+    /// Size of the pvpanic port.
+    const PVPANIC_PORT_SIZE: u64 = 0x1;
 
     /// Register supported legacy devices.
     pub fn register_devices(&mut self, vm: &Vm) -> Result<(), LegacyDeviceError> {
@@ -64,6 +70,11 @@ impl PortIODeviceManager {
             Self::I8042_KDB_DATA_REGISTER_ADDRESS,
             Self::I8042_KDB_DATA_REGISTER_SIZE,
         )?;
+        io_bus.insert(
+            self.pvpanic.clone(),
+            Self::PVPANIC_PORT_ADDRESS,
+            Self::PVPANIC_PORT_SIZE,
+        )?;
 
         vm.register_irq(
             self.stdio_serial
@@ -138,6 +149,23 @@ impl PortIODeviceManager {
                 )?,
             ],
         )
+        .append_aml_bytes(bytes)?;
+        // Setup pvpanic
+        aml::Device::new(
+            "_SB_.PEVT".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &"QEMU0001")?,
+                &aml::Name::new(
+                    "_CRS".try_into()?,
+                    &aml::ResourceTemplate::new(vec![&aml::Io::new(
+                        Self::PVPANIC_PORT_ADDRESS.try_into().unwrap(),
+                        Self::PVPANIC_PORT_ADDRESS.try_into().unwrap(),
+                        1u8,
+                        Self::PVPANIC_PORT_SIZE.try_into().unwrap(),
+                    )]),
+                )?,
+            ],
+        )
         .append_aml_bytes(bytes)
     }
 }
@@ -171,6 +199,7 @@ mod tests {
             i8042: Arc::new(Mutex::new(
                 I8042Device::new(EventFd::new(libc::EFD_NONBLOCK).unwrap()).unwrap(),
             )),
+            pvpanic: Arc::new(Mutex::new(PvPanicDevice::new().unwrap())),
         };
         ldm.register_devices(&vm).unwrap();
     }
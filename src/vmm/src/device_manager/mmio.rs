@@ -23,7 +23,7 @@ use crate::arch::BOOT_DEVICE_MEM_START;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::{RTC_MEM_START, SERIAL_MEM_START};
 #[cfg(target_arch = "aarch64")]
-use crate::devices::legacy::{RTCDevice, SerialDevice};
+use crate::devices::legacy::{PvPanicDevice, RTCDevice, SerialDevice};
 use crate::devices::pseudo::BootTimer;
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::transport::mmio::MmioTransport;
@@ -131,6 +131,9 @@ pub struct MMIODeviceManager {
     #[cfg(target_arch = "aarch64")]
     /// Serial device on Aarch64 platforms
     pub(crate) serial: Option<MMIODevice<SerialDevice>>,
+    #[cfg(target_arch = "aarch64")]
+    /// pvpanic device on Aarch64 platforms
+    pub(crate) pvpanic: Option<MMIODevice<PvPanicDevice>>,
     #[cfg(target_arch = "x86_64")]
     // We create the AML byte code for every VirtIO device in the order we build
     // it, so that we ensure the root block device is appears first in the DSDT.
@@ -365,6 +368,37 @@ impl MMIODeviceManager {
         Ok(())
     }
 
+    #[cfg(target_arch = "aarch64")]
+    /// Create and register a MMIO pvpanic device at the specified MMIO configuration if
+    /// given as parameter, otherwise allocate a new MMIO resources for it.
+    pub fn register_mmio_pvpanic(
+        &mut self,
+        vm: &Vm,
+        pvpanic: Arc<Mutex<PvPanicDevice>>,
+        device_info_opt: Option<MMIODeviceInfo>,
+    ) -> Result<(), MmioError> {
+        // Create a new MMIODeviceInfo object on boot path or unwrap the
+        // existing object on restore path. The device doesn't use interrupts.
+        let device_info = match device_info_opt {
+            Some(device_info) => device_info,
+            None => self.allocate_mmio_resources(&mut vm.resource_allocator(), 0)?,
+        };
+
+        let device = MMIODevice {
+            resources: device_info,
+            inner: pvpanic,
+            sub_id: None,
+        };
+
+        vm.common.mmio_bus.insert(
+            device.inner.clone(),
+            device.resources.addr,
+            device.resources.len,
+        )?;
+        self.pvpanic = Some(device);
+        Ok(())
+    }
+
     /// Register a boot timer device.
     pub fn register_mmio_boot_timer(
         &mut self,
@@ -441,6 +475,11 @@ impl MMIODeviceManager {
     pub fn serial_device_info(&self) -> Option<&MMIODeviceInfo> {
         self.serial.as_ref().map(|device| &device.resources)
     }
+
+    #[cfg(target_arch = "aarch64")]
+    pub fn pvpanic_device_info(&self) -> Option<&MMIODeviceInfo> {
+        self.pvpanic.as_ref().map(|device| &device.resources)
+    }
 }
 
 #[cfg(test)]
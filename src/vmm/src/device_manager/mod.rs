@@ -29,12 +29,13 @@ use crate::device_manager::acpi::ACPIDeviceError;
 use crate::devices::legacy::I8042Device;
 #[cfg(target_arch = "aarch64")]
 use crate::devices::legacy::RTCDevice;
-use crate::devices::legacy::SerialDevice;
 use crate::devices::legacy::serial::{SerialOut, SerialOutInner};
+use crate::devices::legacy::{PvPanicDevice, SerialDevice, SerialSocket, SerialSocketOutput};
 use crate::devices::pseudo::BootTimer;
 use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::balloon::BalloonError;
 use crate::devices::virtio::block::BlockError;
+use crate::devices::virtio::console::persist::ConsolePersistError;
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::mem::persist::VirtioMemPersistError;
 use crate::devices::virtio::net::persist::NetPersistError;
@@ -48,7 +49,7 @@ use crate::snapshot::Persist;
 use crate::utils::open_file_nonblock;
 use crate::vmm_config::mmds::MmdsConfigError;
 use crate::vstate::bus::BusError;
-use crate::vstate::memory::GuestMemoryMmap;
+use crate::vstate::memory::{DiscardedRanges, GuestMemoryMmap};
 use crate::{EventManager, Vm};
 
 /// ACPI device manager.
@@ -89,6 +90,9 @@ pub enum AttachDeviceError {
     #[cfg(target_arch = "aarch64")]
     /// Error creating serial device: {0}
     CreateSerial(#[from] std::io::Error),
+    #[cfg(target_arch = "aarch64")]
+    /// Error creating pvpanic device: {0}
+    CreatePvPanic(std::io::Error),
     /// Error attach PCI device: {0}
     PciTransport(#[from] PciManagerError),
 }
@@ -134,18 +138,32 @@ impl DeviceManager {
     fn setup_serial_device(
         event_manager: &mut EventManager,
         output: Option<&PathBuf>,
+        socket: Option<&PathBuf>,
         state: Option<&serial::SerialState>,
         rate_limiter: Option<TokenBucket>,
     ) -> Result<Arc<Mutex<SerialDevice>>, std::io::Error> {
-        let (serial_in, serial_out) = match output {
-            Some(path) => (
+        let socket = match socket {
+            Some(path) => Some((
+                SerialSocket::bind(path)?,
+                Arc::new(Mutex::new(SerialSocketOutput::default())),
+            )),
+            None => None,
+        };
+
+        let (serial_in, serial_out) = match (output, &socket) {
+            // The input of the client attached to the socket is forwarded by the socket backend.
+            (_, Some((_, socket_output))) => (
+                None,
+                SerialOut::new(SerialOutInner::Socket(socket_output.clone()), rate_limiter),
+            ),
+            (Some(path), None) => (
                 None,
                 SerialOut::new(
                     SerialOutInner::File(open_file_nonblock(path)?),
                     rate_limiter,
                 ),
             ),
-            None => {
+            (None, None) => {
                 Self::set_stdout_nonblocking();
 
                 (
@@ -157,6 +175,10 @@ impl DeviceManager {
 
         let serial = Arc::new(Mutex::new(SerialDevice::new(serial_in, serial_out, state)?));
         event_manager.add_subscriber(serial.clone());
+        if let Some((listener, socket_output)) = socket {
+            let serial_socket = SerialSocket::new(listener, socket_output, serial.clone())?;
+            event_manager.add_subscriber(Arc::new(Mutex::new(serial_socket)));
+        }
         Ok(serial)
     }
 
@@ -183,12 +205,29 @@ impl DeviceManager {
         }
     }
 
+    /// Returns the pvpanic device, if any.
+    pub(crate) fn pvpanic(&self) -> Option<&Arc<Mutex<PvPanicDevice>>> {
+        #[cfg(target_arch = "aarch64")]
+        {
+            self.mmio_devices
+                .pvpanic
+                .as_ref()
+                .map(|device| &device.inner)
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            Some(&self.legacy_devices.pvpanic)
+        }
+    }
+
     #[cfg(target_arch = "x86_64")]
     fn create_legacy_devices(
         event_manager: &mut EventManager,
         vcpus_exit_evt: &EventFd,
         vm: &Vm,
         serial_output: Option<&PathBuf>,
+        serial_socket: Option<&PathBuf>,
         serial_state: Option<&serial::SerialState>,
         serial_rate_limiter: Option<TokenBucket>,
     ) -> Result<PortIODeviceManager, DeviceManagerCreateError> {
@@ -196,6 +235,7 @@ impl DeviceManager {
         let serial = Self::setup_serial_device(
             event_manager,
             serial_output,
+            serial_socket,
             serial_state,
             serial_rate_limiter,
         )?;
@@ -204,11 +244,14 @@ impl DeviceManager {
             .map_err(DeviceManagerCreateError::EventFd)?;
         // Create keyboard emulator for reset event
         let i8042 = Arc::new(Mutex::new(I8042Device::new(reset_evt)?));
+        // Create pvpanic device for guest panic notifications
+        let pvpanic = Arc::new(Mutex::new(PvPanicDevice::new()?));
 
         // create pio dev manager with legacy devices
         let mut legacy_devices = PortIODeviceManager {
             stdio_serial: serial,
             i8042,
+            pvpanic,
         };
         legacy_devices.register_devices(vm)?;
         Ok(legacy_devices)
@@ -220,6 +263,7 @@ impl DeviceManager {
         vcpus_exit_evt: &EventFd,
         vm: &Vm,
         serial_output: Option<&PathBuf>,
+        serial_socket: Option<&PathBuf>,
         serial_rate_limiter: Option<TokenBucket>,
     ) -> Result<Self, DeviceManagerCreateError> {
         #[cfg(target_arch = "x86_64")]
@@ -228,6 +272,7 @@ impl DeviceManager {
             vcpus_exit_evt,
             vm,
             serial_output,
+            serial_socket,
             None,
             serial_rate_limiter,
         )?;
@@ -316,6 +361,7 @@ impl DeviceManager {
         event_manager: &mut EventManager,
         cmdline: &mut Cmdline,
         serial_out_path: Option<&PathBuf>,
+        serial_socket_path: Option<&PathBuf>,
         serial_rate_limiter: Option<TokenBucket>,
     ) -> Result<(), AttachDeviceError> {
         // Serial device setup.
@@ -330,6 +376,7 @@ impl DeviceManager {
             let serial = Self::setup_serial_device(
                 event_manager,
                 serial_out_path,
+                serial_socket_path,
                 None,
                 serial_rate_limiter,
             )?;
@@ -339,6 +386,11 @@ impl DeviceManager {
 
         let rtc = Arc::new(Mutex::new(RTCDevice::new()));
         self.mmio_devices.register_mmio_rtc(vm, rtc, None)?;
+
+        let pvpanic = Arc::new(Mutex::new(
+            PvPanicDevice::new().map_err(AttachDeviceError::CreatePvPanic)?,
+        ));
+        self.mmio_devices.register_mmio_pvpanic(vm, pvpanic, None)?;
         Ok(())
     }
 
@@ -347,6 +399,29 @@ impl DeviceManager {
         self.pci_devices.attach_pci_segment(vm)
     }
 
+    /// Attaches the controller used to hotplug PCI devices and routes its interrupt through the
+    /// GED device.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn attach_pci_hotplug_controller(&mut self, vm: &Vm) -> Result<(), PciManagerError> {
+        let gsi = self.pci_devices.attach_hotplug_controller(vm)?;
+        self.acpi_devices.set_pci_hotplug_gsi(gsi);
+        Ok(())
+    }
+
+    /// Attaches the controller used to hotplug vCPUs and routes its interrupt through the GED
+    /// device.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn attach_cpu_hotplug_controller(
+        &mut self,
+        vm: &Vm,
+        vcpu_count: u8,
+        max_vcpus: u8,
+    ) -> Result<(), AttachDeviceError> {
+        self.acpi_devices
+            .attach_cpu_hotplug(vm, vcpu_count, max_vcpus)?;
+        Ok(())
+    }
+
     /// Artificially kick VirtIO devices as if they had external events.
     pub fn kick_virtio_devices(&self) {
         info!("Artificially kick devices");
@@ -479,6 +554,29 @@ pub struct DevicesState {
     pub serial_state: Option<persist::SerialState>,
 }
 
+impl DevicesState {
+    /// Guest memory ranges that were discarded by the balloon or unplugged from the virtio-mem
+    /// device, and hence hold no data.
+    pub fn discarded_memory_ranges(&self) -> DiscardedRanges {
+        let mut ranges = DiscardedRanges::default();
+        let balloons = [
+            &self.mmio_state.balloon_device,
+            &self.pci_state.balloon_device,
+        ];
+        for balloon in balloons.into_iter().flatten() {
+            ranges.extend(balloon.device_state.discarded_ranges());
+        }
+        let memory_devices = [
+            &self.mmio_state.memory_device,
+            &self.pci_state.memory_device,
+        ];
+        for memory_device in memory_devices.into_iter().flatten() {
+            ranges.extend(&memory_device.device_state.unplugged_ranges());
+        }
+        ranges
+    }
+}
+
 /// Errors for (de)serialization of the devices.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum DevicePersistError {
@@ -507,6 +605,8 @@ pub enum DevicePersistError {
     MmdsConfig(#[from] MmdsConfigError),
     /// Entropy: {0}
     Entropy(#[from] EntropyPersistError),
+    /// Console: {0}
+    Console(#[from] ConsolePersistError),
     /// Pmem: {0}
     Pmem(#[from] PmemPersistError),
     /// virtio-mem: {0}
@@ -578,6 +678,7 @@ impl<'a> Persist<'a> for DeviceManager {
             constructor_args.vcpus_exit_evt,
             constructor_args.vm,
             constructor_args.vm_resources.serial_out_path.as_ref(),
+            constructor_args.vm_resources.serial_socket_path.as_ref(),
             serial_state.as_ref(),
             constructor_args.vm_resources.serial_rate_limiter(),
         )?;
@@ -644,6 +745,7 @@ pub(crate) mod tests {
             i8042: Arc::new(Mutex::new(
                 I8042Device::new(EventFd::new(libc::EFD_NONBLOCK).unwrap()).unwrap(),
             )),
+            pvpanic: Arc::new(Mutex::new(PvPanicDevice::new().unwrap())),
         };
 
         DeviceManager {
@@ -660,22 +762,39 @@ pub(crate) mod tests {
     fn test_attach_legacy_serial() {
         let mut vmm = default_vmm();
         assert!(vmm.device_manager.mmio_devices.rtc.is_none());
+        assert!(vmm.device_manager.mmio_devices.pvpanic.is_none());
         assert!(vmm.device_manager.mmio_devices.serial.is_none());
 
         let mut cmdline = Cmdline::new(4096).unwrap();
         let mut event_manager = EventManager::new().unwrap();
         vmm.device_manager
-            .attach_legacy_devices_aarch64(&vmm.vm, &mut event_manager, &mut cmdline, None, None)
+            .attach_legacy_devices_aarch64(
+                &vmm.vm,
+                &mut event_manager,
+                &mut cmdline,
+                None,
+                None,
+                None,
+            )
             .unwrap();
         assert!(vmm.device_manager.mmio_devices.rtc.is_some());
+        assert!(vmm.device_manager.mmio_devices.pvpanic.is_some());
         assert!(vmm.device_manager.mmio_devices.serial.is_none());
 
         let mut vmm = default_vmm();
         cmdline.insert("console", "/dev/blah").unwrap();
         vmm.device_manager
-            .attach_legacy_devices_aarch64(&vmm.vm, &mut event_manager, &mut cmdline, None, None)
+            .attach_legacy_devices_aarch64(
+                &vmm.vm,
+                &mut event_manager,
+                &mut cmdline,
+                None,
+                None,
+                None,
+            )
             .unwrap();
         assert!(vmm.device_manager.mmio_devices.rtc.is_some());
+        assert!(vmm.device_manager.mmio_devices.pvpanic.is_some());
         assert!(vmm.device_manager.mmio_devices.serial.is_some());
 
         assert!(
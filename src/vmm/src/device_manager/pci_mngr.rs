@@ -6,17 +6,22 @@ use std::fmt::Debug;
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex};
 
-use event_manager::{MutEventSubscriber, SubscriberOps};
-use log::{debug, warn};
+use event_manager::{MutEventSubscriber, SubscriberId, SubscriberOps};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 
 use super::persist::MmdsState;
 use crate::device_manager::DevicePersistError;
 use crate::devices::pci::PciSegment;
+use crate::devices::pci::hotplug::{
+    PCI_HOTPLUG_MMIO_SIZE, PciHotplugController, PciHotplugControllerState, PciHotplugError,
+};
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::balloon::persist::{BalloonConstructorArgs, BalloonState};
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::block::persist::{BlockConstructorArgs, BlockState};
+use crate::devices::virtio::console::Console;
+use crate::devices::virtio::console::persist::{ConsoleConstructorArgs, ConsoleState};
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::mem::VirtioMem;
 use crate::devices::virtio::mem::persist::{VirtioMemConstructorArgs, VirtioMemState};
@@ -33,6 +38,7 @@ use crate::devices::virtio::vsock::persist::{
     VsockConstructorArgs, VsockState, VsockUdsConstructorArgs,
 };
 use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
+use crate::logger::{EVENT_LOG, EventKind};
 use crate::pci::PciSBDF;
 use crate::pci::bus::PciRootError;
 use crate::resources::VmResources;
@@ -43,12 +49,27 @@ use crate::vstate::interrupts::InterruptError;
 use crate::vstate::memory::GuestMemoryMmap;
 use crate::{EventManager, Vm};
 
+/// Change of the event manager subscribers, following the hotplug or the removal of a device
+/// while the microVM runs.
+#[derive(Debug)]
+pub enum SubscriberUpdate {
+    /// Subscribe the VirtIO device of a hotplugged PCI device.
+    Add(Arc<Mutex<VirtioPciDevice>>),
+    /// Unsubscribe the VirtIO device of a removed PCI device.
+    Remove(SubscriberId),
+}
+
 #[derive(Debug, Default)]
 pub struct PciDevices {
     /// PCIe segment of the VMM, if PCI is enabled. We currently support a single PCIe segment.
     pub pci_segment: Option<PciSegment>,
     /// All VirtIO PCI devices of the system
     pub virtio_devices: HashMap<(VirtioDeviceType, String), Arc<Mutex<VirtioPciDevice>>>,
+    /// ACPI controller used to hotplug and unplug devices while the microVM runs.
+    pub hotplug_controller: Option<Arc<Mutex<PciHotplugController>>>,
+    /// Subscriber changes to apply to the event manager, which is not reachable while devices
+    /// are hotplugged or removed.
+    pub subscriber_updates: Vec<SubscriberUpdate>,
 }
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -65,6 +86,10 @@ pub enum PciManagerError {
     VirtioPciDevice(#[from] VirtioPciDeviceError),
     /// KVM error: {0}
     Kvm(#[from] vmm_sys_util::errno::Error),
+    /// PCI hotplug error: {0}
+    Hotplug(#[from] PciHotplugError),
+    /// PCI hotplug is not available.
+    HotplugUnavailable,
 }
 
 impl PciDevices {
@@ -85,6 +110,30 @@ impl PciDevices {
         Ok(())
     }
 
+    /// Attaches the ACPI controller used to hotplug devices, and returns its GSI.
+    #[cfg(target_arch = "x86_64")]
+    pub fn attach_hotplug_controller(&mut self, vm: &Vm) -> Result<u32, PciManagerError> {
+        let controller = PciHotplugController::new(&mut vm.resource_allocator())?;
+        let gsi = controller.gsi;
+        self.register_hotplug_controller(vm, controller)?;
+        Ok(gsi)
+    }
+
+    fn register_hotplug_controller(
+        &mut self,
+        vm: &Vm,
+        controller: PciHotplugController,
+    ) -> Result<(), PciManagerError> {
+        vm.register_irq(&controller.interrupt_evt, controller.gsi)?;
+        let mmio_address = controller.mmio_address;
+        let controller = Arc::new(Mutex::new(controller));
+        vm.common
+            .mmio_bus
+            .insert(controller.clone(), mmio_address, PCI_HOTPLUG_MMIO_SIZE)?;
+        self.hotplug_controller = Some(controller);
+        Ok(())
+    }
+
     fn register_bars_with_bus(
         vm: &Vm,
         virtio_device: &Arc<Mutex<VirtioPciDevice>>,
@@ -111,7 +160,7 @@ impl PciDevices {
         id: String,
         sbdf: PciSBDF,
         virtio_device: Arc<Mutex<VirtioPciDevice>>,
-        event_manager: &mut EventManager,
+        event_manager: Option<&mut EventManager>,
     ) -> Result<(), PciManagerError> {
         // We should only be reaching this point if PCI is enabled
         let pci_segment = self.pci_segment.as_ref().unwrap();
@@ -130,8 +179,15 @@ impl PciDevices {
         let mut device = virtio_device.lock().expect("Poisoned lock");
         device.register_notification_ioevent(vm)?;
 
-        let sub_id = event_manager.add_subscriber(device.virtio_device());
-        device.sub_id = Some(sub_id);
+        match event_manager {
+            Some(event_manager) => {
+                let sub_id = event_manager.add_subscriber(device.virtio_device());
+                device.sub_id = Some(sub_id);
+            }
+            None => self
+                .subscriber_updates
+                .push(SubscriberUpdate::Add(virtio_device.clone())),
+        }
 
         Ok(())
     }
@@ -145,6 +201,40 @@ impl PciDevices {
         device: Arc<Mutex<T>>,
         event_manager: &mut EventManager,
     ) -> Result<(), PciManagerError> {
+        self.add_pci_virtio_device(vm, id, device, Some(event_manager))?;
+        Ok(())
+    }
+
+    /// Adds a VirtIO device to the running microVM and tells the guest about it.
+    ///
+    /// The device is subscribed to the event manager when the subscriber updates are applied.
+    pub(crate) fn hotplug_pci_virtio_device<
+        T: 'static + VirtioDevice + MutEventSubscriber + Debug,
+    >(
+        &mut self,
+        vm: &Arc<Vm>,
+        id: String,
+        device: Arc<Mutex<T>>,
+    ) -> Result<(), PciManagerError> {
+        let controller = self
+            .hotplug_controller
+            .clone()
+            .ok_or(PciManagerError::HotplugUnavailable)?;
+        let sbdf = self.add_pci_virtio_device(vm, id, device, None)?;
+        controller
+            .lock()
+            .expect("Poisoned lock")
+            .plug(sbdf.device())?;
+        Ok(())
+    }
+
+    fn add_pci_virtio_device<T: 'static + VirtioDevice + MutEventSubscriber + Debug>(
+        &mut self,
+        vm: &Arc<Vm>,
+        id: String,
+        device: Arc<Mutex<T>>,
+        event_manager: Option<&mut EventManager>,
+    ) -> Result<PciSBDF, PciManagerError> {
         // We should only be reaching this point if PCI is enabled
         let pci_segment = self.pci_segment.as_ref().unwrap();
         let sbdf = pci_segment.next_device_sbdf()?;
@@ -171,7 +261,8 @@ impl PciDevices {
 
         let virtio_device = Arc::new(Mutex::new(virtio_device));
 
-        self.attach_common(vm, device_type, id, sbdf, virtio_device, event_manager)
+        self.attach_common(vm, device_type, id, sbdf, virtio_device, event_manager)?;
+        Ok(sbdf)
     }
 
     fn restore_pci_device<T: 'static + VirtioDevice + MutEventSubscriber + Debug>(
@@ -197,12 +288,102 @@ impl PciDevices {
             device_id.to_string(),
             transport_state.sbdf,
             virtio_device,
-            event_manager,
+            Some(event_manager),
         )?;
 
         Ok(())
     }
 
+    /// Asks the guest to remove a device. The device is removed once the guest ejects it.
+    pub(crate) fn request_unplug(
+        &self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<bool, PciManagerError> {
+        let controller = self
+            .hotplug_controller
+            .as_ref()
+            .ok_or(PciManagerError::HotplugUnavailable)?;
+        let Some(pci_device) = self.get_virtio_device(device_type, device_id) else {
+            return Ok(false);
+        };
+        let slot = pci_device.lock().expect("Poisoned lock").sbdf().device();
+        controller
+            .lock()
+            .expect("Poisoned lock")
+            .request_unplug(slot)?;
+        Ok(true)
+    }
+
+    /// Removes the devices ejected by the guest.
+    pub(crate) fn remove_ejected_devices(&mut self, vm: &Vm) -> Result<(), PciManagerError> {
+        let Some(controller) = &self.hotplug_controller else {
+            return Ok(());
+        };
+        let ejected = controller.lock().expect("Poisoned lock").take_ejected();
+        for slot in (0..32u8).filter(|slot| ejected & (1 << slot) != 0) {
+            self.remove_pci_virtio_device(vm, slot)?;
+        }
+        Ok(())
+    }
+
+    fn remove_pci_virtio_device(&mut self, vm: &Vm, slot: u8) -> Result<(), PciManagerError> {
+        let Some(key) = self
+            .virtio_devices
+            .iter()
+            .find(|(_, device)| device.lock().expect("Poisoned lock").sbdf().device() == slot)
+            .map(|(key, _)| key.clone())
+        else {
+            warn!("pci-hotplug: guest ejected empty slot {slot}");
+            return Ok(());
+        };
+
+        // Only the devices which can be hotplugged can be removed.
+        let (device_type, device_id) = key;
+        if !matches!(
+            device_type,
+            VirtioDeviceType::Block | VirtioDeviceType::Net | VirtioDeviceType::Vsock
+        ) {
+            warn!("pci-hotplug: ignoring ejection of {device_type:?} device {device_id}");
+            return Ok(());
+        }
+
+        let virtio_device = self
+            .virtio_devices
+            .remove(&(device_type, device_id.clone()))
+            .unwrap();
+        // We should only be reaching this point if PCI is enabled
+        self.pci_segment
+            .as_ref()
+            .unwrap()
+            .pci_bus
+            .lock()
+            .expect("Poisoned lock")
+            .remove_device(slot);
+
+        let device = virtio_device.lock().expect("Poisoned lock");
+        vm.common
+            .mmio_bus
+            .remove(device.bar_address, CAPABILITY_BAR_SIZE)?;
+        device.unregister_notification_ioevent(vm)?;
+        device.release_resources(vm)?;
+
+        match device.sub_id {
+            Some(sub_id) => self
+                .subscriber_updates
+                .push(SubscriberUpdate::Remove(sub_id)),
+            // The device was never subscribed.
+            None => self.subscriber_updates.retain(|update| match update {
+                SubscriberUpdate::Add(added) => !Arc::ptr_eq(added, &virtio_device),
+                SubscriberUpdate::Remove(_) => true,
+            }),
+        }
+
+        info!("pci-hotplug: removed {device_type:?} device {device_id} from slot {slot}");
+        EVENT_LOG.emit(EventKind::DeviceUnplugged { device_id });
+        Ok(())
+    }
+
     /// Gets the specified device.
     pub fn get_virtio_device(
         &self,
@@ -250,10 +431,14 @@ pub struct PciDevicesState {
     pub mmds: Option<MmdsState>,
     /// Entropy device state.
     pub entropy_device: Option<VirtioDeviceState<EntropyState>>,
+    /// Console device state.
+    pub console_device: Option<VirtioDeviceState<ConsoleState>>,
     /// Pmem device states.
     pub pmem_devices: Vec<VirtioDeviceState<PmemState>>,
     /// Memory device state.
     pub memory_device: Option<VirtioDeviceState<VirtioMemState>>,
+    /// PCI hotplug controller state.
+    pub hotplug_controller: Option<PciHotplugControllerState>,
 }
 
 pub struct PciDevicesConstructorArgs<'a> {
@@ -392,6 +577,20 @@ impl<'a> Persist<'a> for PciDevices {
                         transport_state,
                     })
                 }
+                VirtioDeviceType::Console => {
+                    let console_dev = locked_virtio_dev
+                        .as_mut_any()
+                        .downcast_mut::<Console>()
+                        .unwrap();
+                    let device_state = console_dev.save();
+
+                    state.console_device = Some(VirtioDeviceState {
+                        device_id: console_dev.id().to_string(),
+                        sbdf,
+                        device_state,
+                        transport_state,
+                    })
+                }
                 VirtioDeviceType::Pmem => {
                     let pmem_dev = locked_virtio_dev
                         .as_mut_any()
@@ -422,6 +621,11 @@ impl<'a> Persist<'a> for PciDevices {
             }
         }
 
+        state.hotplug_controller = self
+            .hotplug_controller
+            .as_ref()
+            .map(|controller| controller.lock().expect("Poisoned lock").save());
+
         state
     }
 
@@ -572,6 +776,28 @@ impl<'a> Persist<'a> for PciDevices {
             )?
         }
 
+        if let Some(console_state) = &state.console_device {
+            let ctor_args = ConsoleConstructorArgs { mem: mem.clone() };
+
+            let device = Arc::new(Mutex::new(Console::restore(
+                ctor_args,
+                &console_state.device_state,
+            )?));
+
+            constructor_args
+                .vm_resources
+                .console
+                .set_device(device.clone());
+
+            pci_devices.restore_pci_device(
+                constructor_args.vm,
+                device,
+                &console_state.device_id,
+                &console_state.transport_state,
+                constructor_args.event_manager,
+            )?
+        }
+
         for pmem_state in &state.pmem_devices {
             let device = Arc::new(Mutex::new(Pmem::restore(
                 PmemConstructorArgs {
@@ -615,6 +841,12 @@ impl<'a> Persist<'a> for PciDevices {
             )?
         }
 
+        if let Some(controller_state) = &state.hotplug_controller {
+            let controller = PciHotplugController::restore((), controller_state)
+                .map_err(PciManagerError::from)?;
+            pci_devices.register_hotplug_controller(constructor_args.vm, controller)?;
+        }
+
         Ok(pci_devices)
     }
 }
@@ -636,6 +868,105 @@ mod tests {
     use crate::vmm_config::pmem::PmemConfig;
     use crate::vmm_config::vsock::VsockDeviceConfig;
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_hotplug_unplug() {
+        use crate::vmm_config::drive::BlockDeviceConfig;
+        use crate::vmm_config::pci_hotplug::{DeviceHotplugError, DeviceUnplugConfig};
+        use crate::vstate::bus::BusDevice;
+
+        let mut vmm = default_vmm();
+        vmm.device_manager.enable_pci(&vmm.vm).unwrap();
+        vmm.device_manager
+            .attach_pci_hotplug_controller(&vmm.vm)
+            .unwrap();
+        let controller = vmm
+            .device_manager
+            .pci_devices
+            .hotplug_controller
+            .clone()
+            .unwrap();
+
+        let block_file = TempFile::new().unwrap();
+        let config = BlockDeviceConfig {
+            drive_id: String::from("scratch"),
+            partuuid: None,
+            is_root_device: false,
+            cache_type: CacheType::Unsafe,
+
+            is_read_only: Some(false),
+            path_on_host: Some(block_file.as_path().to_str().unwrap().to_string()),
+            rate_limiter: None,
+            file_engine_type: None,
+
+            socket: None,
+        };
+        vmm.hotplug_block_device(config.clone()).unwrap();
+        assert!(matches!(
+            vmm.hotplug_block_device(config.clone()),
+            Err(DeviceHotplugError::DeviceExists(_))
+        ));
+
+        let pci_devices = &vmm.device_manager.pci_devices;
+        let slot = pci_devices
+            .get_virtio_device(VirtioDeviceType::Block, "scratch")
+            .unwrap()
+            .lock()
+            .unwrap()
+            .sbdf()
+            .device();
+        assert_eq!(controller.lock().unwrap().devices_up, 1 << slot);
+        assert!(matches!(
+            pci_devices.subscriber_updates.as_slice(),
+            [SubscriberUpdate::Add(_)]
+        ));
+        // The runtime configuration is built from the live devices, so it shows the new drive.
+        let drives = vmm.full_config().drives;
+        assert_eq!(drives.len(), 1);
+        assert_eq!(drives[0].drive_id, "scratch");
+
+        let unplug = DeviceUnplugConfig::Drive {
+            drive_id: String::from("scratch"),
+        };
+        vmm.unplug_device(unplug.clone()).unwrap();
+        assert_eq!(controller.lock().unwrap().devices_down, 1 << slot);
+
+        // The guest ejects the device through the B0EJ register.
+        controller
+            .lock()
+            .unwrap()
+            .write(0, 0x8, &(1u32 << slot).to_le_bytes());
+        vmm.device_manager
+            .pci_devices
+            .remove_ejected_devices(&vmm.vm)
+            .unwrap();
+        let pci_devices = &vmm.device_manager.pci_devices;
+        assert!(
+            pci_devices
+                .get_virtio_device(VirtioDeviceType::Block, "scratch")
+                .is_none()
+        );
+        // The device was never subscribed, so there is nothing left to do.
+        assert!(pci_devices.subscriber_updates.is_empty());
+        assert!(vmm.full_config().drives.is_empty());
+        assert!(matches!(
+            vmm.unplug_device(unplug),
+            Err(DeviceHotplugError::DeviceNotFound(_))
+        ));
+
+        // The slot and the resources of the removed device can be used again.
+        vmm.hotplug_block_device(config).unwrap();
+        let pci_devices = &vmm.device_manager.pci_devices;
+        let new_slot = pci_devices
+            .get_virtio_device(VirtioDeviceType::Block, "scratch")
+            .unwrap()
+            .lock()
+            .unwrap()
+            .sbdf()
+            .device();
+        assert_eq!(new_slot, slot);
+    }
+
     #[test]
     fn test_device_manager_persistence() {
         // These need to survive so the restored blocks find them.
@@ -659,6 +990,7 @@ mod tests {
                 stats_polling_interval_s: 1,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                policy: None,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -705,6 +1037,7 @@ mod tests {
                 path_on_host: "".into(),
                 root_device: true,
                 read_only: true,
+                shared_lock: false,
             }];
             _pmem_files =
                 insert_pmem_devices(&mut vmm, &mut cmdline, &mut event_manager, pmem_configs);
@@ -812,7 +1145,8 @@ mod tests {
       "id": "pmem",
       "path_on_host": "{}",
       "root_device": true,
-      "read_only": true
+      "read_only": true,
+      "shared_lock": false
     }}
   ],
   "memory-hotplug": {{
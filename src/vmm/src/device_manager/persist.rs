@@ -15,14 +15,17 @@ use super::mmio::*;
 use crate::arch::DeviceType;
 use crate::device_manager::DevicePersistError;
 use crate::device_manager::acpi::ACPIDeviceError;
+use crate::devices::acpi::cpu_hotplug::{CpuHotplugController, CpuHotplugControllerState};
 use crate::devices::acpi::vmclock::{VmClock, VmClockState};
 use crate::devices::acpi::vmgenid::{VMGenIDState, VmGenId};
 #[cfg(target_arch = "aarch64")]
-use crate::devices::legacy::RTCDevice;
+use crate::devices::legacy::{PvPanicDevice, RTCDevice};
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::balloon::persist::{BalloonConstructorArgs, BalloonState};
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::block::persist::{BlockConstructorArgs, BlockState};
+use crate::devices::virtio::console::Console;
+use crate::devices::virtio::console::persist::{ConsoleConstructorArgs, ConsoleState};
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::mem::VirtioMem;
 use crate::devices::virtio::mem::persist::{VirtioMemConstructorArgs, VirtioMemState};
@@ -140,6 +143,8 @@ pub struct DeviceStates {
     pub mmds: Option<MmdsState>,
     /// Entropy device state.
     pub entropy_device: Option<VirtioDeviceState<EntropyState>>,
+    /// Console device state.
+    pub console_device: Option<VirtioDeviceState<ConsoleState>>,
     /// Pmem device states.
     pub pmem_devices: Vec<VirtioDeviceState<PmemState>>,
     /// Memory device state.
@@ -171,6 +176,7 @@ impl fmt::Debug for MMIODevManagerConstructorArgs<'_> {
 pub struct ACPIDeviceManagerState {
     vmgenid: VMGenIDState,
     vmclock: VmClockState,
+    cpu_hotplug: Option<CpuHotplugControllerState>,
 }
 
 impl<'a> Persist<'a> for ACPIDeviceManager {
@@ -182,6 +188,10 @@ impl<'a> Persist<'a> for ACPIDeviceManager {
         ACPIDeviceManagerState {
             vmgenid: self.vmgenid().save(),
             vmclock: self.vmclock().save(),
+            cpu_hotplug: self
+                .cpu_hotplug
+                .as_ref()
+                .map(|controller| controller.lock().expect("Poisoned lock").save()),
         }
     }
 
@@ -199,6 +209,11 @@ impl<'a> Persist<'a> for ACPIDeviceManager {
         acpi_devices.activate_vmclock(vm)?;
         acpi_devices.do_post_restore_vmclock(vm.guest_memory())?;
 
+        if let Some(controller_state) = &state.cpu_hotplug {
+            let controller = CpuHotplugController::restore((), controller_state)?;
+            acpi_devices.register_cpu_hotplug(vm, controller)?;
+        }
+
         Ok(acpi_devices)
     }
 }
@@ -226,6 +241,13 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                     device_info: device.resources,
                 });
             }
+
+            if let Some(device) = &self.pvpanic {
+                states.legacy_devices.push(ConnectedLegacyState {
+                    type_: DeviceType::PvPanic,
+                    device_info: device.resources,
+                });
+            }
         }
 
         let _: Result<(), ()> = self.for_each_virtio_mmio_device(|_, devid, device| {
@@ -324,6 +346,20 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         device_info,
                     });
                 }
+                VirtioDeviceType::Console => {
+                    let console = locked_device
+                        .as_mut_any()
+                        .downcast_mut::<Console>()
+                        .unwrap();
+                    let device_state = console.save();
+
+                    states.console_device = Some(VirtioDeviceState {
+                        device_id,
+                        device_state,
+                        transport_state,
+                        device_info,
+                    });
+                }
                 VirtioDeviceType::Pmem => {
                     let pmem = locked_device.as_mut_any().downcast_mut::<Pmem>().unwrap();
                     let device_state = pmem.save();
@@ -372,6 +408,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                     let serial = crate::DeviceManager::setup_serial_device(
                         constructor_args.event_manager,
                         constructor_args.vm_resources.serial_out_path.as_ref(),
+                        constructor_args.vm_resources.serial_socket_path.as_ref(),
                         serial_state.as_ref(),
                         constructor_args.vm_resources.serial_rate_limiter(),
                     )?;
@@ -382,6 +419,10 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                     let rtc = Arc::new(Mutex::new(RTCDevice::new()));
                     dev_manager.register_mmio_rtc(vm, rtc, Some(state.device_info))?;
                 }
+                if state.type_ == DeviceType::PvPanic {
+                    let pvpanic = Arc::new(Mutex::new(PvPanicDevice::new()?));
+                    dev_manager.register_mmio_pvpanic(vm, pvpanic, Some(state.device_info))?;
+                }
             }
         }
 
@@ -562,6 +603,30 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             )?;
         }
 
+        if let Some(console_state) = &state.console_device {
+            let ctor_args = ConsoleConstructorArgs { mem: mem.clone() };
+
+            let device = Arc::new(Mutex::new(Console::restore(
+                ctor_args,
+                &console_state.device_state,
+            )?));
+
+            constructor_args
+                .vm_resources
+                .console
+                .set_device(device.clone());
+
+            restore_helper(
+                device,
+                console_state.device_state.virtio_state.activated,
+                false,
+                &console_state.device_id,
+                &console_state.transport_state,
+                &console_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
         for pmem_state in &state.pmem_devices {
             let device = Arc::new(Mutex::new(Pmem::restore(
                 PmemConstructorArgs {
@@ -644,6 +709,7 @@ mod tests {
                 && self.net_devices == other.net_devices
                 && self.vsock_device == other.vsock_device
                 && self.entropy_device == other.entropy_device
+                && self.console_device == other.console_device
                 && self.memory_device == other.memory_device
         }
     }
@@ -693,6 +759,7 @@ mod tests {
                 stats_polling_interval_s: 1,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                policy: None,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -739,6 +806,7 @@ mod tests {
                 path_on_host: "".into(),
                 root_device: true,
                 read_only: true,
+                shared_lock: false,
             }];
             _pmem_files =
                 insert_pmem_devices(&mut vmm, &mut cmdline, &mut event_manager, pmem_configs);
@@ -843,7 +911,8 @@ mod tests {
       "id": "pmem",
       "path_on_host": "{}",
       "root_device": true,
-      "read_only": true
+      "read_only": true,
+      "shared_lock": false
     }}
   ],
   "memory-hotplug": {{
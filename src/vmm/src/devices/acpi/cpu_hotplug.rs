@@ -0,0 +1,572 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Barrier};
+
+#[cfg(target_arch = "x86_64")]
+use acpi_tables::madt::LocalAPIC;
+#[cfg(target_arch = "x86_64")]
+use acpi_tables::{Aml, aml};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use vm_superio::Trigger;
+use vmm_sys_util::eventfd::EventFd;
+#[cfg(target_arch = "x86_64")]
+use zerocopy::IntoBytes;
+
+use crate::devices::legacy::EventFdTrigger;
+use crate::snapshot::Persist;
+#[cfg(target_arch = "x86_64")]
+use crate::utils::u64_to_usize;
+use crate::vstate::bus::BusDevice;
+use crate::vstate::resources::{AllocPolicy, ResourceAllocator};
+
+/// Size of the MMIO region holding the registers of the CPU hotplug controller.
+pub const CPU_HOTPLUG_MMIO_SIZE: u64 = 0x8;
+
+/// Index of the vCPU which the status register refers to (`CSEL`).
+const CSEL_OFFSET: u64 = 0x0;
+/// Status of the selected vCPU (`CSTS`).
+const CSTS_OFFSET: u64 = 0x4;
+
+/// The selected vCPU is enabled (`CPEN`). Read-only.
+const CPEN: u8 = 1 << 0;
+/// The selected vCPU was added and the guest was not told yet (`CINS`). Writing 1 clears it.
+const CINS: u8 = 1 << 1;
+/// The selected vCPU is to be removed and the guest was not told yet (`CRMV`). Writing 1 clears
+/// it.
+const CRMV: u8 = 1 << 2;
+/// Writing 1 ejects the selected vCPU (`CEJ0`).
+const CEJ0: u8 = 1 << 3;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum CpuHotplugError {
+    /// Could not create EventFd: {0}
+    CreateEventFd(std::io::Error),
+    /// Could not allocate GSI: {0}
+    AllocateGsi(vm_allocator::Error),
+    /// Could not allocate MMIO memory: {0}
+    AllocateMemory(vm_allocator::Error),
+    /// Could not notify guest: {0}
+    NotifyGuest(std::io::Error),
+}
+
+/// ACPI CPU hotplug controller
+///
+/// The controller tells the guest, through a GED interrupt, which vCPUs were added or are to be
+/// removed, and lets the guest eject the vCPUs it took offline. It is described to the guest by
+/// the `\_SB_.PRES` device of the DSDT, which holds its registers, and by the `\_SB_.CPUS`
+/// processor container, which holds a device for every possible vCPU and the `CSCN` method
+/// called by the GED handler.
+///
+/// The vCPUs themselves are created at boot. Plugging a vCPU only makes it visible to the guest,
+/// and ejecting it hides it again.
+#[derive(Debug)]
+pub struct CpuHotplugController {
+    /// Number of possible vCPUs.
+    max_vcpus: u8,
+    /// vCPUs visible to the guest.
+    enabled: u32,
+    /// vCPUs added, not yet reported to the guest.
+    inserting: u32,
+    /// vCPUs to remove, not yet reported to the guest.
+    removing: u32,
+    /// vCPU selected by the guest.
+    selected: u32,
+    /// Interrupt line for notifying the guest about vCPUs to check
+    pub interrupt_evt: EventFdTrigger,
+    /// Guest physical address of the registers of the controller.
+    pub mmio_address: u64,
+    /// GSI number for the device
+    pub gsi: u32,
+}
+
+impl CpuHotplugController {
+    fn from_parts(max_vcpus: u8, mmio_address: u64, gsi: u32) -> Result<Self, CpuHotplugError> {
+        debug!(
+            "cpu-hotplug: building CPU hotplug controller. Address: {:#010x}. IRQ: {}",
+            mmio_address, gsi
+        );
+        let interrupt_evt = EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).map_err(CpuHotplugError::CreateEventFd)?,
+        );
+
+        Ok(Self {
+            max_vcpus,
+            enabled: 0,
+            inserting: 0,
+            removing: 0,
+            selected: 0,
+            interrupt_evt,
+            mmio_address,
+            gsi,
+        })
+    }
+
+    /// Create a new CPU hotplug controller for `max_vcpus` vCPUs, of which the first
+    /// `vcpu_count` are enabled.
+    ///
+    /// Allocate the MMIO region of its registers and a GSI for sending notifications.
+    pub fn new(
+        resource_allocator: &mut ResourceAllocator,
+        vcpu_count: u8,
+        max_vcpus: u8,
+    ) -> Result<Self, CpuHotplugError> {
+        let gsi = resource_allocator
+            .allocate_gsi_legacy(1)
+            .map_err(CpuHotplugError::AllocateGsi)?[0];
+        let mmio_address = resource_allocator
+            .allocate_32bit_mmio_memory(
+                CPU_HOTPLUG_MMIO_SIZE,
+                CPU_HOTPLUG_MMIO_SIZE,
+                AllocPolicy::FirstMatch,
+            )
+            .map_err(CpuHotplugError::AllocateMemory)?;
+
+        let mut controller = Self::from_parts(max_vcpus, mmio_address, gsi)?;
+        controller.enabled = (0..vcpu_count).fold(0, |enabled, idx| enabled | (1 << idx));
+        Ok(controller)
+    }
+
+    fn notify_guest(&self) -> Result<(), CpuHotplugError> {
+        self.interrupt_evt
+            .trigger()
+            .map_err(CpuHotplugError::NotifyGuest)
+    }
+
+    /// Returns the number of possible vCPUs.
+    pub fn max_vcpus(&self) -> u8 {
+        self.max_vcpus
+    }
+
+    /// Returns whether vCPU `idx` is visible to the guest.
+    pub fn is_enabled(&self, idx: u8) -> bool {
+        self.enabled & (1 << idx) != 0
+    }
+
+    /// Returns the bitmap of the vCPUs visible to the guest.
+    pub fn enabled_vcpus(&self) -> u32 {
+        self.enabled
+    }
+
+    /// Tell the guest that vCPU `idx` was added.
+    pub fn plug(&mut self, idx: u8) -> Result<(), CpuHotplugError> {
+        debug!("cpu-hotplug: notifying guest about vCPU {idx} being added");
+        self.enabled |= 1 << idx;
+        self.inserting |= 1 << idx;
+        self.removing &= !(1 << idx);
+        self.notify_guest()
+    }
+
+    /// Ask the guest to take vCPU `idx` offline and eject it.
+    pub fn request_unplug(&mut self, idx: u8) -> Result<(), CpuHotplugError> {
+        debug!("cpu-hotplug: asking guest to remove vCPU {idx}");
+        self.removing |= 1 << idx;
+        self.inserting &= !(1 << idx);
+        self.notify_guest()
+    }
+
+    fn selected_mask(&self) -> u32 {
+        if self.selected < u32::from(self.max_vcpus) {
+            1 << self.selected
+        } else {
+            0
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let mask = self.selected_mask();
+        let mut status = 0;
+        if self.enabled & mask != 0 {
+            status |= CPEN;
+        }
+        if self.inserting & mask != 0 {
+            status |= CINS;
+        }
+        if self.removing & mask != 0 {
+            status |= CRMV;
+        }
+        status
+    }
+
+    fn write_status(&mut self, value: u8) {
+        let mask = self.selected_mask();
+        if value & CINS != 0 {
+            self.inserting &= !mask;
+        }
+        if value & CRMV != 0 {
+            self.removing &= !mask;
+        }
+        if value & CEJ0 != 0 {
+            debug!("cpu-hotplug: guest ejected vCPU {}", self.selected);
+            self.enabled &= !mask;
+        }
+    }
+}
+
+impl BusDevice for CpuHotplugController {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        match (offset, data.len()) {
+            (CSEL_OFFSET, 4) => data.copy_from_slice(&self.selected.to_le_bytes()),
+            (CSTS_OFFSET, 1) => data[0] = self.status(),
+            _ => {
+                warn!(
+                    "cpu-hotplug: invalid read of {} bytes at offset {offset:#x}",
+                    data.len()
+                );
+                data.fill(0);
+            }
+        }
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        match (offset, data) {
+            (CSEL_OFFSET, &[b0, b1, b2, b3]) => {
+                self.selected = u32::from_le_bytes([b0, b1, b2, b3]);
+            }
+            (CSTS_OFFSET, &[value]) => self.write_status(value),
+            _ => warn!(
+                "cpu-hotplug: invalid write of {} bytes at offset {offset:#x}",
+                data.len()
+            ),
+        }
+        None
+    }
+}
+
+/// Logic to save/restore the state of the CPU hotplug controller
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct CpuHotplugControllerState {
+    /// Number of possible vCPUs
+    pub max_vcpus: u8,
+    /// vCPUs visible to the guest
+    pub enabled: u32,
+    /// vCPUs added, not yet reported to the guest
+    pub inserting: u32,
+    /// vCPUs to remove, not yet reported to the guest
+    pub removing: u32,
+    /// vCPU selected by the guest
+    pub selected: u32,
+    /// GSI used by the controller
+    pub gsi: u32,
+    /// Guest physical address of the registers of the controller
+    pub mmio_address: u64,
+}
+
+impl<'a> Persist<'a> for CpuHotplugController {
+    type State = CpuHotplugControllerState;
+    type ConstructorArgs = ();
+    type Error = CpuHotplugError;
+
+    fn save(&self) -> Self::State {
+        CpuHotplugControllerState {
+            max_vcpus: self.max_vcpus,
+            enabled: self.enabled,
+            inserting: self.inserting,
+            removing: self.removing,
+            selected: self.selected,
+            gsi: self.gsi,
+            mmio_address: self.mmio_address,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        let mut controller = Self::from_parts(state.max_vcpus, state.mmio_address, state.gsi)?;
+        controller.enabled = state.enabled;
+        controller.inserting = state.inserting;
+        controller.removing = state.removing;
+        controller.selected = state.selected;
+        // Changes which were not reported before the snapshot still need to be.
+        if controller.inserting != 0 || controller.removing != 0 {
+            controller.notify_guest()?;
+        }
+        Ok(controller)
+    }
+}
+
+/// Device of a possible vCPU, in the `\_SB_.CPUS` container.
+#[cfg(target_arch = "x86_64")]
+struct Cpu {
+    idx: u8,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Aml for Cpu {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        // The APIC ID of a vCPU is its index.
+        let mat = LocalAPIC::new(self.idx).as_bytes().to_vec();
+        aml::Device::new(
+            format!("C{:03X}", self.idx).as_str().try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &"ACPI0007")?,
+                &aml::Name::new("_UID".try_into()?, &self.idx)?,
+                &aml::Method::new(
+                    "_STA".try_into()?,
+                    0,
+                    false,
+                    vec![&aml::Return::new(&aml::MethodCall::new(
+                        "CSTA".try_into()?,
+                        vec![&self.idx],
+                    ))],
+                ),
+                &aml::Name::new("_MAT".try_into()?, &aml::Buffer::new(mat))?,
+                &aml::Method::new(
+                    "_EJ0".try_into()?,
+                    1,
+                    false,
+                    vec![&aml::MethodCall::new("CEJ0".try_into()?, vec![&self.idx])],
+                ),
+            ],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+/// Notification of the device of a vCPU, sent when `Arg0` is its index.
+#[cfg(target_arch = "x86_64")]
+struct CpuNotify {
+    idx: u8,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Aml for CpuNotify {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        let path = aml::Path::new(&format!("C{:03X}", self.idx))?;
+        aml::If::new(
+            &aml::Equal::new(&aml::Arg(0), &self.idx),
+            vec![&aml::Notify::new(&path, &aml::Arg(1))],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+/// Methods of the `\_SB_.CPUS` container, used by the devices of the vCPUs and the GED handler.
+#[cfg(target_arch = "x86_64")]
+struct CpuMethods {
+    max_vcpus: u8,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Aml for CpuMethods {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        // Status of vCPU Arg0: present, enabled, shown in UI and functioning if it is enabled.
+        aml::Method::new(
+            "CSTA".try_into()?,
+            1,
+            true,
+            vec![
+                &aml::Acquire::new("\\_SB_.PRES.CPLK".try_into()?, 0xffff),
+                &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CSEL")?, &aml::Arg(0)),
+                &aml::Store::new(&aml::Local(0), &aml::ZERO),
+                &aml::If::new(
+                    &aml::Equal::new(&aml::Path::new("\\_SB_.PRES.CPEN")?, &aml::ONE),
+                    vec![&aml::Store::new(&aml::Local(0), &0xfu8)],
+                ),
+                &aml::Release::new("\\_SB_.PRES.CPLK".try_into()?),
+                &aml::Return::new(&aml::Local(0)),
+            ],
+        )
+        .append_aml_bytes(v)?;
+
+        // Eject vCPU Arg0.
+        aml::Method::new(
+            "CEJ0".try_into()?,
+            1,
+            true,
+            vec![
+                &aml::Acquire::new("\\_SB_.PRES.CPLK".try_into()?, 0xffff),
+                &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CSEL")?, &aml::Arg(0)),
+                &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CEJ0")?, &aml::ONE),
+                &aml::Release::new("\\_SB_.PRES.CPLK".try_into()?),
+            ],
+        )
+        .append_aml_bytes(v)?;
+
+        // Notify the device of vCPU Arg0 with value Arg1.
+        let notifies: Vec<CpuNotify> = (0..self.max_vcpus).map(|idx| CpuNotify { idx }).collect();
+        aml::Method::new(
+            "CTFY".try_into()?,
+            2,
+            true,
+            notifies.iter().map(|notify| notify as &dyn Aml).collect(),
+        )
+        .append_aml_bytes(v)?;
+
+        // Check every vCPU for being added (device check) or to be removed (eject request).
+        aml::Method::new(
+            "CSCN".try_into()?,
+            0,
+            true,
+            vec![
+                &aml::Acquire::new("\\_SB_.PRES.CPLK".try_into()?, 0xffff),
+                &aml::Store::new(&aml::Local(0), &aml::ZERO),
+                &aml::While::new(
+                    &aml::LessThan::new(&aml::Local(0), &self.max_vcpus),
+                    vec![
+                        &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CSEL")?, &aml::Local(0)),
+                        &aml::If::new(
+                            &aml::Equal::new(&aml::Path::new("\\_SB_.PRES.CINS")?, &aml::ONE),
+                            vec![
+                                &aml::MethodCall::new(
+                                    "CTFY".try_into()?,
+                                    vec![&aml::Local(0), &1u8],
+                                ),
+                                &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CINS")?, &aml::ONE),
+                            ],
+                        ),
+                        &aml::If::new(
+                            &aml::Equal::new(&aml::Path::new("\\_SB_.PRES.CRMV")?, &aml::ONE),
+                            vec![
+                                &aml::MethodCall::new(
+                                    "CTFY".try_into()?,
+                                    vec![&aml::Local(0), &3u8],
+                                ),
+                                &aml::Store::new(&aml::Path::new("\\_SB_.PRES.CRMV")?, &aml::ONE),
+                            ],
+                        ),
+                        &aml::Add::new(&aml::Local(0), &aml::Local(0), &aml::ONE),
+                    ],
+                ),
+                &aml::Release::new("\\_SB_.PRES.CPLK".try_into()?),
+            ],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Aml for CpuHotplugController {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        // Both values fit in 32 bits, since the registers live in the 32-bit MMIO address space.
+        let mmio_address = u32::try_from(self.mmio_address).unwrap();
+        let mmio_size = u32::try_from(CPU_HOTPLUG_MMIO_SIZE).unwrap();
+        aml::Device::new(
+            "_SB_.PRES".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &aml::EisaName::new("PNP0A06")?)?,
+                &aml::Name::new("_STA".try_into()?, &0x0bu8)?,
+                &aml::Name::new("_UID".try_into()?, &"CPU Hotplug Controller")?,
+                &aml::Mutex::new("CPLK".try_into()?, 0),
+                &aml::Name::new(
+                    "_CRS".try_into()?,
+                    &aml::ResourceTemplate::new(vec![&aml::Memory32Fixed::new(
+                        true,
+                        mmio_address,
+                        mmio_size,
+                    )]),
+                )?,
+                &aml::OpRegion::new(
+                    "PRST".try_into()?,
+                    aml::OpRegionSpace::SystemMemory,
+                    u64_to_usize(self.mmio_address),
+                    u64_to_usize(CPU_HOTPLUG_MMIO_SIZE),
+                ),
+                &aml::Field::new(
+                    "PRST".try_into()?,
+                    aml::FieldAccessType::DWord,
+                    aml::FieldUpdateRule::Preserve,
+                    vec![aml::FieldEntry::Named(*b"CSEL", 32)],
+                ),
+                &aml::Field::new(
+                    "PRST".try_into()?,
+                    aml::FieldAccessType::Byte,
+                    aml::FieldUpdateRule::WriteAsZeroes,
+                    vec![
+                        aml::FieldEntry::Reserved(32),
+                        aml::FieldEntry::Named(*b"CPEN", 1),
+                        aml::FieldEntry::Named(*b"CINS", 1),
+                        aml::FieldEntry::Named(*b"CRMV", 1),
+                        aml::FieldEntry::Named(*b"CEJ0", 1),
+                        aml::FieldEntry::Reserved(4),
+                    ],
+                ),
+            ],
+        )
+        .append_aml_bytes(v)?;
+
+        let hid = aml::Name::new("_HID".try_into()?, &"ACPI0010")?;
+        let cid = aml::Name::new("_CID".try_into()?, &aml::EisaName::new("PNP0A05")?)?;
+        let methods = CpuMethods {
+            max_vcpus: self.max_vcpus,
+        };
+        let cpus: Vec<Cpu> = (0..self.max_vcpus).map(|idx| Cpu { idx }).collect();
+        let mut children: Vec<&dyn Aml> = vec![&hid, &cid, &methods];
+        children.extend(cpus.iter().map(|cpu| cpu as &dyn Aml));
+
+        aml::Device::new("_SB_.CPUS".try_into()?, children).append_aml_bytes(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(controller: &mut CpuHotplugController, idx: u32) {
+        controller.write(0, CSEL_OFFSET, &idx.to_le_bytes());
+    }
+
+    fn read_status(controller: &mut CpuHotplugController) -> u8 {
+        let mut data = [0u8; 1];
+        controller.read(0, CSTS_OFFSET, &mut data);
+        data[0]
+    }
+
+    #[test]
+    fn test_plug_unplug() {
+        let mut resource_allocator = ResourceAllocator::new();
+        let mut controller = CpuHotplugController::new(&mut resource_allocator, 2, 4).unwrap();
+        assert!(controller.is_enabled(1));
+        assert!(!controller.is_enabled(2));
+        assert_eq!(controller.enabled_vcpus(), 0b11);
+        select(&mut controller, 1);
+        assert_eq!(read_status(&mut controller), CPEN);
+
+        controller.plug(2).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 1);
+        assert_eq!(controller.enabled_vcpus(), 0b111);
+        select(&mut controller, 2);
+        assert_eq!(read_status(&mut controller), CPEN | CINS);
+        // The guest acknowledges the insertion.
+        controller.write(0, CSTS_OFFSET, &[CINS]);
+        assert_eq!(read_status(&mut controller), CPEN);
+
+        controller.request_unplug(2).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 1);
+        assert_eq!(read_status(&mut controller), CPEN | CRMV);
+        controller.write(0, CSTS_OFFSET, &[CRMV]);
+        assert_eq!(read_status(&mut controller), CPEN);
+        // The vCPU stays enabled until the guest ejects it.
+        assert!(controller.is_enabled(2));
+        controller.write(0, CSTS_OFFSET, &[CEJ0]);
+        assert_eq!(read_status(&mut controller), 0);
+        assert_eq!(controller.enabled_vcpus(), 0b11);
+
+        // A vCPU removed before the guest learnt about it is not reported as added.
+        controller.plug(3).unwrap();
+        controller.request_unplug(3).unwrap();
+        select(&mut controller, 3);
+        assert_eq!(read_status(&mut controller), CPEN | CRMV);
+
+        // vCPUs past the maximum are never enabled.
+        select(&mut controller, 4);
+        assert_eq!(read_status(&mut controller), 0);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut resource_allocator = ResourceAllocator::new();
+        let mut controller = CpuHotplugController::new(&mut resource_allocator, 1, 4).unwrap();
+        controller.plug(2).unwrap();
+        select(&mut controller, 2);
+
+        let mut restored = CpuHotplugController::restore((), &controller.save()).unwrap();
+        assert_eq!(restored.gsi, controller.gsi);
+        assert_eq!(restored.mmio_address, controller.mmio_address);
+        assert_eq!(restored.max_vcpus(), 4);
+        // The enabled vCPUs do not have to be contiguous.
+        assert_eq!(restored.enabled_vcpus(), 0b101);
+        // Pending insertions are signalled again.
+        assert_eq!(restored.interrupt_evt.read().unwrap(), 1);
+        assert_eq!(read_status(&mut restored), CPEN | CINS);
+    }
+}
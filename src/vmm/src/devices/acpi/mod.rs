@@ -1,6 +1,7 @@
 // Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod cpu_hotplug;
 mod generated;
 pub mod vmclock;
 pub mod vmgenid;
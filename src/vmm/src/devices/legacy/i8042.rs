@@ -13,7 +13,7 @@ use log::warn;
 use serde::Serialize;
 use vmm_sys_util::eventfd::EventFd;
 
-use crate::logger::{IncMetric, SharedIncMetric, error};
+use crate::logger::{EVENT_LOG, EventKind, IncMetric, SharedIncMetric, error};
 use crate::vstate::bus::BusDevice;
 
 /// Errors thrown by the i8042 device.
@@ -265,6 +265,7 @@ impl BusDevice for I8042Device {
                     METRICS.error_count.inc();
                 }
                 METRICS.reset_count.inc();
+                EVENT_LOG.emit(EventKind::GuestReset);
             }
             OFS_STATUS if data[0] == CMD_READ_CTR => {
                 // The guest wants to read the control register.
@@ -7,9 +7,11 @@
 
 //! Implements legacy devices (UART, RTC etc).
 mod i8042;
+pub mod pvpanic;
 #[cfg(target_arch = "aarch64")]
 pub mod rtc_pl031;
 pub mod serial;
+pub mod serial_socket;
 
 use std::io;
 use std::ops::Deref;
@@ -20,9 +22,11 @@ use vm_superio::Trigger;
 use vmm_sys_util::eventfd::EventFd;
 
 pub use self::i8042::{I8042Device, I8042Error as I8042DeviceError};
+pub use self::pvpanic::PvPanicDevice;
 #[cfg(target_arch = "aarch64")]
 pub use self::rtc_pl031::RTCDevice;
 pub use self::serial::{SerialDevice, SerialEventsWrapper, SerialWrapper};
+pub use self::serial_socket::{SerialSocket, SerialSocketOutput};
 
 /// Wrapper for implementing the trigger functionality for `EventFd`.
 ///
@@ -66,6 +70,7 @@ impl EventFdTrigger {
 pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
     let mut seq = serializer.serialize_map(Some(1))?;
     seq.serialize_entry("i8042", &i8042::METRICS)?;
+    seq.serialize_entry("pvpanic", &pvpanic::METRICS)?;
     #[cfg(target_arch = "aarch64")]
     seq.serialize_entry("rtc", &rtc_pl031::METRICS)?;
     seq.serialize_entry("uart", &serial::METRICS)?;
@@ -0,0 +1,166 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Emulates the pvpanic device, used by the guest kernel to notify the VMM that it panicked.
+//!
+//! The device exposes a single byte-wide register. Reading it returns the events supported by the
+//! device, and the guest writes it with the event that occurred. See
+//! <https://www.qemu.org/docs/master/specs/pvpanic.html>.
+
+use std::io;
+use std::sync::{Arc, Barrier};
+
+use serde::Serialize;
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::logger::{EVENT_LOG, EventKind, IncMetric, SharedIncMetric, error};
+use crate::vstate::bus::BusDevice;
+
+/// Metrics specific to the pvpanic device.
+#[derive(Debug, Serialize)]
+pub(super) struct PvPanicDeviceMetrics {
+    /// Number of panics reported by the guest.
+    panic_count: SharedIncMetric,
+    /// Number of panics after which the guest boots into its crash kernel.
+    crash_loaded_count: SharedIncMetric,
+    /// Errors triggered while using the pvpanic device.
+    error_count: SharedIncMetric,
+    /// Number of superfluous read intents on this pvpanic device.
+    missed_read_count: SharedIncMetric,
+    /// Number of superfluous write intents on this pvpanic device.
+    missed_write_count: SharedIncMetric,
+}
+impl PvPanicDeviceMetrics {
+    /// Const default construction.
+    const fn new() -> Self {
+        Self {
+            panic_count: SharedIncMetric::new(),
+            crash_loaded_count: SharedIncMetric::new(),
+            error_count: SharedIncMetric::new(),
+            missed_read_count: SharedIncMetric::new(),
+            missed_write_count: SharedIncMetric::new(),
+        }
+    }
+}
+
+/// Stores aggregated metrics
+pub(super) static METRICS: PvPanicDeviceMetrics = PvPanicDeviceMetrics::new();
+
+/// The guest kernel panicked.
+const PVPANIC_PANICKED: u8 = 1 << 0;
+/// The guest kernel panicked and is booting into its crash kernel.
+const PVPANIC_CRASH_LOADED: u8 = 1 << 1;
+/// Events supported by the device.
+const PVPANIC_CAPABILITIES: u8 = PVPANIC_PANICKED | PVPANIC_CRASH_LOADED;
+
+/// A pvpanic device, exposed as an I/O port on x86_64 and as a MMIO region on aarch64.
+#[derive(Debug)]
+pub struct PvPanicDevice {
+    /// Panic eventfd. We will set this event when the guest reports a panic.
+    pub panic_evt: EventFd,
+    /// Whether the guest reported a panic.
+    panicked: bool,
+}
+
+impl PvPanicDevice {
+    /// Constructor for the pvpanic device.
+    pub fn new() -> Result<Self, io::Error> {
+        Ok(Self {
+            panic_evt: EventFd::new(libc::EFD_NONBLOCK)?,
+            panicked: false,
+        })
+    }
+
+    /// Whether the guest reported a panic.
+    pub fn panicked(&self) -> bool {
+        self.panicked
+    }
+}
+
+impl BusDevice for PvPanicDevice {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        // The device only has a byte-wide register.
+        if offset != 0 || data.len() != 1 {
+            METRICS.missed_read_count.inc();
+            return;
+        }
+        data[0] = PVPANIC_CAPABILITIES;
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        // The device only has a byte-wide register.
+        if offset != 0 || data.len() != 1 {
+            METRICS.missed_write_count.inc();
+            return None;
+        }
+
+        let event = data[0] & PVPANIC_CAPABILITIES;
+        if event & PVPANIC_PANICKED != 0 {
+            // Let the VMM thread pause the microVM or exit, as soon as it wakes up to handle
+            // this event.
+            self.panicked = true;
+            if let Err(err) = self.panic_evt.write(1) {
+                error!("Failed to trigger pvpanic event: {:?}", err);
+                METRICS.error_count.inc();
+            }
+            METRICS.panic_count.inc();
+            EVENT_LOG.emit(EventKind::GuestPanic);
+        } else if event & PVPANIC_CRASH_LOADED != 0 {
+            // The guest keeps running its crash kernel, so there is nothing to do apart from
+            // reporting the event.
+            METRICS.crash_loaded_count.inc();
+            EVENT_LOG.emit(EventKind::GuestCrashLoaded);
+        } else {
+            METRICS.missed_write_count.inc();
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pvpanic_read() {
+        let mut pvpanic = PvPanicDevice::new().unwrap();
+
+        let mut data = [0];
+        pvpanic.read(0x0, 0, &mut data);
+        assert_eq!(data[0], PVPANIC_CAPABILITIES);
+
+        // Check if invalid reads don't have side effects.
+        let mut data = [0, 0];
+        pvpanic.read(0x0, 0, &mut data);
+        assert_eq!(data, [0, 0]);
+        let mut data = [0];
+        pvpanic.read(0x0, 1, &mut data);
+        assert_eq!(data, [0]);
+    }
+
+    #[test]
+    fn test_pvpanic_write() {
+        let mut pvpanic = PvPanicDevice::new().unwrap();
+
+        // Check if invalid writes don't have side effects.
+        pvpanic.write(0x0, 0, &[PVPANIC_PANICKED, 0]);
+        pvpanic.write(0x0, 1, &[PVPANIC_PANICKED]);
+        pvpanic.write(0x0, 0, &[1 << 2]);
+        assert!(!pvpanic.panicked());
+        pvpanic.panic_evt.read().unwrap_err();
+
+        // A crash kernel loaded event doesn't stop the guest.
+        let crash_loaded_count = METRICS.crash_loaded_count.count();
+        pvpanic.write(0x0, 0, &[PVPANIC_CRASH_LOADED]);
+        assert!(!pvpanic.panicked());
+        pvpanic.panic_evt.read().unwrap_err();
+        assert_eq!(METRICS.crash_loaded_count.count(), crash_loaded_count + 1);
+
+        let panic_count = METRICS.panic_count.count();
+        pvpanic.write(0x0, 0, &[PVPANIC_PANICKED]);
+        assert!(pvpanic.panicked());
+        assert_eq!(pvpanic.panic_evt.read().unwrap(), 1);
+        assert_eq!(METRICS.panic_count.count(), panic_count + 1);
+    }
+}
@@ -10,7 +10,7 @@ use std::fmt::Debug;
 use std::fs::File;
 use std::io::{self, Read, Stdin, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
-use std::sync::{Arc, Barrier};
+use std::sync::{Arc, Barrier, Mutex};
 
 use crate::rate_limiter::{BucketReduction, TokenBucket};
 
@@ -24,6 +24,7 @@ use vmm_sys_util::epoll::EventSet;
 use vmm_sys_util::eventfd::EventFd;
 
 use crate::devices::legacy::EventFdTrigger;
+use crate::devices::legacy::serial_socket::SerialSocketOutput;
 use crate::logger::{IncMetric, SharedIncMetric};
 use crate::utils::usize_to_u64;
 use crate::vstate::bus::BusDevice;
@@ -133,6 +134,7 @@ pub enum SerialOutInner {
     Sink,
     Stdout(std::io::Stdout),
     File(File),
+    Socket(Arc<Mutex<SerialSocketOutput>>),
 }
 
 /// Output sink for the serial device, with optional rate limiting.
@@ -175,6 +177,10 @@ impl std::io::Write for SerialOut {
         match &mut self.inner {
             SerialOutInner::Stdout(stdout) => stdout.write(buf),
             SerialOutInner::File(file) => file.write(buf),
+            SerialOutInner::Socket(output) => {
+                output.lock().expect("Poisoned lock").write(buf);
+                Ok(buf.len())
+            }
             SerialOutInner::Sink => Ok(buf.len()),
         }
     }
@@ -183,6 +189,7 @@ impl std::io::Write for SerialOut {
             SerialOutInner::Sink => Ok(()),
             SerialOutInner::Stdout(stdout) => stdout.flush(),
             SerialOutInner::File(file) => file.flush(),
+            SerialOutInner::Socket(_) => Ok(()),
         }
     }
 }
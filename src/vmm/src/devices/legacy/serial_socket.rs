@@ -0,0 +1,357 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements a serial console backend listening on a Unix socket.
+//!
+//! A single client at a time is attached to the console: it receives the output of the guest and
+//! its input is forwarded to the guest. Clients can detach by closing their connection and attach
+//! again later, getting the last output of the guest replayed from a scrollback buffer.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use event_manager::{EventOps, Events, MutEventSubscriber};
+use log::{error, info, warn};
+use vmm_sys_util::epoll::EventSet;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::serial::{METRICS, RawIOHandler, SerialDevice};
+use crate::logger::IncMetric;
+
+/// Size of the scrollback buffer replayed to the clients when they attach.
+pub const SERIAL_SCROLLBACK_SIZE: usize = 64 << 10;
+
+/// Serial console output, shared between the serial device and the socket backend.
+#[derive(Debug, Default)]
+pub struct SerialSocketOutput {
+    /// Connection of the attached client, if any.
+    client: Option<Arc<UnixStream>>,
+    /// Last bytes written by the guest.
+    scrollback: VecDeque<u8>,
+    /// Output that the attached client was too slow to receive.
+    pending_output: Vec<u8>,
+}
+
+impl SerialSocketOutput {
+    /// Records the output of the guest and sends it to the attached client, if any.
+    ///
+    /// The output never blocks the guest: the bytes the client is too slow to receive are kept
+    /// and sent by `flush_output()` once its connection is writable. Only the last
+    /// `SERIAL_SCROLLBACK_SIZE` bytes are kept, the older ones are dropped.
+    pub fn write(&mut self, buf: &[u8]) {
+        let tail = &buf[buf.len().saturating_sub(SERIAL_SCROLLBACK_SIZE)..];
+        let excess = (self.scrollback.len() + tail.len()).saturating_sub(SERIAL_SCROLLBACK_SIZE);
+        self.scrollback.drain(..excess);
+        self.scrollback.extend(tail);
+
+        if self.client.is_some() {
+            self.pending_output.extend_from_slice(buf);
+            self.flush_output();
+            let excess = self
+                .pending_output
+                .len()
+                .saturating_sub(SERIAL_SCROLLBACK_SIZE);
+            if excess > 0 {
+                self.pending_output.drain(..excess);
+                METRICS.missed_write_count.inc();
+            }
+        }
+    }
+
+    /// Sends the output kept by `write()` to the attached client, until its connection would
+    /// block.
+    pub fn flush_output(&mut self) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        while !self.pending_output.is_empty() {
+            match (&**client).write(&self.pending_output) {
+                Ok(count) => {
+                    self.pending_output.drain(..count);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    // The backend detaches the client once it notices the connection was closed.
+                    self.client = None;
+                    self.pending_output.clear();
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Replays the scrollback buffer to a new client and sends it the output from now on.
+    fn attach(&mut self, client: Arc<UnixStream>) {
+        self.pending_output = self.scrollback.iter().copied().collect();
+        self.client = Some(client);
+        self.flush_output();
+    }
+
+    /// Stops sending the output to the attached client.
+    fn detach(&mut self) {
+        self.client = None;
+        self.pending_output.clear();
+    }
+}
+
+/// Serial console backend listening on a Unix socket.
+#[derive(Debug)]
+pub struct SerialSocket {
+    /// Socket on which the clients connect.
+    listener: UnixListener,
+    /// Connection of the attached client, if any, shared with the output.
+    client: Option<Arc<UnixStream>>,
+    /// Output of the serial device.
+    output: Arc<Mutex<SerialSocketOutput>>,
+    /// Serial device to which the input of the client is forwarded.
+    serial: Arc<Mutex<SerialDevice>>,
+    /// Event signaled when the guest emptied the input FIFO of the serial device.
+    buffer_ready_evt: EventFd,
+}
+
+impl SerialSocket {
+    /// Creates the socket on which the clients connect.
+    pub fn bind(path: &Path) -> io::Result<UnixListener> {
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    /// Creates a backend forwarding the input of the clients connecting on `listener` to
+    /// `serial`, whose output is written to `output`.
+    pub fn new(
+        listener: UnixListener,
+        output: Arc<Mutex<SerialSocketOutput>>,
+        serial: Arc<Mutex<SerialDevice>>,
+    ) -> io::Result<Self> {
+        let locked_serial = serial.lock().expect("Poisoned lock");
+        let buffer_ready_evt = locked_serial
+            .serial
+            .events()
+            .buffer_ready_event_fd
+            .as_ref()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let buffer_ready_evt = EventFd::try_clone(buffer_ready_evt)?;
+        drop(locked_serial);
+        Ok(Self {
+            listener,
+            client: None,
+            output,
+            serial,
+            buffer_ready_evt,
+        })
+    }
+
+    fn accept(&mut self, ops: &mut EventOps) {
+        let client = match self.listener.accept() {
+            Ok((client, _)) => client,
+            Err(err) => {
+                error!("Failed to accept a serial console connection: {}", err);
+                return;
+            }
+        };
+        if self.client.is_some() {
+            warn!("Rejected a serial console connection: a client is already attached.");
+            return;
+        }
+
+        if let Err(err) = client.set_nonblocking(true) {
+            error!("Failed to set up the serial console connection: {}", err);
+            return;
+        }
+        // The connection is edge-triggered, so the input is read until either the connection or
+        // the input FIFO is drained.
+        if let Err(err) = ops.add(Events::new(
+            &client,
+            EventSet::IN | EventSet::OUT | EventSet::EDGE_TRIGGERED,
+        )) {
+            error!("Failed to register the serial console connection: {}", err);
+            return;
+        }
+        let client = Arc::new(client);
+        self.output
+            .lock()
+            .expect("Poisoned lock")
+            .attach(client.clone());
+        self.client = Some(client);
+        info!("Attached a client to the serial console.");
+    }
+
+    fn detach(&mut self, ops: &mut EventOps) {
+        if let Some(client) = self.client.take() {
+            if let Err(err) = ops.remove(Events::new(&*client, EventSet::IN)) {
+                error!(
+                    "Failed to unregister the serial console connection: {}",
+                    err
+                );
+            }
+            self.output.lock().expect("Poisoned lock").detach();
+            info!("Detached the client from the serial console.");
+        }
+    }
+
+    fn recv_bytes(&mut self, ops: &mut EventOps) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        let mut serial = self.serial.lock().expect("Poisoned lock");
+        let detach = loop {
+            let avail_cap = serial.serial.fifo_capacity();
+            if avail_cap == 0 {
+                // The rest of the input is read once the guest drains the FIFO.
+                break false;
+            }
+            let mut buf = vec![0u8; avail_cap];
+            match (&**client).read(&mut buf) {
+                Ok(0) => break true,
+                Ok(count) => {
+                    if let Err(err) = serial.serial.raw_input(&buf[..count]) {
+                        error!("Failed to forward the serial console input: {}", err);
+                        METRICS.error_count.inc();
+                        break false;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(err) => {
+                    warn!("Failed to read from the serial console connection: {}", err);
+                    break true;
+                }
+            }
+        };
+        drop(serial);
+
+        if detach {
+            self.detach(ops);
+        }
+    }
+}
+
+impl MutEventSubscriber for SerialSocket {
+    /// Handle the connections on the socket and the input and output of the attached client.
+    fn process(&mut self, event: Events, ops: &mut EventOps) {
+        let source = event.fd();
+
+        if source == self.listener.as_raw_fd() {
+            self.accept(ops);
+        } else if source == self.buffer_ready_evt.as_raw_fd() {
+            let _ = self.buffer_ready_evt.read();
+            self.recv_bytes(ops);
+        } else if self
+            .client
+            .as_ref()
+            .is_some_and(|client| source == client.as_raw_fd())
+        {
+            if event.event_set().contains(EventSet::OUT) {
+                self.output.lock().expect("Poisoned lock").flush_output();
+            }
+            // We expect to receive: `EventSet::IN`, `EventSet::HANG_UP` or
+            // `EventSet::ERROR`. To process all these events we just have to
+            // read from the connection.
+            self.recv_bytes(ops);
+        } else {
+            warn!("Spurious event for the serial console socket: {}", source);
+        }
+    }
+
+    /// Register the socket and the buffer ready event.
+    fn init(&mut self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::new(&self.listener, EventSet::IN)) {
+            error!("Failed to register the serial console socket: {}", err);
+        }
+        if let Err(err) = ops.add(Events::new(&self.buffer_ready_evt, EventSet::IN)) {
+            error!("Failed to register serial buffer ready event: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_serial_socket_output_scrollback() {
+        let mut output = SerialSocketOutput::default();
+        output.write(b"abc");
+        output.write(b"def");
+        assert_eq!(output.scrollback, b"abcdef");
+
+        // The scrollback only keeps the last bytes.
+        output.write(&vec![b'x'; SERIAL_SCROLLBACK_SIZE - 1]);
+        assert_eq!(output.scrollback.len(), SERIAL_SCROLLBACK_SIZE);
+        assert_eq!(output.scrollback.front(), Some(&b'f'));
+        output.write(&vec![b'y'; SERIAL_SCROLLBACK_SIZE + 1]);
+        assert_eq!(output.scrollback.len(), SERIAL_SCROLLBACK_SIZE);
+        assert!(output.scrollback.iter().all(|byte| *byte == b'y'));
+    }
+
+    #[test]
+    fn test_serial_socket_output_attach() {
+        let mut output = SerialSocketOutput::default();
+        output.write(b"before");
+
+        // The scrollback is replayed on attach, followed by the new output.
+        let (client, mut peer) = UnixStream::pair().unwrap();
+        output.attach(Arc::new(client));
+        output.write(b" after");
+        let mut buf = [0u8; 12];
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"before after");
+    }
+
+    #[test]
+    fn test_serial_socket_output_detach() {
+        let mut output = SerialSocketOutput::default();
+        let (client, peer) = UnixStream::pair().unwrap();
+        client.set_nonblocking(true).unwrap();
+        output.attach(Arc::new(client));
+        drop(peer);
+
+        // Writing to a closed connection detaches the client, but the output is still recorded.
+        output.write(b"abc");
+        assert!(output.client.is_none());
+        output.write(b"def");
+        assert_eq!(output.scrollback, b"abcdef");
+    }
+
+    #[test]
+    fn test_serial_socket_output_pending() {
+        let mut output = SerialSocketOutput::default();
+        let (client, mut peer) = UnixStream::pair().unwrap();
+        client.set_nonblocking(true).unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        output.attach(Arc::new(client));
+
+        // The output the client is too slow to receive is kept.
+        let mut sent = Vec::new();
+        while output.pending_output.is_empty() {
+            let chunk: Vec<u8> = (0..4096)
+                .map(|i| u8::try_from((sent.len() + i) % 251).unwrap())
+                .collect();
+            output.write(&chunk);
+            sent.extend_from_slice(&chunk);
+        }
+
+        // It is sent once the client reads the previous output.
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        while received.len() < sent.len() {
+            let count = peer.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..count]);
+            output.flush_output();
+        }
+        assert_eq!(received, sent);
+        assert!(output.pending_output.is_empty());
+
+        // Only the last output is kept for a client which does not read it.
+        output.write(&vec![b'x'; 16 * SERIAL_SCROLLBACK_SIZE]);
+        assert!(!output.pending_output.is_empty());
+        assert!(output.pending_output.len() <= SERIAL_SCROLLBACK_SIZE);
+    }
+}
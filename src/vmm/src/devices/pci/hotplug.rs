@@ -0,0 +1,370 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Barrier};
+
+#[cfg(target_arch = "x86_64")]
+use acpi_tables::{Aml, aml};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use vm_superio::Trigger;
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::devices::legacy::EventFdTrigger;
+use crate::snapshot::Persist;
+use crate::utils::u64_to_usize;
+use crate::vstate::bus::BusDevice;
+use crate::vstate::resources::{AllocPolicy, ResourceAllocator};
+
+/// Size of the MMIO region holding the registers of the PCI hotplug controller.
+pub const PCI_HOTPLUG_MMIO_SIZE: u64 = 0x10;
+
+/// Slots with a device added since the guest last read the register (`PCIU`).
+const PCIU_OFFSET: u64 = 0x0;
+/// Slots with a device to be removed by the guest (`PCID`).
+const PCID_OFFSET: u64 = 0x4;
+/// Slots of the devices ejected by the guest (`B0EJ`).
+const B0EJ_OFFSET: u64 = 0x8;
+/// PCI segment which the other registers refer to (`PSEG`).
+const PSEG_OFFSET: u64 = 0xc;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum PciHotplugError {
+    /// Could not create EventFd: {0}
+    CreateEventFd(std::io::Error),
+    /// Could not allocate GSI: {0}
+    AllocateGsi(vm_allocator::Error),
+    /// Could not allocate MMIO memory: {0}
+    AllocateMemory(vm_allocator::Error),
+    /// Could not notify guest: {0}
+    NotifyGuest(std::io::Error),
+}
+
+/// ACPI PCI hotplug controller
+///
+/// The controller tells the guest, through a GED interrupt, which PCI slots have a device to add
+/// or to remove, and lets the guest eject the devices it removed. It is described to the guest by
+/// the `\_SB_.PHPR` device of the DSDT, which exposes the `PCEJ` method called by the `_EJ0`
+/// method of the PCI slots and the `PSCN` method called by the GED handler.
+///
+/// Ejections happen on the vCPU threads, so the controller only records the ejected slots and
+/// signals `eject_evt`, leaving the actual removal of the devices to the VMM thread.
+#[derive(Debug)]
+pub struct PciHotplugController {
+    /// Slots with a device to add, not yet reported to the guest.
+    pub devices_up: u32,
+    /// Slots with a device to remove, not yet reported to the guest.
+    pub devices_down: u32,
+    /// Slots of the devices ejected by the guest and not removed yet.
+    ejected: u32,
+    /// PCI segment selected by the guest.
+    segment: u32,
+    /// Interrupt line for notifying the guest about slots to check
+    pub interrupt_evt: EventFdTrigger,
+    /// Signalled when the guest ejects a device
+    pub eject_evt: EventFd,
+    /// Guest physical address of the registers of the controller.
+    pub mmio_address: u64,
+    /// GSI number for the device
+    pub gsi: u32,
+}
+
+impl PciHotplugController {
+    fn from_parts(mmio_address: u64, gsi: u32) -> Result<Self, PciHotplugError> {
+        debug!(
+            "pci-hotplug: building PCI hotplug controller. Address: {:#010x}. IRQ: {}",
+            mmio_address, gsi
+        );
+        let interrupt_evt = EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).map_err(PciHotplugError::CreateEventFd)?,
+        );
+        let eject_evt = EventFd::new(libc::EFD_NONBLOCK).map_err(PciHotplugError::CreateEventFd)?;
+
+        Ok(Self {
+            devices_up: 0,
+            devices_down: 0,
+            ejected: 0,
+            segment: 0,
+            interrupt_evt,
+            eject_evt,
+            mmio_address,
+            gsi,
+        })
+    }
+
+    /// Create a new PCI hotplug controller
+    ///
+    /// Allocate the MMIO region of its registers and a GSI for sending notifications.
+    pub fn new(resource_allocator: &mut ResourceAllocator) -> Result<Self, PciHotplugError> {
+        let gsi = resource_allocator
+            .allocate_gsi_legacy(1)
+            .map_err(PciHotplugError::AllocateGsi)?[0];
+        let mmio_address = resource_allocator
+            .allocate_32bit_mmio_memory(
+                PCI_HOTPLUG_MMIO_SIZE,
+                PCI_HOTPLUG_MMIO_SIZE,
+                AllocPolicy::FirstMatch,
+            )
+            .map_err(PciHotplugError::AllocateMemory)?;
+
+        Self::from_parts(mmio_address, gsi)
+    }
+
+    fn notify_guest(&self) -> Result<(), PciHotplugError> {
+        self.interrupt_evt
+            .trigger()
+            .map_err(PciHotplugError::NotifyGuest)
+    }
+
+    /// Tell the guest that a device was added to `slot`.
+    pub fn plug(&mut self, slot: u8) -> Result<(), PciHotplugError> {
+        debug!("pci-hotplug: notifying guest about device added to slot {slot}");
+        self.devices_up |= 1 << slot;
+        self.devices_down &= !(1 << slot);
+        self.notify_guest()
+    }
+
+    /// Ask the guest to remove and eject the device of `slot`.
+    pub fn request_unplug(&mut self, slot: u8) -> Result<(), PciHotplugError> {
+        debug!("pci-hotplug: asking guest to remove device of slot {slot}");
+        self.devices_down |= 1 << slot;
+        self.devices_up &= !(1 << slot);
+        self.notify_guest()
+    }
+
+    /// Returns the slots of the devices ejected by the guest since the last call.
+    pub fn take_ejected(&mut self) -> u32 {
+        std::mem::take(&mut self.ejected)
+    }
+}
+
+impl BusDevice for PciHotplugController {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        if data.len() != 4 {
+            warn!("pci-hotplug: invalid read of {} bytes", data.len());
+            return;
+        }
+
+        // The guest only learns once about each added or removed device.
+        let value = match offset {
+            PCIU_OFFSET => std::mem::take(&mut self.devices_up),
+            PCID_OFFSET => std::mem::take(&mut self.devices_down),
+            B0EJ_OFFSET => 0,
+            PSEG_OFFSET => self.segment,
+            _ => {
+                warn!("pci-hotplug: invalid read at offset {offset:#x}");
+                0
+            }
+        };
+        data.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        let Ok(data) = <[u8; 4]>::try_from(data) else {
+            warn!("pci-hotplug: invalid write of {} bytes", data.len());
+            return None;
+        };
+        let value = u32::from_le_bytes(data);
+
+        match offset {
+            B0EJ_OFFSET => {
+                // We only have a single PCI segment.
+                if self.segment != 0 {
+                    warn!("pci-hotplug: ejection on unknown segment {}", self.segment);
+                    return None;
+                }
+                debug!("pci-hotplug: guest ejected slots {value:#034b}");
+                self.ejected |= value;
+                if let Err(err) = self.eject_evt.write(1) {
+                    warn!("pci-hotplug: could not signal ejection: {err}");
+                }
+            }
+            PSEG_OFFSET => self.segment = value,
+            _ => warn!("pci-hotplug: invalid write at offset {offset:#x}"),
+        }
+        None
+    }
+}
+
+/// Logic to save/restore the state of the PCI hotplug controller
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct PciHotplugControllerState {
+    /// Slots with a device to add, not yet reported to the guest
+    pub devices_up: u32,
+    /// Slots with a device to remove, not yet reported to the guest
+    pub devices_down: u32,
+    /// Slots of the devices ejected by the guest and not removed yet
+    pub ejected: u32,
+    /// GSI used by the controller
+    pub gsi: u32,
+    /// Guest physical address of the registers of the controller
+    pub mmio_address: u64,
+}
+
+impl<'a> Persist<'a> for PciHotplugController {
+    type State = PciHotplugControllerState;
+    type ConstructorArgs = ();
+    type Error = PciHotplugError;
+
+    fn save(&self) -> Self::State {
+        PciHotplugControllerState {
+            devices_up: self.devices_up,
+            devices_down: self.devices_down,
+            ejected: self.ejected,
+            gsi: self.gsi,
+            mmio_address: self.mmio_address,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        let mut controller = Self::from_parts(state.mmio_address, state.gsi)?;
+        controller.devices_up = state.devices_up;
+        controller.devices_down = state.devices_down;
+        controller.ejected = state.ejected;
+        // Ejections which were not handled before the snapshot still need to be.
+        if controller.ejected != 0 {
+            controller
+                .eject_evt
+                .write(1)
+                .map_err(PciHotplugError::NotifyGuest)?;
+        }
+        Ok(controller)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Aml for PciHotplugController {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        // Both values fit in 32 bits, since the registers live in the 32-bit MMIO address space.
+        let mmio_address = u32::try_from(self.mmio_address).unwrap();
+        let mmio_size = u32::try_from(PCI_HOTPLUG_MMIO_SIZE).unwrap();
+        aml::Device::new(
+            "_SB_.PHPR".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &aml::EisaName::new("PNP0A06")?)?,
+                &aml::Name::new("_STA".try_into()?, &0x0bu8)?,
+                &aml::Name::new("_UID".try_into()?, &"PCI Hotplug Controller")?,
+                &aml::Mutex::new("BLCK".try_into()?, 0),
+                &aml::Name::new(
+                    "_CRS".try_into()?,
+                    &aml::ResourceTemplate::new(vec![&aml::Memory32Fixed::new(
+                        true,
+                        mmio_address,
+                        mmio_size,
+                    )]),
+                )?,
+                &aml::OpRegion::new(
+                    "PCST".try_into()?,
+                    aml::OpRegionSpace::SystemMemory,
+                    u64_to_usize(self.mmio_address),
+                    u64_to_usize(PCI_HOTPLUG_MMIO_SIZE),
+                ),
+                &aml::Field::new(
+                    "PCST".try_into()?,
+                    aml::FieldAccessType::DWord,
+                    aml::FieldUpdateRule::WriteAsZeroes,
+                    vec![
+                        aml::FieldEntry::Named(*b"PCIU", 32),
+                        aml::FieldEntry::Named(*b"PCID", 32),
+                        aml::FieldEntry::Named(*b"B0EJ", 32),
+                        aml::FieldEntry::Named(*b"PSEG", 32),
+                    ],
+                ),
+                // Eject the device of slot Arg0 of segment Arg1.
+                &aml::Method::new(
+                    "PCEJ".try_into()?,
+                    2,
+                    true,
+                    vec![
+                        &aml::Acquire::new("BLCK".try_into()?, 0xffff),
+                        &aml::Store::new(&aml::Path::new("PSEG")?, &aml::Arg(1)),
+                        &aml::ShiftLeft::new(&aml::Path::new("B0EJ")?, &aml::ONE, &aml::Arg(0)),
+                        &aml::Release::new("BLCK".try_into()?),
+                        &aml::Return::new(&aml::ZERO),
+                    ],
+                ),
+                // Check the slots of the PCI segment for devices to add or remove.
+                &aml::Method::new(
+                    "PSCN".try_into()?,
+                    0,
+                    true,
+                    vec![&aml::MethodCall::new(
+                        "\\_SB_.PC00.PCNT".try_into()?,
+                        vec![],
+                    )],
+                ),
+            ],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_register(controller: &mut PciHotplugController, offset: u64) -> u32 {
+        let mut data = [0u8; 4];
+        controller.read(0, offset, &mut data);
+        u32::from_le_bytes(data)
+    }
+
+    #[test]
+    fn test_plug_unplug() {
+        let mut resource_allocator = ResourceAllocator::new();
+        let mut controller = PciHotplugController::new(&mut resource_allocator).unwrap();
+
+        controller.plug(3).unwrap();
+        controller.plug(4).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 2);
+        // Reading the register clears it.
+        assert_eq!(read_register(&mut controller, PCIU_OFFSET), 0b11000);
+        assert_eq!(read_register(&mut controller, PCIU_OFFSET), 0);
+
+        controller.request_unplug(3).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 1);
+        assert_eq!(read_register(&mut controller, PCID_OFFSET), 0b1000);
+        assert_eq!(read_register(&mut controller, PCID_OFFSET), 0);
+
+        // A device removed before the guest learnt about it is not reported as added.
+        controller.plug(5).unwrap();
+        controller.request_unplug(5).unwrap();
+        assert_eq!(read_register(&mut controller, PCIU_OFFSET), 0);
+        assert_eq!(read_register(&mut controller, PCID_OFFSET), 0b100000);
+    }
+
+    #[test]
+    fn test_eject() {
+        let mut resource_allocator = ResourceAllocator::new();
+        let mut controller = PciHotplugController::new(&mut resource_allocator).unwrap();
+
+        controller.write(0, PSEG_OFFSET, &0u32.to_le_bytes());
+        controller.write(0, B0EJ_OFFSET, &(1u32 << 3).to_le_bytes());
+        assert_eq!(controller.eject_evt.read().unwrap(), 1);
+        assert_eq!(read_register(&mut controller, B0EJ_OFFSET), 0);
+        assert_eq!(controller.take_ejected(), 1 << 3);
+        assert_eq!(controller.take_ejected(), 0);
+
+        // Ejections on other segments are ignored.
+        controller.write(0, PSEG_OFFSET, &1u32.to_le_bytes());
+        assert_eq!(read_register(&mut controller, PSEG_OFFSET), 1);
+        controller.write(0, B0EJ_OFFSET, &(1u32 << 3).to_le_bytes());
+        assert_eq!(controller.take_ejected(), 0);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut resource_allocator = ResourceAllocator::new();
+        let mut controller = PciHotplugController::new(&mut resource_allocator).unwrap();
+        controller.plug(3).unwrap();
+        controller.write(0, B0EJ_OFFSET, &(1u32 << 4).to_le_bytes());
+
+        let mut restored = PciHotplugController::restore((), &controller.save()).unwrap();
+        assert_eq!(restored.gsi, controller.gsi);
+        assert_eq!(restored.mmio_address, controller.mmio_address);
+        assert_eq!(restored.devices_up, 1 << 3);
+        // Pending ejections are signalled again.
+        assert_eq!(restored.eject_evt.read().unwrap(), 1);
+        assert_eq!(restored.take_ejected(), 1 << 4);
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::ops::Deref;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
@@ -14,28 +14,31 @@ use super::super::ActivateError;
 use super::super::device::{DeviceState, VirtioDevice};
 use super::super::queue::Queue;
 use super::metrics::METRICS;
+use super::policy::{BalloonPolicy, PolicyEngine};
 use super::util::compact_page_frame_numbers;
 use super::{
     BALLOON_DEV_ID, BALLOON_MIN_NUM_QUEUES, BALLOON_QUEUE_SIZE, DEFLATE_INDEX, FREE_PAGE_HINT_DONE,
     FREE_PAGE_HINT_STOP, INFLATE_INDEX, MAX_PAGE_COMPACT_BUFFER, MAX_PAGES_IN_DESC,
     MIB_TO_4K_PAGES, STATS_INDEX, VIRTIO_BALLOON_F_DEFLATE_ON_OOM,
     VIRTIO_BALLOON_F_FREE_PAGE_HINTING, VIRTIO_BALLOON_F_FREE_PAGE_REPORTING,
-    VIRTIO_BALLOON_F_STATS_VQ, VIRTIO_BALLOON_PFN_SHIFT, VIRTIO_BALLOON_S_ALLOC_STALL,
-    VIRTIO_BALLOON_S_ASYNC_RECLAIM, VIRTIO_BALLOON_S_ASYNC_SCAN, VIRTIO_BALLOON_S_AVAIL,
-    VIRTIO_BALLOON_S_CACHES, VIRTIO_BALLOON_S_DIRECT_RECLAIM, VIRTIO_BALLOON_S_DIRECT_SCAN,
-    VIRTIO_BALLOON_S_HTLB_PGALLOC, VIRTIO_BALLOON_S_HTLB_PGFAIL, VIRTIO_BALLOON_S_MAJFLT,
-    VIRTIO_BALLOON_S_MEMFREE, VIRTIO_BALLOON_S_MEMTOT, VIRTIO_BALLOON_S_MINFLT,
-    VIRTIO_BALLOON_S_OOM_KILL, VIRTIO_BALLOON_S_SWAP_IN, VIRTIO_BALLOON_S_SWAP_OUT,
+    VIRTIO_BALLOON_F_MUST_TELL_HOST, VIRTIO_BALLOON_F_STATS_VQ, VIRTIO_BALLOON_PFN_SHIFT,
+    VIRTIO_BALLOON_S_ALLOC_STALL, VIRTIO_BALLOON_S_ASYNC_RECLAIM, VIRTIO_BALLOON_S_ASYNC_SCAN,
+    VIRTIO_BALLOON_S_AVAIL, VIRTIO_BALLOON_S_CACHES, VIRTIO_BALLOON_S_DIRECT_RECLAIM,
+    VIRTIO_BALLOON_S_DIRECT_SCAN, VIRTIO_BALLOON_S_HTLB_PGALLOC, VIRTIO_BALLOON_S_HTLB_PGFAIL,
+    VIRTIO_BALLOON_S_MAJFLT, VIRTIO_BALLOON_S_MEMFREE, VIRTIO_BALLOON_S_MEMTOT,
+    VIRTIO_BALLOON_S_MINFLT, VIRTIO_BALLOON_S_OOM_KILL, VIRTIO_BALLOON_S_SWAP_IN,
+    VIRTIO_BALLOON_S_SWAP_OUT,
 };
-use crate::devices::virtio::balloon::BalloonError;
+use crate::devices::virtio::balloon::{BalloonError, report_balloon_event_fail};
 use crate::devices::virtio::device::{ActiveState, VirtioDeviceType};
 use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
 use crate::devices::virtio::queue::InvalidAvailIdx;
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
-use crate::logger::{IncMetric, log_dev_preview_warning};
+use crate::logger::{EVENT_LOG, EventKind, IncMetric, log_dev_preview_warning};
 use crate::utils::u64_to_usize;
 use crate::vstate::memory::{
-    Address, ByteValued, Bytes, GuestAddress, GuestMemoryExtension, GuestMemoryMmap,
+    Address, ByteValued, Bytes, DiscardedRanges, GuestAddress, GuestMemoryExtension,
+    GuestMemoryMmap,
 };
 use crate::{impl_device_type, mem_size_mib};
 
@@ -145,6 +148,9 @@ pub struct BalloonConfig {
     /// Free page reporting enabled
     #[serde(default)]
     pub free_page_reporting: bool,
+    /// Automatic balloon policy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<BalloonPolicy>,
 }
 
 /// BalloonStats holds statistics returned from the stats_queue.
@@ -269,6 +275,13 @@ pub struct Balloon {
 
     // Holds state for free page hinting
     pub(crate) hinting_state: HintingState,
+
+    // Automatic sizing policy, driven by the statistics updates.
+    pub(crate) policy: Option<PolicyEngine>,
+
+    // Guest memory ranges currently held by the balloon, which need no backing data in a
+    // snapshot. Only tracked when the driver tells the device before reusing deflated pages.
+    pub(crate) discarded_ranges: DiscardedRanges,
 }
 
 impl Balloon {
@@ -280,7 +293,10 @@ impl Balloon {
         free_page_hinting: bool,
         free_page_reporting: bool,
     ) -> Result<Balloon, BalloonError> {
-        let mut avail_features = 1u64 << VIRTIO_F_VERSION_1;
+        // The driver has to wait for the deflate requests to be processed before reusing the
+        // pages, so that the device knows which pages hold no data.
+        let mut avail_features =
+            (1u64 << VIRTIO_F_VERSION_1) | (1u64 << VIRTIO_BALLOON_F_MUST_TELL_HOST);
 
         if deflate_on_oom {
             avail_features |= 1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM;
@@ -332,6 +348,8 @@ impl Balloon {
             latest_stats: BalloonStats::default(),
             pfn_buffer: [0u32; MAX_PAGE_COMPACT_BUFFER],
             hinting_state: Default::default(),
+            policy: None,
+            discarded_ranges: DiscardedRanges::default(),
         })
     }
 
@@ -376,6 +394,9 @@ impl Balloon {
     }
 
     pub(crate) fn process_inflate(&mut self) -> Result<(), BalloonError> {
+        // Without this feature the driver may reuse pages before deflating them, so the
+        // inflated pages can not be assumed to hold no data.
+        let track_discarded = self.must_tell_host();
         // This is safe since we checked in the event handler that the device is activated.
         let mem = &self
             .device_state
@@ -451,11 +472,11 @@ impl Balloon {
                 let guest_addr =
                     GuestAddress(u64::from(page_frame_number) << VIRTIO_BALLOON_PFN_SHIFT);
 
-                if let Err(err) = mem.discard_range(
-                    guest_addr,
-                    usize::try_from(range_len).unwrap() << VIRTIO_BALLOON_PFN_SHIFT,
-                ) {
+                let len = usize::try_from(range_len).unwrap() << VIRTIO_BALLOON_PFN_SHIFT;
+                if let Err(err) = mem.discard_range(guest_addr, len) {
                     error!("Error removing memory range: {:?}", err);
+                } else if track_discarded {
+                    self.discarded_ranges.insert(guest_addr, len);
                 }
             }
         }
@@ -469,17 +490,51 @@ impl Balloon {
     }
 
     pub(crate) fn process_deflate_queue(&mut self) -> Result<(), BalloonError> {
+        // The host only asks the guest to deflate the balloon when it is larger than its target,
+        // so the guest deflating it otherwise means that it ran out of memory.
+        let oom_deflate = self.acked_features & (1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM) != 0
+            && self.config_space.actual_pages <= self.config_space.num_pages;
+        let mem = &self
+            .device_state
+            .active_state()
+            .ok_or(BalloonError::DeviceNotActive)?
+            .mem;
         METRICS.deflate_count.inc();
 
         let queue = &mut self.queues[DEFLATE_INDEX];
         let mut needs_interrupt = false;
 
         while let Some(head) = queue.pop()? {
+            let len = head.len as usize;
+            // The deflated pages are given back to the guest, so they hold data again.
+            if !head.is_write_only()
+                && len.is_multiple_of(SIZE_OF_U32)
+                && len <= MAX_PAGES_IN_DESC * SIZE_OF_U32
+            {
+                for index in (0..len).step_by(SIZE_OF_U32) {
+                    let Some(page_frame_number) = head
+                        .addr
+                        .checked_add(index as u64)
+                        .and_then(|addr| mem.read_obj::<u32>(addr).ok())
+                    else {
+                        break;
+                    };
+                    self.discarded_ranges.remove(
+                        GuestAddress(u64::from(page_frame_number) << VIRTIO_BALLOON_PFN_SHIFT),
+                        1 << VIRTIO_BALLOON_PFN_SHIFT,
+                    );
+                }
+            }
             queue.add_used(head.index, 0)?;
             needs_interrupt = true;
         }
         queue.advance_used_ring_idx();
 
+        if needs_interrupt && oom_deflate {
+            EVENT_LOG.emit(EventKind::BalloonOomDeflate {
+                target_mib: pages_to_mib(self.config_space.num_pages),
+            });
+        }
         if needs_interrupt {
             self.signal_used_queue(DEFLATE_INDEX)
         } else {
@@ -491,6 +546,7 @@ impl Balloon {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = &self.device_state.active_state().unwrap().mem;
         METRICS.stats_updates_count.inc();
+        let mut stats_updated = false;
 
         while let Some(head) = self.queues[STATS_INDEX].pop()? {
             if let Some(prev_stats_desc) = self.stats_desc_index {
@@ -532,11 +588,51 @@ impl Balloon {
             }
 
             self.stats_desc_index = Some(head.index);
+            stats_updated = true;
+        }
+
+        if stats_updated {
+            self.apply_policy();
         }
 
         Ok(())
     }
 
+    /// Adjusts the balloon size according to the automatic policy, if one is set.
+    fn apply_policy(&mut self) {
+        // The available memory reported by the guest already accounts for the
+        // pages it put in the balloon, but not for an inflation still in progress,
+        // so the policy starts from the current inflation rather than the target.
+        let inflated_mib = pages_to_mib(self.config_space.actual_pages);
+        let current_mib = self.size_mb();
+        let Some(target_mib) = self
+            .policy
+            .as_mut()
+            .and_then(|engine| engine.evaluate(inflated_mib, &self.latest_stats, Instant::now()))
+        else {
+            return;
+        };
+
+        // The policy bounds are not checked against the guest memory size
+        // when set, so make sure the target stays within the guest memory.
+        let mem = &self.device_state.active_state().unwrap().mem;
+        let target_mib = target_mib.min(u32::try_from(mem_size_mib(mem)).unwrap_or(u32::MAX));
+        if target_mib == current_mib {
+            return;
+        }
+
+        if target_mib > current_mib {
+            METRICS.policy_inflate_count.inc();
+        } else {
+            METRICS.policy_deflate_count.inc();
+        }
+        debug!("balloon: policy changing target size from {current_mib} MiB to {target_mib} MiB");
+        if let Err(err) = self.update_size(target_mib) {
+            METRICS.policy_fails.inc();
+            error!("balloon: failed to apply policy target size: {err:?}");
+        }
+    }
+
     pub(crate) fn process_free_page_hinting_queue(&mut self) -> Result<(), BalloonError> {
         let mem = &self
             .device_state
@@ -714,22 +810,27 @@ impl Balloon {
         }
     }
 
+    /// Check that the target size of the balloon can be updated to `amount_mib`.
+    pub fn check_size(&self, amount_mib: u32) -> Result<(), BalloonError> {
+        let Some(active_state) = self.device_state.active_state() else {
+            return Err(BalloonError::DeviceNotActive);
+        };
+        // The balloon cannot have a target size greater than the size of
+        // the guest memory.
+        if u64::from(amount_mib) > mem_size_mib(&active_state.mem) {
+            return Err(BalloonError::TooMuchMemoryRequested(amount_mib));
+        }
+        mib_to_pages(amount_mib)?;
+        Ok(())
+    }
+
     /// Update the target size of the balloon.
     pub fn update_size(&mut self, amount_mib: u32) -> Result<(), BalloonError> {
-        if self.is_activated() {
-            let mem = &self.device_state.active_state().unwrap().mem;
-            // The balloon cannot have a target size greater than the size of
-            // the guest memory.
-            if u64::from(amount_mib) > mem_size_mib(mem) {
-                return Err(BalloonError::TooMuchMemoryRequested(amount_mib));
-            }
-            self.config_space.num_pages = mib_to_pages(amount_mib)?;
-            self.interrupt_trigger()
-                .trigger(VirtioInterruptType::Config)
-                .map_err(BalloonError::InterruptError)
-        } else {
-            Err(BalloonError::DeviceNotActive)
-        }
+        self.check_size(amount_mib)?;
+        self.config_space.num_pages = mib_to_pages(amount_mib)?;
+        self.interrupt_trigger()
+            .trigger(VirtioInterruptType::Config)
+            .map_err(BalloonError::InterruptError)
     }
 
     pub fn free_page_hinting(&self) -> bool {
@@ -764,15 +865,52 @@ impl Balloon {
         idx
     }
 
+    /// Check that the automatic balloon policy can be set to `policy`.
+    pub fn check_policy(&self, policy: Option<&BalloonPolicy>) -> Result<(), BalloonError> {
+        if let Some(policy) = policy {
+            if !self.stats_enabled() {
+                return Err(BalloonError::PolicyRequiresStatistics);
+            }
+            policy.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Set or clear the automatic balloon policy.
+    pub fn set_policy(&mut self, policy: Option<BalloonPolicy>) -> Result<(), BalloonError> {
+        self.check_policy(policy.as_ref())?;
+        self.policy = policy.map(PolicyEngine::new);
+        Ok(())
+    }
+
+    /// Obtain the automatic balloon policy.
+    pub fn policy(&self) -> Option<BalloonPolicy> {
+        self.policy.as_ref().map(|engine| engine.policy)
+    }
+
+    /// Obtain the guest memory ranges currently held by the balloon.
+    pub fn discarded_ranges(&self) -> &DiscardedRanges {
+        &self.discarded_ranges
+    }
+
+    /// Check that the statistics polling interval can be updated to `interval_s`.
+    pub fn check_stats_polling_interval(&self, interval_s: u16) -> Result<(), BalloonError> {
+        // Statistics cannot be enabled or disabled once the device is created.
+        if self.stats_polling_interval_s != interval_s
+            && (self.stats_polling_interval_s == 0 || interval_s == 0)
+        {
+            return Err(BalloonError::StatisticsStateChange);
+        }
+        Ok(())
+    }
+
     /// Update the statistics polling interval.
     pub fn update_stats_polling_interval(&mut self, interval_s: u16) -> Result<(), BalloonError> {
         if self.stats_polling_interval_s == interval_s {
             return Ok(());
         }
 
-        if self.stats_polling_interval_s == 0 || interval_s == 0 {
-            return Err(BalloonError::StatisticsStateChange);
-        }
+        self.check_stats_polling_interval(interval_s)?;
 
         self.trigger_stats_update()?;
 
@@ -800,6 +938,10 @@ impl Balloon {
         self.avail_features & (1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM) != 0
     }
 
+    pub(crate) fn must_tell_host(&self) -> bool {
+        self.acked_features & (1u64 << VIRTIO_BALLOON_F_MUST_TELL_HOST) != 0
+    }
+
     pub fn stats_polling_interval_s(&self) -> u16 {
         self.stats_polling_interval_s
     }
@@ -877,6 +1019,7 @@ impl Balloon {
             stats_polling_interval_s: self.stats_polling_interval_s(),
             free_page_hinting: self.free_page_hinting(),
             free_page_reporting: self.free_page_reporting(),
+            policy: self.policy(),
         }
     }
 
@@ -980,6 +1123,38 @@ impl VirtioDevice for Balloon {
         self.device_state.is_activated()
     }
 
+    fn reset(&mut self) -> Option<(Arc<dyn VirtioInterrupt>, Vec<EventFd>)> {
+        let queue_evts = self
+            .queue_evts
+            .iter()
+            .map(EventFd::try_clone)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let DeviceState::Activated(ActiveState { interrupt, .. }) =
+            std::mem::replace(&mut self.device_state, DeviceState::Inactive)
+        else {
+            return None;
+        };
+
+        // The next driver starts with an empty balloon and owns all the guest memory again.
+        self.config_space.actual_pages = 0;
+        self.discarded_ranges = DiscardedRanges::default();
+        self.stats_desc_index = None;
+        self.hinting_state = HintingState::default();
+        self.stats_timer.arm(Duration::ZERO, None);
+
+        Some((interrupt, queue_evts))
+    }
+
+    fn prepare_save(&mut self) {
+        // Pages the driver asked to get back are in use as soon as the request is acknowledged,
+        // so they have to be saved with the rest of the guest memory.
+        if self.is_activated() {
+            self.process_deflate_queue()
+                .unwrap_or_else(report_balloon_event_fail);
+        }
+    }
+
     fn kick(&mut self) {
         if self.is_activated() {
             if self.free_page_hinting() {
@@ -1003,7 +1178,6 @@ pub(crate) mod tests {
     use super::*;
     use crate::arch::host_page_size;
     use crate::check_metric_after_block;
-    use crate::devices::virtio::balloon::report_balloon_event_fail;
     use crate::devices::virtio::balloon::test_utils::{
         check_request_completion, invoke_handler_for_queue_event, set_request,
     };
@@ -1159,6 +1333,7 @@ pub(crate) mod tests {
             assert_eq!(balloon.device_type(), VirtioDeviceType::Balloon);
 
             let features: u64 = (1u64 << VIRTIO_F_VERSION_1)
+                | (1u64 << VIRTIO_BALLOON_F_MUST_TELL_HOST)
                 | (u64::from(*deflate_on_oom) << VIRTIO_BALLOON_F_DEFLATE_ON_OOM)
                 | ((u64::from(*reporting)) << VIRTIO_BALLOON_F_FREE_PAGE_REPORTING)
                 | ((u64::from(*hinting)) << VIRTIO_BALLOON_F_FREE_PAGE_HINTING)
@@ -1191,6 +1366,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
         assert_eq!(balloon.config(), cfg);
 
@@ -1368,6 +1544,7 @@ pub(crate) mod tests {
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(INFLATE_INDEX, infq.create_queue());
         balloon.set_queue(DEFLATE_INDEX, infq.create_queue());
+        balloon.set_acked_features(balloon.avail_features);
         balloon.activate(mem.clone(), interrupt).unwrap();
 
         // Fill the third page with non-zero bytes.
@@ -1429,6 +1606,31 @@ pub(crate) mod tests {
             for i in 0..0x1000 {
                 assert_eq!(mem.read_obj::<u8>(GuestAddress((1 << 12) + i)).unwrap(), 0);
             }
+            // Check that the page is tracked as discarded.
+            assert_eq!(
+                balloon.discarded_ranges().iter().collect::<Vec<_>>(),
+                vec![(GuestAddress(1 << 12), 0x1000)]
+            );
+        }
+
+        // A driver that did not negotiate VIRTIO_BALLOON_F_MUST_TELL_HOST may reuse the pages
+        // before deflating them, so they are not tracked as discarded.
+        {
+            balloon.set_acked_features(
+                balloon.avail_features & !(1u64 << VIRTIO_BALLOON_F_MUST_TELL_HOST),
+            );
+            mem.write_obj::<u32>(0x2, GuestAddress(page_addr)).unwrap();
+            set_request(
+                &infq,
+                1,
+                page_addr,
+                SIZE_OF_U32.try_into().unwrap(),
+                VIRTQ_DESC_F_NEXT,
+            );
+
+            invoke_handler_for_queue_event(&mut balloon, INFLATE_INDEX);
+            check_request_completion(&infq, 1);
+            assert!(!balloon.discarded_ranges().contains(GuestAddress(2 << 12)));
         }
     }
 
@@ -1473,15 +1675,121 @@ pub(crate) mod tests {
                 SIZE_OF_U32.try_into().unwrap(),
                 VIRTQ_DESC_F_NEXT,
             );
+            let page_frame_number = mem.read_obj::<u32>(GuestAddress(page_addr)).unwrap();
+            let page = GuestAddress(u64::from(page_frame_number) << VIRTIO_BALLOON_PFN_SHIFT);
+            balloon.discarded_ranges.insert(page, 0x1000);
             check_metric_after_block!(
                 METRICS.deflate_count,
                 1,
                 invoke_handler_for_queue_event(&mut balloon, DEFLATE_INDEX)
             );
             check_request_completion(&defq, 1);
+            // Check that the deflated page is not tracked as discarded anymore.
+            assert!(!balloon.discarded_ranges().contains(page));
+        }
+
+        // Pending deflate requests are processed before saving the state, as the driver reuses
+        // the pages once the requests are acknowledged.
+        {
+            set_request(
+                &defq,
+                2,
+                page_addr,
+                SIZE_OF_U32.try_into().unwrap(),
+                VIRTQ_DESC_F_NEXT,
+            );
+            let page_frame_number = mem.read_obj::<u32>(GuestAddress(page_addr)).unwrap();
+            let page = GuestAddress(u64::from(page_frame_number) << VIRTIO_BALLOON_PFN_SHIFT);
+            balloon.discarded_ranges.insert(page, 0x1000);
+            balloon.prepare_save();
+            check_request_completion(&defq, 2);
+            assert!(!balloon.discarded_ranges().contains(page));
         }
     }
 
+    #[test]
+    fn test_deflate_on_oom() {
+        let mut balloon = Balloon::new(13, true, 0, false, false).unwrap();
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let defq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        balloon.set_queue(INFLATE_INDEX, defq.create_queue());
+        balloon.set_queue(DEFLATE_INDEX, defq.create_queue());
+        balloon.set_acked_features(balloon.avail_features);
+        balloon.activate(mem.clone(), interrupt).unwrap();
+
+        // Other tests emit events concurrently, so only the events after the cursor are checked.
+        let oom_deflated = |cursor| {
+            EVENT_LOG
+                .read(Some(cursor))
+                .events
+                .iter()
+                .any(|event| event.kind == EventKind::BalloonOomDeflate { target_mib: 13 })
+        };
+
+        // Deflating a balloon larger than its target is requested by the host.
+        balloon.update_actual_pages(mib_to_pages(14).unwrap());
+        let cursor = EVENT_LOG.next_seq();
+        set_request(
+            &defq,
+            0,
+            0x10,
+            SIZE_OF_U32.try_into().unwrap(),
+            VIRTQ_DESC_F_NEXT,
+        );
+        invoke_handler_for_queue_event(&mut balloon, DEFLATE_INDEX);
+        check_request_completion(&defq, 0);
+        assert!(!oom_deflated(cursor));
+
+        // Deflating it below its target is done by the guest when it runs out of memory.
+        balloon.update_actual_pages(mib_to_pages(13).unwrap());
+        let cursor = EVENT_LOG.next_seq();
+        set_request(
+            &defq,
+            1,
+            0x10,
+            SIZE_OF_U32.try_into().unwrap(),
+            VIRTQ_DESC_F_NEXT,
+        );
+        invoke_handler_for_queue_event(&mut balloon, DEFLATE_INDEX);
+        check_request_completion(&defq, 1);
+        assert!(oom_deflated(cursor));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let q = VirtQueue::new(GuestAddress(0), &mem, 16);
+        balloon.set_queue(INFLATE_INDEX, q.create_queue());
+        balloon.set_queue(DEFLATE_INDEX, q.create_queue());
+        balloon.set_queue(STATS_INDEX, q.create_queue());
+
+        // Nothing to reset before the device is activated.
+        assert!(balloon.reset().is_none());
+
+        balloon.activate(mem.clone(), interrupt.clone()).unwrap();
+        balloon.config_space.actual_pages = 1;
+        balloon
+            .discarded_ranges
+            .insert(GuestAddress(0x1000), 0x1000);
+        assert!(balloon.stats_timer.is_armed());
+
+        // The pages held by the balloon belong to the guest again after a reset.
+        let (_, queue_evts) = balloon.reset().unwrap();
+        assert_eq!(queue_evts.len(), balloon.queue_evts.len());
+        assert!(!balloon.is_activated());
+        assert_eq!(balloon.config_space.actual_pages, 0);
+        assert!(balloon.discarded_ranges().is_empty());
+        assert!(!balloon.stats_timer.is_armed());
+
+        // The device can be activated again by the next driver.
+        balloon.activate(mem, interrupt).unwrap();
+        assert!(balloon.is_activated());
+        assert!(balloon.stats_timer.is_armed());
+    }
+
     #[test]
     fn test_stats() {
         let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
@@ -1574,6 +1882,81 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_policy() {
+        let policy = BalloonPolicy {
+            target_free_mib: 1,
+            min_mib: 0,
+            max_mib: 4,
+            max_step_mib: 0,
+            min_interval_s: 0,
+        };
+
+        // Policies need statistics and consistent bounds.
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
+        assert!(matches!(
+            balloon.set_policy(Some(policy)),
+            Err(BalloonError::PolicyRequiresStatistics)
+        ));
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
+        assert!(matches!(
+            balloon.set_policy(Some(BalloonPolicy {
+                min_mib: 5,
+                ..policy
+            })),
+            Err(BalloonError::InvalidPolicy(5, 4))
+        ));
+        balloon.set_policy(Some(policy)).unwrap();
+        assert_eq!(balloon.config().policy, Some(policy));
+
+        // The balloon target is bounded by the guest memory size.
+        let mem = single_region_mem(4 << 20);
+        let interrupt = default_interrupt();
+        let statsq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        balloon.set_queue(INFLATE_INDEX, statsq.create_queue());
+        balloon.set_queue(DEFLATE_INDEX, statsq.create_queue());
+        balloon.set_queue(STATS_INDEX, statsq.create_queue());
+        balloon.activate(mem.clone(), interrupt).unwrap();
+
+        // The guest reports 3 MiB available, so the balloon grows by 2 MiB.
+        let page_addr = 0x100;
+        let avail_stat = BalloonStat {
+            tag: VIRTIO_BALLOON_S_AVAIL,
+            val: 3 << 20,
+        };
+        mem.write_obj::<BalloonStat>(avail_stat, GuestAddress(page_addr))
+            .unwrap();
+        set_request(
+            &statsq,
+            0,
+            page_addr,
+            u32::try_from(SIZE_OF_STAT).unwrap(),
+            VIRTQ_DESC_F_NEXT,
+        );
+        check_metric_after_block!(METRICS.policy_inflate_count, 1, {
+            balloon.queue_events()[STATS_INDEX].write(1).unwrap();
+            balloon.process_stats_queue_event().unwrap();
+        });
+        assert_eq!(balloon.size_mb(), 2);
+        assert!(
+            balloon
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Config)
+        );
+
+        // The guest has not inflated the balloon yet, so the same statistics
+        // must not grow the target any further.
+        assert_eq!(balloon.config_space.actual_pages, 0);
+        check_metric_after_block!(METRICS.policy_inflate_count, 0, {
+            balloon.apply_policy();
+        });
+        assert_eq!(balloon.size_mb(), 2);
+
+        // Clearing the policy stops the adjustments.
+        balloon.set_policy(None).unwrap();
+        assert_eq!(balloon.config().policy, None);
+    }
+
     #[test]
     fn test_process_reporting() {
         let mem = create_virtio_mem();
@@ -75,6 +75,14 @@ pub(super) struct BalloonDeviceMetrics {
     pub free_page_hint_freed: SharedIncMetric,
     /// Number of errors occurred while hinting
     pub free_page_hint_fails: SharedIncMetric,
+    /// Number of balloon inflations requested by the automatic policy.
+    pub policy_inflate_count: SharedIncMetric,
+    /// Number of balloon deflations requested by the automatic policy.
+    pub policy_deflate_count: SharedIncMetric,
+    /// Number of deflations requested by the automatic policy after a guest OOM kill.
+    pub policy_oom_deflate_count: SharedIncMetric,
+    /// Number of errors occurred while applying the automatic policy.
+    pub policy_fails: SharedIncMetric,
 }
 impl BalloonDeviceMetrics {
     /// Const default construction.
@@ -92,6 +100,10 @@ impl BalloonDeviceMetrics {
             free_page_hint_count: SharedIncMetric::new(),
             free_page_hint_freed: SharedIncMetric::new(),
             free_page_hint_fails: SharedIncMetric::new(),
+            policy_inflate_count: SharedIncMetric::new(),
+            policy_deflate_count: SharedIncMetric::new(),
+            policy_oom_deflate_count: SharedIncMetric::new(),
+            policy_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -7,12 +7,14 @@ pub mod device;
 mod event_handler;
 pub mod metrics;
 pub mod persist;
+pub mod policy;
 pub mod test_utils;
 mod util;
 
 use log::error;
 
 pub use self::device::{Balloon, BalloonConfig, BalloonStats};
+pub use self::policy::BalloonPolicy;
 use super::queue::{InvalidAvailIdx, QueueError};
 use crate::devices::virtio::balloon::metrics::METRICS;
 use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
@@ -50,6 +52,7 @@ pub const FREE_PAGE_HINT_STOP: u32 = 0;
 pub const FREE_PAGE_HINT_DONE: u32 = 1;
 
 // The feature bitmap for virtio balloon.
+const VIRTIO_BALLOON_F_MUST_TELL_HOST: u32 = 0; // Tell before reclaiming pages.
 const VIRTIO_BALLOON_F_STATS_VQ: u32 = 1; // Enable statistics.
 const VIRTIO_BALLOON_F_DEFLATE_ON_OOM: u32 = 2; // Deflate balloon on OOM.
 const VIRTIO_BALLOON_F_FREE_PAGE_HINTING: u32 = 3; // Enable free page hinting
@@ -88,6 +91,10 @@ pub enum BalloonError {
     MalformedDescriptor,
     /// Guest gave us a malformed payload.
     MalformedPayload,
+    /// Balloon policy minimum size {0}MiB is larger than its maximum size {1}MiB
+    InvalidPolicy(u32, u32),
+    /// Balloon policy requires statistics to be enabled.
+    PolicyRequiresStatistics,
     /// Error restoring the balloon device queues.
     QueueRestoreError,
     /// Received stats query when stats are disabled.
@@ -10,12 +10,13 @@ use serde::{Deserialize, Serialize};
 
 use super::*;
 use crate::devices::virtio::balloon::device::{BalloonStats, ConfigSpace, HintingState};
+use crate::devices::virtio::balloon::policy::PolicyEngine;
 use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDeviceType};
 use crate::devices::virtio::persist::VirtioDeviceState;
 use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
 use crate::devices::virtio::transport::VirtioInterrupt;
 use crate::snapshot::Persist;
-use crate::vstate::memory::GuestMemoryMmap;
+use crate::vstate::memory::{DiscardedRanges, GuestMemoryMmap};
 
 /// Information about the balloon config's that are saved
 /// at snapshot.
@@ -104,9 +105,18 @@ pub struct BalloonState {
     latest_stats: BalloonStatsState,
     config_space: BalloonConfigSpaceState,
     hinting_state: HintingState,
+    policy: Option<BalloonPolicy>,
+    discarded_ranges: DiscardedRanges,
     pub virtio_state: VirtioDeviceState,
 }
 
+impl BalloonState {
+    /// Guest memory ranges held by the balloon at snapshot time.
+    pub fn discarded_ranges(&self) -> &DiscardedRanges {
+        &self.discarded_ranges
+    }
+}
+
 /// Auxiliary structure for creating a device when resuming from a snapshot.
 #[derive(Debug)]
 pub struct BalloonConstructorArgs {
@@ -125,6 +135,8 @@ impl Persist<'_> for Balloon {
             stats_desc_index: self.stats_desc_index,
             latest_stats: BalloonStatsState::from_stats(&self.latest_stats),
             hinting_state: self.hinting_state,
+            policy: self.policy(),
+            discarded_ranges: self.discarded_ranges.clone(),
             config_space: BalloonConfigSpaceState {
                 num_pages: self.config_space.num_pages,
                 actual_pages: self.config_space.actual_pages,
@@ -187,6 +199,8 @@ impl Persist<'_> for Balloon {
             free_page_hint_cmd_id: FREE_PAGE_HINT_DONE,
         };
         balloon.hinting_state = state.hinting_state;
+        balloon.policy = state.policy.map(PolicyEngine::new);
+        balloon.discarded_ranges = state.discarded_ranges.clone();
 
         if state.virtio_state.activated && balloon.stats_enabled() {
             // Restore the stats descriptor.
@@ -206,13 +220,26 @@ mod tests {
     use super::*;
     use crate::devices::virtio::device::VirtioDevice;
     use crate::devices::virtio::test_utils::{default_interrupt, default_mem};
+    use crate::vstate::memory::GuestAddress;
 
     #[test]
     fn test_persistence() {
         let guest_mem = default_mem();
 
         // Create and save the balloon device.
-        let balloon = Balloon::new(0x42, false, 2, false, false).unwrap();
+        let mut balloon = Balloon::new(0x42, false, 2, false, false).unwrap();
+        balloon
+            .set_policy(Some(BalloonPolicy {
+                target_free_mib: 64,
+                min_mib: 0,
+                max_mib: 0x42,
+                max_step_mib: 16,
+                min_interval_s: 5,
+            }))
+            .unwrap();
+        balloon
+            .discarded_ranges
+            .insert(GuestAddress(0x1000), 0x2000);
 
         let balloon_state = balloon.save();
         let serialized_data = bitcode::serialize(&balloon_state).unwrap();
@@ -248,5 +275,10 @@ mod tests {
         );
         assert_eq!(restored_balloon.stats_desc_index, balloon.stats_desc_index);
         assert_eq!(restored_balloon.latest_stats, balloon.latest_stats);
+        assert_eq!(restored_balloon.policy(), balloon.policy());
+        assert_eq!(
+            restored_balloon.discarded_ranges(),
+            balloon.discarded_ranges()
+        );
     }
 }
@@ -0,0 +1,270 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements an automatic sizing policy for the balloon device.
+//!
+//! When a policy is configured, every statistics update received from the guest
+//! is used to recompute the balloon target so that the guest keeps
+//! `target_free_mib` of memory available. The target is kept within
+//! `[min_mib, max_mib]`, changes by at most `max_step_mib` per adjustment and
+//! is not changed more often than every `min_interval_s` seconds. If the guest
+//! reports new OOM killer invocations, the balloon is deflated regardless of the
+//! reported available memory.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::BalloonError;
+use super::device::BalloonStats;
+use super::metrics::METRICS;
+use crate::logger::{EVENT_LOG, EventKind, IncMetric};
+
+const MIB_SHIFT: u32 = 20;
+
+/// Configuration of the automatic balloon policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalloonPolicy {
+    /// Amount of memory in MiB the guest should keep available.
+    pub target_free_mib: u32,
+    /// Minimum balloon size in MiB.
+    #[serde(default)]
+    pub min_mib: u32,
+    /// Maximum balloon size in MiB.
+    pub max_mib: u32,
+    /// Maximum change of the balloon size in MiB for a single adjustment.
+    /// A value of 0 means the change is not limited.
+    #[serde(default)]
+    pub max_step_mib: u32,
+    /// Minimum interval in seconds between two adjustments.
+    #[serde(default)]
+    pub min_interval_s: u16,
+}
+
+impl BalloonPolicy {
+    /// Checks that the policy bounds are consistent.
+    pub fn validate(&self) -> Result<(), BalloonError> {
+        if self.min_mib > self.max_mib {
+            return Err(BalloonError::InvalidPolicy(self.min_mib, self.max_mib));
+        }
+        Ok(())
+    }
+
+    /// Computes the balloon target that keeps the configured headroom, starting
+    /// from `current_mib`. Returns `None` when the stats do not contain the
+    /// memory availability or when the target does not need to change.
+    fn next_target(&self, current_mib: u32, stats: &BalloonStats, oom: bool) -> Option<u32> {
+        let desired = if oom {
+            // The guest is under memory pressure, give memory back.
+            u64::from(self.min_mib)
+        } else {
+            let available_mib = stats.available_memory.or(stats.free_memory)? >> MIB_SHIFT;
+            (u64::from(current_mib) + available_mib).saturating_sub(u64::from(self.target_free_mib))
+        };
+        let mut desired = desired.clamp(u64::from(self.min_mib), u64::from(self.max_mib));
+
+        if self.max_step_mib > 0 {
+            let step = u64::from(self.max_step_mib);
+            let current = u64::from(current_mib);
+            desired = desired.clamp(current.saturating_sub(step), current + step);
+        }
+
+        // The result is bounded by `max_mib` or `current_mib`, so it fits into u32.
+        let desired = u32::try_from(desired).ok()?;
+        (desired != current_mib).then_some(desired)
+    }
+}
+
+/// Runtime state of the automatic balloon policy.
+#[derive(Debug)]
+pub(crate) struct PolicyEngine {
+    pub policy: BalloonPolicy,
+    // Time of the last applied adjustment, used for rate limiting.
+    last_adjustment: Option<Instant>,
+    // OOM kill counter reported by the previous statistics update.
+    last_oom_kill: Option<u64>,
+}
+
+impl PolicyEngine {
+    pub fn new(policy: BalloonPolicy) -> Self {
+        Self {
+            policy,
+            last_adjustment: None,
+            last_oom_kill: None,
+        }
+    }
+
+    /// Evaluates the policy against the latest guest statistics and returns
+    /// the new balloon target in MiB, if it should be changed.
+    pub fn evaluate(
+        &mut self,
+        current_mib: u32,
+        stats: &BalloonStats,
+        now: Instant,
+    ) -> Option<u32> {
+        let oom = match (self.last_oom_kill, stats.oom_kill) {
+            (Some(prev), Some(cur)) => cur > prev,
+            _ => false,
+        };
+        if stats.oom_kill.is_some() {
+            self.last_oom_kill = stats.oom_kill;
+        }
+
+        // OOM kills bypass the rate limiting so that memory is given back quickly.
+        let interval = Duration::from_secs(u64::from(self.policy.min_interval_s));
+        if !oom
+            && self
+                .last_adjustment
+                .is_some_and(|last| now.saturating_duration_since(last) < interval)
+        {
+            return None;
+        }
+
+        let target = self.policy.next_target(current_mib, stats, oom)?;
+        self.last_adjustment = Some(now);
+        if oom {
+            METRICS.policy_oom_deflate_count.inc();
+            EVENT_LOG.emit(EventKind::BalloonOomDeflate { target_mib: target });
+        }
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_with_available(available_mib: u64) -> BalloonStats {
+        BalloonStats {
+            available_memory: Some(available_mib << MIB_SHIFT),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut policy = BalloonPolicy {
+            target_free_mib: 64,
+            min_mib: 0,
+            max_mib: 128,
+            ..Default::default()
+        };
+        policy.validate().unwrap();
+
+        policy.min_mib = 256;
+        assert!(matches!(
+            policy.validate(),
+            Err(BalloonError::InvalidPolicy(256, 128))
+        ));
+    }
+
+    #[test]
+    fn test_next_target() {
+        let policy = BalloonPolicy {
+            target_free_mib: 100,
+            min_mib: 10,
+            max_mib: 500,
+            max_step_mib: 0,
+            min_interval_s: 0,
+        };
+
+        // Inflate by the excess of available memory.
+        assert_eq!(
+            policy.next_target(50, &stats_with_available(300), false),
+            Some(250)
+        );
+        // Deflate when the guest is below the headroom.
+        assert_eq!(
+            policy.next_target(250, &stats_with_available(40), false),
+            Some(190)
+        );
+        // Bounded by max_mib and min_mib.
+        assert_eq!(
+            policy.next_target(400, &stats_with_available(1000), false),
+            Some(500)
+        );
+        assert_eq!(
+            policy.next_target(20, &stats_with_available(0), false),
+            Some(10)
+        );
+        // Nothing to do when the headroom is met.
+        assert_eq!(
+            policy.next_target(50, &stats_with_available(100), false),
+            None
+        );
+        // Falls back to free memory and gives up without any of them.
+        let stats = BalloonStats {
+            free_memory: Some(200 << MIB_SHIFT),
+            ..Default::default()
+        };
+        assert_eq!(policy.next_target(0, &stats, false), Some(100));
+        assert_eq!(policy.next_target(0, &BalloonStats::default(), false), None);
+        // OOM kills deflate the balloon to its minimum.
+        assert_eq!(
+            policy.next_target(300, &stats_with_available(1000), true),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_next_target_step() {
+        let policy = BalloonPolicy {
+            target_free_mib: 0,
+            min_mib: 0,
+            max_mib: 1000,
+            max_step_mib: 32,
+            min_interval_s: 0,
+        };
+
+        assert_eq!(
+            policy.next_target(100, &stats_with_available(500), false),
+            Some(132)
+        );
+        assert_eq!(
+            policy.next_target(100, &stats_with_available(0), true),
+            Some(68)
+        );
+        assert_eq!(
+            policy.next_target(10, &stats_with_available(0), true),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_rate_limit() {
+        let mut engine = PolicyEngine::new(BalloonPolicy {
+            target_free_mib: 100,
+            min_mib: 0,
+            max_mib: 1000,
+            max_step_mib: 0,
+            min_interval_s: 10,
+        });
+        let now = Instant::now();
+
+        let mut stats = stats_with_available(300);
+        stats.oom_kill = Some(0);
+        assert_eq!(engine.evaluate(0, &stats, now), Some(200));
+        // Rate limited.
+        let stats_no_oom = stats_with_available(300);
+        assert_eq!(
+            engine.evaluate(200, &stats_no_oom, now + Duration::from_secs(5)),
+            None
+        );
+        assert_eq!(
+            engine.evaluate(200, &stats_no_oom, now + Duration::from_secs(10)),
+            Some(400)
+        );
+        // A new OOM kill is not rate limited.
+        stats.oom_kill = Some(1);
+        assert_eq!(
+            engine.evaluate(400, &stats, now + Duration::from_secs(11)),
+            Some(0)
+        );
+        // The same OOM kill counter does not trigger again.
+        assert_eq!(
+            engine.evaluate(0, &stats, now + Duration::from_secs(12)),
+            None
+        );
+    }
+}
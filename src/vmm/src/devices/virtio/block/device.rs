@@ -60,6 +60,15 @@ impl Block {
         }
     }
 
+    pub fn check_disk_image(&self, disk_image_path: &str) -> Result<(), BlockError> {
+        match self {
+            Self::Virtio(b) => b
+                .check_disk_image(disk_image_path)
+                .map_err(BlockError::VirtioBackend),
+            Self::VhostUser(_) => Err(BlockError::InvalidBlockBackend),
+        }
+    }
+
     pub fn update_rate_limiter(
         &mut self,
         bytes: BucketUpdate,
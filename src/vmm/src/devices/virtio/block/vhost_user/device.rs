@@ -6,9 +6,10 @@
 
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 use log::error;
-use utils::time::{ClockType, get_time_us};
+use utils::time::{ClockType, TimerFd, get_time_us};
 use vhost::vhost_user::Frontend;
 use vhost::vhost_user::message::*;
 use vmm_sys_util::eventfd::EventFd;
@@ -35,6 +36,13 @@ use crate::{MutEventSubscriber, impl_device_type};
 /// Block device config space size in bytes.
 const BLOCK_CONFIG_SPACE_SIZE: u32 = 60;
 
+/// Delay before the first attempt to reconnect to a backend that went away.
+const RECONNECT_MIN_DELAY_MS: u64 = 100;
+/// Upper bound of the delay between reconnection attempts.
+const RECONNECT_MAX_DELAY_MS: u64 = 5000;
+/// How long requests to a backend being restored can block the event loop.
+const RECONNECT_TIMEOUT_MS: u64 = 1000;
+
 const AVAILABLE_FEATURES: u64 = (1 << VIRTIO_F_VERSION_1)
     | (1 << VIRTIO_RING_F_EVENT_IDX)
     // vhost-user specific bit. Not defined in standard virtio spec.
@@ -133,6 +141,10 @@ pub struct VhostUserBlockImpl<T: VhostUserHandleBackend> {
     pub vu_handle: VhostUserHandleImpl<T>,
     pub vu_acked_protocol_features: u64,
     pub metrics: Arc<VhostUserDeviceMetrics>,
+
+    // Backend reconnection state.
+    pub reconnect_timer: TimerFd,
+    pub reconnect_attempts: u32,
 }
 
 // Need custom implementation because otherwise `Debug` is required for `vhost::Master`
@@ -157,6 +169,7 @@ impl<T: VhostUserHandleBackend> std::fmt::Debug for VhostUserBlockImpl<T> {
                 &self.vu_acked_protocol_features,
             )
             .field("metrics", &self.metrics)
+            .field("reconnect_attempts", &self.reconnect_attempts)
             .finish()
     }
 }
@@ -171,7 +184,10 @@ impl<T: VhostUserHandleBackend> VhostUserBlockImpl<T> {
             requested_features |= 1 << VIRTIO_BLK_F_FLUSH;
         }
 
-        let requested_protocol_features = VhostUserProtocolFeatures::CONFIG;
+        // Inflight I/O tracking allows to resume processing of the queues
+        // if the backend restarts.
+        let requested_protocol_features =
+            VhostUserProtocolFeatures::CONFIG | VhostUserProtocolFeatures::INFLIGHT_SHMFD;
 
         let mut vu_handle = VhostUserHandleImpl::<T>::new(&config.socket, NUM_QUEUES)
             .map_err(VhostUserBlockError::VhostUser)?;
@@ -236,6 +252,9 @@ impl<T: VhostUserHandleBackend> VhostUserBlockImpl<T> {
             vu_handle,
             vu_acked_protocol_features: acked_protocol_features,
             metrics,
+
+            reconnect_timer: TimerFd::new(),
+            reconnect_attempts: 0,
         })
     }
 
@@ -285,6 +304,49 @@ impl<T: VhostUserHandleBackend> VhostUserBlockImpl<T> {
 
         Ok(())
     }
+
+    /// Whether the inflight I/O tracking was negotiated with the backend.
+    fn inflight_enabled(&self) -> bool {
+        self.vu_acked_protocol_features & VhostUserProtocolFeatures::INFLIGHT_SHMFD.bits() != 0
+    }
+
+    /// Connect to a restarted backend and bring it to the state the previous
+    /// backend had: negotiated features, inflight region, memory table and vrings.
+    pub fn reconnect(&mut self) -> Result<(), VhostUserBlockError> {
+        let start_time = get_time_us(ClockType::Monotonic);
+        let ActiveState { mem, interrupt } = self
+            .device_state
+            .active_state()
+            .expect("Device is not initialized");
+
+        self.vu_handle
+            .reconnect(NUM_QUEUES, Duration::from_millis(RECONNECT_TIMEOUT_MS))
+            .and_then(|()| {
+                self.vu_handle.restore_backend(
+                    self.acked_features,
+                    self.vu_acked_protocol_features,
+                    mem,
+                    &[(0, &self.queues[0], &self.queue_evts[0])],
+                    interrupt.clone(),
+                )
+            })
+            .and_then(|()| self.vu_handle.set_timeout(None))
+            .map_err(VhostUserBlockError::VhostUser)?;
+
+        let delta_us = get_time_us(ClockType::Monotonic) - start_time;
+        self.metrics.reconnect_time_us.store(delta_us);
+        Ok(())
+    }
+
+    /// Arm the reconnection timer, doubling the delay with every failed attempt.
+    pub fn schedule_reconnect(&mut self) {
+        let delay_ms = RECONNECT_MIN_DELAY_MS
+            .saturating_mul(2u64.saturating_pow(self.reconnect_attempts))
+            .min(RECONNECT_MAX_DELAY_MS);
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
+        self.reconnect_timer
+            .arm(Duration::from_millis(delay_ms), None);
+    }
 }
 
 impl<T: VhostUserHandleBackend + Send + 'static> VirtioDevice for VhostUserBlockImpl<T>
@@ -358,9 +420,13 @@ where
         let start_time = get_time_us(ClockType::Monotonic);
         // Setting features again, because now we negotiated them
         // with guest driver as well.
+        let inflight_enabled = self.inflight_enabled();
         self.vu_handle
             .set_features(self.acked_features)
             .and_then(|()| {
+                if inflight_enabled {
+                    self.vu_handle.setup_inflight(&self.queues)?;
+                }
                 self.vu_handle.setup_backend(
                     &mem,
                     &[(0, &self.queues[0], &self.queue_evts[0])],
@@ -647,7 +713,7 @@ mod tests {
         );
         assert_eq!(
             vhost_block.vu_acked_protocol_features,
-            VhostUserProtocolFeatures::CONFIG.bits()
+            (VhostUserProtocolFeatures::CONFIG | VhostUserProtocolFeatures::INFLIGHT_SHMFD).bits()
         );
         assert_eq!(
             unsafe { &*vhost_block.vu_handle.vu.hdr_flags.get() }.bits(),
@@ -831,4 +897,51 @@ mod tests {
         assert!(unsafe { *vhost_block.vu_handle.vu.vring_enabled.get() });
         assert!(vhost_block.is_activated());
     }
+
+    #[test]
+    fn test_schedule_reconnect() {
+        struct MockMaster;
+
+        impl VhostUserHandleBackend for MockMaster {
+            fn from_stream(_sock: UnixStream, _max_queue_num: u64) -> Self {
+                Self
+            }
+
+            fn set_owner(&self) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+
+            fn get_features(&self) -> Result<u64, vhost::Error> {
+                Ok(0)
+            }
+
+            fn set_hdr_flags(&self, _flags: VhostUserHeaderFlag) {}
+        }
+
+        impl MutEventSubscriber for VhostUserBlockImpl<MockMaster> {
+            fn process(&mut self, _: Events, _: &mut EventOps) {}
+            fn init(&mut self, _: &mut EventOps) {}
+        }
+
+        let (_tmp_dir, tmp_socket_path) = create_tmp_socket();
+        let vhost_block_config = VhostUserBlockConfig {
+            drive_id: "test_drive".to_string(),
+            partuuid: None,
+            is_root_device: false,
+            cache_type: CacheType::Unsafe,
+            socket: tmp_socket_path,
+        };
+        let mut vhost_block = VhostUserBlockImpl::<MockMaster>::new(vhost_block_config).unwrap();
+        assert!(!vhost_block.reconnect_timer.is_armed());
+
+        // Every failed attempt arms the timer and backs off further, up to the cap.
+        for attempt in 1..=10 {
+            vhost_block.schedule_reconnect();
+            assert!(vhost_block.reconnect_timer.is_armed());
+            assert_eq!(vhost_block.reconnect_attempts, attempt);
+        }
+        vhost_block.reconnect_attempts = u32::MAX;
+        vhost_block.schedule_reconnect();
+        assert_eq!(vhost_block.reconnect_attempts, u32::MAX);
+    }
 }
@@ -5,10 +5,34 @@ use vmm_sys_util::epoll::EventSet;
 
 use super::VhostUserBlock;
 use crate::devices::virtio::device::VirtioDevice;
-use crate::logger::{error, warn};
+use crate::devices::virtio::vhost_user::VhostUserHandleBackend;
+use crate::logger::{IncMetric, error, info, warn};
 
 impl VhostUserBlock {
     const PROCESS_ACTIVATE: u32 = 0;
+    const PROCESS_BACKEND_SOCKET: u32 = 1;
+    const PROCESS_RECONNECT_TIMER: u32 = 2;
+
+    fn register_runtime_events(&self, ops: &mut EventOps) {
+        self.register_backend_socket_event(ops);
+        if let Err(err) = ops.add(Events::with_data(
+            &self.reconnect_timer,
+            Self::PROCESS_RECONNECT_TIMER,
+            EventSet::IN,
+        )) {
+            error!("Failed to register reconnect timer event: {}", err);
+        }
+    }
+
+    fn register_backend_socket_event(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            &self.vu_handle.vu.socket_fd(),
+            Self::PROCESS_BACKEND_SOCKET,
+            EventSet::READ_HANG_UP,
+        )) {
+            error!("Failed to register backend socket event: {}", err);
+        }
+    }
 
     fn register_activate_event(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
@@ -24,6 +48,7 @@ impl VhostUserBlock {
         if let Err(err) = self.activate_evt.read() {
             error!("Failed to consume block activate event: {:?}", err);
         }
+        self.register_runtime_events(ops);
         if let Err(err) = ops.remove(Events::with_data(
             &self.activate_evt,
             Self::PROCESS_ACTIVATE,
@@ -32,14 +57,72 @@ impl VhostUserBlock {
             error!("Failed to un-register activate event: {}", err);
         }
     }
+
+    fn process_backend_socket_event(&mut self, ops: &mut EventOps) {
+        // The socket is going to be replaced on reconnection, so it has to be
+        // removed from the epoll set while its file descriptor is still open.
+        if let Err(err) = ops.remove(Events::with_data(
+            &self.vu_handle.vu.socket_fd(),
+            Self::PROCESS_BACKEND_SOCKET,
+            EventSet::READ_HANG_UP,
+        )) {
+            error!("Failed to un-register backend socket event: {}", err);
+        }
+        warn!(
+            "BlockVhost: backend {} of drive {} disconnected",
+            self.vu_handle.socket_path, self.id
+        );
+        self.metrics.disconnects.inc();
+        // Without inflight I/O tracking a new backend can not tell which requests
+        // were already processed, so it would run some of them again.
+        if self.vu_handle.inflight.is_none() {
+            error!(
+                "BlockVhost: drive {} can not be reconnected without inflight I/O tracking",
+                self.id
+            );
+            return;
+        }
+        // Reconnection attempts are driven by the timer, so that the event loop
+        // is not held up while the backend is restarting.
+        self.reconnect_attempts = 0;
+        self.schedule_reconnect();
+    }
+
+    fn process_reconnect_timer_event(&mut self, ops: &mut EventOps) {
+        _ = self.reconnect_timer.read();
+        self.try_reconnect(ops);
+    }
+
+    fn try_reconnect(&mut self, ops: &mut EventOps) {
+        match self.reconnect() {
+            Ok(()) => {
+                info!(
+                    "BlockVhost: drive {} reconnected to backend {}",
+                    self.id, self.vu_handle.socket_path
+                );
+                self.metrics.reconnects.inc();
+                self.reconnect_attempts = 0;
+                self.register_backend_socket_event(ops);
+            }
+            Err(err) => {
+                warn!(
+                    "BlockVhost: failed to reconnect drive {} to backend: {}",
+                    self.id, err
+                );
+                self.metrics.reconnect_fails.inc();
+                self.schedule_reconnect();
+            }
+        }
+    }
 }
 
 impl MutEventSubscriber for VhostUserBlock {
-    // Handle an event for queue or rate limiter.
+    // Handle an event for the activate event, the backend socket or the reconnect timer.
     fn process(&mut self, event: Events, ops: &mut EventOps) {
         let source = event.data();
         let event_set = event.event_set();
-        let supported_events = EventSet::IN;
+        let supported_events =
+            EventSet::IN | EventSet::READ_HANG_UP | EventSet::HANG_UP | EventSet::ERROR;
 
         if !supported_events.contains(event_set) {
             warn!(
@@ -50,10 +133,11 @@ impl MutEventSubscriber for VhostUserBlock {
         }
 
         if self.is_activated() {
-            if Self::PROCESS_ACTIVATE == source {
-                self.process_activate_event(ops)
-            } else {
-                warn!("BlockVhost: Spurious event received: {:?}", source)
+            match source {
+                Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
+                Self::PROCESS_BACKEND_SOCKET => self.process_backend_socket_event(ops),
+                Self::PROCESS_RECONNECT_TIMER => self.process_reconnect_timer_event(ops),
+                _ => warn!("BlockVhost: Spurious event received: {:?}", source),
             }
         } else {
             warn!(
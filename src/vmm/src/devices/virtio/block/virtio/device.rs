@@ -549,6 +549,13 @@ impl VirtioBlock {
         Ok(())
     }
 
+    /// Checks that the file at `disk_image_path` can back the device, without updating it.
+    pub fn check_disk_image(&self, disk_image_path: &str) -> Result<(), VirtioBlockError> {
+        let mut disk_image = DiskProperties::open_file(disk_image_path, self.read_only)?;
+        DiskProperties::file_size(disk_image_path, &mut disk_image)?;
+        Ok(())
+    }
+
     /// Updates the parameters for the rate limiter
     pub fn update_rate_limiter(&mut self, bytes: BucketUpdate, ops: BucketUpdate) {
         self.rate_limiter.update_buckets(bytes, ops);
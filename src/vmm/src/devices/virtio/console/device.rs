@@ -0,0 +1,818 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+
+use vmm_sys_util::eventfd::EventFd;
+
+use super::metrics::METRICS;
+use super::port::ConsolePort;
+use super::{CONSOLE_MAX_PORTS, CONTROL_RXQ, CONTROL_TXQ, num_queues, queue_port, rx_queue};
+use crate::devices::DeviceError;
+use crate::devices::virtio::ActivateError;
+use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
+use crate::devices::virtio::iov_deque::IovDequeError;
+use crate::devices::virtio::iovec::{IoVecBuffer, IoVecBufferMut};
+use crate::devices::virtio::queue::{FIRECRACKER_MAX_QUEUE_SIZE, InvalidAvailIdx, Queue};
+use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
+use crate::impl_device_type;
+use crate::logger::{IncMetric, error, info, warn};
+use crate::utils::{u64_to_usize, usize_to_u64};
+use crate::vmm_config::console::ConsolePortConfig;
+use crate::vstate::memory::GuestMemoryMmap;
+
+pub const CONSOLE_DEV_ID: &str = "console";
+
+/// The device has multiple ports, set up through the control queues.
+pub const VIRTIO_CONSOLE_F_MULTIPORT: u32 = 1;
+
+// Events of the control messages exchanged with the guest driver.
+pub(crate) const VIRTIO_CONSOLE_DEVICE_READY: u16 = 0;
+pub(crate) const VIRTIO_CONSOLE_DEVICE_ADD: u16 = 1;
+pub(crate) const VIRTIO_CONSOLE_PORT_READY: u16 = 3;
+pub(crate) const VIRTIO_CONSOLE_CONSOLE_PORT: u16 = 4;
+pub(crate) const VIRTIO_CONSOLE_PORT_OPEN: u16 = 6;
+pub(crate) const VIRTIO_CONSOLE_PORT_NAME: u16 = 7;
+
+/// Size of the header of a control message: port id (u32), event (u16) and value (u16).
+pub(crate) const CONTROL_MSG_SIZE: usize = 8;
+
+/// Size of the configuration space: cols (u16), rows (u16), max_nr_ports (u32) and
+/// emerg_wr (u32).
+const CONFIG_SPACE_SIZE: usize = 12;
+
+/// Maximum number of bytes moved per descriptor chain.
+///
+/// Like for the entropy device, this keeps the host side allocation bounded regardless of how
+/// the descriptor chain is constructed.
+const MAX_BUFFER_SIZE: u32 = 64 * 1024;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ConsoleError {
+    /// Error while handling an Event file descriptor: {0}
+    EventFd(#[from] io::Error),
+    /// Underlying IovDeque error: {0}
+    IovDeque(#[from] IovDequeError),
+    /// The console device needs between 1 and 16 ports
+    PortCount,
+    /// Invalid or duplicate port name: {0:?}
+    PortName(String),
+    /// Port {0} needs exactly one of uds_path and output_path
+    PortBackend(String),
+    /// Could not bind the socket {0}: {1}
+    Bind(String, io::Error),
+    /// Could not open the output file {0}: {1}
+    OpenOutput(String, io::Error),
+}
+
+#[derive(Debug)]
+pub struct Console {
+    // VirtIO fields
+    avail_features: u64,
+    acked_features: u64,
+    activate_event: EventFd,
+
+    // Transport fields
+    device_state: DeviceState,
+    pub(crate) queues: Vec<Queue>,
+    queue_events: Vec<EventFd>,
+
+    // Device specific fields
+    pub(crate) ports: Vec<ConsolePort>,
+    /// Control messages waiting for buffers on the control receive queue, at most one for each
+    /// port and event.
+    pub(crate) pending_control: VecDeque<Vec<u8>>,
+    /// Connections of the detached clients, to remove from the event loop.
+    pub(crate) detached_clients: Vec<UnixStream>,
+
+    rx_buffer: IoVecBufferMut,
+    tx_buffer: IoVecBuffer,
+}
+
+impl Console {
+    pub fn new(configs: Vec<ConsolePortConfig>) -> Result<Self, ConsoleError> {
+        if configs.is_empty() || configs.len() > CONSOLE_MAX_PORTS {
+            return Err(ConsoleError::PortCount);
+        }
+        for (i, config) in configs.iter().enumerate() {
+            // The name ends up in a path of the guest: `/dev/virtio-ports/<name>`.
+            if config.name.is_empty()
+                || config.name.contains('/')
+                || configs[..i].iter().any(|other| other.name == config.name)
+            {
+                return Err(ConsoleError::PortName(config.name.clone()));
+            }
+        }
+
+        let ports = configs
+            .into_iter()
+            .map(ConsolePort::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let queues = vec![Queue::new(FIRECRACKER_MAX_QUEUE_SIZE); num_queues(ports.len())];
+        Self::new_with_queues(queues, ports)
+    }
+
+    pub fn new_with_queues(
+        queues: Vec<Queue>,
+        ports: Vec<ConsolePort>,
+    ) -> Result<Self, ConsoleError> {
+        let activate_event = EventFd::new(libc::EFD_NONBLOCK)?;
+        let queue_events = (0..queues.len())
+            .map(|_| EventFd::new(libc::EFD_NONBLOCK))
+            .collect::<Result<Vec<EventFd>, io::Error>>()?;
+
+        Ok(Self {
+            avail_features: (1 << VIRTIO_F_VERSION_1) | (1 << VIRTIO_CONSOLE_F_MULTIPORT),
+            acked_features: 0u64,
+            activate_event,
+            device_state: DeviceState::Inactive,
+            queues,
+            queue_events,
+            ports,
+            pending_control: VecDeque::new(),
+            detached_clients: Vec::new(),
+            rx_buffer: IoVecBufferMut::new()?,
+            tx_buffer: IoVecBuffer::default(),
+        })
+    }
+
+    /// Configurations of the ports of the device.
+    pub fn port_configs(&self) -> Vec<ConsolePortConfig> {
+        self.ports.iter().map(|port| port.config.clone()).collect()
+    }
+
+    fn signal_used_queue(&self, queue: usize) -> Result<(), DeviceError> {
+        self.interrupt_trigger()
+            .trigger(VirtioInterruptType::Queue(queue.try_into().unwrap()))
+            .map_err(DeviceError::FailedSignalingIrq)
+    }
+
+    /// Returns the used descriptors of `queue` to the guest.
+    fn complete_queue(&mut self, queue: usize, used_any: bool) {
+        self.queues[queue].advance_used_ring_idx();
+
+        if used_any {
+            self.signal_used_queue(queue).unwrap_or_else(|err| {
+                error!("console: {err:?}");
+                METRICS.event_fails.inc()
+            });
+        }
+    }
+
+    /// Queues a control message about `port` for the guest driver.
+    pub(crate) fn send_control(&mut self, port: usize, event: u16, value: u16, data: &[u8]) {
+        let mut msg = Vec::with_capacity(CONTROL_MSG_SIZE + data.len());
+        msg.extend_from_slice(&u32::try_from(port).unwrap().to_le_bytes());
+        msg.extend_from_slice(&event.to_le_bytes());
+        msg.extend_from_slice(&value.to_le_bytes());
+        msg.extend_from_slice(data);
+        // Only the latest message about the same port and event matters to the guest driver.
+        // Dropping the older one keeps the queue bounded when the guest keeps sending requests
+        // without providing buffers for the replies.
+        self.pending_control
+            .retain(|pending| pending[..6] != msg[..6]);
+        self.pending_control.push_back(msg);
+    }
+
+    /// Sends the queued control messages, as long as the guest provides buffers for them.
+    fn process_control_rx(&mut self) -> Result<(), InvalidAvailIdx> {
+        let mut used_any = false;
+        while !self.pending_control.is_empty() {
+            let Some(head) = self.queues[CONTROL_RXQ].pop()? else {
+                break;
+            };
+            // This is safe since we checked in the caller that the device is activated.
+            let mem = &self.device_state.active_state().unwrap().mem;
+            let index = head.index;
+            // The message is dropped if the guest gives a buffer too small for it.
+            let msg = self.pending_control.pop_front().unwrap();
+
+            // SAFETY: This descriptor chain points to a single `DescriptorChain` memory buffer,
+            // no other `IoVecBufferMut` object points to the same `DescriptorChain` at the same
+            // time and we clear the `iovec` after we process the request.
+            let len = match unsafe { self.rx_buffer.load_descriptor_chain(mem, head) } {
+                Ok(()) => match self.rx_buffer.write_all_volatile_at(&msg, 0) {
+                    Ok(()) => u32::try_from(msg.len()).unwrap(),
+                    Err(err) => {
+                        error!("console: Could not write control message: {err}");
+                        METRICS.event_fails.inc();
+                        0
+                    }
+                },
+                Err(err) => {
+                    error!("console: Could not parse descriptor chain: {err}");
+                    METRICS.event_fails.inc();
+                    0
+                }
+            };
+
+            if let Err(err) = self.queues[CONTROL_RXQ].add_used(index, len) {
+                error!("console: Could not add used descriptor to queue: {err}");
+                METRICS.event_fails.inc();
+                break;
+            }
+            used_any = true;
+        }
+        self.complete_queue(CONTROL_RXQ, used_any);
+
+        Ok(())
+    }
+
+    /// Handles the control messages of the guest driver.
+    fn process_control_tx(&mut self) -> Result<(), InvalidAvailIdx> {
+        let mut used_any = false;
+        while let Some(head) = self.queues[CONTROL_TXQ].pop()? {
+            // This is safe since we checked in the caller that the device is activated.
+            let mem = &self.device_state.active_state().unwrap().mem;
+            let index = head.index;
+            let mut msg = [0u8; CONTROL_MSG_SIZE];
+
+            // SAFETY: This descriptor chain points to a single `DescriptorChain` memory buffer,
+            // no other `IoVecBuffer` object points to the same `DescriptorChain` at the same
+            // time and we clear the `iovec` after we process the request.
+            match unsafe { self.tx_buffer.load_descriptor_chain(mem, head) } {
+                Ok(()) => match self.tx_buffer.read_exact_volatile_at(&mut msg, 0) {
+                    Ok(()) => self.handle_control(
+                        u32::from_le_bytes(msg[0..4].try_into().unwrap()),
+                        u16::from_le_bytes(msg[4..6].try_into().unwrap()),
+                        u16::from_le_bytes(msg[6..8].try_into().unwrap()),
+                    ),
+                    Err(err) => {
+                        error!("console: Could not read control message: {err}");
+                        METRICS.event_fails.inc();
+                    }
+                },
+                Err(err) => {
+                    error!("console: Could not parse descriptor chain: {err}");
+                    METRICS.event_fails.inc();
+                }
+            }
+
+            if let Err(err) = self.queues[CONTROL_TXQ].add_used(index, 0) {
+                error!("console: Could not add used descriptor to queue: {err}");
+                METRICS.event_fails.inc();
+                break;
+            }
+            used_any = true;
+        }
+        self.complete_queue(CONTROL_TXQ, used_any);
+
+        // Send the replies to the messages handled above.
+        self.process_control_rx()
+    }
+
+    /// Handles a control message of the guest driver about the port `id`.
+    fn handle_control(&mut self, id: u32, event: u16, value: u16) {
+        METRICS.control_count.inc();
+        if event == VIRTIO_CONSOLE_DEVICE_READY {
+            if value != 1 {
+                warn!("console: The guest driver failed to set up the device");
+                return;
+            }
+            for port in 0..self.ports.len() {
+                self.send_control(port, VIRTIO_CONSOLE_DEVICE_ADD, 0, &[]);
+            }
+            return;
+        }
+
+        let Some(port) = usize::try_from(id)
+            .ok()
+            .filter(|port| *port < self.ports.len())
+        else {
+            warn!("console: Control message {event} for unknown port {id}");
+            METRICS.event_fails.inc();
+            return;
+        };
+        match event {
+            VIRTIO_CONSOLE_PORT_READY => {
+                if value != 1 {
+                    warn!(
+                        "console: The guest driver failed to add port {}",
+                        self.ports[port].config.name
+                    );
+                    return;
+                }
+                self.ports[port].ready = true;
+                if self.ports[port].config.console {
+                    self.send_control(port, VIRTIO_CONSOLE_CONSOLE_PORT, 1, &[]);
+                }
+                let name = self.ports[port].config.name.clone();
+                self.send_control(port, VIRTIO_CONSOLE_PORT_NAME, 1, name.as_bytes());
+                if self.ports[port].host_connected() {
+                    self.send_control(port, VIRTIO_CONSOLE_PORT_OPEN, 1, &[]);
+                }
+            }
+            VIRTIO_CONSOLE_PORT_OPEN => {
+                self.ports[port].guest_connected = value == 1;
+                if self.ports[port].guest_connected {
+                    info!(
+                        "console: Port {} opened by the guest",
+                        self.ports[port].config.name
+                    );
+                    // Input may have been waiting for the guest.
+                    self.process_rx(port).unwrap();
+                }
+            }
+            _ => info!("console: Ignoring control message {event} for port {id}"),
+        }
+    }
+
+    /// Moves the input of the client attached to `port` to the receive queue of the port.
+    fn process_rx(&mut self, port: usize) -> Result<(), InvalidAvailIdx> {
+        // The guest driver discards the input of the ports that it did not open.
+        if !self.ports[port].guest_connected {
+            return Ok(());
+        }
+
+        let queue = rx_queue(port);
+        let mut used_any = false;
+        while self.ports[port].client().is_some() {
+            let Some(head) = self.queues[queue].pop()? else {
+                break;
+            };
+            // This is safe since we checked in the caller that the device is activated.
+            let mem = &self.device_state.active_state().unwrap().mem;
+            let index = head.index;
+
+            // SAFETY: This descriptor chain points to a single `DescriptorChain` memory buffer,
+            // no other `IoVecBufferMut` object points to the same `DescriptorChain` at the same
+            // time and we clear the `iovec` after we process the request.
+            let len = match unsafe { self.rx_buffer.load_descriptor_chain(mem, head) } {
+                Ok(()) => std::cmp::min(self.rx_buffer.len(), MAX_BUFFER_SIZE),
+                Err(err) => {
+                    error!("console: Could not parse descriptor chain: {err}");
+                    METRICS.event_fails.inc();
+                    0
+                }
+            };
+
+            let mut count = 0;
+            if len > 0 {
+                let mut buf = vec![0u8; len as usize];
+                match self.ports[port].read_input(&mut buf) {
+                    Ok(0) => self.detach_client(port),
+                    Ok(read) => {
+                        // It is ok to unwrap here. We are writing at most `len` bytes at
+                        // offset 0.
+                        self.rx_buffer
+                            .write_all_volatile_at(&buf[..read], 0)
+                            .unwrap();
+                        count = read;
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                    Err(err) => {
+                        warn!("console: Could not read from client: {err}");
+                        self.detach_client(port);
+                    }
+                }
+                if count == 0 {
+                    // Keep the buffer for the next input.
+                    self.queues[queue].undo_pop();
+                    break;
+                }
+            }
+
+            if let Err(err) = self.queues[queue].add_used(index, u32::try_from(count).unwrap()) {
+                error!("console: Could not add used descriptor to queue: {err}");
+                METRICS.event_fails.inc();
+                break;
+            }
+            METRICS.rx_bytes_count.add(usize_to_u64(count));
+            used_any = true;
+        }
+        self.complete_queue(queue, used_any);
+
+        Ok(())
+    }
+
+    /// Moves the output of the guest on `port` to the host side of the port.
+    fn process_tx(&mut self, port: usize) -> Result<(), InvalidAvailIdx> {
+        let queue = rx_queue(port) + 1;
+        let mut used_any = false;
+        // The output of the guest is not consumed while the client is too slow to receive it.
+        while !self.ports[port].has_pending_output() {
+            let Some(head) = self.queues[queue].pop()? else {
+                break;
+            };
+            // This is safe since we checked in the caller that the device is activated.
+            let mem = &self.device_state.active_state().unwrap().mem;
+            let index = head.index;
+
+            // SAFETY: This descriptor chain points to a single `DescriptorChain` memory buffer,
+            // no other `IoVecBuffer` object points to the same `DescriptorChain` at the same
+            // time and we clear the `iovec` after we process the request.
+            match unsafe { self.tx_buffer.load_descriptor_chain(mem, head) } {
+                Ok(()) if self.tx_buffer.len() > 0 => {
+                    let len = std::cmp::min(self.tx_buffer.len(), MAX_BUFFER_SIZE);
+                    let mut buf = vec![0u8; len as usize];
+                    // It is ok to unwrap here. We are reading at most `len` bytes at offset 0.
+                    self.tx_buffer.read_exact_volatile_at(&mut buf, 0).unwrap();
+                    METRICS.tx_bytes_count.add(u64::from(len));
+                    if let Err(err) = self.ports[port].write_output(&buf) {
+                        warn!("console: Could not write to client: {err}");
+                        self.detach_client(port);
+                    }
+                }
+                Ok(()) => (),
+                Err(err) => {
+                    error!("console: Could not parse descriptor chain: {err}");
+                    METRICS.event_fails.inc();
+                }
+            }
+
+            if let Err(err) = self.queues[queue].add_used(index, 0) {
+                error!("console: Could not add used descriptor to queue: {err}");
+                METRICS.event_fails.inc();
+                break;
+            }
+            used_any = true;
+        }
+        self.complete_queue(queue, used_any);
+
+        Ok(())
+    }
+
+    /// Tells the guest driver that a client attached to `port`.
+    pub(crate) fn attach_client(&mut self, port: usize) {
+        info!(
+            "console: Client attached to port {}",
+            self.ports[port].config.name
+        );
+        if self.ports[port].ready {
+            self.send_control(port, VIRTIO_CONSOLE_PORT_OPEN, 1, &[]);
+        }
+        if self.is_activated() {
+            self.process_control_rx().unwrap();
+            self.process_rx(port).unwrap();
+        }
+    }
+
+    /// Detaches the client of `port` and tells the guest driver about it.
+    pub(crate) fn detach_client(&mut self, port: usize) {
+        let Some(client) = self.ports[port].detach() else {
+            return;
+        };
+        info!(
+            "console: Client detached from port {}",
+            self.ports[port].config.name
+        );
+        // The connection is closed once removed from the event loop.
+        self.detached_clients.push(client);
+        if self.ports[port].ready {
+            self.send_control(port, VIRTIO_CONSOLE_PORT_OPEN, 0, &[]);
+        }
+        if self.is_activated() {
+            self.process_control_rx().unwrap();
+        }
+    }
+
+    pub(crate) fn process_queue_event(&mut self, queue: usize) {
+        if let Err(err) = self.queue_events[queue].read() {
+            error!("console: Failed to read queue event: {err}");
+            METRICS.event_fails.inc();
+            return;
+        }
+
+        match queue {
+            CONTROL_RXQ => self.process_control_rx().unwrap(),
+            CONTROL_TXQ => self.process_control_tx().unwrap(),
+            _ => match queue_port(queue) {
+                (port, true) => self.process_tx(port).unwrap(),
+                (port, false) => self.process_rx(port).unwrap(),
+            },
+        }
+    }
+
+    /// Handles the readiness of the connection of the client attached to `port`.
+    ///
+    /// `hang_up` tells that the client closed its connection.
+    pub(crate) fn process_client_event(&mut self, port: usize, hang_up: bool) {
+        if let Err(err) = self.ports[port].flush_output() {
+            warn!("console: Could not write to client: {err}");
+            self.detach_client(port);
+            return;
+        }
+        if self.is_activated() {
+            // Resume the output of the guest, paused while the client was too slow.
+            self.process_tx(port).unwrap();
+            self.process_rx(port).unwrap();
+        }
+        // The input is only read while the guest opened the port, so the client can be gone
+        // without the end of its connection being read.
+        if hang_up {
+            self.detach_client(port);
+        }
+    }
+
+    pub fn process_virtio_queues(&mut self) -> Result<(), InvalidAvailIdx> {
+        self.process_control_tx()?;
+        for port in 0..self.ports.len() {
+            self.process_tx(port)?;
+            self.process_rx(port)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_avail_features(&mut self, features: u64) {
+        self.avail_features = features;
+    }
+
+    pub(crate) fn set_acked_features(&mut self, features: u64) {
+        self.acked_features = features;
+    }
+
+    pub(crate) fn set_activated(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) {
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+    }
+
+    pub(crate) fn activate_event(&self) -> &EventFd {
+        &self.activate_event
+    }
+}
+
+impl VirtioDevice for Console {
+    impl_device_type!(VirtioDeviceType::Console);
+
+    fn id(&self) -> &str {
+        CONSOLE_DEV_ID
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
+        self.device_state
+            .active_state()
+            .expect("Device is not initialized")
+            .interrupt
+            .deref()
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn read_config(&self, offset: u64, data: &mut [u8]) {
+        // The console size is not reported, and the emergency write is not supported.
+        let mut config_space = [0u8; CONFIG_SPACE_SIZE];
+        config_space[4..8].copy_from_slice(&u32::try_from(self.ports.len()).unwrap().to_le_bytes());
+
+        if let Some(config_space_bytes) = config_space.get(u64_to_usize(offset)..) {
+            let len = config_space_bytes.len().min(data.len());
+            data[..len].copy_from_slice(&config_space_bytes[..len]);
+        } else {
+            error!("console: Failed to read config space");
+        }
+    }
+
+    fn write_config(&mut self, _offset: u64, _data: &[u8]) {}
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn activate(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), ActivateError> {
+        for q in self.queues.iter_mut() {
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        self.activate_event.write(1).map_err(|_| {
+            METRICS.activate_fails.inc();
+            ActivateError::EventFd
+        })?;
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use vm_memory::{Bytes, GuestAddress};
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::devices::virtio::queue::VIRTQ_DESC_F_WRITE;
+    use crate::devices::virtio::test_utils::test::{
+        VirtioTestDevice, VirtioTestHelper, create_virtio_mem,
+    };
+
+    impl VirtioTestDevice for Console {
+        fn set_queues(&mut self, queues: Vec<Queue>) {
+            self.queues = queues;
+        }
+
+        fn num_queues(&self) -> usize {
+            num_queues(self.ports.len())
+        }
+    }
+
+    fn socket_path() -> TempFile {
+        let mut path = TempFile::new().unwrap();
+        path.remove().unwrap();
+        path
+    }
+
+    fn port_config(name: &str, uds_path: &TempFile) -> ConsolePortConfig {
+        ConsolePortConfig {
+            name: name.to_string(),
+            console: false,
+            uds_path: Some(uds_path.as_path().to_str().unwrap().to_string()),
+            output_path: None,
+        }
+    }
+
+    fn control_msg(port: u32, event: u16, value: u16) -> Vec<u8> {
+        let mut msg = port.to_le_bytes().to_vec();
+        msg.extend_from_slice(&event.to_le_bytes());
+        msg.extend_from_slice(&value.to_le_bytes());
+        msg
+    }
+
+    // Sends a control message from the guest driver.
+    fn send_control(th: &mut VirtioTestHelper<Console>, mem: &GuestMemoryMmap, msg: &[u8]) {
+        th.add_desc_chain(CONTROL_TXQ, 0, &[(0, 8, 0)]);
+        mem.write_slice(msg, th.desc_address(CONTROL_TXQ, 0))
+            .unwrap();
+        th.emulate_for_msec(100).unwrap();
+    }
+
+    // Receives a control message in the guest driver.
+    fn recv_control(th: &mut VirtioTestHelper<Console>, mem: &GuestMemoryMmap) -> Vec<u8> {
+        th.add_desc_chain(CONTROL_RXQ, 0x1000, &[(0, 64, VIRTQ_DESC_F_WRITE)]);
+        th.emulate_for_msec(100).unwrap();
+        let mut msg = vec![0u8; 64];
+        mem.read_slice(&mut msg, th.desc_address(CONTROL_RXQ, 0))
+            .unwrap();
+        msg
+    }
+
+    #[test]
+    fn test_new() {
+        let path = socket_path();
+        let console = Console::new(vec![port_config("port", &path)]).unwrap();
+        assert_eq!(
+            console.avail_features(),
+            (1 << VIRTIO_F_VERSION_1) | (1 << VIRTIO_CONSOLE_F_MULTIPORT)
+        );
+        assert_eq!(console.id(), CONSOLE_DEV_ID);
+        assert_eq!(console.device_type(), VirtioDeviceType::Console);
+        assert_eq!(console.queues().len(), 4);
+        assert!(!console.is_activated());
+
+        let mut config = [0u8; CONFIG_SPACE_SIZE];
+        console.read_config(0, &mut config);
+        assert_eq!(config, [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        drop(console);
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        // Invalid configurations.
+        assert!(matches!(Console::new(vec![]), Err(ConsoleError::PortCount)));
+        let path = socket_path();
+        for name in ["", "a/b"] {
+            assert!(matches!(
+                Console::new(vec![port_config(name, &path)]),
+                Err(ConsoleError::PortName(_))
+            ));
+        }
+        assert!(matches!(
+            Console::new(vec![port_config("port", &path), port_config("port", &path)]),
+            Err(ConsoleError::PortName(_))
+        ));
+    }
+
+    #[test]
+    fn test_console_ports() {
+        let path = socket_path();
+        let mem = create_virtio_mem();
+        let console = Console::new(vec![port_config("port", &path)]).unwrap();
+        let mut th = VirtioTestHelper::<Console>::new(&mem, console);
+        th.activate_device(&mem);
+
+        // The ports are added once the guest driver is ready.
+        send_control(
+            &mut th,
+            &mem,
+            &control_msg(0, VIRTIO_CONSOLE_DEVICE_READY, 1),
+        );
+        assert_eq!(
+            recv_control(&mut th, &mem)[..8],
+            control_msg(0, VIRTIO_CONSOLE_DEVICE_ADD, 0)
+        );
+        send_control(&mut th, &mem, &control_msg(0, VIRTIO_CONSOLE_PORT_READY, 1));
+        let msg = recv_control(&mut th, &mem);
+        assert_eq!(msg[..8], control_msg(0, VIRTIO_CONSOLE_PORT_NAME, 1));
+        assert_eq!(&msg[8..12], b"port");
+        assert!(th.device().pending_control.is_empty());
+        send_control(&mut th, &mem, &control_msg(0, VIRTIO_CONSOLE_PORT_OPEN, 1));
+        assert!(th.device().ports[0].guest_connected);
+
+        // The guest driver is told when a client attaches.
+        let mut client = UnixStream::connect(path.as_path()).unwrap();
+        th.emulate_for_msec(100).unwrap();
+        assert!(th.device().ports[0].host_connected());
+        assert_eq!(
+            recv_control(&mut th, &mem)[..8],
+            control_msg(0, VIRTIO_CONSOLE_PORT_OPEN, 1)
+        );
+
+        // Output of the guest.
+        th.add_desc_chain(1, 0x2000, &[(0, 5, 0)]);
+        mem.write_slice(b"hello", th.desc_address(1, 0)).unwrap();
+        th.emulate_for_msec(100).unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // Input of the client.
+        client.write_all(b"world").unwrap();
+        th.add_desc_chain(0, 0x3000, &[(0, 16, VIRTQ_DESC_F_WRITE)]);
+        th.emulate_for_msec(100).unwrap();
+        let mut buf = [0u8; 5];
+        mem.read_slice(&mut buf, th.desc_address(0, 0)).unwrap();
+        assert_eq!(&buf, b"world");
+
+        // The guest driver is told when the client detaches.
+        drop(client);
+        th.emulate_for_msec(100).unwrap();
+        assert!(!th.device().ports[0].host_connected());
+        assert!(th.device().detached_clients.is_empty());
+        assert_eq!(
+            recv_control(&mut th, &mem)[..8],
+            control_msg(0, VIRTIO_CONSOLE_PORT_OPEN, 0)
+        );
+
+        std::fs::remove_file(path.as_path()).unwrap();
+    }
+
+    #[test]
+    fn test_control_flood() {
+        let paths = [socket_path(), socket_path()];
+        let mem = create_virtio_mem();
+        let console = Console::new(vec![
+            port_config("a", &paths[0]),
+            port_config("b", &paths[1]),
+        ])
+        .unwrap();
+        let mut th = VirtioTestHelper::<Console>::new(&mem, console);
+        th.activate_device(&mem);
+
+        // The guest keeps sending requests without providing buffers for the replies.
+        for _ in 0..1000 {
+            let mut console = th.device();
+            console.handle_control(0, VIRTIO_CONSOLE_DEVICE_READY, 1);
+            console.handle_control(0, VIRTIO_CONSOLE_PORT_READY, 1);
+        }
+        assert_eq!(th.device().pending_control.len(), 3);
+
+        // The guest still gets the latest reply for each port and event.
+        assert_eq!(
+            recv_control(&mut th, &mem)[..8],
+            control_msg(0, VIRTIO_CONSOLE_DEVICE_ADD, 0)
+        );
+        assert_eq!(
+            recv_control(&mut th, &mem)[..8],
+            control_msg(1, VIRTIO_CONSOLE_DEVICE_ADD, 0)
+        );
+        let msg = recv_control(&mut th, &mem);
+        assert_eq!(msg[..8], control_msg(0, VIRTIO_CONSOLE_PORT_NAME, 1));
+        assert_eq!(&msg[8..9], b"a");
+        assert!(th.device().pending_control.is_empty());
+
+        for path in &paths {
+            std::fs::remove_file(path.as_path()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use event_manager::{EventOps, Events, MutEventSubscriber};
+use vmm_sys_util::epoll::EventSet;
+
+use super::Console;
+use super::metrics::METRICS;
+use crate::devices::virtio::device::VirtioDevice;
+use crate::logger::{IncMetric, error, warn};
+
+impl Console {
+    const PROCESS_ACTIVATE: u32 = 0;
+    // The lower 16 bits of the following sources hold the index of the queue or of the port.
+    const PROCESS_QUEUE: u32 = 1 << 16;
+    const PROCESS_LISTENER: u32 = 2 << 16;
+    const PROCESS_CLIENT: u32 = 3 << 16;
+    const INDEX_MASK: u32 = 0xffff;
+
+    fn register_runtime_events(&self, ops: &mut EventOps) {
+        for (i, queue_event) in self.queue_events().iter().enumerate() {
+            if let Err(err) = ops.add(Events::with_data(
+                queue_event,
+                Self::PROCESS_QUEUE | u32::try_from(i).unwrap(),
+                EventSet::IN,
+            )) {
+                error!("console: Failed to register queue event: {err}");
+            }
+        }
+    }
+
+    fn register_activate_event(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            self.activate_event(),
+            Self::PROCESS_ACTIVATE,
+            EventSet::IN,
+        )) {
+            error!("console: Failed to register activate event: {err}");
+        }
+    }
+
+    // The clients can attach before the device is activated.
+    fn register_listeners(&self, ops: &mut EventOps) {
+        for (i, port) in self.ports.iter().enumerate() {
+            let Some(listener) = port.listener() else {
+                continue;
+            };
+            if let Err(err) = ops.add(Events::with_data(
+                listener,
+                Self::PROCESS_LISTENER | u32::try_from(i).unwrap(),
+                EventSet::IN,
+            )) {
+                error!(
+                    "console: Failed to register socket of port {}: {err}",
+                    port.config.name
+                );
+            }
+        }
+    }
+
+    fn process_activate_event(&self, ops: &mut EventOps) {
+        if let Err(err) = self.activate_event().read() {
+            error!("console: Failed to consume activate event: {err}");
+        }
+
+        // Register runtime events
+        self.register_runtime_events(ops);
+
+        // Remove activate event
+        if let Err(err) = ops.remove(Events::with_data(
+            self.activate_event(),
+            Self::PROCESS_ACTIVATE,
+            EventSet::IN,
+        )) {
+            error!("console: Failed to un-register activate event: {err}");
+        }
+    }
+
+    fn process_listener_event(&mut self, port: usize, ops: &mut EventOps) {
+        match self.ports[port].accept() {
+            Ok(true) => {
+                // The connection is edge triggered so that a client that is too slow to receive
+                // the output of the guest does not keep the event loop busy.
+                let client = self.ports[port].client().unwrap();
+                if let Err(err) = ops.add(Events::with_data(
+                    client,
+                    Self::PROCESS_CLIENT | u32::try_from(port).unwrap(),
+                    EventSet::IN | EventSet::OUT | EventSet::EDGE_TRIGGERED,
+                )) {
+                    error!("console: Failed to register client connection: {err}");
+                    self.ports[port].detach();
+                    return;
+                }
+                self.attach_client(port);
+            }
+            Ok(false) => (),
+            Err(err) => {
+                error!("console: Failed to accept client connection: {err}");
+                METRICS.event_fails.inc();
+            }
+        }
+    }
+
+    fn remove_detached_clients(&mut self, ops: &mut EventOps) {
+        for client in self.detached_clients.drain(..) {
+            if let Err(err) = ops.remove(Events::new(&client, EventSet::IN)) {
+                error!("console: Failed to un-register client connection: {err}");
+            }
+        }
+    }
+}
+
+impl MutEventSubscriber for Console {
+    fn init(&mut self, ops: &mut event_manager::EventOps) {
+        // This function can be called during different points in the device lifetime:
+        //  - shortly after device creation,
+        //  - on device activation (is-activated already true at this point),
+        //  - on device restore from snapshot.
+        self.register_listeners(ops);
+        if self.is_activated() {
+            self.register_runtime_events(ops);
+        } else {
+            self.register_activate_event(ops);
+        }
+    }
+
+    fn process(&mut self, events: event_manager::Events, ops: &mut event_manager::EventOps) {
+        let event_set = events.event_set();
+        let source = events.data();
+        let index = (source & Self::INDEX_MASK) as usize;
+
+        match source & !Self::INDEX_MASK {
+            Self::PROCESS_LISTENER if index < self.ports.len() => {
+                self.process_listener_event(index, ops)
+            }
+            Self::PROCESS_CLIENT if self.ports.get(index).is_some_and(|p| p.client().is_some()) => {
+                self.process_client_event(index, event_set.contains(EventSet::HANG_UP))
+            }
+            _ if !self.is_activated() => {
+                warn!("console: The device is not activated yet. Spurious event received: {source}")
+            }
+            Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
+            Self::PROCESS_QUEUE if index < self.queue_events().len() => {
+                self.process_queue_event(index)
+            }
+            _ => {
+                warn!("console: Unknown event received: {source}");
+            }
+        }
+
+        self.remove_detached_clients(ops);
+    }
+}
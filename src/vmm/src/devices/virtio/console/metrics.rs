@@ -0,0 +1,91 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the metrics system for console devices.
+//!
+//! # Metrics format
+//! The metrics are flushed in JSON when requested by vmm::logger::metrics::METRICS.write().
+//!
+//! ## JSON example with metrics:
+//! ```json
+//!  "console": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "event_fails": "SharedIncMetric",
+//!     "rx_bytes_count": "SharedIncMetric",
+//!     ...
+//!  }
+//! }
+//! ```
+//! Each `console` field in the example above is a serializable `ConsoleDeviceMetrics` structure
+//! collecting metrics such as `activate_fails`, `event_fails` etc. for the console device.
+//! Since console doesn't support multiple devices, there is no per device metrics and
+//! `console` represents the aggregate console metrics.
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::logger::SharedIncMetric;
+
+/// Stores aggregated console metrics
+pub(super) static METRICS: ConsoleDeviceMetrics = ConsoleDeviceMetrics::new();
+
+/// Called by METRICS.flush(), this function facilitates serialization of console device metrics.
+pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_map(Some(1))?;
+    seq.serialize_entry("console", &METRICS)?;
+    seq.end()
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ConsoleDeviceMetrics {
+    /// Number of device activation failures
+    pub activate_fails: SharedIncMetric,
+    /// Number of queue event handling failures
+    pub event_fails: SharedIncMetric,
+    /// Number of control messages received from the guest
+    pub control_count: SharedIncMetric,
+    /// Number of bytes sent to the guest
+    pub rx_bytes_count: SharedIncMetric,
+    /// Number of bytes received from the guest
+    pub tx_bytes_count: SharedIncMetric,
+    /// Number of times the output of the guest could not be written to a file or pipe
+    pub output_dropped: SharedIncMetric,
+    /// Number of clients attached to a port
+    pub client_attach_count: SharedIncMetric,
+    /// Number of connections rejected because a client was already attached to the port
+    pub client_reject_count: SharedIncMetric,
+}
+impl ConsoleDeviceMetrics {
+    /// Const default construction.
+    const fn new() -> Self {
+        Self {
+            activate_fails: SharedIncMetric::new(),
+            event_fails: SharedIncMetric::new(),
+            control_count: SharedIncMetric::new(),
+            rx_bytes_count: SharedIncMetric::new(),
+            tx_bytes_count: SharedIncMetric::new(),
+            output_dropped: SharedIncMetric::new(),
+            client_attach_count: SharedIncMetric::new(),
+            client_reject_count: SharedIncMetric::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::logger::IncMetric;
+
+    #[test]
+    fn test_console_dev_metrics() {
+        let console_metrics: ConsoleDeviceMetrics = ConsoleDeviceMetrics::new();
+        let console_metrics_local: String = serde_json::to_string(&console_metrics).unwrap();
+        // the 1st serialize flushes the metrics and resets values to 0 so that
+        // we can compare the values with local metrics.
+        serde_json::to_string(&METRICS).unwrap();
+        let console_metrics_global: String = serde_json::to_string(&METRICS).unwrap();
+        assert_eq!(console_metrics_local, console_metrics_global);
+        console_metrics.control_count.inc();
+        assert_eq!(console_metrics.control_count.count(), 1);
+    }
+}
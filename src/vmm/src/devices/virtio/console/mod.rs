@@ -0,0 +1,40 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements a virtio-console device with the `VIRTIO_CONSOLE_F_MULTIPORT` feature.
+//!
+//! Each port of the device is backed by a Unix socket, on which a single client at a time can
+//! attach, or by a file or named pipe receiving the output of the guest.
+
+pub mod device;
+mod event_handler;
+pub mod metrics;
+pub mod persist;
+pub mod port;
+
+pub use self::device::{Console, ConsoleError};
+
+/// Maximum number of ports of the console device.
+pub const CONSOLE_MAX_PORTS: usize = 16;
+
+/// Index of the receive queue of the control port.
+pub(crate) const CONTROL_RXQ: usize = 2;
+/// Index of the transmit queue of the control port.
+pub(crate) const CONTROL_TXQ: usize = 3;
+
+/// Number of queues used by a console device with `nr_ports` ports.
+pub(crate) fn num_queues(nr_ports: usize) -> usize {
+    2 * (nr_ports + 1)
+}
+
+/// Index of the receive queue of `port`. The transmit queue follows it.
+pub(crate) fn rx_queue(port: usize) -> usize {
+    // The queues of the control port are placed between the ones of the ports 0 and 1.
+    if port == 0 { 0 } else { 2 * (port + 1) }
+}
+
+/// Port of the data queue at index `queue`, and whether it is a transmit queue.
+pub(crate) fn queue_port(queue: usize) -> (usize, bool) {
+    let port = if queue < 2 { 0 } else { queue / 2 - 1 };
+    (port, !queue.is_multiple_of(2))
+}
@@ -0,0 +1,177 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the structures needed for saving/restoring console devices.
+
+use serde::{Deserialize, Serialize};
+
+use super::device::VIRTIO_CONSOLE_PORT_OPEN;
+use super::port::ConsolePort;
+use super::{Console, ConsoleError, num_queues};
+use crate::devices::virtio::device::VirtioDeviceType;
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
+use crate::snapshot::Persist;
+use crate::vmm_config::console::ConsolePortConfig;
+use crate::vstate::memory::GuestMemoryMmap;
+
+/// State of a port of the console device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolePortState {
+    name: String,
+    console: bool,
+    uds_path: Option<String>,
+    output_path: Option<String>,
+    ready: bool,
+    guest_connected: bool,
+    host_connected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsoleState {
+    pub virtio_state: VirtioDeviceState,
+    ports: Vec<ConsolePortState>,
+    pending_control: Vec<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct ConsoleConstructorArgs {
+    pub mem: GuestMemoryMmap,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ConsolePersistError {
+    /// Create console: {0}
+    CreateConsole(#[from] ConsoleError),
+    /// Virtio state: {0}
+    VirtioState(#[from] VirtioStateError),
+}
+
+impl Persist<'_> for Console {
+    type State = ConsoleState;
+    type ConstructorArgs = ConsoleConstructorArgs;
+    type Error = ConsolePersistError;
+
+    fn save(&self) -> Self::State {
+        ConsoleState {
+            virtio_state: VirtioDeviceState::from_device(self),
+            ports: self
+                .ports
+                .iter()
+                .map(|port| ConsolePortState {
+                    name: port.config.name.clone(),
+                    console: port.config.console,
+                    uds_path: port.config.uds_path.clone(),
+                    output_path: port.config.output_path.clone(),
+                    ready: port.ready,
+                    guest_connected: port.guest_connected,
+                    host_connected: port.host_connected(),
+                })
+                .collect(),
+            pending_control: self.pending_control.iter().cloned().collect(),
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        let queues = state.virtio_state.build_queues_checked(
+            &constructor_args.mem,
+            VirtioDeviceType::Console,
+            num_queues(state.ports.len()),
+            FIRECRACKER_MAX_QUEUE_SIZE,
+        )?;
+
+        // The host side of the ports is set up again, like the socket of the vsock device.
+        let ports = state
+            .ports
+            .iter()
+            .map(|port_state| {
+                let mut port = ConsolePort::new(ConsolePortConfig {
+                    name: port_state.name.clone(),
+                    console: port_state.console,
+                    uds_path: port_state.uds_path.clone(),
+                    output_path: port_state.output_path.clone(),
+                })?;
+                port.ready = port_state.ready;
+                port.guest_connected = port_state.guest_connected;
+                Ok(port)
+            })
+            .collect::<Result<Vec<_>, ConsoleError>>()?;
+
+        let mut console = Console::new_with_queues(queues, ports)?;
+        console.set_avail_features(state.virtio_state.avail_features);
+        console.set_acked_features(state.virtio_state.acked_features);
+        console.pending_control = state.pending_control.iter().cloned().collect();
+
+        // The clients attached at snapshot time are gone.
+        for (i, port_state) in state.ports.iter().enumerate() {
+            if port_state.ready && port_state.host_connected && !console.ports[i].host_connected() {
+                console.send_control(i, VIRTIO_CONSOLE_PORT_OPEN, 0, &[]);
+            }
+        }
+
+        Ok(console)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::devices::virtio::console::device::CONSOLE_DEV_ID;
+    use crate::devices::virtio::device::VirtioDevice;
+    use crate::devices::virtio::test_utils::test::create_virtio_mem;
+
+    #[test]
+    fn test_persistence() {
+        let output = TempFile::new().unwrap();
+        let mut uds_path = TempFile::new().unwrap();
+        uds_path.remove().unwrap();
+        let configs = vec![
+            ConsolePortConfig {
+                name: "console".to_string(),
+                console: true,
+                uds_path: None,
+                output_path: Some(output.as_path().to_str().unwrap().to_string()),
+            },
+            ConsolePortConfig {
+                name: "agent".to_string(),
+                console: false,
+                uds_path: Some(uds_path.as_path().to_str().unwrap().to_string()),
+                output_path: None,
+            },
+        ];
+        let mut console = Console::new(configs.clone()).unwrap();
+        console.ports[1].ready = true;
+        let _client = UnixStream::connect(uds_path.as_path()).unwrap();
+        assert!(console.ports[1].accept().unwrap());
+
+        let console_state = console.save();
+        let serialized_data = bitcode::serialize(&console_state).unwrap();
+        drop(console);
+        std::fs::remove_file(uds_path.as_path()).unwrap();
+
+        let guest_mem = create_virtio_mem();
+        let restored_state = bitcode::deserialize(&serialized_data).unwrap();
+        let restored =
+            Console::restore(ConsoleConstructorArgs { mem: guest_mem }, &restored_state).unwrap();
+
+        assert_eq!(restored.device_type(), VirtioDeviceType::Console);
+        assert_eq!(restored.id(), CONSOLE_DEV_ID);
+        assert!(!restored.is_activated());
+        assert_eq!(restored.port_configs(), configs);
+        assert!(restored.ports[1].ready);
+        assert!(!restored.ports[1].host_connected());
+        // The guest driver is told that the client is gone.
+        let mut msg = 1u32.to_le_bytes().to_vec();
+        msg.extend_from_slice(&VIRTIO_CONSOLE_PORT_OPEN.to_le_bytes());
+        msg.extend_from_slice(&0u16.to_le_bytes());
+        assert_eq!(restored.pending_control, [msg]);
+        std::fs::remove_file(uds_path.as_path()).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the host side of the ports of the console device.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+use super::ConsoleError;
+use super::metrics::METRICS;
+use crate::logger::{IncMetric, warn};
+use crate::utils::open_file_nonblock;
+use crate::vmm_config::console::ConsolePortConfig;
+
+/// Host side of a port.
+#[derive(Debug)]
+pub enum PortBackend {
+    /// Unix socket on which a single client at a time can attach to the port.
+    Socket {
+        /// Socket on which the clients connect.
+        listener: UnixListener,
+        /// Connection of the attached client, if any.
+        client: Option<UnixStream>,
+    },
+    /// File or named pipe to which the output of the guest is written.
+    File(File),
+}
+
+/// A port of the console device.
+#[derive(Debug)]
+pub struct ConsolePort {
+    /// Configuration of the port.
+    pub(crate) config: ConsolePortConfig,
+    /// Host side of the port.
+    pub(crate) backend: PortBackend,
+    /// Whether the guest driver is ready to use the port.
+    pub(crate) ready: bool,
+    /// Whether the port is opened in the guest.
+    pub(crate) guest_connected: bool,
+    /// Output of the guest that the attached client was too slow to receive.
+    pending_output: Vec<u8>,
+}
+
+impl ConsolePort {
+    /// Creates a port and its host side, as described by `config`.
+    pub fn new(config: ConsolePortConfig) -> Result<Self, ConsoleError> {
+        let backend = match (&config.uds_path, &config.output_path) {
+            (Some(uds_path), None) => {
+                let listener = UnixListener::bind(uds_path)
+                    .map_err(|err| ConsoleError::Bind(uds_path.clone(), err))?;
+                listener
+                    .set_nonblocking(true)
+                    .map_err(|err| ConsoleError::Bind(uds_path.clone(), err))?;
+                PortBackend::Socket {
+                    listener,
+                    client: None,
+                }
+            }
+            (None, Some(output_path)) => {
+                let file = open_file_nonblock(Path::new(output_path))
+                    .map_err(|err| ConsoleError::OpenOutput(output_path.clone(), err))?;
+                PortBackend::File(file)
+            }
+            _ => return Err(ConsoleError::PortBackend(config.name)),
+        };
+
+        Ok(Self {
+            config,
+            backend,
+            ready: false,
+            guest_connected: false,
+            pending_output: Vec::new(),
+        })
+    }
+
+    /// Whether the host side of the port is connected.
+    pub fn host_connected(&self) -> bool {
+        match &self.backend {
+            PortBackend::Socket { client, .. } => client.is_some(),
+            PortBackend::File(_) => true,
+        }
+    }
+
+    /// Socket on which the clients connect, if the port is backed by a Unix socket.
+    pub fn listener(&self) -> Option<&UnixListener> {
+        match &self.backend {
+            PortBackend::Socket { listener, .. } => Some(listener),
+            PortBackend::File(_) => None,
+        }
+    }
+
+    /// Connection of the attached client, if any.
+    pub fn client(&self) -> Option<&UnixStream> {
+        match &self.backend {
+            PortBackend::Socket { client, .. } => client.as_ref(),
+            PortBackend::File(_) => None,
+        }
+    }
+
+    /// Accepts a connection on the socket of the port.
+    ///
+    /// Returns whether the connection was attached to the port: it is closed right away if
+    /// another client is already attached.
+    pub fn accept(&mut self) -> io::Result<bool> {
+        let PortBackend::Socket { listener, client } = &mut self.backend else {
+            return Ok(false);
+        };
+        let (stream, _) = listener.accept()?;
+        if client.is_some() {
+            METRICS.client_reject_count.inc();
+            warn!(
+                "console: Rejected a connection on port {}: a client is already attached.",
+                self.config.name
+            );
+            return Ok(false);
+        }
+        stream.set_nonblocking(true)?;
+        *client = Some(stream);
+        METRICS.client_attach_count.inc();
+        Ok(true)
+    }
+
+    /// Detaches the attached client, if any, and returns its connection.
+    pub fn detach(&mut self) -> Option<UnixStream> {
+        self.pending_output.clear();
+        match &mut self.backend {
+            PortBackend::Socket { client, .. } => client.take(),
+            PortBackend::File(_) => None,
+        }
+    }
+
+    /// Whether some output of the guest is still to be sent to the attached client.
+    pub fn has_pending_output(&self) -> bool {
+        !self.pending_output.is_empty()
+    }
+
+    /// Sends the output of the guest to the host side of the port.
+    ///
+    /// The output that the attached client is too slow to receive is kept, and sent by
+    /// `flush_output()`. An error means that the client must be detached.
+    pub fn write_output(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut self.backend {
+            PortBackend::Socket {
+                client: Some(client),
+                ..
+            } => {
+                // The output kept earlier is sent first.
+                let count = if self.pending_output.is_empty() {
+                    match client.write(buf) {
+                        Ok(count) => count,
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => 0,
+                        Err(err) => return Err(err),
+                    }
+                } else {
+                    0
+                };
+                self.pending_output.extend_from_slice(&buf[count..]);
+            }
+            // The output is discarded while no client is attached.
+            PortBackend::Socket { client: None, .. } => (),
+            PortBackend::File(file) => {
+                // Like for the serial console, the output is dropped when the file or pipe is
+                // not ready, so that it never blocks the VMM.
+                if file.write_all(buf).is_err() {
+                    METRICS.output_dropped.inc();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the output kept by `write_output()` to the attached client.
+    ///
+    /// An error means that the client must be detached.
+    pub fn flush_output(&mut self) -> io::Result<()> {
+        let PortBackend::Socket {
+            client: Some(client),
+            ..
+        } = &mut self.backend
+        else {
+            return Ok(());
+        };
+        while !self.pending_output.is_empty() {
+            match client.write(&self.pending_output) {
+                Ok(count) => {
+                    self.pending_output.drain(..count);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the input of the attached client.
+    ///
+    /// Returns 0 once the client closed its connection.
+    pub fn read_input(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.backend {
+            PortBackend::Socket {
+                client: Some(client),
+                ..
+            } => client.read(buf),
+            _ => Err(io::Error::from(io::ErrorKind::WouldBlock)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn socket_port(path: &Path) -> ConsolePort {
+        ConsolePort::new(ConsolePortConfig {
+            name: "port".to_string(),
+            console: false,
+            uds_path: Some(path.to_str().unwrap().to_string()),
+            output_path: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_port_backend() {
+        let mut config = ConsolePortConfig {
+            name: "port".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ConsolePort::new(config.clone()),
+            Err(ConsoleError::PortBackend(_))
+        ));
+
+        let output = TempFile::new().unwrap();
+        config.output_path = Some(output.as_path().to_str().unwrap().to_string());
+        let mut port = ConsolePort::new(config.clone()).unwrap();
+        assert!(port.host_connected());
+        assert!(port.listener().is_none());
+        port.write_output(b"hello").unwrap();
+        assert_eq!(std::fs::read(output.as_path()).unwrap(), b"hello");
+
+        config.uds_path = Some("console.sock".to_string());
+        assert!(matches!(
+            ConsolePort::new(config),
+            Err(ConsoleError::PortBackend(_))
+        ));
+    }
+
+    #[test]
+    fn test_port_socket() {
+        let mut path = TempFile::new().unwrap();
+        path.remove().unwrap();
+        let mut port = socket_port(path.as_path());
+        assert!(!port.host_connected());
+        // The output is discarded while no client is attached.
+        port.write_output(b"lost").unwrap();
+        assert!(!port.has_pending_output());
+
+        let mut client = UnixStream::connect(path.as_path()).unwrap();
+        assert!(port.accept().unwrap());
+        assert!(port.host_connected());
+
+        // A second client is rejected.
+        let mut other = UnixStream::connect(path.as_path()).unwrap();
+        assert!(!port.accept().unwrap());
+        let mut buf = [0u8; 4];
+        assert_eq!(other.read(&mut buf).unwrap(), 0);
+
+        port.write_output(b"out").unwrap();
+        client.read_exact(&mut buf[..3]).unwrap();
+        assert_eq!(&buf[..3], b"out");
+
+        client.write_all(b"in").unwrap();
+        assert_eq!(port.read_input(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"in");
+
+        drop(client);
+        assert_eq!(port.read_input(&mut buf).unwrap(), 0);
+        assert!(port.detach().is_some());
+        assert!(!port.host_connected());
+        std::fs::remove_file(path.as_path()).unwrap();
+    }
+}
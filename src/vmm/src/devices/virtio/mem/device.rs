@@ -5,6 +5,7 @@ use std::io;
 use std::ops::{Deref, Range};
 use std::sync::Arc;
 use std::sync::atomic::AtomicU32;
+use std::time::{Duration, Instant};
 
 use bitvec::vec::BitVec;
 use log::info;
@@ -33,7 +34,8 @@ use crate::logger::{IncMetric, debug, error};
 use crate::utils::{bytes_to_mib, mib_to_bytes, u64_to_usize, usize_to_u64};
 use crate::vstate::interrupts::InterruptError;
 use crate::vstate::memory::{
-    ByteValued, GuestMemoryExtension, GuestMemoryMmap, GuestRegionMmap, GuestRegionType,
+    ByteValued, DiscardedRanges, GuestMemoryExtension, GuestMemoryMmap, GuestRegionMmap,
+    GuestRegionType,
 };
 use crate::vstate::vm::VmError;
 use crate::{Vm, impl_device_type};
@@ -41,6 +43,23 @@ use crate::{Vm, impl_device_type};
 // SAFETY: virtio_mem_config only contains plain data types
 unsafe impl ByteValued for virtio_mem_config {}
 
+/// Builds the guest memory ranges of the unplugged blocks of a device starting at `addr`.
+pub(crate) fn unplugged_ranges(
+    addr: u64,
+    block_size: u64,
+    plugged_blocks: impl Iterator<Item = bool>,
+) -> DiscardedRanges {
+    let mut ranges = DiscardedRanges::default();
+    let mut block_addr = GuestAddress(addr);
+    for plugged in plugged_blocks {
+        if !plugged {
+            ranges.insert(block_addr, u64_to_usize(block_size));
+        }
+        block_addr = block_addr.unchecked_add(block_size);
+    }
+    ranges
+}
+
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum VirtioMemError {
     /// Error while handling an Event file descriptor: {0}
@@ -79,6 +98,8 @@ pub enum VirtioMemError {
     UnplugRequestBlockStateInvalid(BlockRangeState),
     /// There was an error updating the KVM slot.
     UpdateKvmSlot(VmError),
+    /// Could not return the unplugged memory to the host: {0}
+    DiscardRange(GuestMemoryError),
 }
 
 #[derive(Debug)]
@@ -99,6 +120,25 @@ pub struct VirtioMem {
     // Bitmap to track which blocks are plugged
     pub(crate) plugged_blocks: BitVec,
     vm: Arc<Vm>,
+
+    // Deadline for the guest to reach the requested size, if set through the API.
+    resize_deadline: Option<Instant>,
+    // Whether unplugged memory could not be returned to the host since the last resize.
+    resize_failed: bool,
+}
+
+/// Progress of the guest towards the requested size of the hotpluggable memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VirtioMemResizeState {
+    /// The plugged size matches the requested size.
+    Completed,
+    /// The guest is plugging or unplugging memory blocks.
+    InProgress,
+    /// The guest did not reach the requested size before the timeout expired.
+    TimedOut,
+    /// Unplugged memory could not be returned to the host.
+    Failed,
 }
 
 /// Memory hotplug device status information.
@@ -115,6 +155,8 @@ pub struct VirtioMemStatus {
     pub plugged_size_mib: usize,
     /// Requested memory size in MiB.
     pub requested_size_mib: usize,
+    /// Progress towards the requested size.
+    pub resize_state: VirtioMemResizeState,
 }
 
 impl VirtioMem {
@@ -166,6 +208,8 @@ impl VirtioMem {
             vm,
             slot_size,
             plugged_blocks,
+            resize_deadline: None,
+            resize_failed: false,
         })
     }
 
@@ -205,9 +249,31 @@ impl VirtioMem {
             slot_size_mib: self.slot_size_mib(),
             plugged_size_mib: self.plugged_size_mib(),
             requested_size_mib: self.requested_size_mib(),
+            resize_state: self.resize_state(Instant::now()),
+        }
+    }
+
+    fn resize_state(&self, now: Instant) -> VirtioMemResizeState {
+        if self.resize_failed {
+            VirtioMemResizeState::Failed
+        } else if self.config.plugged_size == self.config.requested_size {
+            VirtioMemResizeState::Completed
+        } else if self.resize_deadline.is_some_and(|deadline| now >= deadline) {
+            VirtioMemResizeState::TimedOut
+        } else {
+            VirtioMemResizeState::InProgress
         }
     }
 
+    /// Gets the guest memory ranges of the unplugged blocks.
+    pub fn unplugged_ranges(&self) -> DiscardedRanges {
+        unplugged_ranges(
+            self.config.addr,
+            self.config.block_size,
+            self.plugged_blocks.iter().by_vals(),
+        )
+    }
+
     fn signal_used_queue(&self) -> Result<(), VirtioMemError> {
         self.interrupt_trigger()
             .trigger(VirtioInterruptType::Queue(MEM_QUEUE.try_into().unwrap()))
@@ -531,6 +597,19 @@ impl VirtioMem {
     /// Note: the range passed to this function must be within the device memory to avoid
     /// out-of-bound panics.
     fn update_range(&mut self, range: &RequestedRange, plug: bool) -> Result<(), VirtioMemError> {
+        // If unplugging, return the memory to the host first. On failure the blocks stay
+        // plugged and the request is rejected, so that the guest keeps using memory that the
+        // host could not reclaim.
+        if !plug
+            && let Err(err) = self
+                .guest_memory()
+                .discard_range(range.addr, self.nb_blocks_to_len(range.nb_blocks))
+        {
+            METRICS.unplug_discard_fails.inc();
+            self.resize_failed = true;
+            return Err(VirtioMemError::DiscardRange(err));
+        }
+
         // Update internal state
         let block_range = self.unchecked_block_range(range);
         let plugged_blocks_slice = &mut self.plugged_blocks[block_range];
@@ -540,18 +619,6 @@ impl VirtioMem {
         self.config.plugged_size -= usize_to_u64(self.nb_blocks_to_len(plugged_before));
         self.config.plugged_size += usize_to_u64(self.nb_blocks_to_len(plugged_after));
 
-        // If unplugging, discard the range
-        if !plug {
-            self.guest_memory()
-                .discard_range(range.addr, self.nb_blocks_to_len(range.nb_blocks))
-                .inspect_err(|err| {
-                    // Failure to discard is not fatal and is not reported to the driver. It only
-                    // gets logged.
-                    METRICS.unplug_discard_fails.inc();
-                    error!("virtio-mem: Failed to discard memory range: {}", err);
-                });
-        }
-
         self.update_kvm_slots(range)
     }
 
@@ -560,17 +627,8 @@ impl VirtioMem {
         &mut self,
         requested_size_mib: usize,
     ) -> Result<(), VirtioMemError> {
+        self.check_requested_size(requested_size_mib)?;
         let requested_size = usize_to_u64(mib_to_bytes(requested_size_mib));
-        if !self.is_activated() {
-            return Err(VirtioMemError::DeviceNotActive);
-        }
-
-        if !requested_size.is_multiple_of(self.config.block_size) {
-            return Err(VirtioMemError::InvalidSize(requested_size));
-        }
-        if requested_size > self.config.region_size {
-            return Err(VirtioMemError::InvalidSize(requested_size));
-        }
 
         // Increase the usable_region_size if it's not enough for the guest to plug new
         // memory blocks.
@@ -586,6 +644,8 @@ impl VirtioMem {
         }
 
         self.config.requested_size = requested_size;
+        self.resize_deadline = None;
+        self.resize_failed = false;
         debug!(
             "virtio-mem: Updated requested size to {} bytes",
             requested_size
@@ -594,6 +654,28 @@ impl VirtioMem {
             .trigger(VirtioInterruptType::Config)
             .map_err(VirtioMemError::InterruptError)
     }
+
+    /// Checks that the requested size can be updated to `requested_size_mib`.
+    pub fn check_requested_size(&self, requested_size_mib: usize) -> Result<(), VirtioMemError> {
+        let requested_size = usize_to_u64(mib_to_bytes(requested_size_mib));
+        if !self.is_activated() {
+            return Err(VirtioMemError::DeviceNotActive);
+        }
+
+        if !requested_size.is_multiple_of(self.config.block_size) {
+            return Err(VirtioMemError::InvalidSize(requested_size));
+        }
+        if requested_size > self.config.region_size {
+            return Err(VirtioMemError::InvalidSize(requested_size));
+        }
+        Ok(())
+    }
+
+    /// Sets the time the guest has to reach the requested size, after which the resize is
+    /// reported as timed out. A timeout too large to be represented never expires.
+    pub fn set_resize_timeout(&mut self, timeout: Duration) {
+        self.resize_deadline = Instant::now().checked_add(timeout);
+    }
 }
 
 impl VirtioDevice for VirtioMem {
@@ -876,10 +958,53 @@ mod tests {
                 slot_size_mib: 128,
                 plugged_size_mib: 0,
                 requested_size_mib: 0,
+                resize_state: VirtioMemResizeState::Completed,
             }
         );
     }
 
+    #[test]
+    fn test_resize_state() {
+        let mem_dev = default_virtio_mem();
+        let guest_mem = mem_dev.vm.guest_memory().clone();
+        let mut th = test_helper(mem_dev, &guest_mem);
+        let now = Instant::now();
+
+        th.device().update_requested_size(512).unwrap();
+        assert_eq!(
+            th.device().resize_state(now),
+            VirtioMemResizeState::InProgress
+        );
+
+        // The resize times out if the guest does not plug the memory in time.
+        th.device().set_resize_timeout(Duration::from_secs(10));
+        assert_eq!(
+            th.device().resize_state(Instant::now()),
+            VirtioMemResizeState::InProgress
+        );
+        assert_eq!(
+            th.device().resize_state(now + Duration::from_secs(11)),
+            VirtioMemResizeState::TimedOut
+        );
+
+        // A timeout that cannot be represented never expires.
+        th.device()
+            .set_resize_timeout(Duration::from_secs(u64::MAX));
+        assert_eq!(
+            th.device().resize_state(now + Duration::from_secs(11)),
+            VirtioMemResizeState::InProgress
+        );
+
+        // Failing to reclaim memory is reported until the next resize.
+        th.device().resize_failed = true;
+        assert_eq!(th.device().resize_state(now), VirtioMemResizeState::Failed);
+        th.device().update_requested_size(0).unwrap();
+        assert_eq!(
+            th.device().resize_state(now + Duration::from_secs(11)),
+            VirtioMemResizeState::Completed
+        );
+    }
+
     #[allow(clippy::cast_possible_truncation)]
     const REQ_SIZE: u32 = std::mem::size_of::<virtio_mem::virtio_mem_req>() as u32;
     #[allow(clippy::cast_possible_truncation)]
@@ -1131,6 +1256,10 @@ mod tests {
         );
         assert!(resp.is_ack());
         assert_eq!(th.device().plugged_size_mib(), 2);
+        assert_eq!(
+            th.device().unplugged_ranges().iter().collect::<Vec<_>>(),
+            vec![(addr.unchecked_add(2 << 20), 1022 << 20)]
+        );
 
         // Then unplug
         let resp = emulate_request(
@@ -1140,6 +1269,10 @@ mod tests {
         );
         assert!(resp.is_ack());
         assert_eq!(th.device().plugged_size_mib(), 0);
+        assert_eq!(
+            th.device().unplugged_ranges().iter().collect::<Vec<_>>(),
+            vec![(addr, 1024 << 20)]
+        );
 
         assert_eq!(METRICS.unplug_count.count(), unplug_count + 1);
         assert_eq!(METRICS.unplug_bytes.count(), unplug_bytes + (2 << 20));
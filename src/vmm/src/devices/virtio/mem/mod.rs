@@ -9,7 +9,7 @@ mod request;
 
 use vm_memory::GuestAddress;
 
-pub use self::device::{VirtioMem, VirtioMemError, VirtioMemStatus};
+pub use self::device::{VirtioMem, VirtioMemError, VirtioMemResizeState, VirtioMemStatus};
 use crate::arch::FIRST_ADDR_PAST_64BITS_MMIO;
 
 pub(crate) const MEM_NUM_QUEUES: usize = 1;
@@ -13,12 +13,13 @@ use crate::Vm;
 use crate::devices::virtio::device::VirtioDeviceType;
 use crate::devices::virtio::generated::virtio_ids::VIRTIO_ID_MEM;
 use crate::devices::virtio::generated::virtio_mem::virtio_mem_config;
+use crate::devices::virtio::mem::device::unplugged_ranges;
 use crate::devices::virtio::mem::{MEM_NUM_QUEUES, VirtioMem, VirtioMemError};
 use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
 use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
 use crate::snapshot::Persist;
 use crate::utils::usize_to_u64;
-use crate::vstate::memory::{GuestMemoryMmap, GuestRegionMmap};
+use crate::vstate::memory::{DiscardedRanges, GuestMemoryMmap, GuestRegionMmap};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VirtioMemState {
@@ -32,6 +33,17 @@ pub struct VirtioMemState {
     plugged_blocks: Vec<bool>,
 }
 
+impl VirtioMemState {
+    /// Guest memory ranges of the blocks unplugged at snapshot time.
+    pub fn unplugged_ranges(&self) -> DiscardedRanges {
+        unplugged_ranges(
+            self.addr,
+            self.block_size,
+            self.plugged_blocks.iter().copied(),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct VirtioMemConstructorArgs {
     vm: Arc<Vm>,
@@ -128,6 +140,7 @@ mod tests {
         );
         assert_eq!(state.requested_size, dev.config.requested_size);
         assert_eq!(state.slot_size, dev.slot_size);
+        assert_eq!(state.unplugged_ranges(), dev.unplugged_ranges());
     }
 
     #[test]
@@ -4,11 +4,13 @@
 use std::fs::{File, OpenOptions};
 use std::ops::{Deref, DerefMut};
 use std::os::fd::AsRawFd;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Barrier, Mutex};
+use std::time::Duration;
 
 use kvm_bindings::{KVM_MEM_READONLY, kvm_userspace_memory_region};
 use kvm_ioctls::VmFd;
 use serde::{Deserialize, Serialize};
+use utils::time::TimerFd;
 use vm_allocator::AllocPolicy;
 use vm_memory::mmap::{MmapRegionBuilder, MmapRegionError};
 use vm_memory::{GuestAddress, GuestMemoryError};
@@ -18,12 +20,13 @@ use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
 use crate::devices::virtio::pmem::PMEM_QUEUE_SIZE;
-use crate::devices::virtio::pmem::metrics::{PmemMetrics, PmemMetricsPerDevice};
+use crate::devices::virtio::pmem::metrics::{PmemMapping, PmemMetrics, PmemMetricsPerDevice};
 use crate::devices::virtio::queue::{DescriptorChain, InvalidAvailIdx, Queue, QueueError};
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
-use crate::logger::{IncMetric, error, info};
+use crate::logger::{IncMetric, StoreMetric, error, info, warn};
 use crate::utils::{align_up, u64_to_usize};
 use crate::vmm_config::pmem::PmemConfig;
+use crate::vstate::bus::{BusDeviceSync, BusError};
 use crate::vstate::memory::{ByteValued, Bytes, GuestMemoryMmap, GuestMmapRegion};
 use crate::vstate::vm::VmError;
 use crate::{Vm, impl_device_type};
@@ -38,6 +41,12 @@ pub enum PmemError {
     BackingFile(std::io::Error),
     /// Error backing file size is 0
     BackingFileZeroSize,
+    /// Pmem devices holding a shared lock must be read-only
+    SharedLockNotReadOnly,
+    /// Cannot take a shared lock on the backing file: {0}
+    SharedLock(std::io::Error),
+    /// Cannot register the device on the MMIO bus: {0}
+    Bus(BusError),
     /// Error with EventFd: {0}
     EventFd(std::io::Error),
     /// Unexpected read-only descriptor
@@ -90,10 +99,37 @@ pub struct Pmem {
     pub file_len: u64,
     pub mmap_ptr: u64,
     pub metrics: Arc<PmemMetrics>,
+    // Triggers the update of the memory usage metrics.
+    pub usage_timer: TimerFd,
+    // Discards guest writes to a read-only device. Kept alive here as the MMIO bus only holds a
+    // weak reference to it.
+    pub write_trap: Option<Arc<ReadOnlyWriteTrap>>,
 
     pub config: PmemConfig,
 }
 
+/// Discards the guest writes to a read-only pmem device, which KVM reports as MMIO writes.
+#[derive(Debug)]
+pub struct ReadOnlyWriteTrap {
+    id: String,
+    metrics: Arc<PmemMetrics>,
+}
+
+impl BusDeviceSync for ReadOnlyWriteTrap {
+    fn write(&self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        // Only the first write is logged, to not flood the log with a misbehaving guest.
+        if self.metrics.read_only_writes.count() == 0 {
+            warn!(
+                "pmem: Discarding guest write to read-only device {} @ {offset:#x}:{:#x}",
+                self.id,
+                data.len()
+            );
+        }
+        self.metrics.read_only_writes.inc();
+        None
+    }
+}
+
 impl Drop for Pmem {
     fn drop(&mut self) {
         let mmap_len = align_up(self.file_len, Self::ALIGNMENT);
@@ -109,6 +145,8 @@ impl Pmem {
     // Pmem devices need to have address and size to be
     // a multiple of 2MB
     pub const ALIGNMENT: u64 = 2 * 1024 * 1024;
+    // Interval between two updates of the memory usage metrics.
+    pub const MEMORY_USAGE_INTERVAL: Duration = Duration::from_secs(10);
 
     /// Create a new Pmem device with a backing file at `disk_image_path` path.
     pub fn new(config: PmemConfig) -> Result<Self, PmemError> {
@@ -118,10 +156,18 @@ impl Pmem {
     /// Create a new Pmem device with a backing file at `disk_image_path` path using a pre-created
     /// set of queues.
     pub fn new_with_queues(config: PmemConfig, queues: Vec<Queue>) -> Result<Self, PmemError> {
+        if config.shared_lock && !config.read_only {
+            return Err(PmemError::SharedLockNotReadOnly);
+        }
         let (file, file_len, mmap_ptr, mmap_len) =
-            Self::mmap_backing_file(&config.path_on_host, config.read_only)?;
-
-        Ok(Self {
+            Self::mmap_backing_file(&config.path_on_host, config.read_only, config.shared_lock)?;
+        let mut usage_timer = TimerFd::new();
+        usage_timer.arm(
+            Self::MEMORY_USAGE_INTERVAL,
+            Some(Self::MEMORY_USAGE_INTERVAL),
+        );
+
+        let pmem = Self {
             avail_features: 1u64 << VIRTIO_F_VERSION_1,
             acked_features: 0u64,
             activate_event: EventFd::new(libc::EFD_NONBLOCK).map_err(PmemError::EventFd)?,
@@ -136,16 +182,50 @@ impl Pmem {
             file_len,
             mmap_ptr,
             metrics: PmemMetricsPerDevice::alloc(config.id.clone()),
+            usage_timer,
+            write_trap: None,
             config,
-        })
+        };
+        pmem.update_memory_usage();
+        Ok(pmem)
     }
 
-    fn mmap_backing_file(path: &str, read_only: bool) -> Result<(File, u64, u64, u64), PmemError> {
+    /// Updates the memory usage metrics from the current state of the mapping.
+    pub fn update_memory_usage(&self) {
+        let mapping = PmemMapping {
+            addr: self.mmap_ptr,
+            file_len: self.file_len,
+            len: self.config_space.size,
+        };
+        let (resident, page_cache) = mapping.resident_bytes();
+        self.metrics.resident_bytes.store(resident);
+        self.metrics.page_cache_bytes.store(page_cache);
+    }
+
+    pub fn process_usage_timer_event(&mut self) {
+        _ = self.usage_timer.read();
+        self.update_memory_usage();
+    }
+
+    fn mmap_backing_file(
+        path: &str,
+        read_only: bool,
+        shared_lock: bool,
+    ) -> Result<(File, u64, u64, u64), PmemError> {
         let file = OpenOptions::new()
             .read(true)
             .write(!read_only)
             .open(path)
             .map_err(PmemError::BackingFile)?;
+        // The shared lock is held as long as the file is open. It guarantees that the file is not
+        // being updated by a tool holding an exclusive lock while the microVMs share its pages.
+        if shared_lock {
+            // SAFETY: Safe because the file descriptor is valid and we check the return value.
+            let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) };
+            if ret < 0 {
+                return Err(PmemError::SharedLock(std::io::Error::last_os_error()));
+            }
+        }
         let file_len = file.metadata().unwrap().len();
         if (file_len == 0) {
             return Err(PmemError::BackingFileZeroSize);
@@ -243,7 +323,25 @@ impl Pmem {
         };
 
         vm.set_user_memory_region(memory_region)
-            .map_err(PmemError::SetUserMemoryRegion)
+            .map_err(PmemError::SetUserMemoryRegion)?;
+
+        // Writes to a read-only slot exit to the VMM as MMIO writes, which are discarded.
+        if self.config.read_only {
+            let write_trap = Arc::new(ReadOnlyWriteTrap {
+                id: self.config.id.clone(),
+                metrics: self.metrics.clone(),
+            });
+            vm.common
+                .mmio_bus
+                .insert(
+                    write_trap.clone(),
+                    self.config_space.start,
+                    self.config_space.size,
+                )
+                .map_err(PmemError::Bus)?;
+            self.write_trap = Some(write_trap);
+        }
+        Ok(())
     }
 
     pub fn handle_queue(&mut self) -> Result<(), PmemError> {
@@ -415,8 +513,11 @@ mod tests {
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
+    use crate::arch::{Kvm, host_page_size};
+    use crate::devices::virtio::pmem::metrics::flush_metrics;
     use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
     use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
+    use crate::utils::usize_to_u64;
 
     #[test]
     fn test_from_config() {
@@ -425,6 +526,7 @@ mod tests {
             path_on_host: "not_a_path".into(),
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         assert!(matches!(
             Pmem::new(config).unwrap_err(),
@@ -438,6 +540,7 @@ mod tests {
             path_on_host: dummy_path.clone(),
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         assert!(matches!(
             Pmem::new(config).unwrap_err(),
@@ -445,13 +548,110 @@ mod tests {
         ));
 
         dummy_file.as_file().set_len(0x20_0000);
-        let config = PmemConfig {
+        let mut config = PmemConfig {
             id: "1".into(),
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: true,
         };
-        Pmem::new(config).unwrap();
+        assert!(matches!(
+            Pmem::new(config.clone()).unwrap_err(),
+            PmemError::SharedLockNotReadOnly,
+        ));
+        config.shared_lock = false;
+        Pmem::new(config.clone()).unwrap();
+
+        // Devices with `shared_lock` take a shared lock on the backing file, which conflicts with
+        // an exclusive lock taken by a tool updating the file.
+        config.read_only = true;
+        config.shared_lock = true;
+        let first = Pmem::new(config.clone()).unwrap();
+        // Many microVMs can share the file.
+        let second = Pmem::new(config.clone()).unwrap();
+        let other = File::open(&config.path_on_host).unwrap();
+        // SAFETY: The file descriptor is valid.
+        let ret = unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        assert_eq!(ret, -1);
+        drop(first);
+        drop(second);
+
+        // SAFETY: The file descriptor is valid.
+        let ret = unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        assert_eq!(ret, 0);
+        assert!(matches!(
+            Pmem::new(config).unwrap_err(),
+            PmemError::SharedLock(_),
+        ));
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let dummy_file = TempFile::new().unwrap();
+        dummy_file.as_file().set_len(0x20_0000);
+        let dummy_path = dummy_file.as_path().to_str().unwrap().to_string();
+        let config = PmemConfig {
+            id: "memory_usage".into(),
+            path_on_host: dummy_path,
+            root_device: false,
+            read_only: false,
+            shared_lock: false,
+        };
+        let mut pmem = Pmem::new(config).unwrap();
+        assert!(pmem.usage_timer.is_armed());
+        // The file is sparse, so none of it is in the page cache.
+        assert_eq!(pmem.metrics.resident_bytes.fetch(), 0);
+        assert_eq!(pmem.metrics.page_cache_bytes.fetch(), 0);
+
+        // SAFETY: The mapping is writable and covers the whole file.
+        unsafe { (pmem.mmap_ptr as *mut u8).write(1) };
+
+        // Serializing the metrics only reads the values stored by the device, without looking at
+        // the mapping.
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        flush_metrics(&mut serializer).unwrap();
+        let metrics: serde_json::Value = serde_json::from_slice(&serializer.into_inner()).unwrap();
+        assert_eq!(metrics["pmem_memory_usage"]["resident_bytes"], 0);
+        assert_eq!(metrics["pmem_memory_usage"]["page_cache_bytes"], 0);
+
+        // The device updates them when its timer expires.
+        pmem.process_usage_timer_event();
+        let page_cache_bytes = pmem.metrics.page_cache_bytes.fetch();
+        assert!(page_cache_bytes >= usize_to_u64(host_page_size()));
+        assert_eq!(pmem.metrics.resident_bytes.fetch(), page_cache_bytes);
+    }
+
+    #[test]
+    fn test_read_only_write_trap() {
+        let dummy_file = TempFile::new().unwrap();
+        dummy_file.as_file().set_len(0x20_0000);
+        let dummy_path = dummy_file.as_path().to_str().unwrap().to_string();
+        let config = PmemConfig {
+            id: "read_only_write_trap".into(),
+            path_on_host: dummy_path,
+            root_device: false,
+            read_only: true,
+            shared_lock: true,
+        };
+        let mut pmem = Pmem::new(config).unwrap();
+        let kvm = Kvm::new(vec![]).unwrap();
+        let vm = Vm::new(&kvm).unwrap();
+        pmem.alloc_region(&vm);
+        pmem.set_mem_region(&vm).unwrap();
+
+        // Guest writes are discarded and counted.
+        let start = pmem.config_space.start;
+        vm.common
+            .mmio_bus
+            .write(start + 0x10, &[1, 2, 3, 4])
+            .unwrap();
+        vm.common.mmio_bus.write(start, &[1]).unwrap();
+        assert_eq!(pmem.metrics.read_only_writes.count(), 2);
+        // Writes past the device are not handled by it.
+        vm.common
+            .mmio_bus
+            .write(start + pmem.config_space.size, &[1])
+            .unwrap_err();
     }
 
     #[test]
@@ -464,6 +664,7 @@ mod tests {
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         let mut pmem = Pmem::new(config).unwrap();
 
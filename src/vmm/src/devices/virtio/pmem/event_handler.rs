@@ -10,6 +10,7 @@ use crate::devices::virtio::device::VirtioDevice;
 impl Pmem {
     const PROCESS_ACTIVATE: u32 = 0;
     const PROCESS_PMEM_QUEUE: u32 = 1;
+    const PROCESS_USAGE_TIMER: u32 = 2;
 
     fn register_runtime_events(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
@@ -21,6 +22,16 @@ impl Pmem {
         }
     }
 
+    fn register_usage_timer_event(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            &self.usage_timer,
+            Self::PROCESS_USAGE_TIMER,
+            EventSet::IN,
+        )) {
+            error!("pmem: Failed to register usage timer event: {err}");
+        }
+    }
+
     fn register_activate_event(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
             &self.activate_event,
@@ -52,6 +63,7 @@ impl Pmem {
 
 impl MutEventSubscriber for Pmem {
     fn init(&mut self, ops: &mut EventOps) {
+        self.register_usage_timer_event(ops);
         if self.is_activated() {
             self.register_runtime_events(ops)
         } else {
@@ -68,6 +80,12 @@ impl MutEventSubscriber for Pmem {
             return;
         }
 
+        // The memory usage is accounted whether the device is activated or not.
+        if source == Self::PROCESS_USAGE_TIMER {
+            self.process_usage_timer_event();
+            return;
+        }
+
         if !self.is_activated() {
             warn!("pmem: The device is not activated yet. Spurious event received from {source}");
             return;
@@ -51,9 +51,11 @@
 //! `pmem_drive_id` represent metrics for the endpoint "/pmem/{drive_id}"
 //! pmem device respectively and `pmem` is the aggregate of all the per device metrics.
 //!
-//! # Limitations
-//! pmem device currently do not have `vmm::logger::metrics::StoreMetrics` so aggregate
-//! doesn't consider them.
+//! # Memory accounting
+//! `resident_bytes` and `page_cache_bytes` are `vmm::logger::metrics::StoreMetrics` computed with
+//! `mincore` on the mapping of the backing file by the device itself, on the VMM thread, every
+//! `Pmem::MEMORY_USAGE_INTERVAL`. Serializing the metrics only reads the stored values, so that
+//! any thread can flush them. The aggregate holds the sum over all the devices.
 //!
 //! # Design
 //! The main design goals of this system are:
@@ -70,9 +72,10 @@
 //!   metrics. So, use Map instead of Vec to help understand which drive the metrics actually
 //!   belongs to.
 //!
-//! The system implements 1 type of metrics:
+//! The system implements 2 types of metrics:
 //! * Shared Incremental Metrics (SharedIncMetrics) - dedicated for the metrics which need a counter
 //!   (i.e the number of times an API request failed). These metrics are reset upon flush.
+//! * Shared Store Metrics (SharedStoreMetrics) - dedicated for the memory usage of the device.
 //!
 //! We add PmemDeviceMetrics entries from pmem::metrics::METRICS into Pmem device instead of
 //! Pmem device having individual separate PmemDeviceMetrics entries because Pmem device is not
@@ -84,7 +87,11 @@ use std::sync::{Arc, RwLock};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::{IncMetric, LatencyAggregateMetrics, SharedIncMetric};
+use crate::arch::host_page_size;
+use crate::logger::{
+    IncMetric, LatencyAggregateMetrics, SharedIncMetric, SharedStoreMetric, StoreMetric,
+};
+use crate::utils::{u64_to_usize, usize_to_u64};
 
 /// map of pmem drive id and metrics
 /// this should be protected by a lock before accessing.
@@ -140,6 +147,43 @@ pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
     seq.end()
 }
 
+/// Host mapping of the backing file of a pmem device, used to account its memory usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PmemMapping {
+    /// Host virtual address of the mapping.
+    pub addr: u64,
+    /// Size of the part of the mapping backed by the file.
+    pub file_len: u64,
+    /// Total size of the mapping.
+    pub len: u64,
+}
+
+impl PmemMapping {
+    /// Returns the number of resident bytes in the whole mapping, and the number of bytes of the
+    /// part of the mapping backed by the file which are in the page cache.
+    pub fn resident_bytes(&self) -> (u64, u64) {
+        let page_size = usize_to_u64(host_page_size());
+        let mut pages = vec![0u8; u64_to_usize(self.len.div_ceil(page_size))];
+        // SAFETY: `pages` holds one byte per page of the mapping, as required by mincore, which
+        // does not access the mapping itself.
+        let ret = unsafe {
+            libc::mincore(
+                self.addr as *mut libc::c_void,
+                u64_to_usize(self.len),
+                pages.as_mut_ptr(),
+            )
+        };
+        if ret < 0 {
+            return (0, 0);
+        }
+        let file_pages = u64_to_usize(self.file_len.div_ceil(page_size)).min(pages.len());
+        let resident = |pages: &[u8]| {
+            usize_to_u64(pages.iter().filter(|&&page| page & 1 != 0).count()) * page_size
+        };
+        (resident(&pages), resident(&pages[..file_pages]))
+    }
+}
+
 /// Pmem Device associated metrics.
 #[derive(Debug, Default, Serialize)]
 pub struct PmemMetrics {
@@ -151,6 +195,12 @@ pub struct PmemMetrics {
     pub event_fails: SharedIncMetric,
     /// Number of events triggered on the queue of this pmem device.
     pub queue_event_count: SharedIncMetric,
+    /// Number of guest writes to a read-only pmem device that were discarded.
+    pub read_only_writes: SharedIncMetric,
+    /// Number of bytes of the device memory resident on the host.
+    pub resident_bytes: SharedStoreMetric,
+    /// Number of bytes of the backing file in the host page cache.
+    pub page_cache_bytes: SharedStoreMetric,
 }
 
 impl PmemMetrics {
@@ -172,6 +222,12 @@ impl PmemMetrics {
         self.event_fails.add(other.event_fails.fetch_diff());
         self.queue_event_count
             .add(other.queue_event_count.fetch_diff());
+        self.read_only_writes
+            .add(other.read_only_writes.fetch_diff());
+        self.resident_bytes
+            .store(self.resident_bytes.fetch() + other.resident_bytes.fetch());
+        self.page_cache_bytes
+            .store(self.page_cache_bytes.fetch() + other.page_cache_bytes.fetch());
     }
 }
 
@@ -222,6 +278,40 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_memory_usage() {
+        let page_size = host_page_size();
+        let len = 4 * page_size;
+        // SAFETY: We are calling the system call with valid arguments and checking the returned
+        // value.
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(addr, libc::MAP_FAILED);
+        // SAFETY: The first two pages are within the mapping.
+        unsafe { std::ptr::write_bytes(addr.cast::<u8>(), 1, 2 * page_size) };
+
+        let mapping = PmemMapping {
+            addr: addr as u64,
+            file_len: usize_to_u64(page_size),
+            len: usize_to_u64(len),
+        };
+        assert_eq!(
+            mapping.resident_bytes(),
+            (usize_to_u64(2 * page_size), usize_to_u64(page_size))
+        );
+
+        // SAFETY: The mapping was created above.
+        unsafe { libc::munmap(addr, len) };
+    }
+
     #[test]
     fn test_single_pmem_dev_metrics() {
         let test_metrics = PmemMetricsPerDevice::alloc(String::from("pmem0"));
@@ -92,6 +92,7 @@ mod tests {
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         let pmem = Pmem::new(config).unwrap();
         let guest_mem = default_mem();
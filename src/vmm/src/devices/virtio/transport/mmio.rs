@@ -15,7 +15,7 @@ use super::{VirtioInterrupt, VirtioInterruptType};
 use crate::devices::virtio::device::VirtioDevice;
 use crate::devices::virtio::device_status;
 use crate::devices::virtio::queue::Queue;
-use crate::logger::{IncMetric, METRICS, error, warn};
+use crate::logger::{EVENT_LOG, EventKind, IncMetric, METRICS, error, warn};
 use crate::utils::byte_order;
 use crate::vstate::bus::BusDevice;
 use crate::vstate::interrupts::InterruptError;
@@ -222,7 +222,11 @@ impl MmioTransport {
                         // configuration change interrupt
                         let _ = self.interrupt.trigger(VirtioInterruptType::Config);
 
-                        error!("Failed to activate virtio device: {}", err)
+                        error!("Failed to activate virtio device: {}", err);
+                        EVENT_LOG.emit(EventKind::DeviceError {
+                            device_id: locked_device.id().to_string(),
+                            error: err.to_string(),
+                        });
                     }
                 }
             }
@@ -32,7 +32,7 @@ use crate::devices::virtio::transport::pci::common_config::{
 };
 use crate::devices::virtio::transport::pci::device_status::*;
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
-use crate::logger::{debug, error};
+use crate::logger::{EVENT_LOG, EventKind, debug, error};
 use crate::pci::configuration::{PciCapability, PciConfiguration, PciConfigurationState};
 use crate::pci::msix::{MsixCap, MsixConfig, MsixConfigState};
 use crate::pci::{
@@ -240,6 +240,8 @@ pub enum VirtioPciDeviceError {
     CreateVirtioPciDevice(#[from] DeviceRelocationError),
     /// Error creating MSI configuration: {0}
     Msi(#[from] InterruptError),
+    /// Error releasing the BAR: {0}
+    ReleaseBar(#[from] vm_allocator::Error),
 }
 
 pub struct VirtioPciDevice {
@@ -264,6 +266,9 @@ pub struct VirtioPciDevice {
     // PCI interrupts.
     virtio_interrupt: Option<Arc<VirtioInterruptMsix>>,
 
+    // MSI-X vectors, kept after a reset of the device to release them when it is removed.
+    msix_vectors: Arc<MsixVectorGroup>,
+
     // Guest memory
     memory: GuestMemoryMmap,
 
@@ -380,7 +385,7 @@ impl VirtioPciDevice {
             msix_config.clone(),
             virtio_common_config.msix_config.clone(),
             virtio_common_config.msix_queues.clone(),
-            msix_vectors,
+            msix_vectors.clone(),
         ));
 
         let virtio_pci_device = VirtioPciDevice {
@@ -392,6 +397,7 @@ impl VirtioPciDevice {
             device,
             device_activated: Arc::new(AtomicBool::new(false)),
             virtio_interrupt: Some(interrupt),
+            msix_vectors,
             memory,
             cap_pci_cfg_info: VirtioPciCfgCapInfo::default(),
             bar_address: 0,
@@ -424,7 +430,7 @@ impl VirtioPciDevice {
             msix_config.clone(),
             virtio_common_config.msix_config.clone(),
             virtio_common_config.msix_queues.clone(),
-            vectors,
+            vectors.clone(),
         ));
 
         let mut virtio_pci_device = VirtioPciDevice {
@@ -436,6 +442,7 @@ impl VirtioPciDevice {
             device,
             device_activated: Arc::new(AtomicBool::new(state.device_activated)),
             virtio_interrupt: Some(interrupt),
+            msix_vectors: vectors,
             memory: vm.guest_memory().clone(),
             cap_pci_cfg_info,
             bar_address: state.bar_address,
@@ -606,6 +613,39 @@ impl VirtioPciDevice {
         Ok(())
     }
 
+    /// Unregister the IoEvent notification for a VirtIO device
+    pub fn unregister_notification_ioevent(&self, vm: &Vm) -> Result<(), errno::Error> {
+        let bar_addr = self.config_bar_addr();
+        for (i, queue_evt) in self
+            .device
+            .lock()
+            .expect("Poisoned lock")
+            .queue_events()
+            .iter()
+            .enumerate()
+        {
+            let notify_base = bar_addr + u64::from(NOTIFICATION_BAR_OFFSET);
+            let io_addr =
+                IoEventAddress::Mmio(notify_base + i as u64 * u64::from(NOTIFY_OFF_MULTIPLIER));
+            vm.fd()
+                .unregister_ioevent(queue_evt, &io_addr, NoDatamatch)?;
+        }
+        Ok(())
+    }
+
+    /// Release the MSI-X vectors and the BAR of the device, once it is removed from the VM.
+    pub fn release_resources(&self, vm: &Vm) -> Result<(), VirtioPciDeviceError> {
+        vm.release_msix_group(&self.msix_vectors)?;
+        vm.resource_allocator()
+            .free_64bit_mmio_memory(self.bar_address, CAPABILITY_BAR_SIZE)?;
+        Ok(())
+    }
+
+    /// SBDF assigned to the device
+    pub fn sbdf(&self) -> PciSBDF {
+        self.sbdf
+    }
+
     pub fn state(&self) -> VirtioPciDeviceState {
         VirtioPciDeviceState {
             sbdf: self.sbdf,
@@ -905,16 +945,17 @@ impl PciDevice for VirtioPciDevice {
         if self.needs_activation() {
             debug!("Activating device");
             let interrupt = Arc::clone(self.virtio_interrupt.as_ref().unwrap());
-            match self
-                .virtio_device()
-                .lock()
-                .unwrap()
-                .activate(self.memory.clone(), interrupt.clone())
-            {
+            let device = self.virtio_device();
+            let mut locked_device = device.lock().unwrap();
+            match locked_device.activate(self.memory.clone(), interrupt.clone()) {
                 Ok(()) => self.device_activated.store(true, Ordering::SeqCst),
                 Err(err) => {
                     self.common_config.driver_status |= DEVICE_NEEDS_RESET;
                     error!("Error activating device: {err:?}");
+                    EVENT_LOG.emit(EventKind::DeviceError {
+                        device_id: locked_device.id().to_string(),
+                        error: err.to_string(),
+                    });
 
                     // Section 2.1.2 of the specification states that we need to send a device
                     // configuration change interrupt
@@ -4,9 +4,11 @@
 // Portions Copyright 2019 Intel Corporation. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::os::fd::AsRawFd;
+use std::fs::File;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
 use std::os::unix::net::UnixStream;
 use std::sync::Arc;
+use std::time::Duration;
 
 use vhost::vhost_user::message::*;
 use vhost::vhost_user::{Frontend, VhostUserFrontend};
@@ -25,10 +27,18 @@ pub enum VhostUserError {
     AvailAddress(GuestMemoryError),
     /// Failed to connect to UDS Unix stream: {0}
     Connect(#[from] std::io::Error),
+    /// Failed to set the timeout of the UDS Unix stream: {0}
+    SetTimeout(std::io::Error),
     /// Invalid descriptor table address
     DescriptorTableAddress(GuestMemoryError),
+    /// Invalid queue count: {0}
+    QueueCount(usize),
+    /// Invalid queue index: {0}
+    QueueIndex(usize),
     /// Get features failed: {0}
     VhostUserGetFeatures(VhostError),
+    /// Get inflight fd failed: {0}
+    VhostUserGetInflightFd(VhostError),
     /// Get protocol features failed: {0}
     VhostUserGetProtocolFeatures(VhostError),
     /// Set owner failed: {0}
@@ -51,6 +61,14 @@ pub enum VhostUserError {
     VhostUserSetVringKick(VhostError),
     /// Set vring enable failed: {0}
     VhostUserSetVringEnable(VhostError),
+    /// Set inflight fd failed: {0}
+    VhostUserSetInflightFd(VhostError),
+    /// Backend does not support previously negotiated features anymore: {0:#x}
+    VhostUserReconnectFeatures(u64),
+    /// Backend does not support previously negotiated protocol features anymore: {0:#x}
+    VhostUserReconnectProtocolFeatures(u64),
+    /// Backend can not be reconnected without inflight I/O tracking
+    VhostUserReconnectNoInflight,
     /// Failed to read vhost eventfd: No memory region found
     VhostUserNoMemoryRegion,
     /// Invalid used address
@@ -71,6 +89,11 @@ pub trait VhostUserHandleBackend: Sized {
         unimplemented!()
     }
 
+    /// Raw file descriptor of the socket connected to the backend.
+    fn socket_fd(&self) -> RawFd {
+        unimplemented!()
+    }
+
     /// Get from the underlying vhost implementation the feature bitmask.
     fn get_features(&self) -> Result<u64, vhost::Error> {
         unimplemented!()
@@ -142,6 +165,21 @@ pub trait VhostUserHandleBackend: Sized {
         unimplemented!()
     }
 
+    fn get_inflight_fd(
+        &mut self,
+        _inflight: &VhostUserInflight,
+    ) -> Result<(VhostUserInflight, File), vhost::Error> {
+        unimplemented!()
+    }
+
+    fn set_inflight_fd(
+        &mut self,
+        _inflight: &VhostUserInflight,
+        _fd: RawFd,
+    ) -> Result<(), vhost::Error> {
+        unimplemented!()
+    }
+
     fn get_config(
         &mut self,
         _offset: u32,
@@ -171,6 +209,10 @@ impl VhostUserHandleBackend for Frontend {
         self.set_hdr_flags(flags)
     }
 
+    fn socket_fd(&self) -> RawFd {
+        <Frontend as AsRawFd>::as_raw_fd(self)
+    }
+
     /// Get from the underlying vhost implementation the feature bitmask.
     fn get_features(&self) -> Result<u64, vhost::Error> {
         <Frontend as VhostBackend>::get_features(self)
@@ -242,6 +284,21 @@ impl VhostUserHandleBackend for Frontend {
         <Frontend as VhostUserFrontend>::set_vring_enable(self, queue_index, enable)
     }
 
+    fn get_inflight_fd(
+        &mut self,
+        inflight: &VhostUserInflight,
+    ) -> Result<(VhostUserInflight, File), vhost::Error> {
+        <Frontend as VhostUserFrontend>::get_inflight_fd(self, inflight)
+    }
+
+    fn set_inflight_fd(
+        &mut self,
+        inflight: &VhostUserInflight,
+        fd: RawFd,
+    ) -> Result<(), vhost::Error> {
+        <Frontend as VhostUserFrontend>::set_inflight_fd(self, inflight, fd)
+    }
+
     fn get_config(
         &mut self,
         offset: u32,
@@ -265,16 +322,18 @@ impl VhostUserHandleBackend for Frontend {
 pub type VhostUserHandle = VhostUserHandleImpl<Frontend>;
 
 /// vhost-user socket handle
-#[derive(Clone)]
 pub struct VhostUserHandleImpl<T: VhostUserHandleBackend> {
     pub vu: T,
     pub socket_path: String,
+    /// Inflight I/O tracking region shared with the backend, if negotiated.
+    pub inflight: Option<(VhostUserInflight, File)>,
 }
 
 impl<T: VhostUserHandleBackend> std::fmt::Debug for VhostUserHandleImpl<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("VhostUserHandle")
             .field("socket_path", &self.socket_path)
+            .field("inflight", &self.inflight.is_some())
             .finish()
     }
 }
@@ -291,9 +350,48 @@ impl<T: VhostUserHandleBackend> VhostUserHandleImpl<T> {
         Ok(Self {
             vu,
             socket_path: socket_path.to_string(),
+            inflight: None,
         })
     }
 
+    /// Connect again to the backend socket after the previous connection
+    /// was closed by the backend and mark self as an owner of the new session.
+    /// Requests on the new session fail after `timeout` instead of blocking on
+    /// a backend that stopped responding, until the timeout is cleared with
+    /// [`Self::set_timeout`].
+    pub fn reconnect(&mut self, num_queues: u64, timeout: Duration) -> Result<(), VhostUserError> {
+        let stream = UnixStream::connect(&self.socket_path).map_err(VhostUserError::Connect)?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|()| stream.set_write_timeout(Some(timeout)))
+            .map_err(VhostUserError::SetTimeout)?;
+
+        let vu = T::from_stream(stream, num_queues);
+        vu.set_owner().map_err(VhostUserError::VhostUserSetOwner)?;
+        self.vu = vu;
+
+        Ok(())
+    }
+
+    /// Set the timeout of the requests sent to the backend. With `None` the
+    /// requests block until the backend replies.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), VhostUserError> {
+        // SAFETY: The file descriptor is the socket owned by `self.vu`, which stays open
+        // for the duration of this borrow.
+        let socket = unsafe { BorrowedFd::borrow_raw(self.vu.socket_fd()) };
+        // Timeouts are socket options, so setting them through a duplicate of the
+        // descriptor applies them to the backend connection as well.
+        let stream = UnixStream::from(
+            socket
+                .try_clone_to_owned()
+                .map_err(VhostUserError::SetTimeout)?,
+        );
+        stream
+            .set_read_timeout(timeout)
+            .and_then(|()| stream.set_write_timeout(timeout))
+            .map_err(VhostUserError::SetTimeout)
+    }
+
     /// Set vhost-user features to the backend.
     pub fn set_features(&self, features: u64) -> Result<(), VhostUserError> {
         self.vu
@@ -363,6 +461,35 @@ impl<T: VhostUserHandleBackend> VhostUserHandleImpl<T> {
         Ok((acked_features, acked_protocol_features.bits()))
     }
 
+    /// Share the inflight I/O tracking region with the backend. The region is
+    /// requested from the backend the first time this is called and reused
+    /// afterwards, so that a restarted backend can resubmit the requests
+    /// that were in flight when the previous one went away.
+    pub fn setup_inflight(&mut self, queues: &[Queue]) -> Result<(), VhostUserError> {
+        if self.inflight.is_none() {
+            let inflight = VhostUserInflight {
+                mmap_size: 0,
+                mmap_offset: 0,
+                num_queues: u16::try_from(queues.len())
+                    .map_err(|_| VhostUserError::QueueCount(queues.len()))?,
+                queue_size: queues.iter().map(|queue| queue.size).max().unwrap_or(0),
+            };
+            let (inflight, file) = self
+                .vu
+                .get_inflight_fd(&inflight)
+                .map_err(VhostUserError::VhostUserGetInflightFd)?;
+            self.inflight = Some((inflight, file));
+        }
+
+        if let Some((inflight, file)) = &self.inflight {
+            self.vu
+                .set_inflight_fd(inflight, file.as_raw_fd())
+                .map_err(VhostUserError::VhostUserSetInflightFd)?;
+        }
+
+        Ok(())
+    }
+
     /// Update guest memory table to the backend.
     fn update_mem_table(&self, mem: &GuestMemoryMmap) -> Result<(), VhostUserError> {
         let mut regions: Vec<VhostUserMemoryRegionInfo> = Vec::new();
@@ -402,7 +529,77 @@ impl<T: VhostUserHandleBackend> VhostUserHandleImpl<T> {
     ) -> Result<(), VhostUserError> {
         // Provide the memory table to the backend.
         self.update_mem_table(mem)?;
+        self.setup_vrings(mem, queues, interrupt, Queue::avail_ring_idx_get)
+    }
 
+    /// Replay the device state to a backend after reconnecting to it. This
+    /// includes features, the inflight I/O tracking region, the memory table
+    /// and the virtio rings. Rings are resumed from the last index the
+    /// previous backend marked as used, the backend resubmits whatever was
+    /// still in flight using the inflight region. Without an inflight region
+    /// the backend could not tell which requests were already processed, so
+    /// the restore is refused.
+    pub fn restore_backend(
+        &mut self,
+        acked_features: u64,
+        acked_protocol_features: u64,
+        mem: &GuestMemoryMmap,
+        queues: &[(usize, &Queue, &EventFd)],
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), VhostUserError> {
+        if self.inflight.is_none() {
+            return Err(VhostUserError::VhostUserReconnectNoInflight);
+        }
+
+        // The new backend may have been started with a different configuration.
+        // Refuse to continue if it can not honor what the guest driver was told.
+        let backend_features = self
+            .vu
+            .get_features()
+            .map_err(VhostUserError::VhostUserGetFeatures)?;
+        let missing_features = acked_features & !backend_features;
+        if missing_features != 0 {
+            return Err(VhostUserError::VhostUserReconnectFeatures(missing_features));
+        }
+
+        // Protocol features are negotiated again with the new backend, they
+        // may have changed the same way the virtio features may have.
+        if acked_features & VhostUserVirtioFeatures::PROTOCOL_FEATURES.bits() != 0 {
+            let backend_protocol_features = self
+                .vu
+                .get_protocol_features()
+                .map_err(VhostUserError::VhostUserGetProtocolFeatures)?;
+            let missing_protocol_features =
+                acked_protocol_features & !backend_protocol_features.bits();
+            if missing_protocol_features != 0 {
+                return Err(VhostUserError::VhostUserReconnectProtocolFeatures(
+                    missing_protocol_features,
+                ));
+            }
+        }
+
+        self.set_protocol_features(acked_features, acked_protocol_features)?;
+        self.set_features(acked_features)?;
+
+        if let Some((inflight, file)) = &self.inflight {
+            self.vu
+                .set_inflight_fd(inflight, file.as_raw_fd())
+                .map_err(VhostUserError::VhostUserSetInflightFd)?;
+        }
+
+        self.update_mem_table(mem)?;
+        self.setup_vrings(mem, queues, interrupt, Queue::used_ring_idx_get)
+    }
+
+    /// Send information about virtio rings to the backend and enable them.
+    /// `vring_base` selects the ring index the backend starts processing from.
+    fn setup_vrings(
+        &mut self,
+        mem: &GuestMemoryMmap,
+        queues: &[(usize, &Queue, &EventFd)],
+        interrupt: Arc<dyn VirtioInterrupt>,
+        vring_base: fn(&Queue) -> u16,
+    ) -> Result<(), VhostUserError> {
         // Send set_vring_num here, since it could tell backends, like SPDK,
         // how many virt queues to be handled, which backend required to know
         // at early stage.
@@ -413,6 +610,13 @@ impl<T: VhostUserHandleBackend> VhostUserHandleImpl<T> {
         }
 
         for (queue_index, queue, queue_evt) in queues.iter() {
+            let notifier = interrupt
+                .notifier(VirtioInterruptType::Queue(
+                    u16::try_from(*queue_index)
+                        .map_err(|_| VhostUserError::QueueIndex(*queue_index))?,
+                ))
+                .ok_or(VhostUserError::QueueIndex(*queue_index))?;
+
             let config_data = VringConfigData {
                 queue_max_size: queue.max_size,
                 queue_size: queue.size,
@@ -434,23 +638,13 @@ impl<T: VhostUserHandleBackend> VhostUserHandleImpl<T> {
                 .set_vring_addr(*queue_index, &config_data)
                 .map_err(VhostUserError::VhostUserSetVringAddr)?;
             self.vu
-                .set_vring_base(*queue_index, queue.avail_ring_idx_get())
+                .set_vring_base(*queue_index, vring_base(queue))
                 .map_err(VhostUserError::VhostUserSetVringBase)?;
 
             // No matter the queue, we set irq_evt for signaling the guest that buffers were
             // consumed.
             self.vu
-                .set_vring_call(
-                    *queue_index,
-                    interrupt
-                        .notifier(VirtioInterruptType::Queue(
-                            (*queue_index).try_into().unwrap_or_else(|_| {
-                                panic!("vhost-user: invalid queue index: {}", *queue_index)
-                            }),
-                        ))
-                        .as_ref()
-                        .unwrap(),
-                )
+                .set_vring_call(*queue_index, notifier)
                 .map_err(VhostUserError::VhostUserSetVringCall)?;
 
             self.vu
@@ -513,6 +707,10 @@ pub(crate) mod tests {
                 }
             }
 
+            fn socket_fd(&self) -> RawFd {
+                self.sock.as_raw_fd()
+            }
+
             fn set_owner(&self) -> Result<(), vhost::Error> {
                 unsafe { *self.is_owner.get() = true };
                 Ok(())
@@ -559,6 +757,7 @@ pub(crate) mod tests {
         let vuh = VhostUserHandleImpl {
             vu: MockFrontend { features: 0.into() },
             socket_path: "".to_string(),
+            inflight: None,
         };
         vuh.set_features(0x69).unwrap();
         assert_eq!(unsafe { *vuh.vu.features.get() }, 0x69);
@@ -591,6 +790,7 @@ pub(crate) mod tests {
                 hdr_flags: std::cell::UnsafeCell::new(VhostUserHeaderFlag::empty()),
             },
             socket_path: "".to_string(),
+            inflight: None,
         };
 
         // No protocol features are set if acked_features do not have PROTOCOL_FEATURES bit
@@ -676,6 +876,7 @@ pub(crate) mod tests {
                 hdr_flags: std::cell::UnsafeCell::new(VhostUserHeaderFlag::empty()),
             },
             socket_path: "".to_string(),
+            inflight: None,
         };
 
         // If nothing is available, nothing is negotiated
@@ -780,6 +981,7 @@ pub(crate) mod tests {
                 regions: std::cell::UnsafeCell::new(vec![]),
             },
             socket_path: "".to_string(),
+            inflight: None,
         };
 
         let region_size = 0x10000;
@@ -898,6 +1100,7 @@ pub(crate) mod tests {
                 vrings: std::cell::UnsafeCell::new(vec![]),
             },
             socket_path: "".to_string(),
+            inflight: None,
         };
 
         let region_size = 0x10000;
@@ -984,4 +1187,332 @@ pub(crate) mod tests {
         assert_eq!(result[0].kick, expected_config.kick);
         assert_eq!(result[0].enable, expected_config.enable);
     }
+
+    #[test]
+    fn test_reconnect() {
+        struct MockFrontend {
+            sock: UnixStream,
+            is_owner: std::cell::UnsafeCell<bool>,
+        }
+
+        impl VhostUserHandleBackend for MockFrontend {
+            fn from_stream(sock: UnixStream, _max_queue_num: u64) -> Self {
+                Self {
+                    sock,
+                    is_owner: std::cell::UnsafeCell::new(false),
+                }
+            }
+
+            fn set_owner(&self) -> Result<(), vhost::Error> {
+                unsafe { *self.is_owner.get() = true };
+                Ok(())
+            }
+        }
+
+        let (_tmp_dir, tmp_socket_path) = create_tmp_socket();
+        let mut vuh = VhostUserHandleImpl::<MockFrontend>::new(&tmp_socket_path, 1).unwrap();
+        unsafe { *vuh.vu.is_owner.get() = false };
+
+        // Reconnecting opens a new session with the same socket path and takes
+        // ownership of it.
+        let timeout = Duration::from_secs(1);
+        vuh.reconnect(1, timeout).unwrap();
+        assert!(unsafe { *vuh.vu.is_owner.get() });
+        assert_eq!(vuh.vu.sock.read_timeout().unwrap(), Some(timeout));
+        assert_eq!(vuh.vu.sock.write_timeout().unwrap(), Some(timeout));
+        assert_eq!(
+            vuh.vu
+                .sock
+                .peer_addr()
+                .unwrap()
+                .as_pathname()
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            &tmp_socket_path,
+        );
+
+        // The timeout is cleared once the backend is restored.
+        vuh.set_timeout(None).unwrap();
+        assert_eq!(vuh.vu.sock.read_timeout().unwrap(), None);
+        assert_eq!(vuh.vu.sock.write_timeout().unwrap(), None);
+
+        // Reconnecting fails if the backend is gone.
+        vuh.socket_path = "/invalid/socket/path".to_string();
+        assert!(matches!(
+            vuh.reconnect(1, timeout).unwrap_err(),
+            VhostUserError::Connect(_)
+        ));
+    }
+
+    #[test]
+    fn test_setup_inflight() {
+        struct MockFrontend {
+            get_calls: u32,
+            set_fds: Vec<RawFd>,
+            requested: Option<VhostUserInflight>,
+        }
+
+        impl VhostUserHandleBackend for MockFrontend {
+            fn get_inflight_fd(
+                &mut self,
+                inflight: &VhostUserInflight,
+            ) -> Result<(VhostUserInflight, File), vhost::Error> {
+                self.get_calls += 1;
+                self.requested = Some(*inflight);
+                let reply = VhostUserInflight {
+                    mmap_size: 0x1000,
+                    ..*inflight
+                };
+                Ok((reply, TempFile::new().unwrap().into_file()))
+            }
+
+            fn set_inflight_fd(
+                &mut self,
+                _inflight: &VhostUserInflight,
+                fd: RawFd,
+            ) -> Result<(), vhost::Error> {
+                self.set_fds.push(fd);
+                Ok(())
+            }
+        }
+
+        let mut vuh = VhostUserHandleImpl {
+            vu: MockFrontend {
+                get_calls: 0,
+                set_fds: vec![],
+                requested: None,
+            },
+            socket_path: "".to_string(),
+            inflight: None,
+        };
+
+        let mut queue = Queue::new(128);
+        queue.size = 64;
+
+        // The queue count has to fit the inflight region description.
+        let queues = vec![queue.clone(); usize::from(u16::MAX) + 1];
+        assert!(matches!(
+            vuh.setup_inflight(&queues).unwrap_err(),
+            VhostUserError::QueueCount(count) if count == queues.len()
+        ));
+        assert_eq!(vuh.vu.get_calls, 0);
+
+        // The first call allocates the region in the backend and shares it back.
+        vuh.setup_inflight(std::slice::from_ref(&queue)).unwrap();
+        let requested = vuh.vu.requested.unwrap();
+        assert_eq!({ requested.num_queues }, 1);
+        assert_eq!({ requested.queue_size }, 64);
+        let fd = vuh.inflight.as_ref().unwrap().1.as_raw_fd();
+        assert_eq!(vuh.vu.get_calls, 1);
+        assert_eq!(vuh.vu.set_fds, vec![fd]);
+
+        // Subsequent calls reuse the same region.
+        vuh.setup_inflight(std::slice::from_ref(&queue)).unwrap();
+        assert_eq!(vuh.vu.get_calls, 1);
+        assert_eq!(vuh.vu.set_fds, vec![fd, fd]);
+    }
+
+    #[test]
+    fn test_restore_backend() {
+        struct MockFrontend {
+            features: u64,
+            acked_features: std::cell::UnsafeCell<u64>,
+            protocol_features: VhostUserProtocolFeatures,
+            acked_protocol_features: Option<VhostUserProtocolFeatures>,
+            memory_is_set: std::cell::UnsafeCell<bool>,
+            vring_base: std::cell::UnsafeCell<Option<u16>>,
+            inflight_fd: Option<RawFd>,
+        }
+
+        impl VhostUserHandleBackend for MockFrontend {
+            fn get_features(&self) -> Result<u64, vhost::Error> {
+                Ok(self.features)
+            }
+
+            fn set_features(&self, features: u64) -> Result<(), vhost::Error> {
+                unsafe { *self.acked_features.get() = features };
+                Ok(())
+            }
+
+            fn get_protocol_features(&mut self) -> Result<VhostUserProtocolFeatures, vhost::Error> {
+                Ok(self.protocol_features)
+            }
+
+            fn set_protocol_features(
+                &mut self,
+                features: VhostUserProtocolFeatures,
+            ) -> Result<(), vhost::Error> {
+                self.acked_protocol_features = Some(features);
+                Ok(())
+            }
+
+            fn set_inflight_fd(
+                &mut self,
+                _inflight: &VhostUserInflight,
+                fd: RawFd,
+            ) -> Result<(), vhost::Error> {
+                self.inflight_fd = Some(fd);
+                Ok(())
+            }
+
+            fn set_mem_table(
+                &self,
+                _regions: &[VhostUserMemoryRegionInfo],
+            ) -> Result<(), vhost::Error> {
+                unsafe { *self.memory_is_set.get() = true };
+                Ok(())
+            }
+
+            fn set_vring_num(&self, _queue_index: usize, _num: u16) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+
+            fn set_vring_addr(
+                &self,
+                _queue_index: usize,
+                _config_data: &VringConfigData,
+            ) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+
+            fn set_vring_base(&self, _queue_index: usize, base: u16) -> Result<(), vhost::Error> {
+                unsafe { *self.vring_base.get() = Some(base) };
+                Ok(())
+            }
+
+            fn set_vring_call(
+                &self,
+                _queue_index: usize,
+                _fd: &EventFd,
+            ) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+
+            fn set_vring_kick(
+                &self,
+                _queue_index: usize,
+                _fd: &EventFd,
+            ) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+
+            fn set_vring_enable(
+                &mut self,
+                _queue_index: usize,
+                _enable: bool,
+            ) -> Result<(), vhost::Error> {
+                Ok(())
+            }
+        }
+
+        let inflight_file = TempFile::new().unwrap().into_file();
+        let inflight_fd = inflight_file.as_raw_fd();
+        let inflight = VhostUserInflight {
+            mmap_size: 0x1000,
+            mmap_offset: 0,
+            num_queues: 1,
+            queue_size: 128,
+        };
+        let mut vuh = VhostUserHandleImpl {
+            vu: MockFrontend {
+                features: 0x1 | VhostUserVirtioFeatures::PROTOCOL_FEATURES.bits(),
+                acked_features: std::cell::UnsafeCell::new(0),
+                protocol_features: VhostUserProtocolFeatures::MQ,
+                acked_protocol_features: None,
+                memory_is_set: std::cell::UnsafeCell::new(false),
+                vring_base: std::cell::UnsafeCell::new(None),
+                inflight_fd: None,
+            },
+            socket_path: "".to_string(),
+            inflight: Some((inflight, inflight_file)),
+        };
+
+        let region_size = 0x10000;
+        let file = TempFile::new().unwrap().into_file();
+        file.set_len(region_size as u64).unwrap();
+        let regions = vec![(GuestAddress(0x0), region_size)];
+        let guest_memory = create_mem(file, &regions);
+
+        let mut queue = Queue::new(128);
+        queue.ready = true;
+        queue.size = queue.max_size;
+        queue.initialize(&guest_memory).unwrap();
+        queue.used_ring_idx_set(0x42);
+        let event_fd = EventFd::new(0).unwrap();
+        let queues = [(0, &queue, &event_fd)];
+        let interrupt = default_interrupt();
+
+        // A backend can not be restored without inflight I/O tracking, as it
+        // could not tell which requests were already processed.
+        let inflight = vuh.inflight.take();
+        assert!(matches!(
+            vuh.restore_backend(0x1, 0, &guest_memory, &queues, interrupt.clone())
+                .unwrap_err(),
+            VhostUserError::VhostUserReconnectNoInflight
+        ));
+        assert!(!unsafe { *vuh.vu.memory_is_set.get() });
+        vuh.inflight = inflight;
+
+        // A backend that lost some of the negotiated features is rejected.
+        assert!(matches!(
+            vuh.restore_backend(0x3, 0, &guest_memory, &queues, interrupt.clone())
+                .unwrap_err(),
+            VhostUserError::VhostUserReconnectFeatures(0x2)
+        ));
+        assert!(!unsafe { *vuh.vu.memory_is_set.get() });
+
+        // Protocol features are queried again and a backend that lost some
+        // of them is rejected as well.
+        let acked_features = 0x1 | VhostUserVirtioFeatures::PROTOCOL_FEATURES.bits();
+        let acked_protocol_features =
+            VhostUserProtocolFeatures::MQ | VhostUserProtocolFeatures::CONFIG;
+        assert!(matches!(
+            vuh.restore_backend(
+                acked_features,
+                acked_protocol_features.bits(),
+                &guest_memory,
+                &queues,
+                interrupt.clone()
+            )
+            .unwrap_err(),
+            VhostUserError::VhostUserReconnectProtocolFeatures(missing)
+                if missing == VhostUserProtocolFeatures::CONFIG.bits()
+        ));
+        assert!(vuh.vu.acked_protocol_features.is_none());
+        assert!(!unsafe { *vuh.vu.memory_is_set.get() });
+
+        // Otherwise the whole state is replayed and the vring resumes from
+        // the last used index.
+        vuh.restore_backend(
+            acked_features,
+            VhostUserProtocolFeatures::MQ.bits(),
+            &guest_memory,
+            &queues,
+            interrupt.clone(),
+        )
+        .unwrap();
+        assert_eq!(unsafe { *vuh.vu.acked_features.get() }, acked_features);
+        assert_eq!(
+            vuh.vu.acked_protocol_features,
+            Some(VhostUserProtocolFeatures::MQ)
+        );
+        assert_eq!(vuh.vu.inflight_fd, Some(inflight_fd));
+        assert!(unsafe { *vuh.vu.memory_is_set.get() });
+        assert_eq!(unsafe { *vuh.vu.vring_base.get() }, Some(0x42));
+
+        // A queue index that does not map to an interrupt is an error.
+        let queue_index = usize::from(u16::MAX) + 1;
+        assert!(matches!(
+            vuh.restore_backend(
+                acked_features,
+                VhostUserProtocolFeatures::MQ.bits(),
+                &guest_memory,
+                &[(queue_index, &queue, &event_fd)],
+                interrupt,
+            )
+            .unwrap_err(),
+            VhostUserError::QueueIndex(index) if index == queue_index
+        ));
+    }
 }
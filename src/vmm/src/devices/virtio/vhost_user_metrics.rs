@@ -15,6 +15,10 @@
 //!     "init_time_us": SharedStoreMetric,
 //!     "activate_time_us": SharedStoreMetric,
 //!     "config_change_time_us": SharedStoreMetric,
+//!     "disconnects": "SharedIncMetric",
+//!     "reconnects": "SharedIncMetric",
+//!     "reconnect_fails": "SharedIncMetric",
+//!     "reconnect_time_us": SharedStoreMetric,
 //!  }
 //!  "vhost_user_{mod}_id1": {
 //!     "activate_fails": "SharedIncMetric",
@@ -22,6 +26,10 @@
 //!     "init_time_us": SharedStoreMetric,
 //!     "activate_time_us": SharedStoreMetric,
 //!     "config_change_time_us": SharedStoreMetric,
+//!     "disconnects": "SharedIncMetric",
+//!     "reconnects": "SharedIncMetric",
+//!     "reconnect_fails": "SharedIncMetric",
+//!     "reconnect_time_us": SharedStoreMetric,
 //!  }
 //!  ...
 //!  "vhost_user_{mod}_idN": {
@@ -30,12 +38,17 @@
 //!     "init_time_us": SharedStoreMetric,
 //!     "activate_time_us": SharedStoreMetric,
 //!     "config_change_time_us": SharedStoreMetric,
+//!     "disconnects": "SharedIncMetric",
+//!     "reconnects": "SharedIncMetric",
+//!     "reconnect_fails": "SharedIncMetric",
+//!     "reconnect_time_us": SharedStoreMetric,
 //!  }
 //! }
 //! ```
 //! Each `vhost_user` field in the example above is a serializable `VhostUserDeviceMetrics`
 //! structure collecting metrics such as `activate_fails`, `cfg_fails`, `init_time_us`,
-//! `activate_time_us` and `config_change_time_us` for the vhost_user device.
+//! `activate_time_us`, `config_change_time_us` and the backend reconnection counters for the
+//! vhost_user device.
 //! For vhost-user block device having endpoint "/drives/drv0" the emitted metrics would be
 //! `vhost_user_block_drv0`.
 //! For vhost-user block device having endpoint "/drives/drvN" the emitted metrics would be
@@ -140,6 +153,14 @@ pub struct VhostUserDeviceMetrics {
     pub activate_time_us: SharedStoreMetric,
     // Vhost-user config change time in microseconds.
     pub config_change_time_us: SharedStoreMetric,
+    // Number of times the backend closed the connection to the device.
+    pub disconnects: SharedIncMetric,
+    // Number of times the device successfully reconnected to the backend.
+    pub reconnects: SharedIncMetric,
+    // Number of failed attempts to reconnect to the backend.
+    pub reconnect_fails: SharedIncMetric,
+    // Vhost-user time of the last successful reconnection in microseconds.
+    pub reconnect_time_us: SharedStoreMetric,
 }
 
 #[cfg(test)]
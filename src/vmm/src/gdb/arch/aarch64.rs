@@ -3,10 +3,12 @@
 
 use std::mem::offset_of;
 
+use gdbstub::target::ext::breakpoints::WatchKind;
 use gdbstub_arch::aarch64::reg::AArch64CoreRegs as CoreRegs;
 use kvm_bindings::{
-    KVM_GUESTDBG_ENABLE, KVM_GUESTDBG_SINGLESTEP, KVM_GUESTDBG_USE_HW, KVM_GUESTDBG_USE_SW_BP,
-    KVM_REG_ARM_CORE, KVM_REG_ARM64, KVM_REG_SIZE_U64, kvm_guest_debug, kvm_regs, user_pt_regs,
+    KVM_CAP_GUEST_DEBUG_HW_BPS, KVM_CAP_GUEST_DEBUG_HW_WPS, KVM_GUESTDBG_ENABLE,
+    KVM_GUESTDBG_SINGLESTEP, KVM_GUESTDBG_USE_HW, KVM_GUESTDBG_USE_SW_BP, KVM_REG_ARM_CORE,
+    KVM_REG_ARM64, KVM_REG_SIZE_U64, kvm_debug_exit_arch, kvm_guest_debug, kvm_regs, user_pt_regs,
 };
 use kvm_ioctls::VcpuFd;
 use vm_memory::{Bytes, GuestAddress};
@@ -16,7 +18,7 @@ use crate::arch::aarch64::regs::{
     Aarch64RegisterVec, ID_AA64MMFR0_EL1, TCR_EL1, TTBR1_EL1, arm64_core_reg_id,
 };
 use crate::arch::aarch64::vcpu::get_registers;
-use crate::gdb::target::GdbTargetError;
+use crate::gdb::target::{GdbTargetError, Watchpoint};
 
 /// Configures the number of bytes required for a software breakpoint.
 ///
@@ -153,11 +155,53 @@ pub fn translate_gva(vcpu_fd: &VcpuFd, gva: u64, vmm: &Vmm) -> Result<u64, GdbTa
     Ok(address)
 }
 
-/// Configures the kvm guest debug regs to register the hardware breakpoints
+/// Exception class, in bits 26 to 31 of the syndrome register, of a watchpoint exception taken
+/// from a lower exception level
+const ESR_EC_WATCHPOINT_LOWER_EL: u32 = 0x34;
+/// Exception class of a watchpoint exception taken from the current exception level
+const ESR_EC_WATCHPOINT_CUR_EL: u32 = 0x35;
+
+/// Number of breakpoint and watchpoint registers in `kvm_guest_debug_arch`
+const KVM_ARM_MAX_DBG_REGS: usize = 16;
+
+/// Returns whether the debug registers of the host can hold the given numbers of hardware
+/// breakpoints and watchpoints. Breakpoints and watchpoints use separate registers, so a
+/// watchpoint never takes the register of a breakpoint.
+pub fn hw_debug_regs_fit(vmm: &Vmm, hw_breakpoints: usize, hw_watchpoints: usize) -> bool {
+    // KVM reports the number of registers of the host, or 0 if it cannot tell.
+    let host_regs = |cap: u32| {
+        usize::try_from(vmm.kvm.fd.check_extension_raw(u64::from(cap)))
+            .unwrap_or(0)
+            .min(KVM_ARM_MAX_DBG_REGS)
+    };
+    hw_breakpoints <= host_regs(KVM_CAP_GUEST_DEBUG_HW_BPS)
+        && hw_watchpoints <= host_regs(KVM_CAP_GUEST_DEBUG_HW_WPS)
+}
+
+/// Returns the watchpoint which caused a debug exit. The faulting address reported in FAR is the
+/// address of the access, which may start before the watched bytes.
+pub fn get_watchpoint_hit(
+    exit: &kvm_debug_exit_arch,
+    _addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
+) -> Option<Watchpoint> {
+    let ec = exit.hsr >> 26;
+    if ec != ESR_EC_WATCHPOINT_LOWER_EL && ec != ESR_EC_WATCHPOINT_CUR_EL {
+        return None;
+    }
+
+    watchpoints
+        .iter()
+        .find(|w| (w.addr.0..w.addr.0 + w.len).contains(&exit.far))
+        .copied()
+}
+
+/// Configures the kvm guest debug regs to register the hardware breakpoints and watchpoints
 fn set_kvm_debug(
     control: u32,
     vcpu_fd: &VcpuFd,
     addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
 ) -> Result<(), GdbTargetError> {
     let mut dbg = kvm_guest_debug {
         control,
@@ -176,6 +220,26 @@ fn set_kvm_debug(
         dbg.arch.dbg_bvr[i] = (!0u64 >> 11) & addr.0;
     }
 
+    for (i, watchpoint) in watchpoints.iter().enumerate() {
+        let lsc: u64 = match watchpoint.kind {
+            WatchKind::Read => 0b01,
+            WatchKind::Write => 0b10,
+            WatchKind::ReadWrite => 0b11,
+        };
+        // Watchpoints are aligned to their size, so the watched bytes are within a doubleword
+        let bas: u64 = ((1 << watchpoint.len) - 1) << (watchpoint.addr.0 & 0b111);
+        // DBGWCR_EL1 (Debug Watchpoint Control Registers, D13.3.11):
+        // bit 0: 1 (Enabled)
+        // bit 1~2: 0b11 (PAC = EL1/EL0)
+        // bit 3~4: LSC (0b01 = load, 0b10 = store, 0b11 = both)
+        // bit 5~12: BAS (watched bytes of the doubleword)
+        // others: 0
+        dbg.arch.dbg_wcr[i] = 0b1 | (0b11 << 1) | (lsc << 3) | (bas << 5);
+        // DBGWVR_EL1 (Debug Watchpoint Value Registers, D13.3.12):
+        // bit 3~52: VA[3:52]
+        dbg.arch.dbg_wvr[i] = (!0u64 >> 11) & watchpoint.addr.0 & !0b111;
+    }
+
     vcpu_fd.set_guest_debug(&dbg)?;
 
     Ok(())
@@ -214,6 +278,7 @@ fn toggle_interrupts(vcpu_fd: &VcpuFd, enable: bool) -> Result<(), GdbTargetErro
 pub fn vcpu_set_debug(
     vcpu_fd: &VcpuFd,
     addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
     step: bool,
 ) -> Result<(), GdbTargetError> {
     let mut control = KVM_GUESTDBG_ENABLE | KVM_GUESTDBG_USE_HW | KVM_GUESTDBG_USE_SW_BP;
@@ -222,13 +287,14 @@ pub fn vcpu_set_debug(
     }
 
     toggle_interrupts(vcpu_fd, step)?;
-    set_kvm_debug(control, vcpu_fd, addrs)
+    set_kvm_debug(control, vcpu_fd, addrs, watchpoints)
 }
 
 /// KVM does not support injecting breakpoints on aarch64 so this is a no-op
 pub fn vcpu_inject_bp(
     _vcpu_fd: &VcpuFd,
     _addrs: &[GuestAddress],
+    _watchpoints: &[Watchpoint],
     _step: bool,
 ) -> Result<(), GdbTargetError> {
     Ok(())
@@ -310,3 +376,48 @@ pub fn write_registers(vcpu_fd: &VcpuFd, regs: &CoreRegs) -> Result<(), GdbTarge
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_watchpoint_hit() {
+        let watchpoints = [
+            Watchpoint {
+                addr: GuestAddress(0x1000),
+                len: 4,
+                kind: WatchKind::Write,
+            },
+            Watchpoint {
+                addr: GuestAddress(0x2008),
+                len: 8,
+                kind: WatchKind::Read,
+            },
+        ];
+        let exit = |ec: u32, far: u64| kvm_debug_exit_arch {
+            hsr: ec << 26,
+            far,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_watchpoint_hit(&exit(ESR_EC_WATCHPOINT_LOWER_EL, 0x200c), &[], &watchpoints),
+            Some(watchpoints[1])
+        );
+        assert_eq!(
+            get_watchpoint_hit(&exit(ESR_EC_WATCHPOINT_CUR_EL, 0x1003), &[], &watchpoints),
+            Some(watchpoints[0])
+        );
+        // An access which matches none of the watchpoints is not reported as one of them.
+        assert_eq!(
+            get_watchpoint_hit(&exit(ESR_EC_WATCHPOINT_LOWER_EL, 0x3000), &[], &watchpoints),
+            None
+        );
+        // Exits other than watchpoint exceptions are not watchpoint hits.
+        assert_eq!(
+            get_watchpoint_hit(&exit(0x3c, 0x1000), &[], &watchpoints),
+            None
+        );
+    }
+}
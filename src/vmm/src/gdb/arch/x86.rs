@@ -1,19 +1,29 @@
 // Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use gdbstub::target::ext::breakpoints::WatchKind;
 use gdbstub_arch::x86::reg::X86_64CoreRegs as CoreRegs;
 use kvm_bindings::*;
 use kvm_ioctls::VcpuFd;
 use vm_memory::GuestAddress;
 
 use crate::Vmm;
-use crate::gdb::target::GdbTargetError;
+use crate::gdb::target::{GdbTargetError, Watchpoint};
 use crate::logger::error;
 
 /// Sets the 9th (Global Exact Breakpoint enable) and the 10th (always 1) bits for the DR7 debug
 /// control register
 const X86_GLOBAL_DEBUG_ENABLE: u64 = 0b11 << 9;
 
+/// Number of debug address registers (DR0 to DR3), shared by hardware breakpoints and watchpoints
+const X86_DEBUG_ADDR_REGS: usize = 4;
+
+/// Vector of the debug exception (#DB), raised by hardware breakpoints and watchpoints
+const X86_DB_VECTOR: u32 = 1;
+
+/// Bits of DR6 set when the condition of the matching debug address register is met
+const X86_DR6_CONDITION_MASK: u64 = 0b1111;
+
 /// Op code to trigger a software breakpoint in x86
 const X86_SW_BP_OP: u8 = 0xCC;
 
@@ -41,15 +51,39 @@ pub fn translate_gva(vcpu_fd: &VcpuFd, gva: u64, _vmm: &Vmm) -> Result<u64, GdbT
     Ok(tr.physical_address)
 }
 
+/// Returns whether the debug address registers can hold the given numbers of hardware breakpoints
+/// and watchpoints, which share these registers
+pub fn hw_debug_regs_fit(_vmm: &Vmm, hw_breakpoints: usize, hw_watchpoints: usize) -> bool {
+    hw_breakpoints + hw_watchpoints <= X86_DEBUG_ADDR_REGS
+}
+
+/// Computes the R/W and LEN fields of DR7 for a watchpoint. x86 has no read only watchpoints, so
+/// read watchpoints also trigger on writes.
+fn watchpoint_dr7_fields(watchpoint: &Watchpoint) -> u64 {
+    let rw = match watchpoint.kind {
+        WatchKind::Write => 0b01,
+        WatchKind::Read | WatchKind::ReadWrite => 0b11,
+    };
+    let len = match watchpoint.len {
+        1 => 0b00,
+        2 => 0b01,
+        8 => 0b10,
+        _ => 0b11,
+    };
+    rw | (len << 2)
+}
+
 /// Configures the kvm guest debug regs to register the hardware breakpoints, the `arch.debugreg`
 /// attribute is used to store the location of the hardware breakpoints, with the 8th slot being
 /// used as a bitfield to track which registers are enabled and setting the
-/// `X86_GLOBAL_DEBUG_ENABLE` flags. Further reading on the DR7 register can be found here:
+/// `X86_GLOBAL_DEBUG_ENABLE` flags. Watchpoints use the registers left after the breakpoints.
+/// Further reading on the DR7 register can be found here:
 /// https://en.wikipedia.org/wiki/X86_debug_register#DR7_-_Debug_control
 fn set_kvm_debug(
     control: u32,
     vcpu_fd: &VcpuFd,
     addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
 ) -> Result<(), GdbTargetError> {
     let mut dbg = kvm_guest_debug {
         control,
@@ -64,6 +98,14 @@ fn set_kvm_debug(
         dbg.arch.debugreg[7] |= 2 << (i * 2);
     }
 
+    for (i, watchpoint) in watchpoints.iter().enumerate() {
+        let slot = addrs.len() + i;
+        dbg.arch.debugreg[slot] = watchpoint.addr.0;
+        dbg.arch.debugreg[7] |= 2 << (slot * 2);
+        // The R/W and LEN fields of each register take 4 bits starting at bit 16
+        dbg.arch.debugreg[7] |= watchpoint_dr7_fields(watchpoint) << (16 + slot * 4);
+    }
+
     vcpu_fd.set_guest_debug(&dbg)?;
 
     Ok(())
@@ -73,6 +115,7 @@ fn set_kvm_debug(
 pub fn vcpu_set_debug(
     vcpu_fd: &VcpuFd,
     addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
     step: bool,
 ) -> Result<(), GdbTargetError> {
     let mut control = KVM_GUESTDBG_ENABLE | KVM_GUESTDBG_USE_HW_BP | KVM_GUESTDBG_USE_SW_BP;
@@ -80,7 +123,7 @@ pub fn vcpu_set_debug(
         control |= KVM_GUESTDBG_SINGLESTEP;
     }
 
-    set_kvm_debug(control, vcpu_fd, addrs)
+    set_kvm_debug(control, vcpu_fd, addrs, watchpoints)
 }
 
 /// Injects a BP back into the guest kernel for it to handle, this is particularly useful for the
@@ -88,6 +131,7 @@ pub fn vcpu_set_debug(
 pub fn vcpu_inject_bp(
     vcpu_fd: &VcpuFd,
     addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
     step: bool,
 ) -> Result<(), GdbTargetError> {
     let mut control = KVM_GUESTDBG_ENABLE
@@ -99,7 +143,25 @@ pub fn vcpu_inject_bp(
         control |= KVM_GUESTDBG_SINGLESTEP;
     }
 
-    set_kvm_debug(control, vcpu_fd, addrs)
+    set_kvm_debug(control, vcpu_fd, addrs, watchpoints)
+}
+
+/// Returns the watchpoint which caused a debug exit, using DR6 to identify the debug address
+/// register whose condition was met
+pub fn get_watchpoint_hit(
+    exit: &kvm_debug_exit_arch,
+    addrs: &[GuestAddress],
+    watchpoints: &[Watchpoint],
+) -> Option<Watchpoint> {
+    if exit.exception != X86_DB_VECTOR {
+        return None;
+    }
+
+    let conditions = exit.dr6 & X86_DR6_CONDITION_MASK;
+    (addrs.len()..X86_DEBUG_ADDR_REGS)
+        .filter(|slot| conditions & (1 << slot) != 0)
+        .find_map(|slot| watchpoints.get(slot - addrs.len()))
+        .copied()
 }
 
 /// Reads the registers for the Vcpu
@@ -13,7 +13,7 @@ use gdbstub::stub::{DisconnectReason, GdbStub, MultiThreadStopReason};
 use gdbstub::target::Target;
 use vm_memory::GuestAddress;
 
-use super::target::{FirecrackerTarget, GdbTargetError, vcpuid_to_tid};
+use super::target::{FirecrackerTarget, GdbTargetError, VcpuDebugEvent, vcpuid_to_tid};
 use crate::Vmm;
 use crate::logger::{error, trace};
 
@@ -21,7 +21,7 @@ use crate::logger::{error, trace};
 pub fn event_loop(
     connection: UnixStream,
     vmm: Arc<Mutex<Vmm>>,
-    gdb_event_receiver: Receiver<usize>,
+    gdb_event_receiver: Receiver<VcpuDebugEvent>,
     entry_addr: GuestAddress,
 ) {
     let target = FirecrackerTarget::new(vmm, gdb_event_receiver, entry_addr);
@@ -58,16 +58,16 @@ impl run_blocking::BlockingEventLoop for GdbBlockingEventLoop {
     > {
         loop {
             match target.gdb_event.try_recv() {
-                Ok(cpu_id) => {
+                Ok(event) => {
                     // The Vcpu reports it's id from raw_id so we straight convert here
-                    let tid = Tid::new(cpu_id).expect("Error converting cpu id to Tid");
+                    let tid = Tid::new(event.raw_tid).expect("Error converting cpu id to Tid");
                     // If notify paused returns false this means we were already debugging a single
                     // core, the target will track this for us to pick up later
                     target.set_paused_vcpu(tid);
                     trace!("Vcpu: {tid:?} paused from debug exit");
 
                     let stop_reason = target
-                        .get_stop_reason(tid)
+                        .get_stop_reason(tid, &event.exit)
                         .map_err(WaitForStopReasonError::Target)?;
 
                     let Some(stop_response) = stop_reason else {
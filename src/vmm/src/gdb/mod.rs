@@ -5,6 +5,8 @@
 mod arch;
 /// Event loop for connection to GDB server
 mod event_loop;
+/// Parsing of the GDB monitor commands
+mod monitor;
 /// Target for gdb
 pub mod target;
 
@@ -15,7 +17,7 @@ use std::sync::{Arc, Mutex};
 
 use arch::vcpu_set_debug;
 use event_loop::event_loop;
-use target::GdbTargetError;
+use target::{GdbTargetError, VcpuDebugEvent};
 use vm_memory::GuestAddress;
 
 use crate::Vmm;
@@ -34,7 +36,7 @@ use crate::logger::trace;
 /// communcation to the GDB server
 pub fn gdb_thread(
     vmm: Arc<Mutex<Vmm>>,
-    gdb_event_receiver: Receiver<usize>,
+    gdb_event_receiver: Receiver<VcpuDebugEvent>,
     entry_addr: GuestAddress,
     socket_addr: &str,
 ) -> Result<(), GdbTargetError> {
@@ -44,9 +46,9 @@ pub fn gdb_thread(
     // when resumed will be removed
     {
         let vmm = vmm.lock().unwrap();
-        vcpu_set_debug(&vmm.vcpus_handles[0].vcpu_fd, &[entry_addr], false)?;
+        vcpu_set_debug(&vmm.vcpus_handles[0].vcpu_fd, &[entry_addr], &[], false)?;
         for handle in &vmm.vcpus_handles[1..] {
-            vcpu_set_debug(&handle.vcpu_fd, &[], false)?;
+            vcpu_set_debug(&handle.vcpu_fd, &[], &[], false)?;
         }
     }
 
@@ -0,0 +1,240 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Maximum number of bytes read by a single `phys-read` command
+pub const MAX_PHYS_READ_LEN: usize = 4096;
+
+/// Output of `monitor help`
+pub const HELP: &str = "\
+phys-read <gpa> [len]            Dump len (default 64) bytes of guest physical memory
+phys-write <gpa> <hex bytes>     Write bytes, e.g. 90ab01, to guest physical memory
+translate <gva>                  Translate a guest virtual address on the paused Vcpu
+devices                          Dump the state of the VMM devices
+snapshot <state path> <mem path> Pause all Vcpus and create a full snapshot
+help                             Show this message
+";
+
+/// Errors from parsing a monitor command
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum MonitorCmdError {
+    /// Unknown command {0}, see `monitor help`
+    UnknownCommand(String),
+    /// Missing argument: {0}
+    MissingArgument(&'static str),
+    /// Unexpected argument: {0}
+    UnexpectedArgument(String),
+    /// Invalid number: {0}
+    InvalidNumber(String),
+    /// Invalid hex bytes: {0}
+    InvalidBytes(String),
+    /// At most {MAX_PHYS_READ_LEN:} bytes can be read at once
+    ReadTooLarge,
+}
+
+/// Commands available through the GDB `monitor` command
+#[derive(Debug, PartialEq, Eq)]
+pub enum MonitorCommand {
+    /// List the available commands
+    Help,
+    /// Read guest physical memory
+    PhysRead {
+        /// Guest physical address to read from
+        addr: u64,
+        /// Number of bytes to read
+        len: usize,
+    },
+    /// Write guest physical memory
+    PhysWrite {
+        /// Guest physical address to write at
+        addr: u64,
+        /// Bytes to write
+        data: Vec<u8>,
+    },
+    /// Translate a guest virtual address to a guest physical address
+    Translate {
+        /// Guest virtual address to translate
+        gva: u64,
+    },
+    /// Dump the state of the VMM devices
+    Devices,
+    /// Create a full snapshot of the microVM
+    Snapshot {
+        /// Path of the microVM state file
+        snapshot_path: PathBuf,
+        /// Path of the guest memory file
+        mem_file_path: PathBuf,
+    },
+}
+
+/// Parses a number, either decimal or hexadecimal with a `0x` prefix
+fn parse_number(arg: &str) -> Result<u64, MonitorCmdError> {
+    let result = match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+    result.map_err(|_| MonitorCmdError::InvalidNumber(arg.to_string()))
+}
+
+/// Parses a string of hex bytes such as `90ab01`
+fn parse_bytes(arg: &str) -> Result<Vec<u8>, MonitorCmdError> {
+    let invalid = || MonitorCmdError::InvalidBytes(arg.to_string());
+    if arg.is_empty() || !arg.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+
+    (0..arg.len())
+        .step_by(2)
+        .map(|i| {
+            arg.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Parses the text following `monitor` in GDB
+pub fn parse_monitor_cmd(cmd: &str) -> Result<MonitorCommand, MonitorCmdError> {
+    let mut args = cmd.split_whitespace();
+    let mut next_arg = |name| args.next().ok_or(MonitorCmdError::MissingArgument(name));
+
+    let command = match next_arg("command")? {
+        "help" => MonitorCommand::Help,
+        "phys-read" => {
+            let addr = parse_number(next_arg("gpa")?)?;
+            let len = match next_arg("len") {
+                Ok(len) => usize::try_from(parse_number(len)?)
+                    .map_err(|_| MonitorCmdError::ReadTooLarge)?,
+                Err(_) => 64,
+            };
+            if len > MAX_PHYS_READ_LEN {
+                return Err(MonitorCmdError::ReadTooLarge);
+            }
+            MonitorCommand::PhysRead { addr, len }
+        }
+        "phys-write" => MonitorCommand::PhysWrite {
+            addr: parse_number(next_arg("gpa")?)?,
+            data: parse_bytes(next_arg("hex bytes")?)?,
+        },
+        "translate" => MonitorCommand::Translate {
+            gva: parse_number(next_arg("gva")?)?,
+        },
+        "devices" => MonitorCommand::Devices,
+        "snapshot" => MonitorCommand::Snapshot {
+            snapshot_path: PathBuf::from(next_arg("state path")?),
+            mem_file_path: PathBuf::from(next_arg("mem path")?),
+        },
+        unknown => return Err(MonitorCmdError::UnknownCommand(unknown.to_string())),
+    };
+
+    match args.next() {
+        Some(arg) => Err(MonitorCmdError::UnexpectedArgument(arg.to_string())),
+        None => Ok(command),
+    }
+}
+
+/// Formats memory read at `addr` as lines of 16 hex bytes prefixed by their address
+pub fn hexdump(addr: u64, data: &[u8]) -> String {
+    let mut output = String::new();
+    for (line_addr, line) in (addr..).step_by(16).zip(data.chunks(16)) {
+        let _ = write!(output, "{line_addr:#018x}:");
+        for byte in line {
+            let _ = write!(output, " {byte:02x}");
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_monitor_cmd() {
+        assert_eq!(parse_monitor_cmd("help"), Ok(MonitorCommand::Help));
+        assert_eq!(parse_monitor_cmd(" devices "), Ok(MonitorCommand::Devices));
+        assert_eq!(
+            parse_monitor_cmd("phys-read 0x1000"),
+            Ok(MonitorCommand::PhysRead {
+                addr: 0x1000,
+                len: 64
+            })
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-read 4096 0x10"),
+            Ok(MonitorCommand::PhysRead {
+                addr: 0x1000,
+                len: 16
+            })
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-write 0x2000 90ab01"),
+            Ok(MonitorCommand::PhysWrite {
+                addr: 0x2000,
+                data: vec![0x90, 0xab, 0x01]
+            })
+        );
+        assert_eq!(
+            parse_monitor_cmd("translate 0xffffffff81000000"),
+            Ok(MonitorCommand::Translate {
+                gva: 0xffff_ffff_8100_0000
+            })
+        );
+        assert_eq!(
+            parse_monitor_cmd("snapshot /tmp/vm.snap /tmp/vm.mem"),
+            Ok(MonitorCommand::Snapshot {
+                snapshot_path: PathBuf::from("/tmp/vm.snap"),
+                mem_file_path: PathBuf::from("/tmp/vm.mem"),
+            })
+        );
+
+        assert_eq!(
+            parse_monitor_cmd(""),
+            Err(MonitorCmdError::MissingArgument("command"))
+        );
+        assert_eq!(
+            parse_monitor_cmd("reboot"),
+            Err(MonitorCmdError::UnknownCommand("reboot".to_string()))
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-read 0xzz"),
+            Err(MonitorCmdError::InvalidNumber("0xzz".to_string()))
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-read 0 4097"),
+            Err(MonitorCmdError::ReadTooLarge)
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-write 0 9"),
+            Err(MonitorCmdError::InvalidBytes("9".to_string()))
+        );
+        assert_eq!(
+            parse_monitor_cmd("phys-write 0 éé"),
+            Err(MonitorCmdError::InvalidBytes("éé".to_string()))
+        );
+        assert_eq!(
+            parse_monitor_cmd("snapshot /tmp/vm.snap"),
+            Err(MonitorCmdError::MissingArgument("mem path"))
+        );
+        assert_eq!(
+            parse_monitor_cmd("devices all"),
+            Err(MonitorCmdError::UnexpectedArgument("all".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let data: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            hexdump(0x1000, &data),
+            concat!(
+                "0x0000000000001000: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n",
+                "0x0000000000001010: 10 11 12 13\n"
+            )
+        );
+        assert_eq!(hexdump(0, &[]), "");
+    }
+}
@@ -2,12 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, RecvError};
 use std::sync::{Arc, Mutex, PoisonError};
 
 use arrayvec::ArrayVec;
 use gdbstub::arch::Arch;
 use gdbstub::common::{Signal, Tid};
+use gdbstub::outputln;
 use gdbstub::stub::{BaseStopReason, MultiThreadStopReason};
 use gdbstub::target::ext::base::BaseOps;
 use gdbstub::target::ext::base::multithread::{
@@ -15,8 +17,10 @@ use gdbstub::target::ext::base::multithread::{
     MultiThreadSchedulerLockingOps, MultiThreadSingleStep, MultiThreadSingleStepOps,
 };
 use gdbstub::target::ext::breakpoints::{
-    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, SwBreakpoint, SwBreakpointOps,
+    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, HwWatchpoint, HwWatchpointOps,
+    SwBreakpoint, SwBreakpointOps, WatchKind,
 };
+use gdbstub::target::ext::monitor_cmd::{ConsoleOutput, MonitorCmd, MonitorCmdOps};
 use gdbstub::target::ext::thread_extra_info::{ThreadExtraInfo, ThreadExtraInfoOps};
 use gdbstub::target::{Target, TargetError, TargetResult};
 #[cfg(target_arch = "aarch64")]
@@ -27,14 +31,19 @@ use gdbstub_arch::aarch64::reg::AArch64CoreRegs as CoreRegs;
 use gdbstub_arch::x86::X86_64_SSE as GdbArch;
 #[cfg(target_arch = "x86_64")]
 use gdbstub_arch::x86::reg::X86_64CoreRegs as CoreRegs;
+use kvm_bindings::kvm_debug_exit_arch;
 use vm_memory::{Bytes, GuestAddress, GuestMemoryError};
 
 use super::arch;
+use super::monitor::{self, MonitorCmdError, MonitorCommand};
 use crate::arch::GUEST_PAGE_SIZE;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::aarch64::vcpu::VcpuArchError as AarchVcpuError;
 use crate::logger::{error, info};
+use crate::persist::{CreateSnapshotError, VmInfo, create_snapshot};
+use crate::snapshot::Persist;
 use crate::utils::u64_to_usize;
+use crate::vmm_config::snapshot::{CreateSnapshotParams, SnapshotType};
 use crate::vstate::vcpu::VcpuSendEventError;
 use crate::{FcExitCode, VcpuEvent, VcpuResponse, Vmm};
 
@@ -93,6 +102,46 @@ pub enum GdbTargetError {
     ReadRegisterVecError,
     /// Error while reading/writing to guest memory
     GuestMemoryError(#[from] GuestMemoryError),
+    /// Invalid monitor command: {0}
+    MonitorCmd(#[from] MonitorCmdError),
+    /// Error creating a snapshot: {0}
+    CreateSnapshot(#[from] CreateSnapshotError),
+}
+
+/// Debug exit of a Vcpu, reported to the GDB thread
+#[derive(Debug, Clone, Copy)]
+pub struct VcpuDebugEvent {
+    /// The 1 indexed id of the Vcpu, as used by GDB
+    pub raw_tid: usize,
+    /// Architecture specific details of the debug exit
+    pub exit: kvm_debug_exit_arch,
+}
+
+/// A hardware watchpoint set by GDB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// Guest virtual address of the watched data
+    pub addr: GuestAddress,
+    /// Number of watched bytes, either 1, 2, 4 or 8
+    pub len: u64,
+    /// Accesses which trigger the watchpoint
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Creates a watchpoint if the debug registers can watch `len` bytes at `addr`, which is the
+    /// case for 1, 2, 4 or 8 bytes aligned to their size
+    fn new(addr: u64, len: u64, kind: WatchKind) -> Option<Self> {
+        if !matches!(len, 1 | 2 | 4 | 8) || !addr.is_multiple_of(len) {
+            return None;
+        }
+
+        Some(Self {
+            addr: GuestAddress(addr),
+            len,
+            kind,
+        })
+    }
 }
 
 impl From<GdbTargetError> for TargetError<GdbTargetError> {
@@ -121,12 +170,15 @@ pub struct FirecrackerTarget {
     entry_addr: GuestAddress,
 
     /// Listener for events sent from the Vcpu
-    pub gdb_event: Receiver<usize>,
+    pub gdb_event: Receiver<VcpuDebugEvent>,
 
     /// Used to track the currently configured hardware breakpoints.
     /// Limited to 4 in x86 see:
     /// https://elixir.bootlin.com/linux/v6.1/source/arch/x86/include/asm/kvm_host.h#L210
     hw_breakpoints: ArrayVec<GuestAddress, 4>,
+    /// Used to track the currently configured hardware watchpoints. On x86 these share the debug
+    /// registers with the hardware breakpoints.
+    hw_watchpoints: ArrayVec<Watchpoint, 4>,
     /// Used to track the currently configured software breakpoints and store the op-code
     /// which was swapped out
     sw_breakpoints: HashMap<<GdbArch as Arch>::Usize, [u8; arch::SW_BP_SIZE]>,
@@ -163,8 +215,14 @@ impl FirecrackerTarget {
     /// Creates a new Target for GDB stub. This is used as the layer between GDB and the VMM it
     /// will handle requests from GDB and perform the appropriate actions, while also updating GDB
     /// with the state of the VMM / Vcpu's as we hit debug events
-    pub fn new(vmm: Arc<Mutex<Vmm>>, gdb_event: Receiver<usize>, entry_addr: GuestAddress) -> Self {
-        let mut vcpu_state = vec![VcpuState::default(); vmm.lock().unwrap().vcpus_handles.len()];
+    pub fn new(
+        vmm: Arc<Mutex<Vmm>>,
+        gdb_event: Receiver<VcpuDebugEvent>,
+        entry_addr: GuestAddress,
+    ) -> Self {
+        // The vcpus parked until they are hotplugged follow the ones online at boot.
+        let vcpu_count = vmm.lock().unwrap().active_vcpus().count();
+        let mut vcpu_state = vec![VcpuState::default(); vcpu_count];
         // By default vcpu 1 will be paused at the entry point
         vcpu_state[0].paused = true;
 
@@ -174,6 +232,7 @@ impl FirecrackerTarget {
             gdb_event,
             // We only support 4 hw breakpoints on x86 this will need to be configurable on arm
             hw_breakpoints: Default::default(),
+            hw_watchpoints: Default::default(),
             sw_breakpoints: HashMap::new(),
             vcpu_state,
 
@@ -183,11 +242,7 @@ impl FirecrackerTarget {
     }
 
     // Update KVM debug info for a specific vcpu index.
-    fn update_vcpu_kvm_debug(
-        &self,
-        vcpu_idx: usize,
-        hw_breakpoints: &[GuestAddress],
-    ) -> Result<(), GdbTargetError> {
+    fn update_vcpu_kvm_debug(&self, vcpu_idx: usize) -> Result<(), GdbTargetError> {
         let state = &self.vcpu_state[vcpu_idx];
         if !state.paused {
             info!("Attempted to update kvm debug on a non paused Vcpu");
@@ -195,7 +250,12 @@ impl FirecrackerTarget {
         }
 
         let vcpu_fd = &self.vmm.lock().unwrap().vcpus_handles[vcpu_idx].vcpu_fd;
-        arch::vcpu_set_debug(vcpu_fd, hw_breakpoints, state.single_step)
+        arch::vcpu_set_debug(
+            vcpu_fd,
+            &self.hw_breakpoints,
+            &self.hw_watchpoints,
+            state.single_step,
+        )
     }
 
     /// Translate guest virtual address to guest pysical address.
@@ -226,7 +286,7 @@ impl FirecrackerTarget {
     /// and resumes
     fn resume_all_vcpus(&mut self) -> Result<(), GdbTargetError> {
         for idx in 0..self.vcpu_state.len() {
-            self.update_vcpu_kvm_debug(idx, &self.hw_breakpoints)?;
+            self.update_vcpu_kvm_debug(idx)?;
         }
 
         for cpu_id in 0..self.vcpu_state.len() {
@@ -281,7 +341,7 @@ impl FirecrackerTarget {
         let vmm = self.vmm.lock().unwrap();
         let vcpu_idx = tid_to_vcpuid(tid);
         let vcpu_fd = &vmm.vcpus_handles[vcpu_idx].vcpu_fd;
-        arch::vcpu_inject_bp(vcpu_fd, &self.hw_breakpoints, false)
+        arch::vcpu_inject_bp(vcpu_fd, &self.hw_breakpoints, &self.hw_watchpoints, false)
     }
 
     /// Resumes the Vcpu, will return early if the Vcpu is already running
@@ -312,9 +372,20 @@ impl FirecrackerTarget {
     pub fn get_stop_reason(
         &self,
         tid: Tid,
+        exit: &kvm_debug_exit_arch,
     ) -> Result<Option<BaseStopReason<Tid, u64>>, GdbTargetError> {
         let vcpu_idx = tid_to_vcpuid(tid);
         let vcpu_state = &self.vcpu_state[vcpu_idx];
+        if let Some(watchpoint) =
+            arch::get_watchpoint_hit(exit, &self.hw_breakpoints, &self.hw_watchpoints)
+        {
+            return Ok(Some(MultiThreadStopReason::Watch {
+                tid,
+                kind: watchpoint.kind,
+                addr: watchpoint.addr.0,
+            }));
+        }
+
         if vcpu_state.single_step {
             return Ok(Some(MultiThreadStopReason::SignalWithThread {
                 tid,
@@ -362,6 +433,11 @@ impl Target for FirecrackerTarget {
         Some(self)
     }
 
+    #[inline(always)]
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
+
     /// We disable implicit sw breakpoints as we want to manage these internally so we can inject
     /// breakpoints back into the guest if we didn't create them
     #[inline(always)]
@@ -560,6 +636,11 @@ impl Breakpoints for FirecrackerTarget {
     fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
         Some(self)
     }
+
+    #[inline(always)]
+    fn support_hw_watchpoint(&mut self) -> Option<HwWatchpointOps<'_, Self>> {
+        Some(self)
+    }
 }
 
 impl HwBreakpoint for FirecrackerTarget {
@@ -575,12 +656,17 @@ impl HwBreakpoint for FirecrackerTarget {
             return Ok(true);
         }
 
-        if self.hw_breakpoints.try_push(ga).is_err() {
+        let fits = arch::hw_debug_regs_fit(
+            &self.vmm.lock().map_err(GdbTargetError::from)?,
+            self.hw_breakpoints.len() + 1,
+            self.hw_watchpoints.len(),
+        );
+        if !fits || self.hw_breakpoints.try_push(ga).is_err() {
             return Ok(false);
         }
 
         let vcpu_idx = self.get_paused_vcpu_idx()?;
-        self.update_vcpu_kvm_debug(vcpu_idx, &self.hw_breakpoints)?;
+        self.update_vcpu_kvm_debug(vcpu_idx)?;
 
         Ok(true)
     }
@@ -597,7 +683,60 @@ impl HwBreakpoint for FirecrackerTarget {
         };
 
         let vcpu_idx = self.get_paused_vcpu_idx()?;
-        self.update_vcpu_kvm_debug(vcpu_idx, &self.hw_breakpoints)?;
+        self.update_vcpu_kvm_debug(vcpu_idx)?;
+
+        Ok(true)
+    }
+}
+
+impl HwWatchpoint for FirecrackerTarget {
+    /// Adds a hardware watchpoint. Watchpoints which the debug registers cannot express are
+    /// refused, GDB then reports that it could not insert them.
+    fn add_hw_watchpoint(
+        &mut self,
+        gva: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        let Some(watchpoint) = Watchpoint::new(gva, len, kind) else {
+            return Ok(false);
+        };
+        if self.hw_watchpoints.contains(&watchpoint) {
+            return Ok(true);
+        }
+
+        let fits = arch::hw_debug_regs_fit(
+            &self.vmm.lock().map_err(GdbTargetError::from)?,
+            self.hw_breakpoints.len(),
+            self.hw_watchpoints.len() + 1,
+        );
+        if !fits || self.hw_watchpoints.try_push(watchpoint).is_err() {
+            return Ok(false);
+        }
+
+        let vcpu_idx = self.get_paused_vcpu_idx()?;
+        self.update_vcpu_kvm_debug(vcpu_idx)?;
+
+        Ok(true)
+    }
+
+    /// Removes a hardware watchpoint.
+    fn remove_hw_watchpoint(
+        &mut self,
+        gva: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        let Some(watchpoint) = Watchpoint::new(gva, len, kind) else {
+            return Ok(false);
+        };
+        match self.hw_watchpoints.iter().position(|&w| w == watchpoint) {
+            None => return Ok(false),
+            Some(pos) => self.hw_watchpoints.remove(pos),
+        };
+
+        let vcpu_idx = self.get_paused_vcpu_idx()?;
+        self.update_vcpu_kvm_debug(vcpu_idx)?;
 
         Ok(true)
     }
@@ -661,3 +800,88 @@ impl ThreadExtraInfo for FirecrackerTarget {
         Ok(size)
     }
 }
+
+impl FirecrackerTarget {
+    /// Runs a monitor command and returns its output
+    fn run_monitor_cmd(&mut self, command: MonitorCommand) -> Result<String, GdbTargetError> {
+        match command {
+            MonitorCommand::Help => Ok(monitor::HELP.to_string()),
+            MonitorCommand::PhysRead { addr, len } => {
+                let mut data = vec![0; len];
+                self.vmm
+                    .lock()?
+                    .vm
+                    .guest_memory()
+                    .read_slice(&mut data, GuestAddress(addr))?;
+                Ok(monitor::hexdump(addr, &data))
+            }
+            MonitorCommand::PhysWrite { addr, data } => {
+                self.vmm
+                    .lock()?
+                    .vm
+                    .guest_memory()
+                    .write_slice(&data, GuestAddress(addr))?;
+                Ok(format!("Wrote {} bytes at {addr:#x}\n", data.len()))
+            }
+            MonitorCommand::Translate { gva } => {
+                let gpa = self.translate_gva(self.get_paused_vcpu_idx()?, gva)?;
+                Ok(format!("{gva:#x} -> {gpa:#x}\n"))
+            }
+            MonitorCommand::Devices => {
+                let device_states = self.vmm.lock()?.device_manager.save();
+                Ok(format!("{device_states:#?}\n"))
+            }
+            MonitorCommand::Snapshot {
+                snapshot_path,
+                mem_file_path,
+            } => self.snapshot_microvm(snapshot_path, mem_file_path),
+        }
+    }
+
+    /// Pauses all the Vcpus and creates a full snapshot of the microVM. The Vcpus are resumed
+    /// when GDB next resumes execution.
+    fn snapshot_microvm(
+        &mut self,
+        snapshot_path: PathBuf,
+        mem_file_path: PathBuf,
+    ) -> Result<String, GdbTargetError> {
+        for cpu_id in 0..self.vcpu_state.len() {
+            self.pause_vcpu(vcpuid_to_tid(cpu_id)?)?;
+        }
+
+        let mut vmm = self.vmm.lock()?;
+        let vm_info = VmInfo::from(&*vmm);
+        let params = CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path,
+            mem_file_path,
+        };
+        create_snapshot(&mut vmm, &vm_info, &params)?;
+
+        Ok(format!(
+            "Snapshot written to {}\n",
+            params.snapshot_path.display()
+        ))
+    }
+}
+
+impl MonitorCmd for FirecrackerTarget {
+    /// Handles `monitor` commands. Errors are reported to GDB rather than ending the session.
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let result = monitor::parse_monitor_cmd(&String::from_utf8_lossy(cmd))
+            .map_err(GdbTargetError::from)
+            .and_then(|command| self.run_monitor_cmd(command));
+
+        match result {
+            Ok(output) => out.write_raw(output.as_bytes()),
+            Err(GdbTargetError::VmmLockError) => return Err(GdbTargetError::VmmLockError),
+            Err(err) => outputln!(out, "{err}"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Restricts the filesystem access of Firecracker threads with a Landlock ruleset.
+//!
+//! A Landlock ruleset applies to the thread which installs it and is inherited by the threads it
+//! spawns afterwards. Access is checked when a path is opened, so files opened before the ruleset
+//! is installed (the logger and metrics files, the API socket, `/dev/kvm`) remain usable. On
+//! kernels without Landlock support no ruleset is installed and a warning is logged.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use crate::logger::{info, warn};
+use crate::resources::VmResources;
+
+// Filesystem access rights, as defined in `include/uapi/linux/landlock.h`.
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+// Rights from `LANDLOCK_ACCESS_FS_REMOVE_DIR` (bit 4) to `LANDLOCK_ACCESS_FS_MAKE_SYM` (bit 12)
+// only apply to directories.
+const ACCESS_FS_ABI_1: u64 = (1 << 13) - 1;
+const ACCESS_FS_REFER: u64 = 1 << 13;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+const ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+/// `struct landlock_ruleset_attr`, restricted to the fields of the first ABI version.
+#[repr(C)]
+#[derive(Debug)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+/// `struct landlock_path_beneath_attr`.
+#[repr(C, packed)]
+#[derive(Debug)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Errors encountered when installing a Landlock ruleset.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum LandlockError {
+    /// Failed to create the Landlock ruleset: {0}
+    CreateRuleset(io::Error),
+    /// Failed to open {0:?}: {1}
+    OpenPath(PathBuf, io::Error),
+    /// Failed to add a Landlock rule for {0:?}: {1}
+    AddRule(PathBuf, io::Error),
+    /// Failed to set no_new_privs: {0}
+    NoNewPrivs(io::Error),
+    /// Failed to install the Landlock ruleset: {0}
+    RestrictSelf(io::Error),
+}
+
+/// Access granted to a path by the ruleset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathAccess {
+    /// The path can only be read.
+    ReadOnly,
+    /// The path can be read and written. For a directory, entries can also be created and removed.
+    ReadWrite,
+}
+
+impl PathAccess {
+    fn allowed_access(self, is_dir: bool, handled_access: u64) -> u64 {
+        let access = match (self, is_dir) {
+            (PathAccess::ReadOnly, false) => ACCESS_FS_READ_FILE | ACCESS_FS_IOCTL_DEV,
+            (PathAccess::ReadWrite, false) => {
+                ACCESS_FS_READ_FILE
+                    | ACCESS_FS_WRITE_FILE
+                    | ACCESS_FS_TRUNCATE
+                    | ACCESS_FS_IOCTL_DEV
+            }
+            (PathAccess::ReadOnly, true) => ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR,
+            (PathAccess::ReadWrite, true) => handled_access & !ACCESS_FS_EXECUTE,
+        };
+        access & handled_access
+    }
+}
+
+/// Landlock configuration of the Firecracker process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LandlockConfig {
+    /// Paths which can be read and written in addition to the ones referenced by the microVM
+    /// configuration, such as the directory where snapshots are created.
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+impl LandlockConfig {
+    /// Returns the paths referenced by the microVM configuration, followed by the allowed paths.
+    pub fn rules(&self, vm_resources: &VmResources) -> Vec<(PathBuf, PathAccess)> {
+        let mut rules = Vec::new();
+        for drive in vm_resources.block.configs() {
+            if let Some(path) = drive.path_on_host {
+                let access = if drive.is_read_only == Some(true) {
+                    PathAccess::ReadOnly
+                } else {
+                    PathAccess::ReadWrite
+                };
+                rules.push((PathBuf::from(path), access));
+            }
+            if let Some(socket) = drive.socket {
+                rules.push((PathBuf::from(socket), PathAccess::ReadWrite));
+            }
+        }
+        for pmem in vm_resources.pmem.configs() {
+            let access = if pmem.read_only {
+                PathAccess::ReadOnly
+            } else {
+                PathAccess::ReadWrite
+            };
+            rules.push((PathBuf::from(pmem.path_on_host), access));
+        }
+        if let Some(vsock) = vm_resources.vsock.config() {
+            rules.push((PathBuf::from(vsock.uds_path), PathAccess::ReadWrite));
+        }
+        if let Some(path) = &vm_resources.serial_out_path {
+            rules.push((path.clone(), PathAccess::ReadWrite));
+        }
+        if let Some(path) = &vm_resources.serial_socket_path {
+            rules.push((path.clone(), PathAccess::ReadWrite));
+        }
+        for port in vm_resources
+            .console
+            .config()
+            .map_or_else(Vec::new, |cfg| cfg.ports)
+        {
+            if let Some(path) = port.uds_path.or(port.output_path) {
+                rules.push((PathBuf::from(path), PathAccess::ReadWrite));
+            }
+        }
+        rules.extend(
+            self.allowed_paths
+                .iter()
+                .map(|path| (path.clone(), PathAccess::ReadWrite)),
+        );
+        rules
+    }
+}
+
+/// Returns the Landlock ABI version of the host kernel, or `None` if Landlock is not supported.
+fn abi_version() -> Result<Option<u32>, LandlockError> {
+    // SAFETY: With `CREATE_RULESET_VERSION`, the attribute pointer must be null and its size 0.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP) => Ok(None),
+            _ => Err(LandlockError::CreateRuleset(err)),
+        };
+    }
+    Ok(u32::try_from(ret).ok())
+}
+
+/// Returns the access rights handled by the given Landlock ABI version.
+fn handled_access(abi: u32) -> u64 {
+    let mut access = ACCESS_FS_ABI_1;
+    if abi >= 2 {
+        access |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        access |= ACCESS_FS_TRUNCATE;
+    }
+    if abi >= 5 {
+        access |= ACCESS_FS_IOCTL_DEV;
+    }
+    access
+}
+
+/// Restricts the filesystem access of the calling thread, and of the threads it spawns
+/// afterwards, to the given paths and the files and directories beneath them.
+///
+/// Returns the Landlock ABI version in use, or `None` if the host kernel does not support
+/// Landlock, in which case the access is not restricted.
+pub fn restrict_thread(rules: &[(PathBuf, PathAccess)]) -> Result<Option<u32>, LandlockError> {
+    let Some(abi) = abi_version()? else {
+        warn!("Landlock is not supported by the host kernel, filesystem access is not restricted.");
+        return Ok(None);
+    };
+    let handled_access = handled_access(abi);
+
+    let attr = RulesetAttr {
+        handled_access_fs: handled_access,
+    };
+    // SAFETY: `attr` is a valid `struct landlock_ruleset_attr` of the given size.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    let fd = i32::try_from(ret)
+        .ok()
+        .filter(|fd| *fd >= 0)
+        .ok_or_else(|| LandlockError::CreateRuleset(io::Error::last_os_error()))?;
+    // SAFETY: The syscall returned a new file descriptor which is owned by nobody else.
+    let ruleset = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    for (path, access) in rules {
+        let file = File::options()
+            .read(true)
+            .custom_flags(libc::O_PATH)
+            .open(path)
+            .map_err(|err| LandlockError::OpenPath(path.clone(), err))?;
+        let is_dir = file
+            .metadata()
+            .map_err(|err| LandlockError::OpenPath(path.clone(), err))?
+            .is_dir();
+        let rule = PathBeneathAttr {
+            allowed_access: access.allowed_access(is_dir, handled_access),
+            parent_fd: file.as_raw_fd(),
+        };
+        // SAFETY: `rule` is a valid `struct landlock_path_beneath_attr` and both file descriptors
+        // are open.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &rule as *const PathBeneathAttr,
+                0u32,
+            )
+        };
+        if ret != 0 {
+            return Err(LandlockError::AddRule(
+                path.clone(),
+                io::Error::last_os_error(),
+            ));
+        }
+    }
+
+    // SAFETY: Safe because the parameters are valid.
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(LandlockError::NoNewPrivs(io::Error::last_os_error()));
+    }
+    // SAFETY: The ruleset file descriptor is open.
+    let ret = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) };
+    if ret != 0 {
+        return Err(LandlockError::RestrictSelf(io::Error::last_os_error()));
+    }
+
+    info!(
+        "Landlock ruleset installed (ABI version {abi}, {} rules).",
+        rules.len()
+    );
+    Ok(Some(abi))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use vmm_sys_util::tempdir::TempDir;
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::vmm_config::drive::BlockDeviceConfig;
+
+    #[test]
+    fn test_allowed_access() {
+        let handled = handled_access(1);
+        assert_eq!(handled & ACCESS_FS_TRUNCATE, 0);
+        assert_eq!(
+            PathAccess::ReadWrite.allowed_access(false, handled),
+            ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE
+        );
+        assert_eq!(
+            PathAccess::ReadOnly.allowed_access(false, handled),
+            ACCESS_FS_READ_FILE
+        );
+        assert_eq!(
+            PathAccess::ReadWrite.allowed_access(true, handled),
+            ACCESS_FS_ABI_1 & !ACCESS_FS_EXECUTE
+        );
+
+        let handled = handled_access(5);
+        assert_eq!(
+            PathAccess::ReadWrite.allowed_access(false, handled),
+            ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE | ACCESS_FS_IOCTL_DEV
+        );
+        assert_ne!(
+            PathAccess::ReadWrite.allowed_access(true, handled) & ACCESS_FS_REFER,
+            0
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let drive = TempFile::new().unwrap();
+        let allowed = TempDir::new().unwrap();
+        let mut vm_resources = VmResources::default();
+        vm_resources
+            .block
+            .insert(
+                BlockDeviceConfig {
+                    drive_id: "rootfs".to_string(),
+                    partuuid: None,
+                    is_root_device: true,
+                    cache_type: Default::default(),
+                    is_read_only: Some(true),
+                    path_on_host: Some(drive.as_path().to_str().unwrap().to_string()),
+                    rate_limiter: None,
+                    file_engine_type: None,
+                    socket: None,
+                },
+                false,
+            )
+            .unwrap();
+
+        let config = LandlockConfig {
+            allowed_paths: vec![allowed.as_path().to_path_buf()],
+        };
+        assert_eq!(
+            config.rules(&vm_resources),
+            vec![
+                (drive.as_path().to_path_buf(), PathAccess::ReadOnly),
+                (allowed.as_path().to_path_buf(), PathAccess::ReadWrite),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restrict_thread() {
+        let allowed = TempFile::new().unwrap();
+        let denied = TempFile::new().unwrap();
+        let allowed_path = allowed.as_path().to_path_buf();
+        let denied_path = denied.as_path().to_path_buf();
+
+        thread::spawn(move || {
+            let rules = vec![(allowed_path.clone(), PathAccess::ReadWrite)];
+            let abi = restrict_thread(&rules).unwrap();
+
+            File::options().write(true).open(&allowed_path).unwrap();
+            let denied = File::open(&denied_path);
+            match abi {
+                Some(_) => {
+                    let err = denied.unwrap_err();
+                    assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+                    // Threads spawned afterwards inherit the ruleset.
+                    thread::spawn(move || File::open(&denied_path).unwrap_err())
+                        .join()
+                        .unwrap();
+                }
+                None => {
+                    denied.unwrap();
+                }
+            }
+        })
+        .join()
+        .unwrap();
+
+        // The thread which did not install the ruleset is not restricted.
+        File::open(denied.as_path()).unwrap();
+    }
+
+    #[test]
+    fn test_restrict_thread_missing_path() {
+        thread::spawn(|| {
+            if abi_version().unwrap().is_none() {
+                return;
+            }
+            let rules = vec![(PathBuf::from("/nonexistent"), PathAccess::ReadOnly)];
+            assert!(matches!(
+                restrict_thread(&rules).unwrap_err(),
+                LandlockError::OpenPath(_, _)
+            ));
+        })
+        .join()
+        .unwrap();
+    }
+}
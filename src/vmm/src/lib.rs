@@ -75,6 +75,8 @@ pub mod rate_limiter;
 pub mod acpi;
 /// Handles setup and initialization a `Vmm` object.
 pub mod builder;
+/// Core dumps of the guest in the ELF format.
+pub mod coredump;
 /// Types for guest configuration.
 pub mod cpu_config;
 pub(crate) mod device_manager;
@@ -86,10 +88,16 @@ pub mod dumbo;
 /// Support for GDB debugging the guest
 #[cfg(feature = "gdb")]
 pub mod gdb;
+/// Landlock filesystem sandboxing.
+pub mod landlock;
 /// Logger
 pub mod logger;
+/// Measured boot log of the microVM.
+pub mod measured_boot;
 /// microVM Metadata Service MMDS
 pub mod mmds;
+/// Progress and cancellation of long-running operations.
+pub mod operation;
 /// PCI specific emulation code.
 pub mod pci;
 /// Save/restore utilities.
@@ -119,12 +127,16 @@ pub mod initrd;
 use std::collections::HashMap;
 use std::io;
 use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Barrier, Mutex};
 use std::time::Duration;
 
 use device_manager::DeviceManager;
-use event_manager::{EventManager as BaseEventManager, EventOps, Events, MutEventSubscriber};
+use device_manager::pci_mngr::SubscriberUpdate;
+use event_manager::{
+    EventManager as BaseEventManager, EventOps, Events, MutEventSubscriber, SubscriberOps,
+};
 use seccomp::BpfProgram;
 use snapshot::Persist;
 use userfaultfd::Uffd;
@@ -134,34 +146,40 @@ use vmm_sys_util::terminal::Terminal;
 use vstate::kvm::Kvm;
 use vstate::vcpu::{self, StartThreadedError, VcpuSendEventError};
 
+use crate::coredump::{CoreDumpError, create_core_dump};
 use crate::cpu_config::templates::CpuConfiguration;
 use crate::devices::virtio::balloon::device::{HintingStatus, StartHintingCmd};
 use crate::devices::virtio::balloon::{
-    BALLOON_DEV_ID, Balloon, BalloonConfig, BalloonError, BalloonStats,
+    BALLOON_DEV_ID, Balloon, BalloonConfig, BalloonError, BalloonPolicy, BalloonStats,
 };
 use crate::devices::virtio::block::BlockError;
 use crate::devices::virtio::block::device::Block;
+use crate::devices::virtio::console::Console;
 use crate::devices::virtio::device::VirtioDeviceType;
 use crate::devices::virtio::mem::device::VirtioMem;
 use crate::devices::virtio::mem::{VIRTIO_MEM_DEV_ID, VirtioMemError, VirtioMemStatus};
 use crate::devices::virtio::net::Net;
 use crate::devices::virtio::pmem::device::Pmem;
 use crate::devices::virtio::rng::Entropy;
-use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
-use crate::logger::{METRICS, MetricsError, error, info, warn};
+use crate::devices::virtio::vsock::{VSOCK_DEV_ID, Vsock, VsockUnixBackend};
+use crate::logger::{EVENT_LOG, EventKind, METRICS, MetricsError, error, info, warn};
 use crate::mmds::data_store::Mmds;
 use crate::persist::{MicrovmState, MicrovmStateError, VmInfo};
 use crate::rate_limiter::BucketUpdate;
 use crate::resources::VmmConfig;
 use crate::vmm_config::balloon::BalloonDeviceConfig;
 use crate::vmm_config::boot_source::BootSourceConfig;
+use crate::vmm_config::console::ConsoleDeviceConfig;
+use crate::vmm_config::drive::BlockDeviceConfig;
 use crate::vmm_config::entropy::EntropyDeviceConfig;
 use crate::vmm_config::instance_info::{InstanceInfo, VmState};
 use crate::vmm_config::machine_config::MachineConfig;
 use crate::vmm_config::memory_hotplug::MemoryHotplugConfig;
 use crate::vmm_config::mmds::MmdsConfig;
-use crate::vmm_config::net::NetworkInterfaceConfig;
-use crate::vmm_config::vsock::VsockDeviceConfig;
+use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
+use crate::vmm_config::pci_hotplug::{DeviceHotplugError, DeviceUnplugConfig};
+use crate::vmm_config::vcpu_hotplug::VcpuHotplugError;
+use crate::vmm_config::vsock::{VsockBuilder, VsockDeviceConfig};
 use crate::vstate::memory::{GuestMemory, GuestMemoryMmap, GuestMemoryRegion};
 use crate::vstate::vcpu::VcpuState;
 pub use crate::vstate::vcpu::{Vcpu, VcpuConfig, VcpuEvent, VcpuHandle, VcpuResponse};
@@ -182,6 +200,8 @@ pub enum FcExitCode {
     GenericError = 1,
     /// Generic exit code error; not possible to occur if the program logic is sound.
     UnexpectedError = 2,
+    /// Firecracker was shut down after the guest kernel panicked.
+    GuestPanic = 3,
     /// Firecracker was shut down after intercepting a restricted system call.
     BadSyscall = 148,
     /// Firecracker was shut down after intercepting `SIGBUS`.
@@ -320,10 +340,17 @@ pub struct Vmm {
     uffd: Option<Uffd>,
     /// Handles to the vcpu threads with vcpu_fds inside them.
     pub vcpus_handles: Vec<VcpuHandle>,
+    // Bitmap of the vCPUs created for hotplugging whose threads stay paused until the vCPUs are
+    // hotplugged.
+    parked_vcpus: u32,
     // Used by Vcpus and devices to initiate teardown; Vmm should never write here.
     vcpus_exit_evt: EventFd,
     // Device manager
     device_manager: DeviceManager,
+    // Path of the core dump written when a vcpu stops because of an error, if any.
+    coredump_on_fault: Option<PathBuf>,
+    // Whether to pause the microVM instead of exiting when the guest kernel panics.
+    pause_on_panic: bool,
 }
 
 impl Vmm {
@@ -367,6 +394,7 @@ impl Vmm {
         let mut balloon = None;
         let mut vsock = None;
         let mut entropy = None;
+        let mut console = None;
         let mut memory_hotplug = None;
         let mut mmds_ipv4_address = None;
         let mut mmds_ref = None;
@@ -410,6 +438,11 @@ impl Vmm {
                         entropy = Some(EntropyDeviceConfig::from(e));
                     }
                 }
+                VirtioDeviceType::Console => {
+                    if let Some(c) = device.as_any().downcast_ref::<Console>() {
+                        console = Some(ConsoleDeviceConfig::from(c));
+                    }
+                }
                 VirtioDeviceType::Mem => {
                     if let Some(m) = device.as_any().downcast_ref::<VirtioMem>() {
                         memory_hotplug = Some(MemoryHotplugConfig::from(m));
@@ -442,14 +475,17 @@ impl Vmm {
             network_interfaces: net,
             vsock,
             entropy,
+            console,
             pmem_devices: pmem,
             // serial_config is marked serde(skip) so that it doesnt end up in snapshots
             serial_config: None,
             memory_hotplug,
+            memory_hotplug_size: None,
         }
     }
 
-    /// Starts the microVM vcpus.
+    /// Starts the microVM vcpus. The threads of the vcpus set in the `parked_vcpus` bitmap are
+    /// parked until the vcpus are hotplugged.
     ///
     /// # Errors
     ///
@@ -459,6 +495,7 @@ impl Vmm {
     pub fn start_vcpus(
         &mut self,
         mut vcpus: Vec<Vcpu>,
+        parked_vcpus: u32,
         vcpu_seccomp_filter: Arc<BpfProgram>,
     ) -> Result<(), StartVcpusError> {
         let vcpu_count = vcpus.len();
@@ -488,6 +525,7 @@ impl Vmm {
                 barrier.clone(),
             )?);
         }
+        self.parked_vcpus = parked_vcpus;
         self.instance_info.state = VmState::Paused;
         // Wait for vCPUs to initialize their TLS before moving forward.
         barrier.wait();
@@ -500,15 +538,13 @@ impl Vmm {
         self.device_manager.kick_virtio_devices();
 
         // Send the events.
-        self.vcpus_handles
-            .iter_mut()
+        self.active_vcpus_mut()
             .try_for_each(|handle| handle.send_event(VcpuEvent::Resume))
             .map_err(|_| VmmError::VcpuMessage)?;
 
         // Check the responses.
         if self
-            .vcpus_handles
-            .iter()
+            .active_vcpus()
             .map(|handle| handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC))
             .any(|response| !matches!(response, Ok(VcpuResponse::Resumed)))
         {
@@ -516,21 +552,20 @@ impl Vmm {
         }
 
         self.instance_info.state = VmState::Running;
+        EVENT_LOG.emit(EventKind::VmResumed);
         Ok(())
     }
 
     /// Sends a pause command to the vCPUs.
     pub fn pause_vm(&mut self) -> Result<(), VmmError> {
         // Send the events.
-        self.vcpus_handles
-            .iter_mut()
+        self.active_vcpus_mut()
             .try_for_each(|handle| handle.send_event(VcpuEvent::Pause))
             .map_err(|_| VmmError::VcpuMessage)?;
 
         // Check the responses.
         if self
-            .vcpus_handles
-            .iter()
+            .active_vcpus()
             .map(|handle| handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC))
             .any(|response| !matches!(response, Ok(VcpuResponse::Paused)))
         {
@@ -538,6 +573,7 @@ impl Vmm {
         }
 
         self.instance_info.state = VmState::Paused;
+        EVENT_LOG.emit(EventKind::VmPaused);
         Ok(())
     }
 
@@ -587,6 +623,7 @@ impl Vmm {
     }
 
     fn save_vcpu_states(&mut self) -> Result<Vec<VcpuState>, MicrovmStateError> {
+        // The parked threads of the vCPUs created for hotplugging save their state as well.
         for handle in self.vcpus_handles.iter_mut() {
             handle
                 .send_event(VcpuEvent::SaveState)
@@ -614,17 +651,59 @@ impl Vmm {
         Ok(vcpu_states)
     }
 
+    /// Writes the core dump of the guest requested with `--coredump-on-fault` if the microVM
+    /// stopped because of a vcpu error.
+    ///
+    /// It is called once the event loop returned rather than from the exit event handler, so that
+    /// the event handlers never block while the core file is written. Pausing the vcpus which are
+    /// still running waits at most [`RECV_TIMEOUT_SEC`] for each of them, and the core file is as
+    /// large as the guest memory.
+    pub fn write_core_dump_on_fault(&mut self) {
+        if matches!(
+            self.shutdown_exit_code,
+            None | Some(FcExitCode::Ok | FcExitCode::GuestPanic)
+        ) {
+            return;
+        }
+        if let Some(path) = self.coredump_on_fault.take() {
+            match self.dump_core_on_fault(&path) {
+                Ok(()) => info!("Wrote the guest core dump to {}", path.display()),
+                Err(err) => error!("Failed to write the guest core dump: {}", err),
+            }
+        }
+    }
+
+    // Writes a core dump of the guest after a vcpu stopped because of an error. The vcpus which
+    // are still running are paused first.
+    fn dump_core_on_fault(&mut self, path: &Path) -> Result<(), CoreDumpError> {
+        // Only the exit notifications of the vcpus can be pending at this point.
+        for handle in self.active_vcpus() {
+            handle.response_receiver().try_iter().for_each(drop);
+        }
+        for handle in self.active_vcpus_mut() {
+            handle
+                .send_event(VcpuEvent::Pause)
+                .map_err(MicrovmStateError::SignalVcpu)?;
+        }
+        for handle in self.active_vcpus() {
+            match handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC) {
+                Ok(VcpuResponse::Paused | VcpuResponse::Exited(_)) => (),
+                _ => return Err(MicrovmStateError::UnexpectedVcpuResponse.into()),
+            }
+        }
+        create_core_dump(self, path)
+    }
+
     /// Dumps CPU configuration.
     pub fn dump_cpu_config(&mut self) -> Result<Vec<CpuConfiguration>, DumpCpuConfigError> {
-        for handle in self.vcpus_handles.iter_mut() {
+        for handle in self.active_vcpus_mut() {
             handle
                 .send_event(VcpuEvent::DumpCpuConfig)
                 .map_err(DumpCpuConfigError::SendEvent)?;
         }
 
         let vcpu_responses = self
-            .vcpus_handles
-            .iter()
+            .active_vcpus()
             .map(|handle| handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC))
             .collect::<Result<Vec<VcpuResponse>, RecvTimeoutError>>()
             .map_err(|_| DumpCpuConfigError::UnexpectedResponse)?;
@@ -656,6 +735,31 @@ impl Vmm {
         Ok(())
     }
 
+    /// Checks that the file at `path_on_host` can back the block device with id `drive_id`.
+    pub fn check_block_device_path(
+        &self,
+        drive_id: &str,
+        path_on_host: &str,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(drive_id, |block: &mut Block| {
+                block.check_disk_image(path_on_host)
+            })??;
+        Ok(())
+    }
+
+    /// Checks that the rate limiter of the block device with id `drive_id` can be updated.
+    pub fn check_block_rate_limiter(&self, drive_id: &str) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(drive_id, |block: &mut Block| {
+                if block.is_vhost_user() {
+                    return Err(BlockError::InvalidBlockBackend);
+                }
+                Ok(())
+            })??;
+        Ok(())
+    }
+
     /// Updates the rate limiter parameters for block device with `drive_id` id.
     pub fn update_block_rate_limiter(
         &mut self,
@@ -709,6 +813,15 @@ impl Vmm {
         Ok(stats)
     }
 
+    /// Checks that the balloon device target size can be updated to `amount_mib`.
+    pub fn check_balloon_config(&self, amount_mib: u32) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(BALLOON_DEV_ID, |dev: &mut Balloon| {
+                dev.check_size(amount_mib)
+            })??;
+        Ok(())
+    }
+
     /// Updates configuration for the balloon device target size.
     pub fn update_balloon_config(&mut self, amount_mib: u32) -> Result<(), VmmError> {
         self.device_manager
@@ -718,6 +831,19 @@ impl Vmm {
         Ok(())
     }
 
+    /// Checks that the balloon statistics polling interval can be updated to
+    /// `stats_polling_interval_s`.
+    pub fn check_balloon_stats_config(
+        &self,
+        stats_polling_interval_s: u16,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(BALLOON_DEV_ID, |dev: &mut Balloon| {
+                dev.check_stats_polling_interval(stats_polling_interval_s)
+            })??;
+        Ok(())
+    }
+
     /// Updates configuration for the balloon device as described in `balloon_stats_update`.
     pub fn update_balloon_stats_config(
         &mut self,
@@ -730,6 +856,20 @@ impl Vmm {
         Ok(())
     }
 
+    /// Checks that the automatic policy of the balloon device can be set to `policy`.
+    pub fn check_balloon_policy(&self, policy: Option<&BalloonPolicy>) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(BALLOON_DEV_ID, |dev: &mut Balloon| dev.check_policy(policy))??;
+        Ok(())
+    }
+
+    /// Sets or clears the automatic policy of the balloon device.
+    pub fn update_balloon_policy(&mut self, policy: Option<BalloonPolicy>) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(BALLOON_DEV_ID, |dev: &mut Balloon| dev.set_policy(policy))??;
+        Ok(())
+    }
+
     /// Returns the current state of the memory hotplug device.
     pub fn memory_hotplug_status(&self) -> Result<VirtioMemStatus, VmmError> {
         self.device_manager
@@ -737,11 +877,31 @@ impl Vmm {
             .map_err(VmmError::FindDeviceError)
     }
 
-    /// Returns the current state of the memory hotplug device.
-    pub fn update_memory_hotplug_size(&self, requested_size_mib: usize) -> Result<(), VmmError> {
+    /// Checks that the requested size of the memory hotplug device can be updated to
+    /// `requested_size_mib`.
+    pub fn check_memory_hotplug_size(&self, requested_size_mib: usize) -> Result<(), VmmError> {
         self.device_manager
             .with_virtio_device(VIRTIO_MEM_DEV_ID, |dev: &mut VirtioMem| {
-                dev.update_requested_size(requested_size_mib)
+                dev.check_requested_size(requested_size_mib)
+            })
+            .map_err(VmmError::FindDeviceError)??;
+        Ok(())
+    }
+
+    /// Updates the requested size of the memory hotplug device, optionally with a timeout for
+    /// the guest to reach it.
+    pub fn update_memory_hotplug_size(
+        &self,
+        requested_size_mib: usize,
+        timeout: Option<Duration>,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(VIRTIO_MEM_DEV_ID, |dev: &mut VirtioMem| {
+                dev.update_requested_size(requested_size_mib)?;
+                if let Some(timeout) = timeout {
+                    dev.set_resize_timeout(timeout);
+                }
+                Ok::<_, VirtioMemError>(())
             })
             .map_err(VmmError::FindDeviceError)??;
         Ok(())
@@ -769,6 +929,240 @@ impl Vmm {
         Ok(())
     }
 
+    fn check_hotplug(
+        &self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<(), DeviceHotplugError> {
+        if !self.device_manager.is_pci_enabled() {
+            return Err(DeviceHotplugError::PciDisabled);
+        }
+        if self
+            .device_manager
+            .get_virtio_device(device_type, device_id)
+            .is_some()
+        {
+            return Err(DeviceHotplugError::DeviceExists(device_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Adds a block device to the running microVM.
+    pub fn hotplug_block_device(
+        &mut self,
+        config: BlockDeviceConfig,
+    ) -> Result<(), DeviceHotplugError> {
+        self.check_hotplug(VirtioDeviceType::Block, &config.drive_id)?;
+        if config.is_root_device {
+            return Err(DeviceHotplugError::RootDevice);
+        }
+        let id = config.drive_id.clone();
+        let block = Arc::new(Mutex::new(Block::new(config)?));
+        self.device_manager
+            .pci_devices
+            .hotplug_pci_virtio_device(&self.vm, id, block)?;
+        Ok(())
+    }
+
+    /// Adds a network device to the running microVM.
+    pub fn hotplug_net_device(
+        &mut self,
+        config: NetworkInterfaceConfig,
+    ) -> Result<(), DeviceHotplugError> {
+        self.check_hotplug(VirtioDeviceType::Net, &config.iface_id)?;
+        let id = config.iface_id.clone();
+        let net = Arc::new(Mutex::new(NetBuilder::create_net(config)?));
+        self.device_manager
+            .pci_devices
+            .hotplug_pci_virtio_device(&self.vm, id, net)?;
+        Ok(())
+    }
+
+    /// Adds a vsock device to the running microVM.
+    pub fn hotplug_vsock_device(
+        &mut self,
+        config: VsockDeviceConfig,
+    ) -> Result<(), DeviceHotplugError> {
+        self.check_hotplug(VirtioDeviceType::Vsock, VSOCK_DEV_ID)?;
+        let vsock = Arc::new(Mutex::new(VsockBuilder::create_unixsock_vsock(config)?));
+        self.device_manager.pci_devices.hotplug_pci_virtio_device(
+            &self.vm,
+            VSOCK_DEV_ID.to_string(),
+            vsock,
+        )?;
+        Ok(())
+    }
+
+    /// Asks the guest to release a device. The device is removed once the guest ejects it.
+    pub fn unplug_device(&mut self, config: DeviceUnplugConfig) -> Result<(), DeviceHotplugError> {
+        if !self.device_manager.is_pci_enabled() {
+            return Err(DeviceHotplugError::PciDisabled);
+        }
+        let (device_type, device_id) = match &config {
+            DeviceUnplugConfig::Drive { drive_id } => (VirtioDeviceType::Block, drive_id.as_str()),
+            DeviceUnplugConfig::NetworkInterface { iface_id } => {
+                (VirtioDeviceType::Net, iface_id.as_str())
+            }
+            DeviceUnplugConfig::Vsock => (VirtioDeviceType::Vsock, VSOCK_DEV_ID),
+        };
+        if !self
+            .device_manager
+            .pci_devices
+            .request_unplug(device_type, device_id)?
+        {
+            return Err(DeviceHotplugError::DeviceNotFound(device_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks that the number of online vCPUs of the running microVM can be set to `vcpu_count`.
+    pub fn check_vcpu_count(&self, vcpu_count: u8) -> Result<(), VcpuHotplugError> {
+        let controller = self
+            .device_manager
+            .acpi_devices
+            .cpu_hotplug
+            .as_ref()
+            .ok_or(VcpuHotplugError::Unavailable)?;
+        let max_vcpus = controller.lock().expect("Poisoned lock").max_vcpus();
+        if vcpu_count == 0 || vcpu_count > max_vcpus {
+            return Err(VcpuHotplugError::InvalidVcpuCount(vcpu_count));
+        }
+        Ok(())
+    }
+
+    /// Sets the number of online vCPUs of the running microVM. Added vCPUs are announced to the
+    /// guest right away, while removed vCPUs stay online until the guest ejects them.
+    pub fn update_vcpu_count(&mut self, vcpu_count: u8) -> Result<(), VcpuHotplugError> {
+        self.check_vcpu_count(vcpu_count)?;
+        let controller = self
+            .device_manager
+            .acpi_devices
+            .cpu_hotplug
+            .clone()
+            .ok_or(VcpuHotplugError::Unavailable)?;
+        let mut controller = controller.lock().expect("Poisoned lock");
+
+        for idx in 0..controller.max_vcpus() {
+            if idx < vcpu_count && !controller.is_enabled(idx) {
+                self.unpark_vcpu(usize::from(idx))?;
+                controller.plug(idx)?;
+            } else if idx >= vcpu_count && controller.is_enabled(idx) {
+                controller.request_unplug(idx)?;
+            }
+        }
+        self.machine_config.vcpu_count = vcpu_count;
+        Ok(())
+    }
+
+    /// Returns whether the thread of vCPU `idx` is parked until the vCPU is hotplugged.
+    fn is_parked(&self, idx: usize) -> bool {
+        self.parked_vcpus & (1 << idx) != 0
+    }
+
+    /// Returns the handles to the threads of the vCPUs which are not parked.
+    fn active_vcpus(&self) -> impl Iterator<Item = &VcpuHandle> {
+        let parked_vcpus = self.parked_vcpus;
+        self.vcpus_handles
+            .iter()
+            .enumerate()
+            .filter(move |(idx, _)| parked_vcpus & (1 << idx) == 0)
+            .map(|(_, handle)| handle)
+    }
+
+    fn active_vcpus_mut(&mut self) -> impl Iterator<Item = &mut VcpuHandle> {
+        let parked_vcpus = self.parked_vcpus;
+        self.vcpus_handles
+            .iter_mut()
+            .enumerate()
+            .filter(move |(idx, _)| parked_vcpus & (1 << idx) == 0)
+            .map(|(_, handle)| handle)
+    }
+
+    // Wakes up the parked thread of vCPU `idx`, if any. A vCPU which is started stays halted
+    // until the guest brings it online.
+    fn unpark_vcpu(&mut self, idx: usize) -> Result<(), VcpuHotplugError> {
+        if !self.is_parked(idx) {
+            return Ok(());
+        }
+        if self.instance_info.state == VmState::Running {
+            let handle = &mut self.vcpus_handles[idx];
+            handle
+                .send_event(VcpuEvent::Resume)
+                .map_err(|_| VcpuHotplugError::VcpuMessage)?;
+            if !matches!(
+                handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC),
+                Ok(VcpuResponse::Resumed)
+            ) {
+                return Err(VcpuHotplugError::VcpuMessage);
+            }
+        }
+        self.parked_vcpus &= !(1 << idx);
+        Ok(())
+    }
+
+    /// Subscribes the hotplugged devices to the event manager, and unsubscribes the removed
+    /// ones.
+    pub fn update_device_subscribers(&mut self, event_manager: &mut EventManager) {
+        for update in self.device_manager.pci_devices.subscriber_updates.drain(..) {
+            match update {
+                SubscriberUpdate::Add(pci_device) => {
+                    let mut pci_device = pci_device.lock().expect("Poisoned lock");
+                    let sub_id = event_manager.add_subscriber(pci_device.virtio_device());
+                    pci_device.sub_id = Some(sub_id);
+                }
+                SubscriberUpdate::Remove(sub_id) => {
+                    if let Err(err) = event_manager.remove_subscriber(sub_id) {
+                        error!("Failed to unsubscribe removed device: {}", err);
+                    }
+                }
+            }
+        }
+    }
+
+    fn pci_eject_fd(&self) -> Option<i32> {
+        self.device_manager
+            .pci_devices
+            .hotplug_controller
+            .as_ref()
+            .map(|controller| {
+                controller
+                    .lock()
+                    .expect("Poisoned lock")
+                    .eject_evt
+                    .as_raw_fd()
+            })
+    }
+
+    fn pvpanic_fd(&self) -> Option<i32> {
+        self.device_manager
+            .pvpanic()
+            .map(|pvpanic| pvpanic.lock().expect("Poisoned lock").panic_evt.as_raw_fd())
+    }
+
+    fn guest_panicked(&self) -> bool {
+        self.device_manager
+            .pvpanic()
+            .is_some_and(|pvpanic| pvpanic.lock().expect("Poisoned lock").panicked())
+    }
+
+    // Pauses the microVM so that it can be inspected, or stops the Vmm if this was not requested.
+    fn handle_guest_panic(&mut self) {
+        error!("The guest kernel panicked.");
+        if !self.pause_on_panic {
+            self.stop(FcExitCode::GuestPanic);
+            return;
+        }
+
+        if self.instance_info.state == VmState::Running
+            && let Err(err) = self.pause_vm()
+        {
+            error!("Failed to pause the microVM after a guest panic: {}", err);
+            self.stop(FcExitCode::GuestPanic);
+            return;
+        }
+        self.instance_info.state = VmState::Panicked;
+    }
+
     /// Signals Vmm to stop and exit.
     pub fn stop(&mut self, exit_code: FcExitCode) {
         info!("Vmm is stopping.");
@@ -850,7 +1244,7 @@ impl MutEventSubscriber for Vmm {
 
             let exit_code = 'exit_code: {
                 // Query each vcpu for their exit_code.
-                for handle in &self.vcpus_handles {
+                for handle in self.active_vcpus() {
                     // Drain all vcpu responses that are pending from this vcpu until we find an
                     // exit status.
                     for response in handle.response_receiver().try_iter() {
@@ -865,10 +1259,31 @@ impl MutEventSubscriber for Vmm {
                     }
                 }
 
-                // No CPUs exited with error status code, report "Ok"
-                FcExitCode::Ok
+                // No CPUs exited with error status code, report a guest panic that led to the
+                // reset of the guest, or "Ok"
+                if self.guest_panicked() {
+                    FcExitCode::GuestPanic
+                } else {
+                    FcExitCode::Ok
+                }
             };
             self.stop(exit_code);
+        } else if Some(source) == self.pci_eject_fd() && event_set == EventSet::IN {
+            if let Some(controller) = &self.device_manager.pci_devices.hotplug_controller {
+                let _ = controller.lock().expect("Poisoned lock").eject_evt.read();
+            }
+            if let Err(err) = self
+                .device_manager
+                .pci_devices
+                .remove_ejected_devices(&self.vm)
+            {
+                error!("Failed to remove ejected PCI devices: {}", err);
+            }
+        } else if Some(source) == self.pvpanic_fd() && event_set == EventSet::IN {
+            if let Some(pvpanic) = self.device_manager.pvpanic() {
+                let _ = pvpanic.lock().expect("Poisoned lock").panic_evt.read();
+            }
+            self.handle_guest_panic();
         } else {
             error!("Spurious EventManager event for handler: Vmm");
         }
@@ -878,5 +1293,17 @@ impl MutEventSubscriber for Vmm {
         if let Err(err) = ops.add(Events::new(&self.vcpus_exit_evt, EventSet::IN)) {
             error!("Failed to register vmm exit event: {}", err);
         }
+        if let Some(controller) = &self.device_manager.pci_devices.hotplug_controller {
+            let controller = controller.lock().expect("Poisoned lock");
+            if let Err(err) = ops.add(Events::new(&controller.eject_evt, EventSet::IN)) {
+                error!("Failed to register PCI eject event: {}", err);
+            }
+        }
+        if let Some(pvpanic) = self.device_manager.pvpanic() {
+            let pvpanic = pvpanic.lock().expect("Poisoned lock");
+            if let Err(err) = ops.add(Events::new(&pvpanic.panic_evt, EventSet::IN)) {
+                error!("Failed to register pvpanic event: {}", err);
+            }
+        }
     }
 }
@@ -0,0 +1,313 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the log of the lifecycle events of the microVM.
+//!
+//! Events are emitted from any thread through [`EVENT_LOG`] and kept in a bounded ring buffer,
+//! which clients poll or stream. Every event gets a sequence number, which clients use as a cursor
+//! to resume reading from where they stopped. The sequence numbers are saved in snapshots, so they
+//! keep increasing across snapshot restores. When a client falls behind by more than
+//! [`EVENT_LOG_CAPACITY`] events, the oldest ones are lost and the number of missed events is
+//! reported to it.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use utils::time::{ClockType, get_time_us};
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::logger::error;
+
+/// Maximum number of events kept for clients to read.
+pub const EVENT_LOG_CAPACITY: usize = 256;
+
+/// Static instance used for emitting the lifecycle events.
+pub static EVENT_LOG: EventLog = EventLog::new();
+
+/// Lifecycle events of the microVM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The microVM was booted.
+    VmStarted,
+    /// The microVM was restored from a snapshot.
+    SnapshotLoaded {
+        /// Path of the snapshot file.
+        snapshot_path: String,
+    },
+    /// A vCPU stopped because of an emulation error.
+    VcpuError {
+        /// Index of the vCPU.
+        vcpu: u8,
+        /// Description of the error.
+        error: String,
+    },
+    /// The guest requested a reset.
+    GuestReset,
+    /// The guest requested a shutdown.
+    GuestShutdown,
+    /// The guest kernel panicked.
+    GuestPanic,
+    /// The guest kernel panicked and is booting into its crash kernel.
+    GuestCrashLoaded,
+    /// The microVM was paused.
+    VmPaused,
+    /// The microVM was resumed.
+    VmResumed,
+    /// A device failed and needs to be reset by the guest driver.
+    DeviceError {
+        /// Id of the device.
+        device_id: String,
+        /// Description of the error.
+        error: String,
+    },
+    /// The balloon was deflated because the guest ran out of memory: either the balloon policy
+    /// lowered its target after an OOM kill, or the guest driver deflated it below its target.
+    BalloonOomDeflate {
+        /// Target size of the balloon in MiB, which the policy lowered or the guest deflated
+        /// below.
+        target_mib: u32,
+    },
+    /// A snapshot of the microVM was created.
+    SnapshotCreated {
+        /// Path of the snapshot file.
+        snapshot_path: String,
+    },
+    /// A core dump of the guest was created.
+    CoreDumpCreated {
+        /// Path of the core dump file.
+        core_dump_path: String,
+    },
+    /// A device was removed after the guest ejected it.
+    DeviceUnplugged {
+        /// Id of the device.
+        device_id: String,
+    },
+}
+
+/// Lifecycle event of the microVM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    /// Sequence number of the event.
+    pub seq: u64,
+    /// Wall clock time at which the event was emitted, in microseconds.
+    pub timestamp_us: u64,
+    /// The event itself.
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Events read from a given cursor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventBatch {
+    /// The events, in the order in which they were emitted.
+    pub events: Vec<Event>,
+    /// Cursor to use for reading the next events.
+    pub next_cursor: u64,
+    /// Number of events emitted after the cursor which are not available anymore.
+    pub missed: u64,
+}
+
+#[derive(Debug)]
+struct EventsInner {
+    buffer: VecDeque<Event>,
+    next_seq: u64,
+    notifier: Option<EventFd>,
+}
+
+/// Bounded log of the lifecycle events, polled or streamed by clients.
+#[derive(Debug)]
+pub struct EventLog {
+    inner: Mutex<EventsInner>,
+}
+
+impl EventLog {
+    /// Creates an empty event log.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(EventsInner {
+                buffer: VecDeque::new(),
+                next_seq: 0,
+                notifier: None,
+            }),
+        }
+    }
+
+    /// Records a new event, dropping the oldest one if the buffer is full.
+    pub fn emit(&self, kind: EventKind) {
+        let mut inner = self.inner.lock().expect("Poisoned lock");
+        let event = Event {
+            seq: inner.next_seq,
+            timestamp_us: get_time_us(ClockType::Real),
+            kind,
+        };
+        inner.next_seq += 1;
+        if inner.buffer.len() == EVENT_LOG_CAPACITY {
+            inner.buffer.pop_front();
+        }
+        inner.buffer.push_back(event);
+        if let Some(notifier) = &inner.notifier
+            && let Err(err) = notifier.write(1)
+        {
+            error!("Failed to notify of a new event: {}", err);
+        }
+    }
+
+    /// Sets the eventfd written to when a new event is emitted.
+    pub fn set_notifier(&self, notifier: EventFd) {
+        self.inner.lock().expect("Poisoned lock").notifier = Some(notifier);
+    }
+
+    /// Returns the sequence number of the next event.
+    pub fn next_seq(&self) -> u64 {
+        self.inner.lock().expect("Poisoned lock").next_seq
+    }
+
+    /// Continues the sequence numbers from the ones of a restored microVM, so that the cursors of
+    /// its clients stay valid.
+    pub fn resume_from(&self, next_seq: u64) {
+        let mut inner = self.inner.lock().expect("Poisoned lock");
+        inner.next_seq = inner.next_seq.max(next_seq);
+    }
+
+    /// Returns the events with a sequence number of at least `cursor`, or all the available
+    /// events if no cursor is given.
+    pub fn read(&self, cursor: Option<u64>) -> EventBatch {
+        let inner = self.inner.lock().expect("Poisoned lock");
+        let oldest = inner
+            .buffer
+            .front()
+            .map_or(inner.next_seq, |event| event.seq);
+        let cursor = cursor.unwrap_or(oldest).min(inner.next_seq);
+        EventBatch {
+            events: inner
+                .buffer
+                .iter()
+                .filter(|event| event.seq >= cursor)
+                .cloned()
+                .collect(),
+            next_cursor: inner.next_seq,
+            missed: oldest.saturating_sub(cursor),
+        }
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::usize_to_u64;
+
+    #[test]
+    fn test_read_with_cursor() {
+        let events = EventLog::new();
+        let batch = events.read(None);
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.next_cursor, 0);
+
+        events.emit(EventKind::VmPaused);
+        events.emit(EventKind::VmResumed);
+        let batch = events.read(None);
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.events[0].seq, 0);
+        assert_eq!(batch.events[0].kind, EventKind::VmPaused);
+        assert_eq!(batch.events[1].kind, EventKind::VmResumed);
+        assert_eq!(batch.next_cursor, 2);
+        assert_eq!(batch.missed, 0);
+
+        // Resume from the returned cursor.
+        events.emit(EventKind::GuestReset);
+        let batch = events.read(Some(batch.next_cursor));
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.events[0].seq, 2);
+        assert_eq!(batch.events[0].kind, EventKind::GuestReset);
+
+        // A cursor in the future returns nothing.
+        let batch = events.read(Some(10));
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.next_cursor, 3);
+    }
+
+    #[test]
+    fn test_read_missed() {
+        let events = EventLog::new();
+        for _ in 0..EVENT_LOG_CAPACITY + 10 {
+            events.emit(EventKind::VmPaused);
+        }
+        let batch = events.read(Some(5));
+        assert_eq!(batch.events.len(), EVENT_LOG_CAPACITY);
+        assert_eq!(batch.events[0].seq, 10);
+        assert_eq!(batch.missed, 5);
+        assert_eq!(batch.next_cursor, usize_to_u64(EVENT_LOG_CAPACITY + 10));
+    }
+
+    #[test]
+    fn test_resume_from() {
+        let events = EventLog::new();
+        events.emit(EventKind::VmPaused);
+        events.resume_from(10);
+        assert_eq!(events.next_seq(), 10);
+        // The sequence numbers never go back.
+        events.resume_from(5);
+        assert_eq!(events.next_seq(), 10);
+
+        events.emit(EventKind::SnapshotLoaded {
+            snapshot_path: "vm.snap".to_string(),
+        });
+        let batch = events.read(None);
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.events[1].seq, 10);
+        assert_eq!(batch.next_cursor, 11);
+        assert_eq!(batch.missed, 0);
+
+        // Cursors of the restored microVM still work.
+        let batch = events.read(Some(7));
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.events[0].seq, 10);
+        assert_eq!(batch.missed, 0);
+    }
+
+    #[test]
+    fn test_notifier() {
+        let events = EventLog::new();
+        let notifier = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        events.set_notifier(notifier.try_clone().unwrap());
+        events.emit(EventKind::VmPaused);
+        events.emit(EventKind::VmResumed);
+        assert_eq!(notifier.read().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let event = Event {
+            seq: 1,
+            timestamp_us: 2,
+            kind: EventKind::VcpuError {
+                vcpu: 0,
+                error: "err".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"seq":1,"timestamp_us":2,"type":"vcpu_error","vcpu":0,"error":"err"}"#
+        );
+
+        let event = Event {
+            seq: 0,
+            timestamp_us: 2,
+            kind: EventKind::SnapshotLoaded {
+                snapshot_path: "vm.snap".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"seq":0,"timestamp_us":2,"type":"snapshot_loaded","snapshot_path":"vm.snap"}"#
+        );
+    }
+}
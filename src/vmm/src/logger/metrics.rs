@@ -71,9 +71,11 @@ use serde::{Serialize, Serializer};
 use utils::time::{ClockType, get_time_ns, get_time_us};
 
 use super::FcLineWriter;
+use super::openmetrics::{self, COUNTER, GAUGE};
 use crate::devices::legacy;
 use crate::devices::virtio::balloon::metrics as balloon_metrics;
 use crate::devices::virtio::block::virtio::metrics as block_metrics;
+use crate::devices::virtio::console::metrics as console_metrics;
 use crate::devices::virtio::mem::metrics as virtio_mem_metrics;
 use crate::devices::virtio::net::metrics as net_metrics;
 use crate::devices::virtio::pmem::metrics as pmem_metrics;
@@ -154,6 +156,12 @@ impl<T: Serialize + Debug, M: Write + Send + Debug> Metrics<T, M> {
             Ok(false)
         }
     }
+
+    /// Returns the metrics in the OpenMetrics text format. Unlike [`Metrics::write`], this
+    /// reports the cumulative value of the counters and does not reset them.
+    pub fn openmetrics(&self) -> Result<String, MetricsError> {
+        openmetrics::encode(&self.app_metrics).map_err(|err| MetricsError::Serde(err.to_string()))
+    }
 }
 
 impl<T: Serialize + Debug, M: Write + Send + Debug> Deref for Metrics<T, M> {
@@ -265,6 +273,10 @@ impl Serialize for SharedIncMetric {
     /// flushing of metrics.
     /// !!! Any print of the metrics will also reset them. Use with caution !!!
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The exposition reports the monotonic counter and does not reset it.
+        if openmetrics::in_exposition() {
+            return serializer.serialize_newtype_struct(COUNTER, &self.count());
+        }
         let snapshot = self.0.load(Ordering::Relaxed);
         let res = serializer.serialize_u64(snapshot - self.1.load(Ordering::Relaxed));
 
@@ -277,6 +289,9 @@ impl Serialize for SharedIncMetric {
 
 impl Serialize for SharedStoreMetric {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if openmetrics::in_exposition() {
+            return serializer.serialize_newtype_struct(GAUGE, &self.fetch());
+        }
         serializer.serialize_u64(self.0.load(Ordering::Relaxed))
     }
 }
@@ -363,6 +378,12 @@ pub struct GetRequestsMetrics {
     pub vmm_version_count: SharedIncMetric,
     /// Number of GETs for getting hotpluggable memory status.
     pub hotplug_memory_count: SharedIncMetric,
+    /// Number of GETs for getting the lifecycle events.
+    pub events_count: SharedIncMetric,
+    /// Number of GETs for getting the metrics.
+    pub metrics_count: SharedIncMetric,
+    /// Number of GETs for getting the status of an operation.
+    pub operations_count: SharedIncMetric,
 }
 impl GetRequestsMetrics {
     /// Const default construction.
@@ -373,6 +394,9 @@ impl GetRequestsMetrics {
             mmds_count: SharedIncMetric::new(),
             vmm_version_count: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
+            events_count: SharedIncMetric::new(),
+            metrics_count: SharedIncMetric::new(),
+            operations_count: SharedIncMetric::new(),
         }
     }
 }
@@ -432,6 +456,18 @@ pub struct PutRequestsMetrics {
     pub hotplug_memory_count: SharedIncMetric,
     /// Number of failed PUTs to /hotplug/memory
     pub hotplug_memory_fails: SharedIncMetric,
+    /// Number of PUTs to /hotplug/unplug
+    pub hotplug_unplug_count: SharedIncMetric,
+    /// Number of failed PUTs to /hotplug/unplug
+    pub hotplug_unplug_fails: SharedIncMetric,
+    /// Number of PUTs submitting an operation.
+    pub operations_count: SharedIncMetric,
+    /// Number of failures in submitting an operation.
+    pub operations_fails: SharedIncMetric,
+    /// Number of PUTs replacing the full microVM configuration.
+    pub vm_config_count: SharedIncMetric,
+    /// Number of failures in replacing the full microVM configuration.
+    pub vm_config_fails: SharedIncMetric,
 }
 impl PutRequestsMetrics {
     /// Const default construction.
@@ -463,6 +499,12 @@ impl PutRequestsMetrics {
             serial_fails: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
             hotplug_memory_fails: SharedIncMetric::new(),
+            hotplug_unplug_count: SharedIncMetric::new(),
+            hotplug_unplug_fails: SharedIncMetric::new(),
+            operations_count: SharedIncMetric::new(),
+            operations_fails: SharedIncMetric::new(),
+            vm_config_count: SharedIncMetric::new(),
+            vm_config_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -490,6 +532,14 @@ pub struct PatchRequestsMetrics {
     pub hotplug_memory_count: SharedIncMetric,
     /// Number of failed PATCHes to /hotplug/memory
     pub hotplug_memory_fails: SharedIncMetric,
+    /// Number of PATCHes to /hotplug/vcpus
+    pub hotplug_vcpus_count: SharedIncMetric,
+    /// Number of failed PATCHes to /hotplug/vcpus
+    pub hotplug_vcpus_fails: SharedIncMetric,
+    /// Number of tries to cancel an operation.
+    pub operations_count: SharedIncMetric,
+    /// Number of failures in cancelling an operation.
+    pub operations_fails: SharedIncMetric,
 }
 impl PatchRequestsMetrics {
     /// Const default construction.
@@ -505,6 +555,10 @@ impl PatchRequestsMetrics {
             mmds_fails: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
             hotplug_memory_fails: SharedIncMetric::new(),
+            hotplug_vcpus_count: SharedIncMetric::new(),
+            hotplug_vcpus_fails: SharedIncMetric::new(),
+            operations_count: SharedIncMetric::new(),
+            operations_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -612,6 +666,8 @@ pub struct PerformanceMetrics {
     pub diff_create_snapshot: SharedStoreMetric,
     /// Measures the snapshot load time, at the API (user) level, in microseconds.
     pub load_snapshot: SharedStoreMetric,
+    /// Measures the core dump create time, at the API (user) level, in microseconds.
+    pub create_core_dump: SharedStoreMetric,
     /// Measures the microVM pausing duration, at the API (user) level, in microseconds.
     pub pause_vm: SharedStoreMetric,
     /// Measures the microVM resuming duration, at the API (user) level, in microseconds.
@@ -622,6 +678,8 @@ pub struct PerformanceMetrics {
     pub vmm_diff_create_snapshot: SharedStoreMetric,
     /// Measures the snapshot load time, at the VMM level, in microseconds.
     pub vmm_load_snapshot: SharedStoreMetric,
+    /// Measures the core dump create time, at the VMM level, in microseconds.
+    pub vmm_create_core_dump: SharedStoreMetric,
     /// Measures the microVM pausing duration, at the VMM level, in microseconds.
     pub vmm_pause_vm: SharedStoreMetric,
     /// Measures the microVM resuming duration, at the VMM level, in microseconds.
@@ -634,11 +692,13 @@ impl PerformanceMetrics {
             full_create_snapshot: SharedStoreMetric::new(),
             diff_create_snapshot: SharedStoreMetric::new(),
             load_snapshot: SharedStoreMetric::new(),
+            create_core_dump: SharedStoreMetric::new(),
             pause_vm: SharedStoreMetric::new(),
             resume_vm: SharedStoreMetric::new(),
             vmm_full_create_snapshot: SharedStoreMetric::new(),
             vmm_diff_create_snapshot: SharedStoreMetric::new(),
             vmm_load_snapshot: SharedStoreMetric::new(),
+            vmm_create_core_dump: SharedStoreMetric::new(),
             vmm_pause_vm: SharedStoreMetric::new(),
             vmm_resume_vm: SharedStoreMetric::new(),
         }
@@ -889,6 +949,7 @@ create_serialize_proxy!(NetMetricsSerializeProxy, net_metrics);
 create_serialize_proxy!(VhostUserMetricsSerializeProxy, vhost_user_metrics);
 create_serialize_proxy!(BalloonMetricsSerializeProxy, balloon_metrics);
 create_serialize_proxy!(EntropyMetricsSerializeProxy, entropy_metrics);
+create_serialize_proxy!(ConsoleMetricsSerializeProxy, console_metrics);
 create_serialize_proxy!(VsockMetricsSerializeProxy, vsock_metrics);
 create_serialize_proxy!(PmemMetricsSerializeProxy, pmem_metrics);
 create_serialize_proxy!(LegacyDevMetricsSerializeProxy, legacy);
@@ -941,6 +1002,9 @@ pub struct FirecrackerMetrics {
     /// Metrics related to virtio-rng entropy device.
     pub entropy_ser: EntropyMetricsSerializeProxy,
     #[serde(flatten)]
+    /// Metrics related to virtio-console device.
+    pub console_ser: ConsoleMetricsSerializeProxy,
+    #[serde(flatten)]
     /// Metrics related to virtio-pmem entropy device.
     pub pmem_ser: PmemMetricsSerializeProxy,
     #[serde(flatten)]
@@ -975,6 +1039,7 @@ impl FirecrackerMetrics {
             signals: SignalMetrics::new(),
             vsock_ser: VsockMetricsSerializeProxy {},
             entropy_ser: EntropyMetricsSerializeProxy {},
+            console_ser: ConsoleMetricsSerializeProxy {},
             pmem_ser: PmemMetricsSerializeProxy {},
             vhost_user_ser: VhostUserMetricsSerializeProxy {},
             interrupts: InterruptMetrics::new(),
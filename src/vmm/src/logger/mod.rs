@@ -4,9 +4,13 @@
 //! Crate that implements Firecracker specific functionality as far as logging and metrics
 //! collecting.
 
+mod events;
 mod logging;
 mod metrics;
+mod openmetrics;
+mod spans;
 
+pub use events::{EVENT_LOG, EVENT_LOG_CAPACITY, Event, EventBatch, EventKind, EventLog};
 pub use log::{Level, debug, error, info, log_enabled, trace, warn};
 pub use logging::{
     DEFAULT_INSTANCE_ID, DEFAULT_LEVEL, INSTANCE_ID, LOGGER, LevelFilter, LevelFilterFromStrError,
@@ -16,6 +20,8 @@ pub use metrics::{
     IncMetric, LatencyAggregateMetrics, METRICS, MetricsError, ProcessTimeReporter,
     SharedIncMetric, SharedStoreMetric, StoreMetric,
 };
+pub use openmetrics::{OPENMETRICS_CONTENT_TYPE, OpenMetricsError};
+pub use spans::{SPANS, Span, Spans, SpansError, TraceFormat};
 use utils::time::{ClockType, get_time_us};
 
 /// Alias for `std::io::LineWriter<std::fs::File>`.
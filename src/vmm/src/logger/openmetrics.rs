@@ -0,0 +1,623 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Exposes the metrics in the OpenMetrics text format.
+//!
+//! The metrics structures are walked with a dedicated `serde` serializer. While it runs,
+//! `SharedIncMetric` values serialize as monotonic counters, without resetting the deltas flushed
+//! as JSON, and `SharedStoreMetric` values serialize as gauges. Device metrics are exposed with a
+//! `device` label holding the device id, while the aggregates over all the devices are left out
+//! since they can be computed by the collectors.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::devices::virtio::balloon::BALLOON_DEV_ID;
+use crate::devices::virtio::console::device::CONSOLE_DEV_ID;
+use crate::devices::virtio::mem::VIRTIO_MEM_DEV_ID;
+use crate::devices::virtio::rng::device::ENTROPY_DEV_ID;
+use crate::devices::virtio::vsock::VSOCK_DEV_ID;
+
+/// Content type of the exposition.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Name given to the newtype struct wrapping the values of the counters.
+pub(crate) const COUNTER: &str = "counter";
+/// Name given to the newtype struct wrapping the values of the gauges.
+pub(crate) const GAUGE: &str = "gauge";
+
+const NAME_PREFIX: &str = "firecracker";
+// Prefixes of the top-level keys holding per-device metrics, along with the name of the metric
+// family. The longest prefixes come first.
+const DEVICE_PREFIXES: [(&str, &str); 4] = [
+    ("vhost_user_block_", "vhost_user_block"),
+    ("block_", "block"),
+    ("net_", "net"),
+    ("pmem_", "pmem"),
+];
+// Top-level keys holding the metrics of the devices which a microVM has at most one of, along
+// with the id of the device.
+const SINGLE_DEVICE_KEYS: [(&str, &str); 5] = [
+    ("balloon", BALLOON_DEV_ID),
+    ("console", CONSOLE_DEV_ID),
+    ("entropy", ENTROPY_DEV_ID),
+    ("memory_hotplug", VIRTIO_MEM_DEV_ID),
+    ("vsock", VSOCK_DEV_ID),
+];
+// Top-level keys which are not exposed.
+const SKIPPED_KEYS: [&str; 4] = ["utc_timestamp_ms", "block", "net", "pmem"];
+
+thread_local! {
+    static EXPOSITION: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns whether the metrics are being serialized for the OpenMetrics exposition on the
+/// current thread.
+pub(crate) fn in_exposition() -> bool {
+    EXPOSITION.with(Cell::get)
+}
+
+/// Errors associated with the OpenMetrics exposition.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum OpenMetricsError {
+    /// Unsupported value for metric {0}
+    Unsupported(String),
+    /// {0}
+    Custom(String),
+}
+
+impl ser::Error for OpenMetricsError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    kind: &'static str,
+    samples: Vec<(Option<String>, u64)>,
+}
+
+#[derive(Debug, Default)]
+struct Collector {
+    path: Vec<String>,
+    families: BTreeMap<String, Family>,
+    // Kind of the metric being serialized, set by the newtype struct wrapping its value.
+    kind: Option<&'static str>,
+}
+
+impl Collector {
+    fn record(&mut self, value: u64) {
+        let kind = self.kind.take().unwrap_or(GAUGE);
+        let Some((top, rest)) = self.path.split_first() else {
+            return;
+        };
+        if SKIPPED_KEYS.contains(&top.as_str()) {
+            return;
+        }
+        let (family, device) = DEVICE_PREFIXES
+            .iter()
+            .find_map(|(prefix, family)| {
+                top.strip_prefix(prefix)
+                    .map(|device| (*family, Some(device.to_string())))
+            })
+            .or_else(|| {
+                SINGLE_DEVICE_KEYS
+                    .iter()
+                    .find(|(key, _)| *key == top.as_str())
+                    .map(|(key, device)| (*key, Some(device.to_string())))
+            })
+            .unwrap_or((top.as_str(), None));
+
+        let mut name = format!("{NAME_PREFIX}_{family}");
+        for component in rest {
+            name.push('_');
+            name.push_str(component);
+        }
+        self.families
+            .entry(name)
+            .or_insert_with(|| Family {
+                kind,
+                samples: Vec::new(),
+            })
+            .samples
+            .push((device, value));
+    }
+
+    fn encode(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in self.families.iter() {
+            let suffix = if family.kind == COUNTER { "_total" } else { "" };
+            // Writing to a String cannot fail.
+            let _ = writeln!(out, "# TYPE {name} {}", family.kind);
+            for (device, value) in family.samples.iter() {
+                match device {
+                    Some(device) => {
+                        let _ = writeln!(
+                            out,
+                            "{name}{suffix}{{device=\"{}\"}} {value}",
+                            escape_label(device)
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(out, "{name}{suffix} {value}");
+                    }
+                }
+            }
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+
+    fn unsupported(&self) -> OpenMetricsError {
+        OpenMetricsError::Unsupported(self.path.join("."))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Encodes the given metrics in the OpenMetrics text format.
+pub fn encode<T: Serialize>(metrics: &T) -> Result<String, OpenMetricsError> {
+    let mut collector = Collector::default();
+    EXPOSITION.with(|exposition| exposition.set(true));
+    let res = metrics.serialize(&mut collector);
+    EXPOSITION.with(|exposition| exposition.set(false));
+    res.map(|()| collector.encode())
+}
+
+impl ser::Serializer for &mut Collector {
+    type Ok = ();
+    type Error = OpenMetricsError;
+    type SerializeSeq = Impossible<(), OpenMetricsError>;
+    type SerializeTuple = Impossible<(), OpenMetricsError>;
+    type SerializeTupleStruct = Impossible<(), OpenMetricsError>;
+    type SerializeTupleVariant = Impossible<(), OpenMetricsError>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), OpenMetricsError>;
+
+    fn serialize_u64(self, v: u64) -> Result<(), OpenMetricsError> {
+        self.record(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), OpenMetricsError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), OpenMetricsError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), OpenMetricsError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), OpenMetricsError> {
+        let v = u64::try_from(v).map_err(|_| self.unsupported())?;
+        self.serialize_u64(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), OpenMetricsError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), OpenMetricsError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), OpenMetricsError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    // Only the integer values are exposed.
+    fn serialize_bool(self, _v: bool) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), OpenMetricsError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), OpenMetricsError> {
+        match name {
+            COUNTER => self.kind = Some(COUNTER),
+            GAUGE => self.kind = Some(GAUGE),
+            _ => (),
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, OpenMetricsError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, OpenMetricsError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, OpenMetricsError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, OpenMetricsError> {
+        Err(self.unsupported())
+    }
+}
+
+impl ser::SerializeStruct for &mut Collector {
+    type Ok = ();
+    type Error = OpenMetricsError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), OpenMetricsError> {
+        self.path.push(key.to_string());
+        let res = value.serialize(&mut **self);
+        self.path.pop();
+        res
+    }
+
+    fn end(self) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Collector {
+    type Ok = ();
+    type Error = OpenMetricsError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), OpenMetricsError> {
+        let key = key
+            .serialize(KeySerializer)
+            .map_err(|_| self.unsupported())?;
+        self.path.push(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), OpenMetricsError> {
+        let res = value.serialize(&mut **self);
+        self.path.pop();
+        res
+    }
+
+    fn end(self) -> Result<(), OpenMetricsError> {
+        Ok(())
+    }
+}
+
+// Serializer for the keys of the maps, which are always strings.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = OpenMetricsError;
+    type SerializeSeq = Impossible<String, OpenMetricsError>;
+    type SerializeTuple = Impossible<String, OpenMetricsError>;
+    type SerializeTupleStruct = Impossible<String, OpenMetricsError>;
+    type SerializeTupleVariant = Impossible<String, OpenMetricsError>;
+    type SerializeMap = Impossible<String, OpenMetricsError>;
+    type SerializeStruct = Impossible<String, OpenMetricsError>;
+    type SerializeStructVariant = Impossible<String, OpenMetricsError>;
+
+    fn serialize_str(self, v: &str) -> Result<String, OpenMetricsError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, OpenMetricsError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_none(self) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_unit(self) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, OpenMetricsError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, OpenMetricsError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, OpenMetricsError> {
+        Err(ser::Error::custom("map keys must be strings"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+    use crate::logger::{IncMetric, SharedIncMetric, SharedStoreMetric, StoreMetric};
+
+    #[derive(Debug, Default, Serialize)]
+    struct DeviceMetrics {
+        read_count: SharedIncMetric,
+        queue_size: SharedStoreMetric,
+    }
+
+    #[derive(Debug, Default, Serialize)]
+    struct TestMetrics {
+        utc_timestamp_ms: u64,
+        balloon: DeviceMetrics,
+        vcpu: DeviceMetrics,
+        #[serde(flatten)]
+        block: BTreeMap<String, DeviceMetrics>,
+    }
+
+    #[test]
+    fn test_encode() {
+        let metrics = TestMetrics::default();
+        metrics.vcpu.read_count.add(5);
+        metrics.vcpu.queue_size.store(7);
+        metrics.balloon.read_count.add(2);
+        let mut block = BTreeMap::new();
+        block.insert("block".to_string(), DeviceMetrics::default());
+        block.insert("block_root\"fs".to_string(), DeviceMetrics::default());
+        block.insert("block_data".to_string(), DeviceMetrics::default());
+        block["block_root\"fs"].read_count.add(3);
+        block["block_data"].read_count.add(1);
+        let metrics = TestMetrics { block, ..metrics };
+
+        let expected = concat!(
+            "# TYPE firecracker_balloon_queue_size gauge\n",
+            "firecracker_balloon_queue_size{device=\"balloon\"} 0\n",
+            "# TYPE firecracker_balloon_read_count counter\n",
+            "firecracker_balloon_read_count_total{device=\"balloon\"} 2\n",
+            "# TYPE firecracker_block_queue_size gauge\n",
+            "firecracker_block_queue_size{device=\"data\"} 0\n",
+            "firecracker_block_queue_size{device=\"root\\\"fs\"} 0\n",
+            "# TYPE firecracker_block_read_count counter\n",
+            "firecracker_block_read_count_total{device=\"data\"} 1\n",
+            "firecracker_block_read_count_total{device=\"root\\\"fs\"} 3\n",
+            "# TYPE firecracker_vcpu_queue_size gauge\n",
+            "firecracker_vcpu_queue_size 7\n",
+            "# TYPE firecracker_vcpu_read_count counter\n",
+            "firecracker_vcpu_read_count_total 5\n",
+            "# EOF\n",
+        );
+        assert_eq!(encode(&metrics).unwrap(), expected);
+        // The counters are not reset by the exposition.
+        assert_eq!(encode(&metrics).unwrap(), expected);
+        assert_eq!(metrics.vcpu.read_count.fetch_diff(), 5);
+        assert!(!in_exposition());
+
+        // The JSON flush still reports the deltas.
+        assert_eq!(
+            serde_json::to_value(&metrics.vcpu).unwrap(),
+            serde_json::json!({"read_count": 5, "queue_size": 7})
+        );
+        assert_eq!(metrics.vcpu.read_count.fetch_diff(), 0);
+        assert!(
+            encode(&metrics)
+                .unwrap()
+                .contains("firecracker_vcpu_read_count_total 5\n")
+        );
+    }
+}
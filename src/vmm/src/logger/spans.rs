@@ -0,0 +1,494 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Records spans covering the handling of API requests and the phases of booting, restoring and
+//! snapshotting the microVM.
+//!
+//! A span starts with [`Spans::start`] and ends when the returned [`Span`] is dropped. A span
+//! started while another one is in progress on the same thread becomes its child; a span started
+//! on a thread with no span in progress becomes the child of the span which was
+//! [propagated](Span::propagate), if any. Spans are written to the trace file as they end, either
+//! as Chrome trace events or as OTLP JSON lines. Until [`Spans::init`] is called, starting a span
+//! only costs an atomic load.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde_json::{Value, json};
+use utils::time::{ClockType, get_time_ns};
+
+use super::FcLineWriter;
+use crate::utils::open_file_nonblock;
+
+/// Static instance used for recording the spans.
+pub static SPANS: Spans = Spans::new();
+
+/// Source of the numeric IDs of the threads recording spans.
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// IDs of the spans in progress on the current thread, innermost last.
+    static SPAN_STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    /// Numeric ID of the current thread in the trace.
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Formats of the trace file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Chrome trace events in the JSON array format, as read by Perfetto and `chrome://tracing`.
+    Chrome,
+    /// One OTLP JSON `ExportTraceServiceRequest` per line, as read by the OpenTelemetry
+    /// collector file receiver.
+    Otlp,
+}
+
+impl FromStr for TraceFormat {
+    type Err = SpansError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chrome" => Ok(TraceFormat::Chrome),
+            "otlp" => Ok(TraceFormat::Otlp),
+            _ => Err(SpansError::InvalidFormat(s.to_string())),
+        }
+    }
+}
+
+/// Errors related to span tracing.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum SpansError {
+    /// Invalid trace format: {0}. Possible values: [chrome, otlp]
+    InvalidFormat(String),
+    /// Span tracing is already initialized.
+    AlreadyInitialized,
+    /// Failed to open the trace file: {0}
+    OpenFile(std::io::Error),
+    /// Failed to write the trace file: {0}
+    WriteFile(std::io::Error),
+    /// Failed to generate the trace ID.
+    TraceId,
+}
+
+/// Destination of the finished spans.
+#[derive(Debug)]
+struct SpanWriter {
+    writer: FcLineWriter,
+    format: TraceFormat,
+    /// ID shared by all the spans of the process, as 32 hex digits.
+    trace_id: String,
+    /// Threads whose name was already written, for the Chrome format.
+    named_threads: HashSet<u64>,
+}
+
+/// Span that ended, ready to be written.
+#[derive(Debug)]
+struct FinishedSpan {
+    id: u64,
+    parent_id: Option<u64>,
+    name: &'static str,
+    thread_id: u64,
+    thread_name: String,
+    start_ns: u64,
+    duration_ns: u64,
+    attributes: Vec<(&'static str, String)>,
+}
+
+/// Recorder of the spans.
+#[derive(Debug)]
+pub struct Spans {
+    enabled: AtomicBool,
+    next_span_id: AtomicU64,
+    /// Parent of the spans started on threads with no span in progress, 0 if none.
+    remote_parent: AtomicU64,
+    writer: Mutex<Option<SpanWriter>>,
+}
+
+impl Spans {
+    /// Creates a disabled recorder.
+    pub const fn new() -> Self {
+        Spans {
+            enabled: AtomicBool::new(false),
+            next_span_id: AtomicU64::new(1),
+            remote_parent: AtomicU64::new(0),
+            writer: Mutex::new(None),
+        }
+    }
+
+    /// Starts writing the spans to the file at `path`.
+    pub fn init(&self, path: &Path, format: TraceFormat) -> Result<(), SpansError> {
+        let mut guard = self.writer.lock().expect("Poisoned lock");
+        if guard.is_some() {
+            return Err(SpansError::AlreadyInitialized);
+        }
+
+        let mut trace_id = [0u8; 16];
+        aws_lc_rs::rand::fill(&mut trace_id).map_err(|_| SpansError::TraceId)?;
+        let file = open_file_nonblock(path).map_err(SpansError::OpenFile)?;
+        let mut writer = FcLineWriter::new(file);
+        if format == TraceFormat::Chrome {
+            // The closing bracket is optional, which lets events be appended until the process
+            // exits.
+            writer.write_all(b"[\n").map_err(SpansError::WriteFile)?;
+        }
+
+        *guard = Some(SpanWriter {
+            writer,
+            format,
+            trace_id: hex(&trace_id),
+            named_threads: HashSet::new(),
+        });
+        self.enabled.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns true if the spans are recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Starts a span, which ends when the returned value is dropped.
+    pub fn start(&'static self, name: &'static str) -> Span {
+        if !self.is_enabled() {
+            return Span { inner: None };
+        }
+
+        let id = self.next_span_id.fetch_add(1, Ordering::Relaxed);
+        let parent_id = SPAN_STACK.with_borrow_mut(|stack| {
+            let parent_id = stack.last().copied().or_else(|| {
+                match self.remote_parent.load(Ordering::Acquire) {
+                    0 => None,
+                    remote_parent => Some(remote_parent),
+                }
+            });
+            stack.push(id);
+            parent_id
+        });
+
+        Span {
+            inner: Some(SpanInner {
+                spans: self,
+                id,
+                parent_id,
+                name,
+                start_ns: get_time_ns(ClockType::Real),
+                start_monotonic_ns: get_time_ns(ClockType::Monotonic),
+                attributes: Vec::new(),
+            }),
+        }
+    }
+
+    fn write(&self, span: FinishedSpan) {
+        let mut guard = self.writer.lock().expect("Poisoned lock");
+        let Some(span_writer) = guard.as_mut() else {
+            return;
+        };
+
+        let mut lines = Vec::with_capacity(2);
+        match span_writer.format {
+            TraceFormat::Chrome => {
+                if span_writer.named_threads.insert(span.thread_id) {
+                    lines.push(chrome_thread_name(&span));
+                }
+                lines.push(chrome_event(&span));
+            }
+            TraceFormat::Otlp => lines.push(otlp_request(&span, &span_writer.trace_id)),
+        }
+
+        for line in lines {
+            let separator = match span_writer.format {
+                TraceFormat::Chrome => ",\n",
+                TraceFormat::Otlp => "\n",
+            };
+            let result = span_writer
+                .writer
+                .write_all(format!("{line}{separator}").as_bytes());
+            if let Err(err) = result {
+                // Do not let a full fifo block or fail the traced operation.
+                log::warn!("Failed to write span {}: {err}", span.name);
+                return;
+            }
+        }
+    }
+}
+
+impl Default for Spans {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+struct SpanInner {
+    spans: &'static Spans,
+    id: u64,
+    parent_id: Option<u64>,
+    name: &'static str,
+    start_ns: u64,
+    start_monotonic_ns: u64,
+    attributes: Vec<(&'static str, String)>,
+}
+
+/// Span in progress, which ends when dropped.
+#[derive(Debug)]
+#[must_use = "the span ends when dropped"]
+pub struct Span {
+    /// `None` when span tracing is disabled.
+    inner: Option<SpanInner>,
+}
+
+impl Span {
+    /// Adds an attribute to the span.
+    pub fn set_attribute(&mut self, key: &'static str, value: impl ToString) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.attributes.push((key, value.to_string()));
+        }
+    }
+
+    /// Makes this span the parent of the spans started on threads with no span in progress,
+    /// until it ends.
+    pub fn propagate(&self) {
+        if let Some(inner) = self.inner.as_ref() {
+            inner.spans.remote_parent.store(inner.id, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(inner) = self.inner.take() else {
+            return;
+        };
+
+        let duration_ns =
+            get_time_ns(ClockType::Monotonic).saturating_sub(inner.start_monotonic_ns);
+        SPAN_STACK.with_borrow_mut(|stack| {
+            if let Some(pos) = stack.iter().rposition(|id| *id == inner.id) {
+                stack.remove(pos);
+            }
+        });
+        let _ = inner.spans.remote_parent.compare_exchange(
+            inner.id,
+            0,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+
+        let thread = std::thread::current();
+        inner.spans.write(FinishedSpan {
+            id: inner.id,
+            parent_id: inner.parent_id,
+            name: inner.name,
+            thread_id: THREAD_ID.with(|id| *id),
+            thread_name: thread.name().unwrap_or("-").to_string(),
+            start_ns: inner.start_ns,
+            duration_ns,
+            attributes: inner.attributes,
+        });
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, byte| {
+        let _ = write!(s, "{byte:02x}");
+        s
+    })
+}
+
+fn ns_to_us(ns: u64) -> f64 {
+    ns as f64 / 1000.0
+}
+
+fn chrome_thread_name(span: &FinishedSpan) -> Value {
+    json!({
+        "name": "thread_name",
+        "ph": "M",
+        "pid": std::process::id(),
+        "tid": span.thread_id,
+        "args": { "name": span.thread_name },
+    })
+}
+
+fn chrome_event(span: &FinishedSpan) -> Value {
+    let mut args = serde_json::Map::new();
+    args.insert("span_id".to_string(), json!(span.id));
+    if let Some(parent_id) = span.parent_id {
+        args.insert("parent_id".to_string(), json!(parent_id));
+    }
+    for (key, value) in &span.attributes {
+        args.insert((*key).to_string(), json!(value));
+    }
+
+    json!({
+        "name": span.name,
+        "cat": "firecracker",
+        "ph": "X",
+        "ts": ns_to_us(span.start_ns),
+        "dur": ns_to_us(span.duration_ns),
+        "pid": std::process::id(),
+        "tid": span.thread_id,
+        "args": args,
+    })
+}
+
+fn otlp_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn otlp_request(span: &FinishedSpan, trace_id: &str) -> Value {
+    let mut attributes = vec![
+        otlp_attribute("thread.name", &span.thread_name),
+        json!({ "key": "thread.id", "value": { "intValue": span.thread_id.to_string() } }),
+    ];
+    attributes.extend(
+        span.attributes
+            .iter()
+            .map(|(key, value)| otlp_attribute(key, value)),
+    );
+
+    let mut otlp_span = json!({
+        "traceId": trace_id,
+        "spanId": format!("{:016x}", span.id),
+        "name": span.name,
+        // SPAN_KIND_INTERNAL
+        "kind": 1,
+        "startTimeUnixNano": span.start_ns.to_string(),
+        "endTimeUnixNano": (span.start_ns + span.duration_ns).to_string(),
+        "attributes": attributes,
+    });
+    if let Some(parent_id) = span.parent_id {
+        otlp_span["parentSpanId"] = json!(format!("{parent_id:016x}"));
+    }
+
+    let instance_id = super::INSTANCE_ID
+        .get()
+        .map(String::as_str)
+        .unwrap_or(super::DEFAULT_INSTANCE_ID);
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    otlp_attribute("service.name", "firecracker"),
+                    otlp_attribute("service.instance.id", instance_id),
+                    {
+                        "key": "process.pid",
+                        "value": { "intValue": std::process::id().to_string() },
+                    },
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "firecracker" },
+                "spans": [otlp_span],
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn read_lines(file: &TempFile) -> Vec<Value> {
+        std::fs::read_to_string(file.as_path())
+            .unwrap()
+            .lines()
+            .filter(|line| *line != "[")
+            .map(|line| serde_json::from_str(line.trim_end_matches(',')).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_format_from_str() {
+        assert_eq!(
+            "chrome".parse::<TraceFormat>().unwrap(),
+            TraceFormat::Chrome
+        );
+        assert_eq!("otlp".parse::<TraceFormat>().unwrap(), TraceFormat::Otlp);
+        "json".parse::<TraceFormat>().unwrap_err();
+    }
+
+    #[test]
+    fn test_disabled() {
+        let spans: &'static Spans = Box::leak(Box::new(Spans::new()));
+        let mut span = spans.start("disabled");
+        span.set_attribute("key", "value");
+        assert!(span.inner.is_none());
+        SPAN_STACK.with_borrow(|stack| assert!(stack.is_empty()));
+    }
+
+    #[test]
+    fn test_chrome_format() {
+        let spans: &'static Spans = Box::leak(Box::new(Spans::new()));
+        let file = TempFile::new().unwrap();
+        spans.init(file.as_path(), TraceFormat::Chrome).unwrap();
+        spans.init(file.as_path(), TraceFormat::Chrome).unwrap_err();
+
+        {
+            let mut parent = spans.start("parent");
+            parent.set_attribute("http.method", "PUT");
+            let _child = spans.start("child");
+        }
+
+        let content = std::fs::read_to_string(file.as_path()).unwrap();
+        assert!(content.starts_with("[\n"));
+        let events = read_lines(&file);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["ph"], "M");
+        assert_eq!(events[1]["name"], "child");
+        assert_eq!(events[1]["ph"], "X");
+        assert_eq!(events[2]["name"], "parent");
+        assert_eq!(events[2]["args"]["http.method"], "PUT");
+        assert_eq!(events[1]["args"]["parent_id"], events[2]["args"]["span_id"]);
+        assert!(events[2]["args"].get("parent_id").is_none());
+        assert!(events[1]["dur"].as_f64().unwrap() <= events[2]["dur"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_otlp_format() {
+        let spans: &'static Spans = Box::leak(Box::new(Spans::new()));
+        let file = TempFile::new().unwrap();
+        spans.init(file.as_path(), TraceFormat::Otlp).unwrap();
+
+        {
+            let parent = spans.start("api_request");
+            parent.propagate();
+            // Spans of other threads with no span in progress become children of the
+            // propagated span.
+            std::thread::spawn(move || drop(spans.start("remote")))
+                .join()
+                .unwrap();
+        }
+        // The propagated span ended, so new root spans have no parent.
+        std::thread::spawn(move || drop(spans.start("root")))
+            .join()
+            .unwrap();
+
+        let requests = read_lines(&file);
+        assert_eq!(requests.len(), 3);
+        let otlp_span =
+            |i: usize| requests[i]["resourceSpans"][0]["scopeSpans"][0]["spans"][0].clone();
+        let (remote, parent, root) = (otlp_span(0), otlp_span(1), otlp_span(2));
+        assert_eq!(remote["name"], "remote");
+        assert_eq!(remote["parentSpanId"], parent["spanId"]);
+        assert_eq!(remote["traceId"], parent["traceId"]);
+        assert_eq!(parent["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(parent["spanId"].as_str().unwrap().len(), 16);
+        assert!(parent.get("parentSpanId").is_none());
+        assert!(root.get("parentSpanId").is_none());
+        let start: u64 = parent["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let end: u64 = parent["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        assert!(start <= end);
+    }
+}
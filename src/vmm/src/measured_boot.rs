@@ -0,0 +1,292 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Measured boot log of a microVM.
+//!
+//! The log holds the SHA-256 digests of the components the microVM was booted with, so that
+//! tenants can attest exactly what was booted. It is only kept when `measured_boot` is set in the
+//! boot source. The files are measured as they are on the host, so that the digests can be checked
+//! with `sha256sum`. Each file is read once, into a sealed in-memory copy from which the guest is
+//! loaded, so that a file changed on the host after it was measured does not change what is
+//! booted.
+
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+use aws_lc_rs::digest::{Context, Digest, SHA256, digest};
+use linux_loader::cmdline::Cmdline;
+use memfd::{FileSeal, MemfdOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::cpu_config::templates::CustomCpuTemplate;
+use crate::utils::usize_to_u64;
+use crate::vmm_config::boot_source::BootConfig;
+
+/// Size of the chunks in which the files are read to be measured.
+const READ_CHUNK_SIZE: usize = 1 << 20;
+
+/// Errors associated with measuring the boot components.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum MeasuredBootError {
+    /// Cannot read the kernel image: {0}
+    ReadKernel(io::Error),
+    /// Cannot read the initrd: {0}
+    ReadInitrd(io::Error),
+    /// Invalid kernel command line: {0}
+    Cmdline(linux_loader::cmdline::Error),
+    /// Cannot serialize the CPU template: {0}
+    CpuTemplate(serde_json::Error),
+}
+
+/// A component of the microVM measured at boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootComponent {
+    /// The kernel image file.
+    Kernel,
+    /// The firmware file, when a firmware is booted instead of a kernel.
+    Firmware,
+    /// The initrd file.
+    Initrd,
+    /// The kernel command line passed to the guest, without the trailing null character.
+    Cmdline,
+    /// The JSON serialization of the CPU template applied to the vCPUs.
+    CpuTemplate,
+}
+
+/// An event of the measured boot log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootMeasurement {
+    /// The measured component.
+    pub component: BootComponent,
+    /// SHA-256 digest of the component, in lowercase hexadecimal.
+    pub sha256: String,
+}
+
+impl BootMeasurement {
+    /// Measures `data`.
+    pub fn new(component: BootComponent, data: &[u8]) -> Self {
+        Self::from_digest(component, digest(&SHA256, data))
+    }
+
+    /// Measures the whole content of `file`, without changing its offset. The content is read
+    /// once, and returned as a sealed in-memory file holding exactly the measured bytes.
+    pub fn from_file(component: BootComponent, file: &File) -> io::Result<(Self, File)> {
+        let copy = MemfdOptions::default()
+            .allow_sealing(true)
+            .create("boot_component")
+            .map_err(io::Error::other)?;
+        let mut context = Context::new(&SHA256);
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+        let mut offset = 0;
+        loop {
+            let count = file.read_at(&mut buf, offset)?;
+            if count == 0 {
+                break;
+            }
+            context.update(&buf[..count]);
+            copy.as_file().write_all_at(&buf[..count], offset)?;
+            offset += usize_to_u64(count);
+        }
+        for seal in [
+            FileSeal::SealShrink,
+            FileSeal::SealGrow,
+            FileSeal::SealWrite,
+            FileSeal::SealSeal,
+        ] {
+            copy.add_seal(seal).map_err(io::Error::other)?;
+        }
+        Ok((
+            Self::from_digest(component, context.finish()),
+            copy.into_file(),
+        ))
+    }
+
+    fn from_digest(component: BootComponent, digest: Digest) -> Self {
+        let sha256 = digest.as_ref().iter().fold(String::new(), |mut acc, byte| {
+            write!(&mut acc, "{byte:02x}").unwrap();
+            acc
+        });
+        Self { component, sha256 }
+    }
+}
+
+/// Measures the kernel or the firmware, and the initrd, of a microVM. The returned boot
+/// configuration loads the guest from the measured copies of these files. The files are neither
+/// read nor copied, and `None` is returned, when the microVM is not booted with measured boot.
+pub fn measure_boot_files(
+    boot_config: &BootConfig,
+) -> Result<Option<(BootConfig, Vec<BootMeasurement>)>, MeasuredBootError> {
+    if !boot_config.measured {
+        return Ok(None);
+    }
+    let mut measurements = Vec::new();
+
+    let component = if boot_config.firmware {
+        BootComponent::Firmware
+    } else {
+        BootComponent::Kernel
+    };
+    let (measurement, kernel_file) =
+        BootMeasurement::from_file(component, &boot_config.kernel_file)
+            .map_err(MeasuredBootError::ReadKernel)?;
+    measurements.push(measurement);
+    let initrd_file = match &boot_config.initrd_file {
+        Some(initrd_file) => {
+            let (measurement, initrd_file) =
+                BootMeasurement::from_file(BootComponent::Initrd, initrd_file)
+                    .map_err(MeasuredBootError::ReadInitrd)?;
+            measurements.push(measurement);
+            Some(initrd_file)
+        }
+        None => None,
+    };
+
+    let boot_config = BootConfig {
+        cmdline: boot_config.cmdline.clone(),
+        kernel_file,
+        firmware: boot_config.firmware,
+        initrd_file,
+        measured: true,
+    };
+    Ok(Some((boot_config, measurements)))
+}
+
+/// Measures the kernel command line and the CPU template, once the command line holds the
+/// parameters of the devices, after the files measured by [`measure_boot_files`].
+pub fn measure_boot_config(
+    measurements: &mut Vec<BootMeasurement>,
+    cmdline: &Cmdline,
+    cpu_template: &CustomCpuTemplate,
+) -> Result<(), MeasuredBootError> {
+    let cmdline = cmdline.as_cstring().map_err(MeasuredBootError::Cmdline)?;
+    measurements.push(BootMeasurement::new(
+        BootComponent::Cmdline,
+        cmdline.as_bytes(),
+    ));
+    let template = serde_json::to_vec(cpu_template).map_err(MeasuredBootError::CpuTemplate)?;
+    measurements.push(BootMeasurement::new(BootComponent::CpuTemplate, &template));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    // SHA-256 digest of "hello".
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    // Reads the whole content of `file`.
+    fn read_file(file: &File) -> Vec<u8> {
+        let mut data = vec![0u8; usize::try_from(file.metadata().unwrap().len()).unwrap()];
+        file.read_exact_at(&mut data, 0).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_measurement() {
+        let measurement = BootMeasurement::new(BootComponent::Cmdline, b"hello");
+        assert_eq!(measurement.sha256, HELLO_SHA256);
+
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(b"hello").unwrap();
+        let (measurement, copy) =
+            BootMeasurement::from_file(BootComponent::Kernel, file.as_file()).unwrap();
+        assert_eq!(measurement.sha256, HELLO_SHA256);
+        assert_eq!(read_file(&copy), b"hello");
+
+        // The copy keeps the measured content when the file changes on the host.
+        file.as_file().write_all_at(b"world", 0).unwrap();
+        assert_eq!(read_file(&copy), b"hello");
+
+        // The copy is sealed, so that it cannot be changed either.
+        copy.write_all_at(b"world", 0).unwrap_err();
+        copy.set_len(0).unwrap_err();
+        let seals = memfd::Memfd::try_from_file(copy).unwrap().seals().unwrap();
+        assert!(seals.contains(&FileSeal::SealWrite));
+        assert!(seals.contains(&FileSeal::SealSeal));
+
+        // A file larger than the chunks in which it is read.
+        let data = vec![0xaau8; READ_CHUNK_SIZE + 1];
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(&data).unwrap();
+        let (measurement, copy) =
+            BootMeasurement::from_file(BootComponent::Initrd, file.as_file()).unwrap();
+        assert_eq!(
+            measurement,
+            BootMeasurement::new(BootComponent::Initrd, &data)
+        );
+        assert_eq!(read_file(&copy), data);
+    }
+
+    #[test]
+    fn test_measure_boot() {
+        let kernel = TempFile::new().unwrap();
+        kernel.as_file().write_all(b"hello").unwrap();
+        let initrd = TempFile::new().unwrap();
+        let boot_config = BootConfig {
+            cmdline: Cmdline::try_from("console=ttyS0", 4096).unwrap(),
+            kernel_file: kernel.into_file(),
+            firmware: false,
+            initrd_file: Some(initrd.into_file()),
+            measured: true,
+        };
+        let cpu_template = CustomCpuTemplate::default();
+
+        let (measured_config, mut measurements) =
+            measure_boot_files(&boot_config).unwrap().unwrap();
+        assert_eq!(read_file(&measured_config.kernel_file), b"hello");
+        assert!(read_file(measured_config.initrd_file.as_ref().unwrap()).is_empty());
+        assert!(!measured_config.firmware);
+        measure_boot_config(&mut measurements, &boot_config.cmdline, &cpu_template).unwrap();
+        assert_eq!(
+            measurements,
+            [
+                BootMeasurement::new(BootComponent::Kernel, b"hello"),
+                BootMeasurement::new(BootComponent::Initrd, b""),
+                BootMeasurement::new(BootComponent::Cmdline, b"console=ttyS0"),
+                BootMeasurement::new(
+                    BootComponent::CpuTemplate,
+                    &serde_json::to_vec(&cpu_template).unwrap()
+                ),
+            ]
+        );
+
+        // The log is serialized with the names of the components.
+        assert_eq!(
+            serde_json::to_value(&measurements[0]).unwrap(),
+            serde_json::json!({"component": "kernel", "sha256": HELLO_SHA256})
+        );
+    }
+
+    #[test]
+    fn test_measure_boot_disabled() {
+        // A kernel file that cannot be read: it is neither read nor copied when the boot is not
+        // measured.
+        let kernel = TempFile::new().unwrap();
+        let boot_config = BootConfig {
+            cmdline: Cmdline::try_from("console=ttyS0", 4096).unwrap(),
+            kernel_file: File::options().write(true).open(kernel.as_path()).unwrap(),
+            firmware: false,
+            initrd_file: None,
+            measured: false,
+        };
+        assert!(measure_boot_files(&boot_config).unwrap().is_none());
+
+        let boot_config = BootConfig {
+            measured: true,
+            ..boot_config
+        };
+        assert!(matches!(
+            measure_boot_files(&boot_config),
+            Err(MeasuredBootError::ReadKernel(_))
+        ));
+    }
+}
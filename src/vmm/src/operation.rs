@@ -0,0 +1,127 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracks the progress of the long-running operation executed by the VMM thread.
+//!
+//! Creating and loading snapshots report the number of bytes processed through
+//! [`OPERATION_PROGRESS`], which the API thread reads while the VMM thread is busy. The API
+//! thread also uses it to request the cancellation of the operation, which the VMM thread
+//! checks at points where stopping leaves the microVM in a consistent state.
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::utils::usize_to_u64;
+
+/// Static instance used for tracking the operation in progress.
+pub static OPERATION_PROGRESS: OperationProgress = OperationProgress::new();
+
+/// The operation was cancelled.
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub struct OperationCancelled;
+
+/// Progress of the operation executed by the VMM thread.
+#[derive(Debug)]
+pub struct OperationProgress {
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    cancel_requested: AtomicBool,
+}
+
+impl OperationProgress {
+    /// Creates a progress tracker with no operation in progress.
+    pub const fn new() -> Self {
+        Self {
+            bytes_done: AtomicU64::new(0),
+            bytes_total: AtomicU64::new(0),
+            cancel_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// Resets the tracker for a new operation.
+    pub fn reset(&self) {
+        self.bytes_done.store(0, Ordering::Relaxed);
+        self.bytes_total.store(0, Ordering::Relaxed);
+        self.cancel_requested.store(false, Ordering::Release);
+    }
+
+    /// Sets the number of bytes the operation processes in total.
+    pub fn set_total(&self, bytes: u64) {
+        self.bytes_total.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Adds `bytes` to the number of bytes the operation processes in total, for operations which
+    /// only learn it step by step.
+    pub fn add_total(&self, bytes: u64) {
+        self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Records that `bytes` more bytes were processed.
+    pub fn advance(&self, bytes: u64) {
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the number of bytes processed so far and the total number of bytes.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.bytes_done.load(Ordering::Relaxed),
+            self.bytes_total.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Requests the cancellation of the operation in progress.
+    pub fn cancel(&self) {
+        self.cancel_requested.store(true, Ordering::Release);
+    }
+
+    /// Returns an error if the cancellation of the operation was requested.
+    pub fn check_cancelled(&self) -> Result<(), OperationCancelled> {
+        if self.cancel_requested.load(Ordering::Acquire) {
+            Err(OperationCancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for OperationProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reader recording the bytes it reads in [`OPERATION_PROGRESS`].
+#[derive(Debug)]
+pub(crate) struct ProgressReader<R>(pub R);
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.0.read(buf)?;
+        OPERATION_PROGRESS.advance(usize_to_u64(count));
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let progress = OperationProgress::new();
+        progress.set_total(100);
+        progress.advance(10);
+        progress.advance(20);
+        assert_eq!(progress.get(), (30, 100));
+        progress.add_total(50);
+        assert_eq!(progress.get(), (30, 150));
+        progress.check_cancelled().unwrap();
+
+        progress.cancel();
+        assert_eq!(progress.check_cancelled(), Err(OperationCancelled));
+
+        progress.reset();
+        assert_eq!(progress.get(), (0, 0));
+        progress.check_cancelled().unwrap();
+    }
+}
@@ -111,9 +111,18 @@ impl PciBus {
 
     /// Insert a device in the bus
     pub fn add_device(&mut self, device_id: u8, device: Arc<Mutex<dyn PciDevice>>) {
+        // Devices restored from a snapshot get their ID from the snapshot.
+        self.device_ids[device_id as usize] = true;
         self.devices.insert(device_id, device);
     }
 
+    /// Remove a device from the bus and release its device ID
+    pub fn remove_device(&mut self, device_id: u8) -> Option<Arc<Mutex<dyn PciDevice>>> {
+        let device = self.devices.remove(&device_id)?;
+        self.device_ids[device_id as usize] = false;
+        Some(device)
+    }
+
     /// Get a new device ID
     // idx is bounded by NUM_DEVICE_IDS (32), so it always fits in u8.
     #[allow(clippy::cast_possible_truncation)]
@@ -988,4 +997,20 @@ mod tests {
         read_mmio_config(&mut mmio_config, 0, 1, 0, 0x6, 0, &mut buffer);
         assert_eq!(buffer, [0x0, 0x0, 0x0, 0x0]);
     }
+
+    #[test]
+    fn test_add_remove_device() {
+        let mock = Arc::new(RelocationMock::default());
+        let mut bus = PciBus::new(PciRoot::new(None), mock);
+
+        // Devices added with a given ID, e.g. when restoring a snapshot, reserve that ID.
+        bus.add_device(1, Arc::new(Mutex::new(PciDevMock::new())));
+        assert_eq!(bus.next_device_id().unwrap(), 2);
+
+        // Removing a device releases its ID.
+        bus.remove_device(1).unwrap();
+        assert!(bus.remove_device(1).is_none());
+        assert_eq!(bus.next_device_id().unwrap(), 1);
+        assert_eq!(bus.next_device_id().unwrap(), 3);
+    }
 }
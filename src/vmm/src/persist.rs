@@ -26,18 +26,21 @@ use crate::cpu_config::x86_64::cpuid::CpuidTrait;
 #[cfg(target_arch = "x86_64")]
 use crate::cpu_config::x86_64::cpuid::common::get_vendor_id_from_host;
 use crate::device_manager::{DevicePersistError, DevicesState};
-use crate::logger::{info, warn};
+use crate::logger::{EVENT_LOG, SPANS, info, warn};
+use crate::measured_boot::BootMeasurement;
+use crate::operation::{OPERATION_PROGRESS, ProgressReader};
 use crate::resources::VmResources;
 use crate::seccomp::BpfThreadMap;
 use crate::snapshot::Snapshot;
-use crate::utils::u64_to_usize;
-use crate::vmm_config::boot_source::BootSourceConfig;
+use crate::utils::{u64_to_usize, usize_to_u64};
+use crate::vmm_config::boot_source::BootSourceState;
 use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::machine_config::{HugePageConfig, MachineConfigError, MachineConfigUpdate};
 use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, MemBackendType};
 use crate::vstate::kvm::KvmState;
 use crate::vstate::memory::{
-    self, GuestMemoryState, GuestRegionMmap, GuestRegionType, MemoryError,
+    self, Address, DiscardedRanges, GuestMemoryRegion, GuestMemoryState, GuestRegionMmap,
+    GuestRegionType, MemoryError,
 };
 use crate::vstate::vcpu::{VcpuSendEventError, VcpuState};
 use crate::vstate::vm::{VmError, VmState};
@@ -53,9 +56,17 @@ pub struct VmInfo {
     /// CPU template type
     pub cpu_template: StaticCpuTemplate,
     /// Boot source information.
-    pub boot_source: BootSourceConfig,
+    pub boot_source: BootSourceState,
     /// Huge page configuration
     pub huge_pages: HugePageConfig,
+    /// Number of online vcpus.
+    pub vcpu_count: u8,
+    /// Maximum number of vcpus, if vcpu hotplug is enabled.
+    pub max_vcpu_count: Option<u8>,
+    /// Measured boot log.
+    pub boot_measurements: Vec<BootMeasurement>,
+    /// Sequence number of the next lifecycle event.
+    pub next_event_seq: u64,
 }
 
 impl From<&VmResources> for VmInfo {
@@ -64,8 +75,13 @@ impl From<&VmResources> for VmInfo {
             mem_size_mib: value.machine_config.mem_size_mib as u64,
             smt: value.machine_config.smt,
             cpu_template: StaticCpuTemplate::from(&value.machine_config.cpu_template),
-            boot_source: value.boot_source.config.clone(),
+            boot_source: BootSourceState::from(&value.boot_source.config),
             huge_pages: value.machine_config.huge_pages,
+            vcpu_count: value.machine_config.vcpu_count,
+            max_vcpu_count: value.machine_config.max_vcpu_count,
+            // The boot components are measured when the microVM is built.
+            boot_measurements: Vec::new(),
+            next_event_seq: 0,
         }
     }
 }
@@ -77,8 +93,12 @@ impl From<&Vmm> for VmInfo {
             mem_size_mib: machine_config.mem_size_mib as u64,
             smt: machine_config.smt,
             cpu_template: StaticCpuTemplate::from(&machine_config.cpu_template),
-            boot_source: value.boot_source_config.clone(),
+            boot_source: BootSourceState::from(&value.boot_source_config),
             huge_pages: machine_config.huge_pages,
+            vcpu_count: machine_config.vcpu_count,
+            max_vcpu_count: machine_config.max_vcpu_count,
+            boot_measurements: value.instance_info.boot_measurements.clone(),
+            next_event_seq: EVENT_LOG.next_seq(),
         }
     }
 }
@@ -122,6 +142,19 @@ pub struct GuestRegionUffdMapping {
     /// to be removed in 2.0.
     #[deprecated]
     pub page_size_kib: usize,
+    /// Ranges of this region that hold no data (e.g. inflated balloon pages or unplugged
+    /// memory) and can be populated with zeros instead of reading the backend.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<GuestRegionUffdHole>,
+}
+
+/// A range of a guest memory region that does not need any backing data.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GuestRegionUffdHole {
+    /// Offset of the range from the start of the region.
+    pub offset: u64,
+    /// Range size.
+    pub size: usize,
 }
 
 /// Errors related to saving and restoring Microvm state.
@@ -160,7 +193,7 @@ pub enum CreateSnapshotError {
 }
 
 /// Snapshot version
-pub const SNAPSHOT_VERSION: Version = Version::new(10, 0, 0);
+pub const SNAPSHOT_VERSION: Version = Version::new(11, 0, 0);
 
 /// Creates a Microvm snapshot.
 pub fn create_snapshot(
@@ -168,14 +201,23 @@ pub fn create_snapshot(
     vm_info: &VmInfo,
     params: &CreateSnapshotParams,
 ) -> Result<(), CreateSnapshotError> {
+    let mut span = SPANS.start("create_snapshot");
+    span.set_attribute("snapshot.type", format!("{:?}", params.snapshot_type));
+
+    let state_span = SPANS.start("save_state");
     let microvm_state = vmm
         .save_state(vm_info)
         .map_err(CreateSnapshotError::MicrovmState)?;
 
     snapshot_state_to_file(&microvm_state, &params.snapshot_path)?;
+    drop(state_span);
 
+    // Memory held by the balloon or unplugged from virtio-mem is not written to the memory file.
+    let discarded = microvm_state.device_states.discarded_memory_ranges();
+    let memory_span = SPANS.start("dump_memory");
     vmm.vm
-        .snapshot_memory_to_file(&params.mem_file_path, params.snapshot_type)?;
+        .snapshot_memory_to_file(&params.mem_file_path, params.snapshot_type, &discarded)?;
+    drop(memory_span);
 
     // We need to mark queues as dirty again for all activated devices. The reason we
     // do it here is that we don't mark pages as dirty during runtime
@@ -362,7 +404,10 @@ pub fn restore_from_snapshot(
     params: &LoadSnapshotParams,
     vm_resources: &mut VmResources,
 ) -> Result<Arc<Mutex<Vmm>>, RestoreFromSnapshotError> {
+    let _span = SPANS.start("restore_from_snapshot");
+    let state_span = SPANS.start("load_state");
     let mut microvm_state = snapshot_state_from_file(&params.snapshot_path)?;
+    drop(state_span);
     for entry in &params.network_overrides {
         microvm_state
             .device_states
@@ -410,16 +455,22 @@ pub fn restore_from_snapshot(
 
     let track_dirty_pages = params.track_dirty_pages;
 
-    let vcpu_count = microvm_state
-        .vcpu_states
-        .len()
-        .try_into()
-        .map_err(|_| MachineConfigError::InvalidVcpuCount)
-        .map_err(BuildMicrovmFromSnapshotError::VmUpdateConfig)?;
+    // With vcpu hotplug enabled, the snapshot holds the state of every possible vcpu, of which
+    // only the first `vcpu_count` are online.
+    let vcpu_count = match microvm_state.vm_info.max_vcpu_count {
+        Some(_) => microvm_state.vm_info.vcpu_count,
+        None => microvm_state
+            .vcpu_states
+            .len()
+            .try_into()
+            .map_err(|_| MachineConfigError::InvalidVcpuCount)
+            .map_err(BuildMicrovmFromSnapshotError::VmUpdateConfig)?,
+    };
 
     vm_resources
         .update_machine_config(&MachineConfigUpdate {
             vcpu_count: Some(vcpu_count),
+            max_vcpu_count: microvm_state.vm_info.max_vcpu_count,
             mem_size_mib: Some(u64_to_usize(microvm_state.vm_info.mem_size_mib)),
             smt: Some(microvm_state.vm_info.smt),
             cpu_template: Some(microvm_state.vm_info.cpu_template),
@@ -436,6 +487,18 @@ pub fn restore_from_snapshot(
     let mem_backend_path = &params.mem_backend.backend_path;
     let mem_state = &microvm_state.vm_state.memory;
 
+    // The guest memory counts as loaded once it is mapped or registered with the page fault
+    // handler, which then loads it on demand.
+    let mem_size: u64 = mem_state
+        .regions()
+        .map(|(_, size)| usize_to_u64(size))
+        .sum();
+    OPERATION_PROGRESS.add_total(mem_size);
+    let mut memory_span = SPANS.start("map_memory");
+    memory_span.set_attribute(
+        "memory.backend",
+        format!("{:?}", params.mem_backend.backend_type),
+    );
     let (guest_memory, uffd) = match params.mem_backend.backend_type {
         MemBackendType::File => {
             if vm_resources.machine_config.huge_pages.is_hugetlbfs() {
@@ -455,9 +518,12 @@ pub fn restore_from_snapshot(
             mem_state,
             track_dirty_pages,
             vm_resources.machine_config.huge_pages,
+            &microvm_state.device_states.discarded_memory_ranges(),
         )
         .map_err(RestoreFromSnapshotGuestMemoryError::Uffd)?,
     };
+    drop(memory_span);
+    OPERATION_PROGRESS.advance(mem_size);
     builder::build_microvm_from_snapshot(
         instance_info,
         event_manager,
@@ -487,7 +553,9 @@ pub enum SnapshotStateFromFileError {
 fn snapshot_state_from_file(
     snapshot_path: &Path,
 ) -> Result<MicrovmState, SnapshotStateFromFileError> {
-    let mut snapshot_reader = File::open(snapshot_path)?;
+    let snapshot_file = File::open(snapshot_path)?;
+    OPERATION_PROGRESS.add_total(snapshot_file.metadata()?.len());
+    let mut snapshot_reader = ProgressReader(snapshot_file);
     let snapshot = Snapshot::load(&mut snapshot_reader)?;
 
     Ok(snapshot.data)
@@ -534,9 +602,10 @@ fn guest_memory_from_uffd(
     mem_state: &GuestMemoryState,
     track_dirty_pages: bool,
     huge_pages: HugePageConfig,
+    discarded: &DiscardedRanges,
 ) -> Result<(Vec<GuestRegionMmap>, Option<Uffd>), GuestMemoryFromUffdError> {
     let (guest_memory, backend_mappings) =
-        create_guest_memory(mem_state, track_dirty_pages, huge_pages)?;
+        create_guest_memory(mem_state, track_dirty_pages, huge_pages, discarded)?;
 
     let mut uffd_builder = UffdBuilder::new();
 
@@ -567,11 +636,19 @@ fn create_guest_memory(
     mem_state: &GuestMemoryState,
     track_dirty_pages: bool,
     huge_pages: HugePageConfig,
+    discarded: &DiscardedRanges,
 ) -> Result<(Vec<GuestRegionMmap>, Vec<GuestRegionUffdMapping>), GuestMemoryFromUffdError> {
     let guest_memory = memory::anonymous(mem_state.regions(), track_dirty_pages, huge_pages)?;
     let mut backend_mappings = Vec::with_capacity(guest_memory.len());
     let mut offset = 0;
     for mem_region in guest_memory.iter() {
+        let holes = discarded
+            .intersections(mem_region.start_addr(), mem_region.size())
+            .map(|(addr, size)| GuestRegionUffdHole {
+                offset: addr.unchecked_offset_from(mem_region.start_addr()),
+                size,
+            })
+            .collect();
         #[allow(deprecated)]
         backend_mappings.push(GuestRegionUffdMapping {
             base_host_virt_addr: mem_region.as_ptr() as u64,
@@ -579,6 +656,7 @@ fn create_guest_memory(
             offset,
             page_size: huge_pages.page_size(),
             page_size_kib: huge_pages.page_size(),
+            holes,
         });
         offset += mem_region.size() as u64;
     }
@@ -662,7 +740,7 @@ mod tests {
     use crate::vmm_config::balloon::BalloonDeviceConfig;
     use crate::vmm_config::net::NetworkInterfaceConfig;
     use crate::vmm_config::vsock::tests::default_config;
-    use crate::vstate::memory::{GuestMemoryRegionState, GuestRegionType};
+    use crate::vstate::memory::{GuestAddress, GuestMemoryRegionState, GuestRegionType};
 
     fn default_vmm_with_devices() -> Vmm {
         let mut event_manager = EventManager::new().expect("Cannot create EventManager");
@@ -676,6 +754,7 @@ mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
         insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
 
@@ -771,13 +850,60 @@ mod tests {
             }],
         };
 
-        let (_, uffd_regions) =
-            create_guest_memory(&mem_state, false, HugePageConfig::None).unwrap();
+        let (_, uffd_regions) = create_guest_memory(
+            &mem_state,
+            false,
+            HugePageConfig::None,
+            &DiscardedRanges::default(),
+        )
+        .unwrap();
 
         assert_eq!(uffd_regions.len(), 1);
         assert_eq!(uffd_regions[0].size, 0x20000);
         assert_eq!(uffd_regions[0].offset, 0);
         assert_eq!(uffd_regions[0].page_size, HugePageConfig::None.page_size());
+        assert!(uffd_regions[0].holes.is_empty());
+
+        // Discarded ranges are reported relative to the start of their region.
+        let mem_state = GuestMemoryState {
+            regions: vec![
+                GuestMemoryRegionState {
+                    base_address: 0,
+                    size: 0x20000,
+                    region_type: GuestRegionType::Dram,
+                    plugged: vec![true],
+                },
+                GuestMemoryRegionState {
+                    base_address: 0x100000,
+                    size: 0x20000,
+                    region_type: GuestRegionType::Dram,
+                    plugged: vec![true],
+                },
+            ],
+        };
+        let mut discarded = DiscardedRanges::default();
+        discarded.insert(GuestAddress(0x1000), 0x2000);
+        discarded.insert(GuestAddress(0x110000), 0x20000);
+
+        let (_, uffd_regions) =
+            create_guest_memory(&mem_state, false, HugePageConfig::None, &discarded).unwrap();
+
+        assert_eq!(uffd_regions.len(), 2);
+        assert_eq!(
+            uffd_regions[0].holes,
+            vec![GuestRegionUffdHole {
+                offset: 0x1000,
+                size: 0x2000
+            }]
+        );
+        assert_eq!(uffd_regions[1].offset, 0x20000);
+        assert_eq!(
+            uffd_regions[1].holes,
+            vec![GuestRegionUffdHole {
+                offset: 0x10000,
+                size: 0x10000
+            }]
+        );
     }
 
     #[test]
@@ -790,6 +916,10 @@ mod tests {
                 offset: 0,
                 page_size: HugePageConfig::None.page_size(),
                 page_size_kib: HugePageConfig::None.page_size(),
+                holes: vec![GuestRegionUffdHole {
+                    offset: 0x1000,
+                    size: 0x1000,
+                }],
             },
             GuestRegionUffdMapping {
                 base_host_virt_addr: 0x100000,
@@ -797,6 +927,7 @@ mod tests {
                 offset: 0,
                 page_size: HugePageConfig::Hugetlbfs2M.page_size(),
                 page_size_kib: HugePageConfig::Hugetlbfs2M.page_size(),
+                holes: vec![],
             },
         ];
 
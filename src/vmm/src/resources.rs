@@ -10,7 +10,8 @@ use vm_memory::GuestAddress;
 
 use crate::cpu_config::templates::CustomCpuTemplate;
 use crate::devices::virtio::device::VirtioDevice;
-use crate::logger::{LoggerConfig, info};
+use crate::landlock::LandlockConfig;
+use crate::logger::{LoggerConfig, error, info};
 use crate::mmds;
 use crate::mmds::data_store::{Mmds, MmdsVersion};
 use crate::mmds::ns::MmdsNetworkStack;
@@ -21,16 +22,19 @@ use crate::vmm_config::balloon::*;
 use crate::vmm_config::boot_source::{
     BootConfig, BootSource, BootSourceConfig, BootSourceConfigError,
 };
+use crate::vmm_config::console::{ConsoleDeviceBuilder, ConsoleDeviceConfig, ConsoleDeviceError};
 use crate::vmm_config::drive::*;
 use crate::vmm_config::entropy::*;
 use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::machine_config::{MachineConfig, MachineConfigError, MachineConfigUpdate};
-use crate::vmm_config::memory_hotplug::{MemoryHotplugConfig, MemoryHotplugConfigError};
+use crate::vmm_config::memory_hotplug::{
+    MemoryHotplugConfig, MemoryHotplugConfigError, MemoryHotplugSizeUpdate,
+};
 use crate::vmm_config::metrics::{MetricsConfig, MetricsConfigError, init_metrics};
 use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
 use crate::vmm_config::net::*;
 use crate::vmm_config::pmem::{PmemBuilder, PmemConfig, PmemConfigError};
-use crate::vmm_config::serial::SerialConfig;
+use crate::vmm_config::serial::{SerialConfig, SerialConfigError};
 use crate::vmm_config::vsock::*;
 use crate::vstate::memory;
 use crate::vstate::memory::{GuestRegionMmap, MemoryError};
@@ -64,10 +68,18 @@ pub enum ResourcesError {
     VsockDevice(#[from] VsockConfigError),
     /// Entropy device error: {0}
     EntropyDevice(#[from] EntropyDeviceError),
+    /// Console device error: {0}
+    ConsoleDevice(#[from] ConsoleDeviceError),
     /// Pmem device error: {0}
     PmemDevice(#[from] PmemConfigError),
+    /// Serial config error: {0}
+    SerialConfig(#[from] SerialConfigError),
     /// Memory hotplug config error: {0}
     MemoryHotplugConfig(#[from] MemoryHotplugConfigError),
+    /// The memory hotplug size can only be updated after the microVM has started.
+    MemoryHotplugSizePreBoot,
+    /// The logger and the metrics cannot be configured by replacing the microVM configuration.
+    ReplaceLoggerOrMetrics,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
@@ -95,11 +107,15 @@ pub struct VmmConfig {
     pub network_interfaces: Vec<NetworkInterfaceConfig>,
     pub vsock: Option<VsockDeviceConfig>,
     pub entropy: Option<EntropyDeviceConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console: Option<ConsoleDeviceConfig>,
     #[serde(default, rename = "pmem")]
     pub pmem_devices: Vec<PmemConfig>,
     #[serde(skip)]
     pub serial_config: Option<SerialConfig>,
     pub memory_hotplug: Option<MemoryHotplugConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_hotplug_size: Option<MemoryHotplugSizeUpdate>,
 }
 
 /// A data structure that encapsulates the device configurations
@@ -120,6 +136,8 @@ pub struct VmResources {
     pub net_builder: NetBuilder,
     /// The entropy device builder.
     pub entropy: EntropyDeviceBuilder,
+    /// The console device builder.
+    pub console: ConsoleDeviceBuilder,
     /// The pmem devices.
     pub pmem: PmemBuilder,
     /// The memory hotplug configuration.
@@ -134,8 +152,16 @@ pub struct VmResources {
     pub boot_timer: bool,
     /// Whether or not to use PCIe transport for VirtIO devices.
     pub pci_enabled: bool,
+    /// Landlock ruleset to install once the microVM is built, if any.
+    pub landlock: Option<LandlockConfig>,
+    /// Path of the core dump written when a vCPU stops because of an error, if any.
+    pub coredump_on_fault: Option<PathBuf>,
+    /// Whether to pause the microVM instead of exiting when the guest kernel panics.
+    pub pause_on_panic: bool,
     /// Where serial console output should be written to
     pub serial_out_path: Option<PathBuf>,
+    /// Unix socket on which a client can attach to the serial console, if any.
+    pub serial_socket_path: Option<PathBuf>,
     /// Optional rate limiter config for serial output.
     pub serial_rate_limiter_cfg: Option<TokenBucketConfig>,
 }
@@ -159,13 +185,13 @@ impl VmResources {
         mmds_size_limit: usize,
         metadata_json: Option<&str>,
     ) -> Result<Self, ResourcesError> {
-        let vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
+        let mut vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
 
-        if let Some(logger_config) = vmm_config.logger {
+        if let Some(logger_config) = vmm_config.logger.take() {
             crate::logger::LOGGER.update(logger_config)?;
         }
 
-        if let Some(metrics) = vmm_config.metrics {
+        if let Some(metrics) = vmm_config.metrics.take() {
             init_metrics(metrics)?;
         }
 
@@ -173,9 +199,92 @@ impl VmResources {
             mmds_size_limit,
             ..Default::default()
         };
+
+        // Init the data store from file, if present.
+        if let Some(data) = metadata_json {
+            resources.locked_mmds_or_default()?.put_data(
+                serde_json::from_str(data).expect("MMDS error: metadata provided not valid json"),
+            )?;
+            info!("Successfully added metadata to mmds from file");
+        }
+
+        resources.apply_vmm_config(vmm_config, &instance_info.id)?;
+        Ok(resources)
+    }
+
+    /// Replaces the configuration of the microVM with the one described by `vmm_config`.
+    ///
+    /// The whole configuration is validated before any of it takes effect: on error, the current
+    /// configuration is left untouched. The MMDS contents, the serial console and the settings
+    /// passed on the command line are kept.
+    pub fn replace_config(
+        &mut self,
+        vmm_config: VmmConfig,
+        instance_id: &str,
+    ) -> Result<(), ResourcesError> {
+        if vmm_config.logger.is_some() || vmm_config.metrics.is_some() {
+            return Err(ResourcesError::ReplaceLoggerOrMetrics);
+        }
+
+        let mut resources = Self {
+            mmds: self.mmds.clone(),
+            mmds_size_limit: self.mmds_size_limit,
+            boot_timer: self.boot_timer,
+            pci_enabled: self.pci_enabled,
+            landlock: self.landlock.clone(),
+            coredump_on_fault: self.coredump_on_fault.clone(),
+            pause_on_panic: self.pause_on_panic,
+            serial_out_path: self.serial_out_path.clone(),
+            serial_socket_path: self.serial_socket_path.clone(),
+            serial_rate_limiter_cfg: self.serial_rate_limiter_cfg,
+            ..Default::default()
+        };
+
+        // A tap device cannot be opened twice, so the current network devices are closed before
+        // the new ones are created, and created again if the new configuration is rejected.
+        let net_configs = self.net_builder.configs();
+        let mmds_config = self.mmds_config();
+        let mmds_basic_config = self.mmds.as_ref().map(|mmds| {
+            let mmds = mmds.lock().expect("Poisoned lock");
+            (mmds.version(), mmds.imds_compat())
+        });
+        self.net_builder = NetBuilder::new();
+
+        if let Err(err) = resources.apply_vmm_config(vmm_config, instance_id) {
+            drop(resources);
+            for net_config in net_configs {
+                if let Err(net_err) = self.build_net_device(net_config) {
+                    error!("Failed to restore network device: {net_err}");
+                }
+            }
+            // The MMDS data store is shared with the rejected configuration.
+            if let Some((version, imds_compat)) = mmds_basic_config {
+                self.set_mmds_basic_config(version, imds_compat, instance_id)?;
+            }
+            if let Some(mmds_config) = mmds_config {
+                self.set_mmds_network_stack_config(&mmds_config)?;
+            }
+            return Err(err);
+        }
+
+        *self = resources;
+        Ok(())
+    }
+
+    // Applies the sections of `vmm_config` other than the logger and the metrics, which are
+    // process-wide.
+    fn apply_vmm_config(
+        &mut self,
+        vmm_config: VmmConfig,
+        instance_id: &str,
+    ) -> Result<(), ResourcesError> {
+        if vmm_config.memory_hotplug_size.is_some() {
+            return Err(ResourcesError::MemoryHotplugSizePreBoot);
+        }
+
         if let Some(machine_config) = vmm_config.machine_config {
             let machine_config = MachineConfigUpdate::from(machine_config);
-            resources.update_machine_config(&machine_config)?;
+            self.update_machine_config(&machine_config)?;
         }
 
         if let Some(either) = vmm_config.cpu_config {
@@ -184,62 +293,57 @@ impl VmResources {
                     let cpu_config_json =
                         std::fs::read_to_string(path).map_err(ResourcesError::File)?;
                     let cpu_template = CustomCpuTemplate::try_from(cpu_config_json.as_str())?;
-                    resources.set_custom_cpu_template(cpu_template);
+                    self.set_custom_cpu_template(cpu_template);
                 }
                 CustomCpuTemplateOrPath::Template(template) => {
-                    resources.set_custom_cpu_template(template)
+                    self.set_custom_cpu_template(template)
                 }
             }
         }
 
-        resources.build_boot_source(vmm_config.boot_source)?;
+        self.build_boot_source(vmm_config.boot_source)?;
 
         for drive_config in vmm_config.drives.into_iter() {
-            resources.set_block_device(drive_config)?;
+            self.set_block_device(drive_config)?;
         }
 
         for net_config in vmm_config.network_interfaces.into_iter() {
-            resources.build_net_device(net_config)?;
+            self.build_net_device(net_config)?;
         }
 
         if let Some(vsock_config) = vmm_config.vsock {
-            resources.set_vsock_device(vsock_config)?;
+            self.set_vsock_device(vsock_config)?;
         }
 
         if let Some(balloon_config) = vmm_config.balloon {
-            resources.set_balloon_device(balloon_config)?;
-        }
-
-        // Init the data store from file, if present.
-        if let Some(data) = metadata_json {
-            resources.locked_mmds_or_default()?.put_data(
-                serde_json::from_str(data).expect("MMDS error: metadata provided not valid json"),
-            )?;
-            info!("Successfully added metadata to mmds from file");
+            self.set_balloon_device(balloon_config)?;
         }
 
         if let Some(mmds_config) = vmm_config.mmds_config {
-            resources.set_mmds_config(mmds_config, &instance_info.id)?;
+            self.set_mmds_config(mmds_config, instance_id)?;
         }
 
         if let Some(entropy_device_config) = vmm_config.entropy {
-            resources.build_entropy_device(entropy_device_config)?;
+            self.build_entropy_device(entropy_device_config)?;
+        }
+
+        if let Some(console_device_config) = vmm_config.console {
+            self.build_console_device(console_device_config)?;
         }
 
         for pmem_config in vmm_config.pmem_devices.into_iter() {
-            resources.build_pmem_device(pmem_config)?;
+            self.build_pmem_device(pmem_config)?;
         }
 
         if let Some(serial_cfg) = vmm_config.serial_config {
-            resources.serial_out_path = serial_cfg.serial_out_path;
-            resources.serial_rate_limiter_cfg = serial_cfg.rate_limiter;
+            self.set_serial_config(serial_cfg)?;
         }
 
         if let Some(memory_hotplug_config) = vmm_config.memory_hotplug {
-            resources.set_memory_hotplug_config(memory_hotplug_config)?;
+            self.set_memory_hotplug_config(memory_hotplug_config)?;
         }
 
-        Ok(resources)
+        Ok(())
     }
 
     /// If not initialised, create the mmds data store with the default config.
@@ -380,6 +484,17 @@ impl VmResources {
         self.vsock.insert(config)
     }
 
+    /// Sets the serial console configuration.
+    pub fn set_serial_config(&mut self, config: SerialConfig) -> Result<(), SerialConfigError> {
+        if config.serial_out_path.is_some() && config.serial_socket_path.is_some() {
+            return Err(SerialConfigError::ConflictingOutputs);
+        }
+        self.serial_out_path = config.serial_out_path;
+        self.serial_socket_path = config.serial_socket_path;
+        self.serial_rate_limiter_cfg = config.rate_limiter;
+        Ok(())
+    }
+
     /// Builds an entropy device to be attached when the VM starts.
     pub fn build_entropy_device(
         &mut self,
@@ -388,6 +503,14 @@ impl VmResources {
         self.entropy.insert(body)
     }
 
+    /// Builds a console device to be attached when the VM starts.
+    pub fn build_console_device(
+        &mut self,
+        body: ConsoleDeviceConfig,
+    ) -> Result<(), ConsoleDeviceError> {
+        self.console.insert(body)
+    }
+
     /// Builds a pmem device to be attached when the VM starts.
     pub fn build_pmem_device(&mut self, body: PmemConfig) -> Result<(), PmemConfigError> {
         let has_block_root = self.block.has_root_device();
@@ -549,10 +672,12 @@ impl From<&VmResources> for VmmConfig {
             network_interfaces: resources.net_builder.configs(),
             vsock: resources.vsock.config(),
             entropy: resources.entropy.config(),
+            console: resources.console.config(),
             pmem_devices: resources.pmem.configs(),
             // serial_config is marked serde(skip) so that it doesnt end up in snapshots.
             serial_config: None,
             memory_hotplug: resources.memory_hotplug.clone(),
+            memory_hotplug_size: None,
         }
     }
 }
@@ -646,7 +771,9 @@ mod tests {
             builder: Some(BootConfig {
                 cmdline: kernel_cmdline,
                 kernel_file: File::open(tmp_file.as_path()).unwrap(),
+                firmware: false,
                 initrd_file: Some(File::open(tmp_file.as_path()).unwrap()),
+                measured: false,
             }),
         }
     }
@@ -663,9 +790,14 @@ mod tests {
             boot_timer: false,
             mmds_size_limit: HTTP_MAX_PAYLOAD_SIZE,
             entropy: Default::default(),
+            console: Default::default(),
             pmem: Default::default(),
             pci_enabled: false,
+            landlock: None,
+            coredump_on_fault: None,
+            pause_on_panic: false,
             serial_out_path: None,
+            serial_socket_path: None,
             serial_rate_limiter_cfg: None,
             memory_hotplug: Default::default(),
         }
@@ -1414,11 +1546,132 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_replace_config() {
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let instance_id = InstanceInfo::default().id;
+        let mut vm_resources = default_vm_resources();
+        vm_resources
+            .locked_mmds_or_default()
+            .unwrap()
+            .put_data(serde_json::json!({"foo": "bar"}))
+            .unwrap();
+        let net_configs = vm_resources.net_builder.configs();
+        let host_dev_name = &net_configs[0].host_dev_name;
+
+        // The logger and the metrics are process-wide and cannot be replaced.
+        let vmm_config = serde_json::from_str::<VmmConfig>(&format!(
+            r#"{{
+                "boot-source": {{ "kernel_image_path": "{}" }},
+                "metrics": {{ "metrics_path": "{}" }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+        ))
+        .unwrap();
+        let error = vm_resources
+            .replace_config(vmm_config, &instance_id)
+            .unwrap_err();
+        assert!(
+            matches!(error, ResourcesError::ReplaceLoggerOrMetrics),
+            "{:?}",
+            error
+        );
+
+        // The memory hotplug size can only be updated at runtime.
+        let vmm_config = serde_json::from_str::<VmmConfig>(&format!(
+            r#"{{
+                "boot-source": {{ "kernel_image_path": "{}" }},
+                "memory-hotplug-size": {{ "requested_size_mib": 1024 }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+        ))
+        .unwrap();
+        let error = vm_resources
+            .replace_config(vmm_config, &instance_id)
+            .unwrap_err();
+        assert!(
+            matches!(error, ResourcesError::MemoryHotplugSizePreBoot),
+            "{:?}",
+            error
+        );
+
+        // An invalid configuration leaves the current one untouched, even though its network
+        // interface uses the same tap device.
+        let vmm_config = serde_json::from_str::<VmmConfig>(&format!(
+            r#"{{
+                "boot-source": {{ "kernel_image_path": "{}" }},
+                "network-interfaces": [
+                    {{ "iface_id": "netif", "host_dev_name": "{}" }}
+                ],
+                "balloon": {{ "amount_mib": 4096, "deflate_on_oom": false }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            host_dev_name,
+        ))
+        .unwrap();
+        let error = vm_resources
+            .replace_config(vmm_config, &instance_id)
+            .unwrap_err();
+        assert!(
+            matches!(error, ResourcesError::BalloonDevice(_)),
+            "{:?}",
+            error
+        );
+        assert_eq!(vm_resources.block.configs()[0].drive_id, "block1");
+        assert_eq!(vm_resources.net_builder.configs(), net_configs);
+        assert!(vm_resources.balloon.get().is_none());
+
+        // A valid configuration replaces the current one and keeps the MMDS contents.
+        let vmm_config = serde_json::from_str::<VmmConfig>(&format!(
+            r#"{{
+                "boot-source": {{ "kernel_image_path": "{}" }},
+                "drives": [
+                    {{
+                        "drive_id": "rootfs",
+                        "path_on_host": "{}",
+                        "is_root_device": true,
+                        "is_read_only": false
+                    }}
+                ],
+                "network-interfaces": [
+                    {{ "iface_id": "netif", "host_dev_name": "{}" }}
+                ],
+                "mmds-config": {{ "network_interfaces": ["netif"] }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+            host_dev_name,
+        ))
+        .unwrap();
+        vm_resources
+            .replace_config(vmm_config, &instance_id)
+            .unwrap();
+        assert_eq!(vm_resources.block.configs().len(), 1);
+        assert_eq!(vm_resources.block.configs()[0].drive_id, "rootfs");
+        assert_eq!(vm_resources.net_builder.configs().len(), 1);
+        assert_eq!(vm_resources.net_builder.configs()[0].iface_id, "netif");
+        assert_eq!(
+            vm_resources.mmds_config().unwrap().network_interfaces,
+            vec!["netif".to_string()]
+        );
+        assert_eq!(
+            vm_resources
+                .locked_mmds_or_default()
+                .unwrap()
+                .data_store_value(),
+            serde_json::json!({"foo": "bar"})
+        );
+        assert_eq!(vm_resources.mmds_size_limit, HTTP_MAX_PAYLOAD_SIZE);
+    }
+
     #[test]
     fn test_update_machine_config() {
         let mut vm_resources = default_vm_resources();
         let mut aux_vm_config = MachineConfigUpdate {
             vcpu_count: Some(32),
+            max_vcpu_count: None,
             mem_size_mib: Some(512),
             smt: Some(false),
             #[cfg(target_arch = "x86_64")]
@@ -1472,6 +1725,26 @@ mod tests {
         vm_resources.update_machine_config(&aux_vm_config).unwrap();
         aux_vm_config.smt = Some(false);
 
+        // The maximum vcpu count cannot be lower than the vcpu count.
+        aux_vm_config.max_vcpu_count = Some(16);
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            vm_resources.update_machine_config(&aux_vm_config),
+            Err(MachineConfigError::InvalidMaxVcpuCount)
+        );
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_machine_config(&aux_vm_config),
+            Err(MachineConfigError::VcpuHotplugNotSupported)
+        );
+        aux_vm_config.vcpu_count = Some(2);
+        #[cfg(target_arch = "x86_64")]
+        {
+            vm_resources.update_machine_config(&aux_vm_config).unwrap();
+            assert_eq!(vm_resources.machine_config.max_vcpus(), 16);
+        }
+        aux_vm_config.max_vcpu_count = None;
+
         // Invalid mem_size_mib.
         aux_vm_config.mem_size_mib = Some(0);
         assert_eq!(
@@ -1488,6 +1761,7 @@ mod tests {
                 stats_polling_interval_s: 0,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                policy: None,
             })
             .unwrap();
         aux_vm_config.mem_size_mib = Some(90);
@@ -1528,6 +1802,7 @@ mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
         assert!(vm_resources.balloon.get().is_none());
         vm_resources
@@ -1568,6 +1843,25 @@ mod tests {
         assert_eq!(actual_entropy_cfg, entropy_device_cfg);
     }
 
+    #[test]
+    fn test_set_console_device() {
+        let output = TempFile::new().unwrap();
+        let mut vm_resources = default_vm_resources();
+        let console_device_cfg = ConsoleDeviceConfig {
+            ports: vec![crate::vmm_config::console::ConsolePortConfig {
+                name: "console".to_string(),
+                console: true,
+                uds_path: None,
+                output_path: Some(output.as_path().to_str().unwrap().to_string()),
+            }],
+        };
+        assert!(vm_resources.console.get().is_none());
+        vm_resources
+            .build_console_device(console_device_cfg.clone())
+            .unwrap();
+        assert_eq!(vm_resources.console.config().unwrap(), console_device_cfg);
+    }
+
     #[test]
     fn test_set_boot_source() {
         let tmp_file = TempFile::new().unwrap();
@@ -1576,6 +1870,8 @@ mod tests {
             kernel_image_path: String::from(tmp_file.as_path().to_str().unwrap()),
             initrd_path: Some(String::from(tmp_file.as_path().to_str().unwrap())),
             boot_args: Some(cmdline.to_string()),
+            firmware_path: None,
+            measured_boot: false,
         };
 
         let mut vm_resources = default_vm_resources();
@@ -1655,6 +1951,36 @@ mod tests {
         assert_eq!(actual_vsock_cfg.lock().unwrap().id(), VSOCK_DEV_ID);
     }
 
+    #[test]
+    fn test_set_serial_config() {
+        let mut vm_resources = default_vm_resources();
+
+        // The output cannot go to both a file and a socket.
+        let err = vm_resources
+            .set_serial_config(SerialConfig {
+                serial_out_path: Some(PathBuf::from("serial.log")),
+                serial_socket_path: Some(PathBuf::from("serial.sock")),
+                rate_limiter: None,
+            })
+            .unwrap_err();
+        assert_eq!(err, SerialConfigError::ConflictingOutputs);
+        assert!(vm_resources.serial_out_path.is_none());
+        assert!(vm_resources.serial_socket_path.is_none());
+
+        vm_resources
+            .set_serial_config(SerialConfig {
+                serial_out_path: None,
+                serial_socket_path: Some(PathBuf::from("serial.sock")),
+                rate_limiter: None,
+            })
+            .unwrap();
+        assert!(vm_resources.serial_out_path.is_none());
+        assert_eq!(
+            vm_resources.serial_socket_path,
+            Some(PathBuf::from("serial.sock"))
+        );
+    }
+
     #[test]
     fn test_set_net_device() {
         let mut vm_resources = default_vm_resources();
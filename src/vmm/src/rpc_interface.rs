@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::fmt::{self, Debug};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use serde_json::Value;
 use utils::time::{ClockType, get_time_us};
@@ -13,21 +15,25 @@ use super::resources::VmResources;
 use super::{Vmm, VmmError};
 use crate::EventManager;
 use crate::builder::StartMicrovmError;
+use crate::coredump::{CoreDumpError, create_core_dump};
 use crate::cpu_config::templates::{CustomCpuTemplate, GuestConfigError};
 use crate::devices::virtio::balloon::device::{HintingStatus, StartHintingCmd};
 use crate::devices::virtio::mem::VirtioMemStatus;
+use crate::landlock::LandlockConfig;
 use crate::logger::{LoggerConfig, info, warn, *};
 use crate::mmds::data_store::{self, Mmds, MmdsDatastoreError};
 use crate::persist::{CreateSnapshotError, RestoreFromSnapshotError, VmInfo};
-use crate::resources::VmmConfig;
+use crate::resources::{ResourcesError, VmmConfig};
 use crate::seccomp::BpfThreadMap;
 use crate::vmm_config::balloon::{
     BalloonConfigError, BalloonDeviceConfig, BalloonStats, BalloonUpdateConfig,
-    BalloonUpdateStatsConfig,
+    BalloonUpdatePolicyConfig, BalloonUpdateStatsConfig,
 };
 use crate::vmm_config::boot_source::{BootSourceConfig, BootSourceConfigError};
+use crate::vmm_config::console::{ConsoleDeviceConfig, ConsoleDeviceError};
 use crate::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig, DriveError};
 use crate::vmm_config::entropy::{EntropyDeviceConfig, EntropyDeviceError};
+use crate::vmm_config::full_config::{FullConfigUpdate, FullConfigUpdateError};
 use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::machine_config::{MachineConfig, MachineConfigError, MachineConfigUpdate};
 use crate::vmm_config::memory_hotplug::{
@@ -38,9 +44,13 @@ use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
 use crate::vmm_config::net::{
     NetworkInterfaceConfig, NetworkInterfaceError, NetworkInterfaceUpdateConfig,
 };
+use crate::vmm_config::pci_hotplug::{DeviceHotplugError, DeviceUnplugConfig};
 use crate::vmm_config::pmem::{PmemConfig, PmemConfigError};
-use crate::vmm_config::serial::SerialConfig;
-use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, SnapshotType};
+use crate::vmm_config::serial::{SerialConfig, SerialConfigError};
+use crate::vmm_config::snapshot::{
+    CreateCoreDumpParams, CreateSnapshotParams, LoadSnapshotParams, SnapshotType,
+};
+use crate::vmm_config::vcpu_hotplug::{VcpuCountUpdate, VcpuHotplugError};
 use crate::vmm_config::vsock::{VsockConfigError, VsockDeviceConfig};
 use crate::vmm_config::{self, RateLimiterUpdate};
 
@@ -59,6 +69,10 @@ pub enum VmmAction {
     ConfigureMetrics(MetricsConfig),
     /// Configure the serial device. This action can only be called before the microVM has booted.
     ConfigureSerial(SerialConfig),
+    /// Create an ELF core dump of the guest using as input the `CreateCoreDumpParams`. This action
+    /// can only be called after the microVM has booted and only when the microVM is in `Paused`
+    /// state.
+    CreateCoreDump(CreateCoreDumpParams),
     /// Create a snapshot using as input the `CreateSnapshotParams`. This action can only be called
     /// after the microVM has booted and only when the microVM is in `Paused` state.
     CreateSnapshot(CreateSnapshotParams),
@@ -66,8 +80,12 @@ pub enum VmmAction {
     GetBalloonConfig,
     /// Get the ballon device latest statistics.
     GetBalloonStats,
+    /// Get the lifecycle events emitted from the given cursor, or all the available ones.
+    GetEvents(Option<u64>),
     /// Get complete microVM configuration in JSON format.
     GetFullVmConfig,
+    /// Get the metrics in the OpenMetrics text format.
+    GetMetrics,
     /// Get MMDS contents.
     GetMMDS,
     /// Get the machine configuration of the microVM.
@@ -79,13 +97,12 @@ pub enum VmmAction {
     /// Flush the metrics. This action can only be called after the logger has been configured.
     FlushMetrics,
     /// Add a new block device or update one that already exists using the `BlockDeviceConfig` as
-    /// input. This action can only be called before the microVM has booted.
+    /// input. After boot, the block device is hotplugged over PCI.
     InsertBlockDevice(BlockDeviceConfig),
     /// Add a virtio-pmem device.
     InsertPmemDevice(PmemConfig),
     /// Add a new network interface config or update one that already exists using the
-    /// `NetworkInterfaceConfig` as input. This action can only be called before the microVM has
-    /// booted.
+    /// `NetworkInterfaceConfig` as input. After boot, the network device is hotplugged over PCI.
     InsertNetworkDevice(NetworkInterfaceConfig),
     /// Load the microVM state using as input the `LoadSnapshotParams`. This action can only be
     /// called before the microVM has booted. If this action is successful, the loaded microVM will
@@ -107,13 +124,18 @@ pub enum VmmAction {
     SetBalloonDevice(BalloonDeviceConfig),
     /// Set the MMDS configuration.
     SetMmdsConfiguration(MmdsConfig),
+    /// Replace the complete microVM configuration before the microVM has booted. After boot, only
+    /// apply the changes which can be made to a running microVM.
+    SetVmConfig(Box<VmmConfig>),
     /// Set the vsock device or update the one that already exists using the
-    /// `VsockDeviceConfig` as input. This action can only be called before the microVM has
-    /// booted.
+    /// `VsockDeviceConfig` as input. After boot, the vsock device is hotplugged over PCI.
     SetVsockDevice(VsockDeviceConfig),
     /// Set the entropy device using `EntropyDeviceConfig` as input. This action can only be called
     /// before the microVM has booted.
     SetEntropyDevice(EntropyDeviceConfig),
+    /// Set the console device using `ConsoleDeviceConfig` as input. This action can only be called
+    /// before the microVM has booted.
+    SetConsoleDevice(ConsoleDeviceConfig),
     /// Get the memory hotplug device configuration and status.
     GetMemoryHotplugStatus,
     /// Set the memory hotplug device using `MemoryHotplugConfig` as input. This action can only be
@@ -132,12 +154,17 @@ pub enum VmmAction {
     UpdateBalloon(BalloonUpdateConfig),
     /// Update the balloon statistics polling interval, after microVM start.
     UpdateBalloonStatistics(BalloonUpdateStatsConfig),
+    /// Set or clear the automatic balloon policy, after microVM start.
+    UpdateBalloonPolicy(BalloonUpdatePolicyConfig),
     /// Start a free page hinting run
     StartFreePageHinting(StartHintingCmd),
     /// Retrieve the status of the hinting run
     GetFreePageHintingStatus,
     /// Stops a free page hinting run
     StopFreePageHinting,
+    /// Ask the guest to release a PCI device, which is removed once the guest ejects it. This
+    /// action can only be called after the microVM has booted.
+    UnplugDevice(DeviceUnplugConfig),
     /// Update existing block device properties such as `path_on_host` or `rate_limiter`.
     UpdateBlockDevice(BlockDeviceUpdateConfig),
     /// Update a network interface, after microVM start. Currently, the only updatable properties
@@ -146,6 +173,21 @@ pub enum VmmAction {
     /// Update the microVM configuration (memory & vcpu) using `VmUpdateConfig` as input. This
     /// action can only be called before the microVM has booted.
     UpdateMachineConfiguration(MachineConfigUpdate),
+    /// Set the number of online vCPUs, hotplugging or unplugging vCPUs. This action can only be
+    /// called after the microVM has booted.
+    UpdateVcpuCount(VcpuCountUpdate),
+}
+
+impl VmmAction {
+    /// Returns the outcome of the actions which only read the metrics or the event log, which the
+    /// API thread serves itself while the VMM thread is busy.
+    pub fn outcome_without_vmm(&self) -> Option<Result<VmmData, VmmActionError>> {
+        match self {
+            VmmAction::GetEvents(cursor) => Some(Ok(VmmData::Events(EVENT_LOG.read(*cursor)))),
+            VmmAction::GetMetrics => Some(get_metrics()),
+            _ => None,
+        }
+    }
 }
 
 /// Wrapper for all errors associated with VMM actions.
@@ -157,14 +199,20 @@ pub enum VmmActionError {
     BalloonUpdate(VmmError),
     /// Boot source error: {0}
     BootSource(#[from] BootSourceConfigError),
+    /// Create core dump error: {0}
+    CreateCoreDump(#[from] CoreDumpError),
     /// Create snapshot error: {0}
     CreateSnapshot(#[from] CreateSnapshotError),
     /// Configure CPU error: {0}
     ConfigureCpu(#[from] GuestConfigError),
+    /// Device hotplug error: {0}
+    DeviceHotplug(#[from] DeviceHotplugError),
     /// Drive config error: {0}
     DriveConfig(#[from] DriveError),
     /// Entropy device error: {0}
     EntropyDevice(#[from] EntropyDeviceError),
+    /// Console device error: {0}
+    ConsoleDevice(#[from] ConsoleDeviceError),
     /// Pmem device error: {0}
     PmemDevice(#[from] PmemConfigError),
     /// Memory hotplug config error: {0}
@@ -197,10 +245,18 @@ pub enum VmmActionError {
     OperationNotSupportedPostBoot,
     /// The requested operation is not supported before starting the microVM.
     OperationNotSupportedPreBoot,
+    /// Serial config error: {0}
+    SerialConfig(#[from] SerialConfigError),
     /// Start microvm error: {0}
     StartMicrovm(#[from] StartMicrovmError),
     /// Vsock config error: {0}
     VsockConfig(#[from] VsockConfigError),
+    /// VM config error: {0}
+    VmConfig(#[from] ResourcesError),
+    /// VM config update error: {0}
+    VmConfigUpdate(#[from] FullConfigUpdateError),
+    /// vCPU hotplug error: {0}
+    VcpuHotplug(#[from] VcpuHotplugError),
 }
 
 /// The enum represents the response sent by the VMM in case of success. The response is either
@@ -214,10 +270,14 @@ pub enum VmmData {
     BalloonStats(BalloonStats),
     /// No data is sent on the channel.
     Empty,
+    /// The lifecycle events read from a cursor.
+    Events(EventBatch),
     /// The complete microVM configuration in JSON format.
     FullVmConfig(VmmConfig),
     /// The microVM configuration represented by `VmConfig`.
     MachineConfiguration(MachineConfig),
+    /// The metrics in the OpenMetrics text format.
+    Metrics(String),
     /// Mmds contents.
     MmdsValue(serde_json::Value),
     /// The microVM instance information.
@@ -230,6 +290,14 @@ pub enum VmmData {
     HintingStatus(HintingStatus),
 }
 
+fn get_metrics() -> Result<VmmData, VmmActionError> {
+    METRICS
+        .openmetrics()
+        .map(VmmData::Metrics)
+        .map_err(super::VmmError::Metrics)
+        .map_err(VmmActionError::InternalVmm)
+}
+
 fn mmds_patch_data(
     mut mmds: MutexGuard<'_, Mmds>,
     value: serde_json::Value,
@@ -354,6 +422,9 @@ impl<'a> PrebootApiController<'a> {
         api_event_fd: &vmm_sys_util::eventfd::EventFd,
         boot_timer_enabled: bool,
         pci_enabled: bool,
+        landlock: Option<LandlockConfig>,
+        coredump_on_fault: Option<PathBuf>,
+        pause_on_panic: bool,
         mmds_size_limit: usize,
         metadata_json: Option<&str>,
     ) -> Result<Arc<Mutex<Vmm>>, BuildMicrovmFromRequestsError> {
@@ -361,6 +432,9 @@ impl<'a> PrebootApiController<'a> {
             boot_timer: boot_timer_enabled,
             mmds_size_limit,
             pci_enabled,
+            landlock,
+            coredump_on_fault,
+            pause_on_panic,
             ..Default::default()
         };
 
@@ -430,12 +504,13 @@ impl<'a> PrebootApiController<'a> {
             ConfigureMetrics(metrics_cfg) => vmm_config::metrics::init_metrics(metrics_cfg)
                 .map(|()| VmmData::Empty)
                 .map_err(VmmActionError::Metrics),
-            ConfigureSerial(serial_cfg) => {
-                self.vm_resources.serial_out_path = serial_cfg.serial_out_path;
-                self.vm_resources.serial_rate_limiter_cfg = serial_cfg.rate_limiter;
-                Ok(VmmData::Empty)
-            }
+            ConfigureSerial(serial_cfg) => self
+                .vm_resources
+                .set_serial_config(serial_cfg)
+                .map(|()| VmmData::Empty)
+                .map_err(VmmActionError::SerialConfig),
             GetBalloonConfig => self.balloon_config(),
+            GetEvents(cursor) => Ok(VmmData::Events(EVENT_LOG.read(cursor))),
             GetFullVmConfig => {
                 warn!(
                     "If the VM was restored from snapshot, boot-source, machine-config.smt, and \
@@ -443,6 +518,7 @@ impl<'a> PrebootApiController<'a> {
                 );
                 Ok(VmmData::FullVmConfig((&*self.vm_resources).into()))
             }
+            GetMetrics => get_metrics(),
             GetMMDS => Ok(VmmData::MmdsValue(
                 self.vm_resources
                     .locked_mmds_or_default()
@@ -478,12 +554,15 @@ impl<'a> PrebootApiController<'a> {
             SetBalloonDevice(config) => self.set_balloon_device(config),
             SetVsockDevice(config) => self.set_vsock_device(config),
             SetMmdsConfiguration(config) => self.set_mmds_config(config),
+            SetVmConfig(config) => self.set_vm_config(*config),
             StartMicroVm => self.start_microvm(),
             UpdateMachineConfiguration(config) => self.update_machine_config(config),
             SetEntropyDevice(config) => self.set_entropy_device(config),
+            SetConsoleDevice(config) => self.set_console_device(config),
             SetMemoryHotplugDevice(config) => self.set_memory_hotplug_device(config),
             // Operations not allowed pre-boot.
-            CreateSnapshot(_)
+            CreateCoreDump(_)
+            | CreateSnapshot(_)
             | FlushMetrics
             | Pause
             | Resume
@@ -491,12 +570,15 @@ impl<'a> PrebootApiController<'a> {
             | GetMemoryHotplugStatus
             | UpdateBalloon(_)
             | UpdateBalloonStatistics(_)
+            | UpdateBalloonPolicy(_)
             | UpdateBlockDevice(_)
             | UpdateMemoryHotplugSize(_)
             | UpdateNetworkInterface(_)
             | StartFreePageHinting(_)
             | GetFreePageHintingStatus
-            | StopFreePageHinting => Err(VmmActionError::OperationNotSupportedPreBoot),
+            | StopFreePageHinting
+            | UnplugDevice(_)
+            | UpdateVcpuCount(_) => Err(VmmActionError::OperationNotSupportedPreBoot),
             #[cfg(target_arch = "x86_64")]
             SendCtrlAltDel => Err(VmmActionError::OperationNotSupportedPreBoot),
         }
@@ -561,6 +643,14 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::MmdsConfig)
     }
 
+    fn set_vm_config(&mut self, cfg: VmmConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .replace_config(cfg, &self.instance_info.id)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::VmConfig)
+    }
+
     fn update_machine_config(
         &mut self,
         cfg: MachineConfigUpdate,
@@ -594,6 +684,12 @@ impl<'a> PrebootApiController<'a> {
         Ok(VmmData::Empty)
     }
 
+    fn set_console_device(&mut self, cfg: ConsoleDeviceConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.build_console_device(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
     fn set_memory_hotplug_device(
         &mut self,
         cfg: MemoryHotplugConfig,
@@ -613,6 +709,7 @@ impl<'a> PrebootApiController<'a> {
             self.seccomp_filters,
         )
         .map(|vmm| {
+            EVENT_LOG.emit(EventKind::VmStarted);
             self.built_vmm = Some(vmm);
             VmmData::Empty
         })
@@ -645,6 +742,9 @@ impl<'a> PrebootApiController<'a> {
             // If restore fails, we consider the process is too dirty to recover.
             self.fatal_error = Some(BuildMicrovmFromRequestsError::Restore);
         })?;
+        EVENT_LOG.emit(EventKind::SnapshotLoaded {
+            snapshot_path: load_params.snapshot_path.display().to_string(),
+        });
         // Resume VM
         if load_params.resume_vm {
             vmm.lock()
@@ -679,6 +779,7 @@ impl RuntimeApiController {
         use self::VmmAction::*;
         match request {
             // Supported operations allowed post-boot.
+            CreateCoreDump(core_dump_cfg) => self.create_core_dump(&core_dump_cfg),
             CreateSnapshot(snapshot_create_cfg) => self.create_snapshot(&snapshot_create_cfg),
             FlushMetrics => self.flush_metrics(),
             GetBalloonConfig => self
@@ -695,9 +796,11 @@ impl RuntimeApiController {
                 .latest_balloon_stats()
                 .map(VmmData::BalloonStats)
                 .map_err(VmmActionError::InternalVmm),
+            GetEvents(cursor) => Ok(VmmData::Events(EVENT_LOG.read(cursor))),
             GetFullVmConfig => Ok(VmmData::FullVmConfig(
                 self.vmm.lock().expect("Poisoned lock").full_config(),
             )),
+            GetMetrics => get_metrics(),
             GetMemoryHotplugStatus => self
                 .vmm
                 .lock()
@@ -766,6 +869,13 @@ impl RuntimeApiController {
                 .update_balloon_stats_config(balloon_stats_update.stats_polling_interval_s)
                 .map(|_| VmmData::Empty)
                 .map_err(VmmActionError::BalloonUpdate),
+            UpdateBalloonPolicy(balloon_policy_update) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .update_balloon_policy(balloon_policy_update.policy)
+                .map(|_| VmmData::Empty)
+                .map_err(VmmActionError::BalloonUpdate),
             StartFreePageHinting(cmd) => self
                 .vmm
                 .lock()
@@ -787,29 +897,48 @@ impl RuntimeApiController {
                 .stop_balloon_hinting()
                 .map(|_| VmmData::Empty)
                 .map_err(VmmActionError::BalloonUpdate),
+            SetVmConfig(config) => self.update_vm_config(*config),
+            InsertBlockDevice(config) => {
+                self.hotplug_device(|vmm| vmm.hotplug_block_device(config))
+            }
+            InsertNetworkDevice(config) => {
+                self.hotplug_device(|vmm| vmm.hotplug_net_device(config))
+            }
+            SetVsockDevice(config) => self.hotplug_device(|vmm| vmm.hotplug_vsock_device(config)),
+            UnplugDevice(config) => self.hotplug_device(|vmm| vmm.unplug_device(config)),
             UpdateBlockDevice(new_cfg) => self.update_block_device(new_cfg),
             UpdateNetworkInterface(netif_update) => self.update_net_rate_limiters(netif_update),
-            UpdateMemoryHotplugSize(cfg) => self
-                .vmm
-                .lock()
-                .expect("Poisoned lock")
-                .update_memory_hotplug_size(cfg.requested_size_mib)
-                .map(|_| VmmData::Empty)
-                .map_err(VmmActionError::MemoryHotplugUpdate),
+            UpdateMemoryHotplugSize(cfg) => {
+                cfg.validate()?;
+                self.vmm
+                    .lock()
+                    .expect("Poisoned lock")
+                    .update_memory_hotplug_size(
+                        cfg.requested_size_mib,
+                        cfg.timeout_s.map(Duration::from_secs),
+                    )
+                    .map(|_| VmmData::Empty)
+                    .map_err(VmmActionError::MemoryHotplugUpdate)
+            }
+            UpdateVcpuCount(update) => {
+                self.vmm
+                    .lock()
+                    .expect("Poisoned lock")
+                    .update_vcpu_count(update.vcpu_count)?;
+                Ok(VmmData::Empty)
+            }
             // Operations not allowed post-boot.
             ConfigureBootSource(_)
             | ConfigureLogger(_)
             | ConfigureMetrics(_)
             | ConfigureSerial(_)
-            | InsertBlockDevice(_)
             | InsertPmemDevice(_)
-            | InsertNetworkDevice(_)
             | LoadSnapshot(_)
             | PutCpuConfiguration(_)
             | SetBalloonDevice(_)
-            | SetVsockDevice(_)
             | SetMmdsConfiguration(_)
             | SetEntropyDevice(_)
+            | SetConsoleDevice(_)
             | SetMemoryHotplugDevice(_)
             | StartMicroVm
             | UpdateMachineConfiguration(_) => Err(VmmActionError::OperationNotSupportedPostBoot),
@@ -872,6 +1001,26 @@ impl RuntimeApiController {
             .map_err(VmmActionError::InternalVmm)
     }
 
+    fn create_core_dump(
+        &mut self,
+        create_params: &CreateCoreDumpParams,
+    ) -> Result<VmmData, VmmActionError> {
+        let mut locked_vmm = self.vmm.lock().unwrap();
+        let create_start_us = get_time_us(ClockType::Monotonic);
+
+        create_core_dump(&mut locked_vmm, &create_params.core_dump_path)?;
+        EVENT_LOG.emit(EventKind::CoreDumpCreated {
+            core_dump_path: create_params.core_dump_path.display().to_string(),
+        });
+
+        let elapsed_time_us = update_metric_with_elapsed_time(
+            &METRICS.latencies_us.vmm_create_core_dump,
+            create_start_us,
+        );
+        info!("'create core dump' VMM action took {} us.", elapsed_time_us);
+        Ok(VmmData::Empty)
+    }
+
     fn create_snapshot(
         &mut self,
         create_params: &CreateSnapshotParams,
@@ -885,6 +1034,9 @@ impl RuntimeApiController {
         let create_start_us = get_time_us(ClockType::Monotonic);
 
         create_snapshot(&mut locked_vmm, &vm_info, create_params)?;
+        EVENT_LOG.emit(EventKind::SnapshotCreated {
+            snapshot_path: create_params.snapshot_path.display().to_string(),
+        });
 
         match create_params.snapshot_type {
             SnapshotType::Full => {
@@ -962,15 +1114,117 @@ impl RuntimeApiController {
             .map_err(NetworkInterfaceError::DeviceUpdate)
             .map_err(VmmActionError::NetworkConfig)
     }
+
+    /// Adds a device to, or removes a device from, the running microVM.
+    fn hotplug_device(
+        &mut self,
+        f: impl FnOnce(&mut Vmm) -> Result<(), DeviceHotplugError>,
+    ) -> Result<VmmData, VmmActionError> {
+        f(&mut self.vmm.lock().expect("Poisoned lock"))?;
+        Ok(VmmData::Empty)
+    }
+
+    /// Checks that all the changes of `update` can be applied to the running microVM, without
+    /// applying any of them.
+    fn check_vm_config_update(&self, update: &FullConfigUpdate) -> Result<(), VmmActionError> {
+        let vmm = self.vmm.lock().expect("Poisoned lock");
+        for drive_update in &update.drives {
+            if let Some(path_on_host) = &drive_update.path_on_host {
+                vmm.check_block_device_path(&drive_update.drive_id, path_on_host)
+                    .map_err(DriveError::DeviceUpdate)?;
+            }
+            if drive_update.rate_limiter.is_some() {
+                vmm.check_block_rate_limiter(&drive_update.drive_id)
+                    .map_err(DriveError::DeviceUpdate)?;
+            }
+        }
+        if let Some(balloon_update) = &update.balloon {
+            vmm.check_balloon_config(balloon_update.amount_mib)
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if let Some(balloon_stats_update) = &update.balloon_statistics {
+            vmm.check_balloon_stats_config(balloon_stats_update.stats_polling_interval_s)
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if let Some(balloon_policy_update) = &update.balloon_policy {
+            vmm.check_balloon_policy(balloon_policy_update.policy.as_ref())
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if update.mmds.is_some() && vmm.get_mmds().is_none() {
+            return Err(VmmActionError::Mmds(MmdsDatastoreError::NotInitialized));
+        }
+        if let Some(memory_hotplug_update) = &update.memory_hotplug_size {
+            memory_hotplug_update.validate()?;
+            vmm.check_memory_hotplug_size(memory_hotplug_update.requested_size_mib)
+                .map_err(VmmActionError::MemoryHotplugUpdate)?;
+        }
+        if let Some(vcpu_count_update) = &update.vcpu_count {
+            vmm.check_vcpu_count(vcpu_count_update.vcpu_count)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the running microVM to the configuration described by `new_cfg`, if all its changes
+    /// from the current configuration can be made at runtime. All the changes are checked before
+    /// any of them is applied, so a rejected change leaves the microVM untouched.
+    fn update_vm_config(&mut self, new_cfg: VmmConfig) -> Result<VmmData, VmmActionError> {
+        let current_cfg = self.vmm.lock().expect("Poisoned lock").full_config();
+        let update = FullConfigUpdate::new(current_cfg, new_cfg)?;
+        self.check_vm_config_update(&update)?;
+
+        for drive_update in update.drives {
+            self.update_block_device(drive_update)?;
+        }
+        for netif_update in update.network_interfaces {
+            self.update_net_rate_limiters(netif_update)?;
+        }
+
+        let mut vmm = self.vmm.lock().expect("Poisoned lock");
+        if let Some(balloon_update) = update.balloon {
+            vmm.update_balloon_config(balloon_update.amount_mib)
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if let Some(balloon_stats_update) = update.balloon_statistics {
+            vmm.update_balloon_stats_config(balloon_stats_update.stats_polling_interval_s)
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if let Some(balloon_policy_update) = update.balloon_policy {
+            vmm.update_balloon_policy(balloon_policy_update.policy)
+                .map_err(VmmActionError::BalloonUpdate)?;
+        }
+        if let Some(mmds_config) = update.mmds {
+            let mmds = vmm
+                .get_mmds()
+                .ok_or(VmmActionError::Mmds(MmdsDatastoreError::NotInitialized))?;
+            let mut mmds = mmds.lock().expect("Poisoned lock");
+            mmds.set_version(mmds_config.version);
+            mmds.set_imds_compat(mmds_config.imds_compat);
+        }
+        if let Some(memory_hotplug_update) = update.memory_hotplug_size {
+            vmm.update_memory_hotplug_size(
+                memory_hotplug_update.requested_size_mib,
+                memory_hotplug_update.timeout_s.map(Duration::from_secs),
+            )
+            .map_err(VmmActionError::MemoryHotplugUpdate)?;
+        }
+        if let Some(vcpu_count_update) = update.vcpu_count {
+            vmm.update_vcpu_count(vcpu_count_update.vcpu_count)?;
+        }
+        Ok(VmmData::Empty)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
+    use vmm_sys_util::tempfile::TempFile;
+
     use super::*;
     use crate::HTTP_MAX_PAYLOAD_SIZE;
-    use crate::builder::tests::default_vmm;
+    use crate::builder::tests::{
+        CustomBlockConfig, default_kernel_cmdline, default_vmm, insert_block_devices,
+    };
     use crate::devices::virtio::block::CacheType;
     use crate::mmds::data_store::MmdsVersion;
     use crate::seccomp::BpfThreadMap;
@@ -1016,6 +1270,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preboot_get_events() {
+        // Other tests may emit events concurrently, so only read from a future cursor.
+        match preboot_request(VmmAction::GetEvents(Some(u64::MAX))).unwrap() {
+            VmmData::Events(batch) => {
+                assert!(batch.events.is_empty());
+                assert_eq!(batch.missed, 0);
+            }
+            data => panic!("Unexpected response: {data:?}"),
+        }
+    }
+
+    #[test]
+    fn test_preboot_get_metrics() {
+        match preboot_request(VmmAction::GetMetrics).unwrap() {
+            VmmData::Metrics(text) => {
+                assert!(text.contains("# TYPE firecracker_vcpu_failures counter\n"));
+                assert!(text.ends_with("# EOF\n"));
+            }
+            data => panic!("Unexpected response: {data:?}"),
+        }
+    }
+
+    #[test]
+    fn test_outcome_without_vmm() {
+        match VmmAction::GetMetrics.outcome_without_vmm() {
+            Some(Ok(VmmData::Metrics(text))) => assert!(text.ends_with("# EOF\n")),
+            outcome => panic!("Unexpected outcome: {outcome:?}"),
+        }
+        match VmmAction::GetEvents(Some(u64::MAX)).outcome_without_vmm() {
+            Some(Ok(VmmData::Events(batch))) => assert!(batch.events.is_empty()),
+            outcome => panic!("Unexpected outcome: {outcome:?}"),
+        }
+        assert!(VmmAction::GetVmInstanceInfo.outcome_without_vmm().is_none());
+        assert!(VmmAction::Pause.outcome_without_vmm().is_none());
+    }
+
     #[test]
     fn test_preboot_get_mmds() {
         assert_eq!(
@@ -1159,6 +1450,9 @@ mod tests {
                 stats_polling_interval_s: 0,
             },
         )));
+        check_unsupported(preboot_request(VmmAction::UpdateBalloonPolicy(
+            BalloonUpdatePolicyConfig::default(),
+        )));
         check_unsupported(preboot_request(VmmAction::UpdateBlockDevice(
             BlockDeviceUpdateConfig::default(),
         )));
@@ -1176,13 +1470,25 @@ mod tests {
                 mem_file_path: PathBuf::new(),
             },
         )));
+        check_unsupported(preboot_request(VmmAction::CreateCoreDump(
+            CreateCoreDumpParams {
+                core_dump_path: PathBuf::new(),
+            },
+        )));
         #[cfg(target_arch = "x86_64")]
         check_unsupported(preboot_request(VmmAction::SendCtrlAltDel));
         check_unsupported(preboot_request(VmmAction::UpdateMemoryHotplugSize(
             MemoryHotplugSizeUpdate {
                 requested_size_mib: 0,
+                timeout_s: None,
             },
         )));
+        check_unsupported(preboot_request(VmmAction::UnplugDevice(
+            DeviceUnplugConfig::Vsock,
+        )));
+        check_unsupported(preboot_request(VmmAction::UpdateVcpuCount(
+            VcpuCountUpdate { vcpu_count: 2 },
+        )));
     }
 
     fn runtime_request(request: VmmAction) -> Result<VmmData, VmmActionError> {
@@ -1200,31 +1506,180 @@ mod tests {
     }
 
     #[test]
-    fn test_runtime_disallowed() {
-        fn check_unsupported(res: Result<VmmData, VmmActionError>) {
+    fn test_preboot_set_vm_config() {
+        let config = VmmConfig {
+            metrics: Some(MetricsConfig {
+                metrics_path: PathBuf::new(),
+            }),
+            ..Default::default()
+        };
+        let res = preboot_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::VmConfig(
+                    ResourcesError::ReplaceLoggerOrMetrics
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_set_vm_config() {
+        let vmm = Arc::new(Mutex::new(default_vmm()));
+        let mut runtime = RuntimeApiController::new(vmm.clone());
+
+        // Sending back the current configuration changes nothing.
+        let config = vmm.lock().unwrap().full_config();
+        runtime
+            .handle_request(VmmAction::SetVmConfig(Box::new(config)))
+            .unwrap();
+
+        let mut config = vmm.lock().unwrap().full_config();
+        config.machine_config.as_mut().unwrap().mem_size_mib += 1;
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::VmConfigUpdate(
+                    FullConfigUpdateError::NotHotUpdatable(_)
+                ))
+            ),
+            "{:?}",
+            res
+        );
+
+        // The vCPU count can be changed, but only when vCPU hotplug is enabled.
+        let mut config = vmm.lock().unwrap().full_config();
+        config.machine_config.as_mut().unwrap().vcpu_count += 1;
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::VcpuHotplug(VcpuHotplugError::Unavailable))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_set_vm_config_rejected_change() {
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        let mut event_manager = EventManager::new().unwrap();
+        let block_configs = vec![CustomBlockConfig::new(
+            String::from("root"),
+            true,
+            None,
+            true,
+            CacheType::Unsafe,
+        )];
+        let _block_files =
+            insert_block_devices(&mut vmm, &mut cmdline, &mut event_manager, block_configs);
+        let vmm = Arc::new(Mutex::new(vmm));
+        let mut runtime = RuntimeApiController::new(vmm.clone());
+        let current_config = vmm.lock().unwrap().full_config();
+
+        // The valid drive change is not applied, because the vCPU count change is rejected.
+        let new_block_file = TempFile::new().unwrap();
+        let mut config = vmm.lock().unwrap().full_config();
+        config.drives[0].path_on_host =
+            Some(new_block_file.as_path().to_str().unwrap().to_string());
+        config.machine_config.as_mut().unwrap().vcpu_count += 1;
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::VcpuHotplug(VcpuHotplugError::Unavailable))
+            ),
+            "{:?}",
+            res
+        );
+        assert_eq!(vmm.lock().unwrap().full_config(), current_config);
+
+        // The same goes for a drive path which cannot be opened.
+        let mut config = vmm.lock().unwrap().full_config();
+        config.drives[0].path_on_host = Some("/invalid/path".to_string());
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::DriveConfig(DriveError::DeviceUpdate(_)))
+            ),
+            "{:?}",
+            res
+        );
+        assert_eq!(vmm.lock().unwrap().full_config(), current_config);
+
+        // And for a memory hotplug size update without a memory hotplug device.
+        let mut config = vmm.lock().unwrap().full_config();
+        config.drives[0].path_on_host =
+            Some(new_block_file.as_path().to_str().unwrap().to_string());
+        config.memory_hotplug_size = Some(MemoryHotplugSizeUpdate {
+            requested_size_mib: 1024,
+            timeout_s: None,
+        });
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(res, Err(VmmActionError::MemoryHotplugUpdate(_))),
+            "{:?}",
+            res
+        );
+        assert_eq!(vmm.lock().unwrap().full_config(), current_config);
+
+        // Or with a timeout which is too large.
+        let mut config = vmm.lock().unwrap().full_config();
+        config.memory_hotplug_size = Some(MemoryHotplugSizeUpdate {
+            requested_size_mib: 1024,
+            timeout_s: Some(u64::MAX),
+        });
+        let res = runtime.handle_request(VmmAction::SetVmConfig(Box::new(config)));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::MemoryHotplugConfig(
+                    MemoryHotplugConfigError::ResizeTimeoutTooLarge(_)
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_update_vcpu_count() {
+        let res = runtime_request(VmmAction::UpdateVcpuCount(VcpuCountUpdate {
+            vcpu_count: 2,
+        }));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::VcpuHotplug(VcpuHotplugError::Unavailable))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_hotplug_without_pci() {
+        fn check_pci_disabled(res: Result<VmmData, VmmActionError>) {
             assert!(
-                matches!(res, Err(VmmActionError::OperationNotSupportedPostBoot)),
+                matches!(
+                    res,
+                    Err(VmmActionError::DeviceHotplug(
+                        DeviceHotplugError::PciDisabled
+                    ))
+                ),
                 "{:?}",
                 res
             );
         }
 
-        check_unsupported(runtime_request(VmmAction::ConfigureBootSource(
-            BootSourceConfig::default(),
-        )));
-        check_unsupported(runtime_request(VmmAction::ConfigureLogger(LoggerConfig {
-            log_path: Some(PathBuf::new()),
-            level: Some(crate::logger::LevelFilter::Debug),
-            show_level: Some(false),
-            show_log_origin: Some(false),
-            module: None,
-        })));
-        check_unsupported(runtime_request(VmmAction::ConfigureMetrics(
-            MetricsConfig {
-                metrics_path: PathBuf::new(),
-            },
-        )));
-        check_unsupported(runtime_request(VmmAction::InsertBlockDevice(
+        check_pci_disabled(runtime_request(VmmAction::InsertBlockDevice(
             BlockDeviceConfig {
                 drive_id: String::new(),
                 partuuid: None,
@@ -1239,7 +1694,7 @@ mod tests {
                 socket: None,
             },
         )));
-        check_unsupported(runtime_request(VmmAction::InsertNetworkDevice(
+        check_pci_disabled(runtime_request(VmmAction::InsertNetworkDevice(
             NetworkInterfaceConfig {
                 iface_id: String::new(),
                 host_dev_name: String::new(),
@@ -1248,23 +1703,48 @@ mod tests {
                 tx_rate_limiter: None,
             },
         )));
-        check_unsupported(runtime_request(VmmAction::SetVsockDevice(
+        check_pci_disabled(runtime_request(VmmAction::SetVsockDevice(
             VsockDeviceConfig {
                 vsock_id: Some(String::new()),
                 guest_cid: 0,
                 uds_path: String::new(),
             },
         )));
-        check_unsupported(runtime_request(VmmAction::SetBalloonDevice(
-            BalloonDeviceConfig::default(),
+        check_pci_disabled(runtime_request(VmmAction::UnplugDevice(
+            DeviceUnplugConfig::Drive {
+                drive_id: String::new(),
+            },
         )));
-        check_unsupported(runtime_request(VmmAction::SetVsockDevice(
-            VsockDeviceConfig {
-                vsock_id: Some(String::new()),
-                guest_cid: 0,
-                uds_path: String::new(),
+    }
+
+    #[test]
+    fn test_runtime_disallowed() {
+        fn check_unsupported(res: Result<VmmData, VmmActionError>) {
+            assert!(
+                matches!(res, Err(VmmActionError::OperationNotSupportedPostBoot)),
+                "{:?}",
+                res
+            );
+        }
+
+        check_unsupported(runtime_request(VmmAction::ConfigureBootSource(
+            BootSourceConfig::default(),
+        )));
+        check_unsupported(runtime_request(VmmAction::ConfigureLogger(LoggerConfig {
+            log_path: Some(PathBuf::new()),
+            level: Some(crate::logger::LevelFilter::Debug),
+            show_level: Some(false),
+            show_log_origin: Some(false),
+            module: None,
+        })));
+        check_unsupported(runtime_request(VmmAction::ConfigureMetrics(
+            MetricsConfig {
+                metrics_path: PathBuf::new(),
             },
         )));
+        check_unsupported(runtime_request(VmmAction::SetBalloonDevice(
+            BalloonDeviceConfig::default(),
+        )));
         check_unsupported(runtime_request(VmmAction::SetMmdsConfiguration(
             MmdsConfig {
                 ipv4_address: None,
@@ -1293,11 +1773,15 @@ mod tests {
         check_unsupported(runtime_request(VmmAction::SetEntropyDevice(
             EntropyDeviceConfig::default(),
         )));
+        check_unsupported(runtime_request(VmmAction::SetConsoleDevice(
+            ConsoleDeviceConfig::default(),
+        )));
         check_unsupported(runtime_request(VmmAction::InsertPmemDevice(PmemConfig {
             id: String::new(),
             path_on_host: String::new(),
             root_device: false,
             read_only: false,
+            shared_lock: false,
         })));
         check_unsupported(runtime_request(VmmAction::SetMemoryHotplugDevice(
             MemoryHotplugConfig::default(),
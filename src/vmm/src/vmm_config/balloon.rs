@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 
 pub use crate::devices::virtio::balloon::BALLOON_DEV_ID;
 pub use crate::devices::virtio::balloon::device::BalloonStats;
+pub use crate::devices::virtio::balloon::policy::BalloonPolicy;
 use crate::devices::virtio::balloon::{Balloon, BalloonConfig};
 
 type MutexBalloon = Arc<Mutex<Balloon>>;
@@ -40,6 +41,9 @@ pub struct BalloonDeviceConfig {
     /// Free page reporting enabled
     #[serde(default)]
     pub free_page_reporting: bool,
+    /// Automatic balloon policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<BalloonPolicy>,
 }
 
 impl From<BalloonConfig> for BalloonDeviceConfig {
@@ -50,6 +54,7 @@ impl From<BalloonConfig> for BalloonDeviceConfig {
             stats_polling_interval_s: state.stats_polling_interval_s,
             free_page_hinting: state.free_page_hinting,
             free_page_reporting: state.free_page_reporting,
+            policy: state.policy,
         }
     }
 }
@@ -74,6 +79,16 @@ pub struct BalloonUpdateStatsConfig {
     pub stats_polling_interval_s: u16,
 }
 
+/// The data fed into a balloon policy update request.
+/// Omitting the policy disables the automatic balloon sizing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalloonUpdatePolicyConfig {
+    /// Automatic balloon policy.
+    #[serde(default)]
+    pub policy: Option<BalloonPolicy>,
+}
+
 /// A builder for `Balloon` devices from 'BalloonDeviceConfig'.
 #[cfg_attr(not(test), derive(Default))]
 #[derive(Debug)]
@@ -90,13 +105,15 @@ impl BalloonBuilder {
     /// Inserts a Balloon device in the store.
     /// If an entry already exists, it will overwrite it.
     pub fn set(&mut self, cfg: BalloonDeviceConfig) -> Result<(), BalloonConfigError> {
-        self.inner = Some(Arc::new(Mutex::new(Balloon::new(
+        let mut balloon = Balloon::new(
             cfg.amount_mib,
             cfg.deflate_on_oom,
             cfg.stats_polling_interval_s,
             cfg.free_page_hinting,
             cfg.free_page_reporting,
-        )?)));
+        )?;
+        balloon.set_policy(cfg.policy)?;
+        self.inner = Some(Arc::new(Mutex::new(balloon)));
 
         Ok(())
     }
@@ -140,6 +157,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         }
     }
 
@@ -152,6 +170,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
         assert_eq!(default_balloon_config, balloon_config);
         let mut builder = BalloonBuilder::new();
@@ -167,6 +186,39 @@ pub(crate) mod tests {
         };
     }
 
+    #[test]
+    fn test_balloon_policy() {
+        let policy = BalloonPolicy {
+            target_free_mib: 64,
+            min_mib: 0,
+            max_mib: 128,
+            max_step_mib: 0,
+            min_interval_s: 0,
+        };
+        let mut builder = BalloonBuilder::new();
+
+        // Policies cannot be used without statistics.
+        builder
+            .set(BalloonDeviceConfig {
+                policy: Some(policy),
+                ..default_config()
+            })
+            .unwrap_err();
+        assert!(builder.get().is_none());
+
+        let balloon_config = BalloonDeviceConfig {
+            stats_polling_interval_s: 1,
+            policy: Some(policy),
+            ..default_config()
+        };
+        builder.set(balloon_config.clone()).unwrap();
+        assert_eq!(builder.get_config().unwrap(), balloon_config);
+
+        // Omitting the policy from an update clears it.
+        let update: BalloonUpdatePolicyConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(update, BalloonUpdatePolicyConfig { policy: None });
+    }
+
     #[test]
     fn test_from_balloon_state() {
         let expected_balloon_config = BalloonDeviceConfig {
@@ -175,6 +227,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 3,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         };
 
         let actual_balloon_config = BalloonDeviceConfig::from(BalloonConfig {
@@ -183,6 +236,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 3,
             free_page_hinting: false,
             free_page_reporting: false,
+            policy: None,
         });
 
         assert_eq!(expected_balloon_config, actual_balloon_config);
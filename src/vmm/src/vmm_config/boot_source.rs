@@ -23,13 +23,59 @@ pub const DEFAULT_KERNEL_CMDLINE: &str = "reboot=k panic=1 nomodule 8250.nr_uart
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct BootSourceConfig {
-    /// Path of the kernel image.
+    /// Path of the kernel image. It is empty when booting a firmware.
+    #[serde(default)]
     pub kernel_image_path: String,
     /// Path of the initrd, if there is one.
     pub initrd_path: Option<String>,
     /// The boot arguments to pass to the kernel. If this field is uninitialized,
     /// DEFAULT_KERNEL_CMDLINE is used.
     pub boot_args: Option<String>,
+    /// Path of the firmware to boot instead of a kernel, if there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware_path: Option<String>,
+    /// Whether to keep a measured boot log of the microVM.
+    #[serde(default)]
+    pub measured_boot: bool,
+}
+
+/// The boot source configuration saved in snapshots.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BootSourceState {
+    /// Path of the kernel image. It is empty when a firmware was booted.
+    pub kernel_image_path: String,
+    /// Path of the initrd, if there is one.
+    pub initrd_path: Option<String>,
+    /// The boot arguments passed to the kernel.
+    pub boot_args: Option<String>,
+    /// Path of the firmware booted instead of a kernel, if there is one.
+    pub firmware_path: Option<String>,
+    /// Whether a measured boot log was kept.
+    pub measured_boot: bool,
+}
+
+impl From<&BootSourceConfig> for BootSourceState {
+    fn from(config: &BootSourceConfig) -> Self {
+        Self {
+            kernel_image_path: config.kernel_image_path.clone(),
+            initrd_path: config.initrd_path.clone(),
+            boot_args: config.boot_args.clone(),
+            firmware_path: config.firmware_path.clone(),
+            measured_boot: config.measured_boot,
+        }
+    }
+}
+
+impl From<BootSourceState> for BootSourceConfig {
+    fn from(state: BootSourceState) -> Self {
+        Self {
+            kernel_image_path: state.kernel_image_path,
+            initrd_path: state.initrd_path,
+            boot_args: state.boot_args,
+            firmware_path: state.firmware_path,
+            measured_boot: state.measured_boot,
+        }
+    }
 }
 
 /// Errors associated with actions on `BootSourceConfig`.
@@ -41,6 +87,14 @@ pub enum BootSourceConfigError {
     InvalidInitrdPath(io::Error),
     /// The kernel command line is invalid: {0}
     InvalidKernelCommandLine(String),
+    /// The firmware file cannot be opened: {0}
+    InvalidFirmwarePath(io::Error),
+    /// Exactly one of the kernel image and the firmware must be set.
+    InvalidBootImage,
+    /// An initrd cannot be used when booting a firmware.
+    FirmwareInitrd,
+    /// Booting a firmware is only supported on x86_64.
+    FirmwareUnsupported,
 }
 
 /// Holds the kernel specification (both configuration as well as runtime details).
@@ -58,21 +112,35 @@ pub struct BootSource {
 pub struct BootConfig {
     /// The commandline validated against correctness.
     pub cmdline: linux_loader::cmdline::Cmdline,
-    /// The descriptor to the kernel file.
+    /// The descriptor to the kernel file, or to the firmware file when `firmware` is set.
     pub kernel_file: File,
+    /// Whether a firmware is booted instead of a kernel.
+    pub firmware: bool,
     /// The descriptor to the initrd file, if there is one.
     pub initrd_file: Option<File>,
+    /// Whether the boot components are measured.
+    pub measured: bool,
 }
 
 impl BootConfig {
     /// Creates the BootConfig based on a given configuration.
     pub fn new(cfg: &BootSourceConfig) -> Result<Self, BootSourceConfigError> {
         use self::BootSourceConfigError::{
-            InvalidInitrdPath, InvalidKernelCommandLine, InvalidKernelPath,
+            FirmwareInitrd, InvalidBootImage, InvalidInitrdPath, InvalidKernelCommandLine,
+            InvalidKernelPath,
         };
 
         // Validate boot source config.
-        let kernel_file = File::open(&cfg.kernel_image_path).map_err(InvalidKernelPath)?;
+        let kernel_file = match &cfg.firmware_path {
+            None => File::open(&cfg.kernel_image_path).map_err(InvalidKernelPath)?,
+            Some(_) if !cfg.kernel_image_path.is_empty() => return Err(InvalidBootImage),
+            // The firmware finds the kernel and the initrd on the root block device.
+            Some(_) if cfg.initrd_path.is_some() => return Err(FirmwareInitrd),
+            #[cfg(target_arch = "aarch64")]
+            Some(_) => return Err(BootSourceConfigError::FirmwareUnsupported),
+            #[cfg(target_arch = "x86_64")]
+            Some(path) => File::open(path).map_err(BootSourceConfigError::InvalidFirmwarePath)?,
+        };
         let initrd_file: Option<File> = match &cfg.initrd_path {
             Some(path) => Some(File::open(path).map_err(InvalidInitrdPath)?),
             None => None,
@@ -89,7 +157,9 @@ impl BootConfig {
         Ok(BootConfig {
             cmdline,
             kernel_file,
+            firmware: cfg.firmware_path.is_some(),
             initrd_file,
+            measured: cfg.measured_boot,
         })
     }
 }
@@ -110,32 +180,89 @@ pub(crate) mod tests {
             boot_args: None,
             initrd_path: None,
             kernel_image_path: kernel_path,
+            firmware_path: None,
+            measured_boot: false,
         };
 
         let boot_cfg = BootConfig::new(&boot_src_cfg).unwrap();
         assert!(boot_cfg.initrd_file.is_none());
+        assert!(!boot_cfg.firmware);
+        assert!(!boot_cfg.measured);
         assert_eq!(
             boot_cfg.cmdline.as_cstring().unwrap().as_bytes_with_nul(),
             [DEFAULT_KERNEL_CMDLINE.as_bytes(), b"\0"].concat()
         );
     }
 
+    #[test]
+    fn test_firmware_boot_config() {
+        let firmware_file = TempFile::new().unwrap();
+        let firmware_path = firmware_file.as_path().to_str().unwrap().to_string();
+
+        let mut boot_src_cfg = BootSourceConfig {
+            firmware_path: Some(firmware_path.clone()),
+            ..Default::default()
+        };
+        #[cfg(target_arch = "x86_64")]
+        assert!(BootConfig::new(&boot_src_cfg).unwrap().firmware);
+        #[cfg(target_arch = "aarch64")]
+        assert!(matches!(
+            BootConfig::new(&boot_src_cfg),
+            Err(BootSourceConfigError::FirmwareUnsupported)
+        ));
+
+        boot_src_cfg.initrd_path = Some(firmware_path.clone());
+        assert!(matches!(
+            BootConfig::new(&boot_src_cfg),
+            Err(BootSourceConfigError::FirmwareInitrd)
+        ));
+
+        boot_src_cfg.initrd_path = None;
+        boot_src_cfg.kernel_image_path = firmware_path;
+        assert!(matches!(
+            BootConfig::new(&boot_src_cfg),
+            Err(BootSourceConfigError::InvalidBootImage)
+        ));
+    }
+
     #[test]
     fn test_serde() {
-        let boot_src_cfg = BootSourceConfig {
+        let boot_src_state = BootSourceState {
             boot_args: Some(DEFAULT_KERNEL_CMDLINE.to_string()),
             initrd_path: Some("/tmp/initrd".to_string()),
             kernel_image_path: "./vmlinux.bin".to_string(),
+            firmware_path: None,
+            measured_boot: false,
         };
 
         // Use bitcode serialization directly for the test data
-        let serialized_data = bitcode::serialize(&boot_src_cfg).unwrap();
-        let restored_boot_cfg: BootSourceConfig = bitcode::deserialize(&serialized_data).unwrap();
-        assert_eq!(boot_src_cfg, restored_boot_cfg);
+        let serialized_data = bitcode::serialize(&boot_src_state).unwrap();
+        let restored_boot_state: BootSourceState = bitcode::deserialize(&serialized_data).unwrap();
+        assert_eq!(boot_src_state, restored_boot_state);
 
         // Also test with Snapshot wrapper
-        let snapshot_data = bitcode::serialize(&Snapshot::new(boot_src_cfg.clone())).unwrap();
+        let snapshot_data = bitcode::serialize(&Snapshot::new(boot_src_state.clone())).unwrap();
         let restored_snapshot = Snapshot::load_without_crc_check(&snapshot_data).unwrap();
-        assert_eq!(boot_src_cfg, restored_snapshot.data);
+        assert_eq!(boot_src_state, restored_snapshot.data);
+    }
+
+    #[test]
+    fn test_boot_source_state() {
+        let boot_src_cfg = BootSourceConfig {
+            boot_args: Some(DEFAULT_KERNEL_CMDLINE.to_string()),
+            initrd_path: Some("/tmp/initrd".to_string()),
+            kernel_image_path: "./vmlinux.bin".to_string(),
+            firmware_path: None,
+            measured_boot: true,
+        };
+        let state = BootSourceState::from(&boot_src_cfg);
+        assert_eq!(BootSourceConfig::from(state), boot_src_cfg);
+
+        let boot_src_cfg = BootSourceConfig {
+            firmware_path: Some("./hypervisor-fw".to_string()),
+            ..Default::default()
+        };
+        let state = BootSourceState::from(&boot_src_cfg);
+        assert_eq!(BootSourceConfig::from(state), boot_src_cfg);
     }
 }
@@ -0,0 +1,127 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::virtio::console::{Console, ConsoleError};
+
+/// Configuration of a port of the virtio-console device.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConsolePortConfig {
+    /// Name of the port, exposed in the guest as `/dev/virtio-ports/<name>`.
+    pub name: String,
+    /// Whether the guest uses the port as a console, exposed as a `/dev/hvc<N>` device.
+    #[serde(default)]
+    pub console: bool,
+    /// Path of the Unix socket on which a client can attach to the port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uds_path: Option<String>,
+    /// Path of the file or named pipe to which the output of the guest is written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<String>,
+}
+
+/// This struct represents the strongly typed equivalent of the json body from console device
+/// related requests.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConsoleDeviceConfig {
+    /// Ports of the device, numbered in this order.
+    pub ports: Vec<ConsolePortConfig>,
+}
+
+impl From<&Console> for ConsoleDeviceConfig {
+    fn from(dev: &Console) -> Self {
+        ConsoleDeviceConfig {
+            ports: dev.port_configs(),
+        }
+    }
+}
+
+/// Errors that can occur while handling configuration for
+/// a console device
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ConsoleDeviceError {
+    /// Could not create console device: {0}
+    CreateDevice(#[from] ConsoleError),
+}
+
+/// A builder type used to construct a console device
+#[derive(Debug, Default)]
+pub struct ConsoleDeviceBuilder(Option<Arc<Mutex<Console>>>);
+
+impl ConsoleDeviceBuilder {
+    /// Create a new instance for the builder
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    /// Build a console device and return a (counted) reference to it protected by a mutex
+    pub fn build(
+        &mut self,
+        config: ConsoleDeviceConfig,
+    ) -> Result<Arc<Mutex<Console>>, ConsoleDeviceError> {
+        let dev = Arc::new(Mutex::new(Console::new(config.ports)?));
+        self.0 = Some(dev.clone());
+
+        Ok(dev)
+    }
+
+    /// Insert a new console device from a configuration object
+    pub fn insert(&mut self, config: ConsoleDeviceConfig) -> Result<(), ConsoleDeviceError> {
+        let _ = self.build(config)?;
+        Ok(())
+    }
+
+    /// Get a reference to the console device, if present
+    pub fn get(&self) -> Option<&Arc<Mutex<Console>>> {
+        self.0.as_ref()
+    }
+
+    /// Get the configuration of the console device (if any)
+    pub fn config(&self) -> Option<ConsoleDeviceConfig> {
+        self.0
+            .as_ref()
+            .map(|dev| ConsoleDeviceConfig::from(dev.lock().unwrap().deref()))
+    }
+
+    /// Set the console device from an already created object
+    pub fn set_device(&mut self, device: Arc<Mutex<Console>>) {
+        self.0 = Some(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_console_device_create() {
+        let output = TempFile::new().unwrap();
+        let config = ConsoleDeviceConfig {
+            ports: vec![ConsolePortConfig {
+                name: "console".to_string(),
+                console: true,
+                uds_path: None,
+                output_path: Some(output.as_path().to_str().unwrap().to_string()),
+            }],
+        };
+        let mut builder = ConsoleDeviceBuilder::new();
+        assert!(builder.get().is_none());
+
+        builder.insert(config.clone()).unwrap();
+        assert!(builder.get().is_some());
+        assert_eq!(builder.config().unwrap(), config);
+
+        // An invalid configuration is rejected.
+        builder
+            .insert(ConsoleDeviceConfig { ports: vec![] })
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::{Map, Value};
+
+use crate::resources::VmmConfig;
+use crate::vmm_config::RateLimiterConfig;
+use crate::vmm_config::balloon::{
+    BalloonUpdateConfig, BalloonUpdatePolicyConfig, BalloonUpdateStatsConfig,
+};
+use crate::vmm_config::drive::BlockDeviceUpdateConfig;
+use crate::vmm_config::memory_hotplug::MemoryHotplugSizeUpdate;
+use crate::vmm_config::mmds::MmdsConfig;
+use crate::vmm_config::net::NetworkInterfaceUpdateConfig;
+use crate::vmm_config::vcpu_hotplug::VcpuCountUpdate;
+
+/// Fields of the microVM configuration which can be changed after the microVM has started.
+/// Changing a field also changes the fields nested under it.
+const HOT_UPDATABLE_FIELDS: &[&str] = &[
+    "drives[*].path_on_host",
+    "drives[*].rate_limiter",
+    "network-interfaces[*].rx_rate_limiter",
+    "network-interfaces[*].tx_rate_limiter",
+    "balloon.amount_mib",
+    "balloon.stats_polling_interval_s",
+    "balloon.policy",
+    "mmds-config.version",
+    "mmds-config.imds_compat",
+    "memory-hotplug-size",
+    "machine-config.vcpu_count",
+];
+
+/// Lists of the microVM configuration whose items are identified by a field rather than by their
+/// position, with the name of that field.
+const KEYED_LISTS: &[(&str, &str)] = &[
+    ("drives", "drive_id"),
+    ("network-interfaces", "iface_id"),
+    ("pmem", "id"),
+];
+
+/// Errors associated with updating the configuration of a running microVM.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum FullConfigUpdateError {
+    /// Cannot serialize the microVM configuration: {0}
+    Serialize(#[from] serde_json::Error),
+    /// The following fields cannot be changed after the microVM has started: {0}
+    NotHotUpdatable(String),
+}
+
+/// The updates needed to bring a running microVM to a new configuration.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FullConfigUpdate {
+    /// Updates of the block devices.
+    pub drives: Vec<BlockDeviceUpdateConfig>,
+    /// Updates of the network interfaces.
+    pub network_interfaces: Vec<NetworkInterfaceUpdateConfig>,
+    /// Update of the balloon target size.
+    pub balloon: Option<BalloonUpdateConfig>,
+    /// Update of the balloon statistics polling interval.
+    pub balloon_statistics: Option<BalloonUpdateStatsConfig>,
+    /// Update of the automatic balloon policy.
+    pub balloon_policy: Option<BalloonUpdatePolicyConfig>,
+    /// New MMDS configuration, of which only the version and the IMDS compatibility change.
+    pub mmds: Option<MmdsConfig>,
+    /// Update of the hotpluggable memory size.
+    pub memory_hotplug_size: Option<MemoryHotplugSizeUpdate>,
+    /// Update of the number of online vCPUs.
+    pub vcpu_count: Option<VcpuCountUpdate>,
+}
+
+impl FullConfigUpdate {
+    /// Computes the updates turning the `current` configuration into the `new` one.
+    ///
+    /// Fails, listing the changed fields, if any of the changes cannot be applied to a running
+    /// microVM.
+    pub fn new(mut current: VmmConfig, mut new: VmmConfig) -> Result<Self, FullConfigUpdateError> {
+        // The order in which the interfaces forwarding MMDS requests are listed does not matter.
+        for config in [&mut current, &mut new] {
+            if let Some(mmds_config) = config.mmds_config.as_mut() {
+                mmds_config.network_interfaces.sort();
+            }
+        }
+
+        let mut changes = Vec::new();
+        diff_values(
+            &mut changes,
+            "",
+            "",
+            &serde_json::to_value(&current)?,
+            &serde_json::to_value(&new)?,
+        );
+        let rejected: Vec<_> = changes
+            .into_iter()
+            .filter(|(pattern, _)| !is_hot_updatable(pattern))
+            .map(|(_, path)| path)
+            .collect();
+        if !rejected.is_empty() {
+            return Err(FullConfigUpdateError::NotHotUpdatable(rejected.join(", ")));
+        }
+
+        let mut update = FullConfigUpdate::default();
+
+        // The lists were checked above to hold the same items.
+        for drive in new.drives {
+            let current_drive = current
+                .drives
+                .iter()
+                .find(|current_drive| current_drive.drive_id == drive.drive_id)
+                .expect("Missing drive");
+            let path_changed = drive.path_on_host != current_drive.path_on_host;
+            let rate_limiter_changed = drive.rate_limiter != current_drive.rate_limiter;
+            if path_changed || rate_limiter_changed {
+                update.drives.push(BlockDeviceUpdateConfig {
+                    drive_id: drive.drive_id,
+                    path_on_host: drive.path_on_host.filter(|_| path_changed),
+                    rate_limiter: rate_limiter_changed
+                        .then(|| replace_rate_limiter(drive.rate_limiter)),
+                });
+            }
+        }
+
+        for iface in new.network_interfaces {
+            let current_iface = current
+                .network_interfaces
+                .iter()
+                .find(|current_iface| current_iface.iface_id == iface.iface_id)
+                .expect("Missing network interface");
+            let rx_changed = iface.rx_rate_limiter != current_iface.rx_rate_limiter;
+            let tx_changed = iface.tx_rate_limiter != current_iface.tx_rate_limiter;
+            if rx_changed || tx_changed {
+                update
+                    .network_interfaces
+                    .push(NetworkInterfaceUpdateConfig {
+                        iface_id: iface.iface_id,
+                        rx_rate_limiter: rx_changed
+                            .then(|| replace_rate_limiter(iface.rx_rate_limiter)),
+                        tx_rate_limiter: tx_changed
+                            .then(|| replace_rate_limiter(iface.tx_rate_limiter)),
+                    });
+            }
+        }
+
+        // A balloon cannot be added or removed, so both configurations either have one or not.
+        if let (Some(current_balloon), Some(balloon)) = (current.balloon, new.balloon) {
+            if balloon.amount_mib != current_balloon.amount_mib {
+                update.balloon = Some(BalloonUpdateConfig {
+                    amount_mib: balloon.amount_mib,
+                });
+            }
+            if balloon.stats_polling_interval_s != current_balloon.stats_polling_interval_s {
+                update.balloon_statistics = Some(BalloonUpdateStatsConfig {
+                    stats_polling_interval_s: balloon.stats_polling_interval_s,
+                });
+            }
+            if balloon.policy != current_balloon.policy {
+                update.balloon_policy = Some(BalloonUpdatePolicyConfig {
+                    policy: balloon.policy,
+                });
+            }
+        }
+
+        if new.mmds_config != current.mmds_config {
+            update.mmds = new.mmds_config;
+        }
+
+        // The hotpluggable memory size is never part of the current configuration.
+        update.memory_hotplug_size = new.memory_hotplug_size;
+
+        if let (Some(current_machine_config), Some(machine_config)) =
+            (current.machine_config, new.machine_config)
+            && machine_config.vcpu_count != current_machine_config.vcpu_count
+        {
+            update.vcpu_count = Some(VcpuCountUpdate {
+                vcpu_count: machine_config.vcpu_count,
+            });
+        }
+
+        Ok(update)
+    }
+}
+
+// Rate limiter updates only change the buckets they specify. A bucket missing from the new
+// configuration is updated to an empty one instead, which disables it.
+fn replace_rate_limiter(rate_limiter: Option<RateLimiterConfig>) -> RateLimiterConfig {
+    let rate_limiter = rate_limiter.unwrap_or_default();
+    RateLimiterConfig {
+        bandwidth: Some(rate_limiter.bandwidth.unwrap_or_default()),
+        ops: Some(rate_limiter.ops.unwrap_or_default()),
+    }
+}
+
+fn is_hot_updatable(pattern: &str) -> bool {
+    HOT_UPDATABLE_FIELDS.iter().any(|field| {
+        pattern
+            .strip_prefix(field)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+fn join_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+// Returns the items of a keyed list, indexed by the value of their key field.
+fn keyed_items<'a>(list: &'a [Value], key: &str) -> Vec<(String, &'a Value)> {
+    list.iter()
+        .map(|item| {
+            let id = match &item[key] {
+                Value::String(id) => id.clone(),
+                id => id.to_string(),
+            };
+            (id, item)
+        })
+        .collect()
+}
+
+// Records the fields which differ between `current` and `new`. Each change is recorded both as a
+// pattern, where list items are written as `[*]`, and as the path of the field. Missing fields are
+// the same as null ones.
+fn diff_values(
+    changes: &mut Vec<(String, String)>,
+    pattern: &str,
+    path: &str,
+    current: &Value,
+    new: &Value,
+) {
+    if current == new {
+        return;
+    }
+
+    match (current, new) {
+        (Value::Object(current), Value::Object(new)) => {
+            diff_objects(changes, pattern, path, current, new);
+        }
+        (Value::Array(current), Value::Array(new)) => {
+            let key = KEYED_LISTS
+                .iter()
+                .find(|(list, _)| *list == pattern)
+                .map(|(_, key)| *key);
+            let Some(key) = key else {
+                changes.push((pattern.to_string(), path.to_string()));
+                return;
+            };
+
+            let current = keyed_items(current, key);
+            let new = keyed_items(new, key);
+            let mut ids: Vec<&String> = Vec::new();
+            for (id, _) in current.iter().chain(new.iter()) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+            for id in ids {
+                let find = |items: &[(String, &Value)]| {
+                    items
+                        .iter()
+                        .find(|(item_id, _)| item_id == id)
+                        .map_or(Value::Null, |(_, item)| (*item).clone())
+                };
+                let current_item = find(&current);
+                let new_item = find(&new);
+                let item_pattern = format!("{pattern}[*]");
+                let item_path = format!("{path}[{id}]");
+                // An item is added or removed as a whole.
+                if current_item.is_null() || new_item.is_null() {
+                    changes.push((item_pattern, item_path));
+                } else {
+                    diff_values(changes, &item_pattern, &item_path, &current_item, &new_item);
+                }
+            }
+        }
+        _ => changes.push((pattern.to_string(), path.to_string())),
+    }
+}
+
+fn diff_objects(
+    changes: &mut Vec<(String, String)>,
+    pattern: &str,
+    path: &str,
+    current: &Map<String, Value>,
+    new: &Map<String, Value>,
+) {
+    let mut fields: Vec<_> = current.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    for field in fields {
+        diff_values(
+            changes,
+            &join_path(pattern, field),
+            &join_path(path, field),
+            current.get(field).unwrap_or(&Value::Null),
+            new.get(field).unwrap_or(&Value::Null),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vmm_config::TokenBucketConfig;
+
+    fn config(json: &str) -> VmmConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const CURRENT: &str = r#"{
+        "boot-source": { "kernel_image_path": "vmlinux" },
+        "drives": [
+            { "drive_id": "rootfs", "path_on_host": "rootfs", "is_root_device": true },
+            { "drive_id": "scratch", "path_on_host": "scratch", "is_root_device": false }
+        ],
+        "network-interfaces": [
+            { "iface_id": "eth0", "host_dev_name": "tap0" },
+            { "iface_id": "eth1", "host_dev_name": "tap1" }
+        ],
+        "balloon": { "amount_mib": 64, "deflate_on_oom": true },
+        "mmds-config": { "network_interfaces": ["eth0", "eth1"] }
+    }"#;
+
+    #[test]
+    fn test_no_changes() {
+        let update = FullConfigUpdate::new(config(CURRENT), config(CURRENT)).unwrap();
+        assert_eq!(update, FullConfigUpdate::default());
+    }
+
+    #[test]
+    fn test_hot_updatable_changes() {
+        let new = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux" },
+            "drives": [
+                { "drive_id": "scratch", "path_on_host": "scratch2", "is_root_device": false },
+                {
+                    "drive_id": "rootfs",
+                    "path_on_host": "rootfs",
+                    "is_root_device": true,
+                    "rate_limiter": { "ops": { "size": 100, "refill_time": 1000 } }
+                }
+            ],
+            "network-interfaces": [
+                { "iface_id": "eth0", "host_dev_name": "tap0" },
+                {
+                    "iface_id": "eth1",
+                    "host_dev_name": "tap1",
+                    "tx_rate_limiter": { "bandwidth": { "size": 1000, "refill_time": 100 } }
+                }
+            ],
+            "balloon": { "amount_mib": 128, "deflate_on_oom": true },
+            "mmds-config": { "network_interfaces": ["eth1", "eth0"], "version": "V2" },
+            "memory-hotplug-size": { "requested_size_mib": 1024, "timeout_s": 30 }
+        }"#;
+        let update = FullConfigUpdate::new(config(CURRENT), config(new)).unwrap();
+
+        let ops = TokenBucketConfig {
+            size: 100,
+            one_time_burst: None,
+            refill_time: 1000,
+        };
+        assert_eq!(
+            update.drives,
+            vec![
+                BlockDeviceUpdateConfig {
+                    drive_id: "scratch".to_string(),
+                    path_on_host: Some("scratch2".to_string()),
+                    rate_limiter: None,
+                },
+                BlockDeviceUpdateConfig {
+                    drive_id: "rootfs".to_string(),
+                    path_on_host: None,
+                    // The missing bandwidth bucket is disabled.
+                    rate_limiter: Some(RateLimiterConfig {
+                        bandwidth: Some(TokenBucketConfig::default()),
+                        ops: Some(ops),
+                    }),
+                },
+            ]
+        );
+        assert_eq!(update.network_interfaces.len(), 1);
+        assert_eq!(update.network_interfaces[0].iface_id, "eth1");
+        assert!(update.network_interfaces[0].rx_rate_limiter.is_none());
+        assert!(update.network_interfaces[0].tx_rate_limiter.is_some());
+        assert_eq!(
+            update.balloon,
+            Some(BalloonUpdateConfig { amount_mib: 128 })
+        );
+        assert!(update.balloon_statistics.is_none());
+        assert!(update.balloon_policy.is_none());
+        assert_eq!(
+            update.mmds.unwrap().version,
+            crate::mmds::data_store::MmdsVersion::V2
+        );
+        assert_eq!(
+            update.memory_hotplug_size,
+            Some(MemoryHotplugSizeUpdate {
+                requested_size_mib: 1024,
+                timeout_s: Some(30),
+            })
+        );
+    }
+
+    #[test]
+    fn test_vcpu_count_change() {
+        let current = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux" },
+            "machine-config": { "vcpu_count": 2, "max_vcpu_count": 4, "mem_size_mib": 128 }
+        }"#;
+        let new = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux" },
+            "machine-config": { "vcpu_count": 4, "max_vcpu_count": 4, "mem_size_mib": 128 }
+        }"#;
+        let update = FullConfigUpdate::new(config(current), config(new)).unwrap();
+        assert_eq!(update.vcpu_count, Some(VcpuCountUpdate { vcpu_count: 4 }));
+
+        let new = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux" },
+            "machine-config": { "vcpu_count": 2, "max_vcpu_count": 8, "mem_size_mib": 128 }
+        }"#;
+        let error = FullConfigUpdate::new(config(current), config(new)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The following fields cannot be changed after the microVM has started: \
+             machine-config.max_vcpu_count"
+        );
+    }
+
+    #[test]
+    fn test_rejected_changes() {
+        let new = r#"{
+            "boot-source": { "kernel_image_path": "vmlinux2" },
+            "drives": [
+                { "drive_id": "rootfs", "path_on_host": "rootfs", "is_root_device": false }
+            ],
+            "network-interfaces": [
+                { "iface_id": "eth0", "host_dev_name": "tap0" },
+                { "iface_id": "eth1", "host_dev_name": "tap1" },
+                { "iface_id": "eth2", "host_dev_name": "tap2" }
+            ],
+            "balloon": { "amount_mib": 128, "deflate_on_oom": false },
+            "mmds-config": { "network_interfaces": ["eth0"] }
+        }"#;
+        let error = FullConfigUpdate::new(config(CURRENT), config(new)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The following fields cannot be changed after the microVM has started: \
+             balloon.deflate_on_oom, boot-source.kernel_image_path, \
+             drives[rootfs].is_root_device, drives[scratch], mmds-config.network_interfaces, \
+             network-interfaces[eth2]"
+        );
+    }
+}
@@ -4,6 +4,8 @@ use std::fmt::{self, Display, Formatter};
 
 use serde::{Serialize, ser};
 
+use crate::measured_boot::BootMeasurement;
+
 /// Enumerates microVM runtime states.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum VmState {
@@ -14,6 +16,8 @@ pub enum VmState {
     Paused,
     /// Vm is running
     Running,
+    /// Vm is paused after a guest kernel panic
+    Panicked,
 }
 
 impl Display for VmState {
@@ -22,6 +26,7 @@ impl Display for VmState {
             VmState::NotStarted => write!(f, "Not started"),
             VmState::Paused => write!(f, "Paused"),
             VmState::Running => write!(f, "Running"),
+            VmState::Panicked => write!(f, "Panicked"),
         }
     }
 }
@@ -46,4 +51,7 @@ pub struct InstanceInfo {
     pub vmm_version: String,
     /// The name of the application that runs the microVM.
     pub app_name: String,
+    /// The measured boot log of the microVM, empty until the microVM is started.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub boot_measurements: Vec<BootMeasurement>,
 }
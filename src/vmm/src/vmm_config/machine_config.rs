@@ -22,11 +22,16 @@ pub enum MachineConfigError {
     InvalidMemorySize,
     /// The number of vCPUs must be greater than 0, less than {MAX_SUPPORTED_VCPUS:} and must be 1 or an even number if SMT is enabled.
     InvalidVcpuCount,
+    /// The maximum number of vCPUs must be at least the number of vCPUs, less than {MAX_SUPPORTED_VCPUS:} and must be 1 or an even number if SMT is enabled.
+    InvalidMaxVcpuCount,
     /// Could not get the configuration of the previously installed balloon device to validate the memory size.
     InvalidVmState,
     /// Enabling simultaneous multithreading is not supported on aarch64.
     #[cfg(target_arch = "aarch64")]
     SmtNotSupported,
+    /// Hotplugging vCPUs is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    VcpuHotplugNotSupported,
     /// Could not determine host kernel version when checking hugetlbfs compatibility
     KernelVersion,
 }
@@ -93,6 +98,9 @@ impl From<HugePageConfig> for Option<memfd::HugetlbSize> {
 pub struct MachineConfig {
     /// Number of vcpu to start.
     pub vcpu_count: u8,
+    /// Maximum number of vcpus, including the ones hotplugged after boot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_vcpu_count: Option<u8>,
     /// The memory size in MiB.
     pub mem_size_mib: usize,
     /// Enables or disabled SMT.
@@ -150,6 +158,7 @@ impl Default for MachineConfig {
     fn default() -> Self {
         Self {
             vcpu_count: 1,
+            max_vcpu_count: None,
             mem_size_mib: DEFAULT_MEM_SIZE_MIB,
             smt: false,
             cpu_template: None,
@@ -173,6 +182,9 @@ pub struct MachineConfigUpdate {
     /// Number of vcpu to start.
     #[serde(default)]
     pub vcpu_count: Option<u8>,
+    /// Maximum number of vcpus, including the ones hotplugged after boot.
+    #[serde(default)]
+    pub max_vcpu_count: Option<u8>,
     /// The memory size in MiB.
     #[serde(default)]
     pub mem_size_mib: Option<usize>,
@@ -207,6 +219,7 @@ impl From<MachineConfig> for MachineConfigUpdate {
     fn from(cfg: MachineConfig) -> Self {
         MachineConfigUpdate {
             vcpu_count: Some(cfg.vcpu_count),
+            max_vcpu_count: cfg.max_vcpu_count,
             mem_size_mib: Some(cfg.mem_size_mib),
             smt: Some(cfg.smt),
             cpu_template: cfg.static_template(),
@@ -224,6 +237,12 @@ impl MachineConfig {
         self.cpu_template = Some(CpuTemplateType::Custom(cpu_template));
     }
 
+    /// Returns the maximum number of vCPUs, which is the number of vCPUs if vCPU hotplug is
+    /// not enabled.
+    pub fn max_vcpus(&self) -> u8 {
+        self.max_vcpu_count.unwrap_or(self.vcpu_count)
+    }
+
     fn static_template(&self) -> Option<StaticCpuTemplate> {
         match self.cpu_template {
             Some(CpuTemplateType::Static(template)) => Some(template),
@@ -259,6 +278,21 @@ impl MachineConfig {
             return Err(MachineConfigError::InvalidVcpuCount);
         }
 
+        let max_vcpu_count = update.max_vcpu_count.or(self.max_vcpu_count);
+
+        #[cfg(target_arch = "aarch64")]
+        if max_vcpu_count.is_some() {
+            return Err(MachineConfigError::VcpuHotplugNotSupported);
+        }
+
+        if let Some(max_vcpu_count) = max_vcpu_count
+            && (max_vcpu_count < vcpu_count
+                || max_vcpu_count > MAX_SUPPORTED_VCPUS
+                || (smt && max_vcpu_count > 1 && max_vcpu_count % 2 == 1))
+        {
+            return Err(MachineConfigError::InvalidMaxVcpuCount);
+        }
+
         let mem_size_mib = update.mem_size_mib.unwrap_or(self.mem_size_mib);
         let page_config = update.huge_pages.unwrap_or(self.huge_pages);
 
@@ -274,6 +308,7 @@ impl MachineConfig {
 
         Ok(MachineConfig {
             vcpu_count,
+            max_vcpu_count,
             mem_size_mib,
             smt,
             cpu_template,
@@ -22,8 +22,13 @@ pub enum MemoryHotplugConfigError {
     TotalSizeTooSmall(usize),
     /// Total size must be a multiple of slot size ({0} MiB)
     TotalSizeNotMultipleOfSlotSize(usize),
+    /// Resize timeout must not be greater than {0} seconds
+    ResizeTimeoutTooLarge(u64),
 }
 
+/// Maximum time in seconds the guest can be given to reach a requested size.
+pub const MAX_RESIZE_TIMEOUT_S: u64 = 24 * 60 * 60;
+
 fn default_block_size_mib() -> usize {
     VIRTIO_MEM_DEFAULT_BLOCK_SIZE_MIB
 }
@@ -102,6 +107,25 @@ impl From<&VirtioMem> for MemoryHotplugConfig {
 pub struct MemoryHotplugSizeUpdate {
     /// Requested size in MiB to resize the hotpluggable memory to.
     pub requested_size_mib: usize,
+    /// Time in seconds the guest has to reach the requested size before the resize is reported
+    /// as timed out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_s: Option<u64>,
+}
+
+impl MemoryHotplugSizeUpdate {
+    /// Validates the update.
+    pub fn validate(&self) -> Result<(), MemoryHotplugConfigError> {
+        if self
+            .timeout_s
+            .is_some_and(|timeout_s| timeout_s > MAX_RESIZE_TIMEOUT_S)
+        {
+            return Err(MemoryHotplugConfigError::ResizeTimeoutTooLarge(
+                MAX_RESIZE_TIMEOUT_S,
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -204,6 +228,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_resize_timeout_too_large() {
+        let mut update = MemoryHotplugSizeUpdate {
+            requested_size_mib: 512,
+            timeout_s: None,
+        };
+        update.validate().unwrap();
+
+        update.timeout_s = Some(MAX_RESIZE_TIMEOUT_S);
+        update.validate().unwrap();
+
+        update.timeout_s = Some(u64::MAX);
+        match update.validate() {
+            Err(MemoryHotplugConfigError::ResizeTimeoutTooLarge(max)) => {
+                assert_eq!(max, MAX_RESIZE_TIMEOUT_S)
+            }
+            _ => panic!("Expected ResizeTimeoutTooLarge error"),
+        }
+    }
+
     #[test]
     fn test_defaults() {
         assert_eq!(default_block_size_mib(), 2);
@@ -12,10 +12,14 @@ use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenBucket};
 pub mod balloon;
 /// Wrapper for configuring the microVM boot source.
 pub mod boot_source;
+/// Wrapper for configuring the console device attached to the microVM.
+pub mod console;
 /// Wrapper for configuring the block devices.
 pub mod drive;
 /// Wrapper for configuring the entropy device attached to the microVM.
 pub mod entropy;
+/// Wrapper for updating the whole configuration of a running microVM.
+pub mod full_config;
 /// Wrapper over the microVM general information attached to the microVM.
 pub mod instance_info;
 /// Wrapper for configuring the memory and CPU of the microVM.
@@ -28,11 +32,15 @@ pub mod metrics;
 pub mod mmds;
 /// Wrapper for configuring the network devices attached to the microVM.
 pub mod net;
+/// Wrapper for hotplugging and unplugging PCI devices on a running microVM.
+pub mod pci_hotplug;
 /// Wrapper for configuring the pmem devises attached to the microVM.
 pub mod pmem;
 /// Wrapper for configuring microVM snapshots and the microVM state.
 pub mod serial;
 pub mod snapshot;
+/// Wrapper for hotplugging and unplugging vCPUs on a running microVM.
+pub mod vcpu_hotplug;
 /// Wrapper for configuring the vsock devices attached to the microVM.
 pub mod vsock;
 
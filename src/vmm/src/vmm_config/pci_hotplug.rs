@@ -0,0 +1,82 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::device_manager::pci_mngr::PciManagerError;
+use crate::devices::virtio::block::BlockError;
+use crate::vmm_config::net::NetworkInterfaceError;
+use crate::vmm_config::vsock::VsockConfigError;
+
+/// Errors associated with the hotplug and the unplug of PCI devices.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum DeviceHotplugError {
+    /// Devices can only be hotplugged when PCI is enabled.
+    PciDisabled,
+    /// A device with id {0} already exists.
+    DeviceExists(String),
+    /// A root device cannot be hotplugged.
+    RootDevice,
+    /// Device {0} not found.
+    DeviceNotFound(String),
+    /// Unable to create the block device: {0}
+    Block(#[from] BlockError),
+    /// Unable to create the network device: {0}
+    Net(#[from] NetworkInterfaceError),
+    /// Unable to create the vsock device: {0}
+    Vsock(#[from] VsockConfigError),
+    /// PCI device manager error: {0}
+    PciManager(#[from] PciManagerError),
+}
+
+/// Device to remove from a running microVM.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "device_type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DeviceUnplugConfig {
+    /// Block device.
+    Drive {
+        /// Unique identifier of the drive.
+        drive_id: String,
+    },
+    /// Network device.
+    NetworkInterface {
+        /// Unique identifier of the network interface.
+        iface_id: String,
+    },
+    /// Vsock device.
+    Vsock,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unplug_config_deserialization() {
+        let config: DeviceUnplugConfig =
+            serde_json::from_str(r#"{"device_type": "drive", "drive_id": "scratch"}"#).unwrap();
+        assert_eq!(
+            config,
+            DeviceUnplugConfig::Drive {
+                drive_id: "scratch".to_string()
+            }
+        );
+
+        let config: DeviceUnplugConfig =
+            serde_json::from_str(r#"{"device_type": "network_interface", "iface_id": "eth1"}"#)
+                .unwrap();
+        assert_eq!(
+            config,
+            DeviceUnplugConfig::NetworkInterface {
+                iface_id: "eth1".to_string()
+            }
+        );
+
+        let config: DeviceUnplugConfig =
+            serde_json::from_str(r#"{"device_type": "vsock"}"#).unwrap();
+        assert_eq!(config, DeviceUnplugConfig::Vsock);
+
+        serde_json::from_str::<DeviceUnplugConfig>(r#"{"device_type": "balloon"}"#).unwrap_err();
+        serde_json::from_str::<DeviceUnplugConfig>(r#"{"device_type": "drive"}"#).unwrap_err();
+    }
+}
@@ -34,6 +34,10 @@ pub struct PmemConfig {
     /// Map the file as read only
     #[serde(default)]
     pub read_only: bool,
+    /// Take a shared lock on the file, so that it cannot be locked exclusively while the device
+    /// uses it. Requires `read_only`.
+    #[serde(default)]
+    pub shared_lock: bool,
 }
 
 /// Wrapper for the collection that holds all the Pmem devices.
@@ -119,6 +123,7 @@ mod tests {
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         builder.build(config.clone(), false).unwrap();
         assert_eq!(builder.devices.len(), 1);
@@ -143,6 +148,7 @@ mod tests {
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         builder.build(config.clone(), false).unwrap();
 
@@ -165,6 +171,7 @@ mod tests {
             path_on_host: dummy_path,
             root_device: true,
             read_only: false,
+            shared_lock: false,
         };
         assert!(matches!(
             builder.build(config, true).unwrap_err(),
@@ -7,12 +7,21 @@ use serde::Deserialize;
 
 use super::TokenBucketConfig;
 
+/// Errors associated with the serial console configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum SerialConfigError {
+    /// The serial console output cannot be written to both a file and a Unix socket.
+    ConflictingOutputs,
+}
+
 /// The body of a PUT /serial request.
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct SerialConfig {
     /// Named pipe or file used as output for guest serial console.
     pub serial_out_path: Option<PathBuf>,
+    /// Unix socket on which a client can attach to the guest serial console.
+    pub serial_socket_path: Option<PathBuf>,
     /// Optional rate limiter for serial output bandwidth.
     pub rate_limiter: Option<TokenBucketConfig>,
 }
@@ -47,6 +47,14 @@ pub struct CreateSnapshotParams {
     pub mem_file_path: PathBuf,
 }
 
+/// Stores the configuration that will be used for creating a core dump of the guest.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateCoreDumpParams {
+    /// Path to the file that will contain the ELF core dump.
+    pub core_dump_path: PathBuf,
+}
+
 /// Allows for changing the mapping between tap devices and host devices
 /// during snapshot restore
 #[derive(Debug, PartialEq, Eq, Deserialize)]
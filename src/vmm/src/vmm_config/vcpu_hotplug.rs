@@ -0,0 +1,27 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::acpi::cpu_hotplug::CpuHotplugError;
+
+/// Errors associated with the hotplug and the unplug of vCPUs.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum VcpuHotplugError {
+    /// vCPUs can only be hotplugged when a maximum number of vCPUs is configured.
+    Unavailable,
+    /// The number of vCPUs must be between 1 and the maximum number of vCPUs, got {0}.
+    InvalidVcpuCount(u8),
+    /// Failed to message the vCPU.
+    VcpuMessage,
+    /// CPU hotplug controller error: {0}
+    Controller(#[from] CpuHotplugError),
+}
+
+/// Number of vCPUs to have online in a running microVM.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VcpuCountUpdate {
+    /// Number of online vCPUs.
+    pub vcpu_count: u8,
+}
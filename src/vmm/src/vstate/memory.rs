@@ -5,9 +5,11 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the THIRD-PARTY file.
 
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::SeekFrom;
+use std::io::{Seek, SeekFrom, Write};
 use std::ops::Deref;
+use std::os::fd::AsRawFd;
 use std::sync::{Arc, Mutex};
 
 use bitvec::vec::BitVec;
@@ -24,7 +26,8 @@ pub use vm_memory::{
 use vm_memory::{GuestMemoryError, GuestMemoryRegionBytes, VolatileSlice, WriteVolatile};
 
 use crate::arch::host_page_size;
-use crate::utils::u64_to_usize;
+use crate::operation::{OPERATION_PROGRESS, OperationCancelled};
+use crate::utils::{u64_to_usize, usize_to_u64};
 use crate::vmm_config::machine_config::HugePageConfig;
 use crate::vstate::vm::VmError;
 use crate::{DirtyBitmap, Vm};
@@ -67,8 +70,12 @@ pub enum MemoryError {
     DirtyBitmapTooSmall,
     /// Seek error: {0}
     SeekError(std::io::Error),
+    /// Cannot punch hole in memory file: {0}
+    PunchHole(std::io::Error),
     /// Volatile memory error: {0}
     VolatileMemoryError(vm_memory::VolatileMemoryError),
+    /// {0}
+    Cancelled(#[from] OperationCancelled),
 }
 
 impl From<vm_memory::VolatileMemoryError> for MemoryError {
@@ -77,6 +84,10 @@ impl From<vm_memory::VolatileMemoryError> for MemoryError {
     }
 }
 
+/// Size of the chunks in which guest memory is written to the memory file. Writing a
+/// snapshot can be cancelled between two chunks.
+const DUMP_CHUNK_SIZE: usize = 64 << 20;
+
 /// Type of the guest region
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum GuestRegionType {
@@ -131,12 +142,34 @@ impl From<&GuestMemorySlot<'_>> for kvm_userspace_memory_region {
 }
 
 impl<'a> GuestMemorySlot<'a> {
+    /// Writes `len` bytes of this slot starting at `offset` onto the writer, reporting the
+    /// progress of the operation after every chunk.
+    fn write_range<T: WriteVolatile>(
+        &self,
+        writer: &mut T,
+        mut offset: usize,
+        len: usize,
+    ) -> Result<(), MemoryError> {
+        let end = offset + len;
+        while offset < end {
+            OPERATION_PROGRESS.check_cancelled()?;
+            let chunk_len = DUMP_CHUNK_SIZE.min(end - offset);
+            writer.write_all_volatile(&self.slice.subslice(offset, chunk_len)?)?;
+            OPERATION_PROGRESS.advance(usize_to_u64(chunk_len));
+            offset += chunk_len;
+        }
+        Ok(())
+    }
+
     /// Dumps the dirty pages in this slot onto the writer
+    ///
+    /// Pages within `discarded` are treated as clean, so that they are left as holes.
     pub(crate) fn dump_dirty<T: WriteVolatile + std::io::Seek>(
         &self,
         writer: &mut T,
         kvm_bitmap: &[u64],
         page_size: usize,
+        discarded: &DiscardedRanges,
     ) -> Result<(), MemoryError> {
         let firecracker_bitmap = self.slice.bitmap();
         let mut write_size = 0;
@@ -168,7 +201,10 @@ impl<'a> GuestMemorySlot<'a> {
                     break;
                 }
 
-                if is_kvm_page_dirty || is_firecracker_page_dirty {
+                let is_page_discarded =
+                    discarded.contains(self.guest_addr.unchecked_add(usize_to_u64(page_offset)));
+
+                if (is_kvm_page_dirty || is_firecracker_page_dirty) && !is_page_discarded {
                     // We are at the start of a new batch of dirty pages.
                     if skip_size > 0 {
                         // Seek forward over the unmodified pages.
@@ -178,6 +214,7 @@ impl<'a> GuestMemorySlot<'a> {
                         writer
                             .seek(SeekFrom::Current(offset))
                             .map_err(MemoryError::SeekError)?;
+                        OPERATION_PROGRESS.advance(usize_to_u64(skip_size));
                         dirty_batch_start = page_offset;
                         skip_size = 0;
                     }
@@ -186,8 +223,7 @@ impl<'a> GuestMemorySlot<'a> {
                     // We are at the end of a batch of dirty pages.
                     if write_size > 0 {
                         // Dump the dirty pages.
-                        let slice = &self.slice.subslice(dirty_batch_start, write_size)?;
-                        writer.write_all_volatile(slice)?;
+                        self.write_range(writer, dirty_batch_start, write_size)?;
                         write_size = 0;
                     }
                     skip_size += page_size;
@@ -196,7 +232,7 @@ impl<'a> GuestMemorySlot<'a> {
         }
 
         if write_size > 0 {
-            writer.write_all_volatile(&self.slice.subslice(dirty_batch_start, write_size)?)?;
+            self.write_range(writer, dirty_batch_start, write_size)?;
         }
 
         // Advance the cursor even if the trailing pages are clean, so that the
@@ -205,11 +241,35 @@ impl<'a> GuestMemorySlot<'a> {
             writer
                 .seek(SeekFrom::Current(skip_size.try_into().unwrap()))
                 .map_err(MemoryError::SeekError)?;
+            OPERATION_PROGRESS.advance(usize_to_u64(skip_size));
         }
 
         Ok(())
     }
 
+    /// Dumps all pages in this slot onto the writer, leaving the ranges in `discarded` as holes
+    pub(crate) fn dump<T: WriteVolatile + std::io::Seek>(
+        &self,
+        writer: &mut T,
+        discarded: &DiscardedRanges,
+    ) -> Result<(), MemoryError> {
+        let mut offset = 0;
+        for (hole_addr, hole_len) in discarded.intersections(self.guest_addr, self.slice.len()) {
+            let hole_offset = u64_to_usize(hole_addr.unchecked_offset_from(self.guest_addr));
+            self.write_range(writer, offset, hole_offset - offset)?;
+            writer
+                .seek(SeekFrom::Current(
+                    hole_len
+                        .try_into()
+                        .map_err(|_| MemoryError::SlotSizeTooLarge)?,
+                ))
+                .map_err(MemoryError::SeekError)?;
+            OPERATION_PROGRESS.advance(usize_to_u64(hole_len));
+            offset = hole_offset + hole_len;
+        }
+        self.write_range(writer, offset, self.slice.len() - offset)
+    }
+
     /// Makes the slot host memory PROT_NONE (true) or PROT_READ|PROT_WRITE (false)
     pub(crate) fn protect(&self, protected: bool) -> Result<(), MemoryError> {
         let prot = if protected {
@@ -420,14 +480,18 @@ impl GuestRegionMmapExt {
             }
             // Match either the case of an anonymous mapping, or the case
             // of a shared file mapping.
-            // TODO: madvise(MADV_DONTNEED) doesn't actually work with memfd
-            // (or in general MAP_SHARED of a fd). In those cases we should use
-            // fallocate64(FALLOC_FL_PUNCH_HOLE|FALLOC_FL_KEEP_SIZE).
-            // We keep falling to the madvise branch to keep the previous behaviour.
-            _ => {
+            (_, flags) => {
+                // MADV_DONTNEED only unmaps the pages of a shared mapping (e.g. memfd or
+                // hugetlbfs), which keep occupying host memory in the backing file. MADV_REMOVE
+                // punches a hole in the file instead, so that the memory is actually freed.
+                let advice = if flags & libc::MAP_SHARED != 0 {
+                    libc::MADV_REMOVE
+                } else {
+                    libc::MADV_DONTNEED
+                };
                 // Madvise the region in order to mark it as not used.
                 // SAFETY: The address and length are known to be valid.
-                let ret = unsafe { libc::madvise(phys_address.cast(), len, libc::MADV_DONTNEED) };
+                let ret = unsafe { libc::madvise(phys_address.cast(), len, advice) };
                 if ret < 0 {
                     let os_error = std::io::Error::last_os_error();
                     error!("discard_range: madvise failed: {:?}", os_error);
@@ -598,16 +662,26 @@ where
     /// Mark memory range as dirty
     fn mark_dirty(&self, addr: GuestAddress, len: usize);
 
-    /// Dumps all contents of GuestMemoryMmap to a writer.
-    fn dump<T: WriteVolatile + std::io::Seek>(&self, writer: &mut T) -> Result<(), MemoryError>;
+    /// Dumps all contents of GuestMemoryMmap to a writer, skipping the `discarded` ranges.
+    fn dump<T: WriteVolatile + std::io::Seek>(
+        &self,
+        writer: &mut T,
+        discarded: &DiscardedRanges,
+    ) -> Result<(), MemoryError>;
 
-    /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer.
+    /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer, skipping the
+    /// `discarded` ranges.
     fn dump_dirty<T: WriteVolatile + std::io::Seek>(
         &self,
         writer: &mut T,
         dirty_bitmap: &DirtyBitmap,
+        discarded: &DiscardedRanges,
     ) -> Result<(), MemoryError>;
 
+    /// Punches holes in a memory file written by `dump` or `dump_dirty` for the
+    /// `discarded` ranges.
+    fn punch_holes(&self, file: &File, discarded: &DiscardedRanges) -> Result<(), MemoryError>;
+
     /// Resets all the memory region bitmaps
     fn reset_dirty(&self);
 
@@ -683,27 +757,33 @@ impl GuestMemoryExtension for GuestMemoryMmap {
         }
     }
 
-    /// Dumps all contents of GuestMemoryMmap to a writer.
-    fn dump<T: WriteVolatile + std::io::Seek>(&self, writer: &mut T) -> Result<(), MemoryError> {
+    /// Dumps all contents of GuestMemoryMmap to a writer, skipping the `discarded` ranges.
+    fn dump<T: WriteVolatile + std::io::Seek>(
+        &self,
+        writer: &mut T,
+        discarded: &DiscardedRanges,
+    ) -> Result<(), MemoryError> {
         self.iter()
             .flat_map(|region| region.slots())
             .try_for_each(|(mem_slot, plugged)| {
                 if !plugged {
                     let ilen = i64::try_from(mem_slot.slice.len()).unwrap();
                     writer.seek(SeekFrom::Current(ilen)).unwrap();
+                    OPERATION_PROGRESS.advance(usize_to_u64(mem_slot.slice.len()));
+                    Ok(())
                 } else {
-                    writer.write_all_volatile(&mem_slot.slice)?;
+                    mem_slot.dump(writer, discarded)
                 }
-                Ok(())
             })
-            .map_err(MemoryError::WriteMemory)
     }
 
-    /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer.
+    /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer, skipping the
+    /// `discarded` ranges.
     fn dump_dirty<T: WriteVolatile + std::io::Seek>(
         &self,
         writer: &mut T,
         dirty_bitmap: &DirtyBitmap,
+        discarded: &DiscardedRanges,
     ) -> Result<(), MemoryError> {
         let page_size = host_page_size();
 
@@ -717,11 +797,12 @@ impl GuestMemoryExtension for GuestMemoryMmap {
                         writer
                             .seek(SeekFrom::Current(ilen))
                             .map_err(MemoryError::SeekError)?;
+                        OPERATION_PROGRESS.advance(usize_to_u64(mem_slot.slice.len()));
                     } else {
                         let kvm_bitmap = dirty_bitmap
                             .get(&mem_slot.slot)
                             .ok_or(MemoryError::DirtyBitmapNotFound(mem_slot.slot))?;
-                        mem_slot.dump_dirty(writer, kvm_bitmap, page_size)?;
+                        mem_slot.dump_dirty(writer, kvm_bitmap, page_size, discarded)?;
                     }
                     Ok(())
                 });
@@ -735,6 +816,22 @@ impl GuestMemoryExtension for GuestMemoryMmap {
         write_result
     }
 
+    /// Punches holes in a memory file written by `dump` or `dump_dirty` for the
+    /// `discarded` ranges.
+    fn punch_holes(&self, file: &File, discarded: &DiscardedRanges) -> Result<(), MemoryError> {
+        // Regions are laid out one after the other in the memory file.
+        let mut region_offset = 0;
+        for region in self.iter() {
+            let holes = discarded.intersections(region.start_addr(), u64_to_usize(region.len()));
+            for (addr, len) in holes {
+                let offset = region_offset + addr.unchecked_offset_from(region.start_addr());
+                punch_hole(file, offset, usize_to_u64(len)).map_err(MemoryError::PunchHole)?;
+            }
+            region_offset += region.len();
+        }
+        Ok(())
+    }
+
     /// Resets all the memory region bitmaps
     fn reset_dirty(&self) {
         self.iter().for_each(|region| {
@@ -810,6 +907,163 @@ impl GuestMemoryExtension for GuestMemoryMmap {
     }
 }
 
+/// A set of non-overlapping guest physical memory ranges whose contents were discarded, e.g.
+/// by inflating the balloon or unplugging virtio-mem blocks. Such ranges do not need any
+/// backing data in a snapshot.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscardedRanges {
+    // Maps the start address of each range to its end address (exclusive). Overlapping and
+    // adjacent ranges are always merged.
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl DiscardedRanges {
+    /// Adds the range `[addr, addr + len)` to the set.
+    pub fn insert(&mut self, addr: GuestAddress, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut start = addr.raw_value();
+        let mut end = start.saturating_add(usize_to_u64(len));
+
+        // Merge with a range that starts before and overlaps or touches the new one.
+        if let Some((&prev_start, &prev_end)) = self.ranges.range(..start).next_back()
+            && prev_end >= start
+        {
+            start = prev_start;
+            end = end.max(prev_end);
+        }
+        // Merge with all ranges starting within the new one.
+        let merged: Vec<u64> = self.ranges.range(start..=end).map(|(&s, _)| s).collect();
+        for range_start in merged {
+            let range_end = self.ranges.remove(&range_start).unwrap();
+            end = end.max(range_end);
+        }
+        self.ranges.insert(start, end);
+    }
+
+    /// Removes the range `[addr, addr + len)` from the set.
+    pub fn remove(&mut self, addr: GuestAddress, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let start = addr.raw_value();
+        let end = start.saturating_add(usize_to_u64(len));
+
+        // Trim a range that starts before and overlaps the removed one.
+        if let Some((&prev_start, &prev_end)) = self.ranges.range(..start).next_back()
+            && prev_end > start
+        {
+            self.ranges.insert(prev_start, start);
+            if prev_end > end {
+                self.ranges.insert(end, prev_end);
+            }
+        }
+        // Drop or trim all ranges starting within the removed one.
+        let overlapping: Vec<(u64, u64)> = self
+            .ranges
+            .range(start..end)
+            .map(|(&s, &e)| (s, e))
+            .collect();
+        for (range_start, range_end) in overlapping {
+            self.ranges.remove(&range_start);
+            if range_end > end {
+                self.ranges.insert(end, range_end);
+            }
+        }
+    }
+
+    /// Adds all the ranges of `other` to the set.
+    pub fn extend(&mut self, other: &DiscardedRanges) {
+        for (addr, len) in other.iter() {
+            self.insert(addr, len);
+        }
+    }
+
+    /// Returns whether `addr` is within one of the ranges.
+    pub fn contains(&self, addr: GuestAddress) -> bool {
+        self.ranges
+            .range(..=addr.raw_value())
+            .next_back()
+            .is_some_and(|(_, &end)| addr.raw_value() < end)
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Iterates over the ranges, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (GuestAddress, usize)> + '_ {
+        self.ranges
+            .iter()
+            .map(|(&start, &end)| (GuestAddress(start), u64_to_usize(end - start)))
+    }
+
+    /// Iterates over the parts of the ranges that are within `[addr, addr + len)`, in ascending
+    /// order.
+    pub fn intersections(
+        &self,
+        addr: GuestAddress,
+        len: usize,
+    ) -> impl Iterator<Item = (GuestAddress, usize)> + '_ {
+        let start = addr.raw_value();
+        let end = start.saturating_add(usize_to_u64(len));
+        // The first range of interest may start before `addr`.
+        let first = self
+            .ranges
+            .range(..start)
+            .next_back()
+            .map_or(start, |(&s, _)| s);
+        self.ranges
+            .range(first..end)
+            .filter_map(move |(&range_start, &range_end)| {
+                let clipped_start = range_start.max(start);
+                let clipped_end = range_end.min(end);
+                (clipped_start < clipped_end).then(|| {
+                    (
+                        GuestAddress(clipped_start),
+                        u64_to_usize(clipped_end - clipped_start),
+                    )
+                })
+            })
+    }
+}
+
+/// Deallocates the given range of a file, so that it reads as zeros. Falls back to writing zeros
+/// if the file system does not support punching holes.
+fn punch_hole(file: &File, offset: u64, len: u64) -> Result<(), std::io::Error> {
+    let off = i64::try_from(offset).map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+    let size = i64::try_from(len).map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+    // SAFETY: The file descriptor is valid and the call does not touch process memory.
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            off,
+            size,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::EOPNOTSUPP) {
+        return Err(err);
+    }
+
+    let zeros = vec![0u8; u64_to_usize(len.min(0x10_0000))];
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut written = 0;
+    while written < len {
+        let chunk = u64_to_usize((len - written).min(usize_to_u64(zeros.len())));
+        file.write_all(&zeros[..chunk])?;
+        written += usize_to_u64(chunk);
+    }
+    Ok(())
+}
+
 fn create_memfd(
     mem_size: u64,
     hugetlb_size: Option<memfd::HugetlbSize>,
@@ -1128,7 +1382,9 @@ mod tests {
 
         // dump the full memory.
         let mut memory_file = TempFile::new().unwrap().into_file();
-        guest_memory.dump(&mut memory_file).unwrap();
+        guest_memory
+            .dump(&mut memory_file, &DiscardedRanges::default())
+            .unwrap();
 
         let restored_guest_memory =
             into_region_ext(snapshot_file(memory_file, memory_state.regions(), false).unwrap());
@@ -1146,6 +1402,99 @@ mod tests {
         assert_eq!(second_region, restored_region);
     }
 
+    #[test]
+    fn test_discarded_ranges() {
+        let mut ranges = DiscardedRanges::default();
+        assert!(ranges.is_empty());
+
+        ranges.insert(GuestAddress(0x1000), 0x1000);
+        ranges.insert(GuestAddress(0x4000), 0x1000);
+        // Adjacent and overlapping ranges are merged.
+        ranges.insert(GuestAddress(0x2000), 0x1000);
+        ranges.insert(GuestAddress(0x3800), 0x1000);
+        ranges.insert(GuestAddress(0x8000), 0);
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            vec![
+                (GuestAddress(0x1000), 0x2000),
+                (GuestAddress(0x3800), 0x1800)
+            ]
+        );
+        assert!(ranges.contains(GuestAddress(0x1000)));
+        assert!(ranges.contains(GuestAddress(0x2fff)));
+        assert!(!ranges.contains(GuestAddress(0x3000)));
+        assert!(!ranges.contains(GuestAddress(0x5000)));
+
+        assert_eq!(
+            ranges
+                .intersections(GuestAddress(0x2000), 0x2000)
+                .collect::<Vec<_>>(),
+            vec![
+                (GuestAddress(0x2000), 0x1000),
+                (GuestAddress(0x3800), 0x800)
+            ]
+        );
+        assert_eq!(ranges.intersections(GuestAddress(0x3000), 0x800).count(), 0);
+
+        // Removing splits and trims ranges.
+        ranges.remove(GuestAddress(0x1800), 0x800);
+        ranges.remove(GuestAddress(0x3000), 0x1000);
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            vec![
+                (GuestAddress(0x1000), 0x800),
+                (GuestAddress(0x2000), 0x1000),
+                (GuestAddress(0x4000), 0x1000)
+            ]
+        );
+
+        let mut other = DiscardedRanges::default();
+        other.insert(GuestAddress(0x1800), 0x800);
+        ranges.extend(&other);
+        ranges.remove(GuestAddress(0x4000), 0x1000);
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            vec![(GuestAddress(0x1000), 0x2000)]
+        );
+    }
+
+    #[test]
+    fn test_dump_discarded() {
+        let page_size = host_page_size();
+
+        let region_size = page_size * 4;
+        let guest_memory = into_region_ext(
+            anonymous(
+                [(GuestAddress(0), region_size)].into_iter(),
+                false,
+                HugePageConfig::None,
+            )
+            .unwrap(),
+        );
+        guest_memory
+            .write(&vec![1u8; region_size], GuestAddress(0))
+            .unwrap();
+
+        let mut discarded = DiscardedRanges::default();
+        discarded.insert(GuestAddress(page_size as u64), page_size * 2);
+
+        // Start from a file with stale contents, as when overwriting a previous snapshot.
+        let mut memory_file = TempFile::new().unwrap().into_file();
+        memory_file.write_all(&vec![3u8; region_size]).unwrap();
+        memory_file.rewind().unwrap();
+
+        guest_memory.dump(&mut memory_file, &discarded).unwrap();
+        guest_memory.punch_holes(&memory_file, &discarded).unwrap();
+
+        let mut contents = Vec::new();
+        memory_file.rewind().unwrap();
+        memory_file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len(), region_size);
+        assert!(contents[..page_size].iter().all(|&b| b == 1));
+        assert!(contents[page_size..page_size * 3].iter().all(|&b| b == 0));
+        assert!(contents[page_size * 3..].iter().all(|&b| b == 1));
+    }
+
     #[test]
     fn test_dump_dirty() {
         let page_size = host_page_size();
@@ -1191,7 +1540,7 @@ mod tests {
 
         let mut file = TempFile::new().unwrap().into_file();
         guest_memory
-            .dump_dirty(&mut file, &kvm_dirty_bitmap)
+            .dump_dirty(&mut file, &kvm_dirty_bitmap, &DiscardedRanges::default())
             .unwrap();
 
         // We can restore from this because this is the first dirty dump.
@@ -1230,7 +1579,7 @@ mod tests {
         kvm_dirty_bitmap.insert(1, vec![0b10]);
 
         guest_memory
-            .dump_dirty(&mut reader, &kvm_dirty_bitmap)
+            .dump_dirty(&mut reader, &kvm_dirty_bitmap, &DiscardedRanges::default())
             .unwrap();
 
         // Check that only the dirty regions are dumped.
@@ -1265,7 +1614,7 @@ mod tests {
 
         let mut reader = file.into_file();
         guest_memory
-            .dump_dirty(&mut reader, &kvm_dirty_bitmap)
+            .dump_dirty(&mut reader, &kvm_dirty_bitmap, &DiscardedRanges::default())
             .unwrap();
 
         // Check that only the dirty regions are dumped.
@@ -1294,19 +1643,19 @@ mod tests {
         kvm_dirty_bitmap.insert(0, vec![0b1, 0b01]);
         kvm_dirty_bitmap.insert(1, vec![0b10]);
         assert!(matches!(
-            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap),
+            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap, &DiscardedRanges::default()),
             Err(MemoryError::DirtyBitmapTooLarge)
         ));
         kvm_dirty_bitmap.insert(0, vec![0b01]);
         kvm_dirty_bitmap.insert(1, vec![0b110]);
         assert!(matches!(
-            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap),
+            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap, &DiscardedRanges::default()),
             Err(MemoryError::DirtyBitmapTooLarge)
         ));
         kvm_dirty_bitmap.insert(0, vec![]);
         kvm_dirty_bitmap.insert(1, vec![0b10]);
         assert!(matches!(
-            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap),
+            guest_memory.dump_dirty(&mut reader, &kvm_dirty_bitmap, &DiscardedRanges::default()),
             Err(MemoryError::DirtyBitmapTooSmall)
         ));
     }
@@ -1409,6 +1758,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_discard_range_memfd() {
+        let page_size: usize = 0x1000;
+        let mem = into_region_ext(
+            memfd_backed(
+                &[(GuestAddress(0), 2 * page_size)],
+                false,
+                HugePageConfig::None,
+            )
+            .unwrap(),
+        );
+
+        // Fill the memory with ones.
+        let ones = vec![1u8; 2 * page_size];
+        mem.write(&ones[..], GuestAddress(0)).unwrap();
+
+        // Remove the first page.
+        mem.discard_range(GuestAddress(0), page_size).unwrap();
+
+        // Check that the first page was removed from the memfd and reads as zeros.
+        let mut actual_page = vec![0u8; page_size];
+        mem.read(actual_page.as_mut_slice(), GuestAddress(0))
+            .unwrap();
+        assert_eq!(vec![0u8; page_size], actual_page);
+        // Check that the second page still contains ones.
+        mem.read(actual_page.as_mut_slice(), GuestAddress(page_size as u64))
+            .unwrap();
+        assert_eq!(vec![1u8; page_size], actual_page);
+    }
+
     #[test]
     fn test_discard_range_on_file() {
         let page_size: usize = 0x1000;
@@ -1748,7 +2127,7 @@ mod tests {
                 let mut opt_file = TempFile::new().unwrap().into_file();
                 opt_file.set_len(total_size as u64).unwrap();
                 guest_memory
-                    .dump_dirty(&mut opt_file, &kvm_bitmap)
+                    .dump_dirty(&mut opt_file, &kvm_bitmap, &DiscardedRanges::default())
                     .unwrap();
                 let opt_pos = opt_file.stream_position().unwrap();
 
@@ -5,7 +5,7 @@ use std::convert::Infallible;
 
 use serde::{Deserialize, Serialize};
 pub use vm_allocator::AllocPolicy;
-use vm_allocator::{AddressAllocator, IdAllocator};
+use vm_allocator::{AddressAllocator, IdAllocator, RangeInclusive};
 
 use crate::arch;
 use crate::snapshot::Persist;
@@ -109,6 +109,18 @@ impl ResourceAllocator {
         allocate_many_ids(&mut self.gsi_msi_allocator, gsi_count)
     }
 
+    /// Free GSIs previously allocated for MSI
+    ///
+    /// # Arguments
+    ///
+    /// * `gsis` - The GSIs to free
+    pub fn free_gsi_msi(&mut self, gsis: &[u32]) -> Result<(), vm_allocator::Error> {
+        for gsi in gsis {
+            self.gsi_msi_allocator.free_id(*gsi)?;
+        }
+        Ok(())
+    }
+
     /// Allocate a memory range in 32-bit MMIO address space
     ///
     /// If it succeeds, it returns the first address of the allocated range
@@ -151,6 +163,21 @@ impl ResourceAllocator {
             .start())
     }
 
+    /// Free a memory range previously allocated in 64-bit MMIO address space
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The first address of the range
+    /// * `size` - The size in bytes of the range
+    pub fn free_64bit_mmio_memory(
+        &mut self,
+        address: u64,
+        size: u64,
+    ) -> Result<(), vm_allocator::Error> {
+        self.mmio64_memory
+            .free(&RangeInclusive::new(address, address + size - 1)?)
+    }
+
     /// Allocate a memory range for system data
     ///
     /// If it succeeds, it returns the first address of the allocated range
@@ -285,6 +312,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_free() {
+        let mut allocator = ResourceAllocator::new();
+        let gsis = allocator.allocate_gsi_msi(2).unwrap();
+        let mmio64_mem = allocator
+            .allocate_64bit_mmio_memory(0x1000, 0x1000, AllocPolicy::FirstMatch)
+            .unwrap();
+
+        allocator.free_gsi_msi(&gsis).unwrap();
+        allocator
+            .free_64bit_mmio_memory(mmio64_mem, 0x1000)
+            .unwrap();
+        // Resources which are not allocated cannot be freed.
+        allocator.free_gsi_msi(&gsis).unwrap_err();
+        allocator
+            .free_64bit_mmio_memory(mmio64_mem, 0x1000)
+            .unwrap_err();
+
+        // Freed resources can be allocated again.
+        assert_eq!(allocator.allocate_gsi_msi(2).unwrap(), gsis);
+        assert_eq!(
+            allocator
+                .allocate_64bit_mmio_memory(0x1000, 0x1000, AllocPolicy::FirstMatch)
+                .unwrap(),
+            mmio64_mem
+        );
+    }
+
     fn clone_allocator(allocator: &ResourceAllocator) -> ResourceAllocator {
         let state = allocator.save();
         let serialized_data = bitcode::serialize(&state).unwrap();
@@ -22,8 +22,8 @@ use crate::FcExitCode;
 pub use crate::arch::{KvmVcpu, KvmVcpuConfigureError, KvmVcpuError, Peripherals, VcpuState};
 use crate::cpu_config::templates::{CpuConfiguration, GuestConfigError};
 #[cfg(feature = "gdb")]
-use crate::gdb::target::{GdbTargetError, get_raw_tid};
-use crate::logger::{IncMetric, METRICS};
+use crate::gdb::target::{GdbTargetError, VcpuDebugEvent, get_raw_tid};
+use crate::logger::{EVENT_LOG, EventKind, IncMetric, METRICS};
 use crate::seccomp::{BpfProgram, BpfProgramRef};
 use crate::utils::signal::{Killable, register_signal_handler, sigrtmin};
 use crate::utils::sm::StateMachine;
@@ -94,7 +94,7 @@ pub struct Vcpu {
     exit_evt: EventFd,
     /// Debugger emitter for gdb events
     #[cfg(feature = "gdb")]
-    gdb_event: Option<Sender<usize>>,
+    gdb_event: Option<Sender<VcpuDebugEvent>>,
     /// The receiving end of events channel owned by the vcpu side.
     event_receiver: Receiver<VcpuEvent>,
     /// The transmitting end of the events channel which will be given to the handler.
@@ -149,7 +149,7 @@ impl Vcpu {
 
     /// Attaches the fields required for debugging
     #[cfg(feature = "gdb")]
-    pub fn attach_debug_info(&mut self, gdb_event: Sender<usize>) {
+    pub fn attach_debug_info(&mut self, gdb_event: Sender<VcpuDebugEvent>) {
         self.gdb_event = Some(gdb_event);
     }
 
@@ -319,20 +319,7 @@ impl Vcpu {
                 StateMachine::next(Self::paused)
             }
             Ok(VcpuEvent::SaveState) => {
-                // Save vcpu state.
-                self.kvm_vcpu
-                    .save_state()
-                    .map(|vcpu_state| {
-                        self.response_sender
-                            .send(VcpuResponse::SavedState(Box::new(vcpu_state)))
-                            .expect("vcpu channel unexpectedly closed");
-                    })
-                    .unwrap_or_else(|err| {
-                        self.response_sender
-                            .send(VcpuResponse::Error(VcpuError::VcpuResponse(err)))
-                            .expect("vcpu channel unexpectedly closed");
-                    });
-
+                self.send_saved_state();
                 StateMachine::next(Self::paused)
             }
             Ok(VcpuEvent::DumpCpuConfig) => {
@@ -368,19 +355,35 @@ impl Vcpu {
             METRICS.vcpu.failures.inc();
             error!("Failed signaling vcpu exit event: {}", err);
         }
-        // From this state we only accept going to finished.
+        self.response_sender
+            .send(VcpuResponse::Exited(exit_code))
+            .expect("vcpu channel unexpectedly closed");
+        // From this state we only accept going to finished. The state of the vcpu can still be
+        // saved, so that a core dump of the guest can be created after a fatal error.
         loop {
-            self.response_sender
-                .send(VcpuResponse::Exited(exit_code))
-                .expect("vcpu channel unexpectedly closed");
-            // Wait for and only accept 'VcpuEvent::Finish'.
-            if let Ok(VcpuEvent::Finish) = self.event_receiver.recv() {
-                break;
+            match self.event_receiver.recv() {
+                Ok(VcpuEvent::Finish) => break,
+                Ok(VcpuEvent::SaveState) => self.send_saved_state(),
+                _ => self
+                    .response_sender
+                    .send(VcpuResponse::Exited(exit_code))
+                    .expect("vcpu channel unexpectedly closed"),
             }
         }
         StateMachine::finish()
     }
 
+    /// Saves the vcpu state and sends it, or the error, as a response.
+    fn send_saved_state(&self) {
+        let response = match self.kvm_vcpu.save_state() {
+            Ok(vcpu_state) => VcpuResponse::SavedState(Box::new(vcpu_state)),
+            Err(err) => VcpuResponse::Error(VcpuError::VcpuResponse(err)),
+        };
+        self.response_sender
+            .send(response)
+            .expect("vcpu channel unexpectedly closed");
+    }
+
     /// Runs the vCPU in KVM context and handles the kvm exit reason.
     ///
     /// Returns error or enum specifying whether emulation was handled or interrupted.
@@ -398,16 +401,27 @@ impl Vcpu {
                 Ok(VcpuEmulation::Interrupted)
             }
             #[cfg(feature = "gdb")]
-            Ok(VcpuExit::Debug(_)) => {
+            Ok(VcpuExit::Debug(exit)) => {
                 if let Some(gdb_event) = &self.gdb_event {
-                    gdb_event
-                        .send(get_raw_tid(self.kvm_vcpu.index.into()))
-                        .expect("Unable to notify gdb event");
+                    let event = VcpuDebugEvent {
+                        raw_tid: get_raw_tid(self.kvm_vcpu.index.into()),
+                        exit,
+                    };
+                    gdb_event.send(event).expect("Unable to notify gdb event");
                 }
 
                 Ok(VcpuEmulation::Paused)
             }
-            emulation_result => handle_kvm_exit(&mut self.kvm_vcpu.peripherals, emulation_result),
+            emulation_result => {
+                let result = handle_kvm_exit(&mut self.kvm_vcpu.peripherals, emulation_result);
+                if let Err(err) = &result {
+                    EVENT_LOG.emit(EventKind::VcpuError {
+                        vcpu: self.kvm_vcpu.index,
+                        error: err.to_string(),
+                    });
+                }
+                result
+            }
         }
     }
 }
@@ -469,6 +483,11 @@ fn handle_kvm_exit(
                         "Received KVM_SYSTEM_EVENT: type: {}, event: {:?}",
                         event_type, event_flags
                     );
+                    EVENT_LOG.emit(if event_type == KVM_SYSTEM_EVENT_RESET {
+                        EventKind::GuestReset
+                    } else {
+                        EventKind::GuestShutdown
+                    });
                     Ok(VcpuEmulation::Stopped)
                 }
                 _ => {
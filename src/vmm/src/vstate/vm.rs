@@ -27,14 +27,15 @@ use vmm_sys_util::eventfd::EventFd;
 pub use crate::arch::{ArchVm as Vm, ArchVmError, VmState};
 use crate::arch::{GSI_MSI_END, host_page_size};
 use crate::logger::info;
+use crate::operation::OPERATION_PROGRESS;
 use crate::pci::{DeviceRelocation, DeviceRelocationError, PciDevice};
 use crate::persist::CreateSnapshotError;
 use crate::vmm_config::snapshot::SnapshotType;
 use crate::vstate::bus::Bus;
 use crate::vstate::interrupts::{InterruptError, MsixVector, MsixVectorConfig, MsixVectorGroup};
 use crate::vstate::memory::{
-    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion, GuestMemoryState,
-    GuestRegionMmap, GuestRegionMmapExt, MemoryError,
+    DiscardedRanges, GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion,
+    GuestMemoryState, GuestRegionMmap, GuestRegionMmapExt, MemoryError,
 };
 use crate::vstate::resources::ResourceAllocator;
 use crate::vstate::vcpu::VcpuError;
@@ -335,6 +336,7 @@ impl Vm {
         &self,
         mem_file_path: &Path,
         snapshot_type: SnapshotType,
+        discarded: &DiscardedRanges,
     ) -> Result<(), CreateSnapshotError> {
         use self::CreateSnapshotError::*;
 
@@ -374,14 +376,20 @@ impl Vm {
         // Set the length of the file to the full size of the memory area.
         file.set_len(expected_size)
             .map_err(|e| MemoryBackingFile("set_length", e))?;
+        OPERATION_PROGRESS.set_total(expected_size);
 
         match snapshot_type {
             SnapshotType::Diff => {
                 let dirty_bitmap = self.get_dirty_bitmap()?;
-                self.guest_memory().dump_dirty(&mut file, &dirty_bitmap)?;
+                self.guest_memory()
+                    .dump_dirty(&mut file, &dirty_bitmap, discarded)?;
             }
             SnapshotType::Full => {
-                self.guest_memory().dump(&mut file)?;
+                self.guest_memory().dump(&mut file, discarded)?;
+                // Discarded ranges were skipped above, but the file may still hold data for them
+                // from a previous snapshot. A diff file is merged on top of a base file, where
+                // holes are not written, so they are only punched in full snapshots.
+                self.guest_memory().punch_holes(&file, discarded)?;
                 self.reset_dirty_bitmap();
                 self.guest_memory().reset_dirty();
             }
@@ -471,6 +479,23 @@ impl Vm {
         Ok(MsixVectorGroup { vm, vectors })
     }
 
+    /// Release a group of MSI-X interrupts, once the device using it is removed
+    pub fn release_msix_group(&self, group: &MsixVectorGroup) -> Result<(), InterruptError> {
+        group.disable()?;
+
+        let gsis: Vec<u32> = group.vectors.iter().map(|vector| vector.gsi).collect();
+        {
+            let mut interrupts = self.common.interrupts.lock().expect("Poisoned lock");
+            for gsi in &gsis {
+                interrupts.remove(gsi);
+            }
+        }
+        self.set_gsi_routes()?;
+
+        self.resource_allocator().free_gsi_msi(&gsis)?;
+        Ok(())
+    }
+
     /// Set GSI routes to KVM
     pub fn set_gsi_routes(&self) -> Result<(), InterruptError> {
         let entries = self.common.interrupts.lock().expect("Poisoned lock");
@@ -541,6 +566,7 @@ pub(crate) mod tests {
 
     use vm_memory::GuestAddress;
     use vm_memory::mmap::MmapRegionBuilder;
+    use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
     use crate::pci::PciSBDF;
@@ -821,6 +847,35 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_release_msix_group() {
+        let (_, mut vm) = setup_vm_with_memory(mib_to_bytes(128));
+        enable_irqchip(&mut vm);
+        let vm = Arc::new(vm);
+        let msix_group = create_msix_group(&vm);
+
+        let config = MsixVectorConfig {
+            high_addr: 0x42,
+            low_addr: 0x13,
+            data: 0x12,
+            devid: PciSBDF::from(0xafa),
+        };
+        msix_group.update(0, config, false, true).unwrap();
+        assert_eq!(vm.common.interrupts.lock().unwrap().len(), 1);
+
+        vm.release_msix_group(&msix_group).unwrap();
+        assert!(vm.common.interrupts.lock().unwrap().is_empty());
+        for vector in &msix_group.vectors {
+            assert!(!vector.enabled.load(Ordering::Acquire));
+        }
+
+        // The GSIs of the group can be used by a new group.
+        let new_group = create_msix_group(&vm);
+        for (vector, new_vector) in msix_group.vectors.iter().zip(&new_group.vectors) {
+            assert_eq!(vector.gsi, new_vector.gsi);
+        }
+    }
+
     #[test]
     fn test_msi_vector_group_persistence() {
         let (_, mut vm) = setup_vm_with_memory(mib_to_bytes(128));
@@ -880,4 +935,29 @@ pub(crate) mod tests {
             .unwrap();
         assert_eq!(range + 1024, range_new);
     }
+
+    #[test]
+    fn test_snapshot_memory_discarded_ranges() {
+        let mem_size = mib_to_bytes(1);
+        let (_, vm) = setup_vm_with_memory(mem_size);
+        let mut discarded = DiscardedRanges::default();
+        discarded.insert(GuestAddress(0x1000), 0x1000);
+
+        // The memory file holds the content of a previous snapshot.
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(&vec![0xaa; mem_size]).unwrap();
+
+        // A diff snapshot leaves discarded ranges untouched, so that merging it keeps the base.
+        vm.snapshot_memory_to_file(file.as_path(), SnapshotType::Diff, &discarded)
+            .unwrap();
+        let content = std::fs::read(file.as_path()).unwrap();
+        assert!(content[0x1000..0x2000].iter().all(|&byte| byte == 0xaa));
+
+        // A full snapshot punches holes for them.
+        vm.snapshot_memory_to_file(file.as_path(), SnapshotType::Full, &discarded)
+            .unwrap();
+        let content = std::fs::read(file.as_path()).unwrap();
+        assert_eq!(content.len(), mem_size);
+        assert!(content.iter().all(|&byte| byte == 0));
+    }
 }
@@ -10,6 +10,7 @@ use std::time::Duration;
 
 use vmm::builder::build_and_boot_microvm;
 use vmm::devices::virtio::block::CacheType;
+use vmm::measured_boot::BootComponent;
 use vmm::persist::{MicrovmState, MicrovmStateError, VmInfo, snapshot_state_sanity_check};
 use vmm::resources::VmResources;
 use vmm::rpc_interface::{
@@ -77,6 +78,24 @@ fn check_build_microvm(vmm: Arc<Mutex<Vmm>>, mut evmgr: EventManager) {
     // The built microVM should be in the `VmState::Paused` state here.
     assert_eq!(vmm.lock().unwrap().instance_info().state, VmState::Paused);
 
+    // The kernel image, command line and CPU template are measured.
+    let components: Vec<_> = vmm
+        .lock()
+        .unwrap()
+        .instance_info()
+        .boot_measurements
+        .iter()
+        .map(|measurement| measurement.component)
+        .collect();
+    assert_eq!(
+        components,
+        [
+            BootComponent::Kernel,
+            BootComponent::Cmdline,
+            BootComponent::CpuTemplate
+        ]
+    );
+
     // The microVM should be able to resume and exit successfully.
     // On x86_64, the vmm should exit once its workload completes and signals the exit event.
     // On aarch64, the test kernel doesn't exit, so the vmm is force-stopped.
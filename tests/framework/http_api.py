@@ -179,6 +179,7 @@ class Api:
         self.vsock = Resource(self, "/vsock")
         self.snapshot_create = Resource(self, "/snapshot/create")
         self.snapshot_load = Resource(self, "/snapshot/load")
+        self.snapshot_coredump = Resource(self, "/snapshot/coredump")
         self.cpu_config = Resource(self, "/cpu-config")
         self.entropy = Resource(self, "/entropy")
         self.pmem = Resource(self, "/pmem", "id")
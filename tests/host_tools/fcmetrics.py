@@ -146,6 +146,10 @@ def validate_fc_metrics(metrics):
             "free_page_hint_count",
             "free_page_hint_freed",
             "free_page_hint_fails",
+            "policy_inflate_count",
+            "policy_deflate_count",
+            "policy_oom_deflate_count",
+            "policy_fails",
         ],
         "block": block_metrics,
         "deprecated_api": [
@@ -157,6 +161,9 @@ def validate_fc_metrics(metrics):
             "mmds_count",
             "vmm_version_count",
             "hotplug_memory_count",
+            "events_count",
+            "metrics_count",
+            "operations_count",
         ],
         "i8042": [
             "error_count",
@@ -170,11 +177,13 @@ def validate_fc_metrics(metrics):
             "full_create_snapshot",
             "diff_create_snapshot",
             "load_snapshot",
+            "create_core_dump",
             "pause_vm",
             "resume_vm",
             "vmm_full_create_snapshot",
             "vmm_diff_create_snapshot",
             "vmm_load_snapshot",
+            "vmm_create_core_dump",
             "vmm_pause_vm",
             "vmm_resume_vm",
         ],
@@ -210,6 +219,17 @@ def validate_fc_metrics(metrics):
             "mmds_fails",
             "hotplug_memory_count",
             "hotplug_memory_fails",
+            "hotplug_vcpus_count",
+            "hotplug_vcpus_fails",
+            "operations_count",
+            "operations_fails",
+        ],
+        "pvpanic": [
+            "panic_count",
+            "crash_loaded_count",
+            "error_count",
+            "missed_read_count",
+            "missed_write_count",
         ],
         "put_api_requests": [
             "actions_count",
@@ -238,6 +258,12 @@ def validate_fc_metrics(metrics):
             "serial_fails",
             "hotplug_memory_count",
             "hotplug_memory_fails",
+            "hotplug_unplug_count",
+            "hotplug_unplug_fails",
+            "operations_count",
+            "operations_fails",
+            "vm_config_count",
+            "vm_config_fails",
         ],
         "seccomp": [
             "num_faults",
@@ -306,12 +332,25 @@ def validate_fc_metrics(metrics):
             "entropy_rate_limiter_throttled",
             "rate_limiter_event_count",
         ],
+        "console": [
+            "activate_fails",
+            "event_fails",
+            "control_count",
+            "rx_bytes_count",
+            "tx_bytes_count",
+            "output_dropped",
+            "client_attach_count",
+            "client_reject_count",
+        ],
         "interrupts": ["triggers", "config_updates"],
         "pmem": [
             "activate_fails",
             "cfg_fails",
             "event_fails",
             "queue_event_count",
+            "read_only_writes",
+            "resident_bytes",
+            "page_cache_bytes",
         ],
         "memory_hotplug": [
             "activate_fails",
@@ -362,6 +401,10 @@ def validate_fc_metrics(metrics):
                 "init_time_us",
                 "activate_time_us",
                 "config_change_time_us",
+                "disconnects",
+                "reconnects",
+                "reconnect_fails",
+                "reconnect_time_us",
             ]
             vhost_user_devices.append(metrics_name)
         if metrics_name.startswith("block_"):
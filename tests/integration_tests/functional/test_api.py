@@ -1268,6 +1268,7 @@ def test_get_full_config_after_restoring_snapshot(microvm_factory, uvm_nano):
         "kernel_image_path": uvm_nano.get_jailed_resource(uvm_nano.kernel_file),
         "initrd_path": None,
         "boot_args": "reboot=k panic=1 nomodule swiotlb=noforce console=ttyS0",
+        "measured_boot": False,
     }
     if not uvm_nano.pci_enabled:
         expected_cfg["boot-source"]["boot_args"] += " pci=off"
@@ -1312,6 +1313,7 @@ def test_get_full_config(uvm_plain):
         "boot_args": "",
         "kernel_image_path": f"/{test_microvm.kernel_file.name}",
         "initrd_path": None,
+        "measured_boot": False,
     }
     expected_cfg["drives"] = [
         {
@@ -204,3 +204,46 @@ def test_pmem_dax_memory_saving(
     assert (
         pmem_rss_usage < block_rss_usage
     ), f"{block_cache_usage} <= {pmem_cache_usage}"
+
+
+def test_pmem_metrics_during_snapshot_operation(uvm_plain_any):
+    """
+    Test that the metrics of a pmem device can be read while a snapshot is
+    created asynchronously, on the API thread instead of the busy VMM thread
+    """
+
+    vm = uvm_plain_any
+    vm.spawn()
+    vm.basic_config(mem_size_mib=1024)
+    vm.add_net_iface()
+    fs = drive_tools.FilesystemFile(os.path.join(vm.fsfiles, "scratch"), size=2)
+    vm.add_pmem("scratch", fs.path, False, True)
+    vm.start()
+    vm.pause()
+
+    api = vm.api
+    response = api.session.put(
+        f"{api.endpoint}/operations/snapshot/create",
+        json={
+            "snapshot_type": "Full",
+            "snapshot_path": "vmstate",
+            "mem_file_path": "mem",
+        },
+    )
+    assert response.status_code == 200, response.text
+    operation_id = response.json()["operation_id"]
+
+    while True:
+        response = api.session.get(f"{api.endpoint}/metrics")
+        assert response.status_code == 200, response.text
+        assert 'firecracker_pmem_page_cache_bytes{device="scratch"}' in response.text
+
+        response = api.session.get(f"{api.endpoint}/operations/{operation_id}")
+        assert response.status_code == 200, response.text
+        state = response.json()["state"]
+        if state != "Running":
+            break
+
+    assert state == "Succeeded", response.json()
+    # Firecracker is still alive and serving the VMM thread requests.
+    vm.resume()